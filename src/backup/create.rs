@@ -3,72 +3,86 @@
 //! Creates ZIP backup files containing the database.
 
 use std::path::{Path, PathBuf};
-use std::fs::{self, File};
-use std::io::{Read, Write};
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
 use chrono::Utc;
+use rusqlite::{Connection, OpenFlags};
 use zip::write::SimpleFileOptions;
 use zip::ZipWriter;
 use crate::error::{Result, WalletError};
-use crate::database::Database;
+use crate::database::{queries, Database};
 use crate::DATABASE_FILENAME;
-use super::{BACKUP_PREFIX, BACKUP_AUTO, BACKUP_MANUAL, BACKUP_DATE_FORMAT};
+use super::{BackupNaming, BACKUP_AUTO, BACKUP_MANUAL};
+
+/// Highest collision counter tried before giving up
+const MAX_SEQUENCE: u32 = 999;
+
+/// Compressed size of an empty database (schema, properties, system
+/// labels) plus the ZIP headers, measured on a new wallet
+const ESTIMATE_BASE_BYTES: u64 = 2300;
+
+/// Compressed size of a row's plaintext columns (IDs, timestamps, flags)
+/// and its share of the b-tree, measured on wallets of 50 and 500 entries
+const ESTIMATE_ROW_BYTES: u64 = 36;
+
+/// Free space kept on top of the backup itself, for the ZIP headers and
+/// whatever else writes to the disk meanwhile
+const FREE_SPACE_MARGIN: u64 = 1024 * 1024;
+
+/// Check that `backup_folder` can take a backup of `required` bytes. Free
+/// space is only checked where the file system reports it.
+pub fn check_destination(backup_folder: &Path, required: u64) -> Result<()> {
+    let not_writable = |e: std::io::Error| {
+        WalletError::BackupFolderNotWritable(format!("{}: {e}", backup_folder.display()))
+    };
+    fs::create_dir_all(backup_folder).map_err(not_writable)?;
+    tempfile::Builder::new()
+        .prefix(".iwcheck")
+        .tempfile_in(backup_folder)
+        .and_then(|mut probe| probe.write_all(&[0]))
+        .map_err(not_writable)?;
+
+    let needed = required.saturating_add(FREE_SPACE_MARGIN);
+    if let Ok(available) = fs4::available_space(backup_folder)
+        && available < needed {
+        return Err(WalletError::InsufficientSpace { required: needed, available });
+    }
+    Ok(())
+}
+
+/// Estimate the size of a backup of `db` without writing one. Encrypted
+/// blobs do not compress and count in full; the rest of each row and the
+/// schema compress well and count at measured rates. Free pages compress
+/// to almost nothing and are ignored. Typically within a few percent.
+pub fn estimate_backup_size(db: &Database) -> Result<u64> {
+    let conn = db.connection()?;
+    Ok(ESTIMATE_BASE_BYTES
+        + queries::get_encrypted_blob_bytes(conn)?
+        + queries::get_record_row_count(conn)? * ESTIMATE_ROW_BYTES)
+}
 
 /// Create a backup of the database
 ///
 /// Performs a WAL checkpoint before creating the backup to ensure all data
 /// is written to the main database file.
-pub fn create_backup(backup_folder: &Path, db: &Database, manual: bool) -> Result<PathBuf> {
+pub fn create_backup(backup_folder: &Path, naming: &BackupNaming, db: &Database, manual: bool) -> Result<PathBuf> {
     // Checkpoint WAL to ensure all data is in main file
     db.checkpoint()?;
 
-    // Ensure backup folder exists
-    fs::create_dir_all(backup_folder)?;
-
-    // Generate backup filename
-    let now = Utc::now();
-    let type_str = if manual { BACKUP_MANUAL } else { BACKUP_AUTO };
-    let filename = format!(
-        "{}-{}-{}.zip",
-        BACKUP_PREFIX,
-        now.format(BACKUP_DATE_FORMAT),
-        type_str
-    );
-    let backup_path = backup_folder.join(&filename);
-
-    // Read database file
-    let db_path = db.path();
-    let mut db_file = File::open(db_path)
-        .map_err(|e| WalletError::BackupError(format!("Failed to open database: {}", e)))?;
-    let mut db_data = Vec::new();
-    db_file.read_to_end(&mut db_data)
-        .map_err(|e| WalletError::BackupError(format!("Failed to read database: {}", e)))?;
-
-    // Create ZIP file
-    let zip_file = File::create(&backup_path)
-        .map_err(|e| WalletError::BackupError(format!("Failed to create backup file: {}", e)))?;
-    let mut zip = ZipWriter::new(zip_file);
-
-    // Add database to ZIP
-    let options = SimpleFileOptions::default()
-        .compression_method(zip::CompressionMethod::Deflated)
-        .unix_permissions(0o644);
-
-    zip.start_file(DATABASE_FILENAME, options)
-        .map_err(|e| WalletError::BackupError(format!("Failed to add file to zip: {}", e)))?;
-    zip.write_all(&db_data)
-        .map_err(|e| WalletError::BackupError(format!("Failed to write to zip: {}", e)))?;
-
-    zip.finish()
-        .map_err(|e| WalletError::BackupError(format!("Failed to finalize zip: {}", e)))?;
-
-    Ok(backup_path)
+    let database_id = if naming.include_database_id {
+        queries::get_database_id(db.connection()?)?
+    } else {
+        None
+    };
+    write_backup(backup_folder, naming, database_id.as_deref(), db.path(), manual)
 }
 
 /// Create a backup from a raw database file path (DB must be closed)
 ///
 /// Unlike `create_backup`, this does not perform a WAL checkpoint since the
 /// database is expected to be closed. Takes a file path instead of a Database reference.
-pub fn create_backup_from_path(backup_folder: &Path, db_path: &Path, manual: bool) -> Result<PathBuf> {
+/// The database ID is included in the name only if the file can be read.
+pub fn create_backup_from_path(backup_folder: &Path, naming: &BackupNaming, db_path: &Path, manual: bool) -> Result<PathBuf> {
     // Verify source file exists
     if !db_path.exists() {
         return Err(WalletError::BackupError(
@@ -76,30 +90,62 @@ pub fn create_backup_from_path(backup_folder: &Path, db_path: &Path, manual: boo
         ));
     }
 
-    // Ensure backup folder exists
-    fs::create_dir_all(backup_folder)?;
-
-    // Generate backup filename
-    let now = Utc::now();
-    let type_str = if manual { BACKUP_MANUAL } else { BACKUP_AUTO };
-    let filename = format!(
-        "{}-{}-{}.zip",
-        BACKUP_PREFIX,
-        now.format(BACKUP_DATE_FORMAT),
-        type_str
-    );
-    let backup_path = backup_folder.join(&filename);
+    let database_id = if naming.include_database_id {
+        Connection::open_with_flags(db_path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .ok()
+            .and_then(|conn| queries::get_database_id(&conn).ok().flatten())
+    } else {
+        None
+    };
+    write_backup(backup_folder, naming, database_id.as_deref(), db_path, manual)
+}
 
+/// Zip the database file into a new, never-overwritten backup file
+fn write_backup(
+    backup_folder: &Path,
+    naming: &BackupNaming,
+    database_id: Option<&str>,
+    db_path: &Path,
+    manual: bool,
+) -> Result<PathBuf> {
     // Read database file
     let mut db_file = File::open(db_path)
-        .map_err(|e| WalletError::BackupError(format!("Failed to open database: {}", e)))?;
+        .map_err(|e| WalletError::backup("Failed to open database", e))?;
     let mut db_data = Vec::new();
     db_file.read_to_end(&mut db_data)
-        .map_err(|e| WalletError::BackupError(format!("Failed to read database: {}", e)))?;
+        .map_err(|e| WalletError::backup("Failed to read database", e))?;
+
+    // The archive is never much larger than the database it compresses
+    check_destination(backup_folder, db_data.len() as u64)?;
 
-    // Create ZIP file
-    let zip_file = File::create(&backup_path)
-        .map_err(|e| WalletError::BackupError(format!("Failed to create backup file: {}", e)))?;
+    // Create ZIP file. Names have second resolution, so a backup taken in
+    // the same second as an existing one gets a counter suffix instead of
+    // replacing it; create_new makes the check atomic.
+    let now = Utc::now();
+    let type_str = if manual { BACKUP_MANUAL } else { BACKUP_AUTO };
+    let mut sequence = 0;
+    let (backup_path, zip_file) = loop {
+        let backup_path = backup_folder.join(naming.filename(database_id, &now, sequence, type_str));
+        match OpenOptions::new().write(true).create_new(true).open(&backup_path) {
+            Ok(file) => break (backup_path, file),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists && sequence < MAX_SEQUENCE => sequence += 1,
+            Err(e) => {
+                return Err(WalletError::backup("Failed to create backup file", e));
+            }
+        }
+    };
+    // A backup that fails part way (e.g. the disk filled up after all)
+    // is removed rather than left behind as a broken archive
+    if let Err(e) = write_zip(zip_file, &db_data) {
+        let _ = fs::remove_file(&backup_path);
+        return Err(e);
+    }
+
+    Ok(backup_path)
+}
+
+/// Write `db_data` as the single entry of a ZIP archive
+fn write_zip(zip_file: File, db_data: &[u8]) -> Result<()> {
     let mut zip = ZipWriter::new(zip_file);
 
     // Add database to ZIP
@@ -108,14 +154,13 @@ pub fn create_backup_from_path(backup_folder: &Path, db_path: &Path, manual: boo
         .unix_permissions(0o644);
 
     zip.start_file(DATABASE_FILENAME, options)
-        .map_err(|e| WalletError::BackupError(format!("Failed to add file to zip: {}", e)))?;
-    zip.write_all(&db_data)
-        .map_err(|e| WalletError::BackupError(format!("Failed to write to zip: {}", e)))?;
+        .map_err(|e| WalletError::backup("Failed to add file to zip", e))?;
+    zip.write_all(db_data)
+        .map_err(|e| WalletError::backup("Failed to write to zip", e))?;
 
     zip.finish()
-        .map_err(|e| WalletError::BackupError(format!("Failed to finalize zip: {}", e)))?;
-
-    Ok(backup_path)
+        .map_err(|e| WalletError::backup("Failed to finalize zip", e))?;
+    Ok(())
 }
 
 #[cfg(test)]
@@ -133,12 +178,29 @@ mod tests {
         let db = Database::create(&db_path).unwrap();
 
         // Create backup
-        let backup_path = create_backup(&backup_dir, &db, true).unwrap();
+        let backup_path = create_backup(&backup_dir, &BackupNaming::default(), &db, true).unwrap();
 
         assert!(backup_path.exists());
         assert!(backup_path.file_name().unwrap().to_str().unwrap().contains("manual"));
     }
 
+    #[test]
+    fn test_estimate_backup_size() {
+        let (mut wallet, temp_dir) = crate::business::wallet::tests::create_test_wallet();
+        for i in 0..100 {
+            let id = wallet.add_item(&format!("Entry {i}"), "document", false, None).unwrap();
+            wallet.add_field(&id, "PASS", &format!("password-{i}"), None).unwrap();
+            wallet.add_field(&id, "NOTE", &"note ".repeat(i % 30 + 1), None).unwrap();
+        }
+        let db = wallet.database().unwrap();
+
+        let estimate = estimate_backup_size(db).unwrap();
+        let backup_path = create_backup(&temp_dir.path().join("backups"), &BackupNaming::default(), db, true).unwrap();
+        let actual = fs::metadata(backup_path).unwrap().len();
+        let error = estimate.abs_diff(actual) as f64 / actual as f64;
+        assert!(error < 0.15, "estimate {estimate}, actual {actual}");
+    }
+
     #[test]
     fn test_create_auto_backup() {
         let temp_dir = TempDir::new().unwrap();
@@ -149,7 +211,7 @@ mod tests {
         let db = Database::create(&db_path).unwrap();
 
         // Create backup
-        let backup_path = create_backup(&backup_dir, &db, false).unwrap();
+        let backup_path = create_backup(&backup_dir, &BackupNaming::default(), &db, false).unwrap();
 
         assert!(backup_path.exists());
         assert!(backup_path.file_name().unwrap().to_str().unwrap().contains("auto"));
@@ -164,7 +226,7 @@ mod tests {
         // Create a raw .dat file
         std::fs::write(&db_path, b"fake database content").unwrap();
 
-        let backup_path = create_backup_from_path(&backup_dir, &db_path, true).unwrap();
+        let backup_path = create_backup_from_path(&backup_dir, &BackupNaming::default(), &db_path, true).unwrap();
 
         assert!(backup_path.exists());
         assert!(backup_path.file_name().unwrap().to_str().unwrap().contains("manual"));
@@ -185,7 +247,7 @@ mod tests {
 
         std::fs::write(&db_path, b"fake database content").unwrap();
 
-        let backup_path = create_backup_from_path(&backup_dir, &db_path, false).unwrap();
+        let backup_path = create_backup_from_path(&backup_dir, &BackupNaming::default(), &db_path, false).unwrap();
 
         assert!(backup_path.exists());
         assert!(backup_path.file_name().unwrap().to_str().unwrap().contains("auto"));
@@ -197,7 +259,29 @@ mod tests {
         let backup_dir = temp_dir.path().join("backups");
         let db_path = temp_dir.path().join("nonexistent.dat");
 
-        let result = create_backup_from_path(&backup_dir, &db_path, false);
+        let result = create_backup_from_path(&backup_dir, &BackupNaming::default(), &db_path, false);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_check_destination() {
+        let temp_dir = TempDir::new().unwrap();
+        let backup_dir = temp_dir.path().join("backups");
+        check_destination(&backup_dir, 1024).unwrap();
+        assert!(backup_dir.is_dir());
+        assert_eq!(std::fs::read_dir(&backup_dir).unwrap().count(), 0, "probe file is removed");
+
+        assert!(matches!(
+            check_destination(&backup_dir, u64::MAX / 2),
+            Err(WalletError::InsufficientSpace { available, .. }) if available < u64::MAX / 2
+        ));
+
+        // A file where the folder should be
+        let blocked = temp_dir.path().join("file");
+        std::fs::write(&blocked, b"x").unwrap();
+        assert!(matches!(
+            check_destination(&blocked.join("backups"), 0),
+            Err(WalletError::BackupFolderNotWritable(_))
+        ));
+    }
 }
@@ -15,12 +15,16 @@ pub use restore::{
     get_db_version,
     check_db_version,
 };
+pub(crate) use restore::{extract_backup, temp_dir_in};
 
 use std::path::{Path, PathBuf};
 use std::fs;
 use chrono::{DateTime, Utc, TimeZone, NaiveDateTime};
 use crate::database::Database;
-use crate::error::Result;
+use crate::database::queries::DatabaseStats;
+use crate::error::{Result, WalletError};
+use crate::localization::Translations;
+use crate::utils::{format_size, sanitize_filename};
 
 /// Backup file prefix
 pub const BACKUP_PREFIX: &str = "iwb";
@@ -40,6 +44,57 @@ pub const BACKUP_IMPORTED: &str = "imported";
 /// Backup date format
 pub const BACKUP_DATE_FORMAT: &str = "%Y%m%d-%H%M%S";
 
+/// How backup files are named.
+///
+/// Names look like `{prefix}[-{database_id}]-YYYYMMDD-HHMMSS[_N]-{type}.zip`.
+/// The `_N` counter only appears when another backup was already taken in
+/// the same second, so the default naming matches the classic format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupNaming {
+    /// File name prefix. Letters, digits, `_` and `.` only.
+    pub prefix: String,
+    /// Put the wallet's database ID in the name, so backups of several
+    /// wallets can share a folder
+    pub include_database_id: bool,
+}
+
+impl Default for BackupNaming {
+    fn default() -> Self {
+        Self {
+            prefix: BACKUP_PREFIX.to_string(),
+            include_database_id: false,
+        }
+    }
+}
+
+impl BackupNaming {
+    /// Check the prefix can be told apart from the rest of the name
+    pub fn validate(&self) -> Result<()> {
+        let valid = !self.prefix.is_empty()
+            && self.prefix.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+        if valid {
+            Ok(())
+        } else {
+            Err(WalletError::BackupError(format!("Invalid backup prefix: {:?}", self.prefix)))
+        }
+    }
+
+    /// File name for a backup taken at `time`
+    fn filename(&self, database_id: Option<&str>, time: &DateTime<Utc>, sequence: u32, type_str: &str) -> String {
+        let mut name = self.prefix.clone();
+        if let Some(id) = database_id {
+            name.push('-');
+            name.push_str(&sanitize_filename(id));
+        }
+        name.push('-');
+        name.push_str(&time.format(BACKUP_DATE_FORMAT).to_string());
+        if sequence > 0 {
+            name.push_str(&format!("_{sequence}"));
+        }
+        format!("{name}-{type_str}.zip")
+    }
+}
+
 /// Backup type
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BackupType {
@@ -51,10 +106,56 @@ pub enum BackupType {
     Imported,
 }
 
+/// When a wallet with an attached [`BackupManager`] takes auto backups, and
+/// how many it keeps. See `Wallet::set_backup_manager`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AutoBackupConfig {
+    /// Back up after this many writes (0 disables the count trigger)
+    pub after_changes: u32,
+    /// Back up once this many minutes have passed since the last backup and
+    /// at least one write happened (0 disables the time trigger)
+    pub after_minutes: u32,
+    /// Auto backups always kept by the retention policy
+    pub min_keep: usize,
+    /// Auto backups older than this are pruned (beyond `min_keep`)
+    pub max_age_days: u32,
+    /// On unlock, back up when the newest auto backup is older than this
+    /// many hours (0 disables the unlock trigger)
+    pub unlock_interval_hours: u32,
+}
+
+impl Default for AutoBackupConfig {
+    fn default() -> Self {
+        Self {
+            after_changes: 20,
+            after_minutes: 60,
+            min_keep: 5,
+            max_age_days: 30,
+            unlock_interval_hours: 24,
+        }
+    }
+}
+
+/// Largest database [`BackupManager::inspect`] reads into memory, in bytes
+pub const INSPECT_MAX_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Summary of a backup read by [`BackupManager::inspect`]
+#[derive(Debug, Clone)]
+pub struct BackupInspection {
+    /// Database version of the backup
+    pub version: String,
+    /// Database ID of the wallet the backup was taken from
+    pub database_id: Option<String>,
+    /// Record counts; `file_size_bytes` is the database size in the backup
+    pub stats: DatabaseStats,
+}
+
 /// Backup manager
 pub struct BackupManager {
     /// Backup folder path
     folder: PathBuf,
+    /// File naming for new backups
+    naming: BackupNaming,
 }
 
 impl BackupManager {
@@ -62,6 +163,7 @@ impl BackupManager {
     pub fn new(folder: &Path) -> Self {
         Self {
             folder: folder.to_path_buf(),
+            naming: BackupNaming::default(),
         }
     }
 
@@ -70,11 +172,40 @@ impl BackupManager {
         &self.folder
     }
 
+    /// File naming used for new backups
+    pub fn naming(&self) -> &BackupNaming {
+        &self.naming
+    }
+
+    /// Change the file naming. `list_backups` keeps finding backups made
+    /// with the default and legacy prefixes as well.
+    pub fn set_naming(&mut self, naming: BackupNaming) -> Result<()> {
+        naming.validate()?;
+        self.naming = naming;
+        Ok(())
+    }
+
     /// Create a backup
     ///
     /// Requires a database reference to perform WAL checkpoint before backup.
     pub fn create_backup(&self, db: &Database, manual: bool) -> Result<PathBuf> {
-        create::create_backup(&self.folder, db, manual)
+        create::create_backup(&self.folder, &self.naming, db, manual)
+    }
+
+    /// Expected size in bytes of the archive `create_backup` would write, so
+    /// apps can warn before backing up over a metered connection or to
+    /// nearly full storage
+    pub fn estimate_backup_size(&self, db: &Database) -> Result<u64> {
+        create::estimate_backup_size(db)
+    }
+
+    /// Check that a backup of `required_bytes` can be written to the backup
+    /// folder: the folder can be created and written to, and its file
+    /// system has that much free space plus a small margin. Fails with
+    /// `BackupFolderNotWritable` or `InsufficientSpace`. Every backup runs
+    /// this check first, sized by the database file.
+    pub fn check_destination(&self, required_bytes: u64) -> Result<()> {
+        create::check_destination(&self.folder, required_bytes)
     }
 
     /// Create a backup from a raw database file path (DB must be closed)
@@ -82,7 +213,7 @@ impl BackupManager {
     /// Unlike `create_backup`, this does not perform a WAL checkpoint since the
     /// database is expected to be closed.
     pub fn create_backup_from_path(&self, db_path: &Path, manual: bool) -> Result<PathBuf> {
-        create::create_backup_from_path(&self.folder, db_path, manual)
+        create::create_backup_from_path(&self.folder, &self.naming, db_path, manual)
     }
 
     /// Restore from a backup
@@ -90,6 +221,20 @@ impl BackupManager {
         restore::restore_backup(backup_path, db_path)
     }
 
+    /// Restore a backup and upgrade it to the current schema, putting the
+    /// current database back if the restore, upgrade or integrity check
+    /// fails. Returns the backup's original version.
+    pub fn restore_and_upgrade(&self, backup_path: &Path, db_path: &Path) -> Result<String> {
+        restore::restore_and_upgrade(backup_path, db_path)
+    }
+
+    /// Read a backup's version, database ID and record counts in memory,
+    /// without extracting it to disk. Refuses databases larger than
+    /// [`INSPECT_MAX_SIZE`].
+    pub fn inspect(&self, backup_path: &Path) -> Result<BackupInspection> {
+        restore::inspect_backup(backup_path, INSPECT_MAX_SIZE)
+    }
+
     /// Extract a backup to a folder (for inspection)
     pub fn extract_backup(&self, backup_path: &Path, target_folder: &Path) -> Result<PathBuf> {
         restore::extract_backup(backup_path, target_folder)
@@ -109,15 +254,13 @@ impl BackupManager {
 
             if path.is_file()
                 && let Some(filename) = path.file_name().and_then(|n| n.to_str())
-                    && (filename.starts_with(BACKUP_PREFIX) || filename.starts_with(BACKUP_PREFIX_LEGACY))
-                        && filename.ends_with(".zip")
-                        && let Some(info) = parse_backup_filename(filename, &path) {
-                            backups.push(info);
-                        }
+                    && let Some(info) = parse_backup_filename(filename, &path, &self.naming.prefix) {
+                        backups.push(info);
+                    }
         }
 
         // Sort by timestamp, newest first
-        backups.sort_by_key(|b| std::cmp::Reverse(b.timestamp));
+        backups.sort_by_key(|b| std::cmp::Reverse((b.timestamp, b.sequence)));
 
         Ok(backups)
     }
@@ -191,36 +334,66 @@ pub struct BackupInfo {
     pub backup_type: BackupType,
     /// File size in bytes
     pub size: u64,
+    /// Database ID, if the name includes one
+    pub database_id: Option<String>,
+    /// Same-second counter from the name (0 when absent)
+    pub sequence: u32,
 }
 
-/// Parse backup filename to extract information
-fn parse_backup_filename(filename: &str, path: &Path) -> Option<BackupInfo> {
-    // Format: {iwb|nswb}-YYYYMMDD-HHMMSS-{auto|manual|imported}.zip
-    let parts: Vec<&str> = filename.split('-').collect();
-
-    if parts.len() != 4 {
-        return None;
+impl BackupInfo {
+    /// One-line description for backup lists in the language of
+    /// `translations`, e.g. "Manual backup — 2.4 MB — 3 d ago"
+    pub fn display_name(&self, translations: &Translations) -> String {
+        self.display_name_at(translations, Utc::now())
+    }
+
+    fn display_name_at(&self, translations: &Translations, now: DateTime<Utc>) -> String {
+        let type_key = match self.backup_type {
+            BackupType::Auto => "backup_type_auto",
+            BackupType::Manual => "backup_type_manual",
+            BackupType::Imported => "backup_type_imported",
+        };
+        let age = now - self.timestamp;
+        let ago = if age.num_minutes() < 1 {
+            translations.get("time_just_now").to_string()
+        } else if age.num_hours() < 1 {
+            translations.format("time_minutes_ago", &[("count", &age.num_minutes().to_string())])
+        } else if age.num_days() < 1 {
+            translations.format("time_hours_ago", &[("count", &age.num_hours().to_string())])
+        } else {
+            translations.format("time_days_ago", &[("count", &age.num_days().to_string())])
+        };
+        format!(
+            "{} — {} — {}",
+            translations.get(type_key),
+            format_size(self.size, translations.get_language()),
+            ago,
+        )
     }
+}
 
-    if parts[0] != BACKUP_PREFIX && parts[0] != BACKUP_PREFIX_LEGACY {
+/// Parse backup filename to extract information. Besides the default and
+/// legacy prefixes, the custom `prefix` is accepted.
+fn parse_backup_filename(filename: &str, path: &Path, prefix: &str) -> Option<BackupInfo> {
+    // Format: {prefix}[-{database_id}]-YYYYMMDD-HHMMSS[_N]-{type}.zip
+    let stem = filename.strip_suffix(".zip")?;
+    let parts: Vec<&str> = stem.split('-').collect();
+    if parts.len() < 4 {
         return None;
     }
+    let (head, tail) = parts.split_at(parts.len() - 3);
 
-    // Parse date and time
-    let date_str = parts[1];
-    let time_str = parts[2];
-
-    if date_str.len() != 8 || time_str.len() != 6 {
-        return None;
-    }
+    let known_prefix = |p: &str| p == BACKUP_PREFIX || p == BACKUP_PREFIX_LEGACY || p == prefix;
+    let database_id = match head {
+        [p] if known_prefix(p) => None,
+        [p, id] if known_prefix(p) && !id.is_empty() => Some(id.to_string()),
+        _ => return None,
+    };
 
-    let datetime_str = format!("{}-{}", date_str, time_str);
-    let ndt = NaiveDateTime::parse_from_str(&datetime_str, BACKUP_DATE_FORMAT).ok()?;
-    let timestamp = Utc.from_utc_datetime(&ndt);
+    let (timestamp, sequence) = parse_date_time(tail[0], tail[1])?;
 
     // Parse type
-    let type_str = parts[3].trim_end_matches(".zip");
-    let backup_type = match type_str {
+    let backup_type = match tail[2] {
         BACKUP_MANUAL => BackupType::Manual,
         BACKUP_IMPORTED => BackupType::Imported,
         _ => BackupType::Auto,
@@ -234,9 +407,26 @@ fn parse_backup_filename(filename: &str, path: &Path) -> Option<BackupInfo> {
         timestamp,
         backup_type,
         size,
+        database_id,
+        sequence,
     })
 }
 
+/// Parse the `YYYYMMDD` and `HHMMSS[_N]` name parts
+fn parse_date_time(date_str: &str, time_str: &str) -> Option<(DateTime<Utc>, u32)> {
+    let (time_str, sequence) = match time_str.split_once('_') {
+        Some((time, seq)) => (time, seq.parse().ok()?),
+        None => (time_str, 0),
+    };
+    if date_str.len() != 8 || time_str.len() != 6 {
+        return None;
+    }
+
+    let datetime_str = format!("{}-{}", date_str, time_str);
+    let ndt = NaiveDateTime::parse_from_str(&datetime_str, BACKUP_DATE_FORMAT).ok()?;
+    Some((Utc.from_utc_datetime(&ndt), sequence))
+}
+
 /// Parse date from backup filename (for compatibility)
 pub fn get_date_from_backup_filename(filename: &str) -> Option<DateTime<Utc>> {
     let name = Path::new(filename)
@@ -245,20 +435,13 @@ pub fn get_date_from_backup_filename(filename: &str) -> Option<DateTime<Utc>> {
 
     let parts: Vec<&str> = name.split('-').collect();
 
-    if parts.len() < 3 {
-        return None;
+    // Date and time come right before the type; names without a type
+    // part ("iwb-YYYYMMDD-HHMMSS") end with them
+    match parts.len() {
+        0..=2 => None,
+        3 => parse_date_time(parts[1], parts[2]).map(|(t, _)| t),
+        n => parse_date_time(parts[n - 3], parts[n - 2]).map(|(t, _)| t),
     }
-
-    let date_str = parts[1];
-    let time_str = parts[2];
-
-    if date_str.len() != 8 || time_str.len() != 6 {
-        return None;
-    }
-
-    let datetime_str = format!("{}-{}", date_str, time_str);
-    let ndt = NaiveDateTime::parse_from_str(&datetime_str, BACKUP_DATE_FORMAT).ok()?;
-    Some(Utc.from_utc_datetime(&ndt))
 }
 
 #[cfg(test)]
@@ -277,7 +460,7 @@ mod tests {
         let mut file = std::fs::File::create(&path).unwrap();
         file.write_all(b"test").unwrap();
 
-        let info = parse_backup_filename("iwb-20171203-113108-auto.zip", &path).unwrap();
+        let info = parse_backup_filename("iwb-20171203-113108-auto.zip", &path, BACKUP_PREFIX).unwrap();
 
         assert_eq!(info.backup_type, BackupType::Auto);
         assert_eq!(info.timestamp.year(), 2017);
@@ -293,7 +476,7 @@ mod tests {
         let mut file = std::fs::File::create(&path).unwrap();
         file.write_all(b"test").unwrap();
 
-        let info = parse_backup_filename("nswb-20171203-113108-auto.zip", &path).unwrap();
+        let info = parse_backup_filename("nswb-20171203-113108-auto.zip", &path, BACKUP_PREFIX).unwrap();
 
         assert_eq!(info.backup_type, BackupType::Auto);
         assert_eq!(info.timestamp.year(), 2017);
@@ -309,7 +492,7 @@ mod tests {
         let mut file = std::fs::File::create(&path).unwrap();
         file.write_all(b"test").unwrap();
 
-        let info = parse_backup_filename("iwb-20231215-143022-imported.zip", &path).unwrap();
+        let info = parse_backup_filename("iwb-20231215-143022-imported.zip", &path, BACKUP_PREFIX).unwrap();
 
         assert_eq!(info.backup_type, BackupType::Imported);
         assert_eq!(info.timestamp.year(), 2023);
@@ -485,7 +668,7 @@ mod tests {
         let path = temp_dir.path().join("iwb-20231215-143022-manual.zip");
         std::fs::File::create(&path).unwrap().write_all(b"test").unwrap();
 
-        let info = parse_backup_filename("iwb-20231215-143022-manual.zip", &path).unwrap();
+        let info = parse_backup_filename("iwb-20231215-143022-manual.zip", &path, BACKUP_PREFIX).unwrap();
         assert_eq!(info.backup_type, BackupType::Manual);
         assert_eq!(info.timestamp.year(), 2023);
         assert_eq!(info.timestamp.month(), 12);
@@ -620,4 +803,80 @@ mod tests {
         let deleted = mgr.cleanup_auto_backups(3, 30).unwrap();
         assert_eq!(deleted, 0);
     }
+
+    #[test]
+    fn test_parse_backup_filename_with_database_id_and_sequence() {
+        let temp_dir = TempDir::new().unwrap();
+        let name = "vault-0a1b2c-20231215-143022_2-manual.zip";
+        let path = temp_dir.path().join(name);
+        std::fs::write(&path, b"test").unwrap();
+
+        let info = parse_backup_filename(name, &path, "vault").unwrap();
+        assert_eq!(info.database_id.as_deref(), Some("0a1b2c"));
+        assert_eq!(info.sequence, 2);
+        assert_eq!(info.backup_type, BackupType::Manual);
+        assert_eq!(info.timestamp.second(), 22);
+        // Unknown prefixes are not backups of ours
+        assert!(parse_backup_filename(name, &path, BACKUP_PREFIX).is_none());
+        assert!(parse_backup_filename("iwb-a-b-20231215-143022-auto.zip", &path, BACKUP_PREFIX).is_none());
+        assert_eq!(get_date_from_backup_filename(name).unwrap().minute(), 30);
+    }
+
+    #[test]
+    fn test_backup_naming() {
+        let time = Utc.with_ymd_and_hms(2023, 12, 15, 14, 30, 22).unwrap();
+        let naming = BackupNaming::default();
+        assert_eq!(naming.filename(None, &time, 0, BACKUP_AUTO), "iwb-20231215-143022-auto.zip");
+        let naming = BackupNaming { prefix: "home".to_string(), include_database_id: true };
+        assert_eq!(naming.filename(Some("abc"), &time, 3, BACKUP_MANUAL), "home-abc-20231215-143022_3-manual.zip");
+
+        let temp_dir = TempDir::new().unwrap();
+        let mut mgr = BackupManager::new(temp_dir.path());
+        assert!(mgr.set_naming(BackupNaming { prefix: String::new(), include_database_id: false }).is_err());
+        assert!(mgr.set_naming(BackupNaming { prefix: "a-b".to_string(), include_database_id: false }).is_err());
+        mgr.set_naming(naming).unwrap();
+        assert_eq!(mgr.naming().prefix, "home");
+    }
+
+    #[test]
+    fn test_same_second_backups_are_kept_and_ordered() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("nswallet.dat");
+        std::fs::write(&db_path, b"fake database content").unwrap();
+        let mut mgr = BackupManager::new(&temp_dir.path().join("backups"));
+        mgr.set_naming(BackupNaming { prefix: "home".to_string(), include_database_id: false }).unwrap();
+
+        let paths: Vec<PathBuf> = (0..3)
+            .map(|_| mgr.create_backup_from_path(&db_path, true).unwrap())
+            .collect();
+        let unique: std::collections::HashSet<&PathBuf> = paths.iter().collect();
+        assert_eq!(unique.len(), 3);
+
+        // Legacy-named files in the same folder are still listed
+        std::fs::write(mgr.folder().join("iwb-20200101-100000-auto.zip"), b"old").unwrap();
+        let backups = mgr.list_backups().unwrap();
+        assert_eq!(backups.len(), 4);
+        assert_eq!(backups[0].path, paths[2]);
+        assert_eq!(backups[3].timestamp.year(), 2020);
+    }
+
+    #[test]
+    fn test_backup_display_name() {
+        let now = Utc.with_ymd_and_hms(2024, 5, 10, 12, 0, 0).unwrap();
+        let info = BackupInfo {
+            path: PathBuf::from("iwb-20240507-120000-manual.zip"),
+            timestamp: now - chrono::Duration::days(3),
+            backup_type: BackupType::Manual,
+            size: 2_516_582,
+            database_id: None,
+            sequence: 0,
+        };
+        let mut tr = Translations::new().unwrap();
+        assert_eq!(info.display_name_at(&tr, now), "Manual backup — 2.4 MB — 3 d ago");
+
+        let recent = BackupInfo { timestamp: now - chrono::Duration::minutes(5), backup_type: BackupType::Auto, ..info };
+        assert_eq!(recent.display_name_at(&tr, now), "Auto backup — 2.4 MB — 5 min ago");
+        tr.set_language("de").unwrap();
+        assert_eq!(recent.display_name_at(&tr, now), "Automatische Sicherung — 2,4 MB — vor 5 Min.");
+    }
 }
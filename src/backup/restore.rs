@@ -8,23 +8,24 @@ use std::io::{Read, Write};
 use zip::ZipArchive;
 use crate::error::{Result, WalletError};
 use crate::DATABASE_FILENAME;
+use super::BackupInspection;
 
 /// Restore a backup to the database path
 pub fn restore_backup(backup_path: &Path, db_path: &Path) -> Result<()> {
     // Open ZIP file
     let file = File::open(backup_path)
-        .map_err(|e| WalletError::BackupError(format!("Failed to open backup: {}", e)))?;
+        .map_err(|e| WalletError::backup("Failed to open backup", e))?;
     let mut archive = ZipArchive::new(file)
-        .map_err(|e| WalletError::BackupError(format!("Failed to read backup: {}", e)))?;
+        .map_err(|e| WalletError::backup("Failed to read backup", e))?;
 
     // Find the database file in the archive
     let mut db_file = archive.by_name(DATABASE_FILENAME)
-        .map_err(|e| WalletError::BackupError(format!("Database not found in backup: {}", e)))?;
+        .map_err(|e| WalletError::backup("Database not found in backup", e))?;
 
     // Read the database content
     let mut db_data = Vec::new();
     db_file.read_to_end(&mut db_data)
-        .map_err(|e| WalletError::BackupError(format!("Failed to read database from backup: {}", e)))?;
+        .map_err(|e| WalletError::backup("Failed to read database from backup", e))?;
 
     // Ensure parent directory exists
     if let Some(parent) = db_path.parent() {
@@ -33,13 +34,138 @@ pub fn restore_backup(backup_path: &Path, db_path: &Path) -> Result<()> {
 
     // Write to target path
     let mut output = File::create(db_path)
-        .map_err(|e| WalletError::BackupError(format!("Failed to create database file: {}", e)))?;
+        .map_err(|e| WalletError::backup("Failed to create database file", e))?;
     output.write_all(&db_data)
-        .map_err(|e| WalletError::BackupError(format!("Failed to write database: {}", e)))?;
+        .map_err(|e| WalletError::backup("Failed to write database", e))?;
 
     Ok(())
 }
 
+/// Read a backup's database into memory and summarize it, without writing
+/// anything to disk. Databases larger than `max_size` bytes are refused.
+pub fn inspect_backup(backup_path: &Path, max_size: u64) -> Result<BackupInspection> {
+    use rusqlite::Connection;
+    use crate::database::{migrations, queries};
+
+    let file = File::open(backup_path)
+        .map_err(|e| WalletError::backup("Failed to open backup", e))?;
+    let mut archive = ZipArchive::new(file)
+        .map_err(|e| WalletError::backup("Failed to read backup", e))?;
+    let mut entry = archive.by_name(DATABASE_FILENAME)
+        .map_err(|e| WalletError::backup("Database not found in backup", e))?;
+    let db_size = entry.size();
+    if db_size > max_size {
+        return Err(WalletError::BackupError(format!(
+            "Database in backup is too large to inspect: {db_size} bytes (limit {max_size})"
+        )));
+    }
+
+    // SQLite cannot open a WAL-mode file from memory; mark the in-memory
+    // copy as a rollback-journal database (header bytes 18 and 19)
+    let mut header = [0u8; 100];
+    let header_len = (db_size as usize).min(header.len());
+    entry.read_exact(&mut header[..header_len])
+        .map_err(|e| WalletError::backup("Failed to read database from backup", e))?;
+    if header_len == header.len() && header[18] == 2 && header[19] == 2 {
+        header[18] = 1;
+        header[19] = 1;
+    }
+
+    let mut conn = Connection::open_in_memory()?;
+    conn.deserialize_read_exact("main", (&header[..header_len]).chain(entry), db_size as usize, true)?;
+
+    let version = migrations::get_database_version(&conn)?;
+    let database_id = queries::get_database_id(&conn)?;
+    let mut stats = queries::get_database_stats(&conn)?;
+    stats.file_size_bytes = db_size;
+    Ok(BackupInspection { version, database_id, stats })
+}
+
+/// Suffix of the copies of the current database kept while a restore runs
+const PRE_RESTORE_SUFFIX: &str = ".pre-restore";
+
+/// `path` with `suffix` appended to its file name
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Move set-aside files back to their original paths
+fn put_back(saved: &[(PathBuf, PathBuf)]) {
+    for (file, snapshot) in saved {
+        let _ = fs::rename(snapshot, file);
+    }
+}
+
+/// Restore a backup over `db_path` and bring it up to the current schema.
+///
+/// The current database and its WAL files are set aside first. The backup
+/// is then restored, upgraded with [`migrations::upgrade_database`] and
+/// checked with SQLite's integrity check. If any step fails, or the backup
+/// comes from a newer app version, the restored file is removed and the
+/// set-aside database put back. Returns the backup's original version.
+///
+/// [`migrations::upgrade_database`]: crate::database::migrations::upgrade_database
+pub fn restore_and_upgrade(backup_path: &Path, db_path: &Path) -> Result<String> {
+    if !verify_backup(backup_path)? {
+        return Err(WalletError::BackupError("Database not found in backup".to_string()));
+    }
+
+    let files = [
+        db_path.to_path_buf(),
+        with_suffix(db_path, "-wal"),
+        with_suffix(db_path, "-shm"),
+    ];
+    let mut saved = Vec::new();
+    for file in &files {
+        if !file.exists() {
+            continue;
+        }
+        let snapshot = with_suffix(file, PRE_RESTORE_SUFFIX);
+        if let Err(e) = fs::rename(file, &snapshot) {
+            put_back(&saved);
+            return Err(WalletError::backup("Failed to set the current database aside", e));
+        }
+        saved.push((file.clone(), snapshot));
+    }
+
+    match restore_backup(backup_path, db_path).and_then(|()| upgrade_restored(db_path)) {
+        Ok(version) => {
+            for (_, snapshot) in &saved {
+                let _ = fs::remove_file(snapshot);
+            }
+            Ok(version)
+        }
+        Err(e) => {
+            for file in &files {
+                let _ = fs::remove_file(file);
+            }
+            put_back(&saved);
+            Err(e)
+        }
+    }
+}
+
+/// Upgrade a freshly restored database in place and check it.
+/// Returns its version before the upgrade.
+fn upgrade_restored(db_path: &Path) -> Result<String> {
+    use rusqlite::Connection;
+    use crate::database::migrations;
+
+    let conn = Connection::open(db_path)?;
+    let integrity: String = conn.query_row("PRAGMA integrity_check", [], |row| row.get(0))?;
+    if integrity != "ok" {
+        return Err(WalletError::BackupError(format!("Restored database is damaged: {integrity}")));
+    }
+    let version = migrations::get_database_version(&conn)?;
+    if !migrations::is_version_compatible(&version) {
+        return Err(WalletError::InvalidVersion(version));
+    }
+    migrations::upgrade_database(&conn, &version)?;
+    Ok(version)
+}
+
 /// Extract a backup to a folder, returning the path to the extracted database
 pub fn extract_backup(backup_path: &Path, target_folder: &Path) -> Result<PathBuf> {
     // Ensure target folder exists
@@ -55,9 +181,9 @@ pub fn extract_backup(backup_path: &Path, target_folder: &Path) -> Result<PathBu
 pub fn verify_backup(backup_path: &Path) -> Result<bool> {
     // Open ZIP file
     let file = File::open(backup_path)
-        .map_err(|e| WalletError::BackupError(format!("Failed to open backup: {}", e)))?;
+        .map_err(|e| WalletError::backup("Failed to open backup", e))?;
     let mut archive = ZipArchive::new(file)
-        .map_err(|e| WalletError::BackupError(format!("Failed to read backup: {}", e)))?;
+        .map_err(|e| WalletError::backup("Failed to read backup", e))?;
 
     // Check if database file exists
     match archive.by_name(DATABASE_FILENAME) {
@@ -74,11 +200,11 @@ pub fn verify_backup(backup_path: &Path) -> Result<bool> {
 /// On some platforms (notably Android/Pixel) the OS default temp location is
 /// not writable by the app sandbox, causing "permission denied". Callers can
 /// pass an app-controlled, writable base folder to avoid that.
-fn temp_dir_in(base: &Path) -> Result<tempfile::TempDir> {
+pub(crate) fn temp_dir_in(base: &Path) -> Result<tempfile::TempDir> {
     fs::create_dir_all(base)
-        .map_err(|e| WalletError::BackupError(format!("Failed to create temp base folder: {}", e)))?;
+        .map_err(|e| WalletError::backup("Failed to create temp base folder", e))?;
     tempfile::TempDir::new_in(base)
-        .map_err(|e| WalletError::BackupError(format!("Failed to create temp dir: {}", e)))
+        .map_err(|e| WalletError::backup("Failed to create temp dir", e))
 }
 
 /// Check database version from a backup without fully restoring.
@@ -89,7 +215,7 @@ pub fn get_backup_db_version(backup_path: &Path) -> Result<String> {
     use tempfile::TempDir;
 
     let temp_dir = TempDir::new()
-        .map_err(|e| WalletError::BackupError(format!("Failed to create temp dir: {}", e)))?;
+        .map_err(|e| WalletError::backup("Failed to create temp dir", e))?;
     db_version_from_extracted(backup_path, temp_dir.path())
 }
 
@@ -105,8 +231,7 @@ fn db_version_from_extracted(backup_path: &Path, target: &Path) -> Result<String
 
     let db_path = extract_backup(backup_path, target)?;
 
-    let conn = Connection::open(&db_path)
-        .map_err(|e| WalletError::DatabaseError(format!("Failed to open database: {}", e)))?;
+    let conn = Connection::open(&db_path)?;
 
     let version: String = conn.query_row(
         "SELECT version FROM nswallet_properties LIMIT 1",
@@ -155,8 +280,7 @@ pub fn check_backup_password_in(backup_path: &Path, password: &str, temp_base: &
 pub fn get_db_version(db_path: &Path) -> Result<String> {
     use rusqlite::Connection;
 
-    let conn = Connection::open(db_path)
-        .map_err(|e| WalletError::DatabaseError(format!("Failed to open database: {}", e)))?;
+    let conn = Connection::open(db_path)?;
 
     let version: String = conn.query_row(
         "SELECT version FROM nswallet_properties LIMIT 1",
@@ -259,6 +383,79 @@ mod tests {
         assert!(is_backup_compatible_in(&backup_path, "5", &base).unwrap());
     }
 
+    fn zip_testdata(dir: &Path, name: &str) -> Option<PathBuf> {
+        use zip::write::SimpleFileOptions;
+        use zip::ZipWriter;
+
+        let source = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("testdata").join(name);
+        if !source.exists() {
+            eprintln!("Skipping test: testdata/{name} not found");
+            return None;
+        }
+        let backup_path = dir.join(format!("{name}.zip"));
+        let mut zip = ZipWriter::new(File::create(&backup_path).unwrap());
+        zip.start_file(DATABASE_FILENAME, SimpleFileOptions::default()).unwrap();
+        zip.write_all(&fs::read(source).unwrap()).unwrap();
+        zip.finish().unwrap();
+        Some(backup_path)
+    }
+
+    #[test]
+    fn test_inspect_backup_in_memory() {
+        let temp_dir = TempDir::new().unwrap();
+        let Some(backup_path) = zip_testdata(temp_dir.path(), "nswallet.dat") else { return };
+        let before: Vec<_> = fs::read_dir(temp_dir.path()).unwrap().collect();
+
+        let inspection = inspect_backup(&backup_path, 64 * 1024 * 1024).unwrap();
+        let conn = rusqlite::Connection::open(
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("testdata").join("nswallet.dat"),
+        ).unwrap();
+        assert_eq!(inspection.version, crate::database::migrations::get_database_version(&conn).unwrap());
+        assert_eq!(inspection.database_id, crate::database::queries::get_database_id(&conn).unwrap());
+        let stats = crate::database::queries::get_database_stats(&conn).unwrap();
+        assert_eq!(inspection.stats.total_items, stats.total_items);
+        assert_eq!(inspection.stats.total_fields, stats.total_fields);
+        assert!(inspection.stats.total_items > 0);
+        assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), before.len());
+
+        assert!(matches!(inspect_backup(&backup_path, 1024), Err(WalletError::BackupError(_))));
+        let garbage = create_test_backup(temp_dir.path());
+        assert!(inspect_backup(&garbage, 1024).is_err());
+    }
+
+    #[test]
+    fn test_restore_and_upgrade_old_backup() {
+        let temp_dir = TempDir::new().unwrap();
+        let Some(backup_path) = zip_testdata(temp_dir.path(), "nswallet_old.dat") else { return };
+        let db_path = temp_dir.path().join(DATABASE_FILENAME);
+        fs::write(&db_path, b"current database").unwrap();
+
+        let version = restore_and_upgrade(&backup_path, &db_path).unwrap();
+        assert_eq!(version, "1");
+        assert_eq!(get_db_version(&db_path).unwrap(), crate::database::migrations::CURRENT_VERSION);
+        assert!(!with_suffix(&db_path, PRE_RESTORE_SUFFIX).exists());
+    }
+
+    #[test]
+    fn test_restore_and_upgrade_rolls_back() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join(DATABASE_FILENAME);
+        fs::write(&db_path, b"current database").unwrap();
+
+        // Not an SQLite file: fails the integrity check
+        let garbage = create_test_backup(temp_dir.path());
+        assert!(restore_and_upgrade(&garbage, &db_path).is_err());
+        assert_eq!(fs::read(&db_path).unwrap(), b"current database");
+
+        let Some(future) = zip_testdata(temp_dir.path(), "nswallet_from_future.dat") else { return };
+        assert!(matches!(
+            restore_and_upgrade(&future, &db_path),
+            Err(WalletError::InvalidVersion(v)) if v == "999"
+        ));
+        assert_eq!(fs::read(&db_path).unwrap(), b"current database");
+        assert!(!with_suffix(&db_path, PRE_RESTORE_SUFFIX).exists());
+    }
+
     /// Test: CheckFutureVersionOfDb from C# BackupFixture
     /// Database with version 999 should NOT be compatible
     #[test]
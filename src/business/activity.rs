@@ -0,0 +1,221 @@
+//! Activity report for audits
+//!
+//! The wallet keeps no audit log, so the report is assembled from what it
+//! does record: the change feed of [`Wallet::recent_changes`], password
+//! edits (a PASS field replaced by `update_field`) and the backups in the
+//! attached backup manager's folder. Field values never appear in it.
+
+use std::collections::HashSet;
+use std::ops::RangeBounds;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::backup::BackupType;
+use crate::error::{Result, WalletError};
+use crate::export::{csv_escape, ExportFormat};
+use super::recent::RecentChangeKind;
+use super::wallet::Wallet;
+
+/// What an [`ActivityEntry`] records
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ActivityKind {
+    ItemCreated,
+    ItemModified,
+    ItemDeleted,
+    FieldCreated,
+    FieldModified,
+    FieldDeleted,
+    /// A password field was given a new value
+    PasswordChanged,
+    /// A backup file was written
+    Backup,
+}
+
+impl ActivityKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ActivityKind::ItemCreated => "item_created",
+            ActivityKind::ItemModified => "item_modified",
+            ActivityKind::ItemDeleted => "item_deleted",
+            ActivityKind::FieldCreated => "field_created",
+            ActivityKind::FieldModified => "field_modified",
+            ActivityKind::FieldDeleted => "field_deleted",
+            ActivityKind::PasswordChanged => "password_changed",
+            ActivityKind::Backup => "backup",
+        }
+    }
+}
+
+/// One line of [`Wallet::export_activity_report`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActivityEntry {
+    pub at: DateTime<Utc>,
+    pub kind: ActivityKind,
+    /// The item concerned; `None` for backups
+    pub item_id: Option<String>,
+    pub item_name: Option<String>,
+    /// Field label, or the backup type and file name
+    pub detail: Option<String>,
+}
+
+#[derive(Serialize)]
+struct JsonReport<'a> {
+    generated_at: DateTime<Utc>,
+    entries: &'a [ActivityEntry],
+}
+
+const CSV_HEADER: &str = "at,kind,item_id,item_name,detail\n";
+
+impl Wallet {
+    /// Activity within `range`, oldest first
+    pub fn activity(&mut self, range: impl RangeBounds<DateTime<Utc>>) -> Result<Vec<ActivityEntry>> {
+        self.ensure_unlocked()?;
+        let mut password_fields: HashSet<String> = self.get_fields()?.iter()
+            .filter(|f| f.field_type == "PASS")
+            .map(|f| f.field_id.clone())
+            .collect();
+        password_fields.extend(self.get_deleted_fields()?.into_iter()
+            .filter(|f| f.field_type == "PASS")
+            .map(|f| f.field_id));
+
+        let mut entries: Vec<ActivityEntry> = self.recent_changes(usize::MAX)?.into_iter()
+            .filter(|c| range.contains(&c.at))
+            .map(|c| {
+                let kind = match (c.field_id.as_deref(), c.kind) {
+                    (None, RecentChangeKind::Created) => ActivityKind::ItemCreated,
+                    (None, RecentChangeKind::Modified) => ActivityKind::ItemModified,
+                    (None, RecentChangeKind::Deleted) => ActivityKind::ItemDeleted,
+                    (Some(id), RecentChangeKind::Modified) if password_fields.contains(id) => {
+                        ActivityKind::PasswordChanged
+                    }
+                    (Some(_), RecentChangeKind::Created) => ActivityKind::FieldCreated,
+                    (Some(_), RecentChangeKind::Modified) => ActivityKind::FieldModified,
+                    (Some(_), RecentChangeKind::Deleted) => ActivityKind::FieldDeleted,
+                };
+                ActivityEntry {
+                    at: c.at,
+                    kind,
+                    item_id: Some(c.item_id),
+                    item_name: Some(c.item_name),
+                    detail: c.field_label,
+                }
+            })
+            .collect();
+
+        if let Some(manager) = self.backup_manager() {
+            for backup in manager.list_backups()? {
+                if !range.contains(&backup.timestamp) {
+                    continue;
+                }
+                let backup_type = match backup.backup_type {
+                    BackupType::Auto => "auto",
+                    BackupType::Manual => "manual",
+                    BackupType::Imported => "imported",
+                };
+                let file = backup.path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+                entries.push(ActivityEntry {
+                    at: backup.timestamp,
+                    kind: ActivityKind::Backup,
+                    item_id: None,
+                    item_name: None,
+                    detail: Some(format!("{backup_type} {file}")),
+                });
+            }
+        }
+
+        entries.sort_by_key(|e| e.at);
+        Ok(entries)
+    }
+
+    /// Export the activity within `range` as a CSV or JSON report: item and
+    /// field changes, password changes and backups, oldest first. Other
+    /// formats are rejected with `ExportError`.
+    pub fn export_activity_report(
+        &mut self,
+        range: impl RangeBounds<DateTime<Utc>>,
+        format: ExportFormat,
+    ) -> Result<Vec<u8>> {
+        if !matches!(format, ExportFormat::Csv | ExportFormat::Json) {
+            return Err(WalletError::ExportError(format!(
+                "Activity report cannot be exported as {}", format.extension()
+            )));
+        }
+        let entries = self.activity(range)?;
+        match format {
+            ExportFormat::Json => serde_json::to_vec_pretty(&JsonReport { generated_at: Utc::now(), entries: &entries })
+                .map_err(|e| WalletError::json("Failed to serialize activity report", e)),
+            _ => {
+                let mut out = String::from(CSV_HEADER);
+                for entry in &entries {
+                    out.push_str(&format!(
+                        "{},{},{},{},{}\n",
+                        entry.at.to_rfc3339(),
+                        entry.kind.as_str(),
+                        csv_escape(entry.item_id.as_deref().unwrap_or("")),
+                        csv_escape(entry.item_name.as_deref().unwrap_or("")),
+                        csv_escape(entry.detail.as_deref().unwrap_or("")),
+                    ));
+                }
+                Ok(out.into_bytes())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::{AutoBackupConfig, BackupManager};
+    use crate::business::wallet::tests::create_test_wallet;
+    use chrono::TimeDelta;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_activity_report() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let backups = TempDir::new().unwrap();
+        wallet.set_backup_manager(BackupManager::new(backups.path()), AutoBackupConfig::default());
+
+        let start = Utc::now() - TimeDelta::seconds(1);
+        let bank = wallet.add_item("Bank, main", "document", false, None).unwrap();
+        let pass = wallet.add_field(&bank, "PASS", "old-secret", None).unwrap();
+        // Move the item out of the creation window so the edit is reported
+        {
+            let conn = wallet.db.as_ref().unwrap().connection().unwrap();
+            conn.execute(
+                "UPDATE nswallet_items SET create_timestamp = datetime(create_timestamp, '-1 hours'), \
+                 change_timestamp = datetime(change_timestamp, '-1 hours')",
+                [],
+            ).unwrap();
+            conn.execute("UPDATE nswallet_fields SET change_timestamp = datetime(change_timestamp, '-1 hours')", []).unwrap();
+        }
+        wallet.clear_caches();
+        wallet.update_field(&pass, "new-secret", None).unwrap();
+        wallet.backup_manager().unwrap().create_backup(wallet.db.as_ref().unwrap(), true).unwrap();
+
+        let entries = wallet.activity(start..).unwrap();
+        let kinds: Vec<ActivityKind> = entries.iter().map(|e| e.kind).collect();
+        assert!(kinds.contains(&ActivityKind::PasswordChanged));
+        assert!(kinds.contains(&ActivityKind::Backup));
+        assert!(!kinds.contains(&ActivityKind::ItemCreated), "created an hour before the range");
+        assert!(entries.windows(2).all(|w| w[0].at <= w[1].at));
+
+        let all = wallet.activity(..).unwrap();
+        assert!(all.iter().any(|e| e.kind == ActivityKind::ItemCreated));
+
+        let csv = String::from_utf8(wallet.export_activity_report(.., ExportFormat::Csv).unwrap()).unwrap();
+        assert!(csv.starts_with(CSV_HEADER));
+        assert!(csv.contains("\"Bank, main\""));
+        assert!(csv.contains("password_changed"));
+        assert!(!csv.contains("secret"), "field values stay out of the report");
+
+        let json: serde_json::Value = serde_json::from_slice(
+            &wallet.export_activity_report(start.., ExportFormat::Json).unwrap()
+        ).unwrap();
+        assert_eq!(json["entries"].as_array().unwrap().len(), entries.len());
+
+        assert!(matches!(
+            wallet.export_activity_report(.., ExportFormat::Pdf),
+            Err(WalletError::ExportError(_))
+        ));
+    }
+}
@@ -0,0 +1,245 @@
+//! Item attachments
+//!
+//! Scans of documents, photos of cards and key files can be attached to an
+//! item. Each attachment is stored as its own row in the wallet database,
+//! with the file name, MIME type and content encrypted under the vault key
+//! (or the folder key below a folder PIN), so backups carry attachments
+//! along with everything else. Deleting an
+//! attachment only marks it; `compact` removes it for good.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::database::queries::{self, parse_timestamp, RawAttachment};
+use crate::crypto;
+use crate::error::{WalletError, Result};
+use crate::ATTACHMENT_MAX_SIZE;
+use super::ids::IdKind;
+use super::wallet::Wallet;
+
+/// A file attached to an item, without its content
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub attachment_id: String,
+    pub item_id: String,
+    /// File name as given when attached
+    pub name: String,
+    pub mime_type: String,
+    /// Content length in bytes
+    pub size: u64,
+    pub create_timestamp: DateTime<Utc>,
+}
+
+impl Wallet {
+    /// Attach a file to an item. Returns the new attachment ID.
+    pub fn add_attachment(&mut self, item_id: &str, name: &str, mime_type: &str, data: &[u8]) -> Result<String> {
+        self.ensure_unlocked()?;
+        if name.trim().is_empty() {
+            return Err(WalletError::ValidationError("Attachment name must not be empty".to_string()));
+        }
+        if data.len() > ATTACHMENT_MAX_SIZE {
+            return Err(WalletError::AttachmentTooLarge { size: data.len(), max: ATTACHMENT_MAX_SIZE });
+        }
+        let item = self.get_item(item_id)?
+            .filter(|i| !i.deleted)
+            .ok_or_else(|| WalletError::ItemNotFound(item_id.to_string()))?;
+        if item.folder {
+            return Err(WalletError::InvalidOperation("Folders cannot have attachments".to_string()));
+        }
+        self.ensure_item_editable(item_id)?;
+        self.ensure_item_accessible(item_id)?;
+
+        let seal = |plaintext: &[u8]| self.seal_item_data(item_id, plaintext);
+        let raw = RawAttachment {
+            attachment_id: String::new(),
+            item_id: item_id.to_string(),
+            name_encrypted: seal(name.as_bytes())?,
+            mime_type_encrypted: seal(mime_type.as_bytes())?,
+            size: data.len() as u64,
+            create_timestamp: None,
+        };
+        let data_encrypted = seal(data)?;
+
+        let attachment_id = self.unique_id(IdKind::Attachment, &[])?;
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        queries::insert_attachment(conn, &RawAttachment { attachment_id: attachment_id.clone(), ..raw }, &data_encrypted)?;
+
+        self.note_mutation();
+        Ok(attachment_id)
+    }
+
+    /// Attachments of an item, oldest first
+    pub fn get_attachments(&self, item_id: &str) -> Result<Vec<Attachment>> {
+        self.ensure_unlocked()?;
+        self.ensure_item_accessible(item_id)?;
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        let open_text = |blob: &[u8]| String::from_utf8(self.open_item_data(item_id, blob)?.to_vec())
+            .map_err(|_| WalletError::from(crypto::DecryptError::BadUtf8));
+        queries::get_attachments_raw(conn, item_id)?
+            .into_iter()
+            .map(|raw| Ok(Attachment {
+                name: open_text(&raw.name_encrypted)?,
+                mime_type: open_text(&raw.mime_type_encrypted)?,
+                create_timestamp: raw.create_timestamp.as_deref()
+                    .and_then(parse_timestamp)
+                    .unwrap_or_default(),
+                attachment_id: raw.attachment_id,
+                item_id: raw.item_id,
+                size: raw.size,
+            }))
+            .collect()
+    }
+
+    /// Decrypted content of an attachment
+    pub fn read_attachment(&self, attachment_id: &str) -> Result<Vec<u8>> {
+        self.ensure_unlocked()?;
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        let item_id = queries::get_attachment_item_id(conn, attachment_id)?
+            .ok_or_else(|| WalletError::AttachmentNotFound(attachment_id.to_string()))?;
+        self.ensure_item_accessible(&item_id)?;
+        let blob = queries::get_attachment_data(conn, attachment_id)?
+            .ok_or_else(|| WalletError::AttachmentNotFound(attachment_id.to_string()))?;
+        Ok(self.open_item_data(&item_id, &blob)?.to_vec())
+    }
+
+    /// Delete an attachment. It stays in the database until `compact`.
+    pub fn delete_attachment(&mut self, attachment_id: &str) -> Result<()> {
+        self.ensure_unlocked()?;
+        let item_id = {
+            let conn = self.db.as_ref()
+                .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+                .connection()?;
+            queries::get_attachment_item_id(conn, attachment_id)?
+                .ok_or_else(|| WalletError::AttachmentNotFound(attachment_id.to_string()))?
+        };
+        self.ensure_item_editable(&item_id)?;
+        self.ensure_item_accessible(&item_id)?;
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        queries::delete_attachment(conn, attachment_id)?;
+        self.note_mutation();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use crate::BackupManager;
+    use crate::business::wallet::tests::create_test_wallet;
+
+    fn attachment_rows(wallet: &Wallet) -> i64 {
+        let conn = wallet.db.as_ref().unwrap().connection().unwrap();
+        conn.query_row("SELECT COUNT(*) FROM nswallet_attachments", [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn test_add_read_and_delete_attachments() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let item = wallet.add_item("Passport", "passport", false, None).unwrap();
+        assert!(wallet.get_attachments(&item).unwrap().is_empty());
+
+        let scan = b"%PDF-1.7 passport scan".to_vec();
+        let first = wallet.add_attachment(&item, "scan.pdf", "application/pdf", &scan).unwrap();
+        let second = wallet.add_attachment(&item, "photo.jpg", "image/jpeg", &[0xFF, 0xD8, 0xFF]).unwrap();
+
+        let list = wallet.get_attachments(&item).unwrap();
+        assert_eq!(list.len(), 2);
+        assert_eq!((list[0].name.as_str(), list[0].mime_type.as_str(), list[0].size), ("scan.pdf", "application/pdf", scan.len() as u64));
+        assert_eq!(wallet.read_attachment(&first).unwrap(), scan);
+
+        // Content is stored encrypted
+        let conn = wallet.db.as_ref().unwrap().connection().unwrap();
+        let stored: Vec<u8> = conn.query_row(
+            "SELECT data FROM nswallet_attachments WHERE attachment_id = ?", [&first], |row| row.get(0),
+        ).unwrap();
+        assert!(!stored.windows(8).any(|w| w == b"passport"));
+
+        wallet.delete_attachment(&second).unwrap();
+        assert_eq!(wallet.get_attachments(&item).unwrap().len(), 1);
+        assert!(matches!(wallet.read_attachment(&second), Err(WalletError::AttachmentNotFound(_))));
+        assert!(matches!(wallet.delete_attachment(&second), Err(WalletError::AttachmentNotFound(_))));
+
+        // Deleted attachments, and those of purged items, go on compact
+        let other = wallet.add_item("Card", "visa", false, None).unwrap();
+        wallet.add_attachment(&other, "card.png", "image/png", b"png").unwrap();
+        wallet.delete_item(&other).unwrap();
+        assert_eq!(attachment_rows(&wallet), 3);
+        wallet.compact().unwrap();
+        assert_eq!(attachment_rows(&wallet), 1);
+        assert_eq!(wallet.read_attachment(&first).unwrap(), scan);
+    }
+
+    #[test]
+    fn test_attachment_rejections() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let folder = wallet.add_item("Docs", "folder", true, None).unwrap();
+        let item = wallet.add_item("Deed", "document", false, Some(&folder)).unwrap();
+        assert!(matches!(
+            wallet.add_attachment(&folder, "a.txt", "text/plain", b"a"),
+            Err(WalletError::InvalidOperation(_))
+        ));
+        assert!(matches!(
+            wallet.add_attachment("missing1", "a.txt", "text/plain", b"a"),
+            Err(WalletError::ItemNotFound(_))
+        ));
+        assert!(matches!(
+            wallet.add_attachment(&item, " ", "text/plain", b"a"),
+            Err(WalletError::ValidationError(_))
+        ));
+        let too_big = vec![0u8; ATTACHMENT_MAX_SIZE + 1];
+        assert!(matches!(
+            wallet.add_attachment(&item, "big.bin", "application/octet-stream", &too_big),
+            Err(WalletError::AttachmentTooLarge { .. })
+        ));
+        wallet.lock();
+        assert!(matches!(wallet.read_attachment("whatever"), Err(WalletError::Locked)));
+    }
+
+    #[test]
+    fn test_attachment_in_locked_folder() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let vault = wallet.add_item("Vault", "folder", true, None).unwrap();
+        let item = wallet.add_item("Passport", "document", false, Some(&vault)).unwrap();
+        let scan = wallet.add_attachment(&item, "scan.png", "image/png", b"png").unwrap();
+        wallet.set_folder_pin(&vault, "1234").unwrap();
+
+        wallet.lock_folder(&vault);
+        assert!(matches!(wallet.read_attachment(&scan), Err(WalletError::FolderLocked(_))));
+        assert!(matches!(wallet.get_attachments(&item), Err(WalletError::FolderLocked(_))));
+        assert!(matches!(
+            wallet.add_attachment(&item, "b.png", "image/png", b"b"),
+            Err(WalletError::FolderLocked(_))
+        ));
+        assert!(matches!(wallet.delete_attachment(&scan), Err(WalletError::FolderLocked(_))));
+
+        assert!(wallet.unlock_folder(&vault, "1234").unwrap());
+        assert_eq!(wallet.read_attachment(&scan).unwrap(), b"png");
+    }
+
+    #[test]
+    fn test_attachments_are_in_backups() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let item = wallet.add_item("Licence", "document", false, None).unwrap();
+        let id = wallet.add_attachment(&item, "licence.key", "application/octet-stream", b"KEY-123").unwrap();
+
+        let backups = TempDir::new().unwrap();
+        let manager = BackupManager::new(backups.path());
+        let backup = manager.create_backup(wallet.database().unwrap(), true).unwrap();
+
+        let restored = TempDir::new().unwrap();
+        manager.restore_backup(&backup, &restored.path().join(crate::DATABASE_FILENAME)).unwrap();
+        let mut copy = Wallet::open(restored.path()).unwrap();
+        assert!(copy.unlock("TestPassword123").unwrap());
+        assert_eq!(copy.get_attachments(&item).unwrap()[0].name, "licence.key");
+        assert_eq!(copy.read_attachment(&id).unwrap(), b"KEY-123");
+    }
+}
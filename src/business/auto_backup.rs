@@ -0,0 +1,227 @@
+//! Automatic backups driven by wallet mutations
+//!
+//! Once a [`BackupManager`] is attached with [`Wallet::set_backup_manager`],
+//! every successful write is counted and an auto backup is taken after
+//! `after_changes` writes or once `after_minutes` have passed since the last
+//! backup, whichever comes first. Old auto backups are then pruned with the
+//! manager's retention policy.
+//!
+//! A successful unlock also takes an auto backup when the newest one is
+//! older than `unlock_interval_hours` (24 by default), so a wallet that is
+//! opened daily gets at most one daily backup even without writes. The time
+//! of the last auto backup is kept in the wallet's properties, so the
+//! cooldown holds across sessions and when backup files are moved away.
+
+use chrono::{DateTime, Utc};
+use crate::backup::{AutoBackupConfig, BackupManager, BackupType};
+use crate::database::queries;
+use crate::error::{WalletError, Result};
+use super::wallet::Wallet;
+
+/// Auto backup state held by the wallet while a manager is attached.
+pub(crate) struct AutoBackupState {
+    pub(crate) manager: BackupManager,
+    pub(crate) config: AutoBackupConfig,
+    /// Writes since the last auto backup
+    pub(crate) pending_changes: u32,
+    /// Time of the last auto backup (or of attaching the manager)
+    pub(crate) last_backup: DateTime<Utc>,
+}
+
+impl Wallet {
+    /// Attach a backup manager so the wallet creates auto backups on its own.
+    /// Replaces any previously attached manager and resets the change counter.
+    pub fn set_backup_manager(&mut self, manager: BackupManager, config: AutoBackupConfig) {
+        self.auto_backup = Some(AutoBackupState {
+            manager,
+            config,
+            pending_changes: 0,
+            last_backup: Utc::now(),
+        });
+    }
+
+    /// Detach the backup manager; no further auto backups are taken.
+    pub fn clear_backup_manager(&mut self) -> Option<BackupManager> {
+        self.auto_backup.take().map(|s| s.manager)
+    }
+
+    /// The attached backup manager, if any
+    pub fn backup_manager(&self) -> Option<&BackupManager> {
+        self.auto_backup.as_ref().map(|s| &s.manager)
+    }
+
+    /// Number of writes since the last auto backup (0 without a manager)
+    pub fn pending_backup_changes(&self) -> u32 {
+        self.auto_backup.as_ref().map_or(0, |s| s.pending_changes)
+    }
+
+    /// Record one successful write and take an auto backup when the
+    /// configured threshold is reached. A failed backup never fails the write
+    /// that triggered it; the counter is kept so the next write retries.
+    /// Quota levels are re-checked after every write.
+    pub(crate) fn note_mutation(&mut self) {
+        self.check_quota();
+        self.pending_writes += 1;
+        self.last_write = Some(Utc::now());
+        let due = match self.auto_backup.as_mut() {
+            Some(state) => {
+                state.pending_changes += 1;
+                state.is_due(Utc::now())
+            }
+            None => false,
+        };
+        if due {
+            let _ = self.run_auto_backup();
+        }
+    }
+
+    /// Create an auto backup now and apply the retention policy.
+    fn run_auto_backup(&mut self) -> Result<()> {
+        let state = self.auto_backup.as_ref()
+            .ok_or_else(|| WalletError::BackupError("No backup manager attached".to_string()))?;
+        let db = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?;
+
+        state.manager.create_backup(db, false)?;
+        state.manager.cleanup_auto_backups(state.config.min_keep, state.config.max_age_days)?;
+        let now = Utc::now();
+        let _ = queries::set_last_auto_backup(db.connection()?, &now);
+
+        let state = self.auto_backup.as_mut().unwrap();
+        state.pending_changes = 0;
+        state.last_backup = now;
+        Ok(())
+    }
+
+    /// Take an auto backup after a successful unlock when the newest auto
+    /// backup (on disk or recorded in properties) is older than the
+    /// configured interval. Never fails the unlock.
+    pub(crate) fn auto_backup_on_unlock(&mut self) {
+        let Some(state) = self.auto_backup.as_ref() else { return };
+        let hours = state.config.unlock_interval_hours;
+        if hours == 0 {
+            return;
+        }
+        let newest_file = state.manager.list_backups().ok()
+            .and_then(|backups| backups.into_iter().find(|b| b.backup_type == BackupType::Auto))
+            .map(|b| b.timestamp);
+        let recorded = self.db.as_ref()
+            .and_then(|db| db.connection().ok())
+            .and_then(|conn| queries::get_last_auto_backup(conn).ok().flatten());
+
+        let due = match newest_file.max(recorded) {
+            Some(last) => Utc::now() - last >= chrono::Duration::hours(hours as i64),
+            None => true,
+        };
+        if due {
+            let _ = self.run_auto_backup();
+        }
+    }
+}
+
+impl AutoBackupState {
+    fn is_due(&self, now: DateTime<Utc>) -> bool {
+        if self.pending_changes == 0 {
+            return false;
+        }
+        let by_count = self.config.after_changes > 0
+            && self.pending_changes >= self.config.after_changes;
+        let by_time = self.config.after_minutes > 0
+            && now - self.last_backup >= chrono::Duration::minutes(self.config.after_minutes as i64);
+        by_count || by_time
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backup::BackupType;
+    use crate::business::wallet::tests::create_test_wallet;
+    use tempfile::TempDir;
+
+    fn attach(wallet: &mut Wallet, after_changes: u32, after_minutes: u32) -> TempDir {
+        let backups = TempDir::new().unwrap();
+        wallet.set_backup_manager(
+            BackupManager::new(backups.path()),
+            AutoBackupConfig { after_changes, after_minutes, ..AutoBackupConfig::default() },
+        );
+        backups
+    }
+
+    #[test]
+    fn test_no_manager_no_backup() {
+        let (mut wallet, _temp) = create_test_wallet();
+        wallet.add_item("Item", "document", false, None).unwrap();
+        assert_eq!(wallet.pending_backup_changes(), 0);
+        assert!(wallet.backup_manager().is_none());
+    }
+
+    #[test]
+    fn test_backup_after_n_changes() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let _backups = attach(&mut wallet, 3, 0);
+
+        let item_id = wallet.add_item("Item", "document", false, None).unwrap();
+        wallet.add_field(&item_id, "NOTE", "one", None).unwrap();
+        assert_eq!(wallet.pending_backup_changes(), 2);
+        assert!(wallet.backup_manager().unwrap().list_backups().unwrap().is_empty());
+
+        wallet.update_item_name(&item_id, "Renamed").unwrap();
+        assert_eq!(wallet.pending_backup_changes(), 0);
+        let backups = wallet.backup_manager().unwrap().list_backups().unwrap();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(backups[0].backup_type, BackupType::Auto);
+    }
+
+    #[test]
+    fn test_backup_after_elapsed_time() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let _backups = attach(&mut wallet, 0, 10);
+
+        wallet.add_item("First", "document", false, None).unwrap();
+        assert_eq!(wallet.pending_backup_changes(), 1);
+
+        wallet.auto_backup.as_mut().unwrap().last_backup = Utc::now() - chrono::Duration::minutes(11);
+        wallet.add_item("Second", "document", false, None).unwrap();
+        assert_eq!(wallet.pending_backup_changes(), 0);
+        assert_eq!(wallet.backup_manager().unwrap().list_backups().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_backup_on_unlock_once_per_interval() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let _backups = attach(&mut wallet, 0, 0);
+
+        wallet.lock();
+        assert!(wallet.unlock("TestPassword123").unwrap());
+        assert_eq!(wallet.backup_manager().unwrap().list_backups().unwrap().len(), 1);
+
+        // Within the interval: no second backup, even with the files gone
+        for backup in wallet.backup_manager().unwrap().list_backups().unwrap() {
+            std::fs::remove_file(backup.path).unwrap();
+        }
+        wallet.lock();
+        assert!(wallet.unlock("TestPassword123").unwrap());
+        assert!(wallet.backup_manager().unwrap().list_backups().unwrap().is_empty());
+
+        // Once the interval has passed, the next unlock backs up again
+        let conn = wallet.db.as_ref().unwrap().connection().unwrap();
+        queries::set_last_auto_backup(conn, &(Utc::now() - chrono::Duration::hours(25))).unwrap();
+        wallet.lock();
+        assert!(!wallet.unlock("wrong password").unwrap());
+        assert!(wallet.backup_manager().unwrap().list_backups().unwrap().is_empty());
+        assert!(wallet.unlock("TestPassword123").unwrap());
+        assert_eq!(wallet.backup_manager().unwrap().list_backups().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_clear_backup_manager() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let _backups = attach(&mut wallet, 1, 0);
+        assert!(wallet.clear_backup_manager().is_some());
+
+        wallet.add_item("Item", "document", false, None).unwrap();
+        assert_eq!(wallet.pending_backup_changes(), 0);
+        assert!(wallet.backup_manager().is_none());
+    }
+}
@@ -0,0 +1,121 @@
+//! Read-only wallets opened from backups
+//!
+//! [`Wallet::open_backup`] lets a user browse an old backup without
+//! restoring it over the current wallet. The backup's database is extracted
+//! into a private temporary folder, unlocked there and switched to
+//! read-only, so the read APIs work as usual while every write fails. When
+//! the wallet is dropped, the extracted files are overwritten with zeros
+//! before the folder is removed.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use tempfile::TempDir;
+use crate::backup::{extract_backup, temp_dir_in};
+use crate::error::{WalletError, Result};
+use super::wallet::Wallet;
+
+/// Folder holding the extracted database of a wallet opened from a backup
+pub(crate) struct BackupCopy {
+    dir: TempDir,
+}
+
+impl Drop for BackupCopy {
+    fn drop(&mut self) {
+        // The folder itself goes when `dir` drops, after this
+        let Ok(entries) = fs::read_dir(self.dir.path()) else {
+            return;
+        };
+        for entry in entries.flatten() {
+            let _ = wipe_file(&entry.path());
+        }
+    }
+}
+
+/// Overwrite a file with zeros
+fn wipe_file(path: &Path) -> std::io::Result<()> {
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    let zeros = [0u8; 64 * 1024];
+    let mut remaining = file.metadata()?.len();
+    while remaining > 0 {
+        let n = remaining.min(zeros.len() as u64) as usize;
+        file.write_all(&zeros[..n])?;
+        remaining -= n as u64;
+    }
+    file.sync_all()
+}
+
+impl Wallet {
+    /// Open a backup as a read-only wallet, unlocked with `password`.
+    ///
+    /// Uses the OS default temp location. Prefer [`Wallet::open_backup_in`]
+    /// on platforms where the default temp folder may not be writable.
+    pub fn open_backup(backup_path: &Path, password: &str) -> Result<Self> {
+        let dir = TempDir::new().map_err(|e| WalletError::backup("Failed to create temp dir", e))?;
+        Self::open_backup_copy(backup_path, password, dir)
+    }
+
+    /// [`Wallet::open_backup`], extracting into the app-writable folder
+    /// `temp_base`
+    pub fn open_backup_in(backup_path: &Path, password: &str, temp_base: &Path) -> Result<Self> {
+        Self::open_backup_copy(backup_path, password, temp_dir_in(temp_base)?)
+    }
+
+    fn open_backup_copy(backup_path: &Path, password: &str, dir: TempDir) -> Result<Self> {
+        let copy = BackupCopy { dir };
+        extract_backup(backup_path, copy.dir.path())?;
+        let mut wallet = Self::open(copy.dir.path())?;
+        wallet.backup_copy = Some(copy);
+        if !wallet.unlock(password)? {
+            return Err(WalletError::InvalidPassword);
+        }
+        wallet.database()?.connection()?.execute_batch("PRAGMA query_only = ON")?;
+        Ok(wallet)
+    }
+
+    /// Whether this wallet was opened from a backup with
+    /// [`Wallet::open_backup`], so that writes fail
+    pub fn is_read_only(&self) -> bool {
+        self.backup_copy.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BackupManager;
+    use crate::business::wallet::tests::create_test_wallet;
+
+    #[test]
+    fn test_open_backup_read_only() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let item = wallet.add_item("Mail", "document", false, None).unwrap();
+        wallet.add_field(&item, "PASS", "old-secret", None).unwrap();
+        let backups = TempDir::new().unwrap();
+        let backup = BackupManager::new(backups.path()).create_backup(wallet.database().unwrap(), true).unwrap();
+        wallet.add_item("Added later", "document", false, None).unwrap();
+
+        let scratch = TempDir::new().unwrap();
+        assert!(matches!(
+            Wallet::open_backup_in(&backup, "wrong", scratch.path()),
+            Err(WalletError::InvalidPassword)
+        ));
+        assert_eq!(fs::read_dir(scratch.path()).unwrap().count(), 0);
+
+        let mut old = Wallet::open_backup_in(&backup, "TestPassword123", scratch.path()).unwrap();
+        assert!(old.is_read_only());
+        assert!(!wallet.is_read_only());
+        let names: Vec<String> = old.get_items().unwrap().iter().map(|i| i.name.clone()).collect();
+        assert!(names.contains(&"Mail".to_string()));
+        assert!(!names.contains(&"Added later".to_string()));
+        assert_eq!(old.get_fields_by_item(&item).unwrap()[0].value, "old-secret");
+        assert!(old.add_item("New", "document", false, None).is_err());
+        assert!(old.delete_item(&item).is_err());
+        assert!(old.get_item(&item).unwrap().is_some_and(|i| !i.deleted));
+
+        let extracted = fs::read_dir(scratch.path()).unwrap().next().unwrap().unwrap().path();
+        assert!(extracted.join(crate::DATABASE_FILENAME).exists());
+        drop(old);
+        assert!(!extracted.exists());
+    }
+}
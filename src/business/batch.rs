@@ -0,0 +1,170 @@
+//! Batch operations with per-record outcomes
+//!
+//! Importers and multi-select actions apply one operation to many inputs.
+//! A bad input (a missing parent, a locked item) should not cost the user
+//! the rest of the batch, so these operations apply each input on its own
+//! and return a [`BatchResult`] listing what succeeded and why the rest
+//! failed. Failures that make the whole batch pointless, such as a locked
+//! wallet, are still returned as a plain error before anything is written.
+
+use crate::error::{WalletError, Result};
+use super::wallet::Wallet;
+
+/// An input of a batch that failed
+#[derive(Debug)]
+pub struct BatchFailure {
+    /// Position of the input in the batch
+    pub index: usize,
+    pub error: WalletError,
+}
+
+/// Outcomes of a batch, each in input order
+#[derive(Debug)]
+pub struct BatchResult<T> {
+    /// Position and result of each input that succeeded
+    pub succeeded: Vec<(usize, T)>,
+    pub failed: Vec<BatchFailure>,
+}
+
+impl<T> Default for BatchResult<T> {
+    fn default() -> Self {
+        Self { succeeded: Vec::new(), failed: Vec::new() }
+    }
+}
+
+impl<T> BatchResult<T> {
+    /// Record the outcome of input `index`
+    pub fn record(&mut self, index: usize, outcome: Result<T>) {
+        match outcome {
+            Ok(value) => self.succeeded.push((index, value)),
+            Err(error) => self.failed.push(BatchFailure { index, error }),
+        }
+    }
+
+    /// Whether every input succeeded
+    pub fn is_complete(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    /// Number of inputs
+    pub fn len(&self) -> usize {
+        self.succeeded.len() + self.failed.len()
+    }
+
+    /// Whether the batch had no inputs
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The values of the successful inputs, in input order
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.succeeded.iter().map(|(_, value)| value)
+    }
+
+    /// All values, or the error of the first failed input
+    pub fn into_result(self) -> Result<Vec<T>> {
+        match self.failed.into_iter().next() {
+            Some(failure) => Err(failure.error),
+            None => Ok(self.succeeded.into_iter().map(|(_, value)| value).collect()),
+        }
+    }
+}
+
+impl<T> FromIterator<Result<T>> for BatchResult<T> {
+    fn from_iter<I: IntoIterator<Item = Result<T>>>(outcomes: I) -> Self {
+        let mut result = Self::default();
+        for (index, outcome) in outcomes.into_iter().enumerate() {
+            result.record(index, outcome);
+        }
+        result
+    }
+}
+
+/// An item to create with [`Wallet::add_items_bulk`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewItem {
+    pub name: String,
+    pub icon: String,
+    pub folder: bool,
+    /// Parent folder; `None` for the root
+    pub parent_id: Option<String>,
+}
+
+impl Wallet {
+    /// Create several items. Each input is created on its own; the result
+    /// holds the new item IDs and the inputs that were refused.
+    pub fn add_items_bulk(&mut self, items: &[NewItem]) -> Result<BatchResult<String>> {
+        self.ensure_unlocked()?;
+        Ok(items.iter()
+            .map(|item| self.add_item(&item.name, &item.icon, item.folder, item.parent_id.as_deref()))
+            .collect())
+    }
+
+    /// Move several items into `new_parent_id`, each on its own
+    pub fn move_items_bulk(&mut self, item_ids: &[&str], new_parent_id: &str) -> Result<BatchResult<()>> {
+        self.ensure_unlocked()?;
+        if new_parent_id != crate::ROOT_ID && !self.get_item(new_parent_id)?.is_some_and(|p| p.folder && !p.deleted) {
+            return Err(WalletError::ParentNotFound(new_parent_id.to_string()));
+        }
+        Ok(item_ids.iter().map(|id| self.move_item(id, new_parent_id)).collect())
+    }
+
+    /// Soft-delete several items, each on its own
+    pub fn delete_items_bulk(&mut self, item_ids: &[&str]) -> Result<BatchResult<()>> {
+        self.ensure_unlocked()?;
+        Ok(item_ids.iter().map(|id| self.delete_item(id)).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::business::wallet::tests::create_test_wallet;
+
+    #[test]
+    fn test_batch_result_records_outcomes() {
+        let result: BatchResult<u32> = vec![Ok(1), Err(WalletError::Locked), Ok(3)].into_iter().collect();
+        assert_eq!(result.len(), 3);
+        assert!(!result.is_complete());
+        assert_eq!(result.succeeded, [(0, 1), (2, 3)]);
+        assert_eq!(result.failed[0].index, 1);
+        assert!(matches!(result.into_result(), Err(WalletError::Locked)));
+
+        let result: BatchResult<u32> = vec![Ok(1), Ok(2)].into_iter().collect();
+        assert_eq!(result.into_result().unwrap(), [1, 2]);
+    }
+
+    #[test]
+    fn test_bulk_operations_keep_going_past_failures() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let folder = wallet.add_item("Work", "folder", true, None).unwrap();
+        let new_item = |name: &str, parent: Option<&str>| NewItem {
+            name: name.to_string(),
+            icon: "document".to_string(),
+            folder: false,
+            parent_id: parent.map(str::to_string),
+        };
+        let added = wallet.add_items_bulk(&[
+            new_item("Mail", None),
+            new_item("Orphan", Some("missing")),
+            new_item("VPN", Some(&folder)),
+        ]).unwrap();
+        assert_eq!(added.succeeded.len(), 2);
+        assert_eq!(added.failed.len(), 1);
+        assert_eq!(added.failed[0].index, 1);
+        let ids: Vec<String> = added.values().cloned().collect();
+
+        let moved = wallet.move_items_bulk(&[&ids[0], "missing"], &folder).unwrap();
+        assert_eq!(moved.succeeded, [(0, ())]);
+        assert_eq!(moved.failed[0].index, 1);
+        assert_eq!(wallet.get_item(&ids[0]).unwrap().unwrap().parent_id.as_deref(), Some(folder.as_str()));
+        assert!(matches!(wallet.move_items_bulk(&[&ids[0]], "missing"), Err(WalletError::ParentNotFound(_))));
+
+        let deleted = wallet.delete_items_bulk(&[crate::ROOT_ID, &ids[1]]).unwrap();
+        assert_eq!(deleted.failed[0].index, 0);
+        assert_eq!(deleted.succeeded.len(), 1);
+
+        wallet.lock();
+        assert!(matches!(wallet.add_items_bulk(&[new_item("Late", None)]), Err(WalletError::Locked)));
+    }
+}
@@ -0,0 +1,300 @@
+//! Wallet cloning
+//!
+//! [`Wallet::clone_to`] writes a copy of the wallet to a new database with
+//! its own key, password and database ID, e.g. to hand a sanitized copy to
+//! a family member. Every encrypted blob is decrypted under this wallet's
+//! key and encrypted again under the copy's.
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use zeroize::Zeroizing;
+
+use crate::database::queries::{self, RawAttachment};
+use crate::error::{WalletError, Result};
+use crate::{DATABASE_FILENAME, ROOT_ID, ROOT_PARENT_ID};
+use super::sync::RawRecord;
+use super::wallet::Wallet;
+
+impl Wallet {
+    /// Create a new wallet in `folder` holding a copy of this wallet's
+    /// labels, items, fields, notes, secure notes, attachments and item
+    /// metadata, encrypted under `new_password`. The copy gets a fresh
+    /// database ID and key; backups, emergency grants and unlock history
+    /// are not copied. Without `include_deleted` the trash is left behind,
+    /// and active items of a deleted folder move to the root. Records that
+    /// do not decrypt are skipped (see `get_undecryptable_records`).
+    /// Metadata encryption carries over, and so do folder PINs: every
+    /// protected folder must be unlocked (else `FolderLocked`) and stays
+    /// unlocked in the copy. Returns the copy, unlocked.
+    pub fn clone_to(&mut self, folder: &Path, new_password: &str, include_deleted: bool) -> Result<Wallet> {
+        self.ensure_unlocked()?;
+        let db_path = folder.join(DATABASE_FILENAME);
+        if db_path.exists() {
+            return Err(WalletError::InvalidOperation(format!(
+                "A wallet already exists in {}", folder.display()
+            )));
+        }
+
+        let lang = self.get_properties()?.lang;
+        let sealed = self.metadata_encryption_enabled()?;
+        let mut copy = Wallet::create(folder, new_password, &lang)?;
+
+        let copied = self.copy_records_to(&mut copy, include_deleted)
+            .and_then(|()| if sealed { copy.enable_metadata_encryption().map(drop) } else { Ok(()) });
+        if let Err(e) = copied {
+            copy.close();
+            for suffix in ["", "-wal", "-shm"] {
+                let mut path = db_path.clone().into_os_string();
+                path.push(suffix);
+                let _ = std::fs::remove_file(path);
+            }
+            return Err(e);
+        }
+        Ok(copy)
+    }
+
+    /// Re-encrypt this wallet's records into the freshly created `copy`
+    fn copy_records_to(&self, copy: &mut Wallet, include_deleted: bool) -> Result<()> {
+        let reseal = |blob: &[u8]| -> Option<Vec<u8>> {
+            if blob.is_empty() {
+                return Some(Vec::new());
+            }
+            copy.enc_value(&self.dec_field_value(blob).ok()?).ok()
+        };
+
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        let mut labels = queries::get_all_labels(conn)?;
+        if include_deleted {
+            labels.extend(queries::get_deleted_labels(conn)?);
+        }
+
+        // Field values under a folder PIN are resealed with the copy's key
+        // of that folder, which needs the PIN this session opened it with
+        let protected = queries::get_folder_pin_ids(conn)?;
+        for folder_id in &protected {
+            self.folder_key(folder_id)?;
+        }
+
+        let mut items = Vec::new();
+        let mut fields = Vec::new();
+        for record in self.get_raw_records()? {
+            match record {
+                RawRecord::Item(mut item) if include_deleted || !item.deleted => {
+                    self.open_item_envelope(&mut item);
+                    item.meta = None;
+                    let Some(name) = reseal(&item.name_encrypted) else { continue };
+                    item.name_encrypted = name;
+                    items.push(item);
+                }
+                RawRecord::Field(mut field) if include_deleted || !field.deleted => {
+                    self.open_field_envelope(&mut field);
+                    field.meta = None;
+                    let value = if field.value_encrypted.is_empty() {
+                        None
+                    } else {
+                        let Ok(value) = self.dec_field_value(&field.value_encrypted) else { continue };
+                        Some(Zeroizing::new(value))
+                    };
+                    fields.push((field, value));
+                }
+                _ => {}
+            }
+        }
+
+        let kept: HashSet<String> = items.iter().map(|i| i.item_id.clone()).collect();
+        for item in &mut items {
+            let parent = item.parent_id.as_deref().unwrap_or(ROOT_ID);
+            if parent != ROOT_ID && parent != ROOT_PARENT_ID && !kept.contains(parent) {
+                item.parent_id = Some(ROOT_ID.to_string());
+            }
+        }
+        fields.retain(|(f, _)| kept.contains(&f.item_id));
+        let protected: Vec<String> = protected.into_iter().filter(|id| kept.contains(id)).collect();
+
+        let mut notes = Vec::new();
+        let mut metadata = Vec::new();
+        let has_notes = queries::item_notes_table_exists(conn)?;
+        let has_metadata = queries::item_metadata_table_exists(conn)?;
+        let mut secure_notes = Vec::new();
+        let mut folder_defaults = Vec::new();
+        let mut attachments = Vec::new();
+        for item in &items {
+            if item.folder
+                && let Some((icon, template)) = queries::get_folder_defaults_raw(conn, &item.item_id)? {
+                    folder_defaults.push((item.item_id.as_str(), icon, template));
+                }
+            // Item data is opened here and sealed once the copy has its
+            // folder keys; a value that does not open is skipped
+            let open = |blob: &[u8]| self.open_item_data(&item.item_id, blob).ok();
+            if has_notes
+                && let Some(note) = queries::get_item_note_raw(conn, &item.item_id)?
+                && let Some(note) = open(&note) {
+                    notes.push((item.item_id.as_str(), note));
+                }
+            if has_metadata
+                && let Some(meta) = queries::get_item_metadata_raw(conn, &item.item_id)?
+                && let Some(meta) = open(&meta) {
+                    metadata.push((item.item_id.as_str(), meta));
+                }
+            // A secure note body with any unreadable chunk is skipped
+            let count = queries::get_secure_note_chunk_count(conn, &item.item_id)?;
+            let chunks = (0..count)
+                .map(|chunk| {
+                    let data = queries::get_secure_note_chunk(conn, &item.item_id, chunk).ok()??;
+                    self.open_note_chunk(&item.item_id, chunk, chunk + 1 == count, &data).ok()
+                        .map(|plain| (chunk, chunk + 1 == count, plain))
+                })
+                .collect::<Option<Vec<_>>>();
+            if let Some(chunks) = chunks {
+                secure_notes.extend(chunks.into_iter().map(|(chunk, last, data)| (item.item_id.as_str(), chunk, last, data)));
+            }
+            for raw in queries::get_attachments_raw(conn, &item.item_id)? {
+                let Some(data) = queries::get_attachment_data(conn, &raw.attachment_id)? else { continue };
+                let opened = [&raw.name_encrypted, &raw.mime_type_encrypted, &data].map(|blob| open(blob));
+                if let [Some(name), Some(mime_type), Some(data)] = opened {
+                    attachments.push((raw, [name, mime_type, data]));
+                }
+            }
+        }
+
+        let target = copy.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        for label in &labels {
+            queries::upsert_label_raw(target, label)?;
+        }
+        for item in &items {
+            queries::upsert_item_raw(target, item)?;
+        }
+        self.copy_folder_pins_to(copy, &protected)?;
+        let target = copy.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        for (mut field, value) in fields {
+            field.value_encrypted = match value {
+                Some(value) => copy.enc_field_value(&field.item_id, &value)?,
+                None => Vec::new(),
+            };
+            queries::upsert_field_raw(target, &field)?;
+        }
+        for (item_id, note) in &notes {
+            queries::set_item_note_raw(target, item_id, &copy.seal_item_data(item_id, note)?)?;
+        }
+        for (item_id, meta) in &metadata {
+            queries::set_item_metadata_raw(target, item_id, &copy.seal_item_data(item_id, meta)?)?;
+        }
+        for (item_id, chunk, last, data) in &secure_notes {
+            queries::set_secure_note_chunk(target, item_id, *chunk, &copy.seal_note_chunk(item_id, *chunk, *last, data)?)?;
+        }
+        for (item_id, icon, template) in &folder_defaults {
+            queries::set_folder_defaults_raw(target, item_id, icon.as_deref(), template.as_deref())?;
+        }
+        for (raw, [name, mime_type, data]) in attachments {
+            let attachment = RawAttachment {
+                name_encrypted: copy.seal_item_data(&raw.item_id, &name)?,
+                mime_type_encrypted: copy.seal_item_data(&raw.item_id, &mime_type)?,
+                ..raw
+            };
+            queries::insert_attachment(target, &attachment, &copy.seal_item_data(&attachment.item_id, &data)?)?;
+        }
+
+        copy.clear_caches();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use crate::business::wallet::tests::create_test_wallet;
+
+    #[test]
+    fn test_clone_to() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let folder = wallet.add_item("Family", "folder", true, None).unwrap();
+        wallet.set_folder_defaults(&folder, Some("card"), &["PASS"]).unwrap();
+        let bank = wallet.add_item("Bank", "document", false, Some(&folder)).unwrap();
+        wallet.add_field(&bank, "PASS", "secret", None).unwrap();
+        wallet.set_item_note(&bank, "PIN is in the safe").unwrap();
+        let codes = wallet.add_secure_note("Codes", None).unwrap();
+        wallet.set_secure_note_body(&codes, "ä1 ä2 ä3").unwrap();
+        let scan = wallet.add_attachment(&bank, "card.png", "image/png", b"\x89PNG").unwrap();
+        let club = wallet.add_label("Club", "labelcard", "text").unwrap();
+        wallet.add_field(&bank, &club, "42", None).unwrap();
+        let old = wallet.add_item("Old", "document", false, None).unwrap();
+        wallet.delete_item(&old).unwrap();
+
+        let target = TempDir::new().unwrap();
+        let dir = target.path().join("copy");
+        let mut copy = wallet.clone_to(&dir, "CopyPassword456", false).unwrap();
+        assert_ne!(copy.get_properties().unwrap().database_id, wallet.get_properties().unwrap().database_id);
+        assert_eq!(copy.get_item(&bank).unwrap().unwrap().parent_id.as_deref(), Some(folder.as_str()));
+        let fields = copy.get_fields_by_item(&bank).unwrap();
+        assert_eq!(fields.len(), 3);
+        assert!(fields.iter().any(|f| f.label == "Club" && f.value == "42"));
+        assert_eq!(copy.get_folder_defaults(&folder).unwrap(), wallet.get_folder_defaults(&folder).unwrap());
+        assert_eq!(copy.get_item_note(&bank).unwrap().as_deref(), Some("PIN is in the safe"));
+        assert_eq!(copy.get_secure_note_body(&codes).unwrap(), "ä1 ä2 ä3");
+        assert_eq!(copy.get_attachments(&bank).unwrap()[0].name, "card.png");
+        assert_eq!(copy.read_attachment(&scan).unwrap(), b"\x89PNG");
+        assert!(copy.get_deleted_items().unwrap().is_empty());
+        drop(copy);
+
+        let mut reopened = Wallet::open(&dir).unwrap();
+        assert!(!reopened.unlock("TestPassword123").unwrap());
+        assert!(reopened.unlock("CopyPassword456").unwrap());
+        assert_eq!(reopened.get_item(&bank).unwrap().unwrap().name, "Bank");
+
+        // The trash comes along on request; an existing wallet is never overwritten
+        let with_trash = wallet.clone_to(&target.path().join("full"), "CopyPassword456", true).unwrap();
+        drop(with_trash);
+        let mut full = Wallet::open(&target.path().join("full")).unwrap();
+        full.unlock("CopyPassword456").unwrap();
+        assert_eq!(full.get_deleted_items().unwrap().len(), 1);
+        assert!(matches!(wallet.clone_to(&dir, "CopyPassword456", false), Err(WalletError::InvalidOperation(_))));
+    }
+
+    #[test]
+    fn test_clone_keeps_folder_pins() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let vault = wallet.add_item("Vault", "folder", true, None).unwrap();
+        let bank = wallet.add_item("Bank", "document", false, Some(&vault)).unwrap();
+        wallet.add_field(&bank, "PASS", "secret", None).unwrap();
+        wallet.set_folder_pin(&vault, "1234").unwrap();
+
+        let target = TempDir::new().unwrap();
+        wallet.lock_folder(&vault);
+        assert!(matches!(
+            wallet.clone_to(&target.path().join("copy"), "CopyPassword456", false),
+            Err(WalletError::FolderLocked(_))
+        ));
+        assert!(!target.path().join("copy").join(DATABASE_FILENAME).exists());
+
+        assert!(wallet.unlock_folder(&vault, "1234").unwrap());
+        let mut copy = wallet.clone_to(&target.path().join("copy"), "CopyPassword456", false).unwrap();
+        assert_eq!(copy.protected_folders().unwrap(), vec![vault.clone()]);
+        assert_eq!(copy.get_fields_by_item(&bank).unwrap()[0].value, "secret");
+        copy.lock_folder(&vault);
+        assert!(matches!(copy.get_fields_by_item(&bank), Err(WalletError::FolderLocked(_))));
+        assert!(copy.unlock_folder(&vault, "1234").unwrap());
+        assert_eq!(copy.get_fields_by_item(&bank).unwrap()[0].value, "secret");
+    }
+
+    #[test]
+    fn test_clone_keeps_profiles() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let work = wallet.create_profile("Work").unwrap();
+        let vpn = wallet.add_item("VPN", "document", false, Some(&work)).unwrap();
+
+        let target = TempDir::new().unwrap();
+        let mut copy = wallet.clone_to(&target.path().join("copy"), "CopyPassword456", false).unwrap();
+        let profile = copy.get_item(&work).unwrap().unwrap();
+        assert_eq!(profile.parent_id.as_deref(), Some(crate::ROOT_PARENT_ID));
+        assert_eq!(copy.get_item(&vpn).unwrap().unwrap().parent_id.as_deref(), Some(work.as_str()));
+        assert_eq!(copy.list_profiles().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,289 @@
+//! Wallet snapshots and diffs
+//!
+//! A [`WalletSnapshot`] is a decrypted, point-in-time copy of the active
+//! items and fields. [`Wallet::diff`] compares two snapshots, e.g. the live
+//! wallet against a backup, for restore previews and "what changed" reports.
+
+use std::collections::{HashMap, HashSet};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::error::Result;
+use crate::database::{IWField, IWItem};
+use crate::ROOT_ID;
+use super::wallet::Wallet;
+
+/// Decrypted point-in-time copy of a wallet's active items and fields
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalletSnapshot {
+    /// When the snapshot was taken
+    pub taken_at: DateTime<Utc>,
+    /// Active items (root excluded)
+    pub items: Vec<IWItem>,
+    /// Active fields
+    pub fields: Vec<IWField>,
+}
+
+/// What changed about an item between two snapshots
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ItemChangeKind {
+    Renamed,
+    IconChanged,
+    Moved,
+}
+
+/// An item present in both snapshots with different attributes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemChange {
+    pub before: IWItem,
+    pub after: IWItem,
+    pub changes: Vec<ItemChangeKind>,
+}
+
+/// A field whose value or position changed between two snapshots
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub before: IWField,
+    pub after: IWField,
+    /// The value differs (otherwise only the sort order changed)
+    pub value_changed: bool,
+}
+
+/// Differences from snapshot A to snapshot B. Every list is sorted by id.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WalletDiff {
+    pub added_items: Vec<IWItem>,
+    pub removed_items: Vec<IWItem>,
+    pub modified_items: Vec<ItemChange>,
+    pub added_fields: Vec<IWField>,
+    pub removed_fields: Vec<IWField>,
+    pub modified_fields: Vec<FieldChange>,
+}
+
+impl WalletDiff {
+    /// True when the two snapshots hold the same data
+    pub fn is_empty(&self) -> bool {
+        self.added_items.is_empty()
+            && self.removed_items.is_empty()
+            && self.modified_items.is_empty()
+            && self.added_fields.is_empty()
+            && self.removed_fields.is_empty()
+            && self.modified_fields.is_empty()
+    }
+
+    /// Total number of changed records
+    pub fn change_count(&self) -> usize {
+        self.added_items.len()
+            + self.removed_items.len()
+            + self.modified_items.len()
+            + self.added_fields.len()
+            + self.removed_fields.len()
+            + self.modified_fields.len()
+    }
+}
+
+impl Wallet {
+    /// Take a decrypted snapshot of the active items and fields
+    ///
+    /// Items and fields are in export order (see `export_json`), so two
+    /// snapshots of the same data serialize identically.
+    pub fn snapshot(&mut self) -> Result<WalletSnapshot> {
+        self.load_items_if_needed()?;
+        self.load_fields_if_needed()?;
+        let (items, fields) = crate::export::ordered(
+            self.items_cache.as_deref().unwrap_or_default(),
+            self.fields_cache.as_deref().unwrap_or_default(),
+        );
+        let items = items.into_iter().filter(|i| i.item_id != ROOT_ID).cloned().collect();
+        let fields = fields.into_iter().cloned().collect();
+        Ok(WalletSnapshot {
+            taken_at: Utc::now(),
+            items,
+            fields,
+        })
+    }
+
+    /// Compare two snapshots.
+    ///
+    /// Items are matched by id. Fields are matched by id first; because
+    /// `update_field` gives the edited field a new id, leftover removed and
+    /// added fields of the same item and type are then paired up (in sort
+    /// order) and reported as modified rather than as remove + add.
+    pub fn diff(a: &WalletSnapshot, b: &WalletSnapshot) -> WalletDiff {
+        let mut diff = WalletDiff::default();
+
+        let items_a: HashMap<&str, &IWItem> = a.items.iter().map(|i| (i.item_id.as_str(), i)).collect();
+        let items_b: HashMap<&str, &IWItem> = b.items.iter().map(|i| (i.item_id.as_str(), i)).collect();
+
+        for (id, before) in &items_a {
+            match items_b.get(id) {
+                None => diff.removed_items.push((*before).clone()),
+                Some(after) => {
+                    let mut changes = Vec::new();
+                    if before.name != after.name {
+                        changes.push(ItemChangeKind::Renamed);
+                    }
+                    if before.icon != after.icon {
+                        changes.push(ItemChangeKind::IconChanged);
+                    }
+                    if before.parent_id != after.parent_id {
+                        changes.push(ItemChangeKind::Moved);
+                    }
+                    if !changes.is_empty() {
+                        diff.modified_items.push(ItemChange {
+                            before: (*before).clone(),
+                            after: (*after).clone(),
+                            changes,
+                        });
+                    }
+                }
+            }
+        }
+        for (id, after) in &items_b {
+            if !items_a.contains_key(id) {
+                diff.added_items.push((*after).clone());
+            }
+        }
+
+        let key = |f: &IWField| (f.item_id.clone(), f.field_id.clone());
+        let fields_a: HashMap<(String, String), &IWField> = a.fields.iter().map(|f| (key(f), f)).collect();
+        let fields_b: HashMap<(String, String), &IWField> = b.fields.iter().map(|f| (key(f), f)).collect();
+
+        let mut removed: Vec<&IWField> = Vec::new();
+        let mut added: Vec<&IWField> = Vec::new();
+        for (k, before) in &fields_a {
+            match fields_b.get(k) {
+                None => removed.push(before),
+                Some(after) => {
+                    if let Some(change) = field_change(before, after) {
+                        diff.modified_fields.push(change);
+                    }
+                }
+            }
+        }
+        for (k, after) in &fields_b {
+            if !fields_a.contains_key(k) {
+                added.push(after);
+            }
+        }
+
+        // Pair same item + type leftovers as edits
+        removed.sort_by_key(|f| (f.sort_weight, f.field_id.clone()));
+        added.sort_by_key(|f| (f.sort_weight, f.field_id.clone()));
+        let mut paired: HashSet<(String, String)> = HashSet::new();
+        for before in &removed {
+            let partner = added.iter().find(|f| {
+                f.item_id == before.item_id
+                    && f.field_type == before.field_type
+                    && !paired.contains(&key(f))
+            });
+            match partner {
+                Some(after) => {
+                    paired.insert(key(after));
+                    diff.modified_fields.push(FieldChange {
+                        before: (*before).clone(),
+                        after: (*after).clone(),
+                        value_changed: before.value != after.value,
+                    });
+                }
+                None => diff.removed_fields.push((*before).clone()),
+            }
+        }
+        for after in added {
+            if !paired.contains(&key(after)) {
+                diff.added_fields.push(after.clone());
+            }
+        }
+
+        diff.added_items.sort_by(|x, y| x.item_id.cmp(&y.item_id));
+        diff.removed_items.sort_by(|x, y| x.item_id.cmp(&y.item_id));
+        diff.modified_items.sort_by(|x, y| x.after.item_id.cmp(&y.after.item_id));
+        diff.added_fields.sort_by_key(key);
+        diff.removed_fields.sort_by_key(key);
+        diff.modified_fields.sort_by_key(|c| key(&c.after));
+        diff
+    }
+}
+
+fn field_change(before: &IWField, after: &IWField) -> Option<FieldChange> {
+    let value_changed = before.value != after.value;
+    if value_changed || before.sort_weight != after.sort_weight {
+        Some(FieldChange {
+            before: before.clone(),
+            after: after.clone(),
+            value_changed,
+        })
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::business::wallet::tests::create_test_wallet;
+
+    #[test]
+    fn test_diff_identical_snapshots_is_empty() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let item = wallet.add_item("Bank", "document", false, None).unwrap();
+        wallet.add_field(&item, "NOTE", "hello", None).unwrap();
+
+        let a = wallet.snapshot().unwrap();
+        let b = wallet.snapshot().unwrap();
+        let diff = Wallet::diff(&a, &b);
+        assert!(diff.is_empty());
+        assert_eq!(diff.change_count(), 0);
+    }
+
+    #[test]
+    fn test_diff_items() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let folder = wallet.add_item("Folder", "folder", true, None).unwrap();
+        let kept = wallet.add_item("Kept", "document", false, None).unwrap();
+        let gone = wallet.add_item("Gone", "document", false, None).unwrap();
+        let a = wallet.snapshot().unwrap();
+
+        wallet.update_item_name(&kept, "Renamed").unwrap();
+        wallet.move_item(&kept, &folder).unwrap();
+        wallet.delete_item(&gone).unwrap();
+        let new = wallet.add_item("New", "document", false, None).unwrap();
+        let b = wallet.snapshot().unwrap();
+
+        let diff = Wallet::diff(&a, &b);
+        assert_eq!(diff.added_items.len(), 1);
+        assert_eq!(diff.added_items[0].item_id, new);
+        assert_eq!(diff.removed_items.len(), 1);
+        assert_eq!(diff.removed_items[0].item_id, gone);
+        assert_eq!(diff.modified_items.len(), 1);
+        assert_eq!(diff.modified_items[0].changes, vec![ItemChangeKind::Renamed, ItemChangeKind::Moved]);
+
+        // Reverse direction swaps added and removed
+        let reverse = Wallet::diff(&b, &a);
+        assert_eq!(reverse.added_items[0].item_id, gone);
+        assert_eq!(reverse.removed_items[0].item_id, new);
+    }
+
+    #[test]
+    fn test_diff_fields_pairs_updates_as_modifications() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let item = wallet.add_item("Bank", "document", false, None).unwrap();
+        let pass = wallet.add_field(&item, "PASS", "old", None).unwrap();
+        let note = wallet.add_field(&item, "NOTE", "bye", None).unwrap();
+        let a = wallet.snapshot().unwrap();
+
+        wallet.update_field(&pass, "new", None).unwrap();
+        wallet.delete_field(&item, &note).unwrap();
+        wallet.add_field(&item, "MAIL", "a@b.com", None).unwrap();
+        let b = wallet.snapshot().unwrap();
+
+        let diff = Wallet::diff(&a, &b);
+        assert_eq!(diff.modified_fields.len(), 1);
+        assert_eq!(diff.modified_fields[0].before.value, "old");
+        assert_eq!(diff.modified_fields[0].after.value, "new");
+        assert!(diff.modified_fields[0].value_changed);
+        assert_eq!(diff.removed_fields.len(), 1);
+        assert_eq!(diff.removed_fields[0].field_type, "NOTE");
+        assert_eq!(diff.added_fields.len(), 1);
+        assert_eq!(diff.added_fields[0].field_type, "MAIL");
+    }
+}
@@ -0,0 +1,300 @@
+//! Emergency access for a trusted contact
+//!
+//! The owner gives a contact their own secret. A grant stores the DEK
+//! wrapped under a KEK derived from that secret, plus a waiting period.
+//! To get in, the contact first requests access; the secret only unlocks
+//! the wallet once the waiting period has passed since the request. Until
+//! then the owner can deny the request or revoke the grant, which also
+//! destroys the wrapped key.
+//!
+//! The waiting period is enforced by this library and the system clock,
+//! not by cryptography: whoever holds both the database file and the
+//! contact's secret can unwrap the DEK with other tools. It protects
+//! against the contact acting early through the app, not against a
+//! contact willing to bypass it.
+
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use crate::crypto;
+use crate::database::queries::{self, EmergencyGrantRecord};
+use crate::error::{WalletError, Result};
+use super::wallet::{random_bytes, Wallet, KDF_SALT_LEN};
+
+/// Emergency grant as seen by the owner
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EmergencyGrant {
+    pub grant_id: String,
+    pub contact: String,
+    /// Time between a request and the moment the contact can unlock
+    pub wait: Duration,
+    pub created_at: Option<DateTime<Utc>>,
+    /// When the contact requested access (`None` if no request is pending)
+    pub requested_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl EmergencyGrant {
+    /// Earliest time a pending request can be used to unlock
+    pub fn available_at(&self) -> Option<DateTime<Utc>> {
+        let wait = chrono::TimeDelta::from_std(self.wait).ok()?;
+        self.requested_at.and_then(|t| t.checked_add_signed(wait))
+    }
+
+    /// True while the grant has not been revoked
+    pub fn is_active(&self) -> bool {
+        self.revoked_at.is_none()
+    }
+}
+
+/// Outcome of [`Wallet::unlock_emergency`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EmergencyUnlock {
+    /// Waiting period over, wallet unlocked
+    Unlocked,
+    /// Access has not been requested (or the owner denied the request)
+    NotRequested,
+    /// Request pending; try again after `retry_after`
+    Waiting { retry_after: Duration },
+    /// The owner revoked the grant
+    Revoked,
+    /// The secret does not match the grant
+    WrongSecret,
+}
+
+fn unwrap_grant(rec: &EmergencyGrantRecord, secret: &str) -> Option<[u8; crypto::dek::DEK_LEN]> {
+    let params = crypto::kdf::KdfParams {
+        m_cost_kib: rec.m_cost_kib,
+        t_cost: rec.t_cost,
+        p_cost: rec.p_cost,
+    };
+    let kek = zeroize::Zeroizing::new(crypto::kdf::derive_kek(secret.as_bytes(), &rec.salt, params).ok()?);
+    crypto::dek::unwrap_dek(&kek, &rec.dek_wrapped).ok()
+}
+
+fn remaining_wait(rec: &EmergencyGrantRecord, requested_at: DateTime<Utc>, now: DateTime<Utc>) -> Duration {
+    // A clock set backwards counts as no time elapsed
+    let elapsed = (now - requested_at).to_std().unwrap_or(Duration::ZERO);
+    Duration::from_secs(rec.wait_secs).saturating_sub(elapsed)
+}
+
+impl Wallet {
+    /// Give `contact` emergency access with their own `secret`. After a
+    /// request they must wait `wait` before the secret unlocks the wallet.
+    /// Returns the grant ID the contact needs. Requires an unlocked wallet.
+    pub fn add_emergency_contact(&mut self, contact: &str, secret: &str, wait: Duration) -> Result<String> {
+        if secret.is_empty() {
+            return Err(WalletError::InvalidOperation("Emergency secret must not be empty".to_string()));
+        }
+        let contact_encrypted = self.enc_value(contact)?;
+        let params = crypto::kdf::KdfParams::current();
+        let salt = random_bytes(KDF_SALT_LEN);
+        let kek = zeroize::Zeroizing::new(
+            crypto::kdf::derive_kek(secret.as_bytes(), &salt, params).map_err(WalletError::EncryptionError)?,
+        );
+        let dek_wrapped = crypto::dek::wrap_dek(&kek, self.dek()?).map_err(WalletError::EncryptionError)?;
+
+        let grant_id = crate::utils::generate_id(16);
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        queries::create_emergency_grant(conn, &EmergencyGrantRecord {
+            grant_id: grant_id.clone(),
+            contact_encrypted,
+            m_cost_kib: params.m_cost_kib,
+            t_cost: params.t_cost,
+            p_cost: params.p_cost,
+            salt,
+            dek_wrapped,
+            wait_secs: wait.as_secs(),
+            created_at: Some(Utc::now()),
+            requested_at: None,
+            revoked_at: None,
+        })?;
+
+        self.note_mutation();
+        Ok(grant_id)
+    }
+
+    /// All emergency grants, including revoked ones. Requires an unlocked
+    /// wallet; check `requested_at` to spot pending requests.
+    pub fn list_emergency_grants(&self) -> Result<Vec<EmergencyGrant>> {
+        self.ensure_unlocked()?;
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        queries::get_emergency_grants(conn)?
+            .into_iter()
+            .map(|rec| {
+                Ok(EmergencyGrant {
+                    contact: self.dec_value(&rec.contact_encrypted)?,
+                    grant_id: rec.grant_id,
+                    wait: Duration::from_secs(rec.wait_secs),
+                    created_at: rec.created_at,
+                    requested_at: rec.requested_at,
+                    revoked_at: rec.revoked_at,
+                })
+            })
+            .collect()
+    }
+
+    /// Contact side: start the waiting period. Works on a locked wallet.
+    /// Returns when the wallet can be unlocked; repeating the request does
+    /// not restart the clock.
+    pub fn request_emergency_access(&mut self, grant_id: &str, secret: &str) -> Result<DateTime<Utc>> {
+        self.request_emergency_access_at(grant_id, secret, Utc::now())
+    }
+
+    /// Owner side: reject a pending request. The grant stays usable for a
+    /// later request.
+    pub fn deny_emergency_request(&mut self, grant_id: &str) -> Result<()> {
+        self.ensure_unlocked()?;
+        let conn = self.emergency_grant(grant_id)?.1;
+        queries::set_emergency_grant_requested(conn, grant_id, None)?;
+        self.note_mutation();
+        Ok(())
+    }
+
+    /// Owner side: revoke a grant for good. The wrapped key is overwritten,
+    /// so the contact's secret stops working even on copies made later.
+    pub fn revoke_emergency_access(&mut self, grant_id: &str) -> Result<()> {
+        self.ensure_unlocked()?;
+        let conn = self.emergency_grant(grant_id)?.1;
+        queries::revoke_emergency_grant(conn, grant_id, &Utc::now())?;
+        self.note_mutation();
+        Ok(())
+    }
+
+    /// Contact side: unlock with the grant's secret once the waiting period
+    /// after the request has passed.
+    pub fn unlock_emergency(&mut self, grant_id: &str, secret: &str) -> Result<EmergencyUnlock> {
+        self.unlock_emergency_at(grant_id, secret, Utc::now())
+    }
+
+    fn emergency_grant(&self, grant_id: &str) -> Result<(EmergencyGrantRecord, &rusqlite::Connection)> {
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        let rec = queries::get_emergency_grant(conn, grant_id)?
+            .ok_or_else(|| WalletError::EmergencyGrantNotFound(grant_id.to_string()))?;
+        Ok((rec, conn))
+    }
+
+    fn request_emergency_access_at(&mut self, grant_id: &str, secret: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+        let (rec, conn) = self.emergency_grant(grant_id)?;
+        if rec.revoked_at.is_some() {
+            return Err(WalletError::InvalidOperation("Emergency grant has been revoked".to_string()));
+        }
+        if unwrap_grant(&rec, secret).is_none() {
+            return Err(WalletError::InvalidPassword);
+        }
+        let requested_at = match rec.requested_at {
+            Some(t) => t,
+            None => {
+                queries::set_emergency_grant_requested(conn, grant_id, Some(&now))?;
+                now
+            }
+        };
+        let wait = chrono::TimeDelta::from_std(Duration::from_secs(rec.wait_secs))
+            .map_err(|e| WalletError::InvalidOperation(e.to_string()))?;
+        Ok(requested_at + wait)
+    }
+
+    fn unlock_emergency_at(&mut self, grant_id: &str, secret: &str, now: DateTime<Utc>) -> Result<EmergencyUnlock> {
+        let (rec, _) = self.emergency_grant(grant_id)?;
+        if rec.revoked_at.is_some() {
+            return Ok(EmergencyUnlock::Revoked);
+        }
+        let Some(requested_at) = rec.requested_at else {
+            return Ok(EmergencyUnlock::NotRequested);
+        };
+        let retry_after = remaining_wait(&rec, requested_at, now);
+        if !retry_after.is_zero() {
+            return Ok(EmergencyUnlock::Waiting { retry_after });
+        }
+        match unwrap_grant(&rec, secret) {
+            Some(dek) => {
+                self.unlock_with_dek(dek)?;
+                Ok(EmergencyUnlock::Unlocked)
+            }
+            None => Ok(EmergencyUnlock::WrongSecret),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+    use crate::business::wallet::tests::create_test_wallet;
+
+    const DAY: Duration = Duration::from_secs(24 * 3600);
+
+    #[test]
+    fn test_emergency_access_after_waiting_period() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let item = wallet.add_item("Will", "document", false, None).unwrap();
+        let grant = wallet.add_emergency_contact("Alice", "alice-secret", DAY).unwrap();
+        let listed = wallet.list_emergency_grants().unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].contact, "Alice");
+        assert!(listed[0].is_active());
+        wallet.lock();
+
+        // Stored timestamps have whole-second precision
+        let t0 = DateTime::from_timestamp(Utc::now().timestamp(), 0).unwrap();
+        assert_eq!(wallet.unlock_emergency_at(&grant, "alice-secret", t0).unwrap(), EmergencyUnlock::NotRequested);
+        assert!(matches!(
+            wallet.request_emergency_access_at(&grant, "wrong", t0),
+            Err(WalletError::InvalidPassword)
+        ));
+        let available = wallet.request_emergency_access_at(&grant, "alice-secret", t0).unwrap();
+        assert_eq!(available, t0 + TimeDelta::days(1));
+        // Asking again does not restart the clock
+        let later = t0 + TimeDelta::hours(5);
+        assert_eq!(wallet.request_emergency_access_at(&grant, "alice-secret", later).unwrap(), available);
+
+        assert_eq!(
+            wallet.unlock_emergency_at(&grant, "alice-secret", later).unwrap(),
+            EmergencyUnlock::Waiting { retry_after: Duration::from_secs(19 * 3600) }
+        );
+        assert!(!wallet.is_unlocked());
+
+        let after = t0 + TimeDelta::days(1);
+        assert_eq!(wallet.unlock_emergency_at(&grant, "nope", after).unwrap(), EmergencyUnlock::WrongSecret);
+        assert_eq!(wallet.unlock_emergency_at(&grant, "alice-secret", after).unwrap(), EmergencyUnlock::Unlocked);
+        assert_eq!(wallet.get_item(&item).unwrap().unwrap().name, "Will");
+    }
+
+    #[test]
+    fn test_emergency_access_deny_and_revoke() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let grant = wallet.add_emergency_contact("Bob", "bob-secret", DAY).unwrap();
+        let t0 = DateTime::from_timestamp(Utc::now().timestamp(), 0).unwrap();
+        let after = t0 + TimeDelta::days(2);
+
+        wallet.request_emergency_access_at(&grant, "bob-secret", t0).unwrap();
+        assert!(wallet.list_emergency_grants().unwrap()[0].requested_at.is_some());
+        wallet.deny_emergency_request(&grant).unwrap();
+        wallet.lock();
+        assert_eq!(wallet.unlock_emergency_at(&grant, "bob-secret", after).unwrap(), EmergencyUnlock::NotRequested);
+
+        wallet.unlock("TestPassword123").unwrap();
+        wallet.request_emergency_access_at(&grant, "bob-secret", t0).unwrap();
+        wallet.revoke_emergency_access(&grant).unwrap();
+        assert!(!wallet.list_emergency_grants().unwrap()[0].is_active());
+        wallet.lock();
+        assert_eq!(wallet.unlock_emergency_at(&grant, "bob-secret", after).unwrap(), EmergencyUnlock::Revoked);
+        assert!(wallet.request_emergency_access_at(&grant, "bob-secret", after).is_err());
+        assert!(!wallet.is_unlocked());
+
+        // The wrapped key is gone, not just flagged
+        let conn = wallet.database().unwrap().connection().unwrap();
+        let rec = queries::get_emergency_grant(conn, &grant).unwrap().unwrap();
+        assert!(rec.dek_wrapped.iter().all(|b| *b == 0));
+
+        assert!(matches!(
+            wallet.unlock_emergency("missing", "x"),
+            Err(WalletError::EmergencyGrantNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,380 @@
+//! Metadata envelope
+//!
+//! By default an item's icon and timestamps and a field's change timestamp
+//! are stored in plaintext next to the encrypted name or value, which shows
+//! when records were created and edited. With metadata encryption on, these
+//! columns are sealed into an encrypted `meta` envelope on each row and the
+//! plaintext columns are cleared. The envelope is opened when items and
+//! fields are loaded, so sorting and display work on the real values.
+//!
+//! While it is on, triggers on the connection seal the metadata in the
+//! same statement that writes the row, through a SQL function that holds
+//! the DEK while the wallet is unlocked. Once the wallet locks, the function
+//! is gone and such writes fail rather than land in plaintext. The folder
+//! flag, parent, field type and sort weight stay in plaintext: tree
+//! queries, label usage counts and field ordering run in SQL on them.
+
+use rusqlite::Connection;
+use rusqlite::functions::FunctionFlags;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::crypto;
+use crate::crypto::dek::DEK_LEN;
+use crate::database::queries::{self, MetaRow, MetaRowFilter, RawField, RawItem};
+use crate::error::{WalletError, Result};
+use super::wallet::Wallet;
+
+/// Envelope format written by this version
+pub const METADATA_ENVELOPE_VERSION: i32 = 1;
+
+/// Sealed content of a `meta` column
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct MetaEnvelope {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    icon: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    created: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    changed: Option<String>,
+}
+
+impl MetaEnvelope {
+    /// Decrypt an envelope; one that does not open is ignored
+    fn open(key: &[u8; DEK_LEN], blob: &[u8]) -> Option<Self> {
+        serde_json::from_slice(&crypto::aead::open(key, blob).ok()?).ok()
+    }
+
+    fn seal(&self, key: &[u8; DEK_LEN]) -> Result<Vec<u8>> {
+        let json = serde_json::to_string(self)
+            .map_err(|e| WalletError::json("Invalid metadata envelope", e))?;
+        crypto::aead::seal(key, json.as_bytes()).map_err(WalletError::EncryptionError)
+    }
+
+    /// Lay plaintext metadata, which is newer, over the envelope
+    fn overlay(&mut self, icon: Option<String>, created: Option<String>, changed: Option<String>) {
+        if let Some(icon) = icon.filter(|icon| !icon.is_empty()) {
+            self.icon = Some(icon);
+        }
+        if created.is_some() {
+            self.created = created;
+        }
+        if changed.is_some() {
+            self.changed = changed;
+        }
+    }
+}
+
+/// Register `iw_seal_meta(meta, icon, created, changed)`, which returns the
+/// envelope `meta` with the plaintext laid over it, sealed again, and
+/// `iw_meta_changed(meta)`, the change timestamp in an envelope. Both hold
+/// their own copy of the DEK.
+fn register_meta_functions(conn: &Connection, key: Zeroizing<[u8; DEK_LEN]>) -> Result<()> {
+    let seal_key = key.clone();
+    conn.create_scalar_function("iw_seal_meta", 4, FunctionFlags::SQLITE_UTF8, move |ctx| {
+        let mut envelope = ctx.get_raw(0).as_blob_or_null().ok().flatten()
+            .and_then(|blob| MetaEnvelope::open(&seal_key, blob))
+            .unwrap_or_default();
+        envelope.overlay(ctx.get(1)?, ctx.get(2)?, ctx.get(3)?);
+        envelope.seal(&seal_key).map_err(|e| rusqlite::Error::UserFunctionError(e.into()))
+    })?;
+    conn.create_scalar_function(
+        "iw_meta_changed",
+        1,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        move |ctx| {
+            Ok(ctx.get_raw(0).as_blob_or_null().ok().flatten()
+                .and_then(|blob| MetaEnvelope::open(&key, blob))
+                .and_then(|envelope| envelope.changed))
+        },
+    )?;
+    Ok(())
+}
+
+impl Wallet {
+    /// Whether item and field metadata is stored in encrypted envelopes
+    pub fn metadata_encryption_enabled(&self) -> Result<bool> {
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        Ok(queries::get_meta_envelope_version(conn)? > 0)
+    }
+
+    /// Turn metadata encryption on and seal every record's metadata.
+    /// Returns the number of rows sealed.
+    pub fn enable_metadata_encryption(&mut self) -> Result<u32> {
+        self.ensure_unlocked()?;
+        {
+            let conn = self.db.as_ref()
+                .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+                .connection()?;
+            queries::set_meta_envelope_version(conn, METADATA_ENVELOPE_VERSION)?;
+            queries::create_meta_seal_triggers(conn)?;
+        }
+        self.seal_metadata()
+    }
+
+    /// Turn metadata encryption off and write every envelope back to the
+    /// plaintext columns. Returns the number of rows unsealed.
+    pub fn disable_metadata_encryption(&mut self) -> Result<u32> {
+        self.ensure_unlocked()?;
+        let rows = {
+            let conn = self.db.as_ref()
+                .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+                .connection()?;
+            queries::drop_meta_seal_triggers(conn)?;
+            let mut rows = queries::get_item_meta_rows(conn, MetaRowFilter::Sealed)?;
+            rows.extend(queries::get_field_meta_rows(conn, MetaRowFilter::Sealed)?);
+            rows
+        };
+
+        let rows: Vec<MetaRow> = rows.into_iter()
+            .map(|row| {
+                let envelope = self.merged_envelope(&row);
+                let item = row.field_id.is_none();
+                MetaRow {
+                    icon: item.then(|| envelope.icon.unwrap_or_default()),
+                    create_timestamp: envelope.created.filter(|_| item),
+                    change_timestamp: envelope.changed,
+                    meta: None,
+                    ..row
+                }
+            })
+            .collect();
+        let count = match self.write_meta_rows(&rows) {
+            Ok(count) => count,
+            Err(e) => {
+                let _ = self.install_meta_sealing();
+                return Err(e);
+            }
+        };
+
+        let conn = self.db.as_ref().unwrap().connection()?;
+        queries::set_meta_envelope_version(conn, 0)?;
+        Ok(count)
+    }
+
+    /// Register the metadata SQL functions for the DEK just unlocked and,
+    /// with metadata encryption on, the triggers that seal every write.
+    /// Rows left in plaintext by versions without the triggers are sealed
+    /// right away.
+    pub(crate) fn install_meta_sealing(&mut self) -> Result<()> {
+        let key = Zeroizing::new(*self.dek()?);
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        register_meta_functions(conn, key)?;
+        if queries::get_meta_envelope_version(conn)? > 0 {
+            queries::create_meta_seal_triggers(conn)?;
+            self.seal_metadata()?;
+        }
+        Ok(())
+    }
+
+    /// Remove the metadata SQL functions, and with them their copy of the
+    /// DEK. The triggers stay, so writes that need sealing fail until the
+    /// next unlock.
+    pub(crate) fn remove_meta_sealing(&self) {
+        if let Some(conn) = self.db.as_ref().and_then(|db| db.connection().ok()) {
+            let _ = conn.remove_function("iw_seal_meta", 4);
+            let _ = conn.remove_function("iw_meta_changed", 1);
+        }
+    }
+
+    /// Seal all plaintext metadata into the envelopes, for turning metadata
+    /// encryption on. Does nothing while it is off or the wallet is locked.
+    /// Returns the number of rows sealed.
+    pub(crate) fn seal_metadata(&mut self) -> Result<u32> {
+        if !self.is_unlocked() || !self.metadata_encryption_enabled()? {
+            return Ok(0);
+        }
+        let rows = {
+            let conn = self.db.as_ref().unwrap().connection()?;
+            let mut rows = queries::get_item_meta_rows(conn, MetaRowFilter::Unsealed)?;
+            rows.extend(queries::get_field_meta_rows(conn, MetaRowFilter::Unsealed)?);
+            rows
+        };
+        if rows.is_empty() {
+            return Ok(0);
+        }
+
+        let mut sealed = Vec::with_capacity(rows.len());
+        for row in rows {
+            let envelope = self.merged_envelope(&row);
+            sealed.push(MetaRow {
+                icon: row.field_id.is_none().then(String::new),
+                create_timestamp: None,
+                change_timestamp: None,
+                meta: Some(envelope.seal(self.dek()?)?),
+                ..row
+            });
+        }
+        self.write_meta_rows(&sealed)
+    }
+
+    /// Fill an item's missing plaintext metadata from its envelope
+    pub(crate) fn open_item_envelope(&self, raw: &mut RawItem) {
+        let Some(envelope) = raw.meta.as_deref().and_then(|blob| self.open_envelope(blob)) else {
+            return;
+        };
+        if raw.icon.is_empty() {
+            raw.icon = envelope.icon.unwrap_or_default();
+        }
+        if raw.create_timestamp.is_none() {
+            raw.create_timestamp = envelope.created;
+        }
+        if raw.change_timestamp.is_none() {
+            raw.change_timestamp = envelope.changed;
+        }
+    }
+
+    /// Fill a field's missing plaintext change timestamp from its envelope
+    pub(crate) fn open_field_envelope(&self, raw: &mut RawField) {
+        if raw.change_timestamp.is_some() {
+            return;
+        }
+        if let Some(envelope) = raw.meta.as_deref().and_then(|blob| self.open_envelope(blob)) {
+            raw.change_timestamp = envelope.changed;
+        }
+    }
+
+    /// Decrypt an envelope; one that does not open is ignored
+    fn open_envelope(&self, blob: &[u8]) -> Option<MetaEnvelope> {
+        MetaEnvelope::open(self.dek().ok()?, blob)
+    }
+
+    /// The row's envelope with its plaintext metadata, which is newer,
+    /// laid over it
+    fn merged_envelope(&self, row: &MetaRow) -> MetaEnvelope {
+        let mut envelope = row.meta.as_deref()
+            .and_then(|blob| self.open_envelope(blob))
+            .unwrap_or_default();
+        envelope.overlay(row.icon.clone(), row.create_timestamp.clone(), row.change_timestamp.clone());
+        envelope
+    }
+
+    /// Write metadata rows in one transaction
+    fn write_meta_rows(&mut self, rows: &[MetaRow]) -> Result<u32> {
+        if rows.is_empty() {
+            return Ok(0);
+        }
+        let db = self.db.as_mut()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?;
+        db.begin_transaction()?;
+        let pass = (|| -> Result<()> {
+            let conn = db.connection()?;
+            for row in rows {
+                queries::write_meta_row(conn, row)?;
+            }
+            Ok(())
+        })();
+        match pass {
+            Ok(()) => db.commit_transaction()?,
+            Err(e) => {
+                db.rollback_transaction()?;
+                return Err(e);
+            }
+        }
+        let _ = db.checkpoint();
+        Ok(rows.len() as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::business::wallet::tests::create_test_wallet;
+    use crate::CompactOptions;
+
+    fn plaintext_item_meta(wallet: &crate::Wallet, item_id: &str) -> (String, Option<String>, Option<String>) {
+        let conn = wallet.db.as_ref().unwrap().connection().unwrap();
+        conn.query_row(
+            "SELECT COALESCE(icon, ''), create_timestamp, change_timestamp FROM nswallet_items WHERE item_id = ?",
+            [item_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        ).unwrap()
+    }
+
+    #[test]
+    fn test_metadata_encryption_round_trip() {
+        let (mut wallet, _dir) = create_test_wallet();
+        let item_id = wallet.add_item("Bank", "bank", false, None).unwrap();
+        wallet.add_field(&item_id, "PASS", "secret", None).unwrap();
+        let before = wallet.get_item(&item_id).unwrap().unwrap();
+
+        assert!(!wallet.metadata_encryption_enabled().unwrap());
+        assert!(wallet.enable_metadata_encryption().unwrap() >= 2);
+        assert!(wallet.metadata_encryption_enabled().unwrap());
+        assert_eq!(plaintext_item_meta(&wallet, &item_id), (String::new(), None, None));
+
+        // Values come back from the envelope after a fresh load
+        wallet.clear_caches();
+        let item = wallet.get_item(&item_id).unwrap().unwrap();
+        assert_eq!(item.icon, "bank");
+        assert_eq!(item.create_timestamp.timestamp(), before.create_timestamp.timestamp());
+        let fields = wallet.get_fields_by_item(&item_id).unwrap();
+        assert_eq!(fields.len(), 1);
+
+        // Later writes are sealed as well
+        wallet.update_item_icon(&item_id, "card").unwrap();
+        assert_eq!(plaintext_item_meta(&wallet, &item_id), (String::new(), None, None));
+        wallet.clear_caches();
+        assert_eq!(wallet.get_item(&item_id).unwrap().unwrap().icon, "card");
+
+        assert!(wallet.disable_metadata_encryption().unwrap() >= 2);
+        assert!(!wallet.metadata_encryption_enabled().unwrap());
+        let (icon, created, changed) = plaintext_item_meta(&wallet, &item_id);
+        assert_eq!(icon, "card");
+        assert!(created.is_some() && changed.is_some());
+    }
+
+    #[test]
+    fn test_compact_with_age_uses_sealed_timestamps() {
+        let (mut wallet, _dir) = create_test_wallet();
+        let item_id = wallet.add_item("Old", "doc", false, None).unwrap();
+        wallet.delete_item(&item_id).unwrap();
+        wallet.enable_metadata_encryption().unwrap();
+
+        // Deleted just now: too recent for a one-day cutoff
+        let options = CompactOptions {
+            older_than: Some(std::time::Duration::from_secs(86400)),
+            ..CompactOptions::default()
+        };
+        let result = wallet.compact_with(&options).unwrap();
+        assert_eq!(result.items, 0);
+        assert_eq!(plaintext_item_meta(&wallet, &item_id), (String::new(), None, None));
+        assert_eq!(wallet.get_deleted_items().unwrap().len(), 1);
+
+        // Backdated, through the sealing trigger: now old enough
+        {
+            let conn = wallet.db.as_ref().unwrap().connection().unwrap();
+            conn.execute(
+                "UPDATE nswallet_items SET change_timestamp = '2020-01-01 00:00:00' WHERE item_id = ?",
+                [&item_id],
+            ).unwrap();
+        }
+        assert_eq!(plaintext_item_meta(&wallet, &item_id), (String::new(), None, None));
+        assert_eq!(wallet.compact_with(&options).unwrap().items, 1);
+        assert!(wallet.get_deleted_items().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_sealing_needs_the_unlocked_wallet() {
+        let (mut wallet, _dir) = create_test_wallet();
+        let item_id = wallet.add_item("Bank", "bank", false, None).unwrap();
+        wallet.enable_metadata_encryption().unwrap();
+
+        // Without the DEK a metadata write fails instead of landing in plaintext
+        wallet.lock();
+        {
+            let conn = wallet.db.as_ref().unwrap().connection().unwrap();
+            assert!(conn.execute("UPDATE nswallet_items SET icon = 'card' WHERE item_id = ?", [&item_id]).is_err());
+        }
+        assert_eq!(plaintext_item_meta(&wallet, &item_id), (String::new(), None, None));
+
+        assert!(wallet.unlock("TestPassword123").unwrap());
+        wallet.update_item_icon(&item_id, "card").unwrap();
+        assert_eq!(plaintext_item_meta(&wallet, &item_id), (String::new(), None, None));
+        wallet.clear_caches();
+        assert_eq!(wallet.get_item(&item_id).unwrap().unwrap().icon, "card");
+    }
+}
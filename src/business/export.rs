@@ -1,6 +1,20 @@
 //! Multi-format export functionality (PDF, CSV, JSON, XML)
+//!
+//! Every document carries the body of each secure note as a NOTE field of
+//! its item.
 
-use crate::error::Result;
+use std::collections::HashSet;
+use std::io::Write;
+use std::ops::ControlFlow;
+
+use zeroize::Zeroizing;
+
+use crate::ROOT_ID;
+use crate::crypto;
+use crate::database::SearchOptions;
+use crate::error::{Result, WalletError};
+use crate::export::{CsvExportOptions, ExportFormat, ExportOptions, FieldExportFormat, FieldMasking};
+use crate::utils::CancelToken;
 use super::wallet::Wallet;
 
 impl Wallet {
@@ -11,7 +25,7 @@ impl Wallet {
         self.ensure_unlocked()?;
 
         let items = self.get_items()?.to_vec();
-        let fields = self.get_fields()?.to_vec();
+        let fields = self.fields_with_secure_notes()?;
 
         crate::export::generate_pdf(&items, &fields)
     }
@@ -23,11 +37,24 @@ impl Wallet {
         self.ensure_unlocked()?;
 
         let items = self.get_items()?.to_vec();
-        let fields = self.get_fields()?.to_vec();
+        let fields = self.fields_with_secure_notes()?;
 
         crate::export::generate_csv(&items, &fields)
     }
 
+    /// Export the wallet's entries as a CSV with the columns and password
+    /// masking of `csv`, in the wallet's language when `localized`.
+    pub fn export_csv_columns(&mut self, csv: &CsvExportOptions, localized: bool) -> Result<Vec<u8>> {
+        self.ensure_unlocked()?;
+        let translations = if localized { Some(self.translations()?) } else { None };
+
+        let items = self.get_items()?.to_vec();
+        let fields = self.fields_with_secure_notes()?;
+
+        let options = ExportOptions { translations: translations.as_ref(), cancel: None };
+        crate::export::generate_csv_columns(&items, &fields, csv, &options)
+    }
+
     /// Export all wallet data as a JSON document.
     ///
     /// Returns pretty-printed JSON as UTF-8 bytes.
@@ -35,7 +62,7 @@ impl Wallet {
         self.ensure_unlocked()?;
 
         let items = self.get_items()?.to_vec();
-        let fields = self.get_fields()?.to_vec();
+        let fields = self.fields_with_secure_notes()?;
 
         crate::export::generate_json(&items, &fields)
     }
@@ -47,15 +74,117 @@ impl Wallet {
         self.ensure_unlocked()?;
 
         let items = self.get_items()?.to_vec();
-        let fields = self.get_fields()?.to_vec();
+        let fields = self.fields_with_secure_notes()?;
 
         crate::export::generate_xml(&items, &fields)
     }
+
+    /// Export all wallet data in `format`, with PDF and CSV documents in
+    /// the wallet's language: localized column titles and dates. JSON and
+    /// XML are the same as from the unlocalized exports.
+    pub fn export_localized(&mut self, format: ExportFormat) -> Result<Vec<u8>> {
+        self.ensure_unlocked()?;
+        let translations = self.translations()?;
+
+        let items = self.get_items()?.to_vec();
+        let fields = self.fields_with_secure_notes()?;
+
+        format.generate_with(&items, &fields, &ExportOptions::localized(&translations))
+    }
+
+    /// [`Wallet::export_localized`] that stops with `Cancelled` once
+    /// `cancel` trips. The token is checked after loading the wallet and
+    /// between entries of the document.
+    pub fn export_localized_with_cancel(&mut self, format: ExportFormat, cancel: &CancelToken) -> Result<Vec<u8>> {
+        self.ensure_unlocked()?;
+        cancel.check()?;
+        let translations = self.translations()?;
+
+        let items = self.get_items()?.to_vec();
+        cancel.check()?;
+        let fields = self.fields_with_secure_notes()?;
+
+        let options = ExportOptions { cancel: Some(cancel), ..ExportOptions::localized(&translations) };
+        format.generate_with(&items, &fields, &options)
+    }
+
+    /// Export all wallet data in `format`, localized as in
+    /// [`Wallet::export_localized`], encrypted to the age `recipients`
+    /// ("age1..." public keys). The result is an age file that any of the
+    /// matching secret identities opens, e.g. with
+    /// [`crypto::recipients::decrypt`](crate::crypto::recipients::decrypt).
+    pub fn export_to_recipients(&mut self, format: ExportFormat, recipients: &[&str]) -> Result<Vec<u8>> {
+        self.ensure_unlocked()?;
+        crypto::recipients::validate_recipients(recipients).map_err(WalletError::ValidationError)?;
+        let document = Zeroizing::new(self.export_localized(format)?);
+        crypto::recipients::encrypt(&document, recipients).map_err(WalletError::EncryptionError)
+    }
+
+    /// Export the fields of one type across the wallet, e.g. all LINK
+    /// fields as browser bookmarks or all MAIL addresses as a CSV. Values
+    /// are masked as `masking` asks; bookmarks must be unmasked links.
+    pub fn export_fields_of_type(
+        &mut self,
+        field_type: &str,
+        format: FieldExportFormat,
+        masking: FieldMasking,
+    ) -> Result<Vec<u8>> {
+        self.ensure_unlocked()?;
+
+        let items = self.get_items()?.to_vec();
+        let fields = self.fields_with_secure_notes()?;
+
+        crate::export::generate_field_export(&items, &fields, field_type, format, masking)
+    }
+
+    /// Run a search and export the matched items, with all their fields,
+    /// to `writer` in `format`. The folders above each match are included
+    /// (without fields) so paths and parent links resolve. Returns the
+    /// number of matched items; nothing is written when there are none.
+    pub fn export_search_results<W: Write>(
+        &mut self,
+        query: &str,
+        options: &SearchOptions,
+        format: ExportFormat,
+        writer: &mut W,
+    ) -> Result<usize> {
+        let mut matched = HashSet::new();
+        self.search_streaming(query, options, |result| {
+            matched.insert(result.item.item_id);
+            ControlFlow::Continue(())
+        })?;
+        if matched.is_empty() {
+            return Ok(0);
+        }
+
+        let all_items = self.get_items()?.to_vec();
+        let mut included = matched.clone();
+        for item in all_items.iter().filter(|i| matched.contains(&i.item_id)) {
+            let mut parent = item.parent_id.as_deref();
+            while let Some(pid) = parent {
+                if pid == ROOT_ID || !included.insert(pid.to_string()) {
+                    break;
+                }
+                parent = all_items.iter()
+                    .find(|i| i.item_id == pid)
+                    .and_then(|i| i.parent_id.as_deref());
+            }
+        }
+        let items: Vec<_> = all_items.into_iter()
+            .filter(|i| included.contains(&i.item_id))
+            .collect();
+        let fields: Vec<_> = self.fields_with_secure_notes()?.into_iter()
+            .filter(|f| matched.contains(&f.item_id))
+            .collect();
+
+        writer.write_all(&format.generate(&items, &fields)?)?;
+        Ok(matched.len())
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::Wallet;
+    use crate::{ExportFormat, FieldExportFormat, FieldMasking, SearchOptions, Wallet};
     use crate::business::wallet::tests::create_test_wallet;
     use tempfile::TempDir;
 
@@ -72,6 +201,25 @@ mod tests {
         (wallet, temp)
     }
 
+    #[test]
+    fn export_to_recipients_opens_with_identity() {
+        use crate::crypto::recipients::{decrypt, Identity};
+        let (mut wallet, _t) = populated();
+        let device = Identity::generate();
+        let sealed = wallet.export_to_recipients(ExportFormat::Json, &[&device.recipient()]).unwrap();
+        assert!(!sealed.windows(7).any(|w| w == b"s3cr3t!"));
+        let json = decrypt(&sealed, &device).unwrap();
+        assert!(String::from_utf8_lossy(&json).contains("s3cr3t!"));
+        assert!(matches!(
+            wallet.export_to_recipients(ExportFormat::Json, &[]),
+            Err(crate::WalletError::ValidationError(_))
+        ));
+        assert!(matches!(
+            wallet.export_to_recipients(ExportFormat::Csv, &["ssh-ed25519 AAAA"]),
+            Err(crate::WalletError::ValidationError(_))
+        ));
+    }
+
     #[test]
     fn export_pdf_produces_pdf_bytes() {
         let (mut wallet, _t) = populated();
@@ -109,6 +257,103 @@ mod tests {
         assert!(!xml.contains("a<b & c"), "raw specials must not appear unescaped");
     }
 
+    #[test]
+    fn exports_carry_secure_note_bodies() {
+        let (mut wallet, _t) = populated();
+        let note = wallet.add_secure_note("Licence", None).unwrap();
+        wallet.set_secure_note_body(&note, "LICENCE-KEY-1234").unwrap();
+        for document in [wallet.export_csv().unwrap(), wallet.export_json().unwrap(), wallet.export_xml().unwrap()] {
+            assert!(String::from_utf8(document).unwrap().contains("LICENCE-KEY-1234"));
+        }
+
+        let mut out = Vec::new();
+        assert_eq!(wallet.export_search_results("Licence", &SearchOptions::default(), ExportFormat::Csv, &mut out).unwrap(), 1);
+        assert!(String::from_utf8(out).unwrap().contains("LICENCE-KEY-1234"));
+    }
+
+    #[test]
+    fn export_search_results_only_contains_matches() {
+        let (mut wallet, _t) = populated();
+        let mut out = Vec::new();
+        let count = wallet.export_search_results("example", &SearchOptions::default(), ExportFormat::Csv, &mut out).unwrap();
+        assert_eq!(count, 1);
+        let csv = String::from_utf8(out).unwrap();
+        assert!(csv.contains("user@example.com"));
+        // Every field of the match, not only the matching one
+        assert!(csv.contains("s3cr3t!"));
+        // Its folder is there for context, the unrelated note is not
+        assert!(csv.contains(",Banking,,true,"));
+        assert!(!csv.contains("a<b"));
+
+        let mut out = Vec::new();
+        wallet.export_search_results("example", &SearchOptions::default(), ExportFormat::Json, &mut out).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json["items"].as_array().unwrap().len(), 2);
+
+        let mut out = Vec::new();
+        let count = wallet.export_search_results("nomatch", &SearchOptions::default(), ExportFormat::Xml, &mut out).unwrap();
+        assert_eq!(count, 0);
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn export_localized_uses_wallet_language() {
+        let temp = TempDir::new().unwrap();
+        let mut wallet = Wallet::create(temp.path(), "TestPassword123", "de").unwrap();
+        let item = wallet.add_item("My Bank", "bank", false, None).unwrap();
+        wallet.add_field(&item, "MAIL", "user@example.com", None).unwrap();
+
+        let csv = String::from_utf8(wallet.export_localized(ExportFormat::Csv).unwrap()).unwrap();
+        let header = csv.lines().next().unwrap();
+        assert!(header.starts_with("Eintrags-ID,"), "{header}");
+        // German dates are day.month.year
+        let row = csv.lines().find(|l| l.contains("user@example.com")).unwrap();
+        let today = chrono::Utc::now().format("%d.%m.%Y").to_string();
+        assert!(row.contains(&today), "{row}");
+        // The unlocalized export keeps the machine header
+        let plain = String::from_utf8(wallet.export_csv().unwrap()).unwrap();
+        assert!(plain.starts_with("item_id,item_name,"));
+
+        let pdf = wallet.export_localized(ExportFormat::Pdf).unwrap();
+        assert_eq!(&pdf[..4], b"%PDF");
+    }
+
+    #[test]
+    fn export_stops_when_cancelled() {
+        let (mut wallet, _t) = populated();
+        let cancel = crate::CancelToken::new();
+        let csv = wallet.export_localized_with_cancel(ExportFormat::Csv, &cancel).unwrap();
+        assert_eq!(csv, wallet.export_localized(ExportFormat::Csv).unwrap());
+        cancel.cancel();
+        for format in [ExportFormat::Csv, ExportFormat::Json, ExportFormat::Pdf] {
+            assert!(matches!(
+                wallet.export_localized_with_cancel(format, &cancel),
+                Err(crate::WalletError::Cancelled)
+            ));
+        }
+    }
+
+    #[test]
+    fn export_fields_of_type_slices_the_wallet() {
+        let (mut wallet, _t) = populated();
+        let other = wallet.add_item("Shop", "document", false, None).unwrap();
+        wallet.add_field(&other, "MAIL", "shop@example.com", None).unwrap();
+
+        let csv = String::from_utf8(
+            wallet.export_fields_of_type("MAIL", FieldExportFormat::Csv, FieldMasking::None).unwrap()
+        ).unwrap();
+        assert_eq!(csv.lines().count(), 3);
+        assert!(csv.contains("My Bank,Banking,"));
+        assert!(csv.contains("shop@example.com"));
+        assert!(!csv.contains("s3cr3t!"));
+
+        let json: serde_json::Value = serde_json::from_slice(
+            &wallet.export_fields_of_type("PASS", FieldExportFormat::Json, FieldMasking::Partial).unwrap()
+        ).unwrap();
+        assert_eq!(json[0]["item_name"], "My Bank");
+        assert_eq!(json[0]["value"], "•••••••");
+    }
+
     #[test]
     fn exports_require_unlock() {
         let (mut wallet, _t) = create_test_wallet();
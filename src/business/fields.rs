@@ -3,12 +3,14 @@
 //! This module provides field management operations for the Wallet.
 
 use std::collections::HashMap;
+use std::time::Instant;
 
 use chrono::Utc;
 use crate::error::{WalletError, Result};
 use crate::database::{IWField, FieldValueUsage, queries};
 use crate::database::queries::parse_timestamp;
-use crate::utils::generate_field_id;
+use crate::utils::ValueType;
+use super::ids::IdKind;
 use super::wallet::Wallet;
 
 impl Wallet {
@@ -19,8 +21,13 @@ impl Wallet {
         Ok(self.fields_cache.as_ref().unwrap())
     }
 
-    /// Get fields for a specific item
+    /// Get fields for a specific item. Fails with `FolderLocked` for an
+    /// item below a PIN-protected folder not opened with `unlock_folder`.
     pub fn get_fields_by_item(&mut self, item_id: &str) -> Result<Vec<IWField>> {
+        self.ensure_unlocked()?;
+        if let Some(folder_id) = self.protecting_folder(item_id)? {
+            self.folder_key(&folder_id)?;
+        }
         let fields = self.get_fields()?;
         let mut result: Vec<IWField> = fields
             .iter()
@@ -70,6 +77,7 @@ impl Wallet {
         }
 
         // Ensure labels are loaded first
+        let started = Instant::now();
         self.load_labels_if_needed()?;
         self.ensure_unlocked()?;
 
@@ -81,18 +89,31 @@ impl Wallet {
         };
 
         let labels = self.labels_cache.as_ref().unwrap();
+        // Fields can outlive their label (undeleted fields, restored
+        // backups); show the deleted label's metadata rather than "Unknown".
+        let deleted_labels = if raw_fields.iter().any(|f| !labels.contains_key(&f.field_type)) {
+            self.load_deleted_labels()?
+        } else {
+            HashMap::new()
+        };
         let mut fields = Vec::with_capacity(raw_fields.len());
 
-        for raw in raw_fields {
+        for mut raw in raw_fields {
+            self.open_field_envelope(&mut raw);
             // NULL value columns arrive as empty blobs (COALESCE in the
             // query); they carry no ciphertext, so the value is empty.
+            // Undecryptable rows are skipped; see `get_undecryptable_records`.
             let value = if raw.value_encrypted.is_empty() {
                 String::new()
             } else {
-                self.dec_value(&raw.value_encrypted)?
+                match self.dec_field_value(&raw.value_encrypted) {
+                    Ok(v) => v,
+                    Err(_) => continue,
+                }
             };
 
-            let label = labels.get(&raw.field_type);
+            let label = labels.get(&raw.field_type)
+                .or_else(|| deleted_labels.get(&raw.field_type));
             let (label_name, icon, value_type) = match label {
                 Some(l) => (l.name.clone(), l.icon.clone(), l.value_type.clone()),
                 None => ("Unknown".to_string(), "unknown".to_string(), "text".to_string()),
@@ -125,16 +146,45 @@ impl Wallet {
         }
 
         self.fields_cache = Some(fields);
+        self.record_cache_warm(started);
         Ok(())
     }
 
+    /// Enable or disable strict value validation. When on, `add_field` and
+    /// `update_field` reject values that do not match the label's value type
+    /// (see [`ValueType::validate`]). Off by default.
+    pub fn set_strict_validation(&mut self, strict: bool) {
+        self.strict_validation = strict;
+    }
+
+    /// Whether strict value validation is enabled
+    pub fn strict_validation(&self) -> bool {
+        self.strict_validation
+    }
+
+    /// Validate `value` against the value type of the `field_type` label.
+    /// No-op unless strict mode is on. Unknown labels validate as free text.
+    pub(crate) fn validate_field_value(&mut self, field_type: &str, value: &str) -> Result<()> {
+        if !self.strict_validation {
+            return Ok(());
+        }
+        self.load_labels_if_needed()?;
+        let value_type = self.labels_cache.as_ref()
+            .and_then(|labels| labels.get(field_type))
+            .map(|l| l.value_type.as_str())
+            .unwrap_or("text");
+        ValueType::for_field(field_type, value_type).validate(value)
+    }
+
     /// Add a new field to an item
     pub fn add_field(&mut self, item_id: &str, field_type: &str, value: &str, sort_weight: Option<i32>) -> Result<String> {
         self.ensure_unlocked()?;
+        self.validate_field_value(field_type, value)?;
+        self.ensure_item_editable(item_id)?;
 
-        let field_id = generate_field_id();
+        let field_id = self.unique_id(IdKind::Field, &[])?;
 
-        let encrypted_value = self.enc_value(value)?;
+        let encrypted_value = self.enc_field_value(item_id, value)?;
 
         let conn = self.db.as_ref()
             .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
@@ -149,17 +199,39 @@ impl Wallet {
         queries::create_field(conn, item_id, &field_id, field_type, &encrypted_value, weight)?;
 
         self.fields_cache = None;
+        self.note_mutation();
         Ok(field_id)
     }
 
     /// Update a field's value. Soft-deletes the old field (preserving its value in the deleted pool)
     /// and creates a new field with the updated value. Returns the new field_id.
+    ///
+    /// PASS values are checked for reuse according to the
+    /// [password reuse policy](Wallet::set_password_reuse_policy).
     pub fn update_field(&mut self, field_id: &str, value: &str, sort_weight: Option<i32>) -> Result<String> {
+        self.update_field_checked(field_id, value, sort_weight).map(|u| u.field_id)
+    }
+
+    /// `update_field` without the password reuse check
+    pub(crate) fn update_field_unchecked(&mut self, field_id: &str, value: &str, sort_weight: Option<i32>) -> Result<String> {
         self.ensure_unlocked()?;
 
-        // Encrypt the new value up front (immutable borrow of the DEK) before
-        // taking the connection.
-        let encrypted_value = self.enc_value(value)?;
+        let (item_id, field_type) = {
+            let conn = self.db.as_ref()
+                .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+                .connection()?;
+            let field = queries::get_field_raw_by_id(conn, field_id)?
+                .ok_or_else(|| WalletError::FieldNotFound(field_id.to_string()))?;
+            (field.item_id, field.field_type)
+        };
+        self.validate_field_value(&field_type, value)?;
+
+        // Generate the new version's field_id
+        let new_field_id = self.unique_id(IdKind::Field, &[])?;
+
+        // Encrypt the new value up front (immutable borrow of the keys)
+        // before taking the connection.
+        let encrypted_value = self.enc_field_value(&item_id, value)?;
 
         let conn = self.db.as_ref()
             .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
@@ -168,17 +240,17 @@ impl Wallet {
         // Fetch old field from DB
         let old_field = queries::get_field_raw_by_id(conn, field_id)?
             .ok_or_else(|| WalletError::FieldNotFound(field_id.to_string()))?;
+        if queries::is_item_locked(conn, &old_field.item_id)? {
+            return Err(WalletError::ItemLocked(old_field.item_id));
+        }
 
         // If PASS type: copy old encrypted bytes directly to OLDP. Both fields
-        // are encrypted under the same DEK, so the ciphertext is reusable as-is.
+        // are encrypted under the same key, so the ciphertext is reusable as-is.
         if old_field.field_type == "PASS"
             && let Some(oldp_field_id) = queries::get_oldp_field_id(conn, &old_field.item_id)? {
                 queries::update_field_value_only(conn, &old_field.item_id, &oldp_field_id, &old_field.value_encrypted)?;
             }
 
-        // Generate new field_id
-        let new_field_id = generate_field_id();
-
         // Determine sort_weight: use explicit param if provided, else preserve old
         let weight = sort_weight.unwrap_or(old_field.sort_weight.unwrap_or(0));
 
@@ -187,13 +259,17 @@ impl Wallet {
 
         // Create new field
         queries::create_field(conn, &old_field.item_id, &new_field_id, &old_field.field_type, &encrypted_value, weight)?;
+        queries::follow_primary_field(conn, &old_field.item_id, field_id, &new_field_id)?;
 
+        self.items_cache = None;
         self.fields_cache = None;
+        self.note_mutation();
         Ok(new_field_id)
     }
 
     /// Delete a field
     pub fn delete_field(&mut self, item_id: &str, field_id: &str) -> Result<()> {
+        self.ensure_item_editable(item_id)?;
         let conn = self.db.as_ref()
             .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
             .connection()?;
@@ -201,6 +277,7 @@ impl Wallet {
         queries::delete_field(conn, item_id, field_id)?;
 
         self.fields_cache = None;
+        self.note_mutation();
         Ok(())
     }
 
@@ -219,15 +296,24 @@ impl Wallet {
         };
 
         let labels = self.labels_cache.as_ref().unwrap();
+        // Fields can outlive their label (undeleted fields, restored
+        // backups); show the deleted label's metadata rather than "Unknown".
+        let deleted_labels = if raw_fields.iter().any(|f| !labels.contains_key(&f.field_type)) {
+            self.load_deleted_labels()?
+        } else {
+            HashMap::new()
+        };
         let mut fields = Vec::with_capacity(raw_fields.len());
 
-        for raw in raw_fields {
-            let value = match self.dec_value(&raw.value_encrypted) {
+        for mut raw in raw_fields {
+            self.open_field_envelope(&mut raw);
+            let value = match self.dec_field_value(&raw.value_encrypted) {
                 Ok(v) => v,
                 Err(_) => continue,
             };
 
-            let label = labels.get(&raw.field_type);
+            let label = labels.get(&raw.field_type)
+                .or_else(|| deleted_labels.get(&raw.field_type));
             let (label_name, icon, value_type) = match label {
                 Some(l) => (l.name.clone(), l.icon.clone(), l.value_type.clone()),
                 None => ("Unknown".to_string(), "unknown".to_string(), "text".to_string()),
@@ -264,6 +350,7 @@ impl Wallet {
     /// Restore a soft-deleted field
     pub fn undelete_field(&mut self, item_id: &str, field_id: &str) -> Result<()> {
         self.ensure_unlocked()?;
+        self.ensure_item_editable(item_id)?;
 
         let conn = self.db.as_ref()
             .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
@@ -272,6 +359,7 @@ impl Wallet {
         queries::undelete_field(conn, item_id, field_id)?;
 
         self.fields_cache = None;
+        self.note_mutation();
         Ok(())
     }
 
@@ -279,13 +367,14 @@ impl Wallet {
     pub fn copy_field(&mut self, source_item_id: &str, field_id: &str, target_item_id: &str) -> Result<String> {
         let fields = self.get_fields_by_item(source_item_id)?;
         let field = fields.iter().find(|f| f.field_id == field_id)
-            .ok_or_else(|| WalletError::InvalidOperation("Field not found".to_string()))?;
+            .ok_or_else(|| WalletError::FieldNotFound(field_id.to_string()))?;
 
         self.add_field(target_item_id, &field.field_type, &field.value, None)
     }
 
     /// Move a field to another item
     pub fn move_field(&mut self, source_item_id: &str, field_id: &str, target_item_id: &str) -> Result<()> {
+        self.ensure_item_editable(source_item_id)?;
         self.copy_field(source_item_id, field_id, target_item_id)?;
         self.delete_field(source_item_id, field_id)?;
         Ok(())
@@ -298,10 +387,7 @@ impl Wallet {
 /// legacy / hand-typed values still resolve. Unparseable input
 /// returns `(false, false)`.
 pub(crate) fn check_expiry(date_str: &str) -> (bool, bool) {
-    let date = chrono::NaiveDate::parse_from_str(date_str.trim(), "%Y%m%d")
-        .or_else(|_| chrono::NaiveDate::parse_from_str(date_str.trim(), "%Y-%m-%d"));
-
-    let Ok(date) = date else { return (false, false); };
+    let Some(date) = parse_expiry_date(date_str) else { return (false, false); };
 
     let today = Utc::now().date_naive();
     let days_until = (date - today).num_days();
@@ -312,6 +398,13 @@ pub(crate) fn check_expiry(date_str: &str) -> (bool, bool) {
     (expired, expiring)
 }
 
+/// Parse a date field value in the formats `check_expiry` accepts
+pub(crate) fn parse_expiry_date(date_str: &str) -> Option<chrono::NaiveDate> {
+    chrono::NaiveDate::parse_from_str(date_str.trim(), "%Y%m%d")
+        .or_else(|_| chrono::NaiveDate::parse_from_str(date_str.trim(), "%Y-%m-%d"))
+        .ok()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -879,4 +972,38 @@ mod tests {
         assert!(top.iter().all(|u| u.value.starts_with("user")));
         assert!(top.iter().all(|u| u.count == 1));
     }
+
+    #[test]
+    fn test_strict_validation_off_by_default() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let item_id = wallet.add_item("Item", "document", false, None).unwrap();
+
+        assert!(!wallet.strict_validation());
+        assert!(wallet.add_field(&item_id, "MAIL", "not an email", None).is_ok());
+    }
+
+    #[test]
+    fn test_strict_validation_rejects_invalid_values() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let item_id = wallet.add_item("Item", "document", false, None).unwrap();
+        wallet.set_strict_validation(true);
+
+        assert!(matches!(
+            wallet.add_field(&item_id, "MAIL", "not an email", None),
+            Err(WalletError::ValidationError(_))
+        ));
+        assert!(matches!(
+            wallet.add_field(&item_id, "PINC", "12ab", None),
+            Err(WalletError::ValidationError(_))
+        ));
+        assert!(wallet.add_field(&item_id, "PINC", "1234", None).is_ok());
+        assert!(wallet.add_field(&item_id, "NOTE", "anything goes", None).is_ok());
+
+        let field_id = wallet.add_field(&item_id, "EXPD", "20300101", None).unwrap();
+        assert!(matches!(
+            wallet.update_field(&field_id, "next year", None),
+            Err(WalletError::ValidationError(_))
+        ));
+        assert!(wallet.update_field(&field_id, "2031-01-01", None).is_ok());
+    }
 }
@@ -0,0 +1,169 @@
+//! Folder defaults
+//!
+//! A folder can name the icon and the field template new entries inside it
+//! start with, e.g. a "Cards" folder whose entries get the card icon and
+//! empty number, expiry and CVV fields. Defaults are inherited: a folder
+//! without its own icon or template uses the nearest ancestor's.
+
+use crate::database::queries;
+use crate::error::{WalletError, Result};
+use super::wallet::Wallet;
+
+/// Defaults applied by `add_item` to entries created inside a folder
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FolderDefaults {
+    /// Icon used when `add_item` is called with an empty icon
+    pub icon: Option<String>,
+    /// Field types added, with empty values, to every new non-folder entry
+    pub template: Vec<String>,
+}
+
+impl FolderDefaults {
+    fn from_raw(icon: Option<String>, template: Option<String>) -> Self {
+        FolderDefaults {
+            icon: icon.filter(|i| !i.is_empty()),
+            template: template
+                .map(|t| t.split(',').filter(|f| !f.is_empty()).map(str::to_string).collect())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Resolve the defaults for a new entry in `parent_id`: icon and
+    /// template each come from the nearest folder that sets them.
+    pub(crate) fn inherited(conn: &rusqlite::Connection, parent_id: &str) -> Result<Self> {
+        let mut resolved = FolderDefaults::default();
+        for (icon, template) in queries::get_folder_defaults_chain(conn, parent_id)? {
+            let level = FolderDefaults::from_raw(icon, template);
+            if resolved.icon.is_none() {
+                resolved.icon = level.icon;
+            }
+            if resolved.template.is_empty() {
+                resolved.template = level.template;
+            }
+        }
+        Ok(resolved)
+    }
+}
+
+impl Wallet {
+    /// Set the icon and field template for new entries created inside
+    /// `folder_id` and its subfolders. `None` and an empty template clear
+    /// the respective default.
+    pub fn set_folder_defaults(&mut self, folder_id: &str, icon: Option<&str>, template: &[&str]) -> Result<()> {
+        self.ensure_unlocked()?;
+        self.ensure_item_editable(folder_id)?;
+
+        self.load_labels_if_needed()?;
+        if let Some(labels) = self.labels_cache.as_ref()
+            && let Some(missing) = template.iter().find(|f| !labels.contains_key(**f)) {
+                return Err(WalletError::LabelNotFound(missing.to_string()));
+            }
+
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        match queries::get_item_folder_flag(conn, folder_id)? {
+            Some(true) => {}
+            Some(false) => {
+                return Err(WalletError::InvalidOperation(format!("Item {folder_id} is not a folder")));
+            }
+            None => return Err(WalletError::ItemNotFound(folder_id.to_string())),
+        }
+
+        let icon = icon.filter(|i| !i.is_empty());
+        if icon.is_none() && template.is_empty() {
+            queries::delete_folder_defaults(conn, folder_id)?;
+        } else {
+            let template = (!template.is_empty()).then(|| template.join(","));
+            queries::set_folder_defaults_raw(conn, folder_id, icon, template.as_deref())?;
+        }
+
+        self.note_mutation();
+        Ok(())
+    }
+
+    /// A folder's own defaults, without inheritance
+    pub fn get_folder_defaults(&self, folder_id: &str) -> Result<Option<FolderDefaults>> {
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        Ok(queries::get_folder_defaults_raw(conn, folder_id)?
+            .map(|(icon, template)| FolderDefaults::from_raw(icon, template)))
+    }
+
+    /// The defaults `add_item` applies to a new entry in `folder_id`,
+    /// including those inherited from its ancestors
+    pub fn effective_folder_defaults(&self, folder_id: &str) -> Result<FolderDefaults> {
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        FolderDefaults::inherited(conn, folder_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FolderDefaults;
+    use crate::business::wallet::tests::create_test_wallet;
+    use crate::error::WalletError;
+
+    #[test]
+    fn test_folder_defaults_applied_and_inherited() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let cards = wallet.add_item("Cards", "folder", true, None).unwrap();
+        let work = wallet.add_item("Work", "folder", true, Some(&cards)).unwrap();
+        wallet.set_folder_defaults(&cards, Some("card"), &["CARD", "EXPD"]).unwrap();
+        wallet.set_folder_defaults(&work, None, &["NOTE"]).unwrap();
+
+        assert_eq!(
+            wallet.effective_folder_defaults(&work).unwrap(),
+            FolderDefaults { icon: Some("card".to_string()), template: vec!["NOTE".to_string()] }
+        );
+
+        // Empty icon takes the default, template fields start empty
+        let visa = wallet.add_item("Visa", "", false, Some(&cards)).unwrap();
+        let item = wallet.get_item(&visa).unwrap().unwrap();
+        assert_eq!(item.icon, "card");
+        let fields = wallet.get_fields_by_item(&visa).unwrap();
+        let types: Vec<&str> = fields.iter().map(|f| f.field_type.as_str()).collect();
+        assert_eq!(types, ["CARD", "EXPD"]);
+        assert!(fields.iter().all(|f| f.value.is_empty()));
+
+        // An explicit icon wins; the subfolder's own template wins
+        let corp = wallet.add_item("Corp", "document", false, Some(&work)).unwrap();
+        assert_eq!(wallet.get_item(&corp).unwrap().unwrap().icon, "document");
+        let fields = wallet.get_fields_by_item(&corp).unwrap();
+        assert_eq!(fields.len(), 1);
+        assert_eq!(fields[0].field_type, "NOTE");
+
+        // Subfolders get the icon but no template fields
+        let sub = wallet.add_item("Sub", "", true, Some(&cards)).unwrap();
+        assert!(wallet.get_fields_by_item(&sub).unwrap().is_empty());
+
+        // Clearing removes the folder's own defaults
+        wallet.set_folder_defaults(&work, None, &[]).unwrap();
+        assert_eq!(wallet.get_folder_defaults(&work).unwrap(), None);
+        let plain = wallet.add_item("Plain", "document", false, None).unwrap();
+        assert!(wallet.get_fields_by_item(&plain).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_folder_defaults_validation() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let folder = wallet.add_item("Folder", "folder", true, None).unwrap();
+        let item = wallet.add_item("Item", "document", false, None).unwrap();
+
+        assert!(matches!(
+            wallet.set_folder_defaults(&folder, None, &["ZZZZ"]),
+            Err(WalletError::LabelNotFound(_))
+        ));
+        assert!(matches!(
+            wallet.set_folder_defaults(&item, Some("card"), &[]),
+            Err(WalletError::InvalidOperation(_))
+        ));
+        assert!(matches!(
+            wallet.set_folder_defaults("NOPE1234", Some("card"), &[]),
+            Err(WalletError::ItemNotFound(_))
+        ));
+    }
+}
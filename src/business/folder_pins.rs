@@ -0,0 +1,622 @@
+//! Folder PINs
+//!
+//! A folder can carry its own PIN on top of the master password, for a
+//! sensitive subtree on a device others may use while the wallet is open.
+//! Field values of every entry below the folder, and its notes, secure note
+//! bodies, metadata and attachments, are sealed under a folder key derived
+//! with Argon2id from the vault DEK and the PIN, so they stay unreadable
+//! until [`Wallet::unlock_folder`] is called in the session. Entry names
+//! remain under the DEK: the tree can still be browsed.
+//!
+//! While a folder is locked its fields are left out of the field cache, and
+//! with it out of search, exports and reports. The nearest protected folder
+//! wins when PINs are nested.
+
+use std::collections::HashMap;
+use rusqlite::Connection;
+use zeroize::Zeroizing;
+use crate::crypto;
+use crate::crypto::dek::DEK_LEN;
+use crate::database::queries::{self, FolderPinRecord, ItemBlob, ItemBlobKind};
+use crate::error::{WalletError, Result};
+use super::secure_notes::chunk_aad;
+use super::wallet::{random_bytes, Wallet, KDF_SALT_LEN};
+
+/// Derive the key of a protected folder from the DEK and its PIN
+fn derive_folder_key(dek: &[u8; DEK_LEN], pin: &str, rec: &FolderPinRecord) -> Result<Zeroizing<[u8; DEK_LEN]>> {
+    let mut input = Zeroizing::new(dek.to_vec());
+    input.extend_from_slice(crypto::normalize_master_password(pin).as_bytes());
+    let params = crypto::kdf::KdfParams {
+        m_cost_kib: rec.m_cost_kib,
+        t_cost: rec.t_cost,
+        p_cost: rec.p_cost,
+    };
+    crypto::kdf::derive_kek(&input, &rec.salt, params)
+        .map(Zeroizing::new)
+        .map_err(WalletError::EncryptionError)
+}
+
+/// True if `key` opens the folder's key check
+fn key_matches(key: &[u8; DEK_LEN], rec: &FolderPinRecord) -> bool {
+    crypto::aead::open(key, &rec.key_check).is_ok_and(|id| id == rec.folder_id.as_bytes())
+}
+
+/// Field values `(item_id, field_id, value)` and item data re-encrypted
+/// for a folder PIN change or a move
+#[derive(Default)]
+pub(crate) struct Resealed {
+    fields: Vec<(String, String, Vec<u8>)>,
+    item_blobs: Vec<ItemBlob>,
+}
+
+impl Resealed {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.fields.is_empty() && self.item_blobs.is_empty()
+    }
+
+    /// Write the blobs back, inside the transaction of the change
+    pub(crate) fn store(&self, conn: &Connection) -> Result<()> {
+        for (item_id, field_id, blob) in &self.fields {
+            queries::update_field_value_only(conn, item_id, field_id, blob)?;
+        }
+        for blob in &self.item_blobs {
+            queries::update_item_blob(conn, blob)?;
+        }
+        Ok(())
+    }
+}
+
+impl Wallet {
+    /// Protect `folder_id` and everything below it with `pin`. Existing
+    /// field values are re-encrypted under the folder key; the folder
+    /// stays unlocked for the rest of the session.
+    pub fn set_folder_pin(&mut self, folder_id: &str, pin: &str) -> Result<()> {
+        self.ensure_unlocked()?;
+        if pin.is_empty() {
+            return Err(WalletError::InvalidOperation("Folder PIN must not be empty".to_string()));
+        }
+        self.ensure_item_editable(folder_id)?;
+
+        let outer = {
+            let conn = self.db.as_ref()
+                .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+                .connection()?;
+            match queries::get_item_folder_flag(conn, folder_id)? {
+                Some(true) => {}
+                Some(false) => {
+                    return Err(WalletError::InvalidOperation(format!("Item {folder_id} is not a folder")));
+                }
+                None => return Err(WalletError::ItemNotFound(folder_id.to_string())),
+            }
+            if queries::get_folder_pin(conn, folder_id)?.is_some() {
+                return Err(WalletError::InvalidOperation(format!("Folder {folder_id} already has a PIN")));
+            }
+            queries::get_protecting_folder(conn, folder_id)?
+        };
+        let outer_key = match &outer {
+            Some(outer) => self.folder_key(outer)?,
+            None => self.dek()?,
+        };
+
+        let params = crypto::kdf::KdfParams::current();
+        let mut rec = FolderPinRecord {
+            folder_id: folder_id.to_string(),
+            m_cost_kib: params.m_cost_kib,
+            t_cost: params.t_cost,
+            p_cost: params.p_cost,
+            salt: random_bytes(KDF_SALT_LEN),
+            key_check: Vec::new(),
+        };
+        let key = derive_folder_key(self.dek()?, pin, &rec)?;
+        rec.key_check = crypto::aead::seal(&key, folder_id.as_bytes()).map_err(WalletError::EncryptionError)?;
+
+        let resealed = self.reseal_subtree(folder_id, outer.as_deref(), outer_key, &key)?;
+
+        self.write_folder_pin_change(Some(&rec), None, &resealed)?;
+        if let Some(unlocked) = self.unlocked.as_mut() {
+            unlocked.folder_keys.insert(folder_id.to_string(), key);
+            unlocked.folder_pins.insert(folder_id.to_string(), Zeroizing::new(pin.to_string()));
+        }
+        self.fields_cache = None;
+        self.note_mutation();
+        Ok(())
+    }
+
+    /// Remove the PIN of `folder_id`, re-encrypting its fields under the
+    /// DEK (or the key of an enclosing protected folder, which must be
+    /// unlocked). Returns false, changing nothing, if `pin` is wrong.
+    pub fn remove_folder_pin(&mut self, folder_id: &str, pin: &str) -> Result<bool> {
+        self.ensure_unlocked()?;
+        let (rec, outer) = {
+            let conn = self.db.as_ref()
+                .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+                .connection()?;
+            let rec = queries::get_folder_pin(conn, folder_id)?
+                .ok_or_else(|| WalletError::InvalidOperation(format!("Folder {folder_id} has no PIN")))?;
+            let outer = queries::get_enclosing_protected_folder(conn, folder_id)?;
+            (rec, outer)
+        };
+        let key = derive_folder_key(self.dek()?, pin, &rec)?;
+        if !key_matches(&key, &rec) {
+            return Ok(false);
+        }
+        let outer_key = match &outer {
+            Some(outer) => self.folder_key(outer)?,
+            None => self.dek()?,
+        };
+        let resealed = self.reseal_subtree(folder_id, Some(folder_id), &key, outer_key)?;
+
+        self.write_folder_pin_change(None, Some(folder_id), &resealed)?;
+        if let Some(unlocked) = self.unlocked.as_mut() {
+            unlocked.folder_keys.remove(folder_id);
+            unlocked.folder_pins.remove(folder_id);
+        }
+        self.fields_cache = None;
+        self.note_mutation();
+        Ok(true)
+    }
+
+    /// Drop the PIN of `folder_id`, if it has one, without asking for it:
+    /// the folder must be unlocked. What is left in its subtree moves to the
+    /// enclosing key. For folders that go away, so no PIN row outlives them.
+    pub(crate) fn drop_folder_pin(&mut self, folder_id: &str) -> Result<()> {
+        let outer = {
+            let conn = self.db.as_ref()
+                .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+                .connection()?;
+            if queries::get_folder_pin(conn, folder_id)?.is_none() {
+                return Ok(());
+            }
+            queries::get_enclosing_protected_folder(conn, folder_id)?
+        };
+        let key = self.folder_key(folder_id)?;
+        let outer_key = match &outer {
+            Some(outer) => self.folder_key(outer)?,
+            None => self.dek()?,
+        };
+        let resealed = self.reseal_subtree(folder_id, Some(folder_id), key, outer_key)?;
+
+        self.write_folder_pin_change(None, Some(folder_id), &resealed)?;
+        if let Some(unlocked) = self.unlocked.as_mut() {
+            unlocked.folder_keys.remove(folder_id);
+            unlocked.folder_pins.remove(folder_id);
+        }
+        self.fields_cache = None;
+        Ok(())
+    }
+
+    /// Open a protected folder for this session. Returns false if `pin` is
+    /// wrong. Locking the wallet locks every folder again.
+    pub fn unlock_folder(&mut self, folder_id: &str, pin: &str) -> Result<bool> {
+        self.ensure_unlocked()?;
+        let rec = {
+            let conn = self.db.as_ref()
+                .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+                .connection()?;
+            queries::get_folder_pin(conn, folder_id)?
+                .ok_or_else(|| WalletError::InvalidOperation(format!("Folder {folder_id} has no PIN")))?
+        };
+        let key = derive_folder_key(self.dek()?, pin, &rec)?;
+        if !key_matches(&key, &rec) {
+            return Ok(false);
+        }
+        if let Some(unlocked) = self.unlocked.as_mut() {
+            unlocked.folder_keys.insert(folder_id.to_string(), key);
+            unlocked.folder_pins.insert(folder_id.to_string(), Zeroizing::new(pin.to_string()));
+        }
+        self.fields_cache = None;
+        Ok(true)
+    }
+
+    /// Close a protected folder again; its fields leave the cache
+    pub fn lock_folder(&mut self, folder_id: &str) {
+        if let Some(unlocked) = self.unlocked.as_mut()
+            && unlocked.folder_keys.remove(folder_id).is_some() {
+                unlocked.folder_pins.remove(folder_id);
+                self.fields_cache = None;
+            }
+    }
+
+    /// IDs of all PIN-protected folders
+    pub fn protected_folders(&self) -> Result<Vec<String>> {
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        queries::get_folder_pin_ids(conn)
+    }
+
+    /// True if the fields of `item_id` can be read in this session: it is
+    /// not below a protected folder, or that folder is unlocked
+    pub fn is_item_accessible(&self, item_id: &str) -> Result<bool> {
+        self.ensure_unlocked()?;
+        Ok(match self.protecting_folder(item_id)? {
+            Some(folder_id) => self.folder_key(&folder_id).is_ok(),
+            None => true,
+        })
+    }
+
+    /// `FolderLocked` unless `item_id` is outside any protected folder or
+    /// its folder is unlocked in this session
+    pub(crate) fn ensure_item_accessible(&self, item_id: &str) -> Result<()> {
+        if let Some(folder_id) = self.protecting_folder(item_id)? {
+            self.folder_key(&folder_id)?;
+        }
+        Ok(())
+    }
+
+    /// The nearest protected folder among `item_id` and its ancestors
+    pub(crate) fn protecting_folder(&self, item_id: &str) -> Result<Option<String>> {
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        queries::get_protecting_folder(conn, item_id)
+    }
+
+    /// Key of an unlocked protected folder
+    pub(crate) fn folder_key(&self, folder_id: &str) -> Result<&[u8; DEK_LEN]> {
+        self.unlocked.as_ref()
+            .ok_or(WalletError::Locked)?
+            .folder_keys.get(folder_id)
+            .map(|k| &**k)
+            .ok_or_else(|| WalletError::FolderLocked(folder_id.to_string()))
+    }
+
+    /// Key of the data of `item_id`: the key of the folder protecting it,
+    /// which must be unlocked, else the DEK
+    pub(crate) fn item_key(&self, item_id: &str) -> Result<&[u8; DEK_LEN]> {
+        match self.protecting_folder(item_id)? {
+            Some(folder_id) => self.folder_key(&folder_id),
+            None => self.dek(),
+        }
+    }
+
+    /// Seal a note, metadata or attachment of `item_id`
+    pub(crate) fn seal_item_data(&self, item_id: &str, plaintext: &[u8]) -> Result<Vec<u8>> {
+        self.seal_item_data_with_aad(item_id, plaintext, b"")
+    }
+
+    /// Open a blob sealed with [`Wallet::seal_item_data`]
+    pub(crate) fn open_item_data(&self, item_id: &str, blob: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+        self.open_item_data_with_aad(item_id, blob, b"")
+    }
+
+    /// Seal data of `item_id` bound to the associated data `aad`
+    pub(crate) fn seal_item_data_with_aad(&self, item_id: &str, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        crypto::aead::seal_with_aad(self.item_key(item_id)?, plaintext, aad).map_err(WalletError::EncryptionError)
+    }
+
+    /// Open a blob sealed with [`Wallet::seal_item_data_with_aad`]
+    pub(crate) fn open_item_data_with_aad(&self, item_id: &str, blob: &[u8], aad: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+        Ok(Zeroizing::new(crypto::aead::open_with_aad(self.item_key(item_id)?, blob, aad)?))
+    }
+
+    /// Move the fields and item data of the subtree of `item_id` to the key
+    /// protecting its new place, before it is moved under `new_parent_id`.
+    /// Nothing to do when the protection is unchanged.
+    pub(crate) fn reseal_for_move(&self, item_id: &str, new_parent_id: &str) -> Result<Resealed> {
+        let (from, to) = {
+            let conn = self.db.as_ref()
+                .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+                .connection()?;
+            if queries::get_folder_pin(conn, item_id)?.is_some() {
+                return Ok(Resealed::default());
+            }
+            (queries::get_protecting_folder(conn, item_id)?, queries::get_protecting_folder(conn, new_parent_id)?)
+        };
+        if from == to {
+            return Ok(Resealed::default());
+        }
+        let from_key = match &from {
+            Some(folder_id) => self.folder_key(folder_id)?,
+            None => self.dek()?,
+        };
+        let to_key = match &to {
+            Some(folder_id) => self.folder_key(folder_id)?,
+            None => self.dek()?,
+        };
+        self.reseal_subtree(item_id, from.as_deref(), from_key, to_key)
+    }
+
+    /// Re-encrypt the field values and item data in the subtree of
+    /// `root_id` that `from` protects (`None`: no folder PIN) from
+    /// `from_key` to `to_key`. Items under a nested PIN keep their key.
+    fn reseal_subtree(
+        &self,
+        root_id: &str,
+        from: Option<&str>,
+        from_key: &[u8; DEK_LEN],
+        to_key: &[u8; DEK_LEN],
+    ) -> Result<Resealed> {
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        let mut protecting: HashMap<String, bool> = HashMap::new();
+        let mut in_scope = |item_id: &str| -> Result<bool> {
+            if let Some(&scoped) = protecting.get(item_id) {
+                return Ok(scoped);
+            }
+            let scoped = queries::get_protecting_folder(conn, item_id)?.as_deref() == from;
+            protecting.insert(item_id.to_string(), scoped);
+            Ok(scoped)
+        };
+        let reseal = |blob: &[u8], aad: &[u8]| -> Result<Vec<u8>> {
+            let plain = Zeroizing::new(crypto::aead::open_with_aad(from_key, blob, aad)?);
+            crypto::aead::seal_with_aad(to_key, &plain, aad).map_err(WalletError::EncryptionError)
+        };
+
+        let mut resealed = Resealed::default();
+        for (item_id, field_id, blob) in queries::get_subtree_field_blobs(conn, root_id)? {
+            if in_scope(&item_id)? {
+                resealed.fields.push((item_id, field_id, reseal(&blob, b"")?));
+            }
+        }
+        for mut item_blob in queries::get_subtree_item_blobs(conn, root_id)? {
+            if in_scope(&item_blob.item_id)? {
+                let aad = match item_blob.kind {
+                    ItemBlobKind::SecureNoteChunk { chunk, last } => chunk_aad(&item_blob.item_id, chunk, last),
+                    _ => Vec::new(),
+                };
+                item_blob.blob = reseal(&item_blob.blob, &aad)?;
+                resealed.item_blobs.push(item_blob);
+            }
+        }
+        Ok(resealed)
+    }
+
+    /// Protect `folder_ids` in `copy`, a clone of this wallet under another
+    /// DEK, with the PINs they were unlocked with here. The folders stay
+    /// unlocked in the copy's session.
+    pub(crate) fn copy_folder_pins_to(&self, copy: &mut Wallet, folder_ids: &[String]) -> Result<()> {
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        for folder_id in folder_ids {
+            let pin = self.unlocked.as_ref()
+                .ok_or(WalletError::Locked)?
+                .folder_pins.get(folder_id)
+                .ok_or_else(|| WalletError::FolderLocked(folder_id.clone()))?;
+            let Some(source) = queries::get_folder_pin(conn, folder_id)? else { continue };
+            let mut rec = FolderPinRecord { salt: random_bytes(KDF_SALT_LEN), key_check: Vec::new(), ..source };
+            let key = derive_folder_key(copy.dek()?, pin, &rec)?;
+            rec.key_check = crypto::aead::seal(&key, folder_id.as_bytes()).map_err(WalletError::EncryptionError)?;
+
+            let target = copy.db.as_ref()
+                .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+                .connection()?;
+            queries::create_folder_pin(target, &rec)?;
+            if let Some(unlocked) = copy.unlocked.as_mut() {
+                unlocked.folder_keys.insert(folder_id.clone(), key);
+                unlocked.folder_pins.insert(folder_id.clone(), pin.clone());
+            }
+        }
+        copy.fields_cache = None;
+        Ok(())
+    }
+
+    /// Store a PIN change and the re-encrypted values in one transaction
+    fn write_folder_pin_change(
+        &mut self,
+        created: Option<&FolderPinRecord>,
+        removed: Option<&str>,
+        resealed: &Resealed,
+    ) -> Result<()> {
+        let db = self.db.as_mut()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?;
+        db.begin_transaction()?;
+
+        let pass = (|| -> Result<()> {
+            let conn = db.connection()?;
+            if let Some(rec) = created {
+                queries::create_folder_pin(conn, rec)?;
+            }
+            if let Some(folder_id) = removed {
+                queries::delete_folder_pin(conn, folder_id)?;
+            }
+            resealed.store(conn)
+        })();
+
+        match pass {
+            Ok(()) => db.commit_transaction()?,
+            Err(e) => {
+                let _ = db.rollback_transaction();
+                return Err(e);
+            }
+        }
+        let _ = self.db.as_ref().unwrap().checkpoint();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::crypto;
+    use crate::database::queries::{self, ItemBlobKind};
+    use crate::business::secure_notes::chunk_aad;
+    use crate::business::wallet::tests::create_test_wallet;
+    use crate::business::wallet::Wallet;
+    use crate::error::WalletError;
+
+    fn values(wallet: &mut Wallet, item_id: &str) -> Vec<String> {
+        wallet.get_fields_by_item(item_id).unwrap().into_iter().map(|f| f.value).collect()
+    }
+
+    #[test]
+    fn test_folder_pin_protects_fields() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let vault = wallet.add_item("Vault", "folder", true, None).unwrap();
+        let inner = wallet.add_item("Inner", "folder", true, Some(&vault)).unwrap();
+        let bank = wallet.add_item("Bank", "document", false, Some(&inner)).unwrap();
+        wallet.add_field(&bank, "PASS", "secret", None).unwrap();
+        let open = wallet.add_item("Open", "document", false, None).unwrap();
+        wallet.add_field(&open, "PASS", "public", None).unwrap();
+
+        wallet.set_folder_pin(&vault, "1234").unwrap();
+        assert_eq!(wallet.protected_folders().unwrap(), vec![vault.clone()]);
+        assert_eq!(values(&mut wallet, &bank), vec!["secret"]);
+
+        // A fresh session starts with the folder locked
+        let folder = wallet.folder.clone();
+        wallet.close();
+        let mut wallet = Wallet::open(&folder).unwrap();
+        assert!(wallet.unlock("TestPassword123").unwrap());
+        assert!(!wallet.is_item_accessible(&bank).unwrap());
+        assert!(matches!(wallet.get_fields_by_item(&bank), Err(WalletError::FolderLocked(id)) if id == vault));
+        assert!(matches!(wallet.add_field(&bank, "NOTE", "x", None), Err(WalletError::FolderLocked(_))));
+        assert!(wallet.get_fields().unwrap().iter().all(|f| f.value != "secret"));
+        assert_eq!(values(&mut wallet, &open), vec!["public"]);
+        assert!(wallet.get_undecryptable_records().unwrap().is_empty());
+
+        assert!(!wallet.unlock_folder(&vault, "0000").unwrap());
+        assert!(wallet.unlock_folder(&vault, "1234").unwrap());
+        assert_eq!(values(&mut wallet, &bank), vec!["secret"]);
+        wallet.add_field(&bank, "NOTE", "added", None).unwrap();
+
+        wallet.lock_folder(&vault);
+        assert!(wallet.get_fields_by_item(&bank).is_err());
+
+        assert!(!wallet.remove_folder_pin(&vault, "0000").unwrap());
+        assert!(wallet.remove_folder_pin(&vault, "1234").unwrap());
+        assert!(wallet.protected_folders().unwrap().is_empty());
+        let mut bank_values = values(&mut wallet, &bank);
+        bank_values.sort();
+        assert_eq!(bank_values, vec!["added", "secret"]);
+    }
+
+    #[test]
+    fn test_move_across_folder_pin() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let vault = wallet.add_item("Vault", "folder", true, None).unwrap();
+        let bank = wallet.add_item("Bank", "document", false, None).unwrap();
+        wallet.add_field(&bank, "PASS", "secret", None).unwrap();
+        wallet.set_folder_pin(&vault, "1234").unwrap();
+
+        wallet.move_item(&bank, &vault).unwrap();
+        wallet.lock_folder(&vault);
+        assert!(matches!(wallet.get_fields_by_item(&bank), Err(WalletError::FolderLocked(_))));
+        assert!(matches!(wallet.move_item(&bank, crate::ROOT_ID), Err(WalletError::FolderLocked(_))));
+
+        assert!(wallet.unlock_folder(&vault, "1234").unwrap());
+        wallet.move_item(&bank, crate::ROOT_ID).unwrap();
+        wallet.lock_folder(&vault);
+        assert_eq!(values(&mut wallet, &bank), vec!["secret"]);
+    }
+
+    #[test]
+    fn test_folder_pin_gates_notes_and_metadata() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let vault = wallet.add_item("Vault", "folder", true, None).unwrap();
+        let bank = wallet.add_item("Bank", "document", false, Some(&vault)).unwrap();
+        let codes = wallet.add_secure_note("Codes", Some(&vault)).unwrap();
+        wallet.set_folder_pin(&vault, "1234").unwrap();
+        wallet.set_item_note(&bank, "note").unwrap();
+        wallet.set_secure_note_body(&codes, "body").unwrap();
+        wallet.set_item_meta(&bank, "hint", serde_json::json!("x")).unwrap();
+
+        wallet.lock_folder(&vault);
+        assert!(matches!(wallet.get_item_note(&bank), Err(WalletError::FolderLocked(_))));
+        assert!(matches!(wallet.set_item_note(&bank, "other"), Err(WalletError::FolderLocked(_))));
+        assert!(matches!(wallet.get_secure_note_body(&codes), Err(WalletError::FolderLocked(_))));
+        assert!(matches!(wallet.set_secure_note_body(&codes, "other"), Err(WalletError::FolderLocked(_))));
+        assert!(matches!(wallet.add_secure_note("More", Some(&vault)), Err(WalletError::FolderLocked(_))));
+        assert!(matches!(wallet.get_item_metadata(&bank), Err(WalletError::FolderLocked(_))));
+        assert!(matches!(wallet.set_item_meta(&bank, "hint", serde_json::json!("y")), Err(WalletError::FolderLocked(_))));
+
+        assert!(wallet.unlock_folder(&vault, "1234").unwrap());
+        assert_eq!(wallet.get_item_note(&bank).unwrap().as_deref(), Some("note"));
+        assert_eq!(wallet.get_secure_note_body(&codes).unwrap(), "body");
+        assert_eq!(wallet.get_item_meta(&bank, "hint").unwrap(), Some(serde_json::json!("x")));
+    }
+
+    #[test]
+    fn test_folder_pin_seals_item_data() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let vault = wallet.add_item("Vault", "folder", true, None).unwrap();
+        let bank = wallet.add_item("Bank", "document", false, Some(&vault)).unwrap();
+        wallet.set_item_note(&bank, "note").unwrap();
+        wallet.set_item_meta(&bank, "hint", serde_json::json!("x")).unwrap();
+        let scan = wallet.add_attachment(&bank, "scan.pdf", "application/pdf", b"%PDF").unwrap();
+        let codes = wallet.add_secure_note("Codes", Some(&vault)).unwrap();
+        wallet.set_secure_note_body(&codes, "body").unwrap();
+        let loose = wallet.add_item("Loose", "document", false, None).unwrap();
+        wallet.set_item_note(&loose, "loose note").unwrap();
+
+        // Every stored blob of the item data, and whether the DEK opens it
+        let under_dek = |wallet: &Wallet, item_id: &str| -> Vec<bool> {
+            let conn = wallet.db.as_ref().unwrap().connection().unwrap();
+            queries::get_subtree_item_blobs(conn, item_id).unwrap().iter()
+                .map(|b| {
+                    let aad = match b.kind {
+                        ItemBlobKind::SecureNoteChunk { chunk, last } => chunk_aad(&b.item_id, chunk, last),
+                        _ => Vec::new(),
+                    };
+                    crypto::aead::open_with_aad(wallet.dek().unwrap(), &b.blob, &aad).is_ok()
+                })
+                .collect()
+        };
+        assert_eq!(under_dek(&wallet, &bank), [true; 5]);
+        assert_eq!(under_dek(&wallet, &codes), [true]);
+
+        wallet.set_folder_pin(&vault, "1234").unwrap();
+        assert_eq!(under_dek(&wallet, &bank), [false; 5]);
+        assert_eq!(under_dek(&wallet, &codes), [false]);
+        assert_eq!(wallet.read_attachment(&scan).unwrap(), b"%PDF");
+        assert_eq!(wallet.get_secure_note_body(&codes).unwrap(), "body");
+
+        wallet.move_item(&loose, &vault).unwrap();
+        assert_eq!(under_dek(&wallet, &loose), [false]);
+        wallet.move_item(&loose, crate::ROOT_ID).unwrap();
+        assert_eq!(under_dek(&wallet, &loose), [true]);
+        assert_eq!(wallet.get_item_note(&loose).unwrap().as_deref(), Some("loose note"));
+
+        // Moves need the wallet unlocked, so values always follow their key
+        wallet.lock();
+        assert!(matches!(wallet.move_item(&loose, &vault), Err(WalletError::Locked)));
+        assert!(wallet.unlock("TestPassword123").unwrap());
+        assert!(wallet.unlock_folder(&vault, "1234").unwrap());
+
+        assert!(wallet.remove_folder_pin(&vault, "1234").unwrap());
+        assert_eq!(under_dek(&wallet, &bank), [true; 5]);
+        assert_eq!(wallet.get_item_meta(&bank, "hint").unwrap(), Some(serde_json::json!("x")));
+        assert_eq!(wallet.get_attachments(&bank).unwrap()[0].name, "scan.pdf");
+    }
+
+    #[test]
+    fn test_folder_pin_goes_with_its_folder() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let keep = wallet.add_item("Vault", "folder", true, None).unwrap();
+        let merged = wallet.add_item("vault", "folder", true, None).unwrap();
+        let bank = wallet.add_item("Bank", "document", false, Some(&merged)).unwrap();
+        wallet.add_field(&bank, "PASS", "secret", None).unwrap();
+        let trashed = wallet.add_item("Old", "document", false, Some(&merged)).unwrap();
+        wallet.add_field(&trashed, "PASS", "old", None).unwrap();
+        wallet.delete_item(&trashed).unwrap();
+        wallet.set_folder_pin(&merged, "1234").unwrap();
+
+        // Merging drops the PIN and reseals what stays behind in the trash
+        wallet.merge_folders(&keep, &merged).unwrap();
+        assert!(wallet.protected_folders().unwrap().is_empty());
+        assert_eq!(values(&mut wallet, &bank), vec!["secret"]);
+        let conn = wallet.db.as_ref().unwrap().connection().unwrap();
+        let blobs = queries::get_subtree_field_blobs(conn, &trashed).unwrap();
+        assert_eq!(crypto::aead::open(wallet.dek().unwrap(), &blobs[0].2).unwrap(), b"old");
+
+        // Purging a protected folder purges its PIN
+        let vault = wallet.add_item("Other", "folder", true, None).unwrap();
+        wallet.set_folder_pin(&vault, "1234").unwrap();
+        wallet.delete_item(&vault).unwrap();
+        wallet.compact().unwrap();
+        assert!(wallet.protected_folders().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_set_folder_pin_rejects() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let vault = wallet.add_item("Vault", "folder", true, None).unwrap();
+        let item = wallet.add_item("Item", "document", false, None).unwrap();
+        assert!(matches!(wallet.set_folder_pin(&item, "1234"), Err(WalletError::InvalidOperation(_))));
+        assert!(matches!(wallet.set_folder_pin(&vault, ""), Err(WalletError::InvalidOperation(_))));
+        wallet.set_folder_pin(&vault, "1234").unwrap();
+        assert!(matches!(wallet.set_folder_pin(&vault, "5678"), Err(WalletError::InvalidOperation(_))));
+        assert!(matches!(wallet.unlock_folder(&item, "1234"), Err(WalletError::InvalidOperation(_))));
+    }
+}
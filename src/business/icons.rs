@@ -0,0 +1,213 @@
+//! Bulk icon assignment
+//!
+//! Imports leave every entry with the same generic icon. The suggestions
+//! here match entries against the wallet's icon catalog (`nswallet_icons`)
+//! by the domains of their LINK fields and by the words of their names:
+//! an entry "PayPal" or one with a link to `paypal.com` gets the catalog
+//! icon `paypal`.
+
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::database::{IWIcon, queries};
+use crate::error::{WalletError, Result};
+use crate::utils::url::{extract_host, registrable_domain};
+use super::batch::BatchResult;
+use super::wallet::Wallet;
+
+/// A catalog icon proposed for an entry by [`Wallet::suggest_icons`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IconSuggestion {
+    pub item_id: String,
+    /// Icon the entry has now
+    pub current: String,
+    /// Suggested catalog icon ID
+    pub icon: String,
+}
+
+/// Lowercase letters and digits of `s`, the form names and icons are
+/// compared in
+fn icon_key(s: &str) -> String {
+    s.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+/// Catalog icons by the keys of their ID (without an `icon_` prefix) and name
+fn catalog_index(icons: &[IWIcon]) -> HashMap<String, String> {
+    let mut index = HashMap::new();
+    for icon in icons {
+        let id = icon.icon_id.strip_prefix("icon_").unwrap_or(&icon.icon_id);
+        for key in [icon_key(id), icon_key(&icon.name)] {
+            if !key.is_empty() {
+                index.entry(key).or_insert_with(|| icon.icon_id.clone());
+            }
+        }
+    }
+    index
+}
+
+impl Wallet {
+    /// The wallet's icon catalog
+    pub fn get_icons(&self) -> Result<Vec<IWIcon>> {
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        queries::get_icons(conn)
+    }
+
+    /// Set the icons of many items in one transaction. An item that does
+    /// not exist or is locked fails on its own; the others are updated.
+    pub fn set_icons_bulk(&mut self, assignments: &[(&str, &str)]) -> Result<BatchResult<()>> {
+        self.ensure_unlocked()?;
+        if assignments.is_empty() {
+            return Ok(BatchResult::default());
+        }
+        let editable: Vec<Result<()>> = assignments.iter()
+            .map(|(item_id, _)| self.ensure_item_editable(item_id))
+            .collect();
+
+        let db = self.db.as_mut()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?;
+        db.begin_transaction()?;
+
+        let pass = (|| -> Result<BatchResult<()>> {
+            let conn = db.connection()?;
+            Ok(assignments.iter().zip(editable)
+                .map(|((item_id, icon), editable)| {
+                    editable.and_then(|()| queries::update_item_icon_no_checkpoint(conn, item_id, icon))
+                })
+                .collect())
+        })();
+
+        let result = match pass {
+            Ok(result) => {
+                db.commit_transaction()?;
+                result
+            }
+            Err(e) => {
+                let _ = db.rollback_transaction();
+                return Err(e);
+            }
+        };
+        let _ = self.db.as_ref().unwrap().checkpoint();
+
+        if !result.succeeded.is_empty() {
+            self.items_cache = None;
+            self.note_mutation();
+        }
+        Ok(result)
+    }
+
+    /// Catalog icons for entries, matched by LINK domains first and then
+    /// by name. Folders, locked items and entries that already use a
+    /// catalog icon are left out.
+    pub fn suggest_icons(&mut self) -> Result<Vec<IconSuggestion>> {
+        self.ensure_unlocked()?;
+        let icons = self.get_icons()?;
+        if icons.is_empty() {
+            return Ok(Vec::new());
+        }
+        let index = catalog_index(&icons);
+
+        let mut domains: HashMap<String, Vec<String>> = HashMap::new();
+        for field in self.get_fields()?.iter().filter(|f| f.field_type == "LINK") {
+            if let Some(host) = extract_host(&field.value) {
+                let domain = registrable_domain(&host);
+                let keys = domains.entry(field.item_id.clone()).or_default();
+                // "paypal" of paypal.com, then the whole domain
+                keys.extend(domain.split('.').next().map(icon_key));
+                keys.push(icon_key(&domain));
+            }
+        }
+
+        let items = self.get_items()?.to_vec();
+        let mut suggestions = Vec::new();
+        for item in items.iter().filter(|i| !i.folder && !i.locked) {
+            if icons.iter().any(|icon| icon.icon_id == item.icon) {
+                continue;
+            }
+            let by_name = std::iter::once(icon_key(&item.name))
+                .chain(item.name.split_whitespace().map(icon_key));
+            let found = domains.get(&item.item_id).into_iter().flatten().cloned()
+                .chain(by_name)
+                .find_map(|key| index.get(&key));
+            if let Some(icon) = found {
+                suggestions.push(IconSuggestion {
+                    item_id: item.item_id.clone(),
+                    current: item.icon.clone(),
+                    icon: icon.clone(),
+                });
+            }
+        }
+        Ok(suggestions)
+    }
+
+    /// Apply [`Wallet::suggest_icons`] and return what was set
+    pub fn auto_assign_icons(&mut self) -> Result<Vec<IconSuggestion>> {
+        let suggestions = self.suggest_icons()?;
+        let assignments: Vec<(&str, &str)> = suggestions.iter()
+            .map(|s| (s.item_id.as_str(), s.icon.as_str()))
+            .collect();
+        let result = self.set_icons_bulk(&assignments)?;
+        Ok(result.succeeded.into_iter()
+            .map(|(index, ())| suggestions[index].clone())
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::business::wallet::tests::create_test_wallet;
+
+    #[test]
+    fn test_auto_assign_icons() {
+        let (mut wallet, _temp) = create_test_wallet();
+        {
+            let conn = wallet.db.as_ref().unwrap().connection().unwrap();
+            conn.execute_batch(
+                "INSERT INTO nswallet_icons (icon_id, name, group_id) VALUES ('paypal', 'PayPal', 1);
+                 INSERT INTO nswallet_icons (icon_id, name, group_id) VALUES ('icon_github', 'GitHub', 1);
+                 INSERT INTO nswallet_icons (icon_id, name, group_id, deleted) VALUES ('gone', 'Bank', 1, 1);"
+            ).unwrap();
+        }
+        let paypal = wallet.add_item("PayPal", "document", false, None).unwrap();
+        let work = wallet.add_item("Work code", "document", false, None).unwrap();
+        wallet.add_field(&work, "LINK", "https://gist.github.com/me", None).unwrap();
+        let bank = wallet.add_item("Bank", "document", false, None).unwrap();
+        let locked = wallet.add_item("PayPal business", "document", false, None).unwrap();
+        wallet.set_item_locked(&locked, true).unwrap();
+        let folder = wallet.add_item("PayPal", "folder", true, None).unwrap();
+
+        let set = wallet.auto_assign_icons().unwrap();
+        let mut ids: Vec<&str> = set.iter().map(|s| s.item_id.as_str()).collect();
+        ids.sort();
+        let mut expected = vec![paypal.as_str(), work.as_str()];
+        expected.sort();
+        assert_eq!(ids, expected);
+
+        let icon_of = |wallet: &mut Wallet, id: &str| wallet.get_item(id).unwrap().unwrap().icon;
+        assert_eq!(icon_of(&mut wallet, &paypal), "paypal");
+        assert_eq!(icon_of(&mut wallet, &work), "icon_github");
+        assert_eq!(icon_of(&mut wallet, &bank), "document");
+        assert_eq!(icon_of(&mut wallet, &folder), "folder");
+        // Nothing left to suggest
+        assert!(wallet.suggest_icons().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_set_icons_bulk_reports_each_item() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let a = wallet.add_item("A", "document", false, None).unwrap();
+        let b = wallet.add_item("B", "document", false, None).unwrap();
+        let locked = wallet.add_item("Locked", "document", false, None).unwrap();
+        wallet.set_item_locked(&locked, true).unwrap();
+
+        let result = wallet.set_icons_bulk(&[(&a, "bank"), ("missing", "bank"), (&locked, "bank"), (&b, "mail")]).unwrap();
+        assert_eq!(result.succeeded.iter().map(|(i, _)| *i).collect::<Vec<_>>(), [0, 3]);
+        assert!(matches!(result.failed[0].error, WalletError::ItemNotFound(_)));
+        assert!(matches!(result.failed[1].error, WalletError::ItemLocked(_)));
+        assert_eq!(wallet.get_item(&a).unwrap().unwrap().icon, "bank");
+        assert_eq!(wallet.get_item(&b).unwrap().unwrap().icon, "mail");
+        assert_eq!(wallet.get_item(&locked).unwrap().unwrap().icon, "document");
+        assert!(wallet.set_icons_bulk(&[]).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,136 @@
+//! ID allocation for new records
+//!
+//! IDs come from the wallet's [`IdGenerator`]. Short random IDs can collide
+//! on large wallets, so every new ID is checked against the table and
+//! regenerated when taken; retries are counted in [`IdCollisionStats`].
+
+use crate::database::queries;
+use crate::error::{WalletError, Result};
+use crate::utils::IdGenerator;
+use super::wallet::Wallet;
+
+/// Attempts per new ID before giving up with `WalletError::IdCollision`
+pub const MAX_ID_ATTEMPTS: u32 = 16;
+
+/// Kind of record an ID is generated for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdKind {
+    Item,
+    Field,
+    Label,
+    Attachment,
+}
+
+/// Number of generated IDs that were already taken, per kind
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IdCollisionStats {
+    pub items: u32,
+    pub fields: u32,
+    pub labels: u32,
+    pub attachments: u32,
+}
+
+impl Wallet {
+    /// Replace the ID generator used for new items, fields and labels
+    /// (attachment IDs are drawn from its item IDs)
+    pub fn set_id_generator(&mut self, generator: Box<dyn IdGenerator>) {
+        self.id_generator = generator;
+    }
+
+    /// Collisions hit (and retried) since the wallet was opened
+    pub fn id_collision_stats(&self) -> IdCollisionStats {
+        self.id_collisions
+    }
+
+    /// Allocate an ID of `kind` that no row uses yet (deleted rows
+    /// included). `reserved` holds IDs handed out for a pending batch that
+    /// are not in the table yet.
+    pub(crate) fn unique_id(&mut self, kind: IdKind, reserved: &[String]) -> Result<String> {
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        for _ in 0..MAX_ID_ATTEMPTS {
+            let id = match kind {
+                IdKind::Item => self.id_generator.item_id(),
+                IdKind::Field => self.id_generator.field_id(),
+                IdKind::Label => self.id_generator.label_id(),
+                IdKind::Attachment => self.id_generator.item_id(),
+            };
+            let taken = id.is_empty() || reserved.contains(&id) || match kind {
+                IdKind::Item => queries::item_row_exists(conn, &id)?,
+                IdKind::Field => queries::field_id_exists(conn, &id)?,
+                IdKind::Label => queries::label_row_exists(conn, &id)?,
+                IdKind::Attachment => queries::attachment_row_exists(conn, &id)?,
+            };
+            if !taken {
+                return Ok(id);
+            }
+            match kind {
+                IdKind::Item => self.id_collisions.items += 1,
+                IdKind::Field => self.id_collisions.fields += 1,
+                IdKind::Label => self.id_collisions.labels += 1,
+                IdKind::Attachment => self.id_collisions.attachments += 1,
+            }
+        }
+        Err(WalletError::IdCollision(format!(
+            "no unused {kind:?} id after {MAX_ID_ATTEMPTS} attempts"
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::business::wallet::tests::create_test_wallet;
+
+    /// Replays a fixed list of IDs, then repeats the last one
+    struct ScriptedIds(Vec<&'static str>);
+
+    impl ScriptedIds {
+        fn next(&mut self) -> String {
+            if self.0.len() > 1 { self.0.remove(0) } else { self.0[0] }.to_string()
+        }
+    }
+
+    impl IdGenerator for ScriptedIds {
+        fn item_id(&mut self) -> String { self.next() }
+        fn field_id(&mut self) -> String { self.next() }
+        fn label_id(&mut self) -> String { self.next() }
+    }
+
+    #[test]
+    fn test_item_id_collision_is_retried() {
+        let (mut wallet, _temp) = create_test_wallet();
+        wallet.set_id_generator(Box::new(ScriptedIds(vec!["AAAAAAAA", "AAAAAAAA", "BBBBBBBB"])));
+
+        assert_eq!(wallet.add_item("One", "document", false, None).unwrap(), "AAAAAAAA");
+        assert_eq!(wallet.add_item("Two", "document", false, None).unwrap(), "BBBBBBBB");
+        assert_eq!(wallet.id_collision_stats(), IdCollisionStats { items: 1, fields: 0, labels: 0, attachments: 0 });
+    }
+
+    #[test]
+    fn test_field_and_label_collisions() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let item = wallet.add_item("Item", "document", false, None).unwrap();
+        wallet.set_id_generator(Box::new(ScriptedIds(vec!["F001", "F001", "F002", "MAIL", "L001"])));
+
+        assert_eq!(wallet.add_field(&item, "NOTE", "a", None).unwrap(), "F001");
+        assert_eq!(wallet.update_field("F001", "b", None).unwrap(), "F002");
+        // "MAIL" is a system label code
+        assert_eq!(wallet.add_label("Club", "labelcard", "text").unwrap(), "L001");
+        assert_eq!(wallet.id_collision_stats(), IdCollisionStats { items: 0, fields: 1, labels: 1, attachments: 0 });
+    }
+
+    #[test]
+    fn test_exhausted_attempts_error() {
+        let (mut wallet, _temp) = create_test_wallet();
+        wallet.set_id_generator(Box::new(ScriptedIds(vec!["SAMEIDXX"])));
+        wallet.add_item("One", "document", false, None).unwrap();
+
+        assert!(matches!(
+            wallet.add_item("Two", "document", false, None),
+            Err(WalletError::IdCollision(_))
+        ));
+        assert_eq!(wallet.id_collision_stats().items, MAX_ID_ATTEMPTS);
+    }
+}
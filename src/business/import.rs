@@ -0,0 +1,428 @@
+//! Import from other password managers
+//!
+//! Turns what [`crate::import`] reads from another password manager into
+//! folders, items and fields: KeePass databases and CSV exports. Each
+//! source record has its outcome in the [`BatchResult`] of the
+//! [`ImportReport`], so one that cannot be imported does not fail the
+//! whole import. The settings of a recurring CSV import can be
+//! saved as an [`ImportProfile`] and replayed with [`Wallet::rerun_import`].
+
+use std::collections::{HashMap, HashSet};
+use std::io::Read;
+use std::path::Path;
+
+use zeroize::Zeroizing;
+
+use crate::{ITEM_NAME_MAX_LENGTH, ROOT_ID};
+use crate::database::queries;
+use crate::error::{Result, WalletError};
+use crate::import::{
+    read_csv, read_kdbx, CsvMapping, CsvSource, ImportConflict, ImportProfile, ImportReport, KdbxEntry, KdbxGroup,
+};
+use super::wallet::Wallet;
+
+/// KeePass string fields with an IntelliWallet field type, in the order
+/// the fields are added
+const KDBX_FIELD_TYPES: &[(&str, &str)] = &[
+    ("UserName", "USER"),
+    ("Password", "PASS"),
+    ("URL", "LINK"),
+    ("Notes", "NOTE"),
+    // TOTP URI as stored by KeePassXC
+    ("otp", "2FAC"),
+];
+
+/// Field types and values of an item
+type FieldValues = HashSet<(String, String)>;
+
+/// Item and folder name, cut to the maximum length
+fn import_name(name: &str) -> String {
+    name.trim().chars().take(ITEM_NAME_MAX_LENGTH).collect()
+}
+
+impl Wallet {
+    /// Import a KeePass 2.x database protected by `password`.
+    ///
+    /// The root group becomes a folder at the wallet root, with subgroups
+    /// as folders and entries as items. UserName, Password, URL and Notes
+    /// become USER, PASS, LINK and NOTE fields; other non-empty strings
+    /// become NOTE fields starting with their name. The recycle bin and
+    /// entry history are left out. A wrong password fails with
+    /// [`WalletError::InvalidPassword`](crate::WalletError::InvalidPassword)
+    /// before anything is written.
+    pub fn import_kdbx(&mut self, path: &Path, password: &str) -> Result<ImportReport> {
+        self.ensure_unlocked()?;
+        let bytes = Zeroizing::new(std::fs::read(path)?);
+        let root = read_kdbx(&bytes, password)?;
+
+        let mut report = ImportReport::default();
+        self.import_kdbx_group(&root, None, "", &mut report)?;
+        Ok(report)
+    }
+
+    fn import_kdbx_group(&mut self, group: &KdbxGroup, parent_id: Option<&str>, parent_path: &str, report: &mut ImportReport) -> Result<()> {
+        let path = format!("{parent_path}/{}", group.name);
+        if group.recycle_bin {
+            report.record(path, Err(WalletError::InvalidOperation("The recycle bin is not imported".to_string())));
+            return Ok(());
+        }
+        let folder_id = match self.add_item(&import_name(&group.name), "folder", true, parent_id) {
+            Ok(id) => id,
+            Err(e) => {
+                report.record(path, Err(e));
+                return Ok(());
+            }
+        };
+        report.folders_created += 1;
+        report.record(path.clone(), Ok(folder_id.clone()));
+
+        for entry in &group.entries {
+            self.import_kdbx_entry(entry, &folder_id, &path, report);
+        }
+        for child in &group.groups {
+            self.import_kdbx_group(child, Some(&folder_id), &path, report)?;
+        }
+        Ok(())
+    }
+
+    fn import_kdbx_entry(&mut self, entry: &KdbxEntry, folder_id: &str, folder_path: &str, report: &mut ImportReport) {
+        let value = |key: &str| entry.get(key).filter(|v| !v.trim().is_empty());
+        let mut fields: Vec<(String, String)> = KDBX_FIELD_TYPES.iter()
+            .filter_map(|(key, field_type)| value(key).map(|v| (field_type.to_string(), v.to_string())))
+            .collect();
+        for (key, v) in &entry.strings {
+            if key != "Title" && !KDBX_FIELD_TYPES.iter().any(|(k, _)| k == key) && !v.trim().is_empty() {
+                fields.push(("NOTE".to_string(), format!("{key}: {v}")));
+            }
+        }
+
+        let name = value("Title").or_else(|| value("URL")).or_else(|| value("UserName"));
+        let path = format!("{folder_path}/{}", name.unwrap_or_default());
+        if fields.is_empty() && name.is_none() {
+            report.record(path, Err(WalletError::ValidationError("Empty entry".to_string())));
+            return;
+        }
+        self.import_item(name.unwrap_or("KeePass"), folder_id, &fields, path, report);
+    }
+
+    /// Import the CSV export of another password manager.
+    /// See [`Wallet::import_csv_with`].
+    pub fn import_csv(&mut self, path: &Path, source: CsvSource) -> Result<ImportReport> {
+        self.import_csv_with(path, &source.mapping())
+    }
+
+    /// Import a UTF-8 CSV file whose columns map to items as `mapping`
+    /// describes.
+    ///
+    /// Items go into the folders named in the folder column, which are
+    /// created under the root unless a folder of that name is already
+    /// there, or into the root. A row whose folder already has an item of
+    /// the same name holding all of the row's field values is counted in
+    /// [`ImportReport::duplicates`] and not imported; this also catches
+    /// rows repeated within the file.
+    pub fn import_csv_with(&mut self, path: &Path, mapping: &CsvMapping) -> Result<ImportReport> {
+        self.ensure_unlocked()?;
+        let bytes = Zeroizing::new(std::fs::read(path)?);
+        self.import_csv_bytes(&bytes, mapping, ImportConflict::Skip)
+    }
+
+    /// Save `profile` under its name, replacing a profile of that name. The
+    /// profile is stored encrypted, as field values are.
+    pub fn save_import_profile(&mut self, profile: &ImportProfile) -> Result<()> {
+        self.ensure_unlocked()?;
+        if profile.name.trim().is_empty() {
+            return Err(WalletError::ValidationError("Import profile name must not be empty".to_string()));
+        }
+        let json = Zeroizing::new(serde_json::to_string(profile)
+            .map_err(|e| WalletError::json("Failed to serialize import profile", e))?);
+        let blob = self.enc_value(&json)?;
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        queries::set_import_profile_raw(conn, &profile.name, &blob)
+    }
+
+    /// The import profile called `name`
+    pub fn get_import_profile(&self, name: &str) -> Result<Option<ImportProfile>> {
+        self.ensure_unlocked()?;
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        let Some(blob) = queries::get_import_profile_raw(conn, name)? else {
+            return Ok(None);
+        };
+        let json = Zeroizing::new(self.dec_value(&blob)?);
+        serde_json::from_str(&json)
+            .map(Some)
+            .map_err(|e| WalletError::json("Invalid import profile", e))
+    }
+
+    /// Names of the saved import profiles, sorted
+    pub fn list_import_profiles(&self) -> Result<Vec<String>> {
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        queries::get_import_profile_names(conn)
+    }
+
+    /// Remove an import profile. Returns true if it existed.
+    pub fn delete_import_profile(&mut self, name: &str) -> Result<bool> {
+        self.ensure_unlocked()?;
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        queries::delete_import_profile(conn, name)
+    }
+
+    /// Import a UTF-8 CSV document from `reader` with the mapping and
+    /// conflict handling saved in the import profile called `profile`
+    pub fn rerun_import<R: Read>(&mut self, profile: &str, mut reader: R) -> Result<ImportReport> {
+        self.ensure_unlocked()?;
+        let profile = self.get_import_profile(profile)?
+            .ok_or_else(|| WalletError::InvalidOperation(format!("No import profile named {profile}")))?;
+        let mut bytes = Zeroizing::new(Vec::new());
+        reader.read_to_end(&mut bytes)?;
+        self.import_csv_bytes(&bytes, &profile.mapping, profile.conflict)
+    }
+
+    /// Import a CSV document, see [`Wallet::import_csv_with`]
+    fn import_csv_bytes(&mut self, bytes: &[u8], mapping: &CsvMapping, conflict: ImportConflict) -> Result<ImportReport> {
+        let text = std::str::from_utf8(bytes)
+            .map_err(|_| WalletError::ValidationError("CSV file is not UTF-8".to_string()))?;
+        let records = read_csv(text, mapping)?;
+
+        // Field values of existing items, by folder and lowercase name
+        let mut values_by_item: HashMap<&str, FieldValues> = HashMap::new();
+        let fields = self.get_fields()?.to_vec();
+        for f in fields.iter().filter(|f| !f.deleted) {
+            values_by_item.entry(f.item_id.as_str()).or_default().insert((f.field_type.clone(), f.value.clone()));
+        }
+        let mut existing: HashMap<(String, String), Vec<FieldValues>> = HashMap::new();
+        for item in self.get_items()?.iter().filter(|i| !i.deleted && !i.folder) {
+            let key = (item.parent_id.clone().unwrap_or_else(|| ROOT_ID.to_string()), item.name.to_lowercase());
+            existing.entry(key).or_default().push(values_by_item.remove(item.item_id.as_str()).unwrap_or_default());
+        }
+
+        let mut report = ImportReport::default();
+        let mut folders: HashMap<Vec<String>, String> = HashMap::new();
+        for record in &records {
+            let link = record.fields.iter().find(|(t, _)| t == "LINK").map(|(_, v)| v.as_str());
+            let name = Some(record.name.as_str()).filter(|n| !n.is_empty()).or(link);
+            let label = format!("line {}: {}", record.line, name.unwrap_or_default());
+            let Some(name) = name else {
+                report.record(label, Err(WalletError::ValidationError("No name".to_string())));
+                continue;
+            };
+            let parent_id = match self.import_folder_path(&record.folders, &mut folders, &mut report) {
+                Ok(id) => id,
+                Err(e) => {
+                    report.record(label, Err(e));
+                    continue;
+                }
+            };
+
+            let key = (parent_id.clone(), import_name(name).to_lowercase());
+            let values: FieldValues = record.fields.iter().cloned().collect();
+            if conflict == ImportConflict::Skip
+                && existing.get(&key).is_some_and(|items| items.iter().any(|have| values.is_subset(have))) {
+                report.duplicates += 1;
+                continue;
+            }
+            if self.import_item(name, &parent_id, &record.fields, label, &mut report) {
+                existing.entry(key).or_default().push(values);
+            }
+        }
+        Ok(report)
+    }
+
+    /// ID of the folder at `path` below the root, creating the folders
+    /// that do not exist yet
+    fn import_folder_path(&mut self, path: &[String], known: &mut HashMap<Vec<String>, String>, report: &mut ImportReport) -> Result<String> {
+        let mut parent_id = ROOT_ID.to_string();
+        for depth in 1..=path.len() {
+            let prefix = &path[..depth];
+            if let Some(id) = known.get(prefix) {
+                parent_id = id.clone();
+                continue;
+            }
+            let name = import_name(&prefix[depth - 1]);
+            let found = self.get_items_by_parent(&parent_id)?.into_iter()
+                .find(|i| i.folder && !i.deleted && i.name == name)
+                .map(|i| i.item_id);
+            parent_id = match found {
+                Some(id) => id,
+                None => {
+                    let id = self.add_item(&name, "folder", true, Some(&parent_id))?;
+                    report.folders_created += 1;
+                    id
+                }
+            };
+            known.insert(prefix.to_vec(), parent_id.clone());
+        }
+        Ok(parent_id)
+    }
+
+    /// Create an item with `fields` in `parent_id`, recording the outcome
+    /// in `report` under `label`. Returns whether the item was created.
+    fn import_item(&mut self, name: &str, parent_id: &str, fields: &[(String, String)], label: String, report: &mut ImportReport) -> bool {
+        let item_id = match self.add_item(&import_name(name), "document", false, Some(parent_id)) {
+            Ok(id) => id,
+            Err(e) => {
+                report.record(label, Err(e));
+                return false;
+            }
+        };
+        report.items_created += 1;
+
+        let mut refused = None;
+        for (field_type, value) in fields {
+            match self.add_field(&item_id, field_type, value, None) {
+                Ok(_) => report.fields_created += 1,
+                Err(e) => {
+                    refused.get_or_insert(e);
+                }
+            }
+        }
+        report.record(label, refused.map_or(Ok(item_id), Err));
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WalletError;
+    use crate::business::wallet::tests::create_test_wallet;
+    use crate::import::kdbx::tests::{entry, write_kdbx, WriteOptions};
+
+    #[test]
+    fn test_import_kdbx() {
+        let (mut wallet, temp) = create_test_wallet();
+        let root = KdbxGroup {
+            name: "Passwords".to_string(),
+            entries: vec![
+                entry(&[("Notes", "Recovery codes"), ("Password", "s3cr3t!"), ("Title", "Mail"), ("URL", "https://mail.example.com"), ("UserName", "joe")]),
+                entry(&[("Title", ""), ("UserName", "")]),
+            ],
+            groups: vec![
+                KdbxGroup {
+                    name: "Banking".to_string(),
+                    entries: vec![entry(&[("Password", "1234"), ("Title", "Bank"), ("Customer ID", "88-12")])],
+                    ..Default::default()
+                },
+                KdbxGroup {
+                    name: "Recycle Bin".to_string(),
+                    entries: vec![entry(&[("Title", "Old")])],
+                    recycle_bin: true,
+                    ..Default::default()
+                },
+            ],
+            recycle_bin: false,
+        };
+        let path = temp.path().join("keepass.kdbx");
+        std::fs::write(&path, write_kdbx(&root, "kp-pass", WriteOptions::default())).unwrap();
+
+        assert!(matches!(wallet.import_kdbx(&path, "wrong"), Err(WalletError::InvalidPassword)));
+        assert!(wallet.get_items().unwrap().iter().all(|i| i.name != "Passwords"));
+
+        let report = wallet.import_kdbx(&path, "kp-pass").unwrap();
+        assert_eq!((report.folders_created, report.items_created, report.fields_created), (2, 2, 6));
+        let skipped: Vec<&str> = report.skipped().map(|(source, _)| source).collect();
+        assert_eq!(skipped, ["/Passwords/", "/Passwords/Recycle Bin"]);
+        assert_eq!(report.records.succeeded.len(), 4);
+        let mail_id = &report.records.succeeded.iter().find(|(i, _)| report.sources[*i] == "/Passwords/Mail").unwrap().1;
+
+        let items = wallet.get_items().unwrap().to_vec();
+        let folder = items.iter().find(|i| i.name == "Passwords" && i.folder).unwrap();
+        let mail = items.iter().find(|i| i.name == "Mail").unwrap();
+        assert_eq!(&mail.item_id, mail_id);
+        assert_eq!(mail.parent_id.as_deref(), Some(folder.item_id.as_str()));
+        let fields: Vec<(String, String)> = wallet.get_fields_by_item(&mail.item_id).unwrap()
+            .into_iter().map(|f| (f.field_type, f.value)).collect();
+        assert_eq!(fields, [
+            ("USER".to_string(), "joe".to_string()),
+            ("PASS".to_string(), "s3cr3t!".to_string()),
+            ("LINK".to_string(), "https://mail.example.com".to_string()),
+            ("NOTE".to_string(), "Recovery codes".to_string()),
+        ]);
+
+        let banking = items.iter().find(|i| i.name == "Banking").unwrap();
+        let bank = items.iter().find(|i| i.name == "Bank").unwrap();
+        assert_eq!(bank.parent_id.as_deref(), Some(banking.item_id.as_str()));
+        let notes: Vec<String> = wallet.get_fields_by_item(&bank.item_id).unwrap()
+            .into_iter().filter(|f| f.field_type == "NOTE").map(|f| f.value).collect();
+        assert_eq!(notes, ["Customer ID: 88-12"]);
+        assert!(items.iter().all(|i| i.name != "Old"));
+    }
+
+    #[test]
+    fn test_import_csv_creates_folders_and_skips_duplicates() {
+        let (mut wallet, temp) = create_test_wallet();
+        let existing = wallet.add_item("Shop", "document", false, None).unwrap();
+        wallet.add_field(&existing, "USER", "ann", None).unwrap();
+        wallet.add_field(&existing, "PASS", "old", None).unwrap();
+
+        let path = temp.path().join("bitwarden.csv");
+        std::fs::write(&path, "folder,favorite,type,name,notes,fields,reprompt,login_uri,login_username,login_password,login_totp\n\
+            Work/VPN,,login,Office VPN,,,0,https://vpn.example.com,joe,\"p,w\"\"1\",\n\
+            Work/VPN,,login,office vpn,,,0,https://vpn.example.com,joe,\"p,w\"\"1\",\n\
+            Work,,note,Door code,\"Front: 4711\nBack: 0815\",,0,,,,\n\
+            ,,login,Shop,,,0,,ann,old,\n\
+            ,,login,,,,0,,,,\n\
+            ,,login,,,,0,,nobody,,\n").unwrap();
+
+        let report = wallet.import_csv(&path, CsvSource::Bitwarden).unwrap();
+        assert_eq!((report.folders_created, report.items_created, report.fields_created, report.duplicates), (2, 2, 4, 2));
+        let skipped: Vec<&str> = report.skipped().map(|(source, _)| source).collect();
+        assert_eq!(skipped, ["line 7: ", "line 8: "]);
+        assert!(report.skipped().all(|(_, e)| matches!(e, WalletError::ValidationError(_))));
+
+        let items = wallet.get_items().unwrap().to_vec();
+        let work = items.iter().find(|i| i.name == "Work" && i.folder).unwrap();
+        let vpn_folder = items.iter().find(|i| i.name == "VPN" && i.folder).unwrap();
+        assert_eq!(vpn_folder.parent_id.as_deref(), Some(work.item_id.as_str()));
+        let vpn = items.iter().find(|i| i.name == "Office VPN").unwrap();
+        assert_eq!(vpn.parent_id.as_deref(), Some(vpn_folder.item_id.as_str()));
+        let pass = wallet.get_fields_by_item(&vpn.item_id).unwrap().into_iter().find(|f| f.field_type == "PASS").unwrap();
+        assert_eq!(pass.value, "p,w\"1");
+        let door = items.iter().find(|i| i.name == "Door code").unwrap();
+        assert_eq!(door.parent_id.as_deref(), Some(work.item_id.as_str()));
+
+        // Importing again finds everything already there, folders included
+        let again = wallet.import_csv(&path, CsvSource::Bitwarden).unwrap();
+        assert_eq!((again.folders_created, again.items_created, again.duplicates), (0, 0, 4));
+    }
+
+    #[test]
+    fn test_import_profiles_and_rerun() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let mapping = CsvMapping::new("Account").folder("Group", None).field("Login", "USER").field("Secret", "PASS");
+        wallet.save_import_profile(&ImportProfile::new("HR system", mapping.clone())).unwrap();
+        assert!(matches!(
+            wallet.save_import_profile(&ImportProfile::new(" ", mapping.clone())),
+            Err(WalletError::ValidationError(_))
+        ));
+        assert_eq!(wallet.list_import_profiles().unwrap(), ["HR system"]);
+        assert_eq!(wallet.get_import_profile("HR system").unwrap().unwrap().mapping, mapping);
+        let raw = queries::get_import_profile_raw(wallet.db.as_ref().unwrap().connection().unwrap(), "HR system")
+            .unwrap()
+            .unwrap();
+        assert!(!String::from_utf8_lossy(&raw).contains("Login"), "profile must not be stored in plaintext");
+
+        let export = "Account,Group,Login,Secret\nPayroll,HR,ann,pw1\n";
+        let report = wallet.rerun_import("HR system", export.as_bytes()).unwrap();
+        assert_eq!((report.folders_created, report.items_created, report.fields_created), (1, 1, 2));
+        let again = wallet.rerun_import("HR system", export.as_bytes()).unwrap();
+        assert_eq!((again.items_created, again.duplicates), (0, 1));
+
+        // A profile that keeps both imports the same rows again
+        let keep = ImportProfile { conflict: ImportConflict::KeepBoth, ..ImportProfile::new("HR system", mapping) };
+        wallet.save_import_profile(&keep).unwrap();
+        let third = wallet.rerun_import("HR system", export.as_bytes()).unwrap();
+        assert_eq!((third.folders_created, third.items_created, third.duplicates), (0, 1, 0));
+        assert_eq!(wallet.get_items().unwrap().iter().filter(|i| i.name == "Payroll").count(), 2);
+
+        assert!(wallet.delete_import_profile("HR system").unwrap());
+        assert!(matches!(wallet.rerun_import("HR system", export.as_bytes()), Err(WalletError::InvalidOperation(_))));
+    }
+}
@@ -0,0 +1,246 @@
+//! Integrity manifest
+//!
+//! Field values are authenticated by their AEAD, but the rows around them
+//! are not: a row can be deleted, restored from an old copy of the file or
+//! moved to another item without any decryption failing. An
+//! [`IntegrityManifest`] records an HMAC-SHA256 of every row of every
+//! table, keyed by a key derived from the vault key (DEK). Exported at the
+//! end of a session and kept outside the wallet file, it lets the next
+//! session detect rows changed, added or removed out of band.
+//!
+//! Only the holder of the master password can produce or check a
+//! manifest. The DEK survives password changes, so a manifest does too.
+//! Columns the library writes while the wallet is locked (unlock
+//! throttling, the auto backup time) are not covered.
+
+use std::collections::BTreeMap;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, KeyInit, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use crate::database::queries;
+use crate::error::{WalletError, Result};
+use super::wallet::Wallet;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Manifest format written by this version
+pub const INTEGRITY_MANIFEST_VERSION: u32 = 1;
+
+/// Derivation context of the manifest key
+const MANIFEST_KEY_CONTEXT: &[u8] = b"iwcore integrity manifest v1";
+
+/// Columns left out of record MACs: written without an unlocked wallet
+const VOLATILE_COLUMNS: &[(&str, &str)] = &[
+    ("nswallet_properties", "failed_unlocks"),
+    ("nswallet_properties", "last_failed_unlock"),
+    ("nswallet_properties", "last_auto_backup"),
+];
+
+/// MAC of one row
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordMac {
+    pub table: String,
+    /// Primary key values joined with `/`
+    pub key: String,
+    /// Hex HMAC-SHA256 of the row
+    pub mac: String,
+}
+
+/// HMACs of every row, with a MAC over the manifest itself
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IntegrityManifest {
+    pub version: u32,
+    pub database_id: String,
+    pub created_at: DateTime<Utc>,
+    pub records: Vec<RecordMac>,
+    /// Hex HMAC-SHA256 over all of the above
+    pub mac: String,
+}
+
+/// A row named by table and primary key
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct RecordRef {
+    pub table: String,
+    pub key: String,
+}
+
+/// Differences found by [`Wallet::verify_integrity_manifest`]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IntegrityReport {
+    /// Rows whose content differs from the manifest
+    pub modified: Vec<RecordRef>,
+    /// Rows not in the manifest
+    pub added: Vec<RecordRef>,
+    /// Rows in the manifest that are gone
+    pub removed: Vec<RecordRef>,
+}
+
+impl IntegrityReport {
+    /// Whether the wallet matches the manifest
+    pub fn is_intact(&self) -> bool {
+        self.modified.is_empty() && self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn new_mac(key: &[u8]) -> HmacSha256 {
+    <HmacSha256 as KeyInit>::new_from_slice(key).expect("HMAC accepts keys of any length")
+}
+
+/// MAC of the manifest header and records
+fn manifest_mac(key: &[u8], version: u32, database_id: &str, created_at: &DateTime<Utc>, records: &[RecordMac]) -> HmacSha256 {
+    let mut mac = new_mac(key);
+    mac.update(&version.to_le_bytes());
+    mac.update(database_id.as_bytes());
+    mac.update(&[0]);
+    mac.update(created_at.to_rfc3339().as_bytes());
+    for record in records {
+        for part in [&record.table, &record.key, &record.mac] {
+            mac.update(&(part.len() as u64).to_le_bytes());
+            mac.update(part.as_bytes());
+        }
+    }
+    mac
+}
+
+impl Wallet {
+    /// Key of record and manifest MACs, derived from the DEK
+    fn manifest_key(&self) -> Result<[u8; 32]> {
+        let mut mac = new_mac(self.dek()?);
+        mac.update(MANIFEST_KEY_CONTEXT);
+        Ok(mac.finalize().into_bytes().into())
+    }
+
+    /// MACs of the current rows, sorted by table and key
+    fn record_macs(&self, key: &[u8]) -> Result<Vec<RecordMac>> {
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        let mut records = Vec::new();
+        for table in queries::list_user_tables(conn)? {
+            let skip: Vec<&str> = VOLATILE_COLUMNS.iter()
+                .filter(|(t, _)| *t == table)
+                .map(|(_, c)| *c)
+                .collect();
+            for (row_key, bytes) in queries::get_canonical_rows(conn, &table, &skip)? {
+                let mut mac = new_mac(key);
+                mac.update(&(table.len() as u64).to_le_bytes());
+                mac.update(table.as_bytes());
+                mac.update(&(row_key.len() as u64).to_le_bytes());
+                mac.update(row_key.as_bytes());
+                mac.update(&bytes);
+                records.push(RecordMac {
+                    table: table.clone(),
+                    key: row_key,
+                    mac: hex(&mac.finalize().into_bytes()),
+                });
+            }
+        }
+        records.sort_by(|a, b| (&a.table, &a.key).cmp(&(&b.table, &b.key)));
+        Ok(records)
+    }
+
+    /// HMACs of every row, for [`Wallet::verify_integrity_manifest`] in a
+    /// later session. Store the manifest outside the wallet folder.
+    pub fn export_integrity_manifest(&self) -> Result<IntegrityManifest> {
+        let key = zeroize::Zeroizing::new(self.manifest_key()?);
+        let database_id = self.get_properties()?.database_id;
+        let records = self.record_macs(&*key)?;
+        let created_at = Utc::now();
+        let mac = manifest_mac(&*key, INTEGRITY_MANIFEST_VERSION, &database_id, &created_at, &records);
+        Ok(IntegrityManifest {
+            version: INTEGRITY_MANIFEST_VERSION,
+            database_id,
+            created_at,
+            records,
+            mac: hex(&mac.finalize().into_bytes()),
+        })
+    }
+
+    /// Compare the wallet with a manifest from
+    /// [`Wallet::export_integrity_manifest`]. Fails with `IntegrityError`
+    /// when the manifest itself was altered, is of another version or
+    /// belongs to another wallet.
+    pub fn verify_integrity_manifest(&self, manifest: &IntegrityManifest) -> Result<IntegrityReport> {
+        if manifest.version != INTEGRITY_MANIFEST_VERSION {
+            return Err(WalletError::IntegrityError(format!(
+                "Unsupported manifest version {}", manifest.version
+            )));
+        }
+        let key = zeroize::Zeroizing::new(self.manifest_key()?);
+        let expected = (0..manifest.mac.len() / 2)
+            .map(|i| u8::from_str_radix(manifest.mac.get(i * 2..i * 2 + 2).unwrap_or(""), 16))
+            .collect::<std::result::Result<Vec<u8>, _>>()
+            .map_err(|_| WalletError::IntegrityError("Malformed manifest MAC".to_string()))?;
+        manifest_mac(&*key, manifest.version, &manifest.database_id, &manifest.created_at, &manifest.records)
+            .verify_slice(&expected)
+            .map_err(|_| WalletError::IntegrityError(
+                "Manifest does not verify: altered, or made for another wallet".to_string()
+            ))?;
+        if manifest.database_id != self.get_properties()?.database_id {
+            return Err(WalletError::IntegrityError("Manifest belongs to another wallet".to_string()));
+        }
+
+        let mut expected: BTreeMap<RecordRef, &str> = manifest.records.iter()
+            .map(|r| (RecordRef { table: r.table.clone(), key: r.key.clone() }, r.mac.as_str()))
+            .collect();
+        let mut report = IntegrityReport::default();
+        for record in self.record_macs(&*key)? {
+            let id = RecordRef { table: record.table, key: record.key };
+            match expected.remove(&id) {
+                Some(mac) if mac == record.mac => {}
+                Some(_) => report.modified.push(id),
+                None => report.added.push(id),
+            }
+        }
+        report.removed = expected.into_keys().collect();
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::business::wallet::tests::create_test_wallet;
+
+    #[test]
+    fn test_integrity_manifest_detects_changes() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let bank = wallet.add_item("Bank", "document", false, None).unwrap();
+        wallet.add_field(&bank, "PASS", "secret", None).unwrap();
+        let mail = wallet.add_item("Mail", "document", false, None).unwrap();
+
+        let manifest = wallet.export_integrity_manifest().unwrap();
+        // Survives a JSON round trip and a new session
+        let manifest: IntegrityManifest = serde_json::from_str(&serde_json::to_string(&manifest).unwrap()).unwrap();
+        wallet.lock();
+        wallet.unlock("TestPassword123").unwrap();
+        assert!(wallet.verify_integrity_manifest(&manifest).unwrap().is_intact());
+
+        // Out-of-band edits: change an item, drop one, insert a label
+        {
+            let conn = wallet.db.as_ref().unwrap().connection().unwrap();
+            conn.execute("UPDATE nswallet_items SET icon = 'bank' WHERE item_id = ?", [&bank]).unwrap();
+            conn.execute("DELETE FROM nswallet_items WHERE item_id = ?", [&mail]).unwrap();
+            conn.execute(
+                "INSERT INTO nswallet_labels (field_type, label_name, value_type, icon, system, change_timestamp, deleted) \
+                 VALUES ('EVIL', 'Evil', 'text', 'x', 0, '2024-01-01 00:00:00', 0)",
+                [],
+            ).unwrap();
+        }
+        let report = wallet.verify_integrity_manifest(&manifest).unwrap();
+        assert!(!report.is_intact());
+        assert_eq!(report.modified, [RecordRef { table: "nswallet_items".into(), key: bank.clone() }]);
+        assert_eq!(report.removed, [RecordRef { table: "nswallet_items".into(), key: mail }]);
+        assert_eq!(report.added, [RecordRef { table: "nswallet_labels".into(), key: "EVIL".into() }]);
+
+        // A tampered manifest is refused
+        let mut forged = manifest.clone();
+        forged.records.retain(|r| r.key != bank);
+        assert!(matches!(wallet.verify_integrity_manifest(&forged), Err(WalletError::IntegrityError(_))));
+    }
+}
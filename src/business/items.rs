@@ -2,14 +2,47 @@
 //!
 //! This module provides item management operations for the Wallet.
 
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+
 use chrono::Utc;
+use rusqlite::Connection;
 use crate::error::{WalletError, Result};
-use crate::database::{IWItem, queries};
-use crate::database::queries::parse_timestamp;
-use crate::utils::generate_item_id;
-use crate::ROOT_ID;
+use crate::database::{CopyOptions, IWField, IWItem, SortOrder, queries};
+use crate::database::queries::{parse_timestamp, RawItem};
+use crate::{ITEM_NAME_MAX_LENGTH, ROOT_ID};
+use super::folder_defaults::FolderDefaults;
+use super::ids::IdKind;
 use super::wallet::Wallet;
 
+/// Normalize an item color to lowercase `#rrggbb`. Accepts `#rgb` and
+/// `#rrggbb`, with or without the leading `#`.
+fn normalize_color(color: &str) -> Result<String> {
+    let hex = color.trim().trim_start_matches('#');
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(WalletError::ValidationError(format!("Invalid color: {color}")));
+    }
+    let hex = hex.to_ascii_lowercase();
+    match hex.len() {
+        6 => Ok(format!("#{hex}")),
+        3 => Ok(hex.chars().fold(String::from("#"), |mut s, c| {
+            s.push(c);
+            s.push(c);
+            s
+        })),
+        _ => Err(WalletError::ValidationError(format!("Invalid color: {color}"))),
+    }
+}
+
+/// Reject names longer than `ITEM_NAME_MAX_LENGTH` characters
+fn check_name_length(name: &str) -> Result<()> {
+    let len = name.chars().count();
+    if len > ITEM_NAME_MAX_LENGTH {
+        return Err(WalletError::NameTooLong { len, max: ITEM_NAME_MAX_LENGTH });
+    }
+    Ok(())
+}
+
 impl Wallet {
     /// Get all items (decrypted)
     pub fn get_items(&mut self) -> Result<&[IWItem]> {
@@ -26,6 +59,11 @@ impl Wallet {
 
     /// Get items by parent ID
     pub fn get_items_by_parent(&mut self, parent_id: &str) -> Result<Vec<IWItem>> {
+        self.get_items_by_parent_sorted(parent_id, SortOrder::NameAsc)
+    }
+
+    /// Get items by parent ID in the given order
+    pub fn get_items_by_parent_sorted(&mut self, parent_id: &str, order: SortOrder) -> Result<Vec<IWItem>> {
         let items = self.get_items()?;
         let mut result: Vec<IWItem> = items
             .iter()
@@ -33,14 +71,32 @@ impl Wallet {
             .cloned()
             .collect();
 
-        // Sort: folders first, then by name
-        result.sort_by(|a, b| {
-            match (a.folder, b.folder) {
-                (true, false) => std::cmp::Ordering::Less,
-                (false, true) => std::cmp::Ordering::Greater,
-                _ => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
-            }
-        });
+        let by_name = |a: &IWItem, b: &IWItem| a.name.to_lowercase().cmp(&b.name.to_lowercase());
+        let folders_first = |a: &IWItem, b: &IWItem| b.folder.cmp(&a.folder);
+        match order {
+            SortOrder::NameAsc => result.sort_by(|a, b| folders_first(a, b).then_with(|| by_name(a, b))),
+            SortOrder::NameDesc => result.sort_by(|a, b| folders_first(a, b).then_with(|| by_name(b, a))),
+            SortOrder::CreatedDesc => result.sort_by(|a, b| {
+                folders_first(a, b)
+                    .then_with(|| b.create_timestamp.cmp(&a.create_timestamp))
+                    .then_with(|| by_name(a, b))
+            }),
+            SortOrder::ModifiedDesc => result.sort_by(|a, b| {
+                folders_first(a, b)
+                    .then_with(|| b.change_timestamp.cmp(&a.change_timestamp))
+                    .then_with(|| by_name(a, b))
+            }),
+            // Arranged items (Some) before the rest (None), by weight
+            SortOrder::Custom => result.sort_by(|a, b| {
+                match (a.sort_weight, b.sort_weight) {
+                    (Some(x), Some(y)) => x.cmp(&y),
+                    (Some(_), None) => std::cmp::Ordering::Less,
+                    (None, Some(_)) => std::cmp::Ordering::Greater,
+                    (None, None) => std::cmp::Ordering::Equal,
+                }
+                .then_with(|| by_name(a, b))
+            }),
+        }
 
         Ok(result)
     }
@@ -52,6 +108,7 @@ impl Wallet {
         }
 
         self.ensure_unlocked()?;
+        let started = Instant::now();
 
         let raw_items = {
             let conn = self.db.as_ref()
@@ -60,61 +117,167 @@ impl Wallet {
             queries::get_all_items_raw(conn)?
         };
 
-        let mut items = Vec::with_capacity(raw_items.len());
-
-        for raw in raw_items {
-            // NULL name columns arrive as empty blobs (COALESCE in the query);
-            // they carry no ciphertext, so the name is simply empty.
-            let name = if raw.name_encrypted.is_empty() {
-                String::new()
-            } else {
-                self.dec_value(&raw.name_encrypted)?
-            };
-
-            items.push(IWItem {
-                item_id: raw.item_id,
-                parent_id: raw.parent_id,
-                name,
-                icon: raw.icon,
-                folder: raw.folder,
-                create_timestamp: raw.create_timestamp
-                    .as_ref()
-                    .and_then(|s| parse_timestamp(s))
-                    .unwrap_or_else(Utc::now),
-                change_timestamp: raw.change_timestamp
-                    .as_ref()
-                    .and_then(|s| parse_timestamp(s))
-                    .unwrap_or_else(Utc::now),
-                deleted: raw.deleted,
-            });
-        }
+        let items = raw_items.into_iter()
+            .filter_map(|raw| self.item_from_raw(raw))
+            .collect();
 
         self.items_cache = Some(items);
+        self.record_cache_warm(started);
         Ok(())
     }
 
+    /// Decrypt an active item row, opening its metadata envelope
+    pub(crate) fn item_from_raw(&self, mut raw: RawItem) -> Option<IWItem> {
+        self.open_item_envelope(&mut raw);
+        // NULL name columns arrive as empty blobs (COALESCE in the query);
+        // they carry no ciphertext, so the name is simply empty. A row
+        // that does not decrypt is skipped rather than failing the whole
+        // listing; see `get_undecryptable_records`.
+        let name = if raw.name_encrypted.is_empty() {
+            String::new()
+        } else {
+            self.dec_value(&raw.name_encrypted).ok()?
+        };
+
+        Some(IWItem {
+            item_id: raw.item_id,
+            parent_id: raw.parent_id,
+            name,
+            icon: raw.icon,
+            folder: raw.folder,
+            create_timestamp: raw.create_timestamp
+                .as_ref()
+                .and_then(|s| parse_timestamp(s))
+                .unwrap_or_else(Utc::now),
+            change_timestamp: raw.change_timestamp
+                .as_ref()
+                .and_then(|s| parse_timestamp(s))
+                .unwrap_or_else(Utc::now),
+            deleted: raw.deleted,
+            primary_field: raw.field_id,
+            color: raw.color,
+            sort_weight: raw.sort_weight,
+            locked: raw.locked,
+        })
+    }
+
     /// Create a new item
     pub fn add_item(&mut self, name: &str, icon: &str, folder: bool, parent_id: Option<&str>) -> Result<String> {
+        self.add_item_with(name, icon, folder, parent_id, |_, _, _| Ok(()))
+    }
+
+    /// `add_item`, then `extra` with the new item's ID, in one transaction:
+    /// if `extra` fails, no item is created.
+    pub(crate) fn add_item_with(
+        &mut self,
+        name: &str,
+        icon: &str,
+        folder: bool,
+        parent_id: Option<&str>,
+        extra: impl FnOnce(&Wallet, &Connection, &str) -> Result<()>,
+    ) -> Result<String> {
         self.ensure_unlocked()?;
 
-        let item_id = generate_item_id();
+        check_name_length(name)?;
+
+        let item_id = self.unique_id(IdKind::Item, &[])?;
         let parent = parent_id.unwrap_or(ROOT_ID);
 
         let encrypted_name = self.enc_value(name)?;
 
+        let defaults = {
+            let conn = self.db.as_ref()
+                .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+                .connection()?;
+            if !queries::item_exists(conn, parent)? {
+                return Err(WalletError::ParentNotFound(parent.to_string()));
+            }
+            FolderDefaults::inherited(conn, parent)?
+        };
+        let icon = match defaults.icon.as_deref() {
+            Some(default_icon) if icon.is_empty() => default_icon,
+            _ => icon,
+        };
+
+        // Template fields of the folder defaults, with empty values
+        let mut template = Vec::new();
+        if !folder && !defaults.template.is_empty() {
+            let empty = self.enc_value("")?;
+            let mut reserved = Vec::with_capacity(defaults.template.len());
+            for _ in &defaults.template {
+                let field_id = self.unique_id(IdKind::Field, &reserved)?;
+                reserved.push(field_id);
+            }
+            template = defaults.template.iter().zip(reserved).map(|(t, id)| (t, id, empty.clone())).collect();
+        }
+
+        self.db.as_mut()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .begin_transaction()?;
+        let pass = (|| -> Result<()> {
+            let conn = self.db.as_ref().unwrap().connection()?;
+            queries::create_item_no_checkpoint(conn, &item_id, parent, &encrypted_name, icon, folder)?;
+            for (i, (field_type, field_id, empty)) in template.iter().enumerate() {
+                queries::create_field_no_checkpoint(conn, &item_id, field_id, field_type, empty, (i as i32 + 1) * 100)?;
+            }
+            extra(self, conn, &item_id)
+        })();
+        let db = self.db.as_mut().unwrap();
+        match pass {
+            Ok(()) => db.commit_transaction()?,
+            Err(e) => {
+                let _ = db.rollback_transaction();
+                return Err(e);
+            }
+        }
+        let _ = db.checkpoint();
+
+        if !template.is_empty() {
+            self.fields_cache = None;
+        }
+        self.items_cache = None;
+        self.note_mutation();
+        Ok(item_id)
+    }
+
+    /// Lock or unlock an item against edits. A locked item (e.g. a seed
+    /// phrase) cannot be renamed, moved, deleted or have its fields and
+    /// note changed until it is unlocked again; bulk operations skip it.
+    pub fn set_item_locked(&mut self, item_id: &str, locked: bool) -> Result<()> {
+        self.ensure_unlocked()?;
+        if item_id == ROOT_ID {
+            return Err(WalletError::InvalidOperation(
+                "Cannot lock the root folder".to_string(),
+            ));
+        }
+
         let conn = self.db.as_ref()
             .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
             .connection()?;
 
-        queries::create_item(conn, &item_id, parent, &encrypted_name, icon, folder)?;
+        queries::update_item_locked(conn, item_id, locked)?;
 
         self.items_cache = None;
-        Ok(item_id)
+        self.note_mutation();
+        Ok(())
+    }
+
+    /// Fail with `ItemLocked` if the item is locked against edits
+    pub(crate) fn ensure_item_editable(&self, item_id: &str) -> Result<()> {
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        if queries::is_item_locked(conn, item_id)? {
+            return Err(WalletError::ItemLocked(item_id.to_string()));
+        }
+        Ok(())
     }
 
     /// Update item name
     pub fn update_item_name(&mut self, item_id: &str, name: &str) -> Result<()> {
         self.ensure_unlocked()?;
+        check_name_length(name)?;
+        self.ensure_item_editable(item_id)?;
 
         let encrypted_name = self.enc_value(name)?;
 
@@ -125,11 +288,13 @@ impl Wallet {
         queries::update_item_name(conn, item_id, &encrypted_name)?;
 
         self.items_cache = None;
+        self.note_mutation();
         Ok(())
     }
 
     /// Update item icon
     pub fn update_item_icon(&mut self, item_id: &str, icon: &str) -> Result<()> {
+        self.ensure_item_editable(item_id)?;
         let conn = self.db.as_ref()
             .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
             .connection()?;
@@ -137,6 +302,66 @@ impl Wallet {
         queries::update_item_icon(conn, item_id, icon)?;
 
         self.items_cache = None;
+        self.note_mutation();
+        Ok(())
+    }
+
+    /// Set the item's display color (`#rgb` or `#rrggbb`, stored as
+    /// `#rrggbb`), or clear it with `None`
+    pub fn set_item_color(&mut self, item_id: &str, color: Option<&str>) -> Result<()> {
+        let color = color.map(normalize_color).transpose()?;
+        self.ensure_item_editable(item_id)?;
+
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+
+        queries::update_item_color(conn, item_id, color.as_deref())?;
+
+        self.items_cache = None;
+        self.note_mutation();
+        Ok(())
+    }
+
+    /// Mark `field_id` as the item's primary field, the one surfaced for
+    /// one-tap copy/autofill (usually PASS or 2FAC). The field must be an
+    /// active field of the item. The choice follows the field through
+    /// `update_field`, which gives the new version a new ID.
+    pub fn set_primary_field(&mut self, item_id: &str, field_id: &str) -> Result<()> {
+        self.ensure_unlocked()?;
+        if !self.get_fields_by_item(item_id)?.iter().any(|f| f.field_id == field_id) {
+            return Err(WalletError::FieldNotFound(field_id.to_string()));
+        }
+        self.write_primary_field(item_id, Some(field_id))
+    }
+
+    /// Clear the item's primary field
+    pub fn clear_primary_field(&mut self, item_id: &str) -> Result<()> {
+        self.ensure_unlocked()?;
+        self.write_primary_field(item_id, None)
+    }
+
+    /// The item's primary field, or `None` if none is set or the field has
+    /// since been deleted
+    pub fn get_primary_field(&mut self, item_id: &str) -> Result<Option<IWField>> {
+        let item = self.get_item(item_id)?
+            .ok_or_else(|| WalletError::ItemNotFound(item_id.to_string()))?;
+        let Some(primary) = item.primary_field else {
+            return Ok(None);
+        };
+        Ok(self.get_fields_by_item(item_id)?.into_iter().find(|f| f.field_id == primary))
+    }
+
+    fn write_primary_field(&mut self, item_id: &str, field_id: Option<&str>) -> Result<()> {
+        self.ensure_item_editable(item_id)?;
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+
+        queries::update_item_primary_field(conn, item_id, field_id)?;
+
+        self.items_cache = None;
+        self.note_mutation();
         Ok(())
     }
 
@@ -144,26 +369,111 @@ impl Wallet {
     /// reparenting it orphans the entire tree and makes the wallet
     /// unrecoverable.
     pub fn move_item(&mut self, item_id: &str, new_parent_id: &str) -> Result<()> {
+        self.ensure_unlocked()?;
         if item_id == ROOT_ID {
             return Err(WalletError::InvalidOperation(
                 "Cannot move the root folder".to_string(),
             ));
         }
+        self.ensure_item_editable(item_id)?;
 
-        let conn = self.db.as_ref()
-            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
-            .connection()?;
+        {
+            let conn = self.db.as_ref()
+                .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+                .connection()?;
+            if !queries::item_exists(conn, new_parent_id)? {
+                return Err(WalletError::ParentNotFound(new_parent_id.to_string()));
+            }
+        }
+        // Fields and item data change key when the move crosses a folder
+        // PIN boundary
+        let resealed = self.reseal_for_move(item_id, new_parent_id)?;
+
+        let db = self.db.as_mut()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?;
+        db.begin_transaction()?;
+
+        let pass = (|| -> Result<()> {
+            let conn = db.connection()?;
+            queries::update_item_parent_no_checkpoint(conn, item_id, new_parent_id)?;
+            resealed.store(conn)
+        })();
+
+        match pass {
+            Ok(()) => db.commit_transaction()?,
+            Err(e) => {
+                let _ = db.rollback_transaction();
+                return Err(e);
+            }
+        }
+        let _ = self.db.as_ref().unwrap().checkpoint();
+
+        self.items_cache = None;
+        if !resealed.is_empty() {
+            self.fields_cache = None;
+        }
+        self.note_mutation();
+        Ok(())
+    }
+
+    /// Persist a user-arranged order for the children of `parent_id`, read
+    /// back with [`SortOrder::Custom`]. Items get weights 100, 200, ... in
+    /// the given order, like field weights; children left out of
+    /// `item_ids` lose their weight and sort after the arranged ones. Fails
+    /// with `ItemLocked` if any child is locked.
+    pub fn reorder_items(&mut self, parent_id: &str, item_ids: &[&str]) -> Result<()> {
+        self.ensure_unlocked()?;
+        let children: HashSet<String> = self.get_items_by_parent(parent_id)?
+            .into_iter()
+            .map(|i| i.item_id)
+            .collect();
+        let mut seen = HashSet::new();
+        for &id in item_ids {
+            if !children.contains(id) {
+                return Err(WalletError::ItemNotFound(id.to_string()));
+            }
+            if !seen.insert(id) {
+                return Err(WalletError::InvalidOperation(format!("Item {id} listed twice")));
+            }
+        }
+        // Every child gets a new weight, listed or not
+        for id in &children {
+            self.ensure_item_editable(id)?;
+        }
 
-        queries::update_item_parent(conn, item_id, new_parent_id)?;
+        let db = self.db.as_mut()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?;
+        db.begin_transaction()?;
+
+        let pass = (|| -> Result<()> {
+            let conn = db.connection()?;
+            for (i, id) in item_ids.iter().enumerate() {
+                queries::update_item_sort_weight(conn, id, Some((i as i32 + 1) * 100))?;
+            }
+            for id in children.iter().filter(|id| !seen.contains(id.as_str())) {
+                queries::update_item_sort_weight(conn, id, None)?;
+            }
+            Ok(())
+        })();
+
+        match pass {
+            Ok(()) => db.commit_transaction()?,
+            Err(e) => {
+                let _ = db.rollback_transaction();
+                return Err(e);
+            }
+        }
+        let _ = self.db.as_ref().unwrap().checkpoint();
 
         self.items_cache = None;
+        self.note_mutation();
         Ok(())
     }
 
     /// Delete an item (soft delete). If the item is a folder, cascades
     /// to all descendants. Refuses to delete the root folder — its
     /// encrypted name is the wallet's password check, so removing it
-    /// makes subsequent unlocks fail.
+    /// makes subsequent unlocks fail — and folders holding locked items.
     pub fn delete_item(&mut self, item_id: &str) -> Result<()> {
         self.ensure_unlocked()?;
 
@@ -182,6 +492,9 @@ impl Wallet {
             .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
             .connection()?;
 
+        if let Some(locked) = queries::get_locked_in_subtree(conn, item_id)?.into_iter().next() {
+            return Err(WalletError::ItemLocked(locked));
+        }
         if is_folder {
             queries::delete_item_descendants(conn, item_id)?;
         }
@@ -190,6 +503,7 @@ impl Wallet {
 
         self.items_cache = None;
         self.fields_cache = None;
+        self.note_mutation();
         Ok(())
     }
 
@@ -206,7 +520,8 @@ impl Wallet {
 
         let mut items = Vec::with_capacity(raw_items.len());
 
-        for raw in raw_items {
+        for mut raw in raw_items {
+            self.open_item_envelope(&mut raw);
             let name = match self.dec_value(&raw.name_encrypted) {
                 Ok(v) => v,
                 Err(_) => continue,
@@ -227,6 +542,10 @@ impl Wallet {
                     .and_then(|s| parse_timestamp(s))
                     .unwrap_or_else(Utc::now),
                 deleted: raw.deleted,
+                primary_field: raw.field_id,
+                color: raw.color,
+                sort_weight: raw.sort_weight,
+                locked: raw.locked,
             });
         }
 
@@ -236,6 +555,7 @@ impl Wallet {
     /// Restore a soft-deleted item
     pub fn undelete_item(&mut self, item_id: &str) -> Result<()> {
         self.ensure_unlocked()?;
+        self.ensure_item_editable(item_id)?;
 
         let conn = self.db.as_ref()
             .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
@@ -244,27 +564,175 @@ impl Wallet {
         queries::undelete_item(conn, item_id)?;
 
         self.items_cache = None;
+        self.note_mutation();
         Ok(())
     }
 
+    /// Name for a copy of `name` created in `parent`: the localized "Copy of
+    /// {name}" template in the wallet's language, with " (2)", " (3)", ...
+    /// appended while an item of the same name exists in `parent`.
+    fn copy_name_for(&mut self, name: &str, parent: &str) -> Result<String> {
+        let base = self.translations()?.format("item_copy_name", &[("name", name)]);
+
+        let taken: HashSet<String> = self.get_items_by_parent(parent)?
+            .into_iter()
+            .map(|i| i.name)
+            .collect();
+
+        if !taken.contains(&base) {
+            return Ok(base);
+        }
+        let mut n = 2;
+        loop {
+            let candidate = format!("{base} ({n})");
+            if !taken.contains(&candidate) {
+                return Ok(candidate);
+            }
+            n += 1;
+        }
+    }
+
+    /// Active folders (root excluded) that contain no active items or
+    /// folders, sorted by name. Nested empty folders make their parent
+    /// non-empty until they are removed themselves.
+    pub fn get_empty_folders(&mut self) -> Result<Vec<IWItem>> {
+        let ids = {
+            let conn = self.db.as_ref()
+                .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+                .connection()?;
+            queries::get_empty_folder_ids(conn)?
+        };
+        self.items_with_ids(ids)
+    }
+
+    /// Active entries (not folders) that have no active fields, sorted by name
+    pub fn get_items_without_fields(&mut self) -> Result<Vec<IWItem>> {
+        let ids = {
+            let conn = self.db.as_ref()
+                .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+                .connection()?;
+            queries::get_item_ids_without_fields(conn)?
+        };
+        self.items_with_ids(ids)
+    }
+
+    fn items_with_ids(&mut self, ids: Vec<String>) -> Result<Vec<IWItem>> {
+        let ids: HashSet<String> = ids.into_iter().collect();
+        let mut result: Vec<IWItem> = self.get_items()?
+            .iter()
+            .filter(|i| ids.contains(&i.item_id))
+            .cloned()
+            .collect();
+        result.sort_by_key(|i| i.name.to_lowercase());
+        Ok(result)
+    }
+
     /// Copy an item (and optionally its fields)
     pub fn copy_item(&mut self, source_item_id: &str) -> Result<String> {
+        self.copy_item_with(source_item_id, &CopyOptions::default())
+    }
+
+    /// Copy an item and its fields as `options` asks. The copy is named
+    /// like `copy_item` names it. Folders are copied without their
+    /// contents; see `copy_item_deep`.
+    pub fn copy_item_with(&mut self, source_item_id: &str, options: &CopyOptions) -> Result<String> {
         self.ensure_unlocked()?;
 
         let source_item = self.get_item(source_item_id)?
-            .ok_or_else(|| WalletError::InvalidOperation("Item not found".to_string()))?;
+            .ok_or_else(|| WalletError::ItemNotFound(source_item_id.to_string()))?;
+        let parent = options.target_parent.clone()
+            .or_else(|| source_item.parent_id.clone())
+            .unwrap_or_else(|| ROOT_ID.to_string());
+
+        let new_name = self.copy_name_for(&source_item.name, &parent)?;
+        self.copy_item_into(&source_item, &new_name, &parent, options)
+    }
+
+    /// Copy a folder with everything below it, or an entry like
+    /// `copy_item_with`. Only the top copy is renamed; the items inside
+    /// keep their names. Returns the ID of the top copy.
+    pub fn copy_item_deep(&mut self, source_item_id: &str, options: &CopyOptions) -> Result<String> {
+        self.ensure_unlocked()?;
+
+        // Active items below the source, parents before children, taken
+        // before anything is copied
+        let items = self.get_items()?;
+        let children_of = |parent: &str| items.iter()
+            .filter(|i| !i.deleted && i.parent_id.as_deref() == Some(parent))
+            .cloned()
+            .collect::<Vec<IWItem>>();
+        let mut subtree = children_of(source_item_id);
+        let mut next = 0;
+        while next < subtree.len() {
+            let children = children_of(&subtree[next].item_id);
+            subtree.extend(children);
+            next += 1;
+        }
+        if let Some(target) = options.target_parent.as_deref()
+            && (target == source_item_id || subtree.iter().any(|i| i.item_id == target)) {
+                return Err(WalletError::InvalidOperation(format!("Cannot copy {source_item_id} into itself")));
+            }
 
-        let copy_prefix = "Copy of ";
-        let new_name = format!("{}{}", copy_prefix, source_item.name);
+        let top = self.copy_item_with(source_item_id, options)?;
+        let mut copies = HashMap::from([(source_item_id.to_string(), top.clone())]);
+        for item in &subtree {
+            let parent = item.parent_id.as_deref()
+                .and_then(|p| copies.get(p))
+                .cloned()
+                .ok_or_else(|| WalletError::ParentNotFound(item.item_id.clone()))?;
+            let copy = self.copy_item_into(item, &item.name, &parent, options)?;
+            copies.insert(item.item_id.clone(), copy);
+        }
+        Ok(top)
+    }
 
-        let new_item_id = self.add_item(&new_name, &source_item.icon, source_item.folder, source_item.parent_id.as_deref())?;
+    /// Create `name` under `parent_id` as a copy of `source`
+    fn copy_item_into(&mut self, source: &IWItem, name: &str, parent_id: &str, options: &CopyOptions) -> Result<String> {
+        let new_item_id = self.add_item(name, &source.icon, source.folder, Some(parent_id))?;
+        if source.color.is_some() {
+            self.set_item_color(&new_item_id, source.color.as_deref())?;
+        }
 
-        // Copy fields if it's not a folder
-        if !source_item.folder {
-            let fields = self.get_fields_by_item(source_item_id)?;
+        // New field IDs with the change time of the field they copy
+        let mut stamps = Vec::new();
+        if !source.folder {
+            let fields = self.get_fields_by_item(&source.item_id)?;
             for field in fields {
-                self.add_field(&new_item_id, &field.field_type, &field.value, Some(field.sort_weight))?;
+                let new_field_id = self.add_field(&new_item_id, &field.field_type, &field.value, Some(field.sort_weight))?;
+                if source.primary_field.as_deref() == Some(field.field_id.as_str()) {
+                    self.write_primary_field(&new_item_id, Some(&new_field_id))?;
+                }
+                stamps.push((new_field_id, field.change_timestamp));
+            }
+            if options.copy_history {
+                let history: Vec<IWField> = self.get_deleted_fields()?.into_iter()
+                    .filter(|f| f.item_id == source.item_id)
+                    .collect();
+                for field in history {
+                    let new_field_id = self.add_field(&new_item_id, &field.field_type, &field.value, Some(field.sort_weight))?;
+                    self.delete_field(&new_item_id, &new_field_id)?;
+                    stamps.push((new_field_id, field.change_timestamp));
+                }
+            }
+        }
+        if options.copy_tags {
+            let metadata = self.get_item_metadata(&source.item_id)?;
+            if !metadata.is_empty() {
+                self.write_item_metadata(&new_item_id, &metadata)?;
+            }
+        }
+
+        if options.preserve_timestamps {
+            {
+                let conn = self.db.as_ref()
+                    .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+                    .connection()?;
+                for (field_id, changed) in &stamps {
+                    queries::set_field_change_timestamp(conn, &new_item_id, field_id, changed)?;
+                }
+                queries::set_item_timestamps(conn, &new_item_id, &source.create_timestamp, &source.change_timestamp)?;
             }
+            self.clear_caches();
         }
 
         Ok(new_item_id)
@@ -275,6 +743,7 @@ impl Wallet {
 mod tests {
     use super::*;
     use crate::business::wallet::tests::create_test_wallet;
+    use crate::business::replace::ReplaceScope;
 
     #[test]
     fn test_create_item() {
@@ -295,6 +764,95 @@ mod tests {
         assert!(item.is_none());
     }
 
+    #[test]
+    fn test_get_items_by_parent_sorted() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let b = wallet.add_item("Bravo", "document", false, None).unwrap();
+        let a = wallet.add_item("alpha", "document", false, None).unwrap();
+        let f = wallet.add_item("Zulu folder", "folder", true, None).unwrap();
+        let c = wallet.add_item("Charlie", "document", false, None).unwrap();
+        {
+            let conn = wallet.db.as_ref().unwrap().connection().unwrap();
+            conn.execute("UPDATE nswallet_items SET create_timestamp = '2020-01-01 00:00:00' WHERE item_id = ?", [&c]).unwrap();
+            conn.execute("UPDATE nswallet_items SET sort_weight = 1 WHERE item_id = ?", [&c]).unwrap();
+            conn.execute("UPDATE nswallet_items SET sort_weight = 2 WHERE item_id = ?", [&f]).unwrap();
+        }
+        wallet.clear_caches();
+
+        let ids = |wallet: &mut Wallet, order| -> Vec<String> {
+            wallet.get_items_by_parent_sorted(ROOT_ID, order).unwrap()
+                .into_iter().map(|i| i.item_id).collect()
+        };
+        assert_eq!(ids(&mut wallet, SortOrder::NameAsc), vec![f.clone(), a.clone(), b.clone(), c.clone()]);
+        assert_eq!(ids(&mut wallet, SortOrder::NameDesc), vec![f.clone(), c.clone(), b.clone(), a.clone()]);
+        assert_eq!(ids(&mut wallet, SortOrder::CreatedDesc).last(), Some(&c));
+        assert_eq!(ids(&mut wallet, SortOrder::Custom), vec![c, f, a, b]);
+    }
+
+    #[test]
+    fn test_reorder_items() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let a = wallet.add_item("Alpha", "document", false, None).unwrap();
+        let b = wallet.add_item("Bravo", "document", false, None).unwrap();
+        let c = wallet.add_item("Charlie", "document", false, None).unwrap();
+        let folder = wallet.add_item("Folder", "folder", true, None).unwrap();
+
+        wallet.reorder_items(ROOT_ID, &[&c, &a]).unwrap();
+        let order: Vec<String> = wallet.get_items_by_parent_sorted(ROOT_ID, SortOrder::Custom).unwrap()
+            .into_iter().map(|i| i.item_id).collect();
+        assert_eq!(order, vec![c.clone(), a.clone(), b.clone(), folder.clone()]);
+        assert_eq!(wallet.get_item(&a).unwrap().unwrap().sort_weight, Some(200));
+
+        // Order survives reopening the cache; moving out drops the weight
+        wallet.clear_caches();
+        wallet.move_item(&c, &folder).unwrap();
+        assert_eq!(wallet.get_item(&c).unwrap().unwrap().sort_weight, None);
+
+        assert!(matches!(wallet.reorder_items(ROOT_ID, &[&c]), Err(WalletError::ItemNotFound(_))));
+        assert!(matches!(wallet.reorder_items(ROOT_ID, &[&a, &a]), Err(WalletError::InvalidOperation(_))));
+    }
+
+    #[test]
+    fn test_item_lock() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let folder = wallet.add_item("Crypto", "folder", true, None).unwrap();
+        let seed = wallet.add_item("Seed", "document", false, Some(&folder)).unwrap();
+        let field = wallet.add_field(&seed, "NOTE", "abandon ability", None).unwrap();
+
+        wallet.set_item_locked(&seed, true).unwrap();
+        wallet.clear_caches();
+        assert!(wallet.get_item(&seed).unwrap().unwrap().locked);
+
+        let locked = |r: Result<()>| matches!(r, Err(WalletError::ItemLocked(id)) if id == seed);
+        assert!(locked(wallet.update_field(&field, "x", None).map(|_| ())));
+        assert!(locked(wallet.add_field(&seed, "MAIL", "a@b.c", None).map(|_| ())));
+        assert!(locked(wallet.delete_field(&seed, &field)));
+        assert!(locked(wallet.update_item_name(&seed, "Renamed")));
+        assert!(locked(wallet.delete_item(&seed)));
+        assert!(locked(wallet.delete_item(&folder)));
+        assert!(locked(wallet.set_item_meta(&seed, "tags", serde_json::json!(["cold"]))));
+        assert!(locked(wallet.reorder_items(&folder, &[&seed])));
+        {
+            let conn = wallet.db.as_ref().unwrap().connection().unwrap();
+            conn.execute("UPDATE nswallet_items SET deleted = 1 WHERE item_id = ?", [&seed]).unwrap();
+        }
+        assert!(locked(wallet.undelete_item(&seed)));
+        {
+            let conn = wallet.db.as_ref().unwrap().connection().unwrap();
+            conn.execute("UPDATE nswallet_items SET deleted = 0 WHERE item_id = ?", [&seed]).unwrap();
+        }
+        wallet.clear_caches();
+
+        // Bulk replace skips the locked item
+        let report = wallet.replace_in_fields("ability", "x", &ReplaceScope::All, &[], false).unwrap();
+        assert!(report.is_empty());
+
+        wallet.set_item_locked(&seed, false).unwrap();
+        wallet.update_field(&field, "abandon", None).unwrap();
+        wallet.delete_item(&folder).unwrap();
+        assert!(matches!(wallet.set_item_locked(ROOT_ID, true), Err(WalletError::InvalidOperation(_))));
+    }
+
     #[test]
     fn test_copy_item() {
         let (mut wallet, _temp) = create_test_wallet();
@@ -310,6 +868,80 @@ mod tests {
         assert_eq!(copy_fields[0].value, "test@test.com");
     }
 
+    #[test]
+    fn test_copy_item_with_options() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let archive = wallet.add_item("Archive", "folder", true, None).unwrap();
+        let item_id = wallet.add_item("Router", "document", false, None).unwrap();
+        let pass = wallet.add_field(&item_id, "PASS", "first", None).unwrap();
+        wallet.update_field(&pass, "second", None).unwrap();
+        wallet.set_item_meta(&item_id, "tags", serde_json::json!(["home"])).unwrap();
+        {
+            let conn = wallet.db.as_ref().unwrap().connection().unwrap();
+            conn.execute(
+                "UPDATE nswallet_items SET create_timestamp = '2019-05-01 10:00:00', change_timestamp = '2020-01-02 03:04:05' WHERE item_id = ?",
+                [&item_id],
+            ).unwrap();
+            conn.execute("UPDATE nswallet_fields SET change_timestamp = '2020-01-02 03:04:05' WHERE item_id = ?", [&item_id]).unwrap();
+        }
+        wallet.clear_caches();
+        let source = wallet.get_item(&item_id).unwrap().unwrap();
+
+        let options = CopyOptions {
+            preserve_timestamps: true,
+            copy_history: true,
+            copy_tags: true,
+            target_parent: Some(archive.clone()),
+        };
+        let copy_id = wallet.copy_item_with(&item_id, &options).unwrap();
+        let copy = wallet.get_item(&copy_id).unwrap().unwrap();
+        assert_eq!(copy.name, "Copy of Router");
+        assert_eq!(copy.parent_id.as_deref(), Some(archive.as_str()));
+        assert_eq!((copy.create_timestamp, copy.change_timestamp), (source.create_timestamp, source.change_timestamp));
+        let fields = wallet.get_fields_by_item(&copy_id).unwrap();
+        assert_eq!(fields.len(), 1);
+        assert_eq!(fields[0].value, "second");
+        assert_eq!(fields[0].change_timestamp, source.change_timestamp);
+        let history: Vec<String> = wallet.get_deleted_fields().unwrap().into_iter()
+            .filter(|f| f.item_id == copy_id)
+            .map(|f| f.value)
+            .collect();
+        assert_eq!(history, ["first"]);
+        assert_eq!(wallet.get_item_meta(&copy_id, "tags").unwrap(), Some(serde_json::json!(["home"])));
+
+        // The defaults copy like copy_item
+        let plain = wallet.copy_item_with(&item_id, &CopyOptions::default()).unwrap();
+        let plain = wallet.get_item(&plain).unwrap().unwrap();
+        assert_eq!(plain.parent_id.as_deref(), Some(ROOT_ID));
+        assert!(plain.create_timestamp > source.create_timestamp);
+        assert!(wallet.get_item_metadata(&plain.item_id).unwrap().is_empty());
+        assert!(!wallet.get_deleted_fields().unwrap().iter().any(|f| f.item_id == plain.item_id));
+    }
+
+    #[test]
+    fn test_copy_item_deep() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let folder = wallet.add_item("Work", "folder", true, None).unwrap();
+        let sub = wallet.add_item("VPN", "folder", true, Some(&folder)).unwrap();
+        let entry = wallet.add_item("Gateway", "document", false, Some(&sub)).unwrap();
+        wallet.add_field(&entry, "PASS", "tunnel", None).unwrap();
+        let gone = wallet.add_item("Old", "document", false, Some(&folder)).unwrap();
+        wallet.delete_item(&gone).unwrap();
+
+        let top = wallet.copy_item_deep(&folder, &CopyOptions::default()).unwrap();
+        assert_eq!(wallet.get_item(&top).unwrap().unwrap().name, "Copy of Work");
+        let children = wallet.get_items_by_parent(&top).unwrap();
+        assert_eq!(children.len(), 1);
+        assert_eq!(children[0].name, "VPN");
+        let entries = wallet.get_items_by_parent(&children[0].item_id).unwrap();
+        assert_eq!(entries[0].name, "Gateway");
+        assert_ne!(entries[0].item_id, entry);
+        assert_eq!(wallet.get_fields_by_item(&entries[0].item_id).unwrap()[0].value, "tunnel");
+
+        let into_itself = CopyOptions { target_parent: Some(sub.clone()), ..CopyOptions::default() };
+        assert!(matches!(wallet.copy_item_deep(&folder, &into_itself), Err(WalletError::InvalidOperation(_))));
+    }
+
     /// Test: ChangeItem from C# BusinessFixture
     #[test]
     fn test_update_item_name() {
@@ -913,6 +1545,54 @@ mod tests {
         assert!(!root.unwrap().deleted);
     }
 
+    #[test]
+    fn test_empty_folders_and_items_without_fields() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let empty = wallet.add_item("Empty", "folder", true, None).unwrap();
+        let outer = wallet.add_item("Outer", "folder", true, None).unwrap();
+        let inner = wallet.add_item("Inner", "folder", true, Some(&outer)).unwrap();
+        let full = wallet.add_item("Full", "folder", true, None).unwrap();
+        let bare = wallet.add_item("Bare", "document", false, Some(&full)).unwrap();
+        let filled = wallet.add_item("Filled", "document", false, Some(&full)).unwrap();
+        wallet.add_field(&filled, "NOTE", "x", None).unwrap();
+
+        let ids = |items: Vec<IWItem>| items.into_iter().map(|i| i.item_id).collect::<Vec<_>>();
+        assert_eq!(ids(wallet.get_empty_folders().unwrap()), vec![empty.clone(), inner.clone()]);
+        assert_eq!(ids(wallet.get_items_without_fields().unwrap()), vec![bare.clone()]);
+
+        // Removing the inner folder empties its parent; deleted children don't count
+        wallet.delete_item(&inner).unwrap();
+        assert_eq!(ids(wallet.get_empty_folders().unwrap()), vec![empty, outer]);
+
+        let stats = wallet.get_database_stats().unwrap();
+        assert_eq!(stats.empty_folders, 2);
+        assert_eq!(stats.items_without_fields, 1);
+    }
+
+    #[test]
+    fn test_item_errors_are_granular() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let item = wallet.add_item("Item", "document", false, None).unwrap();
+
+        assert!(matches!(
+            wallet.add_item("Orphan", "document", false, Some("NOPE1234")),
+            Err(WalletError::ParentNotFound(id)) if id == "NOPE1234"
+        ));
+        assert!(matches!(wallet.move_item(&item, "NOPE1234"), Err(WalletError::ParentNotFound(_))));
+        assert!(matches!(wallet.update_item_icon("NOPE1234", "x"), Err(WalletError::ItemNotFound(_))));
+        assert!(matches!(wallet.update_item_name("NOPE1234", "x"), Err(WalletError::ItemNotFound(_))));
+        assert!(matches!(wallet.copy_item("NOPE1234"), Err(WalletError::ItemNotFound(_))));
+
+        let long = "x".repeat(ITEM_NAME_MAX_LENGTH + 1);
+        assert!(matches!(
+            wallet.add_item(&long, "document", false, None),
+            Err(WalletError::NameTooLong { len, max }) if len == ITEM_NAME_MAX_LENGTH + 1 && max == ITEM_NAME_MAX_LENGTH
+        ));
+        assert!(matches!(wallet.update_item_name(&item, &long), Err(WalletError::NameTooLong { .. })));
+        // Limit counts characters, not bytes
+        wallet.update_item_name(&item, &"ä".repeat(ITEM_NAME_MAX_LENGTH)).unwrap();
+    }
+
     #[test]
     fn test_move_root_is_rejected() {
         let (mut wallet, _temp) = create_test_wallet();
@@ -954,4 +1634,108 @@ mod tests {
         let root = reopened.get_item(ROOT_ID).unwrap();
         assert!(root.is_some(), "root must survive a rejected delete + compact");
     }
+
+    #[test]
+    fn test_item_color() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let item_id = wallet.add_item("Bank", "document", false, None).unwrap();
+        assert_eq!(wallet.get_item(&item_id).unwrap().unwrap().color, None);
+
+        wallet.set_item_color(&item_id, Some("#F0A")).unwrap();
+        assert_eq!(wallet.get_item(&item_id).unwrap().unwrap().color.as_deref(), Some("#ff00aa"));
+        wallet.set_item_color(&item_id, Some("12AB9c")).unwrap();
+        assert_eq!(wallet.get_item(&item_id).unwrap().unwrap().color.as_deref(), Some("#12ab9c"));
+
+        let copy_id = wallet.copy_item(&item_id).unwrap();
+        assert_eq!(wallet.get_item(&copy_id).unwrap().unwrap().color.as_deref(), Some("#12ab9c"));
+
+        for bad in ["red", "#12345", "#ggg", ""] {
+            assert!(matches!(wallet.set_item_color(&item_id, Some(bad)), Err(WalletError::ValidationError(_))), "{bad}");
+        }
+        assert!(matches!(wallet.set_item_color("missing1", Some("#fff")), Err(WalletError::ItemNotFound(_))));
+
+        wallet.set_item_color(&item_id, None).unwrap();
+        assert_eq!(wallet.get_item(&item_id).unwrap().unwrap().color, None);
+    }
+
+    #[test]
+    fn test_item_color_column_added_on_open() {
+        let (mut wallet, temp) = create_test_wallet();
+        let item_id = wallet.add_item("Old", "document", false, None).unwrap();
+        wallet.database().unwrap().connection().unwrap()
+            .execute("ALTER TABLE nswallet_items DROP COLUMN color", []).unwrap();
+        wallet.close();
+
+        let mut wallet = Wallet::open(temp.path()).unwrap();
+        assert!(wallet.unlock("TestPassword123").unwrap());
+        assert_eq!(wallet.get_item(&item_id).unwrap().unwrap().color, None);
+        wallet.set_item_color(&item_id, Some("#abc")).unwrap();
+        assert_eq!(wallet.get_item(&item_id).unwrap().unwrap().color.as_deref(), Some("#aabbcc"));
+    }
+
+    #[test]
+    fn test_primary_field() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let item_id = wallet.add_item("Bank", "document", false, None).unwrap();
+        wallet.add_field(&item_id, "MAIL", "me@bank.com", None).unwrap();
+        let pass = wallet.add_field(&item_id, "PASS", "secret1", None).unwrap();
+        assert!(wallet.get_primary_field(&item_id).unwrap().is_none());
+
+        wallet.set_primary_field(&item_id, &pass).unwrap();
+        assert_eq!(wallet.get_item(&item_id).unwrap().unwrap().primary_field, Some(pass.clone()));
+        assert_eq!(wallet.get_primary_field(&item_id).unwrap().unwrap().value, "secret1");
+
+        // Follows the field to its new version
+        let new_pass = wallet.update_field(&pass, "secret2", None).unwrap();
+        let primary = wallet.get_primary_field(&item_id).unwrap().unwrap();
+        assert_eq!(primary.field_id, new_pass);
+        assert_eq!(primary.value, "secret2");
+
+        // Copies point at their own copy of the field
+        let copy_id = wallet.copy_item(&item_id).unwrap();
+        let copied = wallet.get_primary_field(&copy_id).unwrap().unwrap();
+        assert_ne!(copied.field_id, new_pass);
+        assert_eq!(copied.value, "secret2");
+
+        // A deleted field is no longer surfaced
+        wallet.delete_field(&item_id, &new_pass).unwrap();
+        assert!(wallet.get_primary_field(&item_id).unwrap().is_none());
+
+        wallet.clear_primary_field(&copy_id).unwrap();
+        assert!(wallet.get_primary_field(&copy_id).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_set_primary_field_rejects_foreign_field() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let a = wallet.add_item("A", "document", false, None).unwrap();
+        let b = wallet.add_item("B", "document", false, None).unwrap();
+        let field = wallet.add_field(&b, "PASS", "x", None).unwrap();
+        assert!(matches!(wallet.set_primary_field(&a, &field), Err(WalletError::FieldNotFound(_))));
+        assert!(matches!(wallet.clear_primary_field("missing1"), Err(WalletError::ItemNotFound(_))));
+    }
+
+    #[test]
+    fn test_copy_item_repeated_copies_get_numbered() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let source = wallet.add_item("Bank", "document", false, None).unwrap();
+
+        let first = wallet.copy_item(&source).unwrap();
+        let second = wallet.copy_item(&source).unwrap();
+        let third = wallet.copy_item(&source).unwrap();
+
+        assert_eq!(wallet.get_item(&first).unwrap().unwrap().name, "Copy of Bank");
+        assert_eq!(wallet.get_item(&second).unwrap().unwrap().name, "Copy of Bank (2)");
+        assert_eq!(wallet.get_item(&third).unwrap().unwrap().name, "Copy of Bank (3)");
+    }
+
+    #[test]
+    fn test_copy_item_uses_wallet_language() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let mut wallet = Wallet::create(temp.path(), "TestPassword123", "de").unwrap();
+        let source = wallet.add_item("Bank", "document", false, None).unwrap();
+
+        let copy = wallet.copy_item(&source).unwrap();
+        assert_eq!(wallet.get_item(&copy).unwrap().unwrap().name, "Kopie von Bank");
+    }
 }
@@ -0,0 +1,123 @@
+//! Label packs
+//!
+//! Optional sets of custom labels for common kinds of records, installed in
+//! one call. Icons reuse the system label icons so every app can draw them.
+
+use crate::database::IWLabel;
+use crate::error::Result;
+use super::wallet::Wallet;
+
+/// A predefined set of custom labels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LabelPack {
+    /// Cryptocurrency wallets and exchange accounts
+    CryptoWallets,
+    /// Health insurance and medical details
+    Medical,
+    /// Passports, visas and travel bookings
+    TravelDocuments,
+}
+
+impl LabelPack {
+    /// Every available pack
+    pub const ALL: [LabelPack; 3] = [LabelPack::CryptoWallets, LabelPack::Medical, LabelPack::TravelDocuments];
+
+    /// The pack's labels as (name, value type, icon)
+    pub fn labels(&self) -> &'static [(&'static str, &'static str, &'static str)] {
+        match self {
+            LabelPack::CryptoWallets => &[
+                ("Wallet Address", "text", "account"),
+                ("Private Key", "pass", "pass"),
+                ("Public Key", "text", "serial"),
+                ("Wallet Passphrase", "pass", "pass"),
+                ("Derivation Path", "text", "serial"),
+                ("Network", "text", "link"),
+                ("Exchange", "link", "link"),
+            ],
+            LabelPack::Medical => &[
+                ("Insurance Provider", "text", "name"),
+                ("Policy Number", "text", "serial"),
+                ("Member ID", "text", "serial"),
+                ("Blood Type", "text", "note"),
+                ("Allergies", "text", "note"),
+                ("Medication", "text", "note"),
+                ("Doctor", "text", "user"),
+                ("Doctor Phone", "phon", "phone"),
+            ],
+            LabelPack::TravelDocuments => &[
+                ("Passport Number", "text", "serial"),
+                ("Issuing Country", "text", "address"),
+                ("Issue Date", "date", "date"),
+                ("Visa Number", "text", "serial"),
+                ("Frequent Flyer Number", "text", "account"),
+                ("Known Traveler Number", "text", "serial"),
+                ("Booking Reference", "text", "serial"),
+            ],
+        }
+    }
+}
+
+/// Outcome of [`Wallet::install_label_pack`]
+#[derive(Debug, Clone, Default)]
+pub struct LabelPackReport {
+    /// Labels this call created
+    pub created: Vec<IWLabel>,
+    /// Pack labels the wallet already had: an active label with the same
+    /// name (case-insensitive) and value type
+    pub existing: Vec<IWLabel>,
+}
+
+impl Wallet {
+    /// Create the labels of `pack` that the wallet does not have yet
+    pub fn install_label_pack(&mut self, pack: LabelPack) -> Result<LabelPackReport> {
+        let labels = self.get_labels()?;
+        let mut report = LabelPackReport::default();
+        let mut created_ids = Vec::new();
+
+        for &(name, value_type, icon) in pack.labels() {
+            let existing = labels.iter().find(|l| {
+                l.name.to_lowercase() == name.to_lowercase() && l.value_type == value_type
+            });
+            match existing {
+                Some(label) => report.existing.push(label.clone()),
+                None => created_ids.push(self.add_label(name, icon, value_type)?),
+            }
+        }
+
+        if !created_ids.is_empty() {
+            let labels = self.get_labels()?;
+            report.created = created_ids.iter()
+                .filter_map(|id| labels.iter().find(|l| &l.field_type == id).cloned())
+                .collect();
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::business::wallet::tests::create_test_wallet;
+
+    #[test]
+    fn test_install_label_pack() {
+        let (mut wallet, _dir) = create_test_wallet();
+        wallet.add_label("blood type", "note", "text").unwrap();
+        let before = wallet.get_labels().unwrap().len();
+
+        let report = wallet.install_label_pack(LabelPack::Medical).unwrap();
+        let pack_len = LabelPack::Medical.labels().len();
+        assert_eq!(report.existing.len(), 1);
+        assert_eq!(report.existing[0].name, "blood type");
+        assert_eq!(report.created.len(), pack_len - 1);
+        assert!(report.created.iter().all(|l| !l.system));
+        let phone = report.created.iter().find(|l| l.name == "Doctor Phone").unwrap();
+        assert_eq!(phone.value_type, "phon");
+        assert_eq!(wallet.get_labels().unwrap().len(), before + pack_len - 1);
+
+        // Installing again creates nothing
+        let again = wallet.install_label_pack(LabelPack::Medical).unwrap();
+        assert!(again.created.is_empty());
+        assert_eq!(again.existing.len(), pack_len);
+    }
+}
@@ -5,11 +5,53 @@
 use std::collections::HashMap;
 use chrono::Utc;
 use crate::error::{WalletError, Result};
-use crate::database::{IWLabel, queries};
-use crate::database::queries::parse_timestamp;
-use crate::utils::generate_label_id;
+use crate::database::{IWField, IWItem, IWLabel, queries};
+use crate::database::queries::{parse_timestamp, RawLabel};
+use crate::utils::id_gen::{is_valid_id, ID_CHARS};
+use crate::LABEL_ID_LENGTH;
+use super::batch::BatchResult;
+use super::ids::IdKind;
 use super::wallet::Wallet;
 
+/// A custom label to create with [`Wallet::add_labels_bulk`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewLabel {
+    pub name: String,
+    pub value_type: String,
+    pub icon: String,
+    /// Field type to create the label under, e.g. the ID used by an import
+    /// source; `None` allocates a new one
+    pub field_type: Option<String>,
+}
+
+/// Outcome for one entry of [`Wallet::add_labels_bulk`]
+#[derive(Debug, Clone)]
+pub enum LabelCreateResult {
+    /// Created under the requested field type, or a new one if none was
+    /// requested
+    Created(IWLabel),
+    /// The requested field type is already an active label with the same
+    /// name (case-insensitive) and value type; nothing was written
+    Existing(IWLabel),
+    /// The requested field type belongs to a different label (or a deleted
+    /// one); the entry was created under another field type
+    Conflict {
+        requested: String,
+        existing: IWLabel,
+        created: IWLabel,
+    },
+}
+
+impl LabelCreateResult {
+    /// The label to use for the entry's fields
+    pub fn label(&self) -> &IWLabel {
+        match self {
+            LabelCreateResult::Created(label) | LabelCreateResult::Existing(label) => label,
+            LabelCreateResult::Conflict { created, .. } => created,
+        }
+    }
+}
+
 impl Wallet {
     /// Add system labels to the database
     pub fn add_system_labels(&mut self) -> Result<()> {
@@ -84,19 +126,7 @@ impl Wallet {
         let mut labels = HashMap::with_capacity(raw_labels.len());
 
         for raw in raw_labels {
-            labels.insert(raw.field_type.clone(), IWLabel {
-                field_type: raw.field_type,
-                name: raw.label_name,
-                value_type: raw.value_type,
-                icon: raw.icon,
-                system: raw.system,
-                change_timestamp: raw.change_timestamp
-                    .as_ref()
-                    .and_then(|s| parse_timestamp(s))
-                    .unwrap_or_else(Utc::now),
-                deleted: raw.deleted,
-                usage: raw.usage as u32,
-            });
+            labels.insert(raw.field_type.clone(), label_from_raw(raw));
         }
 
         self.labels_cache = Some(labels);
@@ -105,7 +135,7 @@ impl Wallet {
 
     /// Add a new label
     pub fn add_label(&mut self, name: &str, icon: &str, value_type: &str) -> Result<String> {
-        let label_id = generate_label_id();
+        let label_id = self.unique_id(IdKind::Label, &[])?;
 
         let conn = self.db.as_ref()
             .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
@@ -118,9 +148,113 @@ impl Wallet {
         }
 
         self.labels_cache = None;
+        self.note_mutation();
         Ok(label_id)
     }
 
+    /// Create several custom labels at once, for importers. An entry whose
+    /// requested field type is already taken by a different label is not
+    /// merged into it: it is created under the first free field type that
+    /// keeps the requested prefix and varies the last character (`ABCD`,
+    /// `ABC0`, `ABC1`, ...), so re-running the same import maps to the same
+    /// IDs, and reported as a `Conflict`. An entry with an empty name or
+    /// an invalid field type fails on its own; the labels of the others
+    /// are written in one transaction.
+    pub fn add_labels_bulk(&mut self, labels: Vec<NewLabel>) -> Result<BatchResult<LabelCreateResult>> {
+        self.load_labels_if_needed()?;
+        let mut taken = self.labels_cache.clone().unwrap();
+        taken.extend(self.load_deleted_labels()?);
+        let now = Utc::now();
+        let mut results = BatchResult::default();
+        let mut new_ids: Vec<String> = Vec::new();
+
+        for (index, label) in labels.into_iter().enumerate() {
+            if label.name.trim().is_empty() {
+                results.record(index, Err(WalletError::ValidationError("Label name must not be empty".to_string())));
+                continue;
+            }
+            if let Some(id) = &label.field_type
+                && (id.len() != LABEL_ID_LENGTH || !is_valid_id(id)) {
+                    results.record(index, Err(WalletError::ValidationError(format!("Invalid label id: {id}"))));
+                    continue;
+                }
+
+            let make = |field_type: String| IWLabel {
+                field_type,
+                name: label.name.clone(),
+                value_type: label.value_type.clone(),
+                icon: label.icon.clone(),
+                system: false,
+                change_timestamp: now,
+                deleted: false,
+                usage: 0,
+            };
+            let result = match &label.field_type {
+                None => LabelCreateResult::Created(make(self.unique_id(IdKind::Label, &new_ids)?)),
+                Some(requested) => match taken.get(requested) {
+                    None => LabelCreateResult::Created(make(requested.clone())),
+                    Some(existing) if !existing.deleted
+                        && existing.name.to_lowercase() == label.name.to_lowercase()
+                        && existing.value_type == label.value_type => {
+                        LabelCreateResult::Existing(existing.clone())
+                    }
+                    Some(existing) => {
+                        let prefix = &requested[..LABEL_ID_LENGTH - 1];
+                        let id = match ID_CHARS.iter()
+                            .map(|&c| format!("{prefix}{}", c as char))
+                            .find(|id| !taken.contains_key(id))
+                        {
+                            Some(id) => id,
+                            None => self.unique_id(IdKind::Label, &new_ids)?,
+                        };
+                        LabelCreateResult::Conflict {
+                            requested: requested.clone(),
+                            existing: existing.clone(),
+                            created: make(id),
+                        }
+                    }
+                },
+            };
+            if !matches!(result, LabelCreateResult::Existing(_)) {
+                let created = result.label().clone();
+                new_ids.push(created.field_type.clone());
+                taken.insert(created.field_type.clone(), created);
+            }
+            results.record(index, Ok(result));
+        }
+
+        if new_ids.is_empty() {
+            return Ok(results);
+        }
+
+        let db = self.db.as_mut()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?;
+        db.begin_transaction()?;
+
+        let pass = (|| -> Result<()> {
+            let conn = db.connection()?;
+            for result in results.values() {
+                if let LabelCreateResult::Created(l) | LabelCreateResult::Conflict { created: l, .. } = result {
+                    queries::insert_label(conn, &l.field_type, &l.name, &l.value_type, &l.icon, &l.change_timestamp)?;
+                }
+            }
+            Ok(())
+        })();
+
+        match pass {
+            Ok(()) => db.commit_transaction()?,
+            Err(e) => {
+                let _ = db.rollback_transaction();
+                return Err(e);
+            }
+        }
+        let _ = self.db.as_ref().unwrap().checkpoint();
+
+        self.labels_cache = None;
+        self.note_mutation();
+        Ok(results)
+    }
+
     /// Update label name
     pub fn update_label_name(&mut self, field_type: &str, name: &str) -> Result<()> {
         let conn = self.db.as_ref()
@@ -129,7 +263,8 @@ impl Wallet {
 
         queries::update_label_name(conn, field_type, name)?;
 
-        self.labels_cache = None;
+        self.note_mutation();
+        self.refresh_label_metadata()?;
         Ok(())
     }
 
@@ -141,20 +276,130 @@ impl Wallet {
 
         queries::update_label_icon(conn, field_type, icon)?;
 
+        self.note_mutation();
+        self.refresh_label_metadata()?;
+        Ok(())
+    }
+
+    /// Reload labels and patch the name, icon and value type copied into
+    /// each cached field, without re-decrypting the fields. Returns the
+    /// labels whose metadata changed since the fields were loaded, sorted
+    /// by name, so callers can update copies they keep themselves. The
+    /// label update methods call this; call it after changing labels by
+    /// other means (e.g. sync).
+    pub fn refresh_label_metadata(&mut self) -> Result<Vec<IWLabel>> {
+        self.labels_cache = None;
+        self.load_labels_if_needed()?;
+        let labels = self.labels_cache.as_ref().unwrap();
+        let Some(fields) = self.fields_cache.as_mut() else {
+            return Ok(Vec::new());
+        };
+
+        let mut changed: HashMap<&str, &IWLabel> = HashMap::new();
+        for field in fields.iter_mut() {
+            // Fields of deleted labels keep their fallback metadata
+            let Some(label) = labels.get(&field.field_type) else { continue };
+            if field.label != label.name || field.icon != label.icon || field.value_type != label.value_type {
+                field.label = label.name.clone();
+                field.icon = label.icon.clone();
+                field.value_type = label.value_type.clone();
+                changed.insert(label.field_type.as_str(), label);
+            }
+        }
+
+        let mut result: Vec<IWLabel> = changed.into_values().cloned().collect();
+        result.sort_by_key(|l| l.name.to_lowercase());
+        Ok(result)
+    }
+
+    /// Delete a label. Fails with `LabelInUse(count)` while active fields
+    /// use it, and with `LabelNotFound` for unknown or already deleted labels.
+    pub fn delete_label(&mut self, field_type: &str) -> Result<()> {
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+
+        queries::delete_label(conn, field_type)?;
+
         self.labels_cache = None;
+        self.note_mutation();
         Ok(())
     }
 
-    /// Delete a label (returns usage count, only deletes if 0)
-    pub fn delete_label(&mut self, field_type: &str) -> Result<i32> {
+    /// List every active field of `field_type` together with the item it
+    /// belongs to, so callers can show where a label is in use (e.g. before
+    /// deleting it). Sorted by item name, then field order.
+    pub fn get_label_usage(&mut self, field_type: &str) -> Result<Vec<(IWItem, IWField)>> {
+        self.load_items_if_needed()?;
+        self.load_fields_if_needed()?;
+
+        let items: HashMap<&str, &IWItem> = self.items_cache.as_ref().unwrap()
+            .iter()
+            .map(|i| (i.item_id.as_str(), i))
+            .collect();
+
+        let mut usage: Vec<(IWItem, IWField)> = self.fields_cache.as_ref().unwrap()
+            .iter()
+            .filter(|f| f.field_type == field_type)
+            .filter_map(|f| items.get(f.item_id.as_str()).map(|i| ((*i).clone(), f.clone())))
+            .collect();
+
+        usage.sort_by(|(ia, fa), (ib, fb)| {
+            ia.name.to_lowercase().cmp(&ib.name.to_lowercase())
+                .then_with(|| ia.item_id.cmp(&ib.item_id))
+                .then_with(|| fa.sort_weight.cmp(&fb.sort_weight))
+        });
+        Ok(usage)
+    }
+
+    /// Get all soft-deleted labels, sorted by name
+    pub fn get_deleted_labels(&self) -> Result<Vec<IWLabel>> {
+        let mut result: Vec<IWLabel> = self.load_deleted_labels()?.into_values().collect();
+        result.sort_by_key(|l| l.name.to_lowercase());
+        Ok(result)
+    }
+
+    /// Restore a soft-deleted label
+    pub fn undelete_label(&mut self, field_type: &str) -> Result<()> {
         let conn = self.db.as_ref()
             .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
             .connection()?;
 
-        let count = queries::delete_label(conn, field_type)?;
+        queries::undelete_label(conn, field_type)?;
 
         self.labels_cache = None;
-        Ok(count)
+        self.fields_cache = None;
+        self.note_mutation();
+        Ok(())
+    }
+
+    /// Soft-deleted labels keyed by field type. Used as a metadata fallback
+    /// for fields that still reference a deleted label.
+    pub(crate) fn load_deleted_labels(&self) -> Result<HashMap<String, IWLabel>> {
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+
+        Ok(queries::get_deleted_labels(conn)?
+            .into_iter()
+            .map(|raw| (raw.field_type.clone(), label_from_raw(raw)))
+            .collect())
+    }
+}
+
+fn label_from_raw(raw: RawLabel) -> IWLabel {
+    IWLabel {
+        field_type: raw.field_type,
+        name: raw.label_name,
+        value_type: raw.value_type,
+        icon: raw.icon,
+        system: raw.system,
+        change_timestamp: raw.change_timestamp
+            .as_ref()
+            .and_then(|s| parse_timestamp(s))
+            .unwrap_or_else(Utc::now),
+        deleted: raw.deleted,
+        usage: raw.usage as u32,
     }
 }
 
@@ -234,4 +479,170 @@ mod tests {
         let label = labels.iter().find(|l| l.field_type == label_id).unwrap();
         assert_eq!(label.icon, "labellink");
     }
+
+    #[test]
+    fn test_add_labels_bulk() {
+        use super::{LabelCreateResult, NewLabel};
+        use crate::error::WalletError;
+
+        let (mut wallet, _temp) = create_test_wallet();
+        let new = |name: &str, id: Option<&str>| NewLabel {
+            name: name.to_string(),
+            value_type: "text".to_string(),
+            icon: "labelcard".to_string(),
+            field_type: id.map(str::to_string),
+        };
+
+        let results = wallet.add_labels_bulk(vec![
+            new("Club", Some("CLUB")),
+            new("Locker", None),
+            new("club", Some("CLUB")),
+            new("Boarding pass", Some("MAIL")),
+            new("Gate", Some("MAIL")),
+            new("Bad", Some("TOOLONG")),
+            new(" ", None),
+        ]).unwrap();
+        let failed: Vec<usize> = results.failed.iter().map(|f| f.index).collect();
+        assert_eq!(failed, [5, 6]);
+        assert!(results.failed.iter().all(|f| matches!(f.error, WalletError::ValidationError(_))));
+        let results: Vec<LabelCreateResult> = results.values().cloned().collect();
+        assert!(matches!(&results[0], LabelCreateResult::Created(l) if l.field_type == "CLUB"));
+        assert!(matches!(&results[1], LabelCreateResult::Created(_)));
+        assert!(matches!(&results[2], LabelCreateResult::Existing(l) if l.name == "Club"));
+        // MAIL is the system email label: deterministic retry on the prefix
+        assert!(matches!(&results[3], LabelCreateResult::Conflict { requested, existing, created }
+            if requested == "MAIL" && existing.system && created.field_type == "MAIA"));
+        assert_eq!(results[4].label().field_type, "MAIB");
+
+        let labels = wallet.get_labels().unwrap();
+        assert_eq!(labels.iter().find(|l| l.field_type == "MAIA").unwrap().name, "Boarding pass");
+        assert_eq!(labels.iter().find(|l| l.field_type == "MAIL").unwrap().value_type, "mail");
+        assert!(labels.iter().all(|l| l.name != "Bad"));
+    }
+
+    #[test]
+    fn test_label_changes_reach_cached_fields() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let item_id = wallet.add_item("Item", "document", false, None).unwrap();
+        let label_id = wallet.add_label("Club", "labelcard", "text").unwrap();
+        wallet.add_field(&item_id, &label_id, "42", None).unwrap();
+        assert_eq!(wallet.get_fields_by_item(&item_id).unwrap()[0].label, "Club");
+
+        wallet.update_label_name(&label_id, "Gym").unwrap();
+        wallet.update_label_icon(&label_id, "labellink").unwrap();
+        let field = &wallet.get_fields_by_item(&item_id).unwrap()[0];
+        assert_eq!((field.label.as_str(), field.icon.as_str()), ("Gym", "labellink"));
+
+        // A change made behind the wallet's back is reported once
+        let conn = wallet.db.as_ref().unwrap().connection().unwrap();
+        conn.execute("UPDATE nswallet_labels SET label_name = 'Pool' WHERE field_type = ?", [&label_id]).unwrap();
+        let changed = wallet.refresh_label_metadata().unwrap();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].name, "Pool");
+        assert_eq!(wallet.get_fields_by_item(&item_id).unwrap()[0].label, "Pool");
+        assert!(wallet.refresh_label_metadata().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_delete_label_in_use_or_missing() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let item_id = wallet.add_item("Item", "document", false, None).unwrap();
+        let label_id = wallet.add_label("Club", "labelcard", "text").unwrap();
+        wallet.add_field(&item_id, &label_id, "1", None).unwrap();
+        wallet.add_field(&item_id, &label_id, "2", None).unwrap();
+
+        assert!(matches!(
+            wallet.delete_label(&label_id),
+            Err(crate::error::WalletError::LabelInUse(2))
+        ));
+        assert!(wallet.get_labels().unwrap().iter().any(|l| l.field_type == label_id));
+        assert!(matches!(
+            wallet.delete_label("NOPE"),
+            Err(crate::error::WalletError::LabelNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_get_deleted_labels_and_undelete() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let label_id = wallet.add_label("Loyalty", "labelcalendar", "text").unwrap();
+        assert!(wallet.get_deleted_labels().unwrap().is_empty());
+
+        wallet.delete_label(&label_id).unwrap();
+        let deleted = wallet.get_deleted_labels().unwrap();
+        assert_eq!(deleted.len(), 1);
+        assert_eq!(deleted[0].field_type, label_id);
+        assert_eq!(deleted[0].name, "Loyalty");
+        assert!(deleted[0].deleted);
+        assert!(!wallet.get_labels().unwrap().iter().any(|l| l.field_type == label_id));
+
+        wallet.undelete_label(&label_id).unwrap();
+        assert!(wallet.get_deleted_labels().unwrap().is_empty());
+        assert!(wallet.get_labels().unwrap().iter().any(|l| l.field_type == label_id));
+    }
+
+    #[test]
+    fn test_undelete_label_not_found() {
+        let (mut wallet, _temp) = create_test_wallet();
+        assert!(matches!(
+            wallet.undelete_label("NOPE"),
+            Err(crate::error::WalletError::LabelNotFound(_))
+        ));
+        // Active labels cannot be "undeleted" either
+        assert!(wallet.undelete_label("MAIL").is_err());
+    }
+
+    #[test]
+    fn test_field_with_deleted_label_uses_deleted_metadata() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let item_id = wallet.add_item("Item", "document", false, None).unwrap();
+        let label_id = wallet.add_label("Membership", "labelcard", "text").unwrap();
+        let field_id = wallet.add_field(&item_id, &label_id, "12345", None).unwrap();
+
+        // Field gone -> label unused -> label can be deleted; then the field
+        // comes back and references a deleted label.
+        wallet.delete_field(&item_id, &field_id).unwrap();
+        wallet.delete_label(&label_id).unwrap();
+        wallet.undelete_field(&item_id, &field_id).unwrap();
+
+        let fields = wallet.get_fields_by_item(&item_id).unwrap();
+        assert_eq!(fields.len(), 1);
+        assert_eq!(fields[0].label, "Membership");
+        assert_eq!(fields[0].icon, "labelcard");
+    }
+
+    #[test]
+    fn test_get_label_usage() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let bank = wallet.add_item("Bank", "document", false, None).unwrap();
+        let airline = wallet.add_item("Airline", "document", false, None).unwrap();
+        let label_id = wallet.add_label("Member No", "labelcard", "text").unwrap();
+
+        wallet.add_field(&bank, &label_id, "B-1", None).unwrap();
+        wallet.add_field(&airline, &label_id, "A-1", None).unwrap();
+        wallet.add_field(&airline, &label_id, "A-2", None).unwrap();
+        wallet.add_field(&airline, "NOTE", "unrelated", None).unwrap();
+
+        let usage = wallet.get_label_usage(&label_id).unwrap();
+        let values: Vec<(&str, &str)> = usage.iter()
+            .map(|(i, f)| (i.name.as_str(), f.value.as_str()))
+            .collect();
+        assert_eq!(values, vec![("Airline", "A-1"), ("Airline", "A-2"), ("Bank", "B-1")]);
+
+        assert!(wallet.get_label_usage("SEED").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_get_label_usage_skips_deleted_items() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let keep = wallet.add_item("Keep", "document", false, None).unwrap();
+        let gone = wallet.add_item("Gone", "document", false, None).unwrap();
+        wallet.add_field(&keep, "MAIL", "a@x.com", None).unwrap();
+        wallet.add_field(&gone, "MAIL", "b@x.com", None).unwrap();
+        wallet.delete_item(&gone).unwrap();
+
+        let usage = wallet.get_label_usage("MAIL").unwrap();
+        assert_eq!(usage.len(), 1);
+        assert_eq!(usage[0].0.item_id, keep);
+    }
 }
@@ -0,0 +1,150 @@
+//! Item metadata
+//!
+//! Each item can carry a small JSON object of host application data, such
+//! as autofill hints or UI state. The object is encrypted as one blob in its
+//! own table (under the folder key below a folder PIN), so apps can add
+//! keys without schema changes.
+
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use crate::database::queries;
+use crate::error::{WalletError, Result};
+use super::wallet::Wallet;
+
+/// Metadata key that marks an item as a favorite
+pub const FAVORITE_META_KEY: &str = "favorite";
+
+impl Wallet {
+    /// All metadata of an item, empty if it has none
+    pub fn get_item_metadata(&self, item_id: &str) -> Result<BTreeMap<String, Value>> {
+        self.ensure_unlocked()?;
+        self.ensure_item_accessible(item_id)?;
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        match queries::get_item_metadata_raw(conn, item_id)? {
+            Some(blob) => serde_json::from_slice(&self.open_item_data(item_id, &blob)?)
+                .map_err(|e| WalletError::json("Invalid item metadata", e)),
+            None => Ok(BTreeMap::new()),
+        }
+    }
+
+    /// One metadata value of an item
+    pub fn get_item_meta(&self, item_id: &str, key: &str) -> Result<Option<Value>> {
+        Ok(self.get_item_metadata(item_id)?.remove(key))
+    }
+
+    /// Set one metadata value of an item, keeping its other keys
+    pub fn set_item_meta(&mut self, item_id: &str, key: &str, value: Value) -> Result<()> {
+        if key.is_empty() {
+            return Err(WalletError::ValidationError("Metadata key cannot be empty".to_string()));
+        }
+        let mut metadata = self.get_item_metadata(item_id)?;
+        metadata.insert(key.to_string(), value);
+        self.write_item_metadata(item_id, &metadata)
+    }
+
+    /// Remove one metadata value of an item. Returns true if it was set.
+    pub fn remove_item_meta(&mut self, item_id: &str, key: &str) -> Result<bool> {
+        let mut metadata = self.get_item_metadata(item_id)?;
+        if metadata.remove(key).is_none() {
+            return Ok(false);
+        }
+        self.write_item_metadata(item_id, &metadata)?;
+        Ok(true)
+    }
+
+    /// True if the item is marked as a favorite
+    pub fn is_item_favorite(&self, item_id: &str) -> Result<bool> {
+        Ok(self.get_item_meta(item_id, FAVORITE_META_KEY)? == Some(Value::Bool(true)))
+    }
+
+    /// Mark or unmark the item as a favorite, see `ItemQuery::favorite`
+    pub fn set_item_favorite(&mut self, item_id: &str, favorite: bool) -> Result<()> {
+        if favorite {
+            self.set_item_meta(item_id, FAVORITE_META_KEY, Value::Bool(true))
+        } else {
+            self.remove_item_meta(item_id, FAVORITE_META_KEY).map(|_| ())
+        }
+    }
+
+    /// Store the whole metadata object; an empty one removes the row
+    pub(crate) fn write_item_metadata(&mut self, item_id: &str, metadata: &BTreeMap<String, Value>) -> Result<()> {
+        self.ensure_item_editable(item_id)?;
+        self.ensure_item_accessible(item_id)?;
+        let encrypted = if metadata.is_empty() {
+            None
+        } else {
+            let json = serde_json::to_string(metadata)
+                .map_err(|e| WalletError::json("Invalid item metadata", e))?;
+            Some(self.seal_item_data(item_id, json.as_bytes())?)
+        };
+
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        if !queries::item_exists(conn, item_id)? {
+            return Err(WalletError::ItemNotFound(item_id.to_string()));
+        }
+        match encrypted {
+            Some(blob) => queries::set_item_metadata_raw(conn, item_id, &blob)?,
+            None => {
+                queries::delete_item_metadata(conn, item_id)?;
+            }
+        }
+
+        self.note_mutation();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use crate::business::wallet::tests::create_test_wallet;
+    use crate::error::WalletError;
+
+    #[test]
+    fn test_item_meta_roundtrip_and_remove() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let item = wallet.add_item("Item", "document", false, None).unwrap();
+        assert!(wallet.get_item_metadata(&item).unwrap().is_empty());
+
+        wallet.set_item_meta(&item, "autofill", json!({"domains": ["example.com"]})).unwrap();
+        wallet.set_item_meta(&item, "pinned", json!(true)).unwrap();
+        assert_eq!(wallet.get_item_meta(&item, "pinned").unwrap(), Some(json!(true)));
+        assert_eq!(
+            wallet.get_item_meta(&item, "autofill").unwrap(),
+            Some(json!({"domains": ["example.com"]}))
+        );
+        assert_eq!(wallet.get_item_metadata(&item).unwrap().len(), 2);
+
+        // Stored encrypted
+        let conn = wallet.database().unwrap().connection().unwrap();
+        let blob = crate::database::queries::get_item_metadata_raw(conn, &item).unwrap().unwrap();
+        assert!(!String::from_utf8_lossy(&blob).contains("example.com"));
+
+        assert!(wallet.remove_item_meta(&item, "pinned").unwrap());
+        assert!(!wallet.remove_item_meta(&item, "pinned").unwrap());
+        assert!(wallet.remove_item_meta(&item, "autofill").unwrap());
+        let conn = wallet.database().unwrap().connection().unwrap();
+        assert_eq!(crate::database::queries::get_item_metadata_raw(conn, &item).unwrap(), None);
+
+        assert!(matches!(wallet.set_item_meta(&item, "", json!(1)), Err(WalletError::ValidationError(_))));
+        assert!(matches!(wallet.set_item_meta("NOPE1234", "k", json!(1)), Err(WalletError::ItemNotFound(_))));
+    }
+
+    #[test]
+    fn test_item_meta_purged_with_item() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let item = wallet.add_item("Item", "document", false, None).unwrap();
+        wallet.set_item_meta(&item, "hint", json!("x")).unwrap();
+        wallet.delete_item(&item).unwrap();
+        wallet.compact().unwrap();
+
+        let conn = wallet.database().unwrap().connection().unwrap();
+        assert_eq!(crate::database::queries::get_item_metadata_raw(conn, &item).unwrap(), None);
+    }
+}
@@ -9,5 +9,64 @@ pub mod fields;
 pub mod labels;
 pub mod search;
 pub mod export;
+pub mod auto_backup;
+pub mod diff;
+pub mod unlock_throttle;
+pub mod replace;
+pub mod notes;
+pub mod metadata;
+pub mod ids;
+pub mod profiles;
+pub mod emergency;
+pub mod sync;
+pub mod envelope;
+pub mod share;
+pub mod label_packs;
+pub mod recent;
+pub mod query;
+pub mod clone;
+pub mod secure_notes;
+pub mod folder_defaults;
+pub mod folder_pins;
+pub mod activity;
+pub mod password_reuse;
+pub mod icons;
+pub mod quota;
+pub mod integrity;
+pub mod sessions;
+pub mod similar_folders;
+pub mod batch;
+pub mod starter;
+pub mod notifications;
+pub mod subtree_export;
+pub mod attachments;
+pub mod import;
+pub mod backup_view;
+pub mod transfer;
 
-pub use wallet::{MigrationSummary, RecoveryResult, Wallet};
+pub use activity::{ActivityEntry, ActivityKind};
+pub use attachments::Attachment;
+pub use batch::{BatchFailure, BatchResult, NewItem};
+pub use diff::{FieldChange, ItemChange, ItemChangeKind, WalletDiff, WalletSnapshot};
+pub use emergency::{EmergencyGrant, EmergencyUnlock};
+pub use folder_defaults::FolderDefaults;
+pub use icons::IconSuggestion;
+pub use ids::{IdCollisionStats, IdKind};
+pub use integrity::{IntegrityManifest, IntegrityReport, RecordMac, RecordRef};
+pub use label_packs::{LabelPack, LabelPackReport};
+pub use labels::{LabelCreateResult, NewLabel};
+pub use notifications::DueNotification;
+pub use password_reuse::{FieldUpdate, PasswordReuse, PasswordReusePolicy};
+pub use query::ItemQuery;
+pub use quota::{QuotaConfig, QuotaEvent, QuotaLevel, QuotaMetric, QuotaStatus, QuotaUsage};
+pub use recent::{RecentChange, RecentChangeKind};
+pub use sessions::{SessionAccess, SessionInfo, SessionPermissions, SessionToken};
+pub use similar_folders::SimilarFolders;
+pub use subtree_export::{SealedField, SealedItem, SealedLabel, SealedSubtree, SEALED_SUBTREE_VERSION};
+pub use starter::StarterKind;
+pub use transfer::{WALLET_JSON_FORMAT, WALLET_JSON_VERSION};
+pub use secure_notes::{SecureNoteReader, SecureNoteWriter, SECURE_NOTE_CHUNK_SIZE, SECURE_NOTE_ICON};
+pub use replace::{FieldReplacement, ReplaceScope};
+pub use sync::{RawRecord, SyncMark};
+pub use unlock_throttle::UnlockAttempt;
+pub use wallet::{MigrationSummary, OpenMetrics, OpenRecovery, RecordKind, RecoveryResult, UndecryptableRecord, Wallet};
@@ -0,0 +1,137 @@
+//! Item notes
+//!
+//! Each item can carry one free-text note of up to `max_note_size` bytes.
+//! Notes are encrypted like field values, under the folder key below a
+//! folder PIN, but stored in their own table, so loading items and fields
+//! never pulls large note text into memory.
+
+use crate::database::queries;
+use crate::error::{WalletError, Result};
+use crate::utils::markdown_to_plaintext;
+use super::wallet::Wallet;
+
+impl Wallet {
+    /// Largest note `set_item_note` accepts, in bytes of UTF-8 text
+    pub fn max_note_size(&self) -> usize {
+        self.max_note_size
+    }
+
+    /// Change the note size limit. Existing larger notes stay readable.
+    pub fn set_max_note_size(&mut self, bytes: usize) {
+        self.max_note_size = bytes;
+    }
+
+    /// Set an item's note. An empty text removes the note.
+    pub fn set_item_note(&mut self, item_id: &str, text: &str) -> Result<()> {
+        self.ensure_unlocked()?;
+        if text.len() > self.max_note_size {
+            return Err(WalletError::NoteTooLarge { size: text.len(), max: self.max_note_size });
+        }
+        self.ensure_item_editable(item_id)?;
+        self.ensure_item_accessible(item_id)?;
+
+        let encrypted = if text.is_empty() { None } else { Some(self.seal_item_data(item_id, text.as_bytes())?) };
+
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        if !queries::item_exists(conn, item_id)? {
+            return Err(WalletError::ItemNotFound(item_id.to_string()));
+        }
+        match encrypted {
+            Some(blob) => queries::set_item_note_raw(conn, item_id, &blob)?,
+            None => {
+                queries::delete_item_note(conn, item_id)?;
+            }
+        }
+
+        self.note_mutation();
+        Ok(())
+    }
+
+    /// Get an item's note, if it has one
+    pub fn get_item_note(&self, item_id: &str) -> Result<Option<String>> {
+        self.ensure_unlocked()?;
+        self.ensure_item_accessible(item_id)?;
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        match queries::get_item_note_raw(conn, item_id)? {
+            Some(blob) => Ok(Some(String::from_utf8(self.open_item_data(item_id, &blob)?.to_vec())
+                .map_err(|_| WalletError::from(crate::crypto::DecryptError::BadUtf8))?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Plain-text preview of an item's note for list views: Markdown is
+    /// stripped and the text cut to `max_chars` characters (with "…").
+    pub fn get_item_note_preview(&self, item_id: &str, max_chars: usize) -> Result<Option<String>> {
+        let Some(note) = self.get_item_note(item_id)? else {
+            return Ok(None);
+        };
+        let plain = markdown_to_plaintext(&note).replace('\n', " ");
+        if plain.chars().count() <= max_chars {
+            return Ok(Some(plain));
+        }
+        let cut: String = plain.chars().take(max_chars).collect();
+        Ok(Some(format!("{}…", cut.trim_end())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::business::wallet::tests::create_test_wallet;
+    use crate::error::WalletError;
+
+    #[test]
+    fn test_item_note_roundtrip_and_remove() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let item = wallet.add_item("Item", "document", false, None).unwrap();
+        assert_eq!(wallet.get_item_note(&item).unwrap(), None);
+
+        wallet.set_item_note(&item, "# Wifi\nPassword is **on the fridge**").unwrap();
+        assert_eq!(wallet.get_item_note(&item).unwrap().as_deref(), Some("# Wifi\nPassword is **on the fridge**"));
+        assert_eq!(
+            wallet.get_item_note_preview(&item, 12).unwrap().as_deref(),
+            Some("Wifi Passwor…")
+        );
+        // Notes stay out of field listings
+        assert!(wallet.get_fields_by_item(&item).unwrap().is_empty());
+
+        wallet.set_item_note(&item, "").unwrap();
+        assert_eq!(wallet.get_item_note(&item).unwrap(), None);
+        assert!(matches!(wallet.set_item_note("NOPE1234", "x"), Err(WalletError::ItemNotFound(_))));
+    }
+
+    #[test]
+    fn test_item_note_size_limit() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let item = wallet.add_item("Item", "document", false, None).unwrap();
+        assert_eq!(wallet.max_note_size(), crate::NOTE_MAX_SIZE_DEFAULT);
+
+        wallet.set_max_note_size(10);
+        wallet.set_item_note(&item, "0123456789").unwrap();
+        assert!(matches!(
+            wallet.set_item_note(&item, "ääääää"),
+            Err(WalletError::NoteTooLarge { size: 12, max: 10 })
+        ));
+
+        // Large notes work with a raised limit
+        wallet.set_max_note_size(1024 * 1024);
+        let big = "x".repeat(512 * 1024);
+        wallet.set_item_note(&item, &big).unwrap();
+        assert_eq!(wallet.get_item_note(&item).unwrap().unwrap().len(), big.len());
+    }
+
+    #[test]
+    fn test_item_note_purged_with_item() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let item = wallet.add_item("Item", "document", false, None).unwrap();
+        wallet.set_item_note(&item, "secret").unwrap();
+        wallet.delete_item(&item).unwrap();
+        wallet.compact().unwrap();
+
+        let conn = wallet.database().unwrap().connection().unwrap();
+        assert_eq!(crate::database::queries::get_item_note_raw(conn, &item).unwrap(), None);
+    }
+}
@@ -0,0 +1,168 @@
+//! Expiry notifications
+//!
+//! Date fields flagged `expired` or `expiring` are what reminder features
+//! in client apps are built on. [`Wallet::get_due_notifications`] lists
+//! the ones a reminder is due for, and [`Wallet::snooze_notification`]
+//! holds a field's reminder back until a given time. Snoozes are stored in
+//! the wallet, so every client that opens it agrees on what is due.
+
+use std::collections::{HashMap, HashSet};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use crate::database::queries::{self, parse_timestamp};
+use crate::error::{WalletError, Result};
+use super::fields::parse_expiry_date;
+use super::wallet::Wallet;
+
+/// A date field whose reminder is due
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DueNotification {
+    pub item_id: String,
+    pub item_name: String,
+    pub field_id: String,
+    /// Label of the field
+    pub label: String,
+    pub date: NaiveDate,
+    /// Days from today to the date; negative once expired
+    pub days_left: i64,
+    pub expired: bool,
+}
+
+impl Wallet {
+    /// Expired and expiring date fields of active items that are not
+    /// snoozed, soonest first
+    pub fn get_due_notifications(&mut self) -> Result<Vec<DueNotification>> {
+        self.ensure_unlocked()?;
+        let now = Utc::now();
+        let snoozed: HashSet<(String, String)> = {
+            let conn = self.db.as_ref()
+                .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+                .connection()?;
+            queries::get_notification_snoozes(conn)?
+                .into_iter()
+                .filter(|(_, _, until)| parse_timestamp(until).is_some_and(|u| u > now))
+                .map(|(item_id, field_id, _)| (item_id, field_id))
+                .collect()
+        };
+
+        let names: HashMap<String, String> = self.get_items()?.iter()
+            .filter(|i| !i.deleted)
+            .map(|i| (i.item_id.clone(), i.name.clone()))
+            .collect();
+        let today = now.date_naive();
+        let mut due: Vec<DueNotification> = self.get_fields()?.iter()
+            .filter(|f| !f.deleted && (f.expired || f.expiring))
+            .filter(|f| !snoozed.contains(&(f.item_id.clone(), f.field_id.clone())))
+            .filter_map(|f| {
+                let item_name = names.get(&f.item_id)?;
+                let date = parse_expiry_date(&f.value)?;
+                Some(DueNotification {
+                    item_id: f.item_id.clone(),
+                    item_name: item_name.clone(),
+                    field_id: f.field_id.clone(),
+                    label: f.label.clone(),
+                    date,
+                    days_left: (date - today).num_days(),
+                    expired: f.expired,
+                })
+            })
+            .collect();
+        due.sort_by(|a, b| a.date.cmp(&b.date).then_with(|| a.item_name.cmp(&b.item_name)));
+        Ok(due)
+    }
+
+    /// Hold back the reminder of a date field until `until`. Snoozing again
+    /// replaces the previous time.
+    pub fn snooze_notification(&mut self, item_id: &str, field_id: &str, until: DateTime<Utc>) -> Result<()> {
+        self.ensure_unlocked()?;
+        if !self.get_fields_by_item(item_id)?.iter().any(|f| f.field_id == field_id) {
+            return Err(WalletError::FieldNotFound(field_id.to_string()));
+        }
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        queries::set_notification_snooze(conn, item_id, field_id, &until)
+    }
+
+    /// Remove the snooze of a field. Returns true if it had one.
+    pub fn unsnooze_notification(&mut self, item_id: &str, field_id: &str) -> Result<bool> {
+        self.ensure_unlocked()?;
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        queries::delete_notification_snooze(conn, item_id, field_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+    use crate::business::wallet::tests::create_test_wallet;
+
+    fn date_in(days: i64) -> String {
+        (Utc::now().date_naive() + TimeDelta::days(days)).format("%Y%m%d").to_string()
+    }
+
+    #[test]
+    fn test_due_notifications_and_snooze() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let passport = wallet.add_item("Passport", "passport", false, None).unwrap();
+        let soon = wallet.add_field(&passport, "EXPD", &date_in(10), None).unwrap();
+        let card = wallet.add_item("Card", "visa", false, None).unwrap();
+        let past = wallet.add_field(&card, "EXPD", &date_in(-3), None).unwrap();
+        let later = wallet.add_item("Licence", "document", false, None).unwrap();
+        wallet.add_field(&later, "EXPD", &date_in(200), None).unwrap();
+
+        let due = wallet.get_due_notifications().unwrap();
+        assert_eq!(due.iter().map(|d| d.field_id.as_str()).collect::<Vec<_>>(), [past.as_str(), soon.as_str()]);
+        assert!(due[0].expired);
+        assert_eq!(due[0].days_left, -3);
+        assert_eq!((due[1].item_name.as_str(), due[1].days_left), ("Passport", 10));
+
+        wallet.snooze_notification(&card, &past, Utc::now() + TimeDelta::days(1)).unwrap();
+        let due = wallet.get_due_notifications().unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].field_id, soon);
+
+        // A snooze in the past no longer holds the reminder back
+        wallet.snooze_notification(&passport, &soon, Utc::now() - TimeDelta::hours(1)).unwrap();
+        assert_eq!(wallet.get_due_notifications().unwrap().len(), 1);
+
+        assert!(wallet.unsnooze_notification(&card, &past).unwrap());
+        assert!(!wallet.unsnooze_notification(&card, &past).unwrap());
+        assert_eq!(wallet.get_due_notifications().unwrap().len(), 2);
+        assert!(matches!(
+            wallet.snooze_notification(&card, "missing", Utc::now()),
+            Err(WalletError::FieldNotFound(_))
+        ));
+
+        wallet.delete_item(&card).unwrap();
+        assert_eq!(wallet.get_due_notifications().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_snoozes_go_with_purged_items_and_fields() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let card = wallet.add_item("Card", "visa", false, None).unwrap();
+        let card_expiry = wallet.add_field(&card, "EXPD", &date_in(5), None).unwrap();
+        let passport = wallet.add_item("Passport", "passport", false, None).unwrap();
+        let old_expiry = wallet.add_field(&passport, "EXPD", &date_in(3), None).unwrap();
+        let new_expiry = wallet.add_field(&passport, "EXPD", &date_in(30), None).unwrap();
+        let until = Utc::now() + TimeDelta::days(1);
+        for (item, field) in [(&card, &card_expiry), (&passport, &old_expiry), (&passport, &new_expiry)] {
+            wallet.snooze_notification(item, field, until).unwrap();
+        }
+
+        wallet.delete_item(&card).unwrap();
+        wallet.delete_field(&passport, &old_expiry).unwrap();
+        wallet.compact().unwrap();
+
+        let conn = wallet.db.as_ref().unwrap().connection().unwrap();
+        let left: Vec<String> = queries::get_notification_snoozes(conn).unwrap()
+            .into_iter()
+            .map(|(_, field_id, _)| field_id)
+            .collect();
+        assert_eq!(left, [new_expiry]);
+    }
+}
@@ -0,0 +1,162 @@
+//! Password reuse checks on update
+//!
+//! With a policy other than [`PasswordReusePolicy::Allow`], `update_field`
+//! on a PASS field compares the new value with the field's current value,
+//! the item's password history (its OLDP field and replaced PASS values in
+//! the deleted pool) and the passwords of all other items.
+
+use std::fmt;
+use serde::{Deserialize, Serialize};
+use crate::error::{Result, WalletError};
+use super::wallet::Wallet;
+
+/// What `update_field` does when a new password is a reused one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PasswordReusePolicy {
+    /// No check (default)
+    #[default]
+    Allow,
+    /// Save the password and report the reuse in [`FieldUpdate::reuse`]
+    Warn,
+    /// Refuse with `WalletError::PasswordReused`
+    Reject,
+}
+
+/// Where a reused password was found
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PasswordReuse {
+    /// Same as the field's current value
+    Unchanged,
+    /// One of the item's earlier passwords
+    Previous,
+    /// The current password of another item
+    OtherItem { item_id: String },
+}
+
+impl fmt::Display for PasswordReuse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PasswordReuse::Unchanged => write!(f, "same as the current password"),
+            PasswordReuse::Previous => write!(f, "same as a previous password"),
+            PasswordReuse::OtherItem { item_id } => write!(f, "same as the password of item {item_id}"),
+        }
+    }
+}
+
+/// Result of [`Wallet::update_field_checked`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldUpdate {
+    /// ID of the new field version
+    pub field_id: String,
+    /// The reuse found under [`PasswordReusePolicy::Warn`]
+    pub reuse: Option<PasswordReuse>,
+}
+
+impl Wallet {
+    /// Set how `update_field` treats reused passwords. `Allow` by default.
+    pub fn set_password_reuse_policy(&mut self, policy: PasswordReusePolicy) {
+        self.password_reuse_policy = policy;
+    }
+
+    /// The current password reuse policy
+    pub fn password_reuse_policy(&self) -> PasswordReusePolicy {
+        self.password_reuse_policy
+    }
+
+    /// Whether `value` would reuse a password if it replaced the PASS field
+    /// `field_id`, regardless of the policy. Empty values are never reuse.
+    pub fn check_password_reuse(&mut self, field_id: &str, value: &str) -> Result<Option<PasswordReuse>> {
+        self.ensure_unlocked()?;
+        if value.is_empty() {
+            return Ok(None);
+        }
+        let field = self.get_fields()?.iter()
+            .find(|f| f.field_id == field_id)
+            .cloned()
+            .ok_or_else(|| WalletError::FieldNotFound(field_id.to_string()))?;
+        if field.field_type != "PASS" {
+            return Ok(None);
+        }
+        if field.value == value {
+            return Ok(Some(PasswordReuse::Unchanged));
+        }
+
+        let item_id = field.item_id;
+        let in_history = self.get_fields()?.iter()
+            .any(|f| f.item_id == item_id && f.field_type == "OLDP" && f.value == value)
+            || self.get_deleted_fields()?.iter()
+                .any(|f| f.item_id == item_id && f.field_type == "PASS" && f.value == value);
+        if in_history {
+            return Ok(Some(PasswordReuse::Previous));
+        }
+
+        Ok(self.get_fields()?.iter()
+            .find(|f| f.field_type == "PASS" && f.item_id != item_id && f.value == value)
+            .map(|f| PasswordReuse::OtherItem { item_id: f.item_id.clone() }))
+    }
+
+    /// [`Wallet::update_field`] that also reports a reused password when
+    /// the policy is `Warn`
+    pub fn update_field_checked(&mut self, field_id: &str, value: &str, sort_weight: Option<i32>) -> Result<FieldUpdate> {
+        let reuse = match self.password_reuse_policy {
+            PasswordReusePolicy::Allow => None,
+            PasswordReusePolicy::Warn => self.check_password_reuse(field_id, value)?,
+            PasswordReusePolicy::Reject => {
+                if let Some(reuse) = self.check_password_reuse(field_id, value)? {
+                    return Err(WalletError::PasswordReused(reuse));
+                }
+                None
+            }
+        };
+        let field_id = self.update_field_unchecked(field_id, value, sort_weight)?;
+        Ok(FieldUpdate { field_id, reuse })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::business::wallet::tests::create_test_wallet;
+
+    #[test]
+    fn test_password_reuse_policy() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let bank = wallet.add_item("Bank", "document", false, None).unwrap();
+        wallet.add_field(&bank, "OLDP", "", None).unwrap();
+        let pass = wallet.add_field(&bank, "PASS", "first-pass", None).unwrap();
+        let mail = wallet.add_item("Mail", "document", false, None).unwrap();
+        wallet.add_field(&mail, "PASS", "mail-pass", None).unwrap();
+
+        // Allowed by default
+        assert_eq!(wallet.password_reuse_policy(), PasswordReusePolicy::Allow);
+        let pass = wallet.update_field(&pass, "first-pass", None).unwrap();
+        let pass = wallet.update_field(&pass, "second-pass", None).unwrap();
+
+        wallet.set_password_reuse_policy(PasswordReusePolicy::Reject);
+        assert!(matches!(
+            wallet.update_field(&pass, "second-pass", None),
+            Err(WalletError::PasswordReused(PasswordReuse::Unchanged))
+        ));
+        assert!(matches!(
+            wallet.update_field(&pass, "first-pass", None),
+            Err(WalletError::PasswordReused(PasswordReuse::Previous))
+        ));
+        match wallet.update_field(&pass, "mail-pass", None) {
+            Err(WalletError::PasswordReused(PasswordReuse::OtherItem { item_id })) => assert_eq!(item_id, mail),
+            other => panic!("expected reuse of the mail password, got {other:?}"),
+        }
+        let pass = wallet.update_field(&pass, "third-pass", None).unwrap();
+
+        wallet.set_password_reuse_policy(PasswordReusePolicy::Warn);
+        let update = wallet.update_field_checked(&pass, "second-pass", None).unwrap();
+        assert_eq!(update.reuse, Some(PasswordReuse::Previous));
+        let value = wallet.get_fields_by_item(&bank).unwrap().into_iter()
+            .find(|f| f.field_id == update.field_id).unwrap().value;
+        assert_eq!(value, "second-pass");
+
+        // Only PASS fields are checked
+        let note = wallet.add_field(&bank, "NOTE", "text", None).unwrap();
+        wallet.set_password_reuse_policy(PasswordReusePolicy::Reject);
+        wallet.update_field(&note, "text", None).unwrap();
+    }
+}
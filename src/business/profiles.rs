@@ -0,0 +1,212 @@
+//! Profiles: several independent hierarchies in one wallet
+//!
+//! The wallet's tree hangs off `__ROOT__`. A profile is another top-level
+//! folder stored next to it, with the same `ROOT_PARENT_ID` parent, so
+//! e.g. "Personal" and "Family shared" can live in one database without
+//! seeing each other's entries. `__ROOT__` itself is the default profile.
+
+use std::collections::HashMap;
+use crate::database::{IWItem, IWProfile, queries};
+use crate::error::{WalletError, Result};
+use crate::{ITEM_NAME_MAX_LENGTH, ROOT_ID, ROOT_PARENT_ID};
+use super::ids::IdKind;
+use super::wallet::Wallet;
+
+/// Icon given to new profile roots
+pub const PROFILE_ICON: &str = "profile";
+
+/// Map every item to the root of its profile (roots map to themselves).
+/// Items whose parent chain does not reach a root are left out.
+pub(crate) fn profile_map(items: &[IWItem]) -> HashMap<&str, &str> {
+    let by_id: HashMap<&str, &IWItem> = items.iter().map(|i| (i.item_id.as_str(), i)).collect();
+    let mut map: HashMap<&str, &str> = HashMap::with_capacity(items.len());
+    for item in items {
+        let mut chain: Vec<&str> = Vec::new();
+        let mut cur = item;
+        let root = loop {
+            if let Some(root) = map.get(cur.item_id.as_str()) {
+                break Some(*root);
+            }
+            if is_profile_root(cur) {
+                break Some(cur.item_id.as_str());
+            }
+            chain.push(cur.item_id.as_str());
+            // Guard against parent cycles in damaged data
+            if chain.len() > items.len() {
+                break None;
+            }
+            match cur.parent_id.as_deref().and_then(|p| by_id.get(p)) {
+                Some(parent) => cur = parent,
+                None => break None,
+            }
+        };
+        if let Some(root) = root {
+            map.insert(cur.item_id.as_str(), root);
+            for id in chain {
+                map.insert(id, root);
+            }
+        }
+    }
+    map
+}
+
+fn is_profile_root(item: &IWItem) -> bool {
+    item.item_id == ROOT_ID || (item.folder && item.parent_id.as_deref() == Some(ROOT_PARENT_ID))
+}
+
+fn profile_from_item(item: &IWItem) -> IWProfile {
+    IWProfile {
+        profile_id: item.item_id.clone(),
+        name: item.name.clone(),
+        icon: item.icon.clone(),
+        is_default: item.item_id == ROOT_ID,
+    }
+}
+
+impl Wallet {
+    /// Create a new, empty profile. Returns its root item ID, which is used
+    /// as parent for the profile's top-level items.
+    pub fn create_profile(&mut self, name: &str) -> Result<String> {
+        self.ensure_unlocked()?;
+        let len = name.chars().count();
+        if len > ITEM_NAME_MAX_LENGTH {
+            return Err(WalletError::NameTooLong { len, max: ITEM_NAME_MAX_LENGTH });
+        }
+
+        let profile_id = self.unique_id(IdKind::Item, &[])?;
+        let encrypted_name = self.enc_value(name)?;
+
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        queries::create_item(conn, &profile_id, ROOT_PARENT_ID, &encrypted_name, PROFILE_ICON, true)?;
+
+        self.items_cache = None;
+        self.note_mutation();
+        Ok(profile_id)
+    }
+
+    /// All profiles: the default one first, then the others by name
+    pub fn list_profiles(&mut self) -> Result<Vec<IWProfile>> {
+        let items = self.get_items()?;
+        let mut profiles: Vec<IWProfile> = items.iter()
+            .filter(|i| is_profile_root(i))
+            .map(profile_from_item)
+            .collect();
+        profiles.sort_by(|a, b| {
+            b.is_default.cmp(&a.is_default)
+                .then_with(|| a.name.to_lowercase().cmp(&b.name.to_lowercase()))
+        });
+        Ok(profiles)
+    }
+
+    /// Profile an item belongs to, or `None` if its parent chain is broken
+    pub fn get_item_profile(&mut self, item_id: &str) -> Result<Option<IWProfile>> {
+        let items = self.get_items()?;
+        let map = profile_map(items);
+        let Some(root_id) = map.get(item_id) else {
+            return Ok(None);
+        };
+        Ok(items.iter().find(|i| i.item_id == *root_id).map(profile_from_item))
+    }
+
+    /// Every item of a profile (its root excluded)
+    pub fn get_profile_items(&mut self, profile_id: &str) -> Result<Vec<IWItem>> {
+        let items = self.get_items()?;
+        if !items.iter().any(|i| i.item_id == profile_id && is_profile_root(i)) {
+            return Err(WalletError::ItemNotFound(profile_id.to_string()));
+        }
+        let map = profile_map(items);
+        Ok(items.iter()
+            .filter(|i| i.item_id != profile_id && map.get(i.item_id.as_str()) == Some(&profile_id))
+            .cloned()
+            .collect())
+    }
+
+    /// Rename a profile
+    pub fn rename_profile(&mut self, profile_id: &str, name: &str) -> Result<()> {
+        self.ensure_profile(profile_id)?;
+        self.update_item_name(profile_id, name)
+    }
+
+    /// Delete a profile with everything in it (soft delete). The default
+    /// profile cannot be deleted.
+    pub fn delete_profile(&mut self, profile_id: &str) -> Result<()> {
+        self.ensure_profile(profile_id)?;
+        self.delete_item(profile_id)
+    }
+
+    fn ensure_profile(&mut self, profile_id: &str) -> Result<()> {
+        let is_profile = self.get_items()?
+            .iter()
+            .any(|i| i.item_id == profile_id && is_profile_root(i));
+        if is_profile {
+            Ok(())
+        } else {
+            Err(WalletError::ItemNotFound(profile_id.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::SearchOptions;
+    use crate::business::wallet::tests::create_test_wallet;
+
+    #[test]
+    fn test_create_and_list_profiles() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let profiles = wallet.list_profiles().unwrap();
+        assert_eq!(profiles.len(), 1);
+        assert!(profiles[0].is_default);
+        assert_eq!(profiles[0].profile_id, ROOT_ID);
+
+        let family = wallet.create_profile("Family shared").unwrap();
+        let work = wallet.create_profile("Work").unwrap();
+        let profiles = wallet.list_profiles().unwrap();
+        let ids: Vec<&str> = profiles.iter().map(|p| p.profile_id.as_str()).collect();
+        assert_eq!(ids, vec![ROOT_ID, family.as_str(), work.as_str()]);
+
+        // Profile roots stay out of the default tree and are never
+        // reported as empty folders
+        assert!(wallet.get_items_by_parent(ROOT_ID).unwrap().is_empty());
+        assert!(wallet.get_empty_folders().unwrap().is_empty());
+
+        wallet.rename_profile(&work, "Office").unwrap();
+        assert!(wallet.list_profiles().unwrap().iter().any(|p| p.name == "Office"));
+        wallet.delete_profile(&work).unwrap();
+        assert_eq!(wallet.list_profiles().unwrap().len(), 2);
+        assert!(wallet.delete_profile(ROOT_ID).is_err());
+    }
+
+    #[test]
+    fn test_profile_scoped_items_and_search() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let family = wallet.create_profile("Family").unwrap();
+        let personal_bank = wallet.add_item("Bank", "document", false, None).unwrap();
+        let folder = wallet.add_item("Utilities", "folder", true, Some(&family)).unwrap();
+        let family_bank = wallet.add_item("Bank", "document", false, Some(&folder)).unwrap();
+
+        let ids = |items: Vec<IWItem>| {
+            let mut v: Vec<String> = items.into_iter().map(|i| i.item_id).collect();
+            v.sort();
+            v
+        };
+        assert_eq!(ids(wallet.get_profile_items(ROOT_ID).unwrap()), vec![personal_bank.clone()]);
+        let mut expected = vec![folder.clone(), family_bank.clone()];
+        expected.sort();
+        assert_eq!(ids(wallet.get_profile_items(&family).unwrap()), expected);
+        assert_eq!(wallet.get_item_profile(&family_bank).unwrap().unwrap().profile_id, family);
+        assert!(wallet.get_profile_items(&folder).is_err());
+
+        let options = SearchOptions { profile_id: Some(family.clone()), ..Default::default() };
+        let mut found = Vec::new();
+        wallet.search_streaming("bank", &options, |r| {
+            found.push(r.item.item_id);
+            std::ops::ControlFlow::Continue(())
+        }).unwrap();
+        assert_eq!(found, vec![family_bank]);
+        assert_eq!(wallet.search("bank").unwrap().len(), 2);
+    }
+}
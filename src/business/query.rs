@@ -0,0 +1,263 @@
+//! Item query builder
+//!
+//! [`Wallet::query`] starts an [`ItemQuery`]; its combinators compile into
+//! SQL, including the time condition, order and limit, and only the names
+//! of the page of rows returned are decrypted. Use it instead of filtering
+//! the whole `get_items()` list.
+
+use chrono::{DateTime, Timelike, Utc};
+use crate::database::{IWItem, ItemFilter, queries};
+use crate::error::{WalletError, Result};
+use super::wallet::Wallet;
+
+/// Builder for an item query; run it with [`ItemQuery::run`]
+pub struct ItemQuery<'a> {
+    wallet: &'a mut Wallet,
+    filter: ItemFilter,
+}
+
+impl Wallet {
+    /// Start a query over the active items (the root excluded)
+    pub fn query(&mut self) -> ItemQuery<'_> {
+        ItemQuery { wallet: self, filter: ItemFilter::default() }
+    }
+}
+
+impl ItemQuery<'_> {
+    /// Only direct children of `folder_id`
+    pub fn folder(mut self, folder_id: &str) -> Self {
+        self.filter.parent_id = Some(folder_id.to_string());
+        self
+    }
+
+    /// Only items with an active field of `field_type`. Repeated calls
+    /// require each of the types.
+    pub fn field_type(mut self, field_type: &str) -> Self {
+        self.filter.field_types.push(field_type.to_string());
+        self
+    }
+
+    /// Only items changed at or after `since`. Timestamps are stored to
+    /// the second, so `since` is truncated to whole seconds.
+    pub fn modified_since(mut self, since: DateTime<Utc>) -> Self {
+        self.filter.modified_since = Some(since.with_nanosecond(0).unwrap_or(since));
+        self
+    }
+
+    /// Only folders (`true`) or only entries (`false`)
+    pub fn is_folder(mut self, folder: bool) -> Self {
+        self.filter.folder = Some(folder);
+        self
+    }
+
+    /// Only locked (`true`) or unlocked (`false`) items
+    pub fn locked(mut self, locked: bool) -> Self {
+        self.filter.locked = Some(locked);
+        self
+    }
+
+    /// Only items with this display color (`#rrggbb`)
+    pub fn color(mut self, color: &str) -> Self {
+        self.filter.color = Some(color.to_ascii_lowercase());
+        self
+    }
+
+    /// Only favorites (`true`) or only other items (`false`), see
+    /// [`Wallet::set_item_favorite`]. The mark is in the encrypted item
+    /// metadata, so it is checked on the rows SQL returns.
+    pub fn favorite(mut self, favorite: bool) -> Self {
+        self.filter.favorite = Some(favorite);
+        self
+    }
+
+    /// Return at most `limit` items
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.filter.limit = Some(limit);
+        self
+    }
+
+    /// The conditions collected so far
+    pub fn filter(&self) -> &ItemFilter {
+        &self.filter
+    }
+
+    /// Run the query. Items come most recently changed first. Rows that do
+    /// not decrypt are skipped, as in `get_items`, and do not count toward
+    /// the limit: further pages are fetched until it is reached.
+    ///
+    /// The time condition compares normalized timestamps, since older app
+    /// generations stored them in other formats. With metadata encryption
+    /// on they are sealed in the envelope, so the time condition, order
+    /// and limit are applied to the decrypted items instead.
+    pub fn run(self) -> Result<Vec<IWItem>> {
+        let wallet = self.wallet;
+        let filter = self.filter;
+        wallet.ensure_unlocked()?;
+        if filter.limit == Some(0) {
+            return Ok(Vec::new());
+        }
+        if wallet.metadata_encryption_enabled()? {
+            return run_in_memory(wallet, &filter);
+        }
+
+        let mut items = Vec::new();
+        let mut offset = 0;
+        loop {
+            let page = {
+                let conn = wallet.db.as_ref()
+                    .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+                    .connection()?;
+                queries::get_items_raw_page(conn, &filter, offset, filter.limit)?
+            };
+            let fetched = page.len();
+            offset += fetched;
+            for raw in page {
+                let Some(item) = wallet.item_from_raw(raw) else { continue };
+                if !is_favorite_match(wallet, &filter, &item) {
+                    continue;
+                }
+                items.push(item);
+                if filter.limit == Some(items.len()) {
+                    return Ok(items);
+                }
+            }
+            if filter.limit.is_none_or(|limit| fetched < limit) {
+                return Ok(items);
+            }
+        }
+    }
+}
+
+/// [`ItemQuery::run`] for sealed timestamps: every matching row is
+/// decrypted, then filtered, sorted and cut to the limit
+fn run_in_memory(wallet: &mut Wallet, filter: &ItemFilter) -> Result<Vec<IWItem>> {
+    let raw_items = {
+        let conn = wallet.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        queries::get_items_raw_filtered(conn, filter)?
+    };
+
+    let mut items: Vec<IWItem> = raw_items.into_iter()
+        .filter_map(|raw| wallet.item_from_raw(raw))
+        .filter(|item| filter.modified_since.is_none_or(|since| item.change_timestamp >= since))
+        .filter(|item| is_favorite_match(wallet, filter, item))
+        .collect();
+    items.sort_by(|a, b| b.change_timestamp.cmp(&a.change_timestamp)
+        .then_with(|| a.item_id.cmp(&b.item_id)));
+    if let Some(limit) = filter.limit {
+        items.truncate(limit);
+    }
+    Ok(items)
+}
+
+/// Whether `item` meets the favorite condition of `filter`. An item whose
+/// metadata cannot be read (behind a locked folder PIN) meets neither.
+fn is_favorite_match(wallet: &Wallet, filter: &ItemFilter, item: &IWItem) -> bool {
+    filter.favorite.is_none_or(|want| wallet.is_item_favorite(&item.item_id).is_ok_and(|is| is == want))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+    use crate::business::wallet::tests::create_test_wallet;
+
+    #[test]
+    fn test_item_query() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let folder = wallet.add_item("Work", "folder", true, None).unwrap();
+        let mail = wallet.add_item("Mail", "document", false, Some(&folder)).unwrap();
+        wallet.add_field(&mail, "PASS", "secret", None).unwrap();
+        wallet.add_field(&mail, "MAIL", "a@b.c", None).unwrap();
+        let vpn = wallet.add_item("VPN", "document", false, Some(&folder)).unwrap();
+        wallet.add_field(&vpn, "MAIL", "x@y.z", None).unwrap();
+        let home = wallet.add_item("Home", "document", false, None).unwrap();
+        wallet.add_field(&home, "PASS", "pw", None).unwrap();
+
+        let ids = |items: Vec<IWItem>| {
+            let mut ids: Vec<String> = items.into_iter().map(|i| i.item_id).collect();
+            ids.sort();
+            ids
+        };
+        let mut expected = vec![mail.clone(), home.clone()];
+        expected.sort();
+        assert_eq!(ids(wallet.query().field_type("PASS").run().unwrap()), expected);
+        assert_eq!(ids(wallet.query().folder(&folder).field_type("PASS").field_type("MAIL").run().unwrap()), vec![mail.clone()]);
+        assert_eq!(ids(wallet.query().is_folder(true).run().unwrap()), vec![folder.clone()]);
+        assert_eq!(wallet.query().is_folder(false).limit(2).run().unwrap().len(), 2);
+
+        wallet.set_item_locked(&vpn, true).unwrap();
+        assert_eq!(ids(wallet.query().locked(true).run().unwrap()), vec![vpn.clone()]);
+
+        let future = Utc::now() + TimeDelta::hours(1);
+        assert!(wallet.query().modified_since(future).run().unwrap().is_empty());
+        let past = Utc::now() - TimeDelta::hours(1);
+        assert_eq!(wallet.query().modified_since(past).run().unwrap().len(), 4);
+
+        // Sealed timestamps are filtered after the envelopes are opened
+        wallet.enable_metadata_encryption().unwrap();
+        assert_eq!(wallet.query().modified_since(past).is_folder(false).run().unwrap().len(), 3);
+        assert!(wallet.query().modified_since(future).run().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_item_query_favorites() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let bank = wallet.add_item("Bank", "bank", false, None).unwrap();
+        let mail = wallet.add_item("Mail", "mail", false, None).unwrap();
+        let tagged = wallet.add_item("Tagged", "document", false, None).unwrap();
+        wallet.set_item_meta(&tagged, "tags", serde_json::json!(["x"])).unwrap();
+        assert!(wallet.query().favorite(true).run().unwrap().is_empty());
+
+        wallet.set_item_favorite(&bank, true).unwrap();
+        wallet.set_item_favorite(&mail, true).unwrap();
+        assert!(wallet.is_item_favorite(&bank).unwrap());
+        let favorites = |items: Vec<IWItem>| {
+            let mut ids: Vec<String> = items.into_iter().map(|i| i.item_id).collect();
+            ids.sort();
+            ids
+        };
+        let mut expected = vec![bank.clone(), mail.clone()];
+        expected.sort();
+        assert_eq!(favorites(wallet.query().favorite(true).run().unwrap()), expected);
+        assert_eq!(wallet.query().favorite(true).limit(1).run().unwrap().len(), 1);
+        assert_eq!(favorites(wallet.query().favorite(false).run().unwrap()), vec![tagged.clone()]);
+
+        wallet.set_item_favorite(&mail, false).unwrap();
+        assert!(!wallet.is_item_favorite(&mail).unwrap());
+        assert_eq!(favorites(wallet.query().favorite(true).run().unwrap()), vec![bank.clone()]);
+
+        wallet.enable_metadata_encryption().unwrap();
+        assert_eq!(favorites(wallet.query().favorite(true).run().unwrap()), vec![bank]);
+    }
+
+    #[test]
+    fn test_item_query_legacy_timestamps_and_undecryptable_rows() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let iso = wallet.add_item("Iso", "document", false, None).unwrap();
+        let ticks = wallet.add_item("Ticks", "document", false, None).unwrap();
+        let bad = wallet.add_item("Bad", "document", false, None).unwrap();
+        let good = wallet.add_item("Good", "document", false, None).unwrap();
+        {
+            // 2020-01-01 in the `T` and .NET ticks formats, both of which
+            // sort after "2021-..." as text
+            let conn = wallet.db.as_ref().unwrap().connection().unwrap();
+            conn.execute("UPDATE nswallet_items SET change_timestamp = '2020-01-01T00:00:00' WHERE item_id = ?",
+                [&iso]).unwrap();
+            conn.execute("UPDATE nswallet_items SET change_timestamp = '637134336000000000' WHERE item_id = ?",
+                [&ticks]).unwrap();
+            conn.execute("UPDATE nswallet_items SET name = ?, change_timestamp = '2999-01-01 00:00:00' WHERE item_id = ?",
+                rusqlite::params![vec![7u8; 40], bad]).unwrap();
+        }
+        wallet.clear_caches();
+
+        let since = "2021-01-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let recent = wallet.query().modified_since(since).run().unwrap();
+        assert_eq!(recent.iter().map(|i| i.item_id.as_str()).collect::<Vec<_>>(), [good.as_str()]);
+
+        let newest = wallet.query().limit(1).run().unwrap();
+        assert_eq!(newest.iter().map(|i| i.item_id.as_str()).collect::<Vec<_>>(), [good.as_str()]);
+        assert_eq!(wallet.query().limit(3).run().unwrap().len(), 3);
+    }
+}
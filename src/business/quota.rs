@@ -0,0 +1,229 @@
+//! Soft quotas on wallet size
+//!
+//! Free tiers of sync and backup services cap file sizes and counts, and a
+//! wallet that outgrows them stops syncing without much notice. A
+//! [`QuotaConfig`] set with [`Wallet::set_quota`] names soft limits on the
+//! item count and the database size. Nothing is refused when a limit is
+//! reached; [`Wallet::quota_status`] reports where the wallet stands, and
+//! every write that moves a metric into another [`QuotaLevel`] queues a
+//! [`QuotaEvent`] for [`Wallet::take_quota_events`].
+
+use serde::{Deserialize, Serialize};
+use crate::database::queries;
+use crate::error::{WalletError, Result};
+use super::wallet::Wallet;
+
+/// Soft limits; a metric without a limit is not tracked
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct QuotaConfig {
+    /// Active items and folders, root excluded
+    pub max_items: Option<u32>,
+    /// Database file plus write-ahead log, in bytes
+    pub max_file_bytes: Option<u64>,
+    /// Fraction of a limit at which the level becomes `Warning`
+    pub warn_ratio: f64,
+}
+
+impl Default for QuotaConfig {
+    fn default() -> Self {
+        Self {
+            max_items: None,
+            max_file_bytes: None,
+            warn_ratio: 0.8,
+        }
+    }
+}
+
+/// How close a metric is to its limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum QuotaLevel {
+    /// Below the warning threshold, or no limit set
+    Ok,
+    /// At or above `warn_ratio` of the limit
+    Warning,
+    /// At or above the limit
+    Exceeded,
+}
+
+/// A measured quantity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QuotaMetric {
+    Items,
+    FileSize,
+}
+
+/// Usage of one metric
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaUsage {
+    pub used: u64,
+    pub limit: Option<u64>,
+    pub level: QuotaLevel,
+}
+
+/// Result of [`Wallet::quota_status`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaStatus {
+    pub items: QuotaUsage,
+    pub file_size: QuotaUsage,
+}
+
+impl QuotaStatus {
+    /// The higher of the two levels
+    pub fn level(&self) -> QuotaLevel {
+        self.items.level.max(self.file_size.level)
+    }
+}
+
+/// A metric moved into another level, up or down
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaEvent {
+    pub metric: QuotaMetric,
+    pub previous: QuotaLevel,
+    pub level: QuotaLevel,
+    pub usage: QuotaUsage,
+}
+
+/// Quota state held by the wallet while a config is set.
+pub(crate) struct QuotaState {
+    pub(crate) config: QuotaConfig,
+    /// Levels at the last check, items then file size
+    pub(crate) levels: [QuotaLevel; 2],
+    /// Events not yet taken
+    pub(crate) events: Vec<QuotaEvent>,
+}
+
+fn usage(used: u64, limit: Option<u64>, warn_ratio: f64) -> QuotaUsage {
+    let level = match limit {
+        Some(limit) if used >= limit => QuotaLevel::Exceeded,
+        Some(limit) if used as f64 >= limit as f64 * warn_ratio => QuotaLevel::Warning,
+        _ => QuotaLevel::Ok,
+    };
+    QuotaUsage { used, limit, level }
+}
+
+impl Wallet {
+    /// Set soft limits. Levels are measured right away, so a wallet that
+    /// is already over a limit reports it with `quota_status` but queues
+    /// no event until a level changes.
+    pub fn set_quota(&mut self, config: QuotaConfig) -> Result<()> {
+        if !(config.warn_ratio > 0.0 && config.warn_ratio <= 1.0) {
+            return Err(WalletError::InvalidOperation(format!(
+                "Quota warning ratio must be in (0, 1], got {}", config.warn_ratio
+            )));
+        }
+        self.quota = Some(QuotaState { config, levels: [QuotaLevel::Ok; 2], events: Vec::new() });
+        let status = self.quota_status()?;
+        if let Some(state) = self.quota.as_mut() {
+            state.levels = [status.items.level, status.file_size.level];
+        }
+        Ok(())
+    }
+
+    /// Remove the soft limits and drop queued events
+    pub fn clear_quota(&mut self) {
+        self.quota = None;
+    }
+
+    /// The limits in effect, if any
+    pub fn quota(&self) -> Option<QuotaConfig> {
+        self.quota.as_ref().map(|s| s.config)
+    }
+
+    /// Current usage against the limits. Works without limits set; every
+    /// level is then `Ok`.
+    pub fn quota_status(&self) -> Result<QuotaStatus> {
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        let items = queries::count_active_items(conn)? as u64;
+
+        let db_path = self.database_path();
+        let mut file_size = std::fs::metadata(&db_path).map(|m| m.len()).unwrap_or(0);
+        let mut wal_path = db_path.into_os_string();
+        wal_path.push("-wal");
+        file_size += std::fs::metadata(&wal_path).map(|m| m.len()).unwrap_or(0);
+
+        let config = self.quota().unwrap_or_default();
+        Ok(QuotaStatus {
+            items: usage(items, config.max_items.map(u64::from), config.warn_ratio),
+            file_size: usage(file_size, config.max_file_bytes, config.warn_ratio),
+        })
+    }
+
+    /// Events queued since the last call, oldest first
+    pub fn take_quota_events(&mut self) -> Vec<QuotaEvent> {
+        self.quota.as_mut().map(|s| std::mem::take(&mut s.events)).unwrap_or_default()
+    }
+
+    /// Re-measure after a write and queue an event for each level change.
+    /// Called from `note_mutation`; failures to measure are ignored.
+    pub(crate) fn check_quota(&mut self) {
+        if self.quota.is_none() {
+            return;
+        }
+        let Ok(status) = self.quota_status() else {
+            return;
+        };
+        let state = self.quota.as_mut().unwrap();
+        let current = [(QuotaMetric::Items, status.items), (QuotaMetric::FileSize, status.file_size)];
+        for (previous, (metric, usage)) in state.levels.iter_mut().zip(current) {
+            if *previous != usage.level {
+                state.events.push(QuotaEvent { metric, previous: *previous, level: usage.level, usage });
+                *previous = usage.level;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::business::wallet::tests::create_test_wallet;
+
+    #[test]
+    fn test_quota_events() {
+        let (mut wallet, _temp) = create_test_wallet();
+        assert_eq!(wallet.quota_status().unwrap().level(), QuotaLevel::Ok);
+
+        wallet.set_quota(QuotaConfig { max_items: Some(5), ..Default::default() }).unwrap();
+        let mut ids = Vec::new();
+        for i in 0..3 {
+            ids.push(wallet.add_item(&format!("Item {i}"), "document", false, None).unwrap());
+        }
+        assert!(wallet.take_quota_events().is_empty());
+
+        // 4 of 5 is the 80% warning threshold
+        ids.push(wallet.add_item("Item 3", "document", false, None).unwrap());
+        let events = wallet.take_quota_events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].metric, QuotaMetric::Items);
+        assert_eq!((events[0].previous, events[0].level), (QuotaLevel::Ok, QuotaLevel::Warning));
+
+        wallet.add_item("Item 4", "document", false, None).unwrap();
+        let status = wallet.quota_status().unwrap();
+        assert_eq!(status.items.used, 5);
+        assert_eq!(status.level(), QuotaLevel::Exceeded);
+        assert_eq!(wallet.take_quota_events()[0].level, QuotaLevel::Exceeded);
+        assert!(wallet.take_quota_events().is_empty(), "events are taken once");
+
+        // Going back below a threshold is reported too
+        wallet.delete_item(&ids[0]).unwrap();
+        wallet.delete_item(&ids[1]).unwrap();
+        let events = wallet.take_quota_events();
+        assert_eq!(events.iter().map(|e| e.level).collect::<Vec<_>>(), [QuotaLevel::Warning, QuotaLevel::Ok]);
+    }
+
+    #[test]
+    fn test_quota_file_size() {
+        let (mut wallet, _temp) = create_test_wallet();
+        // Already over when set: reported, but no event
+        wallet.set_quota(QuotaConfig { max_file_bytes: Some(1), ..Default::default() }).unwrap();
+        assert_eq!(wallet.quota_status().unwrap().file_size.level, QuotaLevel::Exceeded);
+        wallet.add_item("Item", "document", false, None).unwrap();
+        assert!(wallet.take_quota_events().is_empty());
+
+        assert!(wallet.set_quota(QuotaConfig { warn_ratio: 0.0, ..Default::default() }).is_err());
+        wallet.clear_quota();
+        assert!(wallet.quota().is_none());
+    }
+}
@@ -0,0 +1,182 @@
+//! Recent changes feed
+//!
+//! The wallet keeps no change journal, so the kind of each change is read
+//! off the records themselves: an item whose change time equals its create
+//! time was created, a soft-deleted record was deleted, and a field that
+//! replaced a deleted field of the same type at the same moment was edited
+//! (`update_field` keeps the old value in the deleted pool).
+
+use std::collections::{HashMap, HashSet};
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use crate::error::Result;
+use crate::ROOT_ID;
+use super::wallet::Wallet;
+
+/// Field changes this close to their item's creation or deletion are part
+/// of that change
+const FOLD_WINDOW: TimeDelta = TimeDelta::seconds(60);
+
+/// How a record changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RecentChangeKind {
+    Created,
+    Modified,
+    Deleted,
+}
+
+/// One entry of [`Wallet::recent_changes`]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecentChange {
+    /// The item changed, or the item owning the changed field
+    pub item_id: String,
+    /// Item name (also for deleted items)
+    pub item_name: String,
+    /// Whether the item is a folder
+    pub folder: bool,
+    /// The changed field; `None` when the item itself changed
+    pub field_id: Option<String>,
+    /// Label of the changed field
+    pub field_label: Option<String>,
+    pub kind: RecentChangeKind,
+    /// When the change happened
+    pub at: DateTime<Utc>,
+}
+
+impl Wallet {
+    /// The `limit` most recent changes to items and fields, newest first.
+    /// Field changes made within a minute of their item's creation or
+    /// deletion are folded into that item entry.
+    pub fn recent_changes(&mut self, limit: usize) -> Result<Vec<RecentChange>> {
+        self.ensure_unlocked()?;
+        let mut items = self.get_items()?.to_vec();
+        items.extend(self.get_deleted_items()?);
+        items.retain(|i| i.item_id != ROOT_ID);
+        let active_fields = self.get_fields()?.to_vec();
+        let deleted_fields = self.get_deleted_fields()?;
+
+        let mut changes = Vec::new();
+        for item in &items {
+            let (kind, at) = if item.deleted {
+                (RecentChangeKind::Deleted, item.change_timestamp)
+            } else if item.change_timestamp <= item.create_timestamp {
+                (RecentChangeKind::Created, item.create_timestamp)
+            } else {
+                (RecentChangeKind::Modified, item.change_timestamp)
+            };
+            changes.push(RecentChange {
+                item_id: item.item_id.clone(),
+                item_name: item.name.clone(),
+                folder: item.folder,
+                field_id: None,
+                field_label: None,
+                kind,
+                at,
+            });
+        }
+
+        let by_id: HashMap<&str, usize> = items.iter().enumerate()
+            .map(|(i, item)| (item.item_id.as_str(), i))
+            .collect();
+        let replaced: HashSet<(&str, &str, DateTime<Utc>)> = deleted_fields.iter()
+            .map(|f| (f.item_id.as_str(), f.field_type.as_str(), f.change_timestamp))
+            .collect();
+        let replacements: HashSet<(&str, &str, DateTime<Utc>)> = active_fields.iter()
+            .map(|f| (f.item_id.as_str(), f.field_type.as_str(), f.change_timestamp))
+            .collect();
+
+        for field in active_fields.iter().chain(&deleted_fields) {
+            // OLDP is the password history kept by edits of PASS
+            if field.field_type == "OLDP" {
+                continue;
+            }
+            let Some(&index) = by_id.get(field.item_id.as_str()) else { continue };
+            let item = &items[index];
+            let near = |at: DateTime<Utc>| (field.change_timestamp - at).abs() <= FOLD_WINDOW;
+            if near(item.create_timestamp) || (item.deleted && near(item.change_timestamp)) {
+                continue;
+            }
+            let key = (field.item_id.as_str(), field.field_type.as_str(), field.change_timestamp);
+            let kind = if field.deleted {
+                if replacements.contains(&key) {
+                    // The old value of an edit; the new field reports it
+                    continue;
+                }
+                RecentChangeKind::Deleted
+            } else if replaced.contains(&key) {
+                RecentChangeKind::Modified
+            } else {
+                RecentChangeKind::Created
+            };
+            changes.push(RecentChange {
+                item_id: item.item_id.clone(),
+                item_name: item.name.clone(),
+                folder: item.folder,
+                field_id: Some(field.field_id.clone()),
+                field_label: Some(field.label.clone()),
+                kind,
+                at: field.change_timestamp,
+            });
+        }
+
+        changes.sort_by_key(|c| std::cmp::Reverse(c.at));
+        changes.truncate(limit);
+        Ok(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::business::wallet::tests::create_test_wallet;
+
+    /// Move every record of the wallet `hours` into the past, so the next
+    /// change gets a later timestamp
+    fn age_records(wallet: &mut Wallet, hours: i64) {
+        let conn = wallet.db.as_ref().unwrap().connection().unwrap();
+        let shift = format!("-{hours} hours");
+        for table in ["nswallet_items", "nswallet_fields"] {
+            conn.execute(
+                &format!("UPDATE {table} SET change_timestamp = datetime(change_timestamp, ?)"),
+                [&shift],
+            ).unwrap();
+        }
+        conn.execute("UPDATE nswallet_items SET create_timestamp = datetime(create_timestamp, ?)", [&shift]).unwrap();
+        wallet.clear_caches();
+    }
+
+    #[test]
+    fn test_recent_changes() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let bank = wallet.add_item("Bank", "document", false, None).unwrap();
+        let pass = wallet.add_field(&bank, "PASS", "old", None).unwrap();
+        wallet.add_field(&bank, "MAIL", "a@b.c", None).unwrap();
+        let old = wallet.add_item("Old", "document", false, None).unwrap();
+        wallet.add_field(&old, "NOTE", "note", None).unwrap();
+
+        // Creation folds the fields added at the same moment
+        let created = wallet.recent_changes(10).unwrap();
+        assert_eq!(created.len(), 2);
+        assert!(created.iter().all(|c| c.kind == RecentChangeKind::Created && c.field_id.is_none()));
+
+        age_records(&mut wallet, 3);
+        wallet.update_field(&pass, "new", None).unwrap();
+        age_records(&mut wallet, 2);
+        wallet.delete_item(&old).unwrap();
+        age_records(&mut wallet, 1);
+        wallet.update_item_name(&bank, "My bank").unwrap();
+
+        let changes = wallet.recent_changes(10).unwrap();
+        let summary: Vec<(&str, Option<&str>, RecentChangeKind)> = changes.iter()
+            .map(|c| (c.item_name.as_str(), c.field_label.as_deref(), c.kind))
+            .collect();
+        let password = wallet.get_labels().unwrap().iter().find(|l| l.field_type == "PASS").unwrap().name.clone();
+        assert_eq!(summary, vec![
+            ("My bank", None, RecentChangeKind::Modified),
+            ("Old", None, RecentChangeKind::Deleted),
+            ("My bank", Some(password.as_str()), RecentChangeKind::Modified),
+        ]);
+
+        assert_eq!(wallet.recent_changes(1).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,255 @@
+//! Bulk search-and-replace in field values
+//!
+//! For edits that touch many entries at once, like a changed email domain or
+//! a company rename. Every replaced field gets a new version exactly as with
+//! `Wallet::update_field`, and all of them are written in one transaction.
+
+use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Serialize};
+use crate::database::queries;
+use crate::error::{WalletError, Result};
+use super::ids::IdKind;
+use super::wallet::Wallet;
+
+/// Which part of the wallet `replace_in_fields` looks at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplaceScope {
+    /// Every active item
+    All,
+    /// A folder and everything below it
+    Folder(String),
+    /// A single item
+    Item(String),
+}
+
+/// One field changed (or, on a dry run, that would change)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldReplacement {
+    pub item_id: String,
+    pub item_name: String,
+    /// Id of the field before the change
+    pub field_id: String,
+    /// Id of the new field version (`None` on a dry run)
+    pub new_field_id: Option<String>,
+    pub field_type: String,
+    pub old_value: String,
+    pub new_value: String,
+    /// Number of occurrences replaced in this field
+    pub occurrences: usize,
+}
+
+impl Wallet {
+    /// Replace every occurrence of `search` (case-sensitive) with `replace`
+    /// in the active fields within `scope`. `field_types` limits the change
+    /// to those field types; an empty slice means all types.
+    ///
+    /// Returns one entry per affected field, sorted by item name and field
+    /// order. With `dry_run` nothing is written. Otherwise all fields are
+    /// updated in a single transaction: either every change lands or none.
+    /// In strict validation mode an invalid resulting value fails the whole
+    /// batch before anything is written. Locked items are left alone.
+    pub fn replace_in_fields(
+        &mut self,
+        search: &str,
+        replace: &str,
+        scope: &ReplaceScope,
+        field_types: &[&str],
+        dry_run: bool,
+    ) -> Result<Vec<FieldReplacement>> {
+        self.ensure_unlocked()?;
+        if search.is_empty() {
+            return Err(WalletError::InvalidOperation("Search text must not be empty".to_string()));
+        }
+
+        self.load_items_if_needed()?;
+        self.load_fields_if_needed()?;
+        let items = self.items_cache.as_ref().unwrap();
+        let names: HashMap<&str, &str> = items.iter()
+            .map(|i| (i.item_id.as_str(), i.name.as_str()))
+            .collect();
+        let locked: HashSet<&str> = items.iter()
+            .filter(|i| i.locked)
+            .map(|i| i.item_id.as_str())
+            .collect();
+
+        let in_scope: Option<HashSet<String>> = match scope {
+            ReplaceScope::All => None,
+            ReplaceScope::Item(id) => {
+                if !names.contains_key(id.as_str()) {
+                    return Err(WalletError::ItemNotFound(id.clone()));
+                }
+                Some(HashSet::from([id.clone()]))
+            }
+            ReplaceScope::Folder(id) => {
+                if !names.contains_key(id.as_str()) {
+                    return Err(WalletError::ItemNotFound(id.clone()));
+                }
+                let mut set = HashSet::from([id.clone()]);
+                let mut frontier = vec![id.clone()];
+                while let Some(parent) = frontier.pop() {
+                    for child in items.iter().filter(|i| i.parent_id.as_deref() == Some(parent.as_str())) {
+                        if set.insert(child.item_id.clone()) {
+                            frontier.push(child.item_id.clone());
+                        }
+                    }
+                }
+                Some(set)
+            }
+        };
+
+        let mut report: Vec<(i32, FieldReplacement)> = self.fields_cache.as_ref().unwrap()
+            .iter()
+            .filter(|f| in_scope.as_ref().is_none_or(|s| s.contains(&f.item_id)))
+            .filter(|f| field_types.is_empty() || field_types.contains(&f.field_type.as_str()))
+            .filter(|f| !locked.contains(f.item_id.as_str()))
+            .filter(|f| f.value.contains(search))
+            .filter_map(|f| {
+                let item_name = names.get(f.item_id.as_str())?;
+                Some((f.sort_weight, FieldReplacement {
+                    item_id: f.item_id.clone(),
+                    item_name: item_name.to_string(),
+                    field_id: f.field_id.clone(),
+                    new_field_id: None,
+                    field_type: f.field_type.clone(),
+                    old_value: f.value.clone(),
+                    new_value: f.value.replace(search, replace),
+                    occurrences: f.value.matches(search).count(),
+                }))
+            })
+            .collect();
+        report.sort_by(|(wa, a), (wb, b)| {
+            a.item_name.to_lowercase().cmp(&b.item_name.to_lowercase())
+                .then_with(|| a.item_id.cmp(&b.item_id))
+                .then_with(|| wa.cmp(wb))
+        });
+
+        if dry_run || report.is_empty() {
+            return Ok(report.into_iter().map(|(_, r)| r).collect());
+        }
+
+        if self.strict_validation() {
+            for (_, r) in &report {
+                self.validate_field_value(&r.field_type, &r.new_value)?;
+            }
+        }
+        let mut encrypted = Vec::with_capacity(report.len());
+        let mut new_ids: Vec<String> = Vec::with_capacity(report.len());
+        for (_, r) in &report {
+            encrypted.push(self.enc_field_value(&r.item_id, &r.new_value)?);
+            new_ids.push(self.unique_id(IdKind::Field, &new_ids)?);
+        }
+
+        let db = self.db.as_mut()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?;
+        db.begin_transaction()?;
+
+        let pass = (|| -> Result<()> {
+            let conn = db.connection()?;
+            for (((weight, r), value), new_field_id) in report.iter_mut().zip(&encrypted).zip(&new_ids) {
+                let old = queries::get_field_raw_by_id(conn, &r.field_id)?
+                    .ok_or_else(|| WalletError::FieldNotFound(r.field_id.clone()))?;
+                // Same password history rule as update_field
+                if old.field_type == "PASS"
+                    && let Some(oldp_field_id) = queries::get_oldp_field_id(conn, &r.item_id)? {
+                        queries::update_field_value_only(conn, &r.item_id, &oldp_field_id, &old.value_encrypted)?;
+                    }
+                queries::replace_field_no_checkpoint(
+                    conn, &r.item_id, &r.field_id, new_field_id, &r.field_type, value, *weight,
+                )?;
+                r.new_field_id = Some(new_field_id.clone());
+            }
+            Ok(())
+        })();
+
+        match pass {
+            Ok(()) => db.commit_transaction()?,
+            Err(e) => {
+                let _ = db.rollback_transaction();
+                return Err(e);
+            }
+        }
+        let _ = self.db.as_ref().unwrap().checkpoint();
+
+        self.items_cache = None;
+        self.fields_cache = None;
+        self.note_mutation();
+        Ok(report.into_iter().map(|(_, r)| r).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::business::wallet::tests::create_test_wallet;
+
+    #[test]
+    fn test_replace_in_fields_dry_run_and_apply() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let bank = wallet.add_item("Bank", "document", false, None).unwrap();
+        let mail = wallet.add_item("Mail", "document", false, None).unwrap();
+        wallet.add_field(&bank, "MAIL", "me@old.com", None).unwrap();
+        wallet.add_field(&bank, "NOTE", "old.com and old.com", None).unwrap();
+        wallet.add_field(&mail, "MAIL", "you@old.com", None).unwrap();
+        wallet.add_field(&mail, "USER", "nothing here", None).unwrap();
+
+        let preview = wallet.replace_in_fields("old.com", "new.org", &ReplaceScope::All, &[], true).unwrap();
+        assert_eq!(preview.len(), 3);
+        assert_eq!(preview[0].item_name, "Bank");
+        assert!(preview.iter().all(|r| r.new_field_id.is_none()));
+        let note = preview.iter().find(|r| r.field_type == "NOTE").unwrap();
+        assert_eq!(note.occurrences, 2);
+        assert_eq!(note.new_value, "new.org and new.org");
+        // Dry run writes nothing
+        assert!(wallet.get_fields().unwrap().iter().any(|f| f.value == "me@old.com"));
+
+        let applied = wallet.replace_in_fields("old.com", "new.org", &ReplaceScope::All, &["MAIL"], false).unwrap();
+        assert_eq!(applied.len(), 2);
+        let values: Vec<String> = wallet.get_fields().unwrap().iter().map(|f| f.value.clone()).collect();
+        assert!(values.contains(&"me@new.org".to_string()));
+        assert!(values.contains(&"you@new.org".to_string()));
+        assert!(values.contains(&"old.com and old.com".to_string()));
+        // Replaced fields keep history like update_field
+        let new_id = applied[0].new_field_id.as_deref().unwrap();
+        assert!(wallet.get_fields().unwrap().iter().any(|f| f.field_id == new_id));
+        assert!(wallet.get_deleted_fields().unwrap().iter().any(|f| f.field_id == applied[0].field_id));
+    }
+
+    #[test]
+    fn test_replace_in_fields_scope() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let folder = wallet.add_item("Work", "folder", true, None).unwrap();
+        let sub = wallet.add_item("Sub", "folder", true, Some(&folder)).unwrap();
+        let inside = wallet.add_item("Inside", "document", false, Some(&sub)).unwrap();
+        let outside = wallet.add_item("Outside", "document", false, None).unwrap();
+        wallet.add_field(&inside, "NOTE", "Acme", None).unwrap();
+        wallet.add_field(&outside, "NOTE", "Acme", None).unwrap();
+
+        let folder_scope = ReplaceScope::Folder(folder.clone());
+        let r = wallet.replace_in_fields("Acme", "Globex", &folder_scope, &[], true).unwrap();
+        assert_eq!(r.len(), 1);
+        assert_eq!(r[0].item_id, inside);
+
+        let r = wallet.replace_in_fields("Acme", "Globex", &ReplaceScope::Item(outside.clone()), &[], true).unwrap();
+        assert_eq!(r.len(), 1);
+        assert_eq!(r[0].item_id, outside);
+
+        assert!(matches!(
+            wallet.replace_in_fields("Acme", "x", &ReplaceScope::Item("NOPE1234".into()), &[], true),
+            Err(WalletError::ItemNotFound(_))
+        ));
+        assert!(wallet.replace_in_fields("", "x", &ReplaceScope::All, &[], true).is_err());
+    }
+
+    #[test]
+    fn test_replace_in_fields_strict_rejects_whole_batch() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let item = wallet.add_item("Item", "document", false, None).unwrap();
+        wallet.add_field(&item, "MAIL", "a@example.com", None).unwrap();
+        wallet.add_field(&item, "NOTE", "example.com", None).unwrap();
+        wallet.set_strict_validation(true);
+
+        let err = wallet.replace_in_fields("@example.com", " at example", &ReplaceScope::All, &[], false);
+        assert!(matches!(err, Err(WalletError::ValidationError(_))));
+        assert!(wallet.get_fields().unwrap().iter().any(|f| f.value == "a@example.com"));
+    }
+}
@@ -5,8 +5,17 @@
 use crate::SEARCH_MIN_LENGTH;
 use crate::ROOT_ID;
 use crate::error::Result;
-use crate::database::{IWField, SearchResult, SearchMatchType};
-use super::wallet::Wallet;
+use std::collections::{HashMap, HashSet};
+use std::ops::ControlFlow;
+use hmac::{Hmac, KeyInit, Mac};
+use sha2::Sha256;
+use crate::database::{FolderGroup, IWField, IWItem, SearchOptions, SearchResult, SearchMatchType, UrlMatch, UrlMatchRank};
+use crate::utils::CancelToken;
+use crate::utils::url::{extract_host, registrable_domain};
+use super::profiles::profile_map;
+use super::wallet::{random_bytes, Wallet};
+
+type HmacSha256 = Hmac<Sha256>;
 
 /// Check if the search phrase meets the minimum length requirement
 ///
@@ -35,6 +44,23 @@ pub fn contains_phrase(text: &str, phrase: &str) -> bool {
     to_lower(text).contains(&to_lower(phrase))
 }
 
+/// Names of `folder_id` and its ancestors from the top level down. The
+/// root is left out; the walk stops at a cycle or a missing parent.
+fn folder_path(by_id: &HashMap<&str, &IWItem>, folder_id: &str) -> Vec<String> {
+    let mut path = Vec::new();
+    let mut current = folder_id;
+    while current != ROOT_ID && path.len() <= by_id.len() {
+        let Some(folder) = by_id.get(current) else { break };
+        path.push(folder.name.clone());
+        match folder.parent_id.as_deref() {
+            Some(parent) => current = parent,
+            None => break,
+        }
+    }
+    path.reverse();
+    path
+}
+
 impl Wallet {
     /// Search items and fields
     ///
@@ -44,32 +70,86 @@ impl Wallet {
     /// - Field value matches include all items
     /// - Returns distinct results
     pub fn search(&mut self, query: &str) -> Result<Vec<SearchResult>> {
+        let mut results = Vec::new();
+        self.search_streaming(query, &SearchOptions::default(), |result| {
+            results.push(result);
+            ControlFlow::Continue(())
+        })?;
+        Ok(results)
+    }
+
+    /// Search like `search`, but hand each result to `on_result` as soon as
+    /// it is found, so a UI can show the first matches before the scan ends.
+    /// Returning `ControlFlow::Break` from the callback cancels the search.
+    /// Returns the number of results delivered.
+    pub fn search_streaming<F>(&mut self, query: &str, options: &SearchOptions, on_result: F) -> Result<usize>
+    where
+        F: FnMut(SearchResult) -> ControlFlow<()>,
+    {
+        self.search_streaming_with_cancel(query, options, &CancelToken::new(), on_result)
+    }
+
+    /// `search_streaming` that stops with `Cancelled` once `cancel` trips.
+    /// The token is checked after loading the wallet and before each item,
+    /// so results already delivered stay valid.
+    pub fn search_streaming_with_cancel<F>(
+        &mut self,
+        query: &str,
+        options: &SearchOptions,
+        cancel: &CancelToken,
+        mut on_result: F,
+    ) -> Result<usize>
+    where
+        F: FnMut(SearchResult) -> ControlFlow<()>,
+    {
         self.ensure_unlocked()?;
+        cancel.check()?;
 
         // Check minimum phrase length (matching C# SM.CheckPhraseLength)
-        if !is_valid_search_phrase(query) {
-            return Ok(Vec::new());
+        if !is_valid_search_phrase(query) || options.limit == Some(0) {
+            return Ok(0);
         }
 
         let query_lower = query.to_lowercase();
-        let items = self.get_items()?.to_vec();
-        let fields = self.get_fields()?.to_vec();
-
-        let mut results = Vec::new();
+        self.load_items_if_needed()?;
+        cancel.check()?;
+        self.load_fields_if_needed()?;
+        cancel.check()?;
+        let items = self.items_cache.as_ref().unwrap();
+        let profile_of = options.profile_id.as_ref().map(|_| profile_map(items));
+
+        let mut fields_by_item: HashMap<&str, Vec<&IWField>> = HashMap::new();
+        for f in self.fields_cache.as_ref().unwrap() {
+            if options.field_types.is_empty() || options.field_types.contains(&f.field_type) {
+                fields_by_item.entry(f.item_id.as_str()).or_default().push(f);
+            }
+        }
 
+        let mut delivered = 0;
         for item in items.iter() {
+            cancel.check()?;
             if item.item_id == ROOT_ID {
                 continue;
             }
+            if let (Some(wanted), Some(map)) = (&options.profile_id, &profile_of)
+                && (item.item_id == *wanted || map.get(item.item_id.as_str()) != Some(&wanted.as_str())) {
+                    continue;
+                }
 
             // Name match: only for non-folders (matching original C# behavior: !x.Folder)
-            let name_match = !item.folder && item.name.to_lowercase().contains(&query_lower);
+            let name_match = options.match_names
+                && !item.folder
+                && item.name.to_lowercase().contains(&query_lower);
 
             // Field match: search in field values
-            let matching_fields: Vec<IWField> = fields.iter()
-                .filter(|f| f.item_id == item.item_id && f.value.to_lowercase().contains(&query_lower))
-                .cloned()
-                .collect();
+            let matching_fields: Vec<IWField> = fields_by_item.get(item.item_id.as_str())
+                .map(|fields| {
+                    fields.iter()
+                        .filter(|f| f.value.to_lowercase().contains(&query_lower))
+                        .map(|f| (*f).clone())
+                        .collect()
+                })
+                .unwrap_or_default();
 
             let field_match = !matching_fields.is_empty();
 
@@ -81,16 +161,155 @@ impl Wallet {
                     (false, false) => unreachable!(),
                 };
 
-                results.push(SearchResult {
+                delivered += 1;
+                let flow = on_result(SearchResult {
                     item: item.clone(),
                     matching_fields,
                     match_type,
                 });
+                if flow.is_break() || options.limit == Some(delivered) {
+                    break;
+                }
             }
         }
 
+        Ok(delivered)
+    }
+
+    /// Search like `search_streaming` and group the results by the folder
+    /// that holds them. Groups are ordered by folder path (top level
+    /// first); results keep their search order within a group.
+    pub fn search_grouped(&mut self, query: &str, options: &SearchOptions) -> Result<Vec<FolderGroup>> {
+        let mut results = Vec::new();
+        self.search_streaming(query, options, |result| {
+            results.push(result);
+            ControlFlow::Continue(())
+        })?;
+
+        let items = self.get_items()?;
+        let by_id: HashMap<&str, &IWItem> = items.iter().map(|i| (i.item_id.as_str(), i)).collect();
+        let mut groups: Vec<FolderGroup> = Vec::new();
+        let mut group_of: HashMap<String, usize> = HashMap::new();
+        for result in results {
+            let folder_id = result.item.parent_id.clone().unwrap_or_else(|| ROOT_ID.to_string());
+            let index = *group_of.entry(folder_id.clone()).or_insert_with(|| {
+                groups.push(FolderGroup {
+                    path: folder_path(&by_id, &folder_id),
+                    folder_id,
+                    count: 0,
+                    results: Vec::new(),
+                });
+                groups.len() - 1
+            });
+            groups[index].count += 1;
+            groups[index].results.push(result);
+        }
+
+        let key = |g: &FolderGroup| -> Vec<String> { g.path.iter().map(|n| n.to_lowercase()).collect() };
+        groups.sort_by_cached_key(key);
+        Ok(groups)
+    }
+
+    /// Find items for a URL, for autofill.
+    ///
+    /// The URL is reduced to its host (scheme, port, path and `www.` are
+    /// ignored) and compared with every LINK field and with item names that
+    /// look like domains. Results are ranked exact host first, then same
+    /// registrable domain (subdomain), then items whose name merely mentions
+    /// the site name; ties sort by item name.
+    pub fn find_by_url(&mut self, url: &str) -> Result<Vec<UrlMatch>> {
+        self.ensure_unlocked()?;
+
+        let Some(host) = extract_host(url) else {
+            return Ok(Vec::new());
+        };
+        let domain = registrable_domain(&host);
+        // "example" for example.co.uk: the site name used for fuzzy matching
+        let site_name = domain.split('.').next().unwrap_or("").to_string();
+
+        let rank_host = |candidate: &str| -> Option<UrlMatchRank> {
+            let candidate_host = extract_host(candidate)?;
+            if candidate_host == host {
+                Some(UrlMatchRank::ExactDomain)
+            } else if registrable_domain(&candidate_host) == domain {
+                Some(UrlMatchRank::Subdomain)
+            } else {
+                None
+            }
+        };
+
+        let items = self.get_items()?.to_vec();
+        let fields = self.get_fields()?.to_vec();
+
+        let mut results = Vec::new();
+        for item in items.iter() {
+            if item.item_id == ROOT_ID || item.folder {
+                continue;
+            }
+
+            let mut matching_fields = Vec::new();
+            let mut best: Option<UrlMatchRank> = None;
+            for field in fields.iter().filter(|f| f.item_id == item.item_id
+                && (f.field_type == "LINK" || f.value_type == "link"))
+            {
+                if let Some(rank) = rank_host(&field.value) {
+                    best = Some(best.map_or(rank, |b| b.min(rank)));
+                    matching_fields.push(field.clone());
+                }
+            }
+
+            let name = item.name.trim();
+            if name.contains('.') && !name.contains(' ')
+                && let Some(rank) = rank_host(name)
+            {
+                best = Some(best.map_or(rank, |b| b.min(rank)));
+            }
+            if best.is_none() && site_name.len() >= 3 && contains_phrase(name, &site_name) {
+                best = Some(UrlMatchRank::Fuzzy);
+            }
+
+            if let Some(rank) = best {
+                results.push(UrlMatch {
+                    item: item.clone(),
+                    matching_fields,
+                    rank,
+                });
+            }
+        }
+
+        results.sort_by(|a, b| a.rank.cmp(&b.rank)
+            .then_with(|| a.item.name.to_lowercase().cmp(&b.item.name.to_lowercase())));
         Ok(results)
     }
+
+    /// Items with a field of `field_type` whose value is exactly `value`,
+    /// sorted by name, e.g. to find which item holds an API key.
+    ///
+    /// Unlike `search`, nothing matches on a part of the value, and values
+    /// are compared by HMAC under a key drawn for this call, in constant
+    /// time, so the time taken does not reveal how much of a stored value
+    /// matched.
+    pub fn find_by_field_value(&mut self, field_type: &str, value: &str) -> Result<Vec<IWItem>> {
+        self.ensure_unlocked()?;
+
+        let key = random_bytes(32);
+        let mac = |text: &str| <HmacSha256 as KeyInit>::new_from_slice(&key)
+            .expect("HMAC accepts keys of any length")
+            .chain_update(text.as_bytes());
+        let wanted = mac(value).finalize().into_bytes();
+
+        let item_ids: HashSet<String> = self.get_fields()?.iter()
+            .filter(|f| !f.deleted && f.field_type == field_type)
+            .filter(|f| mac(&f.value).verify_slice(&wanted).is_ok())
+            .map(|f| f.item_id.clone())
+            .collect();
+        let mut items: Vec<IWItem> = self.get_items()?.iter()
+            .filter(|i| !i.deleted && item_ids.contains(&i.item_id))
+            .cloned()
+            .collect();
+        items.sort_by_key(|i| i.name.to_lowercase());
+        Ok(items)
+    }
 }
 
 #[cfg(test)]
@@ -227,4 +446,152 @@ mod tests {
         assert_eq!(results.len(), 1);
         assert_eq!(results[0].match_type, SearchMatchType::Both);
     }
+
+    #[test]
+    fn test_search_streaming_with_cancel_token() {
+        let (mut wallet, _temp) = create_test_wallet();
+        for name in ["Acme One", "Acme Two"] {
+            wallet.add_item(name, "document", false, None).unwrap();
+        }
+        let cancel = CancelToken::new();
+        let mut seen = 0;
+        let result = wallet.search_streaming_with_cancel("acme", &SearchOptions::default(), &cancel, |_| {
+            seen += 1;
+            cancel.cancel();
+            ControlFlow::Continue(())
+        });
+        assert!(matches!(result, Err(crate::WalletError::Cancelled)));
+        assert_eq!(seen, 1);
+        let n = wallet.search_streaming_with_cancel("acme", &SearchOptions::default(), &CancelToken::new(), |_| ControlFlow::Continue(()));
+        assert_eq!(n.unwrap(), 2);
+    }
+
+    #[test]
+    fn test_search_streaming_cancel_and_options() {
+        let (mut wallet, _temp) = create_test_wallet();
+        for name in ["Acme One", "Acme Two", "Acme Three"] {
+            wallet.add_item(name, "document", false, None).unwrap();
+        }
+        let other = wallet.add_item("Other", "document", false, None).unwrap();
+        wallet.add_field(&other, "MAIL", "me@acme.com", None).unwrap();
+        wallet.add_field(&other, "NOTE", "acme note", None).unwrap();
+
+        // Cancel after the first result
+        let mut seen = Vec::new();
+        let n = wallet.search_streaming("acme", &SearchOptions::default(), |r| {
+            seen.push(r.item.name);
+            ControlFlow::Break(())
+        }).unwrap();
+        assert_eq!(n, 1);
+        assert_eq!(seen.len(), 1);
+
+        let limited = SearchOptions { limit: Some(2), ..Default::default() };
+        assert_eq!(wallet.search_streaming("acme", &limited, |_| ControlFlow::Continue(())).unwrap(), 2);
+
+        // Field values only, restricted to MAIL
+        let mail_only = SearchOptions { match_names: false, field_types: vec!["MAIL".into()], ..Default::default() };
+        let mut results = Vec::new();
+        wallet.search_streaming("acme", &mail_only, |r| {
+            results.push(r);
+            ControlFlow::Continue(())
+        }).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].item.item_id, other);
+        assert_eq!(results[0].matching_fields.len(), 1);
+        assert_eq!(results[0].match_type, SearchMatchType::Field);
+
+        // search() is the unfiltered stream collected
+        assert_eq!(wallet.search("acme").unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_search_grouped() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let work = wallet.add_item("Work", "folder", true, None).unwrap();
+        let banks = wallet.add_item("Banks", "folder", true, Some(&work)).unwrap();
+        wallet.add_item("Acme bank", "document", false, Some(&banks)).unwrap();
+        wallet.add_item("Acme card", "document", false, Some(&banks)).unwrap();
+        wallet.add_item("Acme mail", "document", false, Some(&work)).unwrap();
+        wallet.add_item("Acme home", "document", false, None).unwrap();
+        wallet.add_item("Unrelated", "document", false, Some(&banks)).unwrap();
+
+        let groups = wallet.search_grouped("acme", &SearchOptions::default()).unwrap();
+        let summary: Vec<(Vec<String>, usize)> = groups.iter().map(|g| (g.path.clone(), g.count)).collect();
+        assert_eq!(summary, vec![
+            (vec![], 1),
+            (vec!["Work".to_string()], 1),
+            (vec!["Work".to_string(), "Banks".to_string()], 2),
+        ]);
+        assert_eq!(groups[0].folder_id, ROOT_ID);
+        assert_eq!(groups[2].folder_id, banks);
+        assert!(groups[2].results.iter().all(|r| r.item.parent_id.as_deref() == Some(banks.as_str())));
+
+        assert!(wallet.search_grouped("a", &SearchOptions::default()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_find_by_url_ranking() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let exact = wallet.add_item("Main account", "document", false, None).unwrap();
+        wallet.add_field(&exact, "LINK", "https://www.example.com/login", None).unwrap();
+        let sub = wallet.add_item("Webmail", "document", false, None).unwrap();
+        wallet.add_field(&sub, "LINK", "mail.example.com", None).unwrap();
+        let fuzzy = wallet.add_item("Example shop card", "document", false, None).unwrap();
+        let other = wallet.add_item("Other", "document", false, None).unwrap();
+        wallet.add_field(&other, "LINK", "https://example.org", None).unwrap();
+
+        let results = wallet.find_by_url("http://example.com/account?id=5").unwrap();
+        let ranked: Vec<(&str, UrlMatchRank)> = results.iter()
+            .map(|r| (r.item.item_id.as_str(), r.rank))
+            .collect();
+        assert_eq!(ranked, vec![
+            (exact.as_str(), UrlMatchRank::ExactDomain),
+            (sub.as_str(), UrlMatchRank::Subdomain),
+            (fuzzy.as_str(), UrlMatchRank::Fuzzy),
+        ]);
+        assert_eq!(results[0].matching_fields.len(), 1);
+        assert!(results[2].matching_fields.is_empty());
+    }
+
+    #[test]
+    fn test_find_by_url_domain_like_item_name() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let item = wallet.add_item("bbc.co.uk", "document", false, None).unwrap();
+
+        let results = wallet.find_by_url("https://news.bbc.co.uk/").unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].item.item_id, item);
+        assert_eq!(results[0].rank, UrlMatchRank::Subdomain);
+
+        assert!(wallet.find_by_url("https://co.uk").unwrap().is_empty());
+
+        // Sites on a hosting platform are separate sites
+        let pages = wallet.add_item("Pages", "document", false, None).unwrap();
+        wallet.add_field(&pages, "LINK", "https://a.github.io/", None).unwrap();
+        assert!(wallet.find_by_url("https://b.github.io/").unwrap().is_empty());
+        assert_eq!(wallet.find_by_url("https://docs.a.github.io/").unwrap()[0].rank, UrlMatchRank::Subdomain);
+        assert!(wallet.find_by_url("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_find_by_field_value() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let ci = wallet.add_item("CI", "document", false, None).unwrap();
+        wallet.add_field(&ci, "PASS", "sk-live-4242", None).unwrap();
+        let deploy = wallet.add_item("deploy bot", "document", false, None).unwrap();
+        wallet.add_field(&deploy, "PASS", "sk-live-4242", None).unwrap();
+        let other = wallet.add_item("Other", "document", false, None).unwrap();
+        wallet.add_field(&other, "PASS", "sk-live-42", None).unwrap();
+        wallet.add_field(&other, "NOTE", "sk-live-4242", None).unwrap();
+
+        let names: Vec<String> = wallet.find_by_field_value("PASS", "sk-live-4242").unwrap()
+            .into_iter().map(|i| i.name).collect();
+        assert_eq!(names, ["CI", "deploy bot"]);
+        // Exact values only: no prefixes or substrings
+        assert!(wallet.find_by_field_value("PASS", "sk-live").unwrap().is_empty());
+        assert_eq!(wallet.find_by_field_value("NOTE", "sk-live-4242").unwrap()[0].item_id, other);
+
+        wallet.delete_item(&ci).unwrap();
+        assert_eq!(wallet.find_by_field_value("PASS", "sk-live-4242").unwrap().len(), 1);
+    }
 }
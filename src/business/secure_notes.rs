@@ -0,0 +1,408 @@
+//! Secure notes
+//!
+//! A secure note is an item whose body is long free text: recovery codes,
+//! licence files, multi-page instructions. Unlike the per-item note (see
+//! `notes`), the body has no size limit and is stored in its own table as a
+//! sequence of separately encrypted chunks. `SecureNoteReader` and
+//! `SecureNoteWriter` stream it one chunk at a time, so neither side needs
+//! the whole text in memory and the fields table never carries it. Each
+//! chunk is bound to its note, its index and whether it ends the body, so
+//! chunks cannot be swapped between notes, reordered or cut off.
+
+use std::io::{self, Read, Write};
+
+use zeroize::Zeroizing;
+
+use crate::crypto;
+use crate::database::{queries, IWField};
+use crate::error::{WalletError, Result};
+use super::wallet::Wallet;
+
+/// Icon given to items created by `add_secure_note`
+pub const SECURE_NOTE_ICON: &str = "note";
+
+/// Plaintext bytes per stored chunk of a secure note body
+pub const SECURE_NOTE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Associated data of chunk `chunk` of the body of `item_id`
+pub(crate) fn chunk_aad(item_id: &str, chunk: u32, last: bool) -> Vec<u8> {
+    let mut aad = Vec::with_capacity(item_id.len() + 6);
+    aad.extend_from_slice(item_id.as_bytes());
+    aad.push(0);
+    aad.extend_from_slice(&chunk.to_be_bytes());
+    aad.push(last as u8);
+    aad
+}
+
+impl Wallet {
+    /// Create a secure note item with an empty body. Returns the item ID.
+    pub fn add_secure_note(&mut self, name: &str, parent_id: Option<&str>) -> Result<String> {
+        self.add_secure_note_with_icon(name, SECURE_NOTE_ICON, parent_id)
+    }
+
+    /// [`Wallet::add_secure_note`] with an icon of the caller's choosing
+    pub(crate) fn add_secure_note_with_icon(&mut self, name: &str, icon: &str, parent_id: Option<&str>) -> Result<String> {
+        if let Some(parent_id) = parent_id {
+            self.ensure_item_accessible(parent_id)?;
+        }
+        self.add_item_with(name, icon, false, parent_id, |wallet, conn, item_id| {
+            let empty = wallet.seal_note_chunk(item_id, 0, true, b"")?;
+            queries::set_secure_note_chunk(conn, item_id, 0, &empty)
+        })
+    }
+
+    /// Seal chunk `chunk` of the body of `item_id`; `last` if it ends the body
+    pub(crate) fn seal_note_chunk(&self, item_id: &str, chunk: u32, last: bool, plaintext: &[u8]) -> Result<Vec<u8>> {
+        self.seal_item_data_with_aad(item_id, plaintext, &chunk_aad(item_id, chunk, last))
+    }
+
+    /// Open a chunk sealed with [`Wallet::seal_note_chunk`]
+    pub(crate) fn open_note_chunk(&self, item_id: &str, chunk: u32, last: bool, blob: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+        self.open_item_data_with_aad(item_id, blob, &chunk_aad(item_id, chunk, last))
+    }
+
+    /// True if the item is a secure note
+    pub fn is_secure_note(&self, item_id: &str) -> Result<bool> {
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        Ok(queries::get_secure_note_chunk_count(conn, item_id)? > 0)
+    }
+
+    /// Stream a secure note's body. Chunks are loaded and decrypted as the
+    /// reader advances.
+    pub fn secure_note_reader(&self, item_id: &str) -> Result<SecureNoteReader<'_>> {
+        self.ensure_unlocked()?;
+        self.ensure_item_accessible(item_id)?;
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        let chunks = queries::get_secure_note_chunk_count(conn, item_id)?;
+        if chunks == 0 {
+            return Err(not_a_secure_note(conn, item_id)?);
+        }
+        Ok(SecureNoteReader {
+            wallet: self,
+            item_id: item_id.to_string(),
+            chunks,
+            next_chunk: 0,
+            buf: Zeroizing::new(Vec::new()),
+            pos: 0,
+        })
+    }
+
+    /// Replace a secure note's body by streaming the new text in. Nothing is
+    /// stored until `SecureNoteWriter::finish`; dropping the writer without
+    /// finishing keeps the previous body.
+    pub fn secure_note_writer(&mut self, item_id: &str) -> Result<SecureNoteWriter<'_>> {
+        self.ensure_unlocked()?;
+        self.ensure_item_editable(item_id)?;
+        self.ensure_item_accessible(item_id)?;
+        let db = self.db.as_mut()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?;
+        let conn = db.connection()?;
+        if queries::get_secure_note_chunk_count(conn, item_id)? == 0 {
+            return Err(not_a_secure_note(conn, item_id)?);
+        }
+        db.begin_transaction()?;
+        Ok(SecureNoteWriter {
+            wallet: self,
+            item_id: item_id.to_string(),
+            buf: Zeroizing::new(Vec::with_capacity(SECURE_NOTE_CHUNK_SIZE)),
+            next_chunk: 0,
+            written: 0,
+            finished: false,
+        })
+    }
+
+    /// Read a whole secure note body into a string
+    pub fn get_secure_note_body(&self, item_id: &str) -> Result<String> {
+        let mut body = Vec::new();
+        self.secure_note_reader(item_id)?.read_to_end(&mut body)?;
+        String::from_utf8(body)
+            .map_err(|_| crypto::DecryptError::BadUtf8.into())
+    }
+
+    /// Replace a secure note body with `text`
+    pub fn set_secure_note_body(&mut self, item_id: &str, text: &str) -> Result<()> {
+        let mut writer = self.secure_note_writer(item_id)?;
+        writer.write_all(text.as_bytes())?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    /// All fields, plus the body of each secure note as a NOTE field
+    /// without a field ID, so exports carry the bodies. Notes behind a
+    /// locked folder PIN are left out like their fields.
+    pub(crate) fn fields_with_secure_notes(&mut self) -> Result<Vec<IWField>> {
+        let mut fields = self.get_fields()?.to_vec();
+        let note_ids = {
+            let conn = self.db.as_ref()
+                .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+                .connection()?;
+            queries::get_secure_note_item_ids(conn)?
+        };
+        if note_ids.is_empty() {
+            return Ok(fields);
+        }
+        let label = self.get_labels()?.iter().find(|l| l.field_type == "NOTE").cloned();
+        for item_id in note_ids {
+            let Some(item) = self.get_item(&item_id)? else { continue };
+            let body = match self.get_secure_note_body(&item_id) {
+                Ok(body) => body,
+                Err(WalletError::FolderLocked(_)) => continue,
+                Err(e) => return Err(e),
+            };
+            fields.push(IWField {
+                item_id,
+                field_id: String::new(),
+                field_type: "NOTE".to_string(),
+                value: body,
+                label: label.as_ref().map_or_else(|| "Note".to_string(), |l| l.name.clone()),
+                icon: label.as_ref().map_or_else(String::new, |l| l.icon.clone()),
+                value_type: label.as_ref().map_or_else(|| "text".to_string(), |l| l.value_type.clone()),
+                sort_weight: i32::MAX,
+                change_timestamp: item.change_timestamp,
+                deleted: false,
+                expired: false,
+                expiring: false,
+            });
+        }
+        Ok(fields)
+    }
+}
+
+/// The error for an item without a secure note body
+fn not_a_secure_note(conn: &rusqlite::Connection, item_id: &str) -> Result<WalletError> {
+    if !queries::item_exists(conn, item_id)? {
+        return Ok(WalletError::ItemNotFound(item_id.to_string()));
+    }
+    Ok(WalletError::InvalidOperation(format!("Item {item_id} is not a secure note")))
+}
+
+/// Streaming reader over a secure note body, see `Wallet::secure_note_reader`
+pub struct SecureNoteReader<'a> {
+    wallet: &'a Wallet,
+    item_id: String,
+    chunks: u32,
+    next_chunk: u32,
+    buf: Zeroizing<Vec<u8>>,
+    pos: usize,
+}
+
+impl SecureNoteReader<'_> {
+    fn load_next_chunk(&mut self) -> Result<()> {
+        let conn = self.wallet.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        let blob = queries::get_secure_note_chunk(conn, &self.item_id, self.next_chunk)?
+            .ok_or_else(|| WalletError::DatabaseError(format!(
+                "secure note {} is missing chunk {}", self.item_id, self.next_chunk
+            )))?;
+        let last = self.next_chunk + 1 == self.chunks;
+        self.buf = self.wallet.open_note_chunk(&self.item_id, self.next_chunk, last, &blob)?;
+        self.pos = 0;
+        self.next_chunk += 1;
+        Ok(())
+    }
+}
+
+impl Read for SecureNoteReader<'_> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.buf.len() {
+            if self.next_chunk >= self.chunks {
+                return Ok(0);
+            }
+            self.load_next_chunk().map_err(io::Error::other)?;
+        }
+        let n = out.len().min(self.buf.len() - self.pos);
+        out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Streaming writer for a secure note body, see `Wallet::secure_note_writer`.
+/// Full chunks are encrypted and stored once more text follows them, inside
+/// a transaction that `finish` commits; the last chunk is stored by `finish`.
+pub struct SecureNoteWriter<'a> {
+    wallet: &'a mut Wallet,
+    item_id: String,
+    buf: Zeroizing<Vec<u8>>,
+    next_chunk: u32,
+    written: u64,
+    finished: bool,
+}
+
+impl SecureNoteWriter<'_> {
+    fn store_chunk(&mut self, len: usize, last: bool) -> Result<()> {
+        let sealed = self.wallet.seal_note_chunk(&self.item_id, self.next_chunk, last, &self.buf[..len])?;
+        let conn = self.wallet.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        queries::set_secure_note_chunk(conn, &self.item_id, self.next_chunk, &sealed)?;
+        self.buf.drain(..len);
+        self.next_chunk += 1;
+        Ok(())
+    }
+
+    /// Store the remaining text, drop chunks left over from a longer
+    /// previous body and commit. Returns the body size in bytes.
+    pub fn finish(mut self) -> Result<u64> {
+        let pass = (|| -> Result<()> {
+            // `write` always leaves text behind, unless the body is empty
+            self.store_chunk(self.buf.len(), true)?;
+            let conn = self.wallet.db.as_ref()
+                .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+                .connection()?;
+            queries::delete_secure_note_chunks(conn, &self.item_id, self.next_chunk)?;
+            Ok(())
+        })();
+
+        self.finished = true;
+        let db = self.wallet.db.as_mut()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?;
+        match pass {
+            Ok(()) => db.commit_transaction()?,
+            Err(e) => {
+                let _ = db.rollback_transaction();
+                return Err(e);
+            }
+        }
+        let _ = db.checkpoint();
+        self.wallet.note_mutation();
+        Ok(self.written)
+    }
+}
+
+impl Write for SecureNoteWriter<'_> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        self.written += data.len() as u64;
+        // A chunk is stored once text follows it, so it is not the last one
+        while self.buf.len() > SECURE_NOTE_CHUNK_SIZE {
+            self.store_chunk(SECURE_NOTE_CHUNK_SIZE, false).map_err(io::Error::other)?;
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for SecureNoteWriter<'_> {
+    fn drop(&mut self) {
+        if !self.finished
+            && let Some(db) = self.wallet.db.as_mut() {
+                let _ = db.rollback_transaction();
+            }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use super::SECURE_NOTE_CHUNK_SIZE;
+    use crate::business::wallet::tests::create_test_wallet;
+    use crate::database::queries;
+    use crate::error::WalletError;
+
+    #[test]
+    fn test_secure_note_streaming_roundtrip() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let note = wallet.add_secure_note("Recovery codes", None).unwrap();
+        assert!(wallet.is_secure_note(&note).unwrap());
+        assert_eq!(wallet.get_secure_note_body(&note).unwrap(), "");
+
+        // Two and a half chunks, written in odd-sized pieces
+        let body: String = (0..SECURE_NOTE_CHUNK_SIZE * 5 / 2)
+            .map(|i| char::from(b'a' + (i % 26) as u8))
+            .collect();
+        let mut writer = wallet.secure_note_writer(&note).unwrap();
+        for piece in body.as_bytes().chunks(10_000) {
+            writer.write_all(piece).unwrap();
+        }
+        assert_eq!(writer.finish().unwrap(), body.len() as u64);
+
+        let conn = wallet.database().unwrap().connection().unwrap();
+        assert_eq!(queries::get_secure_note_chunk_count(conn, &note).unwrap(), 3);
+        assert!(wallet.get_fields_by_item(&note).unwrap().is_empty());
+
+        let mut reader = wallet.secure_note_reader(&note).unwrap();
+        let mut first = vec![0u8; 100];
+        reader.read_exact(&mut first).unwrap();
+        assert_eq!(&first[..], &body.as_bytes()[..100]);
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(&rest[..], &body.as_bytes()[100..]);
+
+        // A shorter body drops the surplus chunks
+        wallet.set_secure_note_body(&note, "short").unwrap();
+        let conn = wallet.database().unwrap().connection().unwrap();
+        assert_eq!(queries::get_secure_note_chunk_count(conn, &note).unwrap(), 1);
+        assert_eq!(wallet.get_secure_note_body(&note).unwrap(), "short");
+    }
+
+    #[test]
+    fn test_secure_note_chunks_are_bound_to_their_place() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let note = wallet.add_secure_note("Note", None).unwrap();
+        let other = wallet.add_secure_note("Other", None).unwrap();
+        // Exactly two full chunks: the second one still ends the body
+        let body = "ab".repeat(SECURE_NOTE_CHUNK_SIZE);
+        wallet.set_secure_note_body(&note, &body).unwrap();
+        wallet.set_secure_note_body(&other, "other").unwrap();
+        assert_eq!(wallet.get_secure_note_body(&note).unwrap(), body);
+
+        let conn = wallet.database().unwrap().connection().unwrap();
+        let chunk = |item_id: &str, n: u32| queries::get_secure_note_chunk(conn, item_id, n).unwrap().unwrap();
+        let (first, second, foreign) = (chunk(&note, 0), chunk(&note, 1), chunk(&other, 0));
+
+        // Cut off after the first chunk
+        queries::delete_secure_note_chunks(conn, &note, 1).unwrap();
+        assert!(wallet.get_secure_note_body(&note).is_err());
+        // Reordered
+        queries::set_secure_note_chunk(conn, &note, 0, &second).unwrap();
+        queries::set_secure_note_chunk(conn, &note, 1, &first).unwrap();
+        assert!(wallet.get_secure_note_body(&note).is_err());
+        // Taken from another note
+        queries::delete_secure_note_chunks(conn, &note, 0).unwrap();
+        queries::set_secure_note_chunk(conn, &note, 0, &foreign).unwrap();
+        assert!(wallet.get_secure_note_body(&note).is_err());
+    }
+
+    #[test]
+    fn test_secure_note_writer_dropped_keeps_body() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let note = wallet.add_secure_note("Note", None).unwrap();
+        wallet.set_secure_note_body(&note, "original").unwrap();
+
+        {
+            let mut writer = wallet.secure_note_writer(&note).unwrap();
+            writer.write_all(&vec![b'x'; SECURE_NOTE_CHUNK_SIZE + 1]).unwrap();
+        }
+        assert_eq!(wallet.get_secure_note_body(&note).unwrap(), "original");
+    }
+
+    #[test]
+    fn test_secure_note_errors_and_purge() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let plain = wallet.add_item("Item", "document", false, None).unwrap();
+        assert!(!wallet.is_secure_note(&plain).unwrap());
+        assert!(matches!(wallet.secure_note_reader(&plain), Err(WalletError::InvalidOperation(_))));
+        assert!(matches!(wallet.secure_note_writer("NOPE1234"), Err(WalletError::ItemNotFound(_))));
+
+        let note = wallet.add_secure_note("Note", None).unwrap();
+        wallet.set_item_locked(&note, true).unwrap();
+        assert!(matches!(wallet.set_secure_note_body(&note, "x"), Err(WalletError::ItemLocked(_))));
+        wallet.set_item_locked(&note, false).unwrap();
+
+        wallet.set_secure_note_body(&note, "secret").unwrap();
+        wallet.delete_item(&note).unwrap();
+        wallet.compact().unwrap();
+        let conn = wallet.database().unwrap().connection().unwrap();
+        assert_eq!(queries::get_secure_note_chunk_count(conn, &note).unwrap(), 0);
+    }
+}
@@ -0,0 +1,203 @@
+//! Session tokens for FFI and daemon hosts
+//!
+//! A host that serves several calls per unlock (an RPC daemon, a browser
+//! extension bridge) should not keep the master password around to prove
+//! each call is allowed. [`Wallet::create_session`] hands out an opaque
+//! token instead; the host passes it back with every call and checks it
+//! with [`Wallet::authorize`] before acting.
+//!
+//! Sessions live only in memory, inside the unlocked state: locking the
+//! wallet revokes them all, and they never outlast the process. Only a
+//! SHA-256 of each token is kept, so tokens cannot be read back.
+
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use crate::error::{WalletError, Result};
+use super::wallet::Wallet;
+
+/// Token length in random bytes (hex-encoded in [`SessionToken`])
+const TOKEN_BYTES: usize = 32;
+
+/// Restrictions of a session; the default allows everything
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionPermissions {
+    /// Reads only: no item, field or label changes
+    pub read_only: bool,
+    /// No exports, backups or shares of wallet data
+    pub no_export: bool,
+}
+
+/// What a call is about to do, for [`Wallet::authorize`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SessionAccess {
+    Read,
+    Write,
+    Export,
+}
+
+/// Opaque session token. Its `Debug` output hides the value.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SessionToken(String);
+
+impl SessionToken {
+    /// The token as text, for passing over FFI or RPC
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for SessionToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SessionToken(..)")
+    }
+}
+
+/// Public details of a session
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionInfo {
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub permissions: SessionPermissions,
+}
+
+/// A session held in the unlocked state
+pub(crate) struct Session {
+    info: SessionInfo,
+    expires: Instant,
+}
+
+fn token_hash(token: &str) -> [u8; 32] {
+    Sha256::digest(token.as_bytes()).into()
+}
+
+impl Wallet {
+    /// Start a session with all permissions, valid for `ttl`
+    pub fn create_session(&mut self, ttl: Duration) -> Result<SessionToken> {
+        self.create_session_with(ttl, SessionPermissions::default())
+    }
+
+    /// Start a session with `permissions`, valid for `ttl` or until the
+    /// wallet is locked
+    pub fn create_session_with(&mut self, ttl: Duration, permissions: SessionPermissions) -> Result<SessionToken> {
+        let unlocked = self.unlocked.as_mut().ok_or(WalletError::Locked)?;
+        let now = Instant::now();
+        unlocked.sessions.retain(|_, s| s.expires > now);
+
+        let mut bytes = [0u8; TOKEN_BYTES];
+        rand::rng().fill_bytes(&mut bytes);
+        let token: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+
+        let created_at = Utc::now();
+        let expires_at = chrono::TimeDelta::from_std(ttl).ok()
+            .and_then(|d| created_at.checked_add_signed(d))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC);
+        let expires = now.checked_add(ttl).unwrap_or(now + Duration::from_secs(100 * 365 * 24 * 3600));
+        unlocked.sessions.insert(token_hash(&token), Session {
+            info: SessionInfo { created_at, expires_at, permissions },
+            expires,
+        });
+        Ok(SessionToken(token))
+    }
+
+    /// Details of a live session. Fails with `InvalidSession` for an
+    /// unknown, expired or revoked token, and with `Locked` once the wallet
+    /// is locked.
+    pub fn session_info(&self, token: &str) -> Result<SessionInfo> {
+        let unlocked = self.unlocked.as_ref().ok_or(WalletError::Locked)?;
+        match unlocked.sessions.get(&token_hash(token)) {
+            Some(session) if session.expires > Instant::now() => Ok(session.info.clone()),
+            _ => Err(WalletError::InvalidSession),
+        }
+    }
+
+    /// Check that the session of `token` may perform `access`. Fails with
+    /// `InvalidSession` or, when its permissions forbid it,
+    /// `PermissionDenied`.
+    pub fn authorize(&self, token: &str, access: SessionAccess) -> Result<()> {
+        let permissions = self.session_info(token)?.permissions;
+        let denied = match access {
+            SessionAccess::Read => false,
+            SessionAccess::Write => permissions.read_only,
+            SessionAccess::Export => permissions.no_export,
+        };
+        if denied {
+            return Err(WalletError::PermissionDenied(format!("{access:?} not allowed in this session")));
+        }
+        Ok(())
+    }
+
+    /// End a session. Returns whether it was live.
+    pub fn revoke_session(&mut self, token: &str) -> bool {
+        let Some(unlocked) = self.unlocked.as_mut() else {
+            return false;
+        };
+        unlocked.sessions.remove(&token_hash(token))
+            .is_some_and(|s| s.expires > Instant::now())
+    }
+
+    /// End all sessions without locking the wallet
+    pub fn revoke_all_sessions(&mut self) {
+        if let Some(unlocked) = self.unlocked.as_mut() {
+            unlocked.sessions.clear();
+        }
+    }
+
+    /// Number of live sessions
+    pub fn active_sessions(&self) -> usize {
+        let now = Instant::now();
+        self.unlocked.as_ref().map_or(0, |u| u.sessions.values().filter(|s| s.expires > now).count())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::business::wallet::tests::create_test_wallet;
+
+    #[test]
+    fn test_session_permissions_and_revocation() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let full = wallet.create_session(Duration::from_secs(600)).unwrap();
+        let viewer = wallet.create_session_with(
+            Duration::from_secs(600),
+            SessionPermissions { read_only: true, no_export: true },
+        ).unwrap();
+        assert_ne!(full, viewer);
+        assert_eq!(format!("{full:?}"), "SessionToken(..)");
+        assert_eq!(wallet.active_sessions(), 2);
+
+        wallet.authorize(full.as_str(), SessionAccess::Write).unwrap();
+        wallet.authorize(full.as_str(), SessionAccess::Export).unwrap();
+        wallet.authorize(viewer.as_str(), SessionAccess::Read).unwrap();
+        assert!(matches!(wallet.authorize(viewer.as_str(), SessionAccess::Write), Err(WalletError::PermissionDenied(_))));
+        assert!(matches!(wallet.authorize(viewer.as_str(), SessionAccess::Export), Err(WalletError::PermissionDenied(_))));
+        assert!(matches!(wallet.authorize("forged", SessionAccess::Read), Err(WalletError::InvalidSession)));
+
+        assert!(wallet.revoke_session(viewer.as_str()));
+        assert!(!wallet.revoke_session(viewer.as_str()));
+        assert!(matches!(wallet.authorize(viewer.as_str(), SessionAccess::Read), Err(WalletError::InvalidSession)));
+
+        // Locking ends every session, and they do not come back on unlock
+        wallet.lock();
+        assert!(matches!(wallet.authorize(full.as_str(), SessionAccess::Read), Err(WalletError::Locked)));
+        assert!(wallet.create_session(Duration::from_secs(1)).is_err());
+        wallet.unlock("TestPassword123").unwrap();
+        assert!(matches!(wallet.authorize(full.as_str(), SessionAccess::Read), Err(WalletError::InvalidSession)));
+    }
+
+    #[test]
+    fn test_session_expiry() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let token = wallet.create_session(Duration::ZERO).unwrap();
+        assert!(matches!(wallet.session_info(token.as_str()), Err(WalletError::InvalidSession)));
+        assert_eq!(wallet.active_sessions(), 0);
+
+        let token = wallet.create_session(Duration::from_secs(3600)).unwrap();
+        let info = wallet.session_info(token.as_str()).unwrap();
+        assert_eq!((info.expires_at - info.created_at).num_seconds(), 3600);
+        assert_eq!(info.permissions, SessionPermissions::default());
+    }
+}
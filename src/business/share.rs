@@ -0,0 +1,368 @@
+//! Single-item share files
+//!
+//! A share file carries one item with its fields, encrypted under a key
+//! derived from a passphrase the sender and recipient agree on, so a login
+//! can be handed to someone else without the vault key or the rest of the
+//! vault. Layout:
+//!
+//! ```text
+//! "IWSHARE" | version (1 byte) | m_cost, t_cost, p_cost (u32 LE each) | salt | sealed payload
+//! ```
+//!
+//! The payload is JSON sealed with XChaCha20-Poly1305 under an Argon2id
+//! key; the KDF parameters and salt travel in the header so the file is
+//! self-describing. Custom labels used by the fields are included and
+//! created on import when the recipient has no matching label. A secure
+//! note travels with its body.
+//!
+//! Instead of a passphrase, a share file can be encrypted to the age public
+//! keys of one or more devices ([`Wallet::export_shared_item_to_recipients`]).
+//! Such a file is a plain age file around the same JSON payload and opens
+//! with the secret identity of any of those devices, so no secret has to be
+//! agreed on.
+
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::crypto;
+use crate::error::{WalletError, Result};
+use crate::utils::sanitize_filename;
+use super::wallet::{random_bytes, Wallet, KDF_SALT_LEN};
+
+/// File extension of share files, without the dot
+pub const SHARE_EXTENSION: &str = "iwshare";
+
+const SHARE_MAGIC: &[u8] = b"IWSHARE";
+const SHARE_VERSION: u8 = 1;
+const HEADER_LEN: usize = SHARE_MAGIC.len() + 1 + 12 + KDF_SALT_LEN;
+
+#[derive(Serialize, Deserialize)]
+struct SharedItem {
+    name: String,
+    icon: String,
+    #[serde(default)]
+    color: Option<String>,
+    fields: Vec<SharedField>,
+    /// Index into `fields` of the item's primary field
+    #[serde(default)]
+    primary: Option<usize>,
+    labels: Vec<SharedLabel>,
+    /// Body of the item if it is a secure note
+    #[serde(default)]
+    secure_note: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SharedField {
+    field_type: String,
+    value: String,
+    sort_weight: i32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SharedLabel {
+    field_type: String,
+    name: String,
+    value_type: String,
+    icon: String,
+}
+
+fn share_key(passphrase: &str, salt: &[u8], params: crypto::kdf::KdfParams) -> Result<Zeroizing<[u8; crypto::kdf::KEK_LEN]>> {
+    crypto::kdf::derive_kek(passphrase.as_bytes(), salt, params)
+        .map(Zeroizing::new)
+        .map_err(WalletError::EncryptionError)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+impl Wallet {
+    /// Encrypt one item and its active fields into a share file readable
+    /// with `passphrase`. Folders cannot be shared.
+    pub fn export_shared_item(&mut self, item_id: &str, passphrase: &str) -> Result<Vec<u8>> {
+        self.ensure_unlocked()?;
+        if passphrase.is_empty() {
+            return Err(WalletError::InvalidOperation("Share passphrase must not be empty".to_string()));
+        }
+        let json = self.shared_item_payload(item_id)?;
+
+        let params = crypto::kdf::KdfParams::current();
+        let salt = random_bytes(KDF_SALT_LEN);
+        let key = share_key(passphrase, &salt, params)?;
+        let sealed = crypto::aead::seal(&key, &json).map_err(WalletError::EncryptionError)?;
+
+        let mut out = Vec::with_capacity(HEADER_LEN + sealed.len());
+        out.extend_from_slice(SHARE_MAGIC);
+        out.push(SHARE_VERSION);
+        out.extend_from_slice(&params.m_cost_kib.to_le_bytes());
+        out.extend_from_slice(&params.t_cost.to_le_bytes());
+        out.extend_from_slice(&params.p_cost.to_le_bytes());
+        out.extend_from_slice(&salt);
+        out.extend_from_slice(&sealed);
+        Ok(out)
+    }
+
+    /// Encrypt one item and its active fields into a share file readable
+    /// by the holder of any of the age `recipients` ("age1..." public keys)
+    pub fn export_shared_item_to_recipients(&mut self, item_id: &str, recipients: &[&str]) -> Result<Vec<u8>> {
+        self.ensure_unlocked()?;
+        crypto::recipients::validate_recipients(recipients).map_err(WalletError::ValidationError)?;
+        let json = self.shared_item_payload(item_id)?;
+        crypto::recipients::encrypt(&json, recipients).map_err(WalletError::EncryptionError)
+    }
+
+    /// JSON payload of a share file for `item_id`
+    fn shared_item_payload(&mut self, item_id: &str) -> Result<Zeroizing<Vec<u8>>> {
+        let item = self.get_item(item_id)?
+            .ok_or_else(|| WalletError::ItemNotFound(item_id.to_string()))?;
+        if item.folder {
+            return Err(WalletError::InvalidOperation("Folders cannot be shared".to_string()));
+        }
+
+        let fields = self.get_fields_by_item(item_id)?;
+        let labels = self.get_labels()?;
+        let mut shared_labels: Vec<SharedLabel> = Vec::new();
+        for field in &fields {
+            if shared_labels.iter().any(|l| l.field_type == field.field_type) {
+                continue;
+            }
+            if let Some(label) = labels.iter().find(|l| l.field_type == field.field_type) {
+                shared_labels.push(SharedLabel {
+                    field_type: label.field_type.clone(),
+                    name: label.name.clone(),
+                    value_type: label.value_type.clone(),
+                    icon: label.icon.clone(),
+                });
+            }
+        }
+        let payload = SharedItem {
+            name: item.name,
+            icon: item.icon,
+            color: item.color,
+            primary: item.primary_field
+                .and_then(|id| fields.iter().position(|f| f.field_id == id)),
+            fields: fields.into_iter()
+                .map(|f| SharedField { field_type: f.field_type, value: f.value, sort_weight: f.sort_weight })
+                .collect(),
+            labels: shared_labels,
+            secure_note: if self.is_secure_note(item_id)? {
+                Some(self.get_secure_note_body(item_id)?)
+            } else {
+                None
+            },
+        };
+        serde_json::to_vec(&payload)
+            .map(Zeroizing::new)
+            .map_err(|e| WalletError::json("Invalid shared item", e))
+    }
+
+    /// Portable file name for the share file of an item, from its name
+    pub fn shared_item_filename(&mut self, item_id: &str) -> Result<String> {
+        let item = self.get_item(item_id)?
+            .ok_or_else(|| WalletError::ItemNotFound(item_id.to_string()))?;
+        Ok(format!("{}.{SHARE_EXTENSION}", sanitize_filename(&item.name)))
+    }
+
+    /// Add the item in a share file to the root of this wallet, creating
+    /// any of its labels this wallet lacks. Returns the new item ID. A
+    /// wrong passphrase (or a damaged file) is `InvalidPassword`.
+    pub fn import_shared_item(&mut self, bytes: &[u8], passphrase: &str) -> Result<String> {
+        self.ensure_unlocked()?;
+        if bytes.len() <= HEADER_LEN || !bytes.starts_with(SHARE_MAGIC) {
+            return Err(WalletError::ValidationError("Not a shared item file".to_string()));
+        }
+        let version = bytes[SHARE_MAGIC.len()];
+        if version != SHARE_VERSION {
+            return Err(WalletError::InvalidVersion(format!("shared item v{version}")));
+        }
+        let header = &bytes[SHARE_MAGIC.len() + 1..HEADER_LEN];
+        let params = crypto::kdf::KdfParams {
+            m_cost_kib: read_u32(&header[0..4]),
+            t_cost: read_u32(&header[4..8]),
+            p_cost: read_u32(&header[8..12]),
+        };
+        if !params.within_untrusted_limits() {
+            return Err(WalletError::ValidationError(
+                "Key derivation parameters of the shared item are out of range".to_string(),
+            ));
+        }
+        let key = share_key(passphrase, &header[12..], params)
+            .map_err(|_| WalletError::InvalidPassword)?;
+        let json = Zeroizing::new(crypto::aead::open(&key, &bytes[HEADER_LEN..])
+            .map_err(|_| WalletError::InvalidPassword)?);
+        self.add_shared_item(&json)
+    }
+
+    /// Add the item in a share file encrypted to age recipients, opening it
+    /// with the secret `identity` ("AGE-SECRET-KEY-1...") of one of them.
+    /// Returns the new item ID. A file not encrypted to the identity (or a
+    /// damaged file) is `DecryptionError`.
+    pub fn import_shared_item_with_identity(&mut self, bytes: &[u8], identity: &str) -> Result<String> {
+        self.ensure_unlocked()?;
+        if !crypto::recipients::is_age_file(bytes) {
+            return Err(WalletError::ValidationError("Not a shared item file".to_string()));
+        }
+        let identity: crypto::recipients::Identity = identity.parse()
+            .map_err(WalletError::ValidationError)?;
+        let json = crypto::recipients::decrypt(bytes, &identity)?;
+        self.add_shared_item(&json)
+    }
+
+    /// Create the item described by a decrypted share payload
+    fn add_shared_item(&mut self, json: &[u8]) -> Result<String> {
+        let shared: SharedItem = serde_json::from_slice(json)
+            .map_err(|e| WalletError::json("Invalid shared item", e))?;
+
+        // Field types of the file mapped to labels of this wallet
+        let labels = self.get_labels()?;
+        let mut type_map: Vec<(String, String)> = Vec::new();
+        for label in &shared.labels {
+            let local = labels.iter()
+                .find(|l| l.field_type == label.field_type)
+                .or_else(|| labels.iter().find(|l| l.name == label.name && l.value_type == label.value_type))
+                .map(|l| l.field_type.clone());
+            let local = match local {
+                Some(field_type) => field_type,
+                None => self.add_label(&label.name, &label.icon, &label.value_type)?,
+            };
+            type_map.push((label.field_type.clone(), local));
+        }
+
+        let item_id = match &shared.secure_note {
+            Some(body) => {
+                let item_id = self.add_secure_note_with_icon(&shared.name, &shared.icon, None)?;
+                self.set_secure_note_body(&item_id, body)?;
+                item_id
+            }
+            None => self.add_item(&shared.name, &shared.icon, false, None)?,
+        };
+        if shared.color.is_some() {
+            self.set_item_color(&item_id, shared.color.as_deref())?;
+        }
+        for (i, field) in shared.fields.iter().enumerate() {
+            let field_type = type_map.iter()
+                .find(|(from, _)| *from == field.field_type)
+                .map_or(field.field_type.as_str(), |(_, to)| to.as_str());
+            let field_id = self.add_field(&item_id, field_type, &field.value, Some(field.sort_weight))?;
+            if shared.primary == Some(i) {
+                self.set_primary_field(&item_id, &field_id)?;
+            }
+        }
+        Ok(item_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::business::wallet::tests::create_test_wallet;
+    use crate::crypto::DecryptError;
+    use crate::crypto::recipients::Identity;
+    use crate::WalletError;
+
+    #[test]
+    fn test_shared_item_filename() {
+        let (mut wallet, _dir) = create_test_wallet();
+        let item_id = wallet.add_item("Bank: main/old 💳", "bank", false, None).unwrap();
+        assert_eq!(wallet.shared_item_filename(&item_id).unwrap(), "Bank_ main_old.iwshare");
+        assert!(matches!(wallet.shared_item_filename("MISSING1"), Err(WalletError::ItemNotFound(_))));
+    }
+
+    #[test]
+    fn test_shared_item_round_trip() {
+        let (mut sender, _dir) = create_test_wallet();
+        let item_id = sender.add_item("Team login", "web", false, None).unwrap();
+        sender.add_field(&item_id, "MAIL", "team@example.com", None).unwrap();
+        let pass_id = sender.add_field(&item_id, "PASS", "s3cret", None).unwrap();
+        sender.set_primary_field(&item_id, &pass_id).unwrap();
+        let pin_label = sender.add_label("Door PIN", "lock", "pass").unwrap();
+        sender.add_field(&item_id, &pin_label, "4711", None).unwrap();
+        sender.add_item("Unrelated", "doc", false, None).unwrap();
+
+        let share = sender.export_shared_item(&item_id, "horse battery").unwrap();
+        assert!(!share.windows(6).any(|w| w == b"s3cret"));
+
+        let (mut recipient, _dir2) = create_test_wallet();
+        assert!(matches!(
+            recipient.import_shared_item(&share, "wrong"),
+            Err(WalletError::InvalidPassword)
+        ));
+        let new_id = recipient.import_shared_item(&share, "horse battery").unwrap();
+
+        let items: Vec<_> = recipient.get_items().unwrap().iter()
+            .filter(|i| i.item_id != crate::ROOT_ID)
+            .cloned()
+            .collect();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].name, "Team login");
+        let fields = recipient.get_fields_by_item(&new_id).unwrap();
+        assert_eq!(fields.len(), 3);
+        assert!(fields.iter().any(|f| f.label == "Door PIN" && f.value == "4711"));
+        let primary = recipient.get_primary_field(&new_id).unwrap().unwrap();
+        assert_eq!(primary.value, "s3cret");
+    }
+
+    #[test]
+    fn test_shared_secure_note_keeps_its_body() {
+        let (mut sender, _dir) = create_test_wallet();
+        let note = sender.add_secure_note("Recovery codes", None).unwrap();
+        sender.set_secure_note_body(&note, "alpha\nbravo\ncharlie").unwrap();
+        let share = sender.export_shared_item(&note, "horse battery").unwrap();
+
+        let (mut recipient, _dir2) = create_test_wallet();
+        let new_id = recipient.import_shared_item(&share, "horse battery").unwrap();
+        assert!(recipient.is_secure_note(&new_id).unwrap());
+        assert_eq!(recipient.get_secure_note_body(&new_id).unwrap(), "alpha\nbravo\ncharlie");
+        assert_eq!(recipient.get_item(&new_id).unwrap().unwrap().icon, "note");
+    }
+
+    #[test]
+    fn test_shared_item_rejects_folders_and_garbage() {
+        let (mut wallet, _dir) = create_test_wallet();
+        let folder = wallet.add_item("Folder", "folder", true, None).unwrap();
+        assert!(matches!(
+            wallet.export_shared_item(&folder, "pass"),
+            Err(WalletError::InvalidOperation(_))
+        ));
+        assert!(matches!(
+            wallet.import_shared_item(b"not a share file at all, just text", "pass"),
+            Err(WalletError::ValidationError(_))
+        ));
+
+        // KDF costs from the header are capped before any key derivation
+        let item_id = wallet.add_item("Item", "doc", false, None).unwrap();
+        let mut share = wallet.export_shared_item(&item_id, "pass").unwrap();
+        share[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(wallet.import_shared_item(&share, "pass"), Err(WalletError::ValidationError(_))));
+    }
+
+    #[test]
+    fn test_shared_item_to_recipients() {
+        let (mut sender, _dir) = create_test_wallet();
+        let item_id = sender.add_item("Router", "web", false, None).unwrap();
+        sender.add_field(&item_id, "PASS", "wpa-key-42", None).unwrap();
+
+        let laptop = Identity::generate();
+        let phone = Identity::generate();
+        assert!(matches!(
+            sender.export_shared_item_to_recipients(&item_id, &[&laptop.recipient(), "age1bogus"]),
+            Err(WalletError::ValidationError(_))
+        ));
+        let share = sender.export_shared_item_to_recipients(&item_id, &[&laptop.recipient(), &phone.recipient()]).unwrap();
+        assert!(!share.windows(10).any(|w| w == b"wpa-key-42"));
+
+        let (mut recipient, _dir2) = create_test_wallet();
+        assert!(matches!(
+            recipient.import_shared_item_with_identity(&share, &Identity::generate().to_secret_string()),
+            Err(WalletError::DecryptionError(DecryptError::WrongKeyOrCorrupt))
+        ));
+        assert!(matches!(
+            recipient.import_shared_item(&share, "pass"),
+            Err(WalletError::ValidationError(_))
+        ));
+        let new_id = recipient.import_shared_item_with_identity(&share, &phone.to_secret_string()).unwrap();
+        assert_eq!(recipient.get_item(&new_id).unwrap().unwrap().name, "Router");
+        let fields = recipient.get_fields_by_item(&new_id).unwrap();
+        assert_eq!(fields[0].value, "wpa-key-42");
+    }
+}
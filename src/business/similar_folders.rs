@@ -0,0 +1,176 @@
+//! Near-duplicate folders
+//!
+//! Merging wallets or importing from other managers tends to leave trees
+//! like "/Banking" next to "/banking" or "/Cafés" next to "/Cafes". Folders
+//! whose paths compare equal ignoring case, diacritics and extra spaces are
+//! reported by [`Wallet::find_similar_folders`] and can be folded into one
+//! with [`Wallet::merge_folders`].
+
+use std::collections::{BTreeMap, HashMap};
+use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+use crate::ROOT_ID;
+use crate::database::IWItem;
+use crate::error::{WalletError, Result};
+use super::wallet::Wallet;
+
+/// Folders whose paths differ only in case, diacritics or spacing
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SimilarFolders {
+    /// Display path of the first folder ("Banking / Cards")
+    pub path: String,
+    /// The folders, oldest first; the first is the natural one to keep
+    pub folder_ids: Vec<String>,
+}
+
+/// Comparison form of a folder name: decomposed, without combining marks,
+/// lowercase, with runs of whitespace collapsed
+fn fold_name(name: &str) -> String {
+    let stripped: String = name.nfd().filter(|c| !is_combining_mark(*c)).collect();
+    stripped.to_lowercase().split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Names of `folder` and its ancestors from the top level down, root
+/// excluded
+fn path_of<'a>(folder: &'a IWItem, by_id: &HashMap<&str, &'a IWItem>) -> Vec<&'a str> {
+    let mut path = vec![folder.name.as_str()];
+    let mut parent = folder.parent_id.as_deref();
+    while let Some(pid) = parent {
+        if pid == ROOT_ID || path.len() > by_id.len() {
+            break;
+        }
+        let Some(item) = by_id.get(pid) else { break };
+        path.push(item.name.as_str());
+        parent = item.parent_id.as_deref();
+    }
+    path.reverse();
+    path
+}
+
+impl Wallet {
+    /// Groups of active folders whose paths match ignoring case,
+    /// diacritics and spacing, sorted by path
+    pub fn find_similar_folders(&mut self) -> Result<Vec<SimilarFolders>> {
+        let items = self.get_items()?;
+        let by_id: HashMap<&str, &IWItem> = items.iter().map(|i| (i.item_id.as_str(), i)).collect();
+
+        let mut groups: BTreeMap<Vec<String>, Vec<&IWItem>> = BTreeMap::new();
+        for folder in items.iter().filter(|i| i.folder && !i.deleted && i.item_id != ROOT_ID) {
+            let key = path_of(folder, &by_id).into_iter().map(fold_name).collect();
+            groups.entry(key).or_default().push(folder);
+        }
+
+        Ok(groups.into_values()
+            .filter(|g| g.len() > 1)
+            .map(|mut group| {
+                group.sort_by(|a, b| a.create_timestamp.cmp(&b.create_timestamp).then_with(|| a.item_id.cmp(&b.item_id)));
+                SimilarFolders {
+                    path: path_of(group[0], &by_id).join(" / "),
+                    folder_ids: group.iter().map(|f| f.item_id.clone()).collect(),
+                }
+            })
+            .collect())
+    }
+
+    /// Move the children of `merge_id` into `keep_id` and soft-delete
+    /// `merge_id`. Children keep their names, so same-named subfolders end
+    /// up side by side and show up in the next `find_similar_folders`.
+    /// Returns the number of children moved.
+    pub fn merge_folders(&mut self, keep_id: &str, merge_id: &str) -> Result<usize> {
+        self.ensure_unlocked()?;
+        if keep_id == merge_id || merge_id == ROOT_ID {
+            return Err(WalletError::InvalidOperation(format!("Cannot merge {merge_id} into {keep_id}")));
+        }
+        let items = self.get_items()?;
+        let by_id: HashMap<&str, &IWItem> = items.iter().map(|i| (i.item_id.as_str(), i)).collect();
+        for id in [keep_id, merge_id] {
+            match by_id.get(id) {
+                Some(item) if item.folder && !item.deleted => {}
+                Some(_) => return Err(WalletError::InvalidOperation(format!("{id} is not a folder"))),
+                None => return Err(WalletError::ItemNotFound(id.to_string())),
+            }
+        }
+        // Moving children into their own subtree would detach it from the root
+        let mut ancestor = Some(keep_id);
+        while let Some(id) = ancestor {
+            if id == merge_id {
+                return Err(WalletError::InvalidOperation(format!("{keep_id} is inside {merge_id}")));
+            }
+            ancestor = by_id.get(id).and_then(|i| i.parent_id.as_deref()).filter(|p| *p != ROOT_ID);
+        }
+
+        let children: Vec<(String, bool)> = items.iter()
+            .filter(|i| i.parent_id.as_deref() == Some(merge_id) && !i.deleted)
+            .map(|i| (i.item_id.clone(), i.locked))
+            .collect();
+        if let Some((locked, _)) = children.iter().find(|(_, locked)| *locked) {
+            return Err(WalletError::ItemLocked(locked.clone()));
+        }
+        self.ensure_item_editable(merge_id)?;
+
+        for (child, _) in &children {
+            self.move_item(child, keep_id)?;
+        }
+        // The merged folder is gone for good, so is its PIN
+        self.drop_folder_pin(merge_id)?;
+        self.delete_item(merge_id)?;
+        Ok(children.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::business::wallet::tests::create_test_wallet;
+
+    #[test]
+    fn test_fold_name() {
+        assert_eq!(fold_name("Banking"), fold_name("banking"));
+        assert_eq!(fold_name("Cafés"), fold_name("Cafe\u{301}s"));
+        assert_eq!(fold_name("Cafés"), "cafes");
+        assert_eq!(fold_name("  Social   media "), "social media");
+        assert_ne!(fold_name("Bank"), fold_name("Banking"));
+    }
+
+    #[test]
+    fn test_find_and_merge_similar_folders() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let banking = wallet.add_item("Banking", "folder", true, None).unwrap();
+        let cards = wallet.add_item("Cards", "folder", true, Some(&banking)).unwrap();
+        let dup = wallet.add_item("banking ", "folder", true, None).unwrap();
+        let dup_cards = wallet.add_item("Cárds", "folder", true, Some(&dup)).unwrap();
+        let entry = wallet.add_item("Savings", "document", false, Some(&dup)).unwrap();
+        wallet.add_item("Bank", "folder", true, None).unwrap();
+        // The duplicates came later, so the originals are listed first
+        {
+            let conn = wallet.db.as_ref().unwrap().connection().unwrap();
+            for id in [&dup, &dup_cards] {
+                conn.execute(
+                    "UPDATE nswallet_items SET create_timestamp = datetime(create_timestamp, '+1 hours') WHERE item_id = ?",
+                    [id],
+                ).unwrap();
+            }
+        }
+        wallet.clear_caches();
+
+        let similar = wallet.find_similar_folders().unwrap();
+        assert_eq!(similar.len(), 2);
+        assert_eq!(similar[0], SimilarFolders { path: "Banking".into(), folder_ids: vec![banking.clone(), dup.clone()] });
+        assert_eq!(similar[1].folder_ids, [cards.clone(), dup_cards.clone()]);
+
+        assert!(wallet.merge_folders(&cards, &banking).is_err(), "cannot merge a folder into its own subfolder");
+        assert!(wallet.merge_folders(&banking, &entry).is_err());
+
+        assert_eq!(wallet.merge_folders(&banking, &dup).unwrap(), 2);
+        assert!(wallet.get_item(&dup).unwrap().is_none_or(|i| i.deleted));
+        assert_eq!(wallet.get_item(&entry).unwrap().unwrap().parent_id.as_deref(), Some(banking.as_str()));
+
+        // The subfolders are now siblings; merge them too
+        let similar = wallet.find_similar_folders().unwrap();
+        assert_eq!(similar.len(), 1);
+        assert_eq!(similar[0].folder_ids, [cards.clone(), dup_cards.clone()]);
+        assert_eq!(wallet.merge_folders(&cards, &dup_cards).unwrap(), 0);
+        assert!(wallet.find_similar_folders().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,116 @@
+//! Starter structure for new wallets
+//!
+//! Client apps used to hardcode the folders a fresh wallet opens with.
+//! [`Wallet::create_with_starter_structure`] creates them here instead, in
+//! the wallet's language, each folder with an icon and a field template
+//! (see [`FolderDefaults`](super::FolderDefaults)) so the first entries a
+//! user adds come with the right fields.
+
+use std::path::Path;
+use chrono::{Datelike, Utc};
+use crate::error::Result;
+use super::wallet::Wallet;
+
+/// What [`Wallet::create_with_starter_structure`] adds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StarterKind {
+    /// Folders with icons and field templates
+    Folders,
+    /// Folders plus a sample card entry and a read-me note
+    Examples,
+}
+
+/// A starter folder: translation key of its name, icon and field template
+struct StarterFolder {
+    name_key: &'static str,
+    icon: &'static str,
+    template: &'static [&'static str],
+}
+
+const STARTER_FOLDERS: &[StarterFolder] = &[
+    StarterFolder { name_key: "init_items_banking", icon: "banking", template: &["CARD", "EXPD", "NAME", "PINC"] },
+    StarterFolder { name_key: "init_items_email", icon: "mailbox", template: &["MAIL", "PASS", "LINK"] },
+    StarterFolder { name_key: "init_items_internet", icon: "earth", template: &["USER", "PASS", "LINK"] },
+    StarterFolder { name_key: "init_items_work", icon: "comp", template: &["USER", "PASS", "LINK", "NOTE"] },
+    StarterFolder { name_key: "init_items_documents", icon: "passport", template: &["NAME", "SNUM", "EXPD", "NOTE"] },
+];
+
+/// Test card number of the sample entry; passes the Luhn check
+const SAMPLE_CARD_NUMBER: &str = "4111111111111111";
+
+impl Wallet {
+    /// Create a wallet, like [`Wallet::create`], and seed it with the
+    /// starter folders in `lang`
+    pub fn create_with_starter_structure(folder: &Path, password: &str, lang: &str, kind: StarterKind) -> Result<Self> {
+        let mut wallet = Self::create(folder, password, lang)?;
+        wallet.add_starter_structure(kind)?;
+        Ok(wallet)
+    }
+
+    /// Add the starter folders, named in the wallet's language, to the
+    /// root. Returns the new folder IDs in display order.
+    pub fn add_starter_structure(&mut self, kind: StarterKind) -> Result<Vec<String>> {
+        self.ensure_unlocked()?;
+        let tr = self.translations()?;
+
+        let mut folders = Vec::with_capacity(STARTER_FOLDERS.len());
+        for folder in STARTER_FOLDERS {
+            folders.push(self.add_item(tr.get(folder.name_key), folder.icon, true, None)?);
+        }
+
+        // Examples first, so they carry only the fields set here rather
+        // than the folder template
+        if kind == StarterKind::Examples {
+            let card = self.add_item(tr.get("init_items_credit_card_sample"), "visa", false, Some(&folders[0]))?;
+            let expiry = format!("{}1231", Utc::now().year() + 3);
+            for (field_type, value) in [("CARD", SAMPLE_CARD_NUMBER), ("EXPD", expiry.as_str())] {
+                self.add_field(&card, field_type, value, None)?;
+            }
+            let readme = self.add_item(tr.get("init_items_readme"), "info", false, None)?;
+            self.add_field(&readme, "NOTE", tr.get("init_items_readme_text"), None)?;
+        }
+
+        for (folder, id) in STARTER_FOLDERS.iter().zip(&folders) {
+            self.set_folder_defaults(id, Some(folder.icon), folder.template)?;
+        }
+        Ok(folders)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_create_with_starter_structure() {
+        let temp = TempDir::new().unwrap();
+        let mut wallet = Wallet::create_with_starter_structure(temp.path(), "TestPassword123", "de", StarterKind::Examples).unwrap();
+
+        let items = wallet.get_items().unwrap().to_vec();
+        let folder_names: Vec<&str> = items.iter().filter(|i| i.folder).map(|i| i.name.as_str()).collect();
+        for name in ["Banking", "E-Mail", "Arbeit", "Dokumente"] {
+            assert!(folder_names.contains(&name), "missing {name} in {folder_names:?}");
+        }
+        let card = items.iter().find(|i| i.name == "Beispiel für eine Kreditkarte").unwrap();
+        let fields = wallet.get_fields_by_item(&card.item_id).unwrap();
+        assert_eq!(fields.iter().map(|f| f.field_type.as_str()).collect::<Vec<_>>(), ["CARD", "EXPD"]);
+        assert!(items.iter().any(|i| i.name == "Lies mich" && !i.folder));
+
+        // New entries in a starter folder get its template
+        let email = items.iter().find(|i| i.name == "E-Mail").unwrap();
+        let entry = wallet.add_item("Posteo", "", false, Some(&email.item_id)).unwrap();
+        assert_eq!(wallet.get_item(&entry).unwrap().unwrap().icon, "mailbox");
+        let types: Vec<String> = wallet.get_fields_by_item(&entry).unwrap().into_iter().map(|f| f.field_type).collect();
+        assert_eq!(types, ["MAIL", "PASS", "LINK"]);
+    }
+
+    #[test]
+    fn test_starter_folders_only() {
+        let temp = TempDir::new().unwrap();
+        let mut wallet = Wallet::create_with_starter_structure(temp.path(), "TestPassword123", "en", StarterKind::Folders).unwrap();
+        let items = wallet.get_items().unwrap();
+        assert_eq!(items.iter().filter(|i| !i.folder).count(), 0);
+        assert_eq!(items.iter().filter(|i| i.folder && i.name == "Documents").count(), 1);
+    }
+}
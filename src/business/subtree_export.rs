@@ -0,0 +1,352 @@
+//! Subtree re-encryption for sharing
+//!
+//! [`Wallet::reencrypt_subtree_for_export`] takes one folder (or item) with
+//! everything below it and re-encrypts each name, field value and secure
+//! note body under a key derived from an export passphrase. Rows are read straight from the
+//! database and converted one at a time: nothing outside the subtree is
+//! decrypted, the item and field caches are left alone, and no plaintext
+//! outlives the value being converted.
+//!
+//! The resulting [`SealedSubtree`] carries only ciphertext and structure,
+//! so it can be stored or sent as is; a wallet that knows the passphrase
+//! adds it back with [`Wallet::import_sealed_subtree`].
+
+use std::collections::{HashMap, VecDeque};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::ROOT_ID;
+use crate::crypto;
+use crate::database::queries;
+use crate::error::{WalletError, Result};
+use super::wallet::{random_bytes, Wallet, KDF_SALT_LEN};
+
+/// Format of [`SealedSubtree`] written by this version
+pub const SEALED_SUBTREE_VERSION: u32 = 1;
+
+/// An item of a [`SealedSubtree`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SealedItem {
+    pub item_id: String,
+    /// Parent within the subtree; `None` for the subtree root
+    pub parent_id: Option<String>,
+    pub folder: bool,
+    pub icon: String,
+    pub color: Option<String>,
+    /// Field ID of the primary field
+    pub primary_field: Option<String>,
+    /// Name sealed under the export key
+    pub name: Vec<u8>,
+    /// Secure note body sealed under the export key, if the item is one
+    #[serde(default)]
+    pub secure_note: Option<Vec<u8>>,
+}
+
+/// A field of a [`SealedSubtree`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SealedField {
+    pub item_id: String,
+    pub field_id: String,
+    pub field_type: String,
+    pub sort_weight: i32,
+    /// Value sealed under the export key
+    pub value: Vec<u8>,
+}
+
+/// A label used by the fields, so the recipient can recreate custom ones
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SealedLabel {
+    pub field_type: String,
+    pub name: String,
+    pub value_type: String,
+    pub icon: String,
+}
+
+/// A subtree re-encrypted under an export passphrase
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SealedSubtree {
+    pub version: u32,
+    /// Argon2id parameters and salt of the export key
+    pub m_cost_kib: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+    pub salt: Vec<u8>,
+    /// Parents before children, the subtree root first
+    pub items: Vec<SealedItem>,
+    pub fields: Vec<SealedField>,
+    pub labels: Vec<SealedLabel>,
+}
+
+impl SealedSubtree {
+    fn kdf_params(&self) -> crypto::kdf::KdfParams {
+        crypto::kdf::KdfParams {
+            m_cost_kib: self.m_cost_kib,
+            t_cost: self.t_cost,
+            p_cost: self.p_cost,
+        }
+    }
+
+    fn key(&self, passphrase: &str) -> Result<Zeroizing<[u8; crypto::kdf::KEK_LEN]>> {
+        export_key(passphrase, &self.salt, self.kdf_params())
+    }
+}
+
+fn export_key(passphrase: &str, salt: &[u8], params: crypto::kdf::KdfParams) -> Result<Zeroizing<[u8; crypto::kdf::KEK_LEN]>> {
+    crypto::kdf::derive_kek(passphrase.as_bytes(), salt, params)
+        .map(Zeroizing::new)
+        .map_err(WalletError::EncryptionError)
+}
+
+fn open_text(key: &[u8; crypto::kdf::KEK_LEN], blob: &[u8]) -> Result<Zeroizing<String>> {
+    let bytes = crypto::aead::open(key, blob).map_err(|_| WalletError::InvalidPassword)?;
+    String::from_utf8(bytes)
+        .map(Zeroizing::new)
+        .map_err(|_| crypto::DecryptError::BadUtf8.into())
+}
+
+impl Wallet {
+    /// Re-encrypt the active items and fields under `folder_id` (the folder
+    /// included) for an export readable with `passphrase`. Only the
+    /// subtree is decrypted. Fails with `FolderLocked` when part of it sits
+    /// behind a folder PIN that is not unlocked.
+    pub fn reencrypt_subtree_for_export(&self, folder_id: &str, passphrase: &str) -> Result<SealedSubtree> {
+        self.ensure_unlocked()?;
+        if passphrase.is_empty() {
+            return Err(WalletError::InvalidOperation("Export passphrase must not be empty".to_string()));
+        }
+        if folder_id == ROOT_ID {
+            return Err(WalletError::InvalidOperation("The root is not a subtree; use a full export".to_string()));
+        }
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        let raw_items = queries::get_subtree_items_raw(conn, folder_id)?;
+        if raw_items.is_empty() {
+            return Err(WalletError::ItemNotFound(folder_id.to_string()));
+        }
+        let raw_fields = queries::get_subtree_fields_raw(conn, folder_id)?;
+        let all_labels = queries::get_all_labels(conn)?;
+
+        let params = crypto::kdf::KdfParams::current();
+        let salt = random_bytes(KDF_SALT_LEN);
+        let key = export_key(passphrase, &salt, params)?;
+        let reseal = |plaintext: &str| crypto::aead::seal(&key, plaintext.as_bytes()).map_err(WalletError::EncryptionError);
+
+        // Parents before children, so an import can create them in order
+        let mut children: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (i, item) in raw_items.iter().enumerate() {
+            children.entry(item.parent_id.as_deref().unwrap_or(ROOT_ID)).or_default().push(i);
+        }
+        let root = raw_items.iter().position(|i| i.item_id == folder_id)
+            .ok_or_else(|| WalletError::ItemNotFound(folder_id.to_string()))?;
+        let mut order = Vec::with_capacity(raw_items.len());
+        let mut queue = VecDeque::from([root]);
+        while let Some(i) = queue.pop_front() {
+            order.push(i);
+            if let Some(kids) = children.get(raw_items[i].item_id.as_str()) {
+                queue.extend(kids.iter().copied().filter(|&k| k != root));
+            }
+        }
+
+        let mut items = Vec::with_capacity(order.len());
+        for i in order {
+            let mut raw = raw_items[i].clone();
+            self.open_item_envelope(&mut raw);
+            let name = Zeroizing::new(self.dec_value(&raw.name_encrypted)?);
+            let secure_note = if queries::get_secure_note_chunk_count(conn, &raw.item_id)? > 0 {
+                Some(reseal(&Zeroizing::new(self.get_secure_note_body(&raw.item_id)?))?)
+            } else {
+                None
+            };
+            items.push(SealedItem {
+                parent_id: if i == root { None } else { raw.parent_id },
+                item_id: raw.item_id,
+                folder: raw.folder,
+                icon: raw.icon,
+                color: raw.color,
+                primary_field: raw.field_id,
+                name: reseal(&name)?,
+                secure_note,
+            });
+        }
+
+        let mut fields = Vec::with_capacity(raw_fields.len());
+        let mut labels: Vec<SealedLabel> = Vec::new();
+        for raw in raw_fields {
+            let value = match self.dec_field_value(&raw.value_encrypted) {
+                Ok(value) => Zeroizing::new(value),
+                Err(e) => {
+                    // Report a locked PIN folder rather than a decryption error
+                    if let Some(folder) = self.protecting_folder(&raw.item_id)? {
+                        self.folder_key(&folder)?;
+                    }
+                    return Err(e);
+                }
+            };
+            if !labels.iter().any(|l| l.field_type == raw.field_type)
+                && let Some(label) = all_labels.iter().find(|l| l.field_type == raw.field_type)
+            {
+                labels.push(SealedLabel {
+                    field_type: label.field_type.clone(),
+                    name: label.label_name.clone(),
+                    value_type: label.value_type.clone(),
+                    icon: label.icon.clone(),
+                });
+            }
+            fields.push(SealedField {
+                value: reseal(&value)?,
+                item_id: raw.item_id,
+                field_id: raw.field_id,
+                field_type: raw.field_type,
+                sort_weight: raw.sort_weight.unwrap_or(0),
+            });
+        }
+
+        Ok(SealedSubtree {
+            version: SEALED_SUBTREE_VERSION,
+            m_cost_kib: params.m_cost_kib,
+            t_cost: params.t_cost,
+            p_cost: params.p_cost,
+            salt,
+            items,
+            fields,
+            labels,
+        })
+    }
+
+    /// Add a subtree from [`Wallet::reencrypt_subtree_for_export`] under
+    /// `parent_id` (the root when `None`), creating labels this wallet
+    /// lacks. Items get new IDs; returns the ID of the new subtree root. A
+    /// wrong passphrase is `InvalidPassword`.
+    pub fn import_sealed_subtree(&mut self, subtree: &SealedSubtree, passphrase: &str, parent_id: Option<&str>) -> Result<String> {
+        self.ensure_unlocked()?;
+        if subtree.version != SEALED_SUBTREE_VERSION {
+            return Err(WalletError::InvalidVersion(format!("sealed subtree v{}", subtree.version)));
+        }
+        if !subtree.kdf_params().within_untrusted_limits() {
+            return Err(WalletError::ValidationError(
+                "Key derivation parameters of the sealed subtree are out of range".to_string(),
+            ));
+        }
+        let key = subtree.key(passphrase).map_err(|_| WalletError::InvalidPassword)?;
+        // Open every record before writing anything, so a wrong passphrase
+        // or a damaged record leaves the wallet untouched
+        let names = subtree.items.iter()
+            .map(|i| open_text(&key, &i.name))
+            .collect::<Result<Vec<_>>>()?;
+        let values = subtree.fields.iter()
+            .map(|f| open_text(&key, &f.value))
+            .collect::<Result<Vec<_>>>()?;
+        let notes = subtree.items.iter()
+            .map(|i| i.secure_note.as_ref().map(|blob| open_text(&key, blob)).transpose())
+            .collect::<Result<Vec<_>>>()?;
+
+        let labels = self.get_labels()?;
+        let mut type_map: HashMap<&str, String> = HashMap::new();
+        for label in &subtree.labels {
+            let local = labels.iter()
+                .find(|l| l.field_type == label.field_type)
+                .or_else(|| labels.iter().find(|l| l.name == label.name && l.value_type == label.value_type))
+                .map(|l| l.field_type.clone());
+            let local = match local {
+                Some(field_type) => field_type,
+                None => self.add_label(&label.name, &label.icon, &label.value_type)?,
+            };
+            type_map.insert(&label.field_type, local);
+        }
+
+        let mut new_ids: HashMap<&str, String> = HashMap::new();
+        let mut root_id = None;
+        for ((item, name), note) in subtree.items.iter().zip(&names).zip(&notes) {
+            let parent = match &item.parent_id {
+                None => parent_id,
+                Some(p) => Some(new_ids.get(p.as_str())
+                    .ok_or_else(|| WalletError::ValidationError(format!("Parent {p} of {} not in the subtree", item.item_id)))?
+                    .as_str()),
+            };
+            let id = match note {
+                Some(body) if !item.folder => {
+                    let id = self.add_secure_note_with_icon(name, &item.icon, parent)?;
+                    self.set_secure_note_body(&id, body)?;
+                    id
+                }
+                _ => self.add_item(name, &item.icon, item.folder, parent)?,
+            };
+            if item.color.is_some() {
+                self.set_item_color(&id, item.color.as_deref())?;
+            }
+            root_id.get_or_insert_with(|| id.clone());
+            new_ids.insert(&item.item_id, id);
+        }
+        for (field, value) in subtree.fields.iter().zip(&values) {
+            let Some(item_id) = new_ids.get(field.item_id.as_str()) else { continue };
+            let field_type = type_map.get(field.field_type.as_str()).map_or(field.field_type.as_str(), String::as_str);
+            let field_id = self.add_field(item_id, field_type, value, Some(field.sort_weight))?;
+            let primary = subtree.items.iter()
+                .any(|i| i.item_id == field.item_id && i.primary_field.as_deref() == Some(field.field_id.as_str()));
+            if primary {
+                self.set_primary_field(item_id, &field_id)?;
+            }
+        }
+        root_id.ok_or_else(|| WalletError::ValidationError("Empty subtree".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::business::wallet::tests::create_test_wallet;
+
+    #[test]
+    fn test_reencrypt_subtree_round_trip() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let work = wallet.add_item("Work", "folder", true, None).unwrap();
+        let vpn = wallet.add_item("VPN", "document", false, Some(&work)).unwrap();
+        let pass = wallet.add_field(&vpn, "PASS", "tunnel-secret", None).unwrap();
+        wallet.set_primary_field(&vpn, &pass).unwrap();
+        let tools = wallet.add_item("Tools", "folder", true, Some(&work)).unwrap();
+        wallet.add_item("CI", "document", false, Some(&tools)).unwrap();
+        let codes = wallet.add_secure_note("Codes", Some(&work)).unwrap();
+        wallet.set_secure_note_body(&codes, "recovery-1 recovery-2").unwrap();
+        let home = wallet.add_item("Home", "document", false, None).unwrap();
+        wallet.add_field(&home, "PASS", "home-secret", None).unwrap();
+
+        // Only the subtree is read, and nothing lands in the caches
+        wallet.clear_caches();
+        let sealed = wallet.reencrypt_subtree_for_export(&work, "share phrase").unwrap();
+        assert!(wallet.items_cache.is_none() && wallet.fields_cache.is_none());
+        assert_eq!(sealed.items.len(), 5);
+        assert_eq!(sealed.items[0].item_id, work);
+        assert_eq!(sealed.items[0].parent_id, None);
+        assert_eq!(sealed.fields.len(), 1);
+        assert!(!sealed.fields[0].value.windows(6).any(|w| w == b"tunnel"));
+        assert!(sealed.labels.iter().any(|l| l.field_type == "PASS"));
+
+        let sealed: SealedSubtree = serde_json::from_slice(&serde_json::to_vec(&sealed).unwrap()).unwrap();
+        assert!(matches!(
+            wallet.import_sealed_subtree(&sealed, "wrong phrase", None),
+            Err(WalletError::InvalidPassword)
+        ));
+        let costly = SealedSubtree { t_cost: u32::MAX, ..sealed.clone() };
+        assert!(matches!(
+            wallet.import_sealed_subtree(&costly, "share phrase", None),
+            Err(WalletError::ValidationError(_))
+        ));
+        let copy = wallet.import_sealed_subtree(&sealed, "share phrase", None).unwrap();
+        let items = wallet.get_items().unwrap().to_vec();
+        let copy_vpn = items.iter()
+            .find(|i| i.name == "VPN" && i.parent_id.as_deref() == Some(copy.as_str()))
+            .unwrap();
+        let fields = wallet.get_fields_by_item(&copy_vpn.item_id).unwrap();
+        assert_eq!(fields[0].value, "tunnel-secret");
+        assert_eq!(copy_vpn.primary_field.as_deref(), Some(fields[0].field_id.as_str()));
+        let copy_tools = items.iter().find(|i| i.name == "Tools" && i.parent_id.as_deref() == Some(copy.as_str())).unwrap();
+        assert!(items.iter().any(|i| i.name == "CI" && i.parent_id.as_deref() == Some(copy_tools.item_id.as_str())));
+        let copy_codes = items.iter().find(|i| i.name == "Codes" && i.parent_id.as_deref() == Some(copy.as_str())).unwrap();
+        assert_eq!(wallet.get_secure_note_body(&copy_codes.item_id).unwrap(), "recovery-1 recovery-2");
+        assert!(sealed.items.iter().all(|i| i.secure_note.as_ref().is_none_or(|b| !b.windows(8).any(|w| w == b"recovery"))));
+
+        assert!(wallet.reencrypt_subtree_for_export(ROOT_ID, "share phrase").is_err());
+        assert!(wallet.reencrypt_subtree_for_export(&work, "").is_err());
+    }
+}
@@ -0,0 +1,332 @@
+//! Raw encrypted record access for sync tools
+//!
+//! Records leave and enter the wallet as stored: item names and field
+//! values stay encrypted under the vault key, so a tool moving them between
+//! devices of the same vault never sees plaintext. Incoming records are
+//! checked before they are written: IDs must be well formed, the blob must
+//! authenticate under this vault's key, timestamps must be well formed and
+//! the record must fit the existing tree without closing a cycle.
+//!
+//! A tool can keep its high-water mark in the wallet itself with
+//! [`Wallet::mark_synced`]; it lives in the properties' `sync_timestamp`.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+
+use crate::database::queries::{self, RawField, RawItem, parse_timestamp};
+use crate::error::{WalletError, Result};
+use crate::utils::id_gen::is_valid_id;
+use crate::{ROOT_ID, ROOT_PARENT_ID};
+use super::wallet::Wallet;
+
+/// Sync high-water mark, see [`Wallet::last_synced`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncMark {
+    pub timestamp: DateTime<Utc>,
+    /// Device that recorded the mark; `None` for marks written by older apps
+    pub device_id: Option<String>,
+}
+
+/// One item or field row with its encrypted blob
+#[derive(Debug, Clone)]
+pub enum RawRecord {
+    Item(RawItem),
+    Field(RawField),
+}
+
+impl Wallet {
+    /// Record that the wallet is synced up to `timestamp` by `device_id`.
+    /// Stored with one-second precision.
+    pub fn mark_synced(&mut self, timestamp: DateTime<Utc>, device_id: &str) -> Result<()> {
+        if device_id.is_empty() {
+            return Err(WalletError::InvalidOperation("Device id must not be empty".to_string()));
+        }
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        queries::set_sync_mark(conn, &timestamp, device_id)
+    }
+
+    /// The last mark recorded with [`Wallet::mark_synced`]. A wallet that
+    /// was never synced reports its creation time without a device.
+    pub fn last_synced(&self) -> Result<Option<SyncMark>> {
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        Ok(queries::get_sync_mark(conn)?
+            .map(|(timestamp, device_id)| SyncMark { timestamp, device_id }))
+    }
+
+    /// Every item and field, active and deleted, with encrypted blobs.
+    /// Items come first, parents before children, then fields. The root
+    /// item is left out: it holds the vault's password check, not data.
+    pub fn get_raw_records(&self) -> Result<Vec<RawRecord>> {
+        self.ensure_unlocked()?;
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+
+        let mut items = queries::get_all_items_raw(conn)?;
+        items.extend(queries::get_deleted_items_raw(conn)?);
+        items.retain(|i| i.item_id != ROOT_ID);
+        let parents: HashMap<&str, Option<&str>> = items.iter()
+            .map(|i| (i.item_id.as_str(), i.parent_id.as_deref()))
+            .collect();
+        let depth = |id: &str| {
+            let mut depth = 0;
+            let mut cur = parents.get(id).copied().flatten();
+            while let Some(pid) = cur {
+                if depth > parents.len() {
+                    break;
+                }
+                depth += 1;
+                cur = parents.get(pid).copied().flatten();
+            }
+            depth
+        };
+        let mut keyed: Vec<(usize, RawItem)> = items.iter()
+            .map(|i| (depth(&i.item_id), i.clone()))
+            .collect();
+        keyed.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.item_id.cmp(&b.1.item_id)));
+
+        let mut fields = queries::get_all_fields_raw(conn)?;
+        fields.extend(queries::get_deleted_fields_raw(conn)?);
+        fields.sort_by(|a, b| a.item_id.cmp(&b.item_id).then_with(|| a.field_id.cmp(&b.field_id)));
+
+        Ok(keyed.into_iter().map(|(_, i)| RawRecord::Item(i))
+            .chain(fields.into_iter().map(RawRecord::Field))
+            .collect())
+    }
+
+    /// Write a record from `get_raw_records` (usually of another device),
+    /// replacing the local row with the same key. The blob is checked to
+    /// authenticate under this vault's key; the decrypted value is
+    /// discarded. Active items need an active parent and fields need their
+    /// item, so records should be put in the order `get_raw_records` lists
+    /// them.
+    pub fn put_raw_record(&mut self, record: &RawRecord) -> Result<()> {
+        self.ensure_unlocked()?;
+        match record {
+            RawRecord::Item(item) => {
+                if item.item_id == ROOT_ID {
+                    return Err(WalletError::InvalidOperation(
+                        "The root item cannot be replaced".to_string(),
+                    ));
+                }
+                check_raw_id("item", &item.item_id)?;
+                self.check_raw_blob(&item.name_encrypted)?;
+                check_raw_timestamp(item.create_timestamp.as_deref())?;
+                check_raw_timestamp(item.change_timestamp.as_deref())?;
+            }
+            RawRecord::Field(field) => {
+                check_raw_id("item", &field.item_id)?;
+                check_raw_id("field", &field.field_id)?;
+                self.check_raw_blob(&field.value_encrypted)?;
+                check_raw_timestamp(field.change_timestamp.as_deref())?;
+            }
+        }
+
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        match record {
+            RawRecord::Item(item) => {
+                // Profile roots are folders with the root's own parent
+                let parent = item.parent_id.as_deref().unwrap_or(ROOT_ID);
+                let profile_root = item.folder && parent == ROOT_PARENT_ID;
+                if !item.deleted && !profile_root && !queries::item_exists(conn, parent)? {
+                    return Err(WalletError::ParentNotFound(parent.to_string()));
+                }
+                if queries::is_self_or_ancestor(conn, &item.item_id, parent)? {
+                    return Err(WalletError::InvalidOperation(format!(
+                        "Item {} cannot be moved into its own subtree", item.item_id
+                    )));
+                }
+                queries::upsert_item_raw(conn, item)?;
+            }
+            RawRecord::Field(field) => {
+                if !queries::item_row_exists(conn, &field.item_id)? {
+                    return Err(WalletError::ItemNotFound(field.item_id.clone()));
+                }
+                if queries::field_id_exists(conn, &field.field_id)?
+                    && !queries::field_row_exists(conn, &field.item_id, &field.field_id)? {
+                    return Err(WalletError::ValidationError(format!(
+                        "Field {} belongs to another item", field.field_id
+                    )));
+                }
+                queries::upsert_field_raw(conn, field)?;
+            }
+        }
+
+        self.clear_caches();
+        self.note_mutation();
+        Ok(())
+    }
+
+    /// The blob must be a v6 blob that authenticates under the vault key
+    /// (or the key of an unlocked protected folder)
+    fn check_raw_blob(&self, blob: &[u8]) -> Result<()> {
+        if !crate::crypto::aead::is_v6_blob(blob) {
+            return Err(WalletError::ValidationError("Not an encrypted v6 record".to_string()));
+        }
+        self.dec_field_value(blob).map(drop)
+    }
+}
+
+fn check_raw_id(kind: &str, id: &str) -> Result<()> {
+    if !is_valid_id(id) {
+        return Err(WalletError::ValidationError(format!("Invalid {kind} id: {id}")));
+    }
+    Ok(())
+}
+
+fn check_raw_timestamp(timestamp: Option<&str>) -> Result<()> {
+    match timestamp {
+        Some(ts) if parse_timestamp(ts).is_none() => Err(WalletError::ValidationError(
+            format!("Invalid timestamp: {ts}"),
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Timelike;
+    use crate::business::wallet::tests::create_test_wallet;
+
+    #[test]
+    fn test_raw_records_roundtrip_between_copies() {
+        let (mut wallet, temp) = create_test_wallet();
+        let folder = wallet.add_item("Folder", "folder", true, None).unwrap();
+        let item = wallet.add_item("Bank", "bank", false, Some(&folder)).unwrap();
+        wallet.add_field(&item, "PASS", "secret", None).unwrap();
+
+        // A second device of the same vault: a copy of the database
+        let other_dir = tempfile::TempDir::new().unwrap();
+        std::fs::copy(
+            temp.path().join(crate::DATABASE_FILENAME),
+            other_dir.path().join(crate::DATABASE_FILENAME),
+        ).unwrap();
+
+        let new_item = wallet.add_item("Mail", "mail", false, Some(&folder)).unwrap();
+        let new_field = wallet.add_field(&new_item, "MAIL", "me@example.com", None).unwrap();
+        wallet.delete_item(&item).unwrap();
+        let profile = wallet.create_profile("Work").unwrap();
+        let vpn = wallet.add_item("VPN", "document", false, Some(&profile)).unwrap();
+
+        let records = wallet.get_raw_records().unwrap();
+        assert!(records.iter().all(|r| !matches!(r, RawRecord::Item(i) if i.item_id == ROOT_ID)));
+        let folder_pos = records.iter().position(|r| matches!(r, RawRecord::Item(i) if i.item_id == folder)).unwrap();
+        let item_pos = records.iter().position(|r| matches!(r, RawRecord::Item(i) if i.item_id == new_item)).unwrap();
+        assert!(folder_pos < item_pos);
+        // No plaintext anywhere
+        for r in &records {
+            let blob = match r {
+                RawRecord::Item(i) => &i.name_encrypted,
+                RawRecord::Field(f) => &f.value_encrypted,
+            };
+            assert!(!String::from_utf8_lossy(blob).contains("example.com"));
+        }
+
+        let mut other = Wallet::open(other_dir.path()).unwrap();
+        assert!(other.unlock("TestPassword123").unwrap());
+        for r in &records {
+            other.put_raw_record(r).unwrap();
+        }
+        assert!(other.get_item(&item).unwrap().is_none());
+        let fields = other.get_fields_by_item(&new_item).unwrap();
+        assert_eq!(fields.len(), 1);
+        assert_eq!(fields[0].field_id, new_field);
+        assert_eq!(fields[0].value, "me@example.com");
+        assert_eq!(other.get_item(&profile).unwrap().unwrap().parent_id.as_deref(), Some(ROOT_PARENT_ID));
+        assert_eq!(other.get_item(&vpn).unwrap().unwrap().parent_id.as_deref(), Some(profile.as_str()));
+    }
+
+    #[test]
+    fn test_mark_synced() {
+        let (mut wallet, temp) = create_test_wallet();
+        let initial = wallet.last_synced().unwrap().unwrap();
+        assert_eq!(initial.device_id, None);
+
+        let at = Utc::now().with_nanosecond(0).unwrap();
+        wallet.mark_synced(at, "laptop-1").unwrap();
+        let mark = wallet.last_synced().unwrap().unwrap();
+        assert_eq!(mark, SyncMark { timestamp: at, device_id: Some("laptop-1".to_string()) });
+        assert_eq!(wallet.get_properties().unwrap().sync_timestamp, Some(at));
+        assert!(matches!(wallet.mark_synced(at, ""), Err(WalletError::InvalidOperation(_))));
+
+        // Persisted across reopen
+        wallet.close();
+        let reopened = Wallet::open(temp.path()).unwrap();
+        assert_eq!(reopened.last_synced().unwrap(), Some(mark));
+    }
+
+    #[test]
+    fn test_put_raw_record_integrity_checks() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let item = wallet.add_item("Bank", "bank", false, None).unwrap();
+        wallet.add_field(&item, "PASS", "secret", None).unwrap();
+        let records = wallet.get_raw_records().unwrap();
+        let RawRecord::Item(raw_item) = records[0].clone() else { panic!() };
+        let RawRecord::Field(raw_field) = records[1].clone() else { panic!() };
+
+        // Records of another vault do not authenticate
+        let (mut stranger, _t2) = create_test_wallet();
+        assert!(matches!(stranger.put_raw_record(&records[0]), Err(WalletError::DecryptionError(_))));
+
+        let mut tampered = raw_field.clone();
+        let last = tampered.value_encrypted.len() - 1;
+        tampered.value_encrypted[last] ^= 1;
+        assert!(matches!(wallet.put_raw_record(&RawRecord::Field(tampered)), Err(WalletError::DecryptionError(_))));
+
+        let mut plain = raw_field.clone();
+        plain.value_encrypted = b"plaintext".to_vec();
+        assert!(matches!(wallet.put_raw_record(&RawRecord::Field(plain)), Err(WalletError::ValidationError(_))));
+
+        let mut bad_time = raw_item.clone();
+        bad_time.change_timestamp = Some("yesterday".to_string());
+        assert!(matches!(wallet.put_raw_record(&RawRecord::Item(bad_time)), Err(WalletError::ValidationError(_))));
+
+        let mut orphan = raw_item.clone();
+        orphan.item_id = "ORPHAN01".to_string();
+        orphan.parent_id = Some("MISSING1".to_string());
+        assert!(matches!(wallet.put_raw_record(&RawRecord::Item(orphan)), Err(WalletError::ParentNotFound(_))));
+        // Only folders can be profile roots
+        let mut stray = raw_item.clone();
+        stray.item_id = "STRAY001".to_string();
+        stray.parent_id = Some(ROOT_PARENT_ID.to_string());
+        assert!(matches!(wallet.put_raw_record(&RawRecord::Item(stray)), Err(WalletError::ParentNotFound(_))));
+
+        let mut moved = raw_field.clone();
+        let other_item = wallet.add_item("Other", "bank", false, None).unwrap();
+        moved.item_id = other_item;
+        assert!(matches!(wallet.put_raw_record(&RawRecord::Field(moved)), Err(WalletError::ValidationError(_))));
+
+        let mut bad_id = raw_item.clone();
+        bad_id.item_id = "../../x".to_string();
+        assert!(matches!(wallet.put_raw_record(&RawRecord::Item(bad_id)), Err(WalletError::ValidationError(_))));
+        let mut bad_field_id = raw_field.clone();
+        bad_field_id.field_id = String::new();
+        assert!(matches!(wallet.put_raw_record(&RawRecord::Field(bad_field_id)), Err(WalletError::ValidationError(_))));
+
+        // A parent inside the item's own subtree would detach it from the root
+        let outer = wallet.add_item("Outer", "folder", true, None).unwrap();
+        let inner = wallet.add_item("Inner", "folder", true, Some(&outer)).unwrap();
+        let raw_outer = wallet.get_raw_records().unwrap().into_iter()
+            .find_map(|r| match r {
+                RawRecord::Item(i) if i.item_id == outer => Some(i),
+                _ => None,
+            })
+            .unwrap();
+        for parent in [&inner, &outer] {
+            let cycle = RawItem { parent_id: Some(parent.clone()), ..raw_outer.clone() };
+            assert!(matches!(wallet.put_raw_record(&RawRecord::Item(cycle)), Err(WalletError::InvalidOperation(_))));
+        }
+
+        let mut root = raw_item;
+        root.item_id = ROOT_ID.to_string();
+        assert!(matches!(wallet.put_raw_record(&RawRecord::Item(root)), Err(WalletError::InvalidOperation(_))));
+    }
+}
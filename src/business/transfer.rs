@@ -0,0 +1,580 @@
+//! Whole-wallet JSON transfer
+//!
+//! [`Wallet::export_json_file`] writes every item, field and label of the
+//! wallet, with the wallet properties, into one JSON document that keeps
+//! the IDs, timestamps, order and flags of each record.
+//! [`Wallet::import_json_file`] writes such a document back into another
+//! wallet unchanged, so a wallet can move between devices without copying
+//! its SQLite file. Item notes, secure note bodies and item metadata go
+//! along with their item; attachments and folder PINs are not carried.
+//!
+//! With a passphrase the document is sealed like a share file:
+//!
+//! ```text
+//! "IWJSON" | version (1 byte) | m_cost, t_cost, p_cost (u32 LE each) | salt | sealed JSON
+//! ```
+//!
+//! Without one it is written as plain JSON, which holds every secret of
+//! the wallet in the clear.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use zeroize::Zeroizing;
+
+use crate::{ROOT_ID, ROOT_PARENT_ID};
+use crate::crypto;
+use crate::database::models::JsonExportOptions;
+use crate::database::queries::{self, parse_timestamp, RawField, RawItem, RawLabel};
+use crate::error::{WalletError, Result};
+use crate::import::ImportReport;
+use super::secure_notes::SECURE_NOTE_CHUNK_SIZE;
+use super::wallet::{random_bytes, Wallet, KDF_SALT_LEN};
+
+/// `format` tag of a wallet JSON document
+pub const WALLET_JSON_FORMAT: &str = "intelliwallet-wallet";
+/// Version of the wallet JSON document written by this version
+pub const WALLET_JSON_VERSION: u32 = 1;
+
+const SEALED_MAGIC: &[u8] = b"IWJSON";
+const SEALED_VERSION: u8 = 1;
+const HEADER_LEN: usize = SEALED_MAGIC.len() + 1 + 12 + KDF_SALT_LEN;
+
+#[derive(Serialize, Deserialize)]
+struct WalletDocument {
+    format: String,
+    version: u32,
+    exported: String,
+    properties: DocProperties,
+    labels: Vec<DocLabel>,
+    /// Parents before children
+    items: Vec<DocItem>,
+    fields: Vec<DocField>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DocProperties {
+    database_id: String,
+    lang: String,
+    version: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DocLabel {
+    field_type: String,
+    name: String,
+    value_type: String,
+    icon: String,
+    system: bool,
+    changed: Option<String>,
+    deleted: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DocItem {
+    item_id: String,
+    parent_id: Option<String>,
+    name: String,
+    icon: String,
+    folder: bool,
+    created: Option<String>,
+    changed: Option<String>,
+    deleted: bool,
+    primary_field: Option<String>,
+    color: Option<String>,
+    sort_weight: Option<i32>,
+    locked: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+    /// Body of a secure note item
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    secure_note: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    metadata: BTreeMap<String, Value>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DocField {
+    item_id: String,
+    field_id: String,
+    field_type: String,
+    value: String,
+    changed: Option<String>,
+    deleted: bool,
+    sort_weight: Option<i32>,
+}
+
+fn transfer_key(passphrase: &str, salt: &[u8], params: crypto::kdf::KdfParams) -> Result<Zeroizing<[u8; crypto::kdf::KEK_LEN]>> {
+    crypto::kdf::derive_kek(passphrase.as_bytes(), salt, params)
+        .map(Zeroizing::new)
+        .map_err(WalletError::EncryptionError)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn check_timestamp(timestamp: Option<&str>) -> Result<()> {
+    match timestamp {
+        Some(ts) if parse_timestamp(ts).is_none() => Err(WalletError::ValidationError(
+            format!("Invalid timestamp: {ts}"),
+        )),
+        _ => Ok(()),
+    }
+}
+
+impl Wallet {
+    /// Write the whole wallet to `path` as a JSON document, encrypted when
+    /// `options` has a passphrase. Fails with `FolderLocked` when a
+    /// PIN-protected folder is not unlocked.
+    pub fn export_json_file(&mut self, path: &Path, options: &JsonExportOptions) -> Result<()> {
+        self.ensure_unlocked()?;
+        if options.passphrase.as_deref() == Some("") {
+            return Err(WalletError::InvalidOperation("Export passphrase must not be empty".to_string()));
+        }
+        let json = self.wallet_document(options.include_deleted)?;
+        let out = match &options.passphrase {
+            None => json,
+            Some(passphrase) => {
+                let params = crypto::kdf::KdfParams::current();
+                let salt = random_bytes(KDF_SALT_LEN);
+                let key = transfer_key(passphrase, &salt, params)?;
+                let sealed = crypto::aead::seal(&key, &json).map_err(WalletError::EncryptionError)?;
+
+                let mut out = Vec::with_capacity(HEADER_LEN + sealed.len());
+                out.extend_from_slice(SEALED_MAGIC);
+                out.push(SEALED_VERSION);
+                out.extend_from_slice(&params.m_cost_kib.to_le_bytes());
+                out.extend_from_slice(&params.t_cost.to_le_bytes());
+                out.extend_from_slice(&params.p_cost.to_le_bytes());
+                out.extend_from_slice(&salt);
+                out.extend_from_slice(&sealed);
+                Zeroizing::new(out)
+            }
+        };
+        fs::write(path, &*out)?;
+        Ok(())
+    }
+
+    /// Add the records of a document from [`Wallet::export_json_file`] to
+    /// this wallet with their IDs, timestamps and flags, and take over its
+    /// language. Labels the wallet already has are kept. `passphrase` is
+    /// needed for an encrypted document; a wrong one is `InvalidPassword`.
+    /// Nothing is written when an item or field ID is already taken here.
+    pub fn import_json_file(&mut self, path: &Path, passphrase: Option<&str>) -> Result<ImportReport> {
+        self.ensure_unlocked()?;
+        let bytes = fs::read(path)?;
+        let json = if bytes.starts_with(SEALED_MAGIC) {
+            let passphrase = passphrase.ok_or_else(|| WalletError::InvalidOperation(
+                "The wallet document is encrypted; a passphrase is needed".to_string(),
+            ))?;
+            if bytes.len() < HEADER_LEN {
+                return Err(WalletError::ValidationError("Truncated wallet document".to_string()));
+            }
+            let version = bytes[SEALED_MAGIC.len()];
+            if version != SEALED_VERSION {
+                return Err(WalletError::InvalidVersion(format!("wallet document v{version}")));
+            }
+            let p = SEALED_MAGIC.len() + 1;
+            let params = crypto::kdf::KdfParams {
+                m_cost_kib: read_u32(&bytes[p..]),
+                t_cost: read_u32(&bytes[p + 4..]),
+                p_cost: read_u32(&bytes[p + 8..]),
+            };
+            if !params.within_untrusted_limits() {
+                return Err(WalletError::ValidationError(
+                    "Key derivation parameters of the wallet document are out of range".to_string(),
+                ));
+            }
+            let salt = &bytes[p + 12..HEADER_LEN];
+            let key = transfer_key(passphrase, salt, params).map_err(|_| WalletError::InvalidPassword)?;
+            Zeroizing::new(crypto::aead::open(&key, &bytes[HEADER_LEN..]).map_err(|_| WalletError::InvalidPassword)?)
+        } else {
+            Zeroizing::new(bytes)
+        };
+        let doc: WalletDocument = serde_json::from_slice(&json)
+            .map_err(|e| WalletError::json("Invalid wallet document", e))?;
+        if doc.format != WALLET_JSON_FORMAT {
+            return Err(WalletError::ValidationError(format!("Not a wallet document: {}", doc.format)));
+        }
+        if doc.version != WALLET_JSON_VERSION {
+            return Err(WalletError::InvalidVersion(format!("wallet document v{}", doc.version)));
+        }
+        self.import_wallet_document(doc)
+    }
+
+    /// JSON of the whole wallet; the trash too with `include_deleted`
+    fn wallet_document(&mut self, include_deleted: bool) -> Result<Zeroizing<Vec<u8>>> {
+        let properties = self.get_properties()?;
+        let (mut raw_items, mut raw_fields, mut raw_labels) = {
+            let conn = self.db.as_ref()
+                .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+                .connection()?;
+            let mut items = queries::get_all_items_raw(conn)?;
+            let mut fields = queries::get_all_fields_raw(conn)?;
+            let mut labels = queries::get_all_labels(conn)?;
+            if include_deleted {
+                items.extend(queries::get_deleted_items_raw(conn)?);
+                fields.extend(queries::get_deleted_fields_raw(conn)?);
+                labels.extend(queries::get_deleted_labels(conn)?);
+            }
+            (items, fields, labels)
+        };
+        raw_items.retain(|i| i.item_id != ROOT_ID);
+        let item_ids: HashSet<String> = raw_items.iter().map(|i| i.item_id.clone()).collect();
+        raw_fields.retain(|f| item_ids.contains(&f.item_id));
+        raw_labels.sort_by(|a, b| a.field_type.cmp(&b.field_type));
+        raw_fields.sort_by(|a, b| a.item_id.cmp(&b.item_id).then_with(|| a.field_id.cmp(&b.field_id)));
+
+        // Parents before children, so a reader can rebuild the tree in order
+        let mut ordered: Vec<RawItem> = Vec::with_capacity(raw_items.len());
+        let mut placed: HashSet<String> = HashSet::new();
+        raw_items.sort_by(|a, b| a.item_id.cmp(&b.item_id));
+        while !raw_items.is_empty() {
+            let (ready, rest): (Vec<RawItem>, Vec<RawItem>) = raw_items.into_iter().partition(|i| {
+                i.parent_id.as_deref().is_none_or(|p| p == ROOT_ID || placed.contains(p) || !item_ids.contains(p))
+            });
+            if ready.is_empty() {
+                // A parent cycle; keep the rest as it is
+                ordered.extend(rest);
+                break;
+            }
+            placed.extend(ready.iter().map(|i| i.item_id.clone()));
+            ordered.extend(ready);
+            raw_items = rest;
+        }
+
+        let mut items = Vec::with_capacity(ordered.len());
+        for mut raw in ordered {
+            self.open_item_envelope(&mut raw);
+            let secure_note = if self.is_secure_note(&raw.item_id)? {
+                Some(self.get_secure_note_body(&raw.item_id)?)
+            } else {
+                None
+            };
+            items.push(DocItem {
+                note: self.get_item_note(&raw.item_id)?,
+                secure_note,
+                metadata: self.get_item_metadata(&raw.item_id)?,
+                name: self.dec_value(&raw.name_encrypted)?,
+                item_id: raw.item_id,
+                parent_id: raw.parent_id,
+                icon: raw.icon,
+                folder: raw.folder,
+                created: raw.create_timestamp,
+                changed: raw.change_timestamp,
+                deleted: raw.deleted,
+                primary_field: raw.field_id,
+                color: raw.color,
+                sort_weight: raw.sort_weight,
+                locked: raw.locked,
+            });
+        }
+
+        let mut fields = Vec::with_capacity(raw_fields.len());
+        for mut raw in raw_fields {
+            self.open_field_envelope(&mut raw);
+            let value = match self.dec_field_value(&raw.value_encrypted) {
+                Ok(value) => value,
+                Err(e) => {
+                    // Report a locked PIN folder rather than a decryption error
+                    if let Some(folder) = self.protecting_folder(&raw.item_id)? {
+                        self.folder_key(&folder)?;
+                    }
+                    return Err(e);
+                }
+            };
+            fields.push(DocField {
+                value,
+                item_id: raw.item_id,
+                field_id: raw.field_id,
+                field_type: raw.field_type,
+                changed: raw.change_timestamp,
+                deleted: raw.deleted,
+                sort_weight: raw.sort_weight,
+            });
+        }
+
+        let labels = raw_labels.into_iter()
+            .map(|l| DocLabel {
+                field_type: l.field_type,
+                name: l.label_name,
+                value_type: l.value_type,
+                icon: l.icon,
+                system: l.system,
+                changed: l.change_timestamp,
+                deleted: l.deleted,
+            })
+            .collect();
+
+        let doc = WalletDocument {
+            format: WALLET_JSON_FORMAT.to_string(),
+            version: WALLET_JSON_VERSION,
+            exported: queries::format_timestamp(&Utc::now()),
+            properties: DocProperties {
+                database_id: properties.database_id,
+                lang: properties.lang,
+                version: properties.version,
+            },
+            labels,
+            items,
+            fields,
+        };
+        serde_json::to_vec_pretty(&doc)
+            .map(Zeroizing::new)
+            .map_err(|e| WalletError::json("Failed to serialize wallet", e))
+    }
+
+    /// Write the records of a parsed document, after checking all of them
+    fn import_wallet_document(&mut self, doc: WalletDocument) -> Result<ImportReport> {
+        {
+            let conn = self.db.as_ref()
+                .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+                .connection()?;
+            let item_ids: HashSet<&str> = doc.items.iter().map(|i| i.item_id.as_str()).collect();
+            if item_ids.len() != doc.items.len() {
+                return Err(WalletError::ValidationError("Duplicate item ID in the document".to_string()));
+            }
+            let mut field_ids = HashSet::new();
+            for item in &doc.items {
+                if item.item_id == ROOT_ID || queries::item_row_exists(conn, &item.item_id)? {
+                    return Err(WalletError::InvalidOperation(format!(
+                        "Item {} already exists in this wallet", item.item_id
+                    )));
+                }
+                // Profile roots are folders with the root's own parent
+                if let Some(parent) = item.parent_id.as_deref()
+                    && parent != ROOT_ID && !(item.folder && parent == ROOT_PARENT_ID)
+                    && !item_ids.contains(parent) && !queries::item_exists(conn, parent)? {
+                    return Err(WalletError::ParentNotFound(parent.to_string()));
+                }
+                check_timestamp(item.created.as_deref())?;
+                check_timestamp(item.changed.as_deref())?;
+            }
+            for field in &doc.fields {
+                if !item_ids.contains(field.item_id.as_str()) {
+                    return Err(WalletError::ItemNotFound(field.item_id.clone()));
+                }
+                if !field_ids.insert(field.field_id.as_str()) || queries::field_id_exists(conn, &field.field_id)? {
+                    return Err(WalletError::InvalidOperation(format!(
+                        "Field {} already exists in this wallet", field.field_id
+                    )));
+                }
+                check_timestamp(field.changed.as_deref())?;
+            }
+            for label in &doc.labels {
+                check_timestamp(label.changed.as_deref())?;
+            }
+        }
+
+        // One transaction, so a failure halfway leaves the wallet unchanged
+        let mut report = ImportReport::default();
+        self.db.as_mut()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .begin_transaction()?;
+        let written = self.write_wallet_document(&doc, &mut report);
+        let db = self.db.as_mut()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?;
+        match written {
+            Ok(()) => db.commit_transaction()?,
+            Err(e) => {
+                let _ = db.rollback_transaction();
+                return Err(e);
+            }
+        }
+        let _ = db.checkpoint();
+
+        self.clear_caches();
+        self.note_mutation();
+        Ok(report)
+    }
+
+    /// Write the checked records of `doc`, inside the caller's transaction
+    fn write_wallet_document(&self, doc: &WalletDocument, report: &mut ImportReport) -> Result<()> {
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        for label in &doc.labels {
+            if queries::label_row_exists(conn, &label.field_type)? {
+                continue;
+            }
+            queries::upsert_label_raw_no_checkpoint(conn, &RawLabel {
+                field_type: label.field_type.clone(),
+                label_name: label.name.clone(),
+                value_type: label.value_type.clone(),
+                icon: label.icon.clone(),
+                system: label.system,
+                change_timestamp: label.changed.clone(),
+                deleted: label.deleted,
+                usage: 0,
+            })?;
+        }
+        for item in &doc.items {
+            let name = Zeroizing::new(self.enc_value(&item.name)?);
+            queries::upsert_item_raw_no_checkpoint(conn, &RawItem {
+                item_id: item.item_id.clone(),
+                parent_id: item.parent_id.clone(),
+                name_encrypted: name.to_vec(),
+                icon: item.icon.clone(),
+                folder: item.folder,
+                create_timestamp: item.created.clone(),
+                change_timestamp: item.changed.clone(),
+                deleted: item.deleted,
+                field_id: item.primary_field.clone(),
+                color: item.color.clone(),
+                meta: None,
+                sort_weight: item.sort_weight,
+                locked: item.locked,
+            })?;
+            if let Some(note) = &item.note {
+                queries::set_item_note_raw_no_checkpoint(conn, &item.item_id, &self.seal_item_data(&item.item_id, note.as_bytes())?)?;
+            }
+            if let Some(body) = &item.secure_note {
+                // An empty body is one empty chunk, as `add_secure_note` stores it
+                let mut chunks: Vec<&[u8]> = body.as_bytes().chunks(SECURE_NOTE_CHUNK_SIZE).collect();
+                if chunks.is_empty() {
+                    chunks.push(b"");
+                }
+                let count = chunks.len();
+                for (n, chunk) in chunks.into_iter().enumerate() {
+                    let sealed = self.seal_note_chunk(&item.item_id, n as u32, n + 1 == count, chunk)?;
+                    queries::set_secure_note_chunk(conn, &item.item_id, n as u32, &sealed)?;
+                }
+            }
+            if !item.metadata.is_empty() {
+                let json = serde_json::to_string(&item.metadata)
+                    .map_err(|e| WalletError::json("Invalid item metadata", e))?;
+                queries::set_item_metadata_raw_no_checkpoint(conn, &item.item_id, &self.seal_item_data(&item.item_id, json.as_bytes())?)?;
+            }
+            if item.folder {
+                report.folders_created += 1;
+            } else {
+                report.items_created += 1;
+            }
+        }
+        for field in &doc.fields {
+            queries::upsert_field_raw_no_checkpoint(conn, &RawField {
+                value_encrypted: self.enc_field_value(&field.item_id, &field.value)?,
+                item_id: field.item_id.clone(),
+                field_id: field.field_id.clone(),
+                field_type: field.field_type.clone(),
+                change_timestamp: field.changed.clone(),
+                deleted: field.deleted,
+                sort_weight: field.sort_weight,
+                meta: None,
+            })?;
+            report.fields_created += 1;
+        }
+        queries::set_lang_no_checkpoint(conn, &doc.properties.lang)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use crate::IWItem;
+    use crate::business::wallet::tests::create_test_wallet;
+
+    #[test]
+    fn test_json_file_round_trip() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let work = wallet.add_item("Work", "folder", true, None).unwrap();
+        let vpn = wallet.add_item("VPN", "document", false, Some(&work)).unwrap();
+        let pass = wallet.add_field(&vpn, "PASS", "tunnel-secret", None).unwrap();
+        wallet.set_primary_field(&vpn, &pass).unwrap();
+        wallet.set_item_color(&vpn, Some("#336699")).unwrap();
+        let pin = wallet.add_label("Door PIN", "lock", "text").unwrap();
+        wallet.add_field(&vpn, &pin, "4321", Some(7)).unwrap();
+        wallet.set_item_note(&vpn, "ask IT for the token").unwrap();
+        wallet.set_item_meta(&vpn, "autofill", serde_json::json!(true)).unwrap();
+        let codes = wallet.add_secure_note("Codes", Some(&work)).unwrap();
+        wallet.set_secure_note_body(&codes, "  1111\n2222  ").unwrap();
+        let gone = wallet.add_item("Old", "document", false, None).unwrap();
+        wallet.delete_item(&gone).unwrap();
+
+        let out = TempDir::new().unwrap();
+        let plain = out.path().join("wallet.json");
+        wallet.export_json_file(&plain, &JsonExportOptions::default()).unwrap();
+        let sealed = out.path().join("wallet.iwjson");
+        let options = JsonExportOptions { passphrase: Some("move phrase".to_string()), include_deleted: true };
+        wallet.export_json_file(&sealed, &options).unwrap();
+        let bytes = fs::read(&sealed).unwrap();
+        assert!(bytes.starts_with(SEALED_MAGIC));
+        assert!(!bytes.windows(6).any(|w| w == b"tunnel"));
+
+        let (mut other, _other_temp) = create_test_wallet();
+        assert!(matches!(other.import_json_file(&sealed, Some("wrong")), Err(WalletError::InvalidPassword)));
+        assert!(other.import_json_file(&sealed, None).is_err());
+        let report = other.import_json_file(&sealed, Some("move phrase")).unwrap();
+        assert_eq!((report.folders_created, report.items_created, report.fields_created), (1, 3, 2));
+
+        let source: Vec<IWItem> = wallet.get_items().unwrap().to_vec();
+        let copied: Vec<IWItem> = other.get_items().unwrap().to_vec();
+        for item in source.iter().filter(|i| !i.is_root()) {
+            let copy = copied.iter().find(|c| c.item_id == item.item_id).unwrap();
+            assert_eq!(
+                (&copy.name, &copy.parent_id, copy.create_timestamp, copy.change_timestamp, &copy.primary_field, &copy.color),
+                (&item.name, &item.parent_id, item.create_timestamp, item.change_timestamp, &item.primary_field, &item.color),
+            );
+        }
+        let fields = other.get_fields_by_item(&vpn).unwrap();
+        let door = fields.iter().find(|f| f.field_type == pin).unwrap();
+        assert_eq!((door.value.as_str(), door.label.as_str(), door.sort_weight), ("4321", "Door PIN", 7));
+        assert!(other.get_deleted_items().unwrap().iter().any(|i| i.item_id == gone));
+        assert_eq!(other.get_item_note(&vpn).unwrap().as_deref(), Some("ask IT for the token"));
+        assert_eq!(other.get_item_meta(&vpn, "autofill").unwrap(), Some(serde_json::json!(true)));
+        assert_eq!(other.get_secure_note_body(&codes).unwrap(), "  1111\n2222  ");
+
+        // The same records cannot be added twice
+        assert!(matches!(other.import_json_file(&plain, None), Err(WalletError::InvalidOperation(_))));
+        let (mut third, _third_temp) = create_test_wallet();
+        third.import_json_file(&plain, None).unwrap();
+        assert!(third.get_deleted_items().unwrap().iter().all(|i| i.item_id != gone));
+    }
+
+    #[test]
+    fn test_json_file_profiles_and_rejections() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let work = wallet.create_profile("Work").unwrap();
+        let vpn = wallet.add_item("VPN", "document", false, Some(&work)).unwrap();
+        wallet.add_field(&vpn, "PASS", "tunnel-secret", None).unwrap();
+
+        let out = TempDir::new().unwrap();
+        let plain = out.path().join("wallet.json");
+        wallet.export_json_file(&plain, &JsonExportOptions::default()).unwrap();
+        let (mut other, _other_temp) = create_test_wallet();
+        other.import_json_file(&plain, None).unwrap();
+        let profile = other.get_item(&work).unwrap().unwrap();
+        assert_eq!(profile.parent_id.as_deref(), Some(ROOT_PARENT_ID));
+        assert_eq!(other.list_profiles().unwrap().len(), 2);
+        assert_eq!(other.get_fields_by_item(&vpn).unwrap()[0].value, "tunnel-secret");
+
+        // A failure halfway (here a field below a locked folder) writes nothing
+        let (mut third, _third_temp) = create_test_wallet();
+        let vault = third.add_item("Vault", "folder", true, None).unwrap();
+        third.set_folder_pin(&vault, "1234").unwrap();
+        third.lock_folder(&vault);
+        let mut doc: Value = serde_json::from_slice(&fs::read(&plain).unwrap()).unwrap();
+        doc["items"][0]["parent_id"] = Value::String(vault.clone());
+        let moved = out.path().join("moved.json");
+        fs::write(&moved, serde_json::to_vec(&doc).unwrap()).unwrap();
+        assert!(matches!(third.import_json_file(&moved, None), Err(WalletError::FolderLocked(_))));
+        assert!(third.get_item(&work).unwrap().is_none());
+        assert!(third.get_item(&vpn).unwrap().is_none());
+
+        // Key derivation costs from the header are capped
+        let sealed = out.path().join("wallet.iwjson");
+        let options = JsonExportOptions { passphrase: Some("move phrase".to_string()), include_deleted: false };
+        wallet.export_json_file(&sealed, &options).unwrap();
+        let mut bytes = fs::read(&sealed).unwrap();
+        let p = SEALED_MAGIC.len() + 1;
+        bytes[p..p + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        fs::write(&sealed, &bytes).unwrap();
+        assert!(matches!(third.import_json_file(&sealed, Some("move phrase")), Err(WalletError::ValidationError(_))));
+    }
+}
@@ -0,0 +1,140 @@
+//! Brute-force resistance for unlock
+//!
+//! [`Wallet::unlock_throttled`] counts consecutive wrong passwords in the
+//! properties table, so the count survives app restarts. After
+//! [`FREE_ATTEMPTS`] failures every further attempt must wait an
+//! exponentially growing delay (1 s, 2 s, 4 s, ... capped at
+//! [`MAX_DELAY_SECS`]); attempts made too early are rejected without
+//! checking the password. A successful unlock resets the counter.
+
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use crate::database::queries;
+use crate::error::{WalletError, Result};
+use super::wallet::Wallet;
+
+/// Consecutive failures allowed before delays start
+pub const FREE_ATTEMPTS: u32 = 3;
+
+/// Upper bound of the delay between attempts
+pub const MAX_DELAY_SECS: u64 = 300;
+
+/// Outcome of [`Wallet::unlock_throttled`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnlockAttempt {
+    /// Password accepted, wallet unlocked
+    Unlocked,
+    /// Password rejected. `retry_after` is the wait before the next attempt
+    /// will be accepted (zero while still within the free attempts).
+    WrongPassword { failures: u32, retry_after: Duration },
+    /// Attempt refused without checking the password: try again later
+    Throttled { retry_after: Duration },
+}
+
+/// Required wait after `failures` consecutive failures
+pub fn unlock_delay(failures: u32) -> Duration {
+    if failures < FREE_ATTEMPTS {
+        return Duration::ZERO;
+    }
+    let exp = (failures - FREE_ATTEMPTS).min(16);
+    Duration::from_secs((1u64 << exp).min(MAX_DELAY_SECS))
+}
+
+impl Wallet {
+    /// Unlock with exponential back-off after repeated wrong passwords.
+    pub fn unlock_throttled(&mut self, password: &str) -> Result<UnlockAttempt> {
+        self.unlock_throttled_at(password, Utc::now())
+    }
+
+    /// Time left before `unlock_throttled` accepts another attempt
+    pub fn unlock_retry_after(&self) -> Result<Duration> {
+        self.unlock_retry_after_at(Utc::now())
+    }
+
+    /// Consecutive failed `unlock_throttled` attempts
+    pub fn failed_unlock_count(&self) -> Result<u32> {
+        let conn = self.throttle_connection()?;
+        Ok(queries::get_unlock_failures(conn)?.0)
+    }
+
+    fn throttle_connection(&self) -> Result<&rusqlite::Connection> {
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        queries::ensure_unlock_throttle_columns(conn)?;
+        Ok(conn)
+    }
+
+    fn unlock_retry_after_at(&self, now: DateTime<Utc>) -> Result<Duration> {
+        let (failures, last) = queries::get_unlock_failures(self.throttle_connection()?)?;
+        let delay = unlock_delay(failures);
+        let Some(last) = last else {
+            return Ok(Duration::ZERO);
+        };
+        // A clock set backwards counts as no time elapsed
+        let elapsed = (now - last).to_std().unwrap_or(Duration::ZERO);
+        Ok(delay.saturating_sub(elapsed))
+    }
+
+    fn unlock_throttled_at(&mut self, password: &str, now: DateTime<Utc>) -> Result<UnlockAttempt> {
+        let retry_after = self.unlock_retry_after_at(now)?;
+        if !retry_after.is_zero() {
+            return Ok(UnlockAttempt::Throttled { retry_after });
+        }
+
+        if self.unlock(password)? {
+            queries::set_unlock_failures(self.throttle_connection()?, 0, None)?;
+            return Ok(UnlockAttempt::Unlocked);
+        }
+
+        let conn = self.throttle_connection()?;
+        let failures = queries::get_unlock_failures(conn)?.0.saturating_add(1);
+        queries::set_unlock_failures(conn, failures, Some(&now))?;
+        Ok(UnlockAttempt::WrongPassword { failures, retry_after: unlock_delay(failures) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeDelta;
+    use crate::business::wallet::tests::create_test_wallet;
+
+    #[test]
+    fn test_unlock_delay() {
+        assert_eq!(unlock_delay(0), Duration::ZERO);
+        assert_eq!(unlock_delay(2), Duration::ZERO);
+        assert_eq!(unlock_delay(3), Duration::from_secs(1));
+        assert_eq!(unlock_delay(5), Duration::from_secs(4));
+        assert_eq!(unlock_delay(100), Duration::from_secs(MAX_DELAY_SECS));
+    }
+
+    #[test]
+    fn test_unlock_throttled_backs_off_and_resets() {
+        let (mut wallet, temp) = create_test_wallet();
+        wallet.lock();
+        // Stored timestamps have whole-second precision
+        let t0 = DateTime::from_timestamp(Utc::now().timestamp(), 0).unwrap();
+
+        for n in 1..=FREE_ATTEMPTS {
+            let attempt = wallet.unlock_throttled_at("wrong", t0).unwrap();
+            assert_eq!(attempt, UnlockAttempt::WrongPassword { failures: n, retry_after: unlock_delay(n) });
+        }
+        // Too early: refused even with the right password
+        assert_eq!(
+            wallet.unlock_throttled_at("TestPassword123", t0).unwrap(),
+            UnlockAttempt::Throttled { retry_after: Duration::from_secs(1) }
+        );
+        assert!(!wallet.is_unlocked());
+
+        // The counter survives reopening the wallet
+        drop(wallet);
+        let mut wallet = Wallet::open(temp.path()).unwrap();
+        assert_eq!(wallet.failed_unlock_count().unwrap(), FREE_ATTEMPTS);
+
+        let later = t0 + TimeDelta::seconds(2);
+        assert_eq!(wallet.unlock_throttled_at("TestPassword123", later).unwrap(), UnlockAttempt::Unlocked);
+        assert_eq!(wallet.failed_unlock_count().unwrap(), 0);
+        assert_eq!(wallet.unlock_retry_after().unwrap(), Duration::ZERO);
+    }
+}
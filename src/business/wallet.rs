@@ -5,13 +5,25 @@
 
 use std::path::{Path, PathBuf};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Utc};
 use crate::error::{WalletError, Result};
-use crate::database::{Database, IWItem, IWField, IWLabel, IWProperties};
+use crate::database::{CompactOptions, CompactResult, Database, IWItem, IWField, IWLabel, IWProperties};
 use crate::database::queries::{self, parse_timestamp, CryptoRecord};
-use crate::database::migrations;
+use crate::database::{migrations, salvage};
+use crate::backup::BackupManager;
+use super::auto_backup::AutoBackupState;
+use super::backup_view::BackupCopy;
+use super::ids::IdCollisionStats;
+use super::password_reuse::PasswordReusePolicy;
+use super::quota::QuotaState;
+use super::sessions::Session;
 use crate::crypto;
+use crate::localization::Translations;
 use crate::crypto::dek::DEK_LEN;
-use crate::utils::generate_database_id;
+use crate::crypto::kdf::KdfParams;
+use crate::utils::{generate_database_id, CancelToken, IdGenerator, RandomIdGenerator};
 use crate::{DATABASE_FILENAME, ROOT_ID, ROOT_PARENT_ID, DB_VERSION, ENCRYPTION_COUNT_DEFAULT};
 use rand::Rng;
 use zeroize::Zeroizing;
@@ -24,12 +36,46 @@ pub const PRE_V6_BACKUP_FILENAME: &str = "nswallet.pre-v6.bak";
 const CRYPTO_SCHEME_V6: i64 = 1;
 
 /// KDF salt length in bytes.
-const KDF_SALT_LEN: usize = 16;
+pub(crate) const KDF_SALT_LEN: usize = 16;
 
 /// In-memory state of an unlocked wallet. Holds the per-vault Data Encryption
 /// Key; zeroized on drop / lock.
 pub(crate) struct Unlocked {
     dek: Zeroizing<[u8; DEK_LEN]>,
+    /// Keys of PIN-protected folders opened with `unlock_folder`
+    pub(crate) folder_keys: HashMap<String, Zeroizing<[u8; DEK_LEN]>>,
+    /// PINs of the same folders, so `clone_to` can protect the copy's
+    /// folders (whose keys derive from another DEK) with them
+    pub(crate) folder_pins: HashMap<String, Zeroizing<String>>,
+    /// Live session tokens, by SHA-256 of the token
+    pub(crate) sessions: HashMap<[u8; 32], Session>,
+    /// When the wallet was unlocked, and milliseconds after that of the
+    /// last call that needed it unlocked (see `Wallet::lock_if_idle`)
+    unlocked_at: Instant,
+    last_activity_ms: AtomicU64,
+}
+
+impl Unlocked {
+    fn new(dek: [u8; DEK_LEN]) -> Self {
+        Unlocked {
+            dek: Zeroizing::new(dek),
+            folder_keys: HashMap::new(),
+            folder_pins: HashMap::new(),
+            sessions: HashMap::new(),
+            unlocked_at: Instant::now(),
+            last_activity_ms: AtomicU64::new(0),
+        }
+    }
+
+    fn touch(&self) {
+        let now = self.unlocked_at.elapsed().as_millis() as u64;
+        self.last_activity_ms.store(now, Ordering::Relaxed);
+    }
+
+    fn idle(&self) -> Duration {
+        let last = Duration::from_millis(self.last_activity_ms.load(Ordering::Relaxed));
+        self.unlocked_at.elapsed().saturating_sub(last)
+    }
 }
 
 /// Outcome of the one-time v5->v6 migration, kept in memory after a
@@ -52,11 +98,30 @@ pub struct MigrationSummary {
     pub mode_no_chain: u32,
     pub mode_md5_chain: u32,
     pub mode_raw_password: u32,
+    /// Records that only decrypted with the iOS-workaround key (first key
+    /// byte zeroed). Rewritten under the DEK like every other record, so
+    /// they never need the workaround again.
+    pub ios_workaround: u32,
     /// Whether the missing root record was synthesized during this migration.
     pub root_created: bool,
     pub duration_ms: u64,
 }
 
+/// How long the phases of opening and unlocking took, so integrators can
+/// report startup performance. See [`Wallet::last_open_metrics`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OpenMetrics {
+    /// Opening the database file
+    pub file_open: Duration,
+    /// Schema migrations on open plus the v5->v6 data migration on unlock
+    pub migration: Duration,
+    /// Key derivation and DEK unwrap (legacy vaults: root item decrypt)
+    pub root_decrypt: Duration,
+    /// Decrypting items and fields into the caches after unlock; zero
+    /// until the first listing fills them
+    pub cache_warm: Duration,
+}
+
 /// Outcome of one quarantine recovery pass.
 #[derive(Debug, Clone)]
 pub struct RecoveryResult {
@@ -67,15 +132,68 @@ pub struct RecoveryResult {
     pub waiting_for_parent: u32,
     /// Records that did not decrypt under this password (still quarantined).
     pub remaining: u32,
+    /// Recovered records that needed the iOS-workaround key; like the
+    /// rest, they are restored under the DEK.
+    pub ios_workaround: u32,
+}
+
+/// Kind of record reported by [`Wallet::get_undecryptable_records`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordKind {
+    Item,
+    Field,
+}
+
+/// An active record whose blob does not decrypt under the vault key. Such
+/// records are skipped by the listings instead of failing them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UndecryptableRecord {
+    pub kind: RecordKind,
+    pub item_id: String,
+    /// Set for fields only
+    pub field_id: Option<String>,
+    /// Decryption error message (no secret material)
+    pub error: String,
+}
+
+/// Which path [`Wallet::open_with_recovery`] took to produce an open wallet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OpenRecovery {
+    /// The database passed its integrity check and opened normally.
+    Clean,
+    /// The database was damaged; readable rows were salvaged into a fresh
+    /// file. The damaged original is kept at `corrupt_copy`.
+    Salvaged {
+        corrupt_copy: PathBuf,
+        rows_recovered: u64,
+        rows_lost: u64,
+    },
+    /// Salvage was not possible; the latest verified backup was restored.
+    /// The damaged original is kept at `corrupt_copy`.
+    RestoredFromBackup {
+        corrupt_copy: PathBuf,
+        backup: PathBuf,
+    },
 }
 
 /// Draw `n` cryptographically random bytes from the OS CSPRNG.
-fn random_bytes(n: usize) -> Vec<u8> {
+pub(crate) fn random_bytes(n: usize) -> Vec<u8> {
     let mut v = vec![0u8; n];
     rand::rng().fill_bytes(&mut v);
     v
 }
 
+/// Reject Argon2id parameters a password key cannot be derived with
+fn check_kdf_params(params: KdfParams) -> Result<()> {
+    if !params.is_valid() {
+        return Err(WalletError::ValidationError(format!(
+            "Invalid Argon2id parameters (m_cost_kib {}, t_cost {}, p_cost {})",
+            params.m_cost_kib, params.t_cost, params.p_cost
+        )));
+    }
+    Ok(())
+}
+
 /// Turn the legacy `email` column (which actually stores the AES
 /// re-encryption iteration count) into the count used for legacy decrypt.
 ///
@@ -93,6 +211,17 @@ fn legacy_encryption_count(email: Option<&str>) -> u32 {
     email.and_then(|s| s.parse().ok()).unwrap_or(0)
 }
 
+/// Forms of a password to try when verifying it: the NFC form, then the
+/// form as typed if it differs (passwords set before normalization).
+fn password_forms(password: &str) -> Vec<String> {
+    let normalized = crypto::normalize_master_password(password);
+    if normalized == password {
+        vec![normalized]
+    } else {
+        vec![normalized, password.to_string()]
+    }
+}
+
 /// Main wallet interface
 pub struct Wallet {
     /// Path to the wallet folder
@@ -112,6 +241,35 @@ pub struct Wallet {
     pub(crate) labels_cache: Option<HashMap<String, IWLabel>>,
     /// Outcome of the v5->v6 migration, when this session performed one.
     pub(crate) last_migration_summary: Option<MigrationSummary>,
+    /// Reject field values that do not match their label's value type.
+    pub(crate) strict_validation: bool,
+    /// Attached backup manager and mutation counter for auto backups.
+    pub(crate) auto_backup: Option<AutoBackupState>,
+    /// Largest item note accepted by `set_item_note`, in bytes.
+    pub(crate) max_note_size: usize,
+    /// How `update_field` treats reused PASS values.
+    pub(crate) password_reuse_policy: PasswordReusePolicy,
+    /// Soft size limits and queued quota events.
+    pub(crate) quota: Option<QuotaState>,
+    /// Source of new item, field and label IDs.
+    pub(crate) id_generator: Box<dyn IdGenerator>,
+    /// Generated IDs that were already taken and had to be retried.
+    pub(crate) id_collisions: IdCollisionStats,
+    /// Writes since open or the last `save`.
+    pub(crate) pending_writes: u64,
+    /// Time of the last write made through this wallet.
+    pub(crate) last_write: Option<DateTime<Utc>>,
+    /// Phase timings of the last open and unlock.
+    pub(crate) open_metrics: OpenMetrics,
+    /// Caches not yet filled since unlock; their loading counts as warm-up.
+    pub(crate) cache_warming: bool,
+    /// Extracted backup this wallet was opened from with `open_backup`,
+    /// wiped once the wallet is dropped and its database closed.
+    pub(crate) backup_copy: Option<BackupCopy>,
+    /// Argon2id parameters for new password keys, see `set_kdf_params`
+    pub(crate) kdf_params: KdfParams,
+    /// Idle time after which `lock_if_idle` locks, see `set_auto_lock`
+    pub(crate) auto_lock: Option<Duration>,
 }
 
 impl Wallet {
@@ -131,16 +289,24 @@ impl Wallet {
             ));
         }
 
+        let started = Instant::now();
         let db = Database::open(&db_path)?;
+        let file_open = started.elapsed();
 
         // Apply pending migrations. Idempotent on already-current DBs.
         // Migrations operate on plaintext schema and label rows, so they
         // don't need the master password — safe to run pre-unlock.
+        let started = Instant::now();
         {
             let conn = db.connection()?;
             let current = migrations::get_database_version(conn)?;
             migrations::upgrade_database(conn, &current)?;
+            queries::ensure_item_color_column(conn)?;
+            queries::ensure_meta_columns(conn)?;
+            queries::ensure_item_sort_weight_column(conn)?;
+            queries::ensure_item_locked_column(conn)?;
         }
+        let migration = started.elapsed();
 
         Ok(Self {
             folder: folder.to_path_buf(),
@@ -151,12 +317,119 @@ impl Wallet {
             fields_cache: None,
             labels_cache: None,
             last_migration_summary: None,
+            strict_validation: false,
+            auto_backup: None,
+            max_note_size: crate::NOTE_MAX_SIZE_DEFAULT,
+            password_reuse_policy: PasswordReusePolicy::default(),
+            quota: None,
+            id_generator: Box::new(RandomIdGenerator),
+            id_collisions: IdCollisionStats::default(),
+            pending_writes: 0,
+            last_write: None,
+            open_metrics: OpenMetrics { file_open, migration, ..OpenMetrics::default() },
+            cache_warming: false,
+            backup_copy: None,
+            kdf_params: KdfParams::current(),
+            auto_lock: None,
         })
     }
 
+    /// Open a wallet, repairing a damaged database instead of failing.
+    ///
+    /// A database that fails `PRAGMA integrity_check` (torn page, truncated
+    /// WAL) is moved aside as `nswallet.dat.corrupt-<timestamp>` together
+    /// with its `-wal`/`-shm` sidecars. Readable rows are then salvaged into
+    /// a fresh file; when the salvaged file lacks the properties and key
+    /// material needed to unlock, the newest backup in `backups` that
+    /// verifies is restored instead. The returned [`OpenRecovery`] reports
+    /// which path was taken.
+    pub fn open_with_recovery(folder: &Path, backups: Option<&BackupManager>) -> Result<(Self, OpenRecovery)> {
+        let db_path = folder.join(DATABASE_FILENAME);
+
+        if !db_path.exists() {
+            return Err(WalletError::DatabaseNotFound(
+                db_path.to_string_lossy().to_string()
+            ));
+        }
+
+        if salvage::is_healthy(&db_path) {
+            return Ok((Self::open(folder)?, OpenRecovery::Clean));
+        }
+
+        let stamp = chrono::Utc::now().format("%Y%m%d-%H%M%S");
+        let corrupt_copy = folder.join(format!("{DATABASE_FILENAME}.corrupt-{stamp}"));
+        std::fs::rename(&db_path, &corrupt_copy)?;
+        for suffix in ["-wal", "-shm"] {
+            let sidecar = folder.join(format!("{DATABASE_FILENAME}{suffix}"));
+            if sidecar.exists() {
+                std::fs::rename(&sidecar, folder.join(format!("{DATABASE_FILENAME}.corrupt-{stamp}{suffix}")))?;
+            }
+        }
+
+        if let Ok(stats) = salvage::salvage_into(&corrupt_copy, &db_path)
+            && Self::is_unlockable(&db_path)
+            && let Ok(wallet) = Self::open(folder)
+        {
+            return Ok((wallet, OpenRecovery::Salvaged {
+                corrupt_copy,
+                rows_recovered: stats.rows_recovered,
+                rows_lost: stats.rows_lost,
+            }));
+        }
+        let _ = std::fs::remove_file(&db_path);
+
+        if let Some(manager) = backups {
+            for backup in manager.list_backups()? {
+                if !manager.verify_backup(&backup.path).unwrap_or(false) {
+                    continue;
+                }
+                if manager.restore_backup(&backup.path, &db_path).is_ok()
+                    && salvage::is_healthy(&db_path)
+                    && let Ok(wallet) = Self::open(folder)
+                {
+                    return Ok((wallet, OpenRecovery::RestoredFromBackup {
+                        corrupt_copy,
+                        backup: backup.path,
+                    }));
+                }
+                let _ = std::fs::remove_file(&db_path);
+            }
+        }
+
+        // Nothing worked: put the original back so no later attempt starts
+        // from an empty folder.
+        std::fs::rename(&corrupt_copy, &db_path)?;
+        Err(WalletError::DatabaseError(
+            "Database is damaged and could not be salvaged or restored from a backup".to_string()
+        ))
+    }
+
+    /// A salvaged file is only usable when it still carries a properties row
+    /// and something to verify the password against (v6 crypto record or the
+    /// legacy root item).
+    fn is_unlockable(db_path: &Path) -> bool {
+        let Ok(db) = Database::open(db_path) else { return false; };
+        let Ok(conn) = db.connection() else { return false; };
+        queries::has_properties(conn).unwrap_or(false)
+            && (matches!(queries::get_crypto_record(conn), Ok(Some(_)))
+                || matches!(queries::get_root_item_raw(conn), Ok(Some(_))))
+    }
+
     /// Create a new wallet in the specified folder. New wallets are born at the
-    /// current (v6) scheme; legacy crypto is never written.
+    /// current (v6) scheme; legacy crypto is never written. The password must
+    /// be `PASSWORD_MIN_LENGTH..=PASSWORD_MAX_LENGTH` characters and is
+    /// NFC-normalized before key derivation.
     pub fn create(folder: &Path, password: &str, lang: &str) -> Result<Self> {
+        Self::create_with_kdf(folder, password, lang, KdfParams::current())
+    }
+
+    /// `create` with the Argon2id parameters of the password key (e.g.
+    /// from [`Config::kdf_params`](crate::config::Config::kdf_params)) instead of
+    /// the defaults. Later password changes use them too.
+    pub fn create_with_kdf(folder: &Path, password: &str, lang: &str, kdf_params: KdfParams) -> Result<Self> {
+        check_kdf_params(kdf_params)?;
+        crypto::check_master_password_length(password)?;
+        let password = crypto::normalize_master_password(password);
         std::fs::create_dir_all(folder)?;
 
         let db_path = folder.join(DATABASE_FILENAME);
@@ -171,9 +444,23 @@ impl Wallet {
             fields_cache: None,
             labels_cache: None,
             last_migration_summary: None,
+            strict_validation: false,
+            auto_backup: None,
+            max_note_size: crate::NOTE_MAX_SIZE_DEFAULT,
+            password_reuse_policy: PasswordReusePolicy::default(),
+            quota: None,
+            id_generator: Box::new(RandomIdGenerator),
+            id_collisions: IdCollisionStats::default(),
+            pending_writes: 0,
+            last_write: None,
+            open_metrics: OpenMetrics::default(),
+            cache_warming: false,
+            backup_copy: None,
+            kdf_params,
+            auto_lock: None,
         };
 
-        wallet.init_new_database(password, lang)?;
+        wallet.init_new_database(&password, lang)?;
 
         Ok(wallet)
     }
@@ -183,15 +470,16 @@ impl Wallet {
     fn init_new_database(&mut self, password: &str, lang: &str) -> Result<()> {
         // Generate fresh key material and wrap the DEK under the password.
         let dek = crypto::dek::generate_dek();
-        let params = crypto::kdf::KdfParams::current();
+        let params = self.kdf_params;
         let salt = random_bytes(KDF_SALT_LEN);
         let kek = crypto::kdf::derive_kek(password.as_bytes(), &salt, params)
             .map_err(WalletError::EncryptionError)?;
         let dek_wrapped = crypto::dek::wrap_dek(&kek, &dek)
             .map_err(WalletError::EncryptionError)?;
+        let key_check = crypto::dek::key_check_value(&kek, &salt);
 
         // Hold the DEK so the root item can be encrypted under it.
-        self.unlocked = Some(Unlocked { dek: Zeroizing::new(dek) });
+        self.hold_dek(dek)?;
 
         let db_id = generate_database_id();
         let root_data = crate::utils::generate_id(32);
@@ -212,6 +500,7 @@ impl Wallet {
                 salt,
                 dek_wrapped,
             })?;
+            queries::set_key_check(conn, Some(&key_check))?;
             queries::create_item(conn, ROOT_ID, ROOT_PARENT_ID, &encrypted_root, "", true)?;
         }
 
@@ -226,60 +515,91 @@ impl Wallet {
     /// the DEK (the AEAD tag is the password verifier). For a not-yet-migrated
     /// v5 vault: verify the password against the legacy root item, then perform
     /// the one-time v5->v6 migration before returning.
+    ///
+    /// The NFC form of the password is tried first, then the form as typed,
+    /// for vaults whose password was set before normalization; such a vault
+    /// is re-wrapped under the NFC form once it unlocks.
+    ///
+    /// With a backup manager attached, a successful unlock takes an auto
+    /// backup when the newest one is older than the configured interval.
     pub fn unlock(&mut self, password: &str) -> Result<bool> {
+        let unlocked = self.unlock_with_password(password)?;
+        if unlocked {
+            self.auto_backup_on_unlock();
+        }
+        Ok(unlocked)
+    }
+
+    /// Verify the password and unlock (and migrate) the vault
+    fn unlock_with_password(&mut self, password: &str) -> Result<bool> {
         // Read all metadata up front into owned values so the immutable
         // connection borrow is fully released before any mutation/migration.
-        let (props, crypto_rec, root_blob) = {
+        let (props, crypto_rec, key_check, root_blob) = {
             let conn = self.db.as_ref()
                 .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
                 .connection()?;
             (
                 queries::get_properties(conn)?,
                 queries::get_crypto_record(conn)?,
+                queries::get_key_check(conn)?,
                 queries::get_root_item_raw(conn)?,
             )
         };
 
         self.encryption_count = legacy_encryption_count(props.as_ref().and_then(|p| p.email.as_deref()));
 
+        let forms = password_forms(password);
+        let started = Instant::now();
+        self.open_metrics.cache_warm = Duration::ZERO;
+        self.cache_warming = true;
         if let Some(rec) = crypto_rec {
             // v6 vault: derive KEK from the stored params and unwrap the DEK.
-            match self.unwrap_with_password(&rec, password) {
-                Some(dek) => {
-                    self.unlocked = Some(Unlocked { dek: Zeroizing::new(dek) });
-                    self.clear_caches();
-                    self.add_system_labels()?;
-                    Ok(true)
+            for (i, form) in forms.iter().enumerate() {
+                if let Some(dek) = self.unwrap_with_password(&rec, key_check.as_deref(), form) {
+                    self.open_metrics.root_decrypt = started.elapsed();
+                    self.unlock_with_dek(dek)?;
+                    if i > 0 {
+                        // Unlocked with the unnormalized form. Failing to
+                        // re-wrap leaves the vault as it was, so it must
+                        // not fail the unlock.
+                        let _ = self.rewrap_dek(&forms[0], &CancelToken::new());
+                    }
+                    return Ok(true);
                 }
-                None => Ok(false),
             }
+            Ok(false)
         } else {
             // Legacy v5 vault: verify the password using the full
             // candidate-key chain (the C# app effectively used several key
             // derivations over its life), then migrate with the chain that
-            // verified.
-            let mut key_chain =
-                crypto::legacy::LegacyKeyChain::new(password, self.encryption_count);
-            let create_root = match &root_blob {
-                Some(encrypted_name) => {
-                    if key_chain.decrypt(encrypted_name).is_err() {
-                        return Ok(false);
-                    }
-                    false
-                }
-                None => {
+            // verified. Legacy apps did not normalize, so the typed form
+            // goes first.
+            let mut verified = None;
+            for form in forms.iter().rev() {
+                let mut key_chain =
+                    crypto::legacy::LegacyKeyChain::new(form, self.encryption_count);
+                let ok = match &root_blob {
+                    Some(encrypted_name) => key_chain.decrypt(encrypted_name).is_ok(),
                     // Old apps could lose the root record while keeping every
                     // item and field (their login silently re-created it).
                     // Verify the password against the DATA itself; the
                     // migration then synthesizes the missing root.
-                    if !self.verify_password_against_sample(&mut key_chain)? {
-                        return Ok(false);
-                    }
-                    true
+                    None => self.verify_password_against_sample(&mut key_chain)?,
+                };
+                if ok {
+                    verified = Some(key_chain);
+                    break;
                 }
+            }
+            let Some(key_chain) = verified else {
+                return Ok(false);
             };
+            self.open_metrics.root_decrypt = started.elapsed();
+            let create_root = root_blob.is_none();
             // Password verified. Perform the one-time migration (sets the DEK).
-            self.migrate_v5_to_v6(password, key_chain, create_root)?;
+            let started = Instant::now();
+            self.migrate_v5_to_v6(&forms[0], key_chain, create_root)?;
+            self.open_metrics.migration += started.elapsed();
             self.clear_caches();
             self.add_system_labels()?;
             Ok(true)
@@ -312,6 +632,22 @@ impl Wallet {
         Ok(samples.iter().any(|blob| key_chain.decrypt(blob).is_ok()))
     }
 
+    /// Count a cache load that started at `started` as warm-up, while the
+    /// caches are being filled for the first time since unlock
+    pub(crate) fn record_cache_warm(&mut self, started: Instant) {
+        if self.cache_warming {
+            self.open_metrics.cache_warm += started.elapsed();
+            self.cache_warming = self.items_cache.is_none() || self.fields_cache.is_none();
+        }
+    }
+
+    /// Phase timings of the last `open` and `unlock` of this wallet. The
+    /// cache warm-up is measured when the first listing after unlock
+    /// decrypts items and fields.
+    pub fn last_open_metrics(&self) -> OpenMetrics {
+        self.open_metrics
+    }
+
     /// Outcome of the v5->v6 migration when this wallet session performed
     /// one; `None` for vaults that were already v6 at unlock.
     pub fn last_migration_summary(&self) -> Option<&MigrationSummary> {
@@ -326,6 +662,136 @@ impl Wallet {
         queries::quarantine_count(conn)
     }
 
+    /// Active records whose blob does not decrypt under the vault key. The
+    /// item and field listings skip these, so one bad row cannot hide the
+    /// rest of the vault; this lists them so the app can offer
+    /// [`Wallet::quarantine_undecryptable`].
+    pub fn get_undecryptable_records(&self) -> Result<Vec<UndecryptableRecord>> {
+        self.ensure_unlocked()?;
+        let (items, fields) = {
+            let conn = self.db.as_ref()
+                .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+                .connection()?;
+            (queries::get_all_items_raw(conn)?, queries::get_all_fields_raw(conn)?)
+        };
+
+        let mut out = Vec::new();
+        for raw in items {
+            if raw.item_id == ROOT_ID || raw.name_encrypted.is_empty() {
+                continue;
+            }
+            if let Err(e) = self.dec_value(&raw.name_encrypted) {
+                out.push(UndecryptableRecord {
+                    kind: RecordKind::Item,
+                    item_id: raw.item_id,
+                    field_id: None,
+                    error: e.to_string(),
+                });
+            }
+        }
+        for raw in fields {
+            if raw.value_encrypted.is_empty() {
+                continue;
+            }
+            if let Err(e) = self.dec_field_value(&raw.value_encrypted) {
+                // Sealed under the key of a folder that is not unlocked
+                if self.protecting_folder(&raw.item_id)?.is_some_and(|f| self.folder_key(&f).is_err()) {
+                    continue;
+                }
+                out.push(UndecryptableRecord {
+                    kind: RecordKind::Field,
+                    item_id: raw.item_id,
+                    field_id: Some(raw.field_id),
+                    error: e.to_string(),
+                });
+            }
+        }
+        Ok(out)
+    }
+
+    /// Move every undecryptable active record into `nswallet_quarantine`
+    /// (blob preserved verbatim) and out of the live tables. An item takes
+    /// its active fields with it, as in the v5->v6 migration. Returns the
+    /// number of records quarantined.
+    pub fn quarantine_undecryptable(&mut self) -> Result<u32> {
+        let records = self.get_undecryptable_records()?;
+        if records.is_empty() {
+            return Ok(0);
+        }
+
+        let db = self.db.as_mut()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?;
+        db.begin_transaction()?;
+
+        let mut count = 0u32;
+        let pass = (|| -> Result<()> {
+            let conn = db.connection()?;
+            queries::ensure_quarantine_table(conn)?;
+            let fields = queries::get_all_fields_raw(conn)?;
+            for record in &records {
+                match record.kind {
+                    RecordKind::Item => {
+                        for f in fields.iter().filter(|f| f.item_id == record.item_id) {
+                            queries::quarantine_field(conn, &f.item_id, &f.field_id, Some(&f.value_encrypted))?;
+                            queries::hard_delete_field(conn, &f.item_id, &f.field_id)?;
+                            count += 1;
+                        }
+                        let blob: Option<Vec<u8>> = conn.query_row(
+                            "SELECT name FROM nswallet_items WHERE item_id = ?",
+                            [&record.item_id],
+                            |row| row.get(0),
+                        )?;
+                        queries::quarantine_item(conn, &record.item_id, blob.as_deref())?;
+                        queries::hard_delete_item(conn, &record.item_id)?;
+                        count += 1;
+                    }
+                    RecordKind::Field => {
+                        let field_id = record.field_id.as_deref().unwrap_or("");
+                        // Already moved along with its undecryptable item
+                        if !queries::field_row_exists(conn, &record.item_id, field_id)? {
+                            continue;
+                        }
+                        let blob = fields.iter()
+                            .find(|f| f.item_id == record.item_id && f.field_id == field_id)
+                            .map(|f| f.value_encrypted.as_slice());
+                        queries::quarantine_field(conn, &record.item_id, field_id, blob)?;
+                        queries::hard_delete_field(conn, &record.item_id, field_id)?;
+                        count += 1;
+                    }
+                }
+            }
+            Ok(())
+        })();
+
+        match pass {
+            Ok(()) => db.commit_transaction()?,
+            Err(e) => {
+                db.rollback_transaction()?;
+                return Err(e);
+            }
+        }
+
+        let _ = self.db.as_ref().unwrap().checkpoint();
+        self.clear_caches();
+        Ok(count)
+    }
+
+    /// Permanently delete every quarantined record. Irreversible: use only
+    /// once recovery with every candidate password has been given up.
+    /// Returns the number of records purged.
+    pub fn purge_quarantine(&mut self) -> Result<u32> {
+        self.ensure_unlocked()?;
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        let count = queries::quarantine_count(conn)?;
+        if count > 0 {
+            conn.execute("DELETE FROM nswallet_quarantine", [])?;
+            conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+        }
+        Ok(count)
+    }
+
     /// Try to recover quarantined records with `password` (which may differ
     /// from the vault's master password: old-app flows could re-create the
     /// root under a new password while the data stayed under the previous
@@ -355,6 +821,7 @@ impl Wallet {
             recovered_fields: 0,
             waiting_for_parent: 0,
             remaining: 0,
+            ios_workaround: 0,
         };
         if rows.is_empty() {
             return Ok(result);
@@ -379,6 +846,7 @@ impl Wallet {
                 };
                 let new_blob = crypto::aead::seal(&dek, plaintext.as_bytes())
                     .map_err(WalletError::EncryptionError)?;
+                let ios = u32::from(chain.used_ios_variant());
 
                 if row.record_type == "item" {
                     if queries::item_row_exists(conn, &row.item_id)? {
@@ -389,6 +857,7 @@ impl Wallet {
                         queries::restore_quarantined_item(conn, row, &new_blob)?;
                         queries::delete_quarantine_row(conn, row.rowid)?;
                         result.recovered_items += 1;
+                        result.ios_workaround += ios;
                     }
                 } else {
                     let field_id = row.field_id.as_deref().unwrap_or("");
@@ -400,6 +869,7 @@ impl Wallet {
                         queries::restore_quarantined_field(conn, row, &new_blob)?;
                         queries::delete_quarantine_row(conn, row.rowid)?;
                         result.recovered_fields += 1;
+                        result.ios_workaround += ios;
                     }
                 }
             }
@@ -419,31 +889,79 @@ impl Wallet {
         Ok(result)
     }
 
-    /// Derive the KEK from `password` using the record's stored params and try
-    /// to unwrap the DEK. Returns the DEK on success, `None` on wrong password.
-    fn unwrap_with_password(&self, rec: &CryptoRecord, password: &str) -> Option<[u8; DEK_LEN]> {
+    /// Derive the KEK from `password` using the record's stored params.
+    fn password_kek(&self, rec: &CryptoRecord, password: &str) -> Option<Zeroizing<[u8; crypto::aead::KEY_LEN]>> {
         let params = crypto::kdf::KdfParams {
             m_cost_kib: rec.m_cost_kib,
             t_cost: rec.t_cost,
             p_cost: rec.p_cost,
         };
-        let kek = crypto::kdf::derive_kek(password.as_bytes(), &rec.salt, params).ok()?;
+        crypto::kdf::derive_kek(password.as_bytes(), &rec.salt, params).ok().map(Zeroizing::new)
+    }
+
+    /// Derive the KEK from `password` and try to unwrap the DEK. A stored key
+    /// check value rejects a wrong password before the unwrap. Returns the
+    /// DEK on success, `None` on wrong password.
+    fn unwrap_with_password(&self, rec: &CryptoRecord, key_check: Option<&[u8]>, password: &str) -> Option<[u8; DEK_LEN]> {
+        let kek = self.password_kek(rec, password)?;
+        if let Some(kcv) = key_check
+            && crypto::dek::verify_key_check(&kek, &rec.salt, kcv) == Some(false) {
+                return None;
+            }
         crypto::dek::unwrap_dek(&kek, &rec.dek_wrapped).ok()
     }
 
+    /// Hold an unwrapped DEK and finish unlocking (caches, system labels).
+    pub(crate) fn unlock_with_dek(&mut self, dek: [u8; DEK_LEN]) -> Result<()> {
+        self.hold_dek(dek)?;
+        self.clear_caches();
+        self.add_system_labels()
+    }
+
+    /// Hold the DEK and set up the metadata sealing that needs it
+    fn hold_dek(&mut self, dek: [u8; DEK_LEN]) -> Result<()> {
+        self.unlocked = Some(Unlocked::new(dek));
+        if let Err(e) = self.install_meta_sealing() {
+            self.lock();
+            return Err(e);
+        }
+        Ok(())
+    }
+
     /// Lock the wallet (zeroizes the in-memory DEK).
     pub fn lock(&mut self) {
+        self.remove_meta_sealing();
         self.unlocked = None;
         self.clear_caches();
     }
 
+    /// Lock the wallet once it has been `idle` without calls that need it
+    /// unlocked; `None` turns auto-lock off. The host calls `lock_if_idle`
+    /// from a timer (see [`Config::auto_lock`](crate::config::Config::auto_lock)).
+    pub fn set_auto_lock(&mut self, idle: Option<Duration>) {
+        self.auto_lock = idle;
+    }
+
+    /// Lock the wallet if auto-lock is on and it has been idle at least
+    /// that long. Returns true if it locked now.
+    pub fn lock_if_idle(&mut self) -> bool {
+        let expired = match (&self.unlocked, self.auto_lock) {
+            (Some(unlocked), Some(limit)) => unlocked.idle() >= limit,
+            _ => false,
+        };
+        if expired {
+            self.lock();
+        }
+        expired
+    }
+
     /// Check if the wallet is unlocked
     pub fn is_unlocked(&self) -> bool {
         self.unlocked.is_some()
     }
 
     /// Borrow the in-memory DEK, or error if locked.
-    fn dek(&self) -> Result<&[u8; DEK_LEN]> {
+    pub(crate) fn dek(&self) -> Result<&[u8; DEK_LEN]> {
         self.unlocked.as_ref().map(|u| &*u.dek).ok_or(WalletError::Locked)
     }
 
@@ -455,9 +973,34 @@ impl Wallet {
 
     /// AEAD-decrypt a stored v6 blob under the DEK.
     pub(crate) fn dec_value(&self, blob: &[u8]) -> Result<String> {
-        let pt = crypto::aead::open(self.dek()?, blob).map_err(WalletError::DecryptionError)?;
-        String::from_utf8(pt)
-            .map_err(|e| WalletError::DecryptionError(format!("invalid UTF-8: {e}")))
+        let pt = crypto::aead::open(self.dek()?, blob)?;
+        String::from_utf8(pt).map_err(|_| crypto::DecryptError::BadUtf8.into())
+    }
+
+    /// Decrypt a field value: under the DEK, or the key of an unlocked
+    /// PIN-protected folder. Fails with the DEK error when neither opens it.
+    pub(crate) fn dec_field_value(&self, blob: &[u8]) -> Result<String> {
+        let err = match self.dec_value(blob) {
+            Ok(v) => return Ok(v),
+            Err(e) => e,
+        };
+        let keys = self.unlocked.as_ref().map(|u| u.folder_keys.values());
+        for key in keys.into_iter().flatten() {
+            if let Ok(pt) = crypto::aead::open(key, blob) {
+                return String::from_utf8(pt).map_err(|_| crypto::DecryptError::BadUtf8.into());
+            }
+        }
+        Err(err)
+    }
+
+    /// Encrypt a field value of `item_id`: under the key of the folder
+    /// protecting it (which must be unlocked), else under the DEK.
+    pub(crate) fn enc_field_value(&self, item_id: &str, plaintext: &str) -> Result<Vec<u8>> {
+        match self.protecting_folder(item_id)? {
+            Some(folder_id) => crypto::aead::seal(self.folder_key(&folder_id)?, plaintext.as_bytes())
+                .map_err(WalletError::EncryptionError),
+            None => self.enc_value(plaintext),
+        }
     }
 
     /// Close the wallet
@@ -480,32 +1023,74 @@ impl Wallet {
         &self.folder
     }
 
-    /// Check a password without unlocking or migrating. Works on both v6 vaults
-    /// (verify via DEK unwrap) and not-yet-migrated v5 vaults (verify via the
-    /// legacy root item). Read-only: never mutates the database.
+    /// Number of writes since the wallet was opened or last saved
+    pub fn pending_writes(&self) -> u64 {
+        self.pending_writes
+    }
+
+    /// Whether writes were made since the wallet was opened or last saved
+    pub fn is_dirty(&self) -> bool {
+        self.pending_writes > 0
+    }
+
+    /// Time of the last write made through this wallet in this session
+    pub fn last_write_time(&self) -> Option<DateTime<Utc>> {
+        self.last_write
+    }
+
+    /// Flush the WAL into the database file and reset the pending-write
+    /// count. Every write is already committed when its call returns; after
+    /// `save` the main file alone holds it, so it can be copied or synced.
+    pub fn save(&mut self) -> Result<()> {
+        self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .checkpoint()?;
+        self.pending_writes = 0;
+        Ok(())
+    }
+
+    /// Check a password without unlocking or migrating. Works on v6 vaults
+    /// (verify via the stored key check value, compared in constant time, or
+    /// via DEK unwrap for vaults without one) and not-yet-migrated v5 vaults
+    /// (verify via the legacy root item). Read-only: never mutates the
+    /// database.
     pub fn check_password(&self, password: &str) -> Result<bool> {
-        let (props, crypto_rec, root_blob) = {
+        let (props, crypto_rec, key_check, root_blob) = {
             let conn = self.db.as_ref()
                 .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
                 .connection()?;
             (
                 queries::get_properties(conn)?,
                 queries::get_crypto_record(conn)?,
+                queries::get_key_check(conn)?,
                 queries::get_root_item_raw(conn)?,
             )
         };
 
-        if let Some(rec) = crypto_rec {
-            Ok(self.unwrap_with_password(&rec, password).is_some())
-        } else {
-            let encryption_count = legacy_encryption_count(props.as_ref().and_then(|p| p.email.as_deref()));
-            let mut key_chain = crypto::legacy::LegacyKeyChain::new(password, encryption_count);
-            match &root_blob {
-                Some(encrypted_name) => Ok(key_chain.decrypt(encrypted_name).is_ok()),
-                // Rootless legacy vault: verify against the data, like unlock.
-                None => self.verify_password_against_sample(&mut key_chain),
+        // Both password forms, as in unlock
+        for form in password_forms(password) {
+            let verified = if let Some(rec) = &crypto_rec {
+                let Some(kek) = self.password_kek(rec, &form) else {
+                    continue;
+                };
+                match key_check.as_deref().and_then(|kcv| crypto::dek::verify_key_check(&kek, &rec.salt, kcv)) {
+                    Some(verified) => verified,
+                    None => crypto::dek::unwrap_dek(&kek, &rec.dek_wrapped).is_ok(),
+                }
+            } else {
+                let encryption_count = legacy_encryption_count(props.as_ref().and_then(|p| p.email.as_deref()));
+                let mut key_chain = crypto::legacy::LegacyKeyChain::new(&form, encryption_count);
+                match &root_blob {
+                    Some(encrypted_name) => key_chain.decrypt(encrypted_name).is_ok(),
+                    // Rootless legacy vault: verify against the data, like unlock.
+                    None => self.verify_password_against_sample(&mut key_chain)?,
+                }
+            };
+            if verified {
+                return Ok(true);
             }
         }
+        Ok(false)
     }
 
     /// Get database properties
@@ -529,22 +1114,64 @@ impl Wallet {
         })
     }
 
+    /// Translations in the wallet's language, or English when the stored
+    /// language is unknown or cannot be read
+    pub(crate) fn translations(&self) -> Result<Translations> {
+        let lang = self.get_properties().map(|p| p.lang).unwrap_or_else(|_| "en".to_string());
+        let mut translations = Translations::new()?;
+        if translations.set_language(&lang).is_err() {
+            translations.set_language("en")?;
+        }
+        Ok(translations)
+    }
+
     /// Change the wallet password.
     ///
     /// Under the v6 scheme this only re-wraps the DEK: a fresh salt + KEK are
     /// derived from `new_password` and the same DEK is re-wrapped. The encrypted
     /// item names and field values are NOT touched (they are under the DEK, not
     /// the password), so this is fast and leaves all data blobs byte-identical.
+    ///
+    /// The new password must be `PASSWORD_MIN_LENGTH..=PASSWORD_MAX_LENGTH`
+    /// characters and is NFC-normalized, as in `create`.
     pub fn change_password(&mut self, new_password: &str) -> Result<bool> {
         self.ensure_unlocked()?;
+        crypto::check_master_password_length(new_password)?;
+        self.rewrap_dek(&crypto::normalize_master_password(new_password), &CancelToken::new())
+    }
+
+    /// `change_password` that stops with `Cancelled` once `cancel` trips.
+    /// The token is checked before and after the key derivation, the slow
+    /// step; once the new key is written the change is complete. A
+    /// cancelled change leaves the old password in place.
+    pub fn change_password_with_cancel(&mut self, new_password: &str, cancel: &CancelToken) -> Result<bool> {
+        self.ensure_unlocked()?;
+        crypto::check_master_password_length(new_password)?;
+        self.rewrap_dek(&crypto::normalize_master_password(new_password), cancel)
+    }
+
+    /// Argon2id parameters for the password key from the next
+    /// `change_password` on (e.g. from
+    /// [`Config::kdf_params`](crate::config::Config::kdf_params)). The current key
+    /// keeps the parameters it was derived with until then.
+    pub fn set_kdf_params(&mut self, params: KdfParams) -> Result<()> {
+        check_kdf_params(params)?;
+        self.kdf_params = params;
+        Ok(())
+    }
 
+    /// Wrap the DEK under `new_password` as given, with a fresh salt
+    fn rewrap_dek(&mut self, new_password: &str, cancel: &CancelToken) -> Result<bool> {
+        cancel.check()?;
         let dek = *self.dek()?;
-        let params = crypto::kdf::KdfParams::current();
+        let params = self.kdf_params;
         let salt = random_bytes(KDF_SALT_LEN);
         let kek = crypto::kdf::derive_kek(new_password.as_bytes(), &salt, params)
             .map_err(WalletError::EncryptionError)?;
+        cancel.check()?;
         let dek_wrapped = crypto::dek::wrap_dek(&kek, &dek)
             .map_err(WalletError::EncryptionError)?;
+        let key_check = crypto::dek::key_check_value(&kek, &salt);
 
         let conn = self.db.as_ref()
             .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
@@ -558,15 +1185,15 @@ impl Wallet {
             salt,
             dek_wrapped,
         })?;
+        queries::set_key_check(conn, Some(&key_check))?;
 
         Ok(true)
     }
 
     /// Ensure wallet is unlocked
     pub(crate) fn ensure_unlocked(&self) -> Result<()> {
-        if self.unlocked.is_none() {
-            return Err(WalletError::Locked);
-        }
+        let unlocked = self.unlocked.as_ref().ok_or(WalletError::Locked)?;
+        unlocked.touch();
         Ok(())
     }
 
@@ -578,14 +1205,14 @@ impl Wallet {
         let dst = self.folder.join(PRE_V6_BACKUP_FILENAME);
         if dst.exists() {
             std::fs::remove_file(&dst).map_err(|e| {
-                WalletError::BackupError(format!("Failed to remove stale pre-v6 backup: {e}"))
+                WalletError::backup("Failed to remove stale pre-v6 backup", e)
             })?;
         }
         let conn = self.db.as_ref()
             .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
             .connection()?;
         conn.execute("VACUUM INTO ?", [dst.to_string_lossy().to_string()])
-            .map_err(|e| WalletError::BackupError(format!("Pre-v6 snapshot (VACUUM INTO) failed: {e}")))?;
+            .map_err(|e| WalletError::backup("Pre-v6 snapshot (VACUUM INTO) failed", e))?;
         Ok(())
     }
 
@@ -624,12 +1251,13 @@ impl Wallet {
 
         // 2. Fresh key material; wrap the DEK under the password.
         let dek = crypto::dek::generate_dek();
-        let params = crypto::kdf::KdfParams::current();
+        let params = self.kdf_params;
         let salt = random_bytes(KDF_SALT_LEN);
         let kek = crypto::kdf::derive_kek(password.as_bytes(), &salt, params)
             .map_err(WalletError::EncryptionError)?;
         let dek_wrapped = crypto::dek::wrap_dek(&kek, &dek)
             .map_err(WalletError::EncryptionError)?;
+        let key_check = crypto::dek::key_check_value(&kek, &salt);
 
         // 3. Read every blob up front (owned), releasing the conn borrow.
         let (item_blobs, field_blobs) = {
@@ -650,6 +1278,7 @@ impl Wallet {
             mode_no_chain: 0,
             mode_md5_chain: 0,
             mode_raw_password: 0,
+            ios_workaround: 0,
             root_created: create_root,
             duration_ms: 0,
         };
@@ -667,12 +1296,15 @@ impl Wallet {
 
             // Tallies which derivation decrypted each record (forensics).
             let count_mode = |summary: &mut MigrationSummary,
-                                  mode: crypto::legacy::LegacyKeyMode| {
-                match mode {
+                                  key: &crypto::legacy::LegacyKeyChain| {
+                match key.preferred_mode() {
                     crypto::legacy::LegacyKeyMode::NoChain => summary.mode_no_chain += 1,
                     crypto::legacy::LegacyKeyMode::Md5Chain => summary.mode_md5_chain += 1,
                     crypto::legacy::LegacyKeyMode::RawPassword => summary.mode_raw_password += 1,
                 }
+                if key.used_ios_variant() {
+                    summary.ios_workaround += 1;
+                }
             };
 
             for (item_id, blob, deleted) in &item_blobs {
@@ -684,7 +1316,7 @@ impl Wallet {
                     Some([]) => Some(String::new()),
                     Some(bytes) => match legacy_key.decrypt(bytes) {
                         Ok(plaintext) => {
-                            count_mode(&mut summary, legacy_key.preferred_mode());
+                            count_mode(&mut summary, &legacy_key);
                             Some(plaintext)
                         }
                         Err(_) => None,
@@ -700,9 +1332,7 @@ impl Wallet {
                     None if item_id.as_str() == ROOT_ID => {
                         // Cannot happen (the chain verified the root before
                         // migration), kept as a hard abort out of caution.
-                        return Err(WalletError::DecryptionError(
-                            "root item undecryptable".to_string(),
-                        ));
+                        return Err(crypto::DecryptError::WrongKeyOrCorrupt.into());
                     }
                     None => {
                         if *deleted {
@@ -734,7 +1364,7 @@ impl Wallet {
                     Some([]) => Some(String::new()),
                     Some(bytes) => match legacy_key.decrypt(bytes) {
                         Ok(plaintext) => {
-                            count_mode(&mut summary, legacy_key.preferred_mode());
+                            count_mode(&mut summary, &legacy_key);
                             Some(plaintext)
                         }
                         Err(_) => None,
@@ -781,6 +1411,7 @@ impl Wallet {
                 salt: salt.clone(),
                 dek_wrapped: dek_wrapped.clone(),
             })?;
+            queries::set_key_check(conn, Some(&key_check))?;
             queries::set_db_version_no_checkpoint(conn, DB_VERSION)?;
             Ok(())
         })();
@@ -805,8 +1436,7 @@ impl Wallet {
         summary.key_mode = legacy_key.preferred_mode().as_str().to_string();
         summary.duration_ms = started.elapsed().as_millis() as u64;
         self.last_migration_summary = Some(summary);
-        self.unlocked = Some(Unlocked { dek: Zeroizing::new(dek) });
-        Ok(())
+        self.hold_dek(dek)
     }
 
     /// Get the database path
@@ -829,6 +1459,79 @@ impl Wallet {
         Ok(result)
     }
 
+    /// Selective `compact`: purge only the chosen kinds of deleted records,
+    /// optionally only those deleted longer ago than `options.older_than`,
+    /// so recent trash stays recoverable.
+    pub fn compact_with(&mut self, options: &CompactOptions) -> Result<CompactResult> {
+        self.ensure_unlocked()?;
+        let cutoff = match options.older_than {
+            Some(age) => Some(chrono::Utc::now() - chrono::TimeDelta::from_std(age)
+                .map_err(|e| WalletError::InvalidOperation(e.to_string()))?),
+            None => None,
+        };
+
+        let conn = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+
+        let (items, fields, labels) = queries::purge_deleted_filtered(
+            conn,
+            options.purge_items,
+            options.purge_fields,
+            options.purge_labels,
+            cutoff.as_ref(),
+        )?;
+
+        self.clear_caches();
+        Ok(CompactResult { items, fields, labels })
+    }
+
+    /// Turn SQLite `secure_delete` on or off for the open database. While on,
+    /// every delete and update zeroes the freed space instead of leaving old
+    /// ciphertext in free pages. Not persisted: re-apply after each open.
+    pub fn set_secure_delete(&self, enabled: bool) -> Result<()> {
+        self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .set_secure_delete(enabled)
+    }
+
+    /// Whether SQLite `secure_delete` is currently on
+    pub fn secure_delete(&self) -> Result<bool> {
+        self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .secure_delete()
+    }
+
+    /// Like `compact`, but first overwrites the purged payloads with zeros
+    /// and runs with `secure_delete` on, then vacuums and truncates the WAL.
+    /// Returns (purged_items_count, purged_fields_count).
+    ///
+    /// Guarantee: afterwards the purged ciphertexts are absent from the
+    /// database file and its WAL. Copies outside SQLite's control are not
+    /// covered: backups, filesystem snapshots, and flash storage that remaps
+    /// blocks on write (SSD wear leveling) may still hold old data.
+    pub fn secure_compact(&mut self) -> Result<(u32, u32)> {
+        self.ensure_unlocked()?;
+
+        let db = self.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?;
+        let was_secure = db.secure_delete()?;
+        db.set_secure_delete(true)?;
+
+        let result = (|| -> Result<(u32, u32)> {
+            let conn = db.connection()?;
+            queries::overwrite_deleted(conn)?;
+            db.checkpoint()?;
+            queries::purge_deleted(conn)
+        })();
+
+        let restored = db.set_secure_delete(was_secure);
+        self.clear_caches();
+        let result = result?;
+        restored?;
+        Ok(result)
+    }
+
     /// Get database statistics (counts of items, fields, labels, deleted records, file size)
     pub fn get_database_stats(&self) -> Result<queries::DatabaseStats> {
         let conn = self.db.as_ref()
@@ -837,15 +1540,29 @@ impl Wallet {
 
         let mut stats = queries::get_database_stats(conn)?;
 
-        // Set file size from filesystem metadata
+        // Set file sizes from filesystem metadata
         let db_path = self.database_path();
         if let Ok(metadata) = std::fs::metadata(&db_path) {
             stats.file_size_bytes = metadata.len();
         }
+        let mut wal_path = db_path.into_os_string();
+        wal_path.push("-wal");
+        if let Ok(metadata) = std::fs::metadata(&wal_path) {
+            stats.wal_size_bytes = metadata.len();
+        }
+        // compact truncates the WAL; the estimate never exceeds what is on disk
+        stats.reclaimable_bytes = (stats.reclaimable_bytes + stats.wal_size_bytes)
+            .min(stats.file_size_bytes + stats.wal_size_bytes);
 
         Ok(stats)
     }
 
+    /// Estimated bytes `compact` would free on disk, for prompts like
+    /// "Compact now to free 4.2 MB" (see `utils::format_size`)
+    pub fn estimate_compact_savings(&self) -> Result<u64> {
+        Ok(self.get_database_stats()?.reclaimable_bytes)
+    }
+
     /// Get a reference to the database for backup operations
     pub fn database(&self) -> Result<&Database> {
         self.db.as_ref().ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))
@@ -870,6 +1587,96 @@ pub(crate) mod tests {
         (wallet, temp_dir)
     }
 
+    #[test]
+    fn test_last_open_metrics() {
+        let (mut wallet, temp) = create_test_wallet();
+        let item_id = wallet.add_item("Item", "document", false, None).unwrap();
+        wallet.add_field(&item_id, "NOTE", "note", None).unwrap();
+        wallet.close();
+
+        let mut wallet = Wallet::open(temp.path()).unwrap();
+        assert_eq!(wallet.last_open_metrics().root_decrypt, Duration::ZERO);
+        assert!(wallet.unlock("TestPassword123").unwrap());
+        assert!(wallet.last_open_metrics().root_decrypt > Duration::ZERO);
+
+        wallet.get_items().unwrap();
+        wallet.get_fields().unwrap();
+        let warm = wallet.last_open_metrics().cache_warm;
+        assert!(warm > Duration::ZERO);
+
+        // Later reloads are not warm-up
+        wallet.clear_caches();
+        wallet.get_items().unwrap();
+        assert_eq!(wallet.last_open_metrics().cache_warm, warm);
+    }
+
+    #[test]
+    fn test_key_check_value() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let stored = {
+            let conn = wallet.database().unwrap().connection().unwrap();
+            queries::get_key_check(conn).unwrap().expect("new wallets store a key check value")
+        };
+        assert!(wallet.check_password("TestPassword123").unwrap());
+        assert!(!wallet.check_password("WrongPassword").unwrap());
+
+        // Changing the password replaces it
+        wallet.change_password("NewPassword456").unwrap();
+        {
+            let conn = wallet.database().unwrap().connection().unwrap();
+            assert_ne!(queries::get_key_check(conn).unwrap().unwrap(), stored);
+        }
+        assert!(wallet.check_password("NewPassword456").unwrap());
+        assert!(!wallet.check_password("TestPassword123").unwrap());
+
+        // Without one, the DEK unwrap still verifies the password
+        {
+            let conn = wallet.database().unwrap().connection().unwrap();
+            queries::set_key_check(conn, None).unwrap();
+        }
+        assert!(wallet.check_password("NewPassword456").unwrap());
+        wallet.lock();
+        assert!(!wallet.unlock("TestPassword123").unwrap());
+        assert!(wallet.unlock("NewPassword456").unwrap());
+    }
+
+    #[test]
+    fn test_password_normalization_and_length() {
+        let temp = TempDir::new().unwrap();
+        let nfd = "Cafe\u{301}Secret";
+        let nfc = "Caf\u{e9}Secret";
+        let mut wallet = Wallet::create(temp.path(), nfd, "en").unwrap();
+        wallet.lock();
+        assert!(wallet.unlock(nfc).unwrap());
+        assert!(wallet.check_password(nfd).unwrap());
+
+        // A vault wrapped under the typed (decomposed) form still opens and
+        // is re-wrapped under the NFC form
+        wallet.rewrap_dek(nfd, &CancelToken::new()).unwrap();
+        wallet.lock();
+        assert!(!wallet.check_password("CafeSecret").unwrap());
+        assert!(wallet.unlock(nfd).unwrap());
+        wallet.lock();
+        assert!(wallet.unlock(nfc).unwrap());
+
+        assert!(matches!(
+            wallet.change_password("ab"),
+            Err(WalletError::PasswordTooShort { len: 2, min: 3 })
+        ));
+        assert!(matches!(
+            wallet.change_password(&"x".repeat(33)),
+            Err(WalletError::PasswordTooLong { len: 33, max: 32 })
+        ));
+        // Counted in characters, not bytes
+        wallet.change_password(&"\u{e9}".repeat(32)).unwrap();
+
+        let other = TempDir::new().unwrap();
+        assert!(matches!(
+            Wallet::create(other.path(), "ab", "en"),
+            Err(WalletError::PasswordTooShort { .. })
+        ));
+    }
+
     #[test]
     fn test_create_and_unlock() {
         let (mut wallet, _temp) = create_test_wallet();
@@ -879,6 +1686,23 @@ pub(crate) mod tests {
         assert!(wallet.is_unlocked());
     }
 
+    #[test]
+    fn test_pending_writes_and_save() {
+        let (mut wallet, _temp) = create_test_wallet();
+        assert!(!wallet.is_dirty());
+        assert!(wallet.last_write_time().is_none());
+
+        let item_id = wallet.add_item("Item", "document", false, None).unwrap();
+        wallet.add_field(&item_id, "MAIL", "a@b.c", None).unwrap();
+        assert_eq!(wallet.pending_writes(), 2);
+        assert!(wallet.is_dirty());
+        let written = wallet.last_write_time().unwrap();
+
+        wallet.save().unwrap();
+        assert_eq!(wallet.pending_writes(), 0);
+        assert_eq!(wallet.last_write_time(), Some(written));
+    }
+
     #[test]
     fn test_open_migrates_old_database() {
         // Simulate the "imported v4 backup" scenario: drop an old-version
@@ -981,6 +1805,61 @@ pub(crate) mod tests {
         assert_eq!(fields[0].value, "secret123");
     }
 
+    #[test]
+    fn test_change_password_cancelled() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        assert!(matches!(wallet.change_password_with_cancel("NewPassword456", &cancel), Err(WalletError::Cancelled)));
+        assert!(wallet.change_password_with_cancel("NewPassword456", &CancelToken::new()).unwrap());
+
+        let expired = CancelToken::with_timeout(Duration::ZERO);
+        assert!(wallet.change_password_with_cancel("Third789", &expired).is_err());
+        wallet.lock();
+        assert!(wallet.unlock("NewPassword456").unwrap());
+    }
+
+    #[test]
+    fn test_create_with_kdf_and_set_kdf_params() {
+        let temp = TempDir::new().unwrap();
+        let light = KdfParams { m_cost_kib: 8192, t_cost: 1, p_cost: 1 };
+        let invalid = KdfParams { m_cost_kib: 4, t_cost: 1, p_cost: 1 };
+        assert!(matches!(Wallet::create_with_kdf(temp.path(), "TestPassword123", "en", invalid),
+            Err(WalletError::ValidationError(_))));
+
+        let mut wallet = Wallet::create_with_kdf(temp.path(), "TestPassword123", "en", light).unwrap();
+        let stored = |wallet: &Wallet| {
+            let record = queries::get_crypto_record(wallet.db.as_ref().unwrap().connection().unwrap()).unwrap().unwrap();
+            (record.m_cost_kib, record.t_cost, record.p_cost)
+        };
+        assert_eq!(stored(&wallet), (8192, 1, 1));
+
+        assert!(wallet.set_kdf_params(invalid).is_err());
+        wallet.set_kdf_params(KdfParams { m_cost_kib: 16384, t_cost: 2, p_cost: 1 }).unwrap();
+        assert_eq!(stored(&wallet), (8192, 1, 1));
+        assert!(wallet.change_password("NewPassword456").unwrap());
+        assert_eq!(stored(&wallet), (16384, 2, 1));
+
+        wallet.lock();
+        assert!(wallet.unlock("NewPassword456").unwrap());
+    }
+
+    #[test]
+    fn test_lock_if_idle() {
+        let (mut wallet, _temp) = create_test_wallet();
+        assert!(!wallet.lock_if_idle());
+
+        wallet.set_auto_lock(Some(Duration::from_secs(3600)));
+        wallet.get_items().unwrap();
+        assert!(!wallet.lock_if_idle());
+
+        wallet.set_auto_lock(Some(Duration::from_millis(20)));
+        std::thread::sleep(Duration::from_millis(40));
+        assert!(wallet.lock_if_idle());
+        assert!(!wallet.is_unlocked());
+        assert!(!wallet.lock_if_idle());
+    }
+
     #[test]
     fn test_wallet_folder() {
         let (wallet, temp) = create_test_wallet();
@@ -1152,6 +2031,7 @@ pub(crate) mod tests {
         // Root item must still be readable post-migration.
         let conn = wallet.db.as_ref().unwrap().connection().unwrap();
         assert!(queries::get_crypto_record(conn).unwrap().is_some());
+        assert!(queries::get_key_check(conn).unwrap().is_some());
     }
 
     /// Fabricates a legacy (v5) vault containing real data: a folder, an item
@@ -1519,6 +2399,33 @@ pub(crate) mod tests {
         assert_eq!(fields[0].value, "legacy_secret");
     }
 
+    /// Records written under the iOS-workaround key are counted by the
+    /// migration and come out under the DEK, readable without the fallback.
+    #[test]
+    fn test_migration_counts_ios_workaround_records() {
+        use rusqlite::Connection;
+        let password = "TestPassword123";
+        let (_temp, path) = create_legacy_vault_with_data_and_email(0, "0");
+        {
+            // The count-0 key with its first byte zeroed, passed as the C# hash
+            let ios_key = "\0estPassword123TestPassword123Te";
+            let blob = crypto::legacy::encrypt("legacy_secret", password, 1, Some(ios_key)).unwrap();
+            assert!(crypto::legacy::decrypt(&blob, password, 0, None).is_ok(), "needs the fallback");
+            let conn = Connection::open(path.join(crate::DATABASE_FILENAME)).unwrap();
+            conn.execute("UPDATE nswallet_fields SET value = ?", rusqlite::params![blob]).unwrap();
+        }
+
+        let mut wallet = Wallet::open(&path).unwrap();
+        assert!(wallet.unlock(password).unwrap());
+        let summary = wallet.last_migration_summary().unwrap().clone();
+        assert_eq!(summary.ios_workaround, 1);
+        assert_eq!(summary.fields_quarantined, 0);
+
+        let conn = wallet.db.as_ref().unwrap().connection().unwrap();
+        let (_, _, blob, _) = queries::get_all_field_blobs(conn).unwrap().remove(0);
+        assert_eq!(wallet.dec_value(&blob.unwrap()).unwrap(), "legacy_secret");
+    }
+
     /// Cases 7-9: the old-app hidden root re-creation replay. Root under
     /// password B, data under password A: unlock with B succeeds and
     /// quarantines the data; recovery with a wrong password is a no-op;
@@ -1775,6 +2682,67 @@ pub(crate) mod tests {
         assert_eq!(props.lang, "en");
     }
 
+    /// Old writers declared the timestamp columns DATETIME and left others
+    /// untyped, so values keep whatever type was written: .NET ticks as
+    /// integers, ISO strings, text sort weights, numeric icons. Every raw
+    /// query must read them without failing the listing.
+    #[test]
+    fn test_legacy_column_types_are_tolerated() {
+        use crate::testsupport::WalletFixture;
+        let (dir, wallet) = WalletFixture::new("TestPassword123")
+            .item("Ticks", &[("PASS", "one")])
+            .item("Iso", &[("NOTE", "two")])
+            .deleted_item("Gone", &[("PASS", "three")])
+            .legacy(0)
+            .build_temp()
+            .unwrap();
+        drop(wallet);
+        {
+            let conn = rusqlite::Connection::open(dir.path().join(crate::DATABASE_FILENAME)).unwrap();
+            conn.execute_batch(
+                "ALTER TABLE nswallet_items RENAME TO old_items;
+                 CREATE TABLE nswallet_items (item_id CHAR(8) NOT NULL PRIMARY KEY, parent_id, name, icon,
+                     field_id, folder, create_timestamp DATETIME, change_timestamp DATETIME, deleted,
+                     color, meta, sort_weight, locked);
+                 INSERT INTO nswallet_items SELECT * FROM old_items;
+                 DROP TABLE old_items;
+                 ALTER TABLE nswallet_fields RENAME TO old_fields;
+                 CREATE TABLE nswallet_fields (item_id CHAR(8) NOT NULL, field_id CHAR(4) NOT NULL, type,
+                     value, change_timestamp DATETIME, deleted, sort_weight, meta,
+                     PRIMARY KEY (item_id, field_id));
+                 INSERT INTO nswallet_fields SELECT * FROM old_fields;
+                 DROP TABLE old_fields;
+                 UPDATE nswallet_items SET create_timestamp = 635046345000000000,
+                     change_timestamp = 635046345000000000, icon = 17, sort_weight = 'x'
+                     WHERE item_id = 'I0000001';
+                 UPDATE nswallet_items SET create_timestamp = '2013-05-20T08:15:00',
+                     change_timestamp = '2013-05-20T08:15:00.000Z', sort_weight = '3'
+                     WHERE item_id = 'I0000002';
+                 UPDATE nswallet_items SET change_timestamp = 635046345000000000 WHERE item_id = 'I0000003';
+                 UPDATE nswallet_fields SET change_timestamp = 635046345000000000, sort_weight = 'x';
+                 UPDATE nswallet_labels SET change_timestamp = 635046345000000000;",
+            ).unwrap();
+        }
+
+        let mut wallet = Wallet::open(dir.path()).unwrap();
+        assert!(wallet.unlock("TestPassword123").unwrap());
+        let expected = chrono::TimeZone::with_ymd_and_hms(&chrono::Utc, 2013, 5, 20, 8, 15, 0).unwrap();
+
+        let items = wallet.get_items().unwrap();
+        let ticks = items.iter().find(|i| i.name == "Ticks").unwrap();
+        assert_eq!(ticks.change_timestamp, expected);
+        assert_eq!(ticks.icon, "17");
+        let iso = items.iter().find(|i| i.name == "Iso").unwrap();
+        assert_eq!(iso.create_timestamp, expected);
+        assert_eq!(iso.change_timestamp, expected);
+
+        let fields = wallet.get_fields().unwrap();
+        assert_eq!(fields.len(), 2);
+        assert!(fields.iter().all(|f| f.change_timestamp == expected));
+        assert_eq!(wallet.get_deleted_items().unwrap().len(), 1);
+        assert!(!wallet.get_labels().unwrap().is_empty());
+    }
+
     #[test]
     fn test_compact_items() {
         let (mut wallet, _temp) = create_test_wallet();
@@ -1862,6 +2830,30 @@ pub(crate) mod tests {
         assert_eq!(fields[0].value, "keep@test.com");
     }
 
+    #[test]
+    fn test_estimate_compact_savings() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let big_value = "x".repeat(50 * 1024);
+        let item_id = wallet.add_item("Bulky", "document", false, None).unwrap();
+        for _ in 0..4 {
+            wallet.add_field(&item_id, "NOTE", &big_value, None).unwrap();
+        }
+        wallet.db.as_ref().unwrap().checkpoint().unwrap();
+        let before = wallet.estimate_compact_savings().unwrap();
+
+        wallet.delete_item(&item_id).unwrap();
+        wallet.db.as_ref().unwrap().checkpoint().unwrap();
+        let stats = wallet.get_database_stats().unwrap();
+        assert!(stats.page_size > 0);
+        assert!(stats.reclaimable_bytes >= before + 200 * 1024);
+        assert!(stats.reclaimable_bytes <= stats.file_size_bytes + stats.wal_size_bytes);
+
+        wallet.compact().unwrap();
+        let after = wallet.get_database_stats().unwrap();
+        assert_eq!(after.freelist_pages, 0);
+        assert!(after.reclaimable_bytes < 1024);
+    }
+
     /// Compact must VACUUM: after purging a bulky deleted record the file
     /// itself shrinks, so the freed pages (and the encrypted blobs in them)
     /// are physically gone, not just unlinked.
@@ -1895,6 +2887,36 @@ pub(crate) mod tests {
         assert!(wallet.get_items().unwrap().iter().all(|i| i.name != "Bulky"));
     }
 
+    /// Secure compact leaves no trace of the purged ciphertext in the file
+    /// or the WAL, and restores the previous secure_delete setting.
+    #[test]
+    fn test_secure_compact_wipes_ciphertext() {
+        let (mut wallet, temp) = create_test_wallet();
+        let item_id = wallet.add_item("Secret", "document", false, None).unwrap();
+        let field_id = wallet.add_field(&item_id, "NOTE", &"s".repeat(300), None).unwrap();
+        let blob: Vec<u8> = wallet.db.as_ref().unwrap().connection().unwrap()
+            .query_row(
+                "SELECT value FROM nswallet_fields WHERE item_id = ? AND field_id = ?",
+                [&item_id, &field_id],
+                |row| row.get(0),
+            )
+            .unwrap();
+
+        wallet.delete_item(&item_id).unwrap();
+        assert!(!wallet.secure_delete().unwrap());
+        let (items, fields) = wallet.secure_compact().unwrap();
+        assert_eq!((items, fields), (1, 1));
+        assert!(!wallet.secure_delete().unwrap());
+
+        for entry in std::fs::read_dir(temp.path()).unwrap() {
+            let bytes = std::fs::read(entry.unwrap().path()).unwrap();
+            assert!(!bytes.windows(blob.len()).any(|w| w == blob.as_slice()));
+        }
+
+        wallet.set_secure_delete(true).unwrap();
+        assert!(wallet.secure_delete().unwrap());
+    }
+
     #[test]
     fn test_compact_double_call_idempotent() {
         let (mut wallet, _temp) = create_test_wallet();
@@ -1910,6 +2932,76 @@ pub(crate) mod tests {
         assert_eq!(f2, 0);
     }
 
+    #[test]
+    fn test_compact_with_age_and_type_selection() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let old_item = wallet.add_item("Old", "document", false, None).unwrap();
+        wallet.add_field(&old_item, "MAIL", "old@test.com", None).unwrap();
+        let new_item = wallet.add_item("Recent", "document", false, None).unwrap();
+        wallet.add_field(&new_item, "MAIL", "new@test.com", None).unwrap();
+        let keep = wallet.add_item("Keep", "document", false, None).unwrap();
+        let old_field = wallet.add_field(&keep, "NOTE", "old note", None).unwrap();
+        let label = wallet.add_label("Custom", "icon", "text").unwrap();
+        wallet.delete_item(&old_item).unwrap();
+        wallet.delete_item(&new_item).unwrap();
+        wallet.delete_field(&keep, &old_field).unwrap();
+        wallet.delete_label(&label).unwrap();
+
+        // Backdate some deletions to 2000, in the .NET ticks and ISO `T`
+        // formats of older app generations
+        let conn = wallet.database().unwrap().connection().unwrap();
+        conn.execute(
+            "UPDATE nswallet_items SET change_timestamp = '630822816000000000' WHERE item_id = ?",
+            [&old_item],
+        ).unwrap();
+        conn.execute(
+            "UPDATE nswallet_fields SET change_timestamp = '2000-01-01T00:00:00' WHERE deleted = 1",
+            [],
+        ).unwrap();
+
+        let ninety_days = std::time::Duration::from_secs(90 * 24 * 3600);
+        let options = CompactOptions { purge_fields: false, older_than: Some(ninety_days), ..Default::default() };
+        let result = wallet.compact_with(&options).unwrap();
+        // Only the old item (with its field) goes; the recent item, the
+        // field of an active item and the recently deleted label stay
+        assert_eq!(result, CompactResult { items: 1, fields: 1, labels: 0 });
+        let deleted: Vec<String> = wallet.get_deleted_items().unwrap().into_iter().map(|i| i.item_id).collect();
+        assert_eq!(deleted, vec![new_item.clone()]);
+        assert_eq!(wallet.get_deleted_fields().unwrap().len(), 2);
+        assert_eq!(wallet.get_deleted_labels().unwrap().len(), 1);
+
+        // Purging fields keeps those of items still in the trash
+        let options = CompactOptions { purge_items: false, purge_labels: false, ..Default::default() };
+        assert_eq!(wallet.compact_with(&options).unwrap(), CompactResult { items: 0, fields: 1, labels: 0 });
+        let deleted_fields = wallet.get_deleted_fields().unwrap();
+        assert_eq!(deleted_fields.len(), 1);
+        assert_eq!(deleted_fields[0].item_id, new_item);
+
+        assert_eq!(wallet.compact_with(&CompactOptions::default()).unwrap().labels, 1);
+    }
+
+    #[test]
+    fn test_compact_with_failure_purges_nothing() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let item = wallet.add_item("Old", "document", false, None).unwrap();
+        wallet.delete_item(&item).unwrap();
+        let label = wallet.add_label("Custom", "icon", "text").unwrap();
+        wallet.delete_label(&label).unwrap();
+        {
+            let conn = wallet.database().unwrap().connection().unwrap();
+            conn.execute_batch(
+                "CREATE TEMP TRIGGER keep_labels BEFORE DELETE ON nswallet_labels BEGIN SELECT RAISE(ABORT, 'kept'); END;"
+            ).unwrap();
+        }
+
+        // The labels are purged after the items; their failure rolls the items back
+        assert!(wallet.compact_with(&CompactOptions::default()).is_err());
+        assert_eq!(wallet.get_deleted_items().unwrap().len(), 1);
+
+        wallet.database().unwrap().connection().unwrap().execute_batch("DROP TRIGGER keep_labels").unwrap();
+        assert_eq!(wallet.compact_with(&CompactOptions::default()).unwrap(), CompactResult { items: 1, fields: 0, labels: 1 });
+    }
+
     #[test]
     fn test_compact_after_cascade_delete() {
         let (mut wallet, _temp) = create_test_wallet();
@@ -1984,6 +3076,8 @@ pub(crate) mod tests {
         assert_eq!(stats.total_fields, 2);   // item1's 2 fields (item2's field cascade-deleted)
         assert_eq!(stats.deleted_items, 1);  // item2
         assert_eq!(stats.deleted_fields, 1); // item2's cascade-deleted field
+        assert_eq!(stats.empty_folders, 1);  // folder (its only child is deleted)
+        assert_eq!(stats.items_without_fields, 0);
         assert!(stats.total_labels >= 19);   // system labels
         assert!(stats.file_size_bytes > 0);
     }
@@ -2095,4 +3189,143 @@ pub(crate) mod tests {
         assert_eq!(deleted_fields.len(), 1);
         assert_eq!(deleted_fields[0].value, "deleted_secret");
     }
+
+    /// Zero out the b-tree page backing `index` so integrity_check fails
+    /// while every table row stays readable.
+    fn zero_index_page(db_path: &Path, index: &str) {
+        let (root, page_size): (i64, i64) = {
+            let conn = rusqlite::Connection::open(db_path).unwrap();
+            let root = conn.query_row(
+                "SELECT rootpage FROM sqlite_master WHERE name = ?", [index], |r| r.get(0),
+            ).unwrap();
+            let page_size = conn.query_row("PRAGMA page_size", [], |r| r.get(0)).unwrap();
+            (root, page_size)
+        };
+        let mut bytes = std::fs::read(db_path).unwrap();
+        let start = ((root - 1) * page_size) as usize;
+        bytes[start..start + page_size as usize].fill(0);
+        std::fs::write(db_path, bytes).unwrap();
+    }
+
+    #[test]
+    fn test_open_with_recovery_clean() {
+        let (wallet, temp) = create_test_wallet();
+        drop(wallet);
+        let (_wallet, outcome) = Wallet::open_with_recovery(temp.path(), None).unwrap();
+        assert_eq!(outcome, OpenRecovery::Clean);
+    }
+
+    #[test]
+    fn test_open_with_recovery_salvages_damaged_index() {
+        let (mut wallet, temp) = create_test_wallet();
+        let item_id = wallet.add_item("Bank", "document", false, None).unwrap();
+        wallet.add_field(&item_id, "NOTE", "kept", None).unwrap();
+        drop(wallet);
+
+        let db_path = temp.path().join(DATABASE_FILENAME);
+        zero_index_page(&db_path, "sqlite_autoindex_nswallet_fields_1");
+        assert!(!salvage::is_healthy(&db_path));
+
+        let (mut wallet, outcome) = Wallet::open_with_recovery(temp.path(), None).unwrap();
+        match outcome {
+            OpenRecovery::Salvaged { corrupt_copy, rows_lost, .. } => {
+                assert!(corrupt_copy.exists());
+                assert_eq!(rows_lost, 0);
+            }
+            other => panic!("expected Salvaged, got {other:?}"),
+        }
+        assert!(salvage::is_healthy(&db_path));
+        assert!(wallet.unlock("TestPassword123").unwrap());
+        let fields = wallet.get_fields_by_item(&item_id).unwrap();
+        assert_eq!(fields.len(), 1);
+        assert_eq!(fields[0].value, "kept");
+    }
+
+    #[test]
+    fn test_open_with_recovery_falls_back_to_backup() {
+        let (mut wallet, temp) = create_test_wallet();
+        wallet.add_item("Backed up", "document", false, None).unwrap();
+        let backups_dir = TempDir::new().unwrap();
+        let manager = BackupManager::new(backups_dir.path());
+        manager.create_backup(wallet.database().unwrap(), true).unwrap();
+        drop(wallet);
+
+        let db_path = temp.path().join(DATABASE_FILENAME);
+        std::fs::write(&db_path, b"this is no longer a database").unwrap();
+
+        let (mut wallet, outcome) = Wallet::open_with_recovery(temp.path(), Some(&manager)).unwrap();
+        assert!(matches!(outcome, OpenRecovery::RestoredFromBackup { .. }));
+        assert!(wallet.unlock("TestPassword123").unwrap());
+        assert!(wallet.get_items().unwrap().iter().any(|i| i.name == "Backed up"));
+    }
+
+    #[test]
+    fn test_open_with_recovery_unrecoverable_keeps_original() {
+        let (wallet, temp) = create_test_wallet();
+        drop(wallet);
+
+        let db_path = temp.path().join(DATABASE_FILENAME);
+        std::fs::write(&db_path, b"garbage").unwrap();
+
+        assert!(Wallet::open_with_recovery(temp.path(), None).is_err());
+        assert_eq!(std::fs::read(&db_path).unwrap(), b"garbage");
+    }
+
+    #[test]
+    fn test_undecryptable_records_are_skipped_and_listed() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let good = wallet.add_item("Good", "document", false, None).unwrap();
+        let bad = wallet.add_item("Bad", "document", false, None).unwrap();
+        wallet.add_field(&good, "NOTE", "fine", None).unwrap();
+        let bad_field = wallet.add_field(&good, "PASS", "secret", None).unwrap();
+        {
+            let conn = wallet.db.as_ref().unwrap().connection().unwrap();
+            conn.execute("UPDATE nswallet_items SET name = ? WHERE item_id = ?",
+                rusqlite::params![vec![7u8; 40], bad]).unwrap();
+            conn.execute("UPDATE nswallet_fields SET value = ? WHERE field_id = ?",
+                rusqlite::params![vec![9u8; 40], bad_field]).unwrap();
+        }
+        wallet.clear_caches();
+
+        let items = wallet.get_items().unwrap();
+        assert!(items.iter().any(|i| i.item_id == good));
+        assert!(!items.iter().any(|i| i.item_id == bad));
+        let fields = wallet.get_fields_by_item(&good).unwrap();
+        assert_eq!(fields.len(), 1);
+        assert_eq!(fields[0].value, "fine");
+
+        let records = wallet.get_undecryptable_records().unwrap();
+        assert_eq!(records.len(), 2);
+        assert!(records.iter().any(|r| r.kind == RecordKind::Item && r.item_id == bad));
+        assert!(records.iter().any(|r| r.kind == RecordKind::Field
+            && r.field_id.as_deref() == Some(bad_field.as_str())));
+    }
+
+    #[test]
+    fn test_quarantine_and_purge_undecryptable() {
+        let (mut wallet, _temp) = create_test_wallet();
+        let bad = wallet.add_item("Bad", "document", false, None).unwrap();
+        wallet.add_field(&bad, "NOTE", "goes along", None).unwrap();
+        {
+            let conn = wallet.db.as_ref().unwrap().connection().unwrap();
+            conn.execute("UPDATE nswallet_items SET name = ? WHERE item_id = ?",
+                rusqlite::params![vec![7u8; 40], bad]).unwrap();
+        }
+
+        assert_eq!(wallet.quarantine_undecryptable().unwrap(), 2);
+        assert_eq!(wallet.quarantine_count().unwrap(), 2);
+        assert!(wallet.get_undecryptable_records().unwrap().is_empty());
+        assert!(wallet.get_fields().unwrap().iter().all(|f| f.item_id != bad));
+
+        // Nothing left to quarantine
+        assert_eq!(wallet.quarantine_undecryptable().unwrap(), 0);
+
+        wallet.lock();
+        assert!(matches!(wallet.purge_quarantine(), Err(WalletError::Locked)));
+        assert!(wallet.unlock("TestPassword123").unwrap());
+        assert_eq!(wallet.quarantine_count().unwrap(), 2);
+
+        assert_eq!(wallet.purge_quarantine().unwrap(), 2);
+        assert_eq!(wallet.quarantine_count().unwrap(), 0);
+    }
 }
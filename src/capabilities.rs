@@ -0,0 +1,137 @@
+//! What this build of iwcore supports
+//!
+//! Clients talking to the library over RPC or FFI may be built against a
+//! different version. [`capabilities`] tells them which wallet databases
+//! can be opened, which encryption formats are read and written, and which
+//! optional features were compiled in, so they can check instead of
+//! guessing from the version number.
+
+use serde::{Deserialize, Serialize};
+use crate::DB_VERSION;
+
+/// Oldest database version that opens (and is upgraded on open)
+pub const MIN_DB_VERSION: u32 = 1;
+
+/// Names of the optional features, as reported in [`Capabilities::features`]
+pub mod feature {
+    /// PDF export and emergency sheets (always compiled in)
+    pub const PDF: &str = "pdf";
+    /// Exports and share files encrypted to age (X25519) recipients (always
+    /// compiled in)
+    pub const AGE_RECIPIENTS: &str = "age-recipients";
+    /// Import of KeePass 2.x databases (always compiled in)
+    pub const KDBX_IMPORT: &str = "kdbx-import";
+    /// Phone number normalization (`phone` cargo feature)
+    pub const PHONE: &str = "phone";
+    /// Breached-password lookups (Have I Been Pwned). Not available in iwcore.
+    pub const HIBP: &str = "hibp";
+    /// SQLCipher page encryption. Not available in iwcore: the database is
+    /// plain SQLite with field-level encryption.
+    pub const SQLCIPHER: &str = "sqlcipher";
+    /// `testsupport::WalletFixture` (`testsupport` cargo feature)
+    pub const TESTSUPPORT: &str = "testsupport";
+}
+
+/// An encryption format for field values and the password check
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EncryptionFormat {
+    /// Stable identifier
+    pub name: String,
+    /// Database version that introduced the format
+    pub db_version: u32,
+    /// Data in this format can be decrypted
+    pub read: bool,
+    /// New data is written in this format
+    pub write: bool,
+}
+
+/// Supported versions, formats and features; see [`capabilities`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Capabilities {
+    /// Version of the iwcore crate
+    pub crate_version: String,
+    /// Oldest database version that opens
+    pub min_db_version: u32,
+    /// Version of new and migrated databases; newer databases are refused
+    pub db_version: u32,
+    pub encryption_formats: Vec<EncryptionFormat>,
+    /// Optional features compiled in, by the names in [`feature`]
+    pub features: Vec<String>,
+}
+
+impl Capabilities {
+    /// Whether the feature `name` (one of [`feature`]) is compiled in
+    pub fn has_feature(&self, name: &str) -> bool {
+        self.features.iter().any(|f| f == name)
+    }
+
+    /// Whether a database of `version` can be opened by this build
+    pub fn supports_db_version(&self, version: u32) -> bool {
+        (self.min_db_version..=self.db_version).contains(&version)
+    }
+}
+
+/// Describe this build: crate version, database versions, encryption
+/// formats and compiled-in features. Serializes to JSON for remote clients.
+pub fn capabilities() -> Capabilities {
+    let mut features = vec![
+        feature::PDF.to_string(),
+        feature::AGE_RECIPIENTS.to_string(),
+        feature::KDBX_IMPORT.to_string(),
+    ];
+    if cfg!(feature = "phone") {
+        features.push(feature::PHONE.to_string());
+    }
+    if cfg!(feature = "testsupport") {
+        features.push(feature::TESTSUPPORT.to_string());
+    }
+
+    Capabilities {
+        crate_version: env!("CARGO_PKG_VERSION").to_string(),
+        min_db_version: MIN_DB_VERSION,
+        db_version: DB_VERSION.parse().unwrap_or(6),
+        encryption_formats: vec![
+            EncryptionFormat {
+                name: "xchacha20poly1305-argon2id".to_string(),
+                db_version: 6,
+                read: true,
+                write: true,
+            },
+            // Read only to migrate and verify v5 vaults
+            EncryptionFormat {
+                name: "aes256cbc-md5".to_string(),
+                db_version: 1,
+                read: true,
+                write: false,
+            },
+        ],
+        features,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capabilities() {
+        let caps = capabilities();
+        assert_eq!(caps.crate_version, env!("CARGO_PKG_VERSION"));
+        assert!(caps.supports_db_version(1));
+        assert!(caps.supports_db_version(DB_VERSION.parse().unwrap()));
+        assert!(!caps.supports_db_version(caps.db_version + 1));
+        assert!(crate::database::migrations::is_version_compatible(&caps.db_version.to_string()));
+        assert_eq!(caps.encryption_formats.iter().filter(|f| f.write).count(), 1);
+
+        assert!(caps.has_feature(feature::PDF));
+        assert!(caps.has_feature(feature::AGE_RECIPIENTS));
+        assert!(caps.has_feature(feature::KDBX_IMPORT));
+        assert_eq!(caps.has_feature(feature::PHONE), cfg!(feature = "phone"));
+        assert!(!caps.has_feature(feature::HIBP));
+        assert!(!caps.has_feature(feature::SQLCIPHER));
+
+        let json = serde_json::to_string(&caps).unwrap();
+        let back: Capabilities = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, caps);
+    }
+}
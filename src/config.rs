@@ -0,0 +1,260 @@
+//! Configuration file for command-line and daemon deployments
+//!
+//! A TOML file names the wallets to serve, where backups go and how long
+//! they are kept, the auto-lock timeout and the Argon2id parameters for new
+//! vaults. Every setting can be overridden with an `IWCORE_*` environment
+//! variable, so one file can be shared between machines:
+//!
+//! ```toml
+//! wallet_paths = ["/var/lib/iwallet/main"]
+//! auto_lock_minutes = 5
+//!
+//! [backup]
+//! folder = "/var/backups/iwallet"
+//! min_keep = 5
+//! max_age_days = 30
+//!
+//! [kdf]
+//! m_cost_kib = 65536
+//! t_cost = 3
+//! p_cost = 1
+//! ```
+//!
+//! | Variable | Setting |
+//! |---|---|
+//! | `IWCORE_WALLET_PATHS` | `wallet_paths`, in the platform's `PATH` list format |
+//! | `IWCORE_AUTO_LOCK_MINUTES` | `auto_lock_minutes` |
+//! | `IWCORE_BACKUP_FOLDER` | `backup.folder` |
+//! | `IWCORE_BACKUP_MIN_KEEP` | `backup.min_keep` |
+//! | `IWCORE_BACKUP_MAX_AGE_DAYS` | `backup.max_age_days` |
+//! | `IWCORE_BACKUP_AFTER_CHANGES` | `backup.after_changes` |
+//! | `IWCORE_BACKUP_AFTER_MINUTES` | `backup.after_minutes` |
+//! | `IWCORE_BACKUP_UNLOCK_INTERVAL_HOURS` | `backup.unlock_interval_hours` |
+//! | `IWCORE_KDF_M_COST_KIB` | `kdf.m_cost_kib` |
+//! | `IWCORE_KDF_T_COST` | `kdf.t_cost` |
+//! | `IWCORE_KDF_P_COST` | `kdf.p_cost` |
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::backup::{AutoBackupConfig, BackupManager};
+use crate::crypto::kdf::KdfParams;
+use crate::error::{WalletError, Result};
+
+/// Settings loaded from a config file
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Wallet folders to serve
+    pub wallet_paths: Vec<PathBuf>,
+    /// Lock an unlocked wallet after this many idle minutes (0 = never)
+    pub auto_lock_minutes: u32,
+    /// Backup folder and retention
+    pub backup: BackupSection,
+    /// Argon2id parameters for new vaults and password changes
+    pub kdf: KdfSection,
+}
+
+/// `[backup]` section of the config file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BackupSection {
+    /// Backup folder; no backups are taken without one
+    pub folder: Option<PathBuf>,
+    /// See [`AutoBackupConfig::after_changes`]
+    pub after_changes: u32,
+    /// See [`AutoBackupConfig::after_minutes`]
+    pub after_minutes: u32,
+    /// See [`AutoBackupConfig::min_keep`]
+    pub min_keep: usize,
+    /// See [`AutoBackupConfig::max_age_days`]
+    pub max_age_days: u32,
+    /// See [`AutoBackupConfig::unlock_interval_hours`]
+    pub unlock_interval_hours: u32,
+}
+
+impl Default for BackupSection {
+    fn default() -> Self {
+        let defaults = AutoBackupConfig::default();
+        Self {
+            folder: None,
+            after_changes: defaults.after_changes,
+            after_minutes: defaults.after_minutes,
+            min_keep: defaults.min_keep,
+            max_age_days: defaults.max_age_days,
+            unlock_interval_hours: defaults.unlock_interval_hours,
+        }
+    }
+}
+
+/// `[kdf]` section of the config file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KdfSection {
+    /// Memory cost in KiB
+    pub m_cost_kib: u32,
+    /// Time cost (iterations)
+    pub t_cost: u32,
+    /// Parallelism (lanes)
+    pub p_cost: u32,
+}
+
+impl Default for KdfSection {
+    fn default() -> Self {
+        let current = KdfParams::current();
+        Self {
+            m_cost_kib: current.m_cost_kib,
+            t_cost: current.t_cost,
+            p_cost: current.p_cost,
+        }
+    }
+}
+
+/// Parse an override value, naming the variable on failure
+fn parse_var<T: FromStr>(name: &str, value: &str) -> Result<T> {
+    value.trim().parse()
+        .map_err(|_| WalletError::ConfigError(format!("{name}: invalid value {value:?}")))
+}
+
+impl Config {
+    /// Parse a config from TOML text. Missing settings take their defaults;
+    /// unknown settings are an error so typos do not go unnoticed.
+    pub fn from_toml_str(text: &str) -> Result<Self> {
+        let config: Config = toml::from_str(text)
+            .map_err(|e| WalletError::ConfigError(e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Load a config file and apply `IWCORE_*` environment overrides
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| WalletError::ConfigError(format!("{}: {e}", path.display())))?;
+        let mut config = Self::from_toml_str(&text)?;
+        config.apply_overrides(|name| std::env::var(name).ok())?;
+        Ok(config)
+    }
+
+    /// Apply overrides looked up by variable name (see the module docs)
+    pub fn apply_overrides(&mut self, lookup: impl Fn(&str) -> Option<String>) -> Result<()> {
+        if let Some(value) = lookup("IWCORE_WALLET_PATHS") {
+            self.wallet_paths = std::env::split_paths(&value).collect();
+        }
+        if let Some(value) = lookup("IWCORE_BACKUP_FOLDER") {
+            self.backup.folder = (!value.is_empty()).then(|| PathBuf::from(value));
+        }
+
+        macro_rules! numeric {
+            ($($name:literal => $field:expr),* $(,)?) => {
+                $(if let Some(value) = lookup($name) {
+                    $field = parse_var($name, &value)?;
+                })*
+            };
+        }
+        numeric! {
+            "IWCORE_AUTO_LOCK_MINUTES" => self.auto_lock_minutes,
+            "IWCORE_BACKUP_MIN_KEEP" => self.backup.min_keep,
+            "IWCORE_BACKUP_MAX_AGE_DAYS" => self.backup.max_age_days,
+            "IWCORE_BACKUP_AFTER_CHANGES" => self.backup.after_changes,
+            "IWCORE_BACKUP_AFTER_MINUTES" => self.backup.after_minutes,
+            "IWCORE_BACKUP_UNLOCK_INTERVAL_HOURS" => self.backup.unlock_interval_hours,
+            "IWCORE_KDF_M_COST_KIB" => self.kdf.m_cost_kib,
+            "IWCORE_KDF_T_COST" => self.kdf.t_cost,
+            "IWCORE_KDF_P_COST" => self.kdf.p_cost,
+        }
+        self.validate()
+    }
+
+    /// Check the settings can be used as given
+    pub fn validate(&self) -> Result<()> {
+        let kdf = &self.kdf;
+        if !self.kdf_params().is_valid() {
+            return Err(WalletError::ConfigError(format!(
+                "kdf: invalid Argon2id parameters (m_cost_kib {}, t_cost {}, p_cost {})",
+                kdf.m_cost_kib, kdf.t_cost, kdf.p_cost
+            )));
+        }
+        Ok(())
+    }
+
+    /// Auto backup policy for [`Wallet::set_backup_manager`](crate::Wallet::set_backup_manager)
+    pub fn auto_backup_config(&self) -> AutoBackupConfig {
+        AutoBackupConfig {
+            after_changes: self.backup.after_changes,
+            after_minutes: self.backup.after_minutes,
+            min_keep: self.backup.min_keep,
+            max_age_days: self.backup.max_age_days,
+            unlock_interval_hours: self.backup.unlock_interval_hours,
+        }
+    }
+
+    /// Backup manager for the configured folder, if one is set
+    pub fn backup_manager(&self) -> Option<BackupManager> {
+        self.backup.folder.as_deref().map(BackupManager::new)
+    }
+
+    /// Idle time before an unlocked wallet locks, for
+    /// [`Wallet::set_auto_lock`](crate::Wallet::set_auto_lock); `None` for 0
+    pub fn auto_lock(&self) -> Option<Duration> {
+        (self.auto_lock_minutes > 0).then(|| Duration::from_secs(u64::from(self.auto_lock_minutes) * 60))
+    }
+
+    /// Argon2id parameters from the `[kdf]` section, for
+    /// [`Wallet::create_with_kdf`](crate::Wallet::create_with_kdf) and
+    /// [`Wallet::set_kdf_params`](crate::Wallet::set_kdf_params)
+    pub fn kdf_params(&self) -> KdfParams {
+        KdfParams {
+            m_cost_kib: self.kdf.m_cost_kib,
+            t_cost: self.kdf.t_cost,
+            p_cost: self.kdf.p_cost,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_parse_config_with_defaults() {
+        let config = Config::from_toml_str(r#"
+            wallet_paths = ["/data/main", "/data/work"]
+            auto_lock_minutes = 5
+
+            [backup]
+            folder = "/backups"
+            min_keep = 3
+        "#).unwrap();
+        assert_eq!(config.wallet_paths, vec![PathBuf::from("/data/main"), PathBuf::from("/data/work")]);
+        assert_eq!(config.auto_lock_minutes, 5);
+        assert_eq!(config.backup.min_keep, 3);
+        assert_eq!(config.backup.max_age_days, AutoBackupConfig::default().max_age_days);
+        assert_eq!(config.kdf_params(), KdfParams::current());
+        assert_eq!(config.auto_lock(), Some(Duration::from_secs(300)));
+        assert_eq!(Config::default().auto_lock(), None);
+        assert_eq!(config.backup_manager().unwrap().folder(), Path::new("/backups"));
+
+        assert_eq!(Config::from_toml_str("").unwrap(), Config::default());
+        assert!(matches!(Config::from_toml_str("auto_lock = 5"), Err(WalletError::ConfigError(_))));
+        assert!(matches!(Config::from_toml_str("[kdf]\nt_cost = 0"), Err(WalletError::ConfigError(_))));
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let mut config = Config::from_toml_str("auto_lock_minutes = 5\n[backup]\nfolder = \"/backups\"").unwrap();
+        let vars: HashMap<&str, &str> = [
+            ("IWCORE_AUTO_LOCK_MINUTES", "15"),
+            ("IWCORE_BACKUP_FOLDER", ""),
+            ("IWCORE_KDF_T_COST", "4"),
+        ].into_iter().collect();
+        config.apply_overrides(|name| vars.get(name).map(|v| v.to_string())).unwrap();
+        assert_eq!(config.auto_lock_minutes, 15);
+        assert!(config.backup.folder.is_none());
+        assert_eq!(config.kdf.t_cost, 4);
+
+        let err = config.apply_overrides(|name| (name == "IWCORE_BACKUP_MIN_KEEP").then(|| "many".to_string()));
+        assert!(matches!(err, Err(WalletError::ConfigError(msg)) if msg.contains("IWCORE_BACKUP_MIN_KEEP")));
+    }
+}
@@ -13,10 +13,12 @@
 //! The leading 0x06 tag lets readers distinguish a v6 blob from a legacy
 //! (v5 AES-CBC) blob, which has no such tag.
 
-use chacha20poly1305::aead::Aead;
+use chacha20poly1305::aead::{Aead, Payload};
 use chacha20poly1305::{KeyInit, XChaCha20Poly1305, XNonce};
 use rand::Rng;
 
+use super::error::DecryptError;
+
 /// Leading byte that marks a v6 AEAD blob.
 pub const FORMAT_TAG: u8 = 0x06;
 /// XChaCha20 nonce length.
@@ -42,12 +44,18 @@ pub fn is_v6_blob(blob: &[u8]) -> bool {
 /// Encrypt `plaintext` under `key`, producing the self-describing blob above.
 /// A fresh random 24-byte nonce is drawn from the OS CSPRNG on every call.
 pub fn seal(key: &[u8; KEY_LEN], plaintext: &[u8]) -> Result<Vec<u8>, String> {
+    seal_with_aad(key, plaintext, b"")
+}
+
+/// Like [`seal`], also authenticating `aad`: the blob only opens with the
+/// same associated data, which binds it to where it is stored.
+pub fn seal_with_aad(key: &[u8; KEY_LEN], plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>, String> {
     let mut nonce_bytes = [0u8; NONCE_LEN];
     rand::rng().fill_bytes(&mut nonce_bytes);
     let nonce = XNonce::from(nonce_bytes);
 
     let ct = cipher(key)
-        .encrypt(&nonce, plaintext)
+        .encrypt(&nonce, Payload { msg: plaintext, aad })
         .map_err(|_| "AEAD seal failed".to_string())?;
 
     let mut out = Vec::with_capacity(1 + NONCE_LEN + ct.len());
@@ -57,14 +65,22 @@ pub fn seal(key: &[u8; KEY_LEN], plaintext: &[u8]) -> Result<Vec<u8>, String> {
     Ok(out)
 }
 
-/// Decrypt a v6 blob produced by [`seal`]. Returns an error on a malformed blob,
-/// a wrong key, or any tampering (authentication failure).
-pub fn open(key: &[u8; KEY_LEN], blob: &[u8]) -> Result<Vec<u8>, String> {
-    if blob.len() < MIN_BLOB_LEN {
-        return Err("AEAD blob too short".to_string());
+/// Decrypt a v6 blob produced by [`seal`]. A malformed blob, a wrong key and
+/// any tampering (authentication failure) all report
+/// [`DecryptError::WrongKeyOrCorrupt`]; an empty blob reports
+/// [`DecryptError::EmptyCiphertext`].
+pub fn open(key: &[u8; KEY_LEN], blob: &[u8]) -> Result<Vec<u8>, DecryptError> {
+    open_with_aad(key, blob, b"")
+}
+
+/// Decrypt a blob produced by [`seal_with_aad`]; a different `aad` fails
+/// like a wrong key.
+pub fn open_with_aad(key: &[u8; KEY_LEN], blob: &[u8], aad: &[u8]) -> Result<Vec<u8>, DecryptError> {
+    if blob.is_empty() {
+        return Err(DecryptError::EmptyCiphertext);
     }
-    if blob[0] != FORMAT_TAG {
-        return Err("not a v6 AEAD blob".to_string());
+    if blob.len() < MIN_BLOB_LEN || blob[0] != FORMAT_TAG {
+        return Err(DecryptError::WrongKeyOrCorrupt);
     }
 
     let mut nonce_bytes = [0u8; NONCE_LEN];
@@ -73,8 +89,8 @@ pub fn open(key: &[u8; KEY_LEN], blob: &[u8]) -> Result<Vec<u8>, String> {
     let ciphertext = &blob[1 + NONCE_LEN..];
 
     cipher(key)
-        .decrypt(&nonce, ciphertext)
-        .map_err(|_| "AEAD open failed (authentication)".to_string())
+        .decrypt(&nonce, Payload { msg: ciphertext, aad })
+        .map_err(|_| DecryptError::WrongKeyOrCorrupt)
 }
 
 #[cfg(test)]
@@ -125,7 +141,22 @@ mod tests {
     fn wrong_key_fails() {
         let blob = seal(&KEY, b"secret").unwrap();
         let wrong = [9u8; KEY_LEN];
-        assert!(open(&wrong, &blob).is_err());
+        assert_eq!(open(&wrong, &blob), Err(DecryptError::WrongKeyOrCorrupt));
+    }
+
+    #[test]
+    fn aad_must_match() {
+        let blob = seal_with_aad(&KEY, b"secret", b"here").unwrap();
+        assert_eq!(open_with_aad(&KEY, &blob, b"here").unwrap(), b"secret");
+        assert_eq!(open_with_aad(&KEY, &blob, b"there"), Err(DecryptError::WrongKeyOrCorrupt));
+        assert_eq!(open(&KEY, &blob), Err(DecryptError::WrongKeyOrCorrupt));
+        // No associated data is the same as empty associated data
+        assert_eq!(open_with_aad(&KEY, &seal(&KEY, b"secret").unwrap(), b"").unwrap(), b"secret");
+    }
+
+    #[test]
+    fn empty_blob_is_reported_as_empty() {
+        assert_eq!(open(&KEY, &[]), Err(DecryptError::EmptyCiphertext));
     }
 
     #[test]
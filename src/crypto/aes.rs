@@ -6,7 +6,10 @@
 //! - MD5 checksum prepended to plaintext before encryption
 //!
 //! **IMPORTANT**: The zero IV is a security weakness but is required for
-//! backward compatibility with existing encrypted data.
+//! backward compatibility with existing encrypted data. Nothing new is
+//! written in this format: current data is sealed by [`super::aead`] with a
+//! random nonce per record, and legacy records are rewritten that way when
+//! a v5 vault is first unlocked.
 
 use aes::Aes256;
 use cbc::{Encryptor, Decryptor};
@@ -15,6 +18,7 @@ use block_padding::Pkcs7;
 
 use super::key::{prepare_key, KEY_LENGTH};
 use super::md5::md5_hex;
+use super::error::DecryptError;
 
 /// IV size for AES-CBC (16 bytes = 128 bits)
 const IV_SIZE: usize = 16;
@@ -88,13 +92,13 @@ pub fn encrypt(
 ///
 /// # Returns
 ///
-/// Decrypted plaintext on success, or error message on failure
+/// Decrypted plaintext on success, or the failure of the last attempt
 pub fn decrypt(
     ciphertext: &[u8],
     password: &str,
     re_encryption_count: u32,
     hash: Option<&str>,
-) -> Result<String, String> {
+) -> Result<String, DecryptError> {
     // Try normal decryption first
     let key = prepare_key(password, hash, re_encryption_count);
 
@@ -214,9 +218,14 @@ impl LegacyKeyChain {
         self.candidates[self.preferred].0
     }
 
+    /// Whether the most recent blob needed the iOS-workaround key variant.
+    pub fn used_ios_variant(&self) -> bool {
+        self.prefer_ios
+    }
+
     /// Decrypt a legacy blob, trying the last-successful candidate/variant
     /// first, then every remaining candidate (normal then iOS variant).
-    pub fn decrypt(&mut self, ciphertext: &[u8]) -> Result<String, String> {
+    pub fn decrypt(&mut self, ciphertext: &[u8]) -> Result<String, DecryptError> {
         // Preferred candidate, preferred variant first.
         let (_, key, ios_key) = &self.candidates[self.preferred];
         let (first, second) = if self.prefer_ios { (ios_key, key) } else { (key, ios_key) };
@@ -229,8 +238,8 @@ impl LegacyKeyChain {
         }
 
         // Remaining candidates in declaration order. Only the final failure
-        // message survives; the per-variant errors carry no extra signal.
-        let mut last_err = String::from("no key candidate matched");
+        // survives; the per-variant errors carry no extra signal.
+        let mut last_err = DecryptError::WrongKeyOrCorrupt;
         for (idx, (_, key, ios_key)) in self.candidates.iter().enumerate() {
             if idx == self.preferred {
                 continue;
@@ -254,9 +263,9 @@ impl LegacyKeyChain {
 }
 
 /// Internal decryption with a specific key
-fn decrypt_with_key(ciphertext: &[u8], key: &[u8; KEY_LENGTH]) -> Result<String, String> {
+fn decrypt_with_key(ciphertext: &[u8], key: &[u8; KEY_LENGTH]) -> Result<String, DecryptError> {
     if ciphertext.is_empty() {
-        return Err("Empty ciphertext".to_string());
+        return Err(DecryptError::EmptyCiphertext);
     }
 
     // Create a mutable copy for in-place decryption
@@ -267,22 +276,22 @@ fn decrypt_with_key(ciphertext: &[u8], key: &[u8; KEY_LENGTH]) -> Result<String,
 
     let decrypted = decryptor
         .decrypt_padded::<Pkcs7>(&mut buffer)
-        .map_err(|e| format!("Decryption failed: {:?}", e))?;
+        .map_err(|_| DecryptError::BadPadding)?;
 
     // Convert to UTF-8 string
     let full_text = String::from_utf8(decrypted.to_vec())
-        .map_err(|e| format!("Invalid UTF-8: {}", e))?;
+        .map_err(|_| DecryptError::BadUtf8)?;
 
     // Verify MD5 checksum
     if full_text.len() < MD5_HEX_LENGTH {
-        return Err("Decrypted text too short".to_string());
+        return Err(DecryptError::ChecksumMismatch);
     }
 
     let (checksum, plaintext) = full_text.split_at(MD5_HEX_LENGTH);
     let computed_checksum = md5_hex(plaintext);
 
     if checksum != computed_checksum {
-        return Err("MD5 checksum mismatch".to_string());
+        return Err(DecryptError::ChecksumMismatch);
     }
 
     Ok(plaintext.to_string())
@@ -383,6 +392,28 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_decrypt_error_kinds() {
+        let encrypted = encrypt("Secret message", "password", 0, None).unwrap();
+        assert_eq!(decrypt(&[], "password", 0, None), Err(DecryptError::EmptyCiphertext));
+        // Not a whole number of blocks: fails before any checksum is seen
+        assert_eq!(
+            decrypt(&encrypted[..encrypted.len() - 1], "password", 0, None),
+            Err(DecryptError::BadPadding)
+        );
+
+        // Valid padding and UTF-8 but a checksum that does not match the text
+        let key = prepare_key("password", None, 0);
+        let bogus = format!("{}x", "0".repeat(MD5_HEX_LENGTH));
+        let mut buffer = vec![0u8; 48];
+        buffer[..bogus.len()].copy_from_slice(bogus.as_bytes());
+        let blob = Aes256CbcEnc::new(&key.into(), &ZERO_IV.into())
+            .encrypt_padded::<Pkcs7>(&mut buffer, bogus.len())
+            .unwrap()
+            .to_vec();
+        assert_eq!(decrypt_with_key(&blob, &key), Err(DecryptError::ChecksumMismatch));
+    }
+
     /// Encrypt with an explicit key, bypassing prepare_key — used to fabricate
     /// ciphertext written under the iOS-workaround key variant.
     fn encrypt_with_raw_key(plaintext: &str, key: &[u8; KEY_LENGTH]) -> Vec<u8> {
@@ -464,6 +495,7 @@ mod tests {
         // First iOS blob decrypts via the fallback and flips the preference...
         assert_eq!(chain.decrypt(&ios_blob_1).unwrap(), "ios record one");
         assert!(chain.prefer_ios);
+        assert!(chain.used_ios_variant());
         // ...so the second one hits the iOS variant on the first try.
         assert_eq!(chain.decrypt(&ios_blob_2).unwrap(), "ios record two");
         // A normal blob still decrypts (and flips the preference back).
@@ -7,11 +7,24 @@
 //! - changing the password only re-wraps the DEK (data is untouched);
 //! - raising the Argon2id cost only re-wraps the DEK (data is untouched);
 //! - password verification is "can we unwrap the DEK" (the AEAD tag is the
-//!   verifier) - no separate verifier, no MD5.
+//!   verifier), short-cut by the key check value below - no MD5.
+//!
+//! The key check value (KCV) is HMAC-SHA256 of a fixed label under the KEK.
+//! It is deterministic, so a candidate password is checked by recomputing it
+//! and comparing in constant time, without touching the wrapped DEK or any
+//! item data. It is stored after the KDF salt it was made with, so a KCV
+//! left over from an older crypto record is recognized as stale instead of
+//! rejecting the right password.
 
+use hmac::{Hmac, KeyInit, Mac};
 use rand::Rng;
+use sha2::Sha256;
 
 use super::aead::{self, KEY_LEN};
+use super::error::DecryptError;
+
+/// Label authenticated under the KEK to form the key check value
+const KEY_CHECK_PLAINTEXT: &[u8] = b"IntelliWallet key check v1";
 
 /// DEK length (32 bytes = 256 bits).
 pub const DEK_LEN: usize = 32;
@@ -32,20 +45,59 @@ pub fn wrap_dek(kek: &[u8; KEY_LEN], dek: &[u8; DEK_LEN]) -> Result<Vec<u8>, Str
 
 /// Unwrap the DEK. Returns an error if the KEK is wrong (the password is
 /// incorrect) or the wrapped blob is malformed/tampered.
-pub fn unwrap_dek(kek: &[u8; KEY_LEN], wrapped: &[u8]) -> Result<[u8; DEK_LEN], String> {
+pub fn unwrap_dek(kek: &[u8; KEY_LEN], wrapped: &[u8]) -> Result<[u8; DEK_LEN], DecryptError> {
     let pt = aead::open(kek, wrapped)?;
     if pt.len() != DEK_LEN {
-        return Err("unwrapped DEK has wrong length".to_string());
+        return Err(DecryptError::WrongKeyOrCorrupt);
     }
     let mut dek = [0u8; DEK_LEN];
     dek.copy_from_slice(&pt);
     Ok(dek)
 }
 
+/// Length of the key check MAC (without the salt prefix)
+const KEY_CHECK_LEN: usize = 32;
+
+fn key_check_mac(kek: &[u8; KEY_LEN]) -> Hmac<Sha256> {
+    let mut mac = <Hmac<Sha256> as KeyInit>::new_from_slice(kek).expect("HMAC accepts keys of any length");
+    mac.update(KEY_CHECK_PLAINTEXT);
+    mac
+}
+
+/// Key check value to store for a KEK derived with `salt`
+pub fn key_check_value(kek: &[u8; KEY_LEN], salt: &[u8]) -> Vec<u8> {
+    let mut out = salt.to_vec();
+    out.extend_from_slice(&key_check_mac(kek).finalize().into_bytes());
+    out
+}
+
+/// Check a KEK derived with `salt` against a stored key check value,
+/// comparing in constant time. `None` when the stored value was made for
+/// another salt (or is malformed) and so cannot decide.
+pub fn verify_key_check(kek: &[u8; KEY_LEN], salt: &[u8], stored: &[u8]) -> Option<bool> {
+    if stored.len() != salt.len() + KEY_CHECK_LEN || &stored[..salt.len()] != salt {
+        return None;
+    }
+    Some(key_check_mac(kek).verify_slice(&stored[salt.len()..]).is_ok())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn key_check_value_is_deterministic_per_kek() {
+        let kek = [3u8; KEY_LEN];
+        let salt = [9u8; 16];
+        let kcv = key_check_value(&kek, &salt);
+        assert_eq!(kcv, key_check_value(&kek, &salt));
+        assert_eq!(verify_key_check(&kek, &salt, &kcv), Some(true));
+        assert_eq!(verify_key_check(&[4u8; KEY_LEN], &salt, &kcv), Some(false));
+        // Made for another salt, or truncated: undecided
+        assert_eq!(verify_key_check(&kek, &[8u8; 16], &kcv), None);
+        assert_eq!(verify_key_check(&kek, &salt, &kcv[..kcv.len() - 1]), None);
+    }
+
     #[test]
     fn wrap_unwrap_roundtrip() {
         let kek = [3u8; KEY_LEN];
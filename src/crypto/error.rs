@@ -0,0 +1,31 @@
+//! Typed decryption failures shared by the legacy and v6 schemes
+
+use thiserror::Error;
+
+/// Why a blob failed to decrypt
+///
+/// The v6 AEAD scheme cannot tell a wrong key from tampered data, so both
+/// surface as [`DecryptError::WrongKeyOrCorrupt`]. The legacy AES-CBC scheme
+/// fails at a specific step, which the remaining variants name.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecryptError {
+    /// Authentication failed or the blob is malformed
+    #[error("wrong key or corrupted data")]
+    WrongKeyOrCorrupt,
+
+    /// Legacy blob did not unpad (wrong key or truncated ciphertext)
+    #[error("bad padding")]
+    BadPadding,
+
+    /// Decrypted bytes are not valid UTF-8
+    #[error("invalid UTF-8")]
+    BadUtf8,
+
+    /// Legacy MD5 checksum does not match the decrypted text
+    #[error("checksum mismatch")]
+    ChecksumMismatch,
+
+    /// Nothing to decrypt
+    #[error("empty ciphertext")]
+    EmptyCiphertext,
+}
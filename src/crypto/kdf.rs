@@ -30,6 +30,15 @@ pub const ARGON2_T_COST: u32 = 3;
 /// Parallelism (number of lanes).
 pub const ARGON2_P_COST: u32 = 1;
 
+/// Largest memory cost accepted from a file header (1 GiB). Params read
+/// from an imported file are attacker-controlled; above these limits a
+/// single derivation could exhaust memory or run for hours.
+pub const UNTRUSTED_MAX_M_COST_KIB: u32 = 1_048_576;
+/// Largest time cost accepted from a file header
+pub const UNTRUSTED_MAX_T_COST: u32 = 64;
+/// Largest parallelism accepted from a file header
+pub const UNTRUSTED_MAX_P_COST: u32 = 16;
+
 /// Argon2id cost parameters for a vault. Stored per-vault and used verbatim on
 /// unlock, so older vaults remain decryptable after the consts are raised.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -51,6 +60,23 @@ impl KdfParams {
             p_cost: ARGON2_P_COST,
         }
     }
+
+    /// True if Argon2id accepts the params: at least one iteration and
+    /// lane, and 8 KiB of memory per lane
+    pub const fn is_valid(&self) -> bool {
+        self.t_cost > 0
+            && self.p_cost > 0
+            && self.p_cost <= u32::MAX / 8
+            && self.m_cost_kib >= 8 * self.p_cost
+    }
+
+    /// True if the params are within the `UNTRUSTED_MAX_*` limits, for
+    /// params read from a file this wallet did not write
+    pub const fn within_untrusted_limits(&self) -> bool {
+        self.m_cost_kib <= UNTRUSTED_MAX_M_COST_KIB
+            && self.t_cost <= UNTRUSTED_MAX_T_COST
+            && self.p_cost <= UNTRUSTED_MAX_P_COST
+    }
 }
 
 /// Derive a 32-byte KEK from a password and salt using Argon2id with the given
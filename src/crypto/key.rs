@@ -6,6 +6,11 @@
 //! 3. Truncate to 32 chars
 //! 4. If re_encryption_count > 0: apply MD5 that many times
 //! 5. UTF-8 encode to 32 bytes
+//!
+//! Legacy (v5) only: this derivation is kept to read and migrate old
+//! vaults and is never used for new data. Current vaults derive their key
+//! with Argon2id ([`super::kdf`]) and write tagged AEAD blobs
+//! ([`super::aead`]), which readers tell apart from untagged AES-CBC data.
 
 use super::md5::md5_hex;
 
@@ -3,12 +3,15 @@
 //! The current (v6) scheme is XChaCha20-Poly1305 authenticated encryption over a
 //! per-vault Data Encryption Key (DEK), with the DEK wrapped by an
 //! Argon2id-derived Key Encryption Key (KEK). See [`aead`], [`kdf`], [`dek`].
+//! Exports can also be encrypted to age (X25519) public keys; see
+//! [`recipients`].
 //!
 //! The legacy (v5) scheme - zero-IV AES-256-CBC + unsalted MD5, matching the
 //! original C# implementation - is retained under [`legacy`] solely for the
 //! one-time v5->v6 migration and for verifying not-yet-migrated vaults.
 
 mod aes;
+mod error;
 mod md5;
 mod key;
 pub mod password;
@@ -18,8 +21,10 @@ mod wordlist;
 pub mod kdf;
 pub mod aead;
 pub mod dek;
+pub mod recipients;
 
 pub use aes::{encrypt, decrypt};
+pub use error::DecryptError;
 pub use md5::md5_hex;
 pub use key::prepare_key;
 
@@ -32,8 +37,8 @@ pub mod legacy {
     pub use super::md5::md5_hex;
 }
 pub use password::{
-    generate_password, generate_clever_password, generate_memorable_password,
-    PasswordOptions, MemorableOptions, MemorableCaps,
+    generate_password, generate_clever_password, generate_memorable_password, validate_pattern,
+    pattern_entropy_bits, normalize_master_password, check_master_password_length, PasswordOptions, PasswordStrength, PatternInfo, PatternToken, MemorableOptions, MemorableCaps,
 };
 
 #[cfg(test)]
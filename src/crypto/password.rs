@@ -17,11 +17,17 @@
 //! entropy is exactly `log2(1024) = 10 bits` plus `log2(10) ≈ 3.32 bits`
 //! per appended digit. Format-aware crackers benefit from knowing this
 //! structure. Use the random mode for high-value secrets.
+//!
+//! Master passwords are normalized to Unicode NFC before key derivation
+//! (`normalize_master_password`), and new ones are length-checked
+//! (`check_master_password_length`).
 
 use rand::rngs::{StdRng, SysRng};
 use rand::{RngExt, SeedableRng};
+use unicode_normalization::UnicodeNormalization;
 
 use super::wordlist::WORDS;
+use crate::error::{Result, WalletError};
 
 /// Build a fresh CSPRNG seeded from the OS entropy source.
 ///
@@ -59,6 +65,11 @@ pub struct PasswordOptions {
     pub avoid_ambiguous: bool,
     /// Password length
     pub length: usize,
+    /// Symbols to use instead of the built-in set when `special` is on,
+    /// for sites that only accept certain symbols.
+    pub custom_symbols: Option<String>,
+    /// Characters that must never appear, for sites that ban some of them.
+    pub exclude_chars: Option<String>,
 }
 
 impl Default for PasswordOptions {
@@ -70,6 +81,8 @@ impl Default for PasswordOptions {
             special: false,
             avoid_ambiguous: false,
             length: 16,
+            custom_symbols: None,
+            exclude_chars: None,
         }
     }
 }
@@ -84,94 +97,52 @@ impl Default for PasswordOptions {
 /// * `options` - Password generation options
 ///
 /// # Returns
-/// Generated password string
+/// Generated password string, or `ValidationError` when no class is
+/// selected or exclusions leave a selected class without characters.
 ///
 /// # Example
 /// ```
 /// use iwcore::crypto::password::{generate_password, PasswordOptions};
 ///
 /// let options = PasswordOptions {
-///     lowercase: true,
-///     uppercase: true,
-///     digits: true,
-///     special: false,
-///     avoid_ambiguous: false,
+///     special: true,
+///     custom_symbols: Some("!#".to_string()),
+///     exclude_chars: Some("0O".to_string()),
 ///     length: 12,
+///     ..Default::default()
 /// };
-/// let password = generate_password(&options);
+/// let password = generate_password(&options).unwrap();
 /// assert_eq!(password.len(), 12);
 /// ```
-pub fn generate_password(options: &PasswordOptions) -> String {
+pub fn generate_password(options: &PasswordOptions) -> Result<String> {
+    let classes = password_classes(options)?;
     let mut rng = csprng();
-    let mut char_pool = String::new();
-
-    // Build weighted character pool (matching C# implementation: letters×3, digits×2)
-    if options.lowercase {
-        char_pool.push_str(LOWER_LETTERS);
-        char_pool.push_str(LOWER_LETTERS);
-        char_pool.push_str(LOWER_LETTERS);
-    }
-    if options.uppercase {
-        char_pool.push_str(UPPER_LETTERS);
-        char_pool.push_str(UPPER_LETTERS);
-        char_pool.push_str(UPPER_LETTERS);
-    }
-    if options.digits {
-        char_pool.push_str(DIGITS);
-        char_pool.push_str(DIGITS);
-    }
-    if options.special {
-        char_pool.push_str(SPECIAL_SYMBOLS);
-    }
-
-    // If nothing selected, use lowercase as fallback
-    if char_pool.is_empty() {
-        char_pool.push_str(LOWER_LETTERS);
-    }
-
-    // Strip visually-similar characters when requested. Done after pool
-    // construction so the per-class weighting (lower×3, upper×3, digit×2)
-    // is preserved across the surviving characters.
-    if options.avoid_ambiguous {
-        char_pool = char_pool
-            .chars()
-            .filter(|c| !AMBIGUOUS_CHARS.contains(*c))
-            .collect();
-        if char_pool.is_empty() {
-            char_pool.push_str(LOWER_LETTERS);
+
+    // Build weighted character pool (matching C# implementation: letters×3,
+    // digits×2). Filtering happens per class first, so the weighting is
+    // preserved across the surviving characters.
+    let mut pool_chars: Vec<char> = Vec::new();
+    for (chars, weight) in &classes {
+        for _ in 0..*weight {
+            pool_chars.extend_from_slice(chars);
         }
     }
 
-    let pool_chars: Vec<char> = char_pool.chars().collect();
-
     // Guarantee at least one character from EACH selected class. Many
     // third-party password rules ("must contain a digit", "must contain a
     // symbol", etc.) demand this — without it a long random password can
     // randomly fail validation on, say, a banking site.
-    let mut required: Vec<char> = Vec::new();
-    if options.lowercase
-        && let Some(c) = pick_from_class(LOWER_LETTERS, options.avoid_ambiguous, &mut rng) {
-            required.push(c);
-        }
-    if options.uppercase
-        && let Some(c) = pick_from_class(UPPER_LETTERS, options.avoid_ambiguous, &mut rng) {
-            required.push(c);
-        }
-    if options.digits
-        && let Some(c) = pick_from_class(DIGITS, options.avoid_ambiguous, &mut rng) {
-            required.push(c);
-        }
-    if options.special
-        && let Some(c) = pick_from_class(SPECIAL_SYMBOLS, options.avoid_ambiguous, &mut rng) {
-            required.push(c);
-        }
+    let mut required: Vec<char> = classes
+        .iter()
+        .map(|(chars, _)| chars[rng.random_range(0..chars.len())])
+        .collect();
 
     // If the user asked for a length shorter than the number of selected
     // classes, shuffle the required chars and truncate. (Practically
     // impossible — UI enforces length ≥ 8 — but we handle it cleanly.)
     if options.length <= required.len() {
         shuffle(&mut required, &mut rng);
-        return required.into_iter().take(options.length).collect();
+        return Ok(required.into_iter().take(options.length).collect());
     }
 
     // Fill the rest from the weighted pool.
@@ -184,23 +155,51 @@ pub fn generate_password(options: &PasswordOptions) -> String {
 
     // Shuffle so the guaranteed chars aren't always at the start.
     shuffle(&mut password_chars, &mut rng);
-    password_chars.into_iter().collect()
+    Ok(password_chars.into_iter().collect())
 }
 
-/// Pick a single random character from a character-class string, optionally
-/// honouring the avoid-ambiguous filter. Returns None if every char in the
-/// class is filtered out (shouldn't happen for the current ambiguous set
-/// but guarded for future-proofing).
-fn pick_from_class(class: &str, avoid_ambiguous: bool, rng: &mut StdRng) -> Option<char> {
-    let candidates: Vec<char> = class
-        .chars()
-        .filter(|c| !avoid_ambiguous || !AMBIGUOUS_CHARS.contains(*c))
-        .collect();
-    if candidates.is_empty() {
-        None
-    } else {
-        Some(candidates[rng.random_range(0..candidates.len())])
+/// Selected character classes with their pool weight, after removing
+/// ambiguous and excluded characters. Errors if nothing is selected or a
+/// selected class ends up empty.
+fn password_classes(options: &PasswordOptions) -> Result<Vec<(Vec<char>, usize)>> {
+    let symbols = options.custom_symbols.as_deref().unwrap_or(SPECIAL_SYMBOLS);
+    let excluded = options.exclude_chars.as_deref().unwrap_or("");
+
+    let mut classes = Vec::new();
+    for (selected, name, chars, weight) in [
+        (options.lowercase, "lowercase letters", LOWER_LETTERS, 3),
+        (options.uppercase, "uppercase letters", UPPER_LETTERS, 3),
+        (options.digits, "digits", DIGITS, 2),
+        (options.special, "symbols", symbols, 1),
+    ] {
+        if !selected {
+            continue;
+        }
+        let mut kept: Vec<char> = Vec::new();
+        for c in chars.chars() {
+            if c.is_whitespace()
+                || excluded.contains(c)
+                || (options.avoid_ambiguous && AMBIGUOUS_CHARS.contains(c))
+                || kept.contains(&c)
+            {
+                continue;
+            }
+            kept.push(c);
+        }
+        if kept.is_empty() {
+            return Err(WalletError::ValidationError(format!(
+                "No {name} left to choose from after exclusions"
+            )));
+        }
+        classes.push((kept, weight));
+    }
+
+    if classes.is_empty() {
+        return Err(WalletError::ValidationError(
+            "Select at least one character class".to_string(),
+        ));
     }
+    Ok(classes)
 }
 
 /// Fisher-Yates shuffle in place using the given CSPRNG.
@@ -211,6 +210,168 @@ fn shuffle<T>(items: &mut [T], rng: &mut StdRng) {
     }
 }
 
+/// One position of a clever-password pattern
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatternToken {
+    /// Any lowercase letter in the pattern
+    Lower,
+    /// Any uppercase letter in the pattern
+    Upper,
+    /// Any digit in the pattern
+    Digit,
+    /// Any special symbol in the pattern
+    Special,
+    /// `\a`, or a character outside every class: random from all classes
+    Any,
+    /// `\w`: a random word from the memorable wordlist
+    Word,
+    /// `\h`: a random lowercase hex digit
+    Hex,
+    /// `\` followed by any other character: that character, unchanged
+    Literal(char),
+}
+
+/// Result of [`validate_pattern`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatternInfo {
+    pub tokens: Vec<PatternToken>,
+    /// Shortest possible output length in characters
+    pub min_length: usize,
+    /// Longest possible output length in characters
+    pub max_length: usize,
+}
+
+const HEX_DIGITS: &str = "0123456789abcdef";
+
+/// Split a pattern into tokens. In strict mode a trailing `\` or a
+/// character outside every class is an error; otherwise they become a
+/// literal backslash and `Any`, as the original generator did.
+fn parse_pattern(pattern: &str, strict: bool) -> Result<Vec<PatternToken>> {
+    let mut tokens = Vec::with_capacity(pattern.len());
+    let mut chars = pattern.chars().enumerate();
+    while let Some((pos, ch)) = chars.next() {
+        let token = if ch == '\\' {
+            match chars.next() {
+                Some((_, 'w')) => PatternToken::Word,
+                Some((_, 'h')) => PatternToken::Hex,
+                Some((_, 'a')) => PatternToken::Any,
+                Some((_, c)) => PatternToken::Literal(c),
+                None if strict => {
+                    return Err(WalletError::ValidationError(format!(
+                        "Pattern ends with an unfinished escape at position {pos}"
+                    )));
+                }
+                None => PatternToken::Literal('\\'),
+            }
+        } else if LOWER_LETTERS.contains(ch) {
+            PatternToken::Lower
+        } else if UPPER_LETTERS.contains(ch) {
+            PatternToken::Upper
+        } else if DIGITS.contains(ch) {
+            PatternToken::Digit
+        } else if SPECIAL_SYMBOLS.contains(ch) {
+            PatternToken::Special
+        } else if strict {
+            return Err(WalletError::ValidationError(format!(
+                "Unknown pattern character {ch:?} at position {pos}; use \\a for any character or \\{ch} for a literal"
+            )));
+        } else {
+            PatternToken::Any
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// Check a clever-password pattern and describe what it will produce.
+///
+/// Besides the classic one-character-per-class pattern, these escapes are
+/// understood:
+/// - `\w` — a random word from the memorable wordlist
+/// - `\h` — a random lowercase hex digit
+/// - `\a` — a random character from all classes
+/// - `\` followed by any other character — that character literally
+///   (e.g. `\-` or `\\`)
+///
+/// Characters outside every class (spaces, accented letters, ...) are
+/// rejected, as is a pattern ending in a lone backslash.
+pub fn validate_pattern(pattern: &str) -> Result<PatternInfo> {
+    let tokens = parse_pattern(pattern, true)?;
+    let word_len = |pick: fn(usize, usize) -> usize| {
+        WORDS.iter().map(|w| w.len()).reduce(pick).unwrap_or(0)
+    };
+    let (min_word, max_word) = (word_len(usize::min), word_len(usize::max));
+    let (mut min_length, mut max_length) = (0, 0);
+    for token in &tokens {
+        if *token == PatternToken::Word {
+            min_length += min_word;
+            max_length += max_word;
+        } else {
+            min_length += 1;
+            max_length += 1;
+        }
+    }
+    Ok(PatternInfo { tokens, min_length, max_length })
+}
+
+/// Rough strength rating for a password's entropy, for UI warnings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PasswordStrength {
+    /// Under 28 bits: found by online guessing
+    VeryWeak,
+    /// 28 to 35 bits
+    Weak,
+    /// 36 to 59 bits
+    Fair,
+    /// 60 to 127 bits
+    Strong,
+    /// 128 bits or more
+    VeryStrong,
+}
+
+impl PasswordStrength {
+    /// Rate an entropy estimate in bits
+    pub fn from_bits(bits: f64) -> Self {
+        if bits < 28.0 {
+            Self::VeryWeak
+        } else if bits < 36.0 {
+            Self::Weak
+        } else if bits < 60.0 {
+            Self::Fair
+        } else if bits < 128.0 {
+            Self::Strong
+        } else {
+            Self::VeryStrong
+        }
+    }
+}
+
+/// Entropy in bits of passwords made by `generate_clever_password` from
+/// `pattern`, assuming the attacker knows the pattern. That is the honest
+/// figure: "Aaaa0000" looks like eight random characters but only gives
+/// about 32 bits, since every position has a known class.
+///
+/// Rate the result with [`PasswordStrength::from_bits`]. Errors like
+/// [`validate_pattern`].
+pub fn pattern_entropy_bits(pattern: &str) -> Result<f64> {
+    let any = (LOWER_LETTERS.len() + UPPER_LETTERS.len() + DIGITS.len() + SPECIAL_SYMBOLS.len()) as f64;
+    let bits = validate_pattern(pattern)?
+        .tokens
+        .iter()
+        .map(|token| match token {
+            PatternToken::Lower => (LOWER_LETTERS.len() as f64).log2(),
+            PatternToken::Upper => (UPPER_LETTERS.len() as f64).log2(),
+            PatternToken::Digit => (DIGITS.len() as f64).log2(),
+            PatternToken::Special => (SPECIAL_SYMBOLS.len() as f64).log2(),
+            PatternToken::Any => any.log2(),
+            PatternToken::Hex => (HEX_DIGITS.len() as f64).log2(),
+            PatternToken::Word => (WORDS.len() as f64).log2(),
+            PatternToken::Literal(_) => 0.0,
+        })
+        .sum();
+    Ok(bits)
+}
+
 /// Generate a password based on a pattern.
 ///
 /// This matches the original C# `GenerateCleverPassword` function.
@@ -222,6 +383,10 @@ fn shuffle<T>(items: &mut [T], rng: &mut StdRng) {
 /// - special symbol -> random special symbol
 /// - anything else -> random from all characters
 ///
+/// The escapes described in [`validate_pattern`] are supported as well.
+/// This function is lenient; call `validate_pattern` first to reject
+/// patterns with unknown characters.
+///
 /// # Arguments
 /// * `pattern` - Password pattern (e.g., "Aaaa0000" for uppercase + 3 lower + 4 digits)
 ///
@@ -235,6 +400,9 @@ fn shuffle<T>(items: &mut [T], rng: &mut StdRng) {
 /// let password = generate_clever_password("Aaaa0000");
 /// assert_eq!(password.len(), 8);
 /// // First char is uppercase, next 3 lowercase, last 4 digits
+///
+/// let password = generate_clever_password("\\w\\-0000");
+/// assert!(password.contains('-'));
 /// ```
 pub fn generate_clever_password(pattern: &str) -> String {
     let mut rng = csprng();
@@ -244,22 +412,22 @@ pub fn generate_clever_password(pattern: &str) -> String {
     let upper_chars: Vec<char> = UPPER_LETTERS.chars().collect();
     let digit_chars: Vec<char> = DIGITS.chars().collect();
     let special_chars: Vec<char> = SPECIAL_SYMBOLS.chars().collect();
+    let hex_chars: Vec<char> = HEX_DIGITS.chars().collect();
 
     let mut password = String::with_capacity(pattern.len());
 
-    for ch in pattern.chars() {
-        let generated_char = if LOWER_LETTERS.contains(ch) {
-            lower_chars[rng.random_range(0..lower_chars.len())]
-        } else if UPPER_LETTERS.contains(ch) {
-            upper_chars[rng.random_range(0..upper_chars.len())]
-        } else if DIGITS.contains(ch) {
-            digit_chars[rng.random_range(0..digit_chars.len())]
-        } else if SPECIAL_SYMBOLS.contains(ch) {
-            special_chars[rng.random_range(0..special_chars.len())]
-        } else {
-            all_chars[rng.random_range(0..all_chars.len())]
-        };
-        password.push(generated_char);
+    // Lenient parsing cannot fail
+    for token in parse_pattern(pattern, false).unwrap_or_default() {
+        match token {
+            PatternToken::Lower => password.push(lower_chars[rng.random_range(0..lower_chars.len())]),
+            PatternToken::Upper => password.push(upper_chars[rng.random_range(0..upper_chars.len())]),
+            PatternToken::Digit => password.push(digit_chars[rng.random_range(0..digit_chars.len())]),
+            PatternToken::Special => password.push(special_chars[rng.random_range(0..special_chars.len())]),
+            PatternToken::Any => password.push(all_chars[rng.random_range(0..all_chars.len())]),
+            PatternToken::Hex => password.push(hex_chars[rng.random_range(0..hex_chars.len())]),
+            PatternToken::Word => password.push_str(WORDS[rng.random_range(0..WORDS.len())]),
+            PatternToken::Literal(c) => password.push(c),
+        }
     }
 
     password
@@ -362,6 +530,30 @@ fn apply_caps(word: &str, caps: MemorableCaps) -> String {
     }
 }
 
+/// NFC form of a master password. The same text can arrive composed or
+/// decomposed depending on platform and keyboard (macOS input often
+/// decomposes accented letters); keys are derived from the NFC form so
+/// every platform unlocks the vault with what the user sees as the same
+/// password.
+pub fn normalize_master_password(password: &str) -> String {
+    password.nfc().collect()
+}
+
+/// Check a new master password's length, counted in characters of its NFC
+/// form, against `PASSWORD_MIN_LENGTH..=PASSWORD_MAX_LENGTH`. Applied when
+/// a password is set; unlocking accepts any length, so vaults whose
+/// password predates the check keep opening.
+pub fn check_master_password_length(password: &str) -> Result<()> {
+    let len = normalize_master_password(password).chars().count();
+    if len < crate::PASSWORD_MIN_LENGTH {
+        return Err(WalletError::PasswordTooShort { len, min: crate::PASSWORD_MIN_LENGTH });
+    }
+    if len > crate::PASSWORD_MAX_LENGTH {
+        return Err(WalletError::PasswordTooLong { len, max: crate::PASSWORD_MAX_LENGTH });
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -370,7 +562,7 @@ mod tests {
     #[test]
     fn test_generate_password_default() {
         let options = PasswordOptions::default();
-        let password = generate_password(&options);
+        let password = generate_password(&options).unwrap();
         assert_eq!(password.len(), 16);
     }
 
@@ -380,7 +572,7 @@ mod tests {
             length: 32,
             ..Default::default()
         };
-        let password = generate_password(&options);
+        let password = generate_password(&options).unwrap();
         assert_eq!(password.len(), 32);
     }
 
@@ -393,8 +585,10 @@ mod tests {
             special: false,
             avoid_ambiguous: false,
             length: 20,
+            custom_symbols: None,
+            exclude_chars: None,
         };
-        let password = generate_password(&options);
+        let password = generate_password(&options).unwrap();
         assert_eq!(password.len(), 20);
         assert!(password.chars().all(|c| c.is_ascii_lowercase()));
     }
@@ -408,8 +602,10 @@ mod tests {
             special: false,
             avoid_ambiguous: false,
             length: 20,
+            custom_symbols: None,
+            exclude_chars: None,
         };
-        let password = generate_password(&options);
+        let password = generate_password(&options).unwrap();
         assert_eq!(password.len(), 20);
         assert!(password.chars().all(|c| c.is_ascii_uppercase()));
     }
@@ -423,8 +619,10 @@ mod tests {
             special: false,
             avoid_ambiguous: false,
             length: 20,
+            custom_symbols: None,
+            exclude_chars: None,
         };
-        let password = generate_password(&options);
+        let password = generate_password(&options).unwrap();
         assert_eq!(password.len(), 20);
         assert!(password.chars().all(|c| c.is_ascii_digit()));
     }
@@ -438,8 +636,10 @@ mod tests {
             special: true,
             avoid_ambiguous: false,
             length: 20,
+            custom_symbols: None,
+            exclude_chars: None,
         };
-        let password = generate_password(&options);
+        let password = generate_password(&options).unwrap();
         assert_eq!(password.len(), 20);
         assert!(password.chars().all(|c| SPECIAL_SYMBOLS.contains(c)));
     }
@@ -453,14 +653,16 @@ mod tests {
             special: true,
             avoid_ambiguous: false,
             length: 100,
+            custom_symbols: None,
+            exclude_chars: None,
         };
-        let password = generate_password(&options);
+        let password = generate_password(&options).unwrap();
         assert_eq!(password.len(), 100);
         // With 100 chars, we should have some of each type (probabilistically)
     }
 
     #[test]
-    fn test_generate_password_empty_options_fallback() {
+    fn test_generate_password_empty_options_error() {
         let options = PasswordOptions {
             lowercase: false,
             uppercase: false,
@@ -468,11 +670,43 @@ mod tests {
             special: false,
             avoid_ambiguous: false,
             length: 10,
+            custom_symbols: None,
+            exclude_chars: None,
         };
-        let password = generate_password(&options);
-        assert_eq!(password.len(), 10);
-        // Should fallback to lowercase
-        assert!(password.chars().all(|c| c.is_ascii_lowercase()));
+        assert!(matches!(generate_password(&options), Err(WalletError::ValidationError(_))));
+    }
+
+    #[test]
+    fn test_generate_password_custom_symbols_and_exclusions() {
+        let options = PasswordOptions {
+            special: true,
+            custom_symbols: Some("!-".to_string()),
+            exclude_chars: Some("aeiouAEIOU0".to_string()),
+            length: 200,
+            ..Default::default()
+        };
+        let pwd = generate_password(&options).unwrap();
+        assert!(pwd.contains('!') || pwd.contains('-'));
+        for c in pwd.chars() {
+            assert!(c.is_ascii_alphanumeric() || c == '!' || c == '-', "unexpected {c:?} in {pwd}");
+            assert!(!"aeiouAEIOU0".contains(c), "excluded {c:?} in {pwd}");
+        }
+
+        // Excluding a whole selected class is an error, not a silent fallback
+        let options = PasswordOptions {
+            lowercase: false,
+            uppercase: false,
+            digits: true,
+            exclude_chars: Some(DIGITS.to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(generate_password(&options), Err(WalletError::ValidationError(_))));
+        let options = PasswordOptions {
+            special: true,
+            custom_symbols: Some(String::new()),
+            ..Default::default()
+        };
+        assert!(generate_password(&options).is_err());
     }
 
     #[test]
@@ -484,8 +718,10 @@ mod tests {
             special: false,
             avoid_ambiguous: true,
             length: 200,
+            custom_symbols: None,
+            exclude_chars: None,
         };
-        let password = generate_password(&options);
+        let password = generate_password(&options).unwrap();
         assert_eq!(password.len(), 200);
         for c in password.chars() {
             assert!(
@@ -507,8 +743,10 @@ mod tests {
             special: false,
             avoid_ambiguous: false,
             length: 1000,
+            custom_symbols: None,
+            exclude_chars: None,
         };
-        let password = generate_password(&options);
+        let password = generate_password(&options).unwrap();
         let saw_any_ambiguous =
             password.chars().any(|c| AMBIGUOUS_CHARS.contains(c));
         assert!(
@@ -528,9 +766,11 @@ mod tests {
             special: true,
             avoid_ambiguous: false,
             length: 8,
+            custom_symbols: None,
+            exclude_chars: None,
         };
         for _ in 0..200 {
-            let pwd = generate_password(&options);
+            let pwd = generate_password(&options).unwrap();
             assert_eq!(pwd.len(), 8);
             assert!(pwd.chars().any(|c| c.is_ascii_lowercase()), "{pwd}");
             assert!(pwd.chars().any(|c| c.is_ascii_uppercase()), "{pwd}");
@@ -552,9 +792,11 @@ mod tests {
             special: false,
             avoid_ambiguous: false,
             length: 100,
+            custom_symbols: None,
+            exclude_chars: None,
         };
         for _ in 0..50 {
-            let pwd = generate_password(&options);
+            let pwd = generate_password(&options).unwrap();
             assert!(
                 pwd.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit()),
                 "unexpected char in {pwd}",
@@ -572,9 +814,11 @@ mod tests {
             special: true,
             avoid_ambiguous: true,
             length: 8,
+            custom_symbols: None,
+            exclude_chars: None,
         };
         for _ in 0..200 {
-            let pwd = generate_password(&options);
+            let pwd = generate_password(&options).unwrap();
             assert!(pwd.chars().any(|c| c.is_ascii_lowercase()));
             assert!(pwd.chars().any(|c| c.is_ascii_uppercase()));
             assert!(pwd.chars().any(|c| c.is_ascii_digit()));
@@ -588,8 +832,8 @@ mod tests {
     #[test]
     fn test_generate_password_uniqueness() {
         let options = PasswordOptions::default();
-        let p1 = generate_password(&options);
-        let p2 = generate_password(&options);
+        let p1 = generate_password(&options).unwrap();
+        let p2 = generate_password(&options).unwrap();
         // Passwords should be different (extremely high probability)
         assert_ne!(p1, p2);
     }
@@ -637,6 +881,61 @@ mod tests {
         assert_eq!(password.len(), 4);
     }
 
+    #[test]
+    fn test_generate_clever_password_extended_tokens() {
+        for _ in 0..50 {
+            let pwd = generate_clever_password("\\w\\-\\h\\h\\\\A");
+            let (word, rest) = pwd.split_once('-').unwrap();
+            assert!(WORDS.contains(&word), "{pwd}");
+            let rest: Vec<char> = rest.chars().collect();
+            assert_eq!(rest.len(), 4);
+            assert!(HEX_DIGITS.contains(rest[0]) && HEX_DIGITS.contains(rest[1]));
+            assert_eq!(rest[2], '\\');
+            assert!(rest[3].is_ascii_uppercase());
+        }
+    }
+
+    #[test]
+    fn test_validate_pattern() {
+        let info = validate_pattern("Aa0!").unwrap();
+        assert_eq!(info.tokens, vec![
+            PatternToken::Upper, PatternToken::Lower, PatternToken::Digit, PatternToken::Special,
+        ]);
+        assert_eq!((info.min_length, info.max_length), (4, 4));
+
+        let info = validate_pattern("\\w\\ \\a\\h").unwrap();
+        assert_eq!(info.tokens, vec![
+            PatternToken::Word, PatternToken::Literal(' '), PatternToken::Any, PatternToken::Hex,
+        ]);
+        let min_word = WORDS.iter().map(|w| w.len()).min().unwrap();
+        let max_word = WORDS.iter().map(|w| w.len()).max().unwrap();
+        assert_eq!((info.min_length, info.max_length), (min_word + 3, max_word + 3));
+
+        assert!(matches!(validate_pattern("Aa 0"), Err(WalletError::ValidationError(_))));
+        assert!(matches!(validate_pattern("Aaé"), Err(WalletError::ValidationError(_))));
+        assert!(matches!(validate_pattern("Aa\\"), Err(WalletError::ValidationError(_))));
+        assert_eq!(validate_pattern("").unwrap().tokens, vec![]);
+        // The lenient generator still accepts what validation rejects
+        assert_eq!(generate_clever_password("Aa\\").chars().last(), Some('\\'));
+    }
+
+    #[test]
+    fn test_pattern_entropy_bits() {
+        let bits = pattern_entropy_bits("Aaaa0000").unwrap();
+        assert!((bits - (4.0 * 26f64.log2() + 4.0 * 10f64.log2())).abs() < 1e-9);
+        assert_eq!(PasswordStrength::from_bits(bits), PasswordStrength::Weak);
+
+        assert_eq!(pattern_entropy_bits("\\-\\-").unwrap(), 0.0);
+        assert_eq!(pattern_entropy_bits("\\w\\w\\w").unwrap(), 30.0);
+        assert_eq!(pattern_entropy_bits("\\h").unwrap(), 4.0);
+        assert_eq!(PasswordStrength::from_bits(pattern_entropy_bits("0000").unwrap()), PasswordStrength::VeryWeak);
+        assert_eq!(
+            PasswordStrength::from_bits(pattern_entropy_bits(&"\\a".repeat(12)).unwrap()),
+            PasswordStrength::Strong
+        );
+        assert!(pattern_entropy_bits("a b").is_err());
+    }
+
     #[test]
     fn test_generate_clever_password_empty() {
         let password = generate_clever_password("");
@@ -796,4 +1095,22 @@ mod tests {
         // is swapped out.
         assert_eq!(WORDS.len(), 1024);
     }
+
+    #[test]
+    fn test_master_password_normalization_and_length() {
+        assert_eq!(normalize_master_password("e\u{301}"), "\u{e9}");
+        assert_eq!(normalize_master_password("plain"), "plain");
+
+        assert!(check_master_password_length("abc").is_ok());
+        assert!(matches!(
+            check_master_password_length("ab"),
+            Err(WalletError::PasswordTooShort { len: 2, min: 3 })
+        ));
+        // Decomposed input is counted after composition
+        assert!(check_master_password_length(&"e\u{301}".repeat(32)).is_ok());
+        assert!(matches!(
+            check_master_password_length(&"a".repeat(33)),
+            Err(WalletError::PasswordTooLong { len: 33, max: 32 })
+        ));
+    }
 }
@@ -0,0 +1,138 @@
+//! Public-key encryption to age (X25519) recipients.
+//!
+//! Passphrase-protected exports need the passphrase to travel alongside the
+//! file. Encrypting to recipients instead lets a device publish its public
+//! key ("age1...") once; anything encrypted to that key opens only with the
+//! matching secret identity ("AGE-SECRET-KEY-1..."), which never leaves the
+//! device. Output is a standard age file, readable by the `age` tool.
+
+use std::io::{Read, Write};
+use std::iter;
+use std::str::FromStr;
+
+use age::secrecy::ExposeSecret;
+use age::x25519;
+use zeroize::Zeroizing;
+
+use super::error::DecryptError;
+
+/// First line of every age file
+const AGE_MAGIC: &[u8] = b"age-encryption.org/v1\n";
+
+/// A secret X25519 identity
+pub struct Identity(x25519::Identity);
+
+impl Identity {
+    /// Generate a new identity from the OS CSPRNG.
+    pub fn generate() -> Self {
+        Self(x25519::Identity::generate())
+    }
+
+    /// The secret key in its "AGE-SECRET-KEY-1..." form, for storing on the
+    /// device that owns it.
+    pub fn to_secret_string(&self) -> Zeroizing<String> {
+        Zeroizing::new(self.0.to_string().expose_secret().to_string())
+    }
+
+    /// The public key ("age1...") that others encrypt to.
+    pub fn recipient(&self) -> String {
+        self.0.to_public().to_string()
+    }
+}
+
+impl FromStr for Identity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.trim().parse().map(Self).map_err(|_| "Invalid age identity".to_string())
+    }
+}
+
+/// Returns true if `bytes` start with the age file header.
+pub fn is_age_file(bytes: &[u8]) -> bool {
+    bytes.starts_with(AGE_MAGIC)
+}
+
+fn parse_recipient(recipient: &str) -> Result<x25519::Recipient, String> {
+    recipient.trim().parse()
+        .map_err(|e| format!("Invalid age recipient {recipient:?}: {e}"))
+}
+
+fn parse_recipients(recipients: &[&str]) -> Result<Vec<x25519::Recipient>, String> {
+    if recipients.is_empty() {
+        return Err("At least one age recipient is required".to_string());
+    }
+    recipients.iter().map(|r| parse_recipient(r)).collect()
+}
+
+/// Check that `recipients` is non-empty and every entry is an "age1..."
+/// X25519 public key.
+pub fn validate_recipients(recipients: &[&str]) -> Result<(), String> {
+    parse_recipients(recipients).map(|_| ())
+}
+
+/// Encrypt `plaintext` so that any one of `recipients` can decrypt it.
+/// At least one recipient is required and every one must be valid.
+pub fn encrypt(plaintext: &[u8], recipients: &[&str]) -> Result<Vec<u8>, String> {
+    let parsed = parse_recipients(recipients)?;
+
+    let encryptor = age::Encryptor::with_recipients(parsed.iter().map(|r| r as &dyn age::Recipient))
+        .map_err(|e| format!("age encryption failed: {e}"))?;
+    let mut out = Vec::with_capacity(plaintext.len() + 256);
+    let mut writer = encryptor.wrap_output(&mut out)
+        .map_err(|e| format!("age encryption failed: {e}"))?;
+    writer.write_all(plaintext)
+        .and_then(|_| writer.finish())
+        .map_err(|e| format!("age encryption failed: {e}"))?;
+    Ok(out)
+}
+
+/// Decrypt an age file with `identity`. A file not encrypted to the
+/// identity, a damaged file and anything that is not an age file all
+/// report [`DecryptError::WrongKeyOrCorrupt`].
+pub fn decrypt(ciphertext: &[u8], identity: &Identity) -> Result<Zeroizing<Vec<u8>>, DecryptError> {
+    if ciphertext.is_empty() {
+        return Err(DecryptError::EmptyCiphertext);
+    }
+    let decryptor = age::Decryptor::new_buffered(ciphertext)
+        .map_err(|_| DecryptError::WrongKeyOrCorrupt)?;
+    let mut reader = decryptor.decrypt(iter::once(&identity.0 as &dyn age::Identity))
+        .map_err(|_| DecryptError::WrongKeyOrCorrupt)?;
+    let mut plaintext = Zeroizing::new(Vec::new());
+    reader.read_to_end(&mut plaintext).map_err(|_| DecryptError::WrongKeyOrCorrupt)?;
+    Ok(plaintext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_to_several_recipients() {
+        let laptop = Identity::generate();
+        let phone = Identity::generate();
+        let other = Identity::generate();
+        assert!(laptop.recipient().starts_with("age1"));
+        assert!(laptop.to_secret_string().starts_with("AGE-SECRET-KEY-1"));
+
+        let sealed = encrypt(b"vault export", &[&laptop.recipient(), &phone.recipient()]).unwrap();
+        assert!(is_age_file(&sealed));
+        assert_eq!(decrypt(&sealed, &laptop).unwrap().as_slice(), b"vault export");
+        assert_eq!(decrypt(&sealed, &phone).unwrap().as_slice(), b"vault export");
+        assert_eq!(decrypt(&sealed, &other).unwrap_err(), DecryptError::WrongKeyOrCorrupt);
+
+        // The secret string parses back into the same identity
+        let restored: Identity = laptop.to_secret_string().parse().unwrap();
+        assert_eq!(restored.recipient(), laptop.recipient());
+    }
+
+    #[test]
+    fn test_rejects_bad_recipients_and_input() {
+        let id = Identity::generate();
+        assert!(encrypt(b"x", &[]).is_err());
+        assert!(encrypt(b"x", &[&id.recipient(), "age1notakey"]).is_err());
+        assert!("AGE-SECRET-KEY-1NOPE".parse::<Identity>().is_err());
+        assert_eq!(decrypt(b"not an age file", &id).unwrap_err(), DecryptError::WrongKeyOrCorrupt);
+        assert_eq!(decrypt(b"", &id).unwrap_err(), DecryptError::EmptyCiphertext);
+    }
+}
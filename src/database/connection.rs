@@ -111,6 +111,23 @@ impl Database {
         self.connection()?.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
         Ok(())
     }
+
+    /// Enable or disable SQLite `secure_delete` for this connection
+    ///
+    /// When on, SQLite overwrites deleted content with zeros instead of just
+    /// unlinking it. The setting is per connection and is not persisted in
+    /// the file, so it must be re-applied after every open.
+    pub fn set_secure_delete(&self, enabled: bool) -> Result<()> {
+        let value = if enabled { "ON" } else { "OFF" };
+        self.connection()?.execute_batch(&format!("PRAGMA secure_delete = {value}"))?;
+        Ok(())
+    }
+
+    /// Whether SQLite `secure_delete` is on for this connection
+    pub fn secure_delete(&self) -> Result<bool> {
+        let value: i64 = self.connection()?.query_row("PRAGMA secure_delete", [], |row| row.get(0))?;
+        Ok(value != 0)
+    }
 }
 
 impl Drop for Database {
@@ -124,6 +141,17 @@ mod tests {
     use super::*;
     use tempfile::TempDir;
 
+    #[test]
+    fn test_secure_delete_toggle() {
+        let temp_dir = TempDir::new().unwrap();
+        let db = Database::create(&temp_dir.path().join("test.db")).unwrap();
+
+        db.set_secure_delete(true).unwrap();
+        assert!(db.secure_delete().unwrap());
+        db.set_secure_delete(false).unwrap();
+        assert!(!db.secure_delete().unwrap());
+    }
+
     #[test]
     fn test_checkpoint_no_error() {
         let temp_dir = TempDir::new().unwrap();
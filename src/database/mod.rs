@@ -10,6 +10,7 @@ pub mod schema;
 pub mod connection;
 pub mod migrations;
 pub mod queries;
+pub mod salvage;
 
 pub use connection::Database;
 pub use models::*;
@@ -52,6 +52,20 @@ pub struct IWItem {
     pub change_timestamp: DateTime<Utc>,
     /// Soft delete flag
     pub deleted: bool,
+    /// Field surfaced for one-tap copy/autofill (usually PASS or 2FAC)
+    #[serde(default)]
+    pub primary_field: Option<String>,
+    /// Display color as `#rrggbb`, for categorizing entries beyond icons
+    #[serde(default)]
+    pub color: Option<String>,
+    /// Manual position within the parent folder (`SortOrder::Custom`);
+    /// `None` until the user arranges the folder
+    #[serde(default)]
+    pub sort_weight: Option<i32>,
+    /// Protected from edits and deletion until unlocked with
+    /// `Wallet::set_item_locked`
+    #[serde(default)]
+    pub locked: bool,
 }
 
 impl IWItem {
@@ -59,6 +73,40 @@ impl IWItem {
     pub fn is_root(&self) -> bool {
         self.item_id == crate::ROOT_ID
     }
+
+    /// Creation time for display, in UTC
+    pub fn created_utc_display(&self, lang: &str) -> String {
+        crate::utils::time::format_utc(&self.create_timestamp, lang)
+    }
+
+    /// Creation time for display, in the device's local time
+    pub fn created_local_display(&self, lang: &str) -> String {
+        crate::utils::time::format_local(&self.create_timestamp, lang)
+    }
+
+    /// Last modification time for display, in UTC
+    pub fn changed_utc_display(&self, lang: &str) -> String {
+        crate::utils::time::format_utc(&self.change_timestamp, lang)
+    }
+
+    /// Last modification time for display, in the device's local time
+    pub fn changed_local_display(&self, lang: &str) -> String {
+        crate::utils::time::format_local(&self.change_timestamp, lang)
+    }
+}
+
+/// Independent top-level hierarchy within one wallet. The default profile
+/// is the `__ROOT__` item; additional profiles are sibling pseudo-roots.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IWProfile {
+    /// Item ID of the profile's root folder
+    pub profile_id: String,
+    /// Profile name (empty for the default profile)
+    pub name: String,
+    /// Icon identifier
+    pub icon: String,
+    /// True for the wallet's original root
+    pub is_default: bool,
 }
 
 /// Field attached to an item
@@ -90,6 +138,18 @@ pub struct IWField {
     pub expiring: bool,
 }
 
+impl IWField {
+    /// Last modification time for display, in UTC
+    pub fn changed_utc_display(&self, lang: &str) -> String {
+        crate::utils::time::format_utc(&self.change_timestamp, lang)
+    }
+
+    /// Last modification time for display, in the device's local time
+    pub fn changed_local_display(&self, lang: &str) -> String {
+        crate::utils::time::format_local(&self.change_timestamp, lang)
+    }
+}
+
 /// Frequency entry for a stored value of a given field type. Returned
 /// by `Wallet::get_top_field_values_by_type` so callers can render
 /// suggestion chips ranked by how often a value has been used.
@@ -157,6 +217,130 @@ pub struct SearchResult {
     pub match_type: SearchMatchType,
 }
 
+/// Search results that share a parent folder (`Wallet::search_grouped`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FolderGroup {
+    /// ID of the folder holding the matches (`ROOT_ID` for the top level)
+    pub folder_id: String,
+    /// Folder names from the top level down; empty for the top level
+    pub path: Vec<String>,
+    /// Number of matches in this folder
+    pub count: usize,
+    /// The matches, in search order
+    pub results: Vec<SearchResult>,
+}
+
+/// Filters for `Wallet::search_streaming`. The default matches `search`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchOptions {
+    /// Match entry names (folders are never matched by name)
+    pub match_names: bool,
+    /// Only match values of these field types; empty means all types
+    pub field_types: Vec<String>,
+    /// Stop after this many results
+    pub limit: Option<usize>,
+    /// Only search items of this profile (root item ID); `None` searches all
+    pub profile_id: Option<String>,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            match_names: true,
+            field_types: Vec::new(),
+            limit: None,
+            profile_id: None,
+        }
+    }
+}
+
+/// Conditions of an item query built with `Wallet::query`. Unset
+/// conditions match everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ItemFilter {
+    /// Direct children of this folder
+    pub parent_id: Option<String>,
+    /// Items having an active field of each of these types
+    pub field_types: Vec<String>,
+    /// Items changed at or after this time
+    pub modified_since: Option<DateTime<Utc>>,
+    /// Folders (`true`) or entries (`false`)
+    pub folder: Option<bool>,
+    /// Locked (`true`) or unlocked (`false`) items
+    pub locked: Option<bool>,
+    /// Items with this display color (`#rrggbb`)
+    pub color: Option<String>,
+    /// Favorites (`true`) or non-favorites (`false`), see
+    /// `Wallet::set_item_favorite`
+    pub favorite: Option<bool>,
+    /// Return at most this many items
+    pub limit: Option<usize>,
+}
+
+/// How `Wallet::copy_item_with` and `Wallet::copy_item_deep` copy. The
+/// default copies like `copy_item`: fresh timestamps, active fields only,
+/// into the source's folder.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CopyOptions {
+    /// Keep the creation and change timestamps of the source item and its
+    /// fields instead of stamping the copy with the current time
+    pub preserve_timestamps: bool,
+    /// Also copy the field history: earlier values kept as deleted fields
+    pub copy_history: bool,
+    /// Also copy the item metadata (see `Wallet::set_item_meta`), where
+    /// apps keep tags and similar annotations
+    pub copy_tags: bool,
+    /// Folder to create the copy in; `None` for the source's folder
+    pub target_parent: Option<String>,
+}
+
+/// How `Wallet::export_json_file` writes the wallet. The default writes the
+/// active records as plain JSON.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct JsonExportOptions {
+    /// Encrypt the document under a key derived from this passphrase;
+    /// `None` writes plain JSON
+    pub passphrase: Option<String>,
+    /// Also export the trash: deleted items, fields and labels, and the
+    /// earlier field values kept as history
+    pub include_deleted: bool,
+}
+
+/// What `Wallet::compact_with` purges. The default purges everything in
+/// the trash, like `compact`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompactOptions {
+    /// Purge deleted items, with their fields and notes
+    pub purge_items: bool,
+    /// Purge deleted fields of active items
+    pub purge_fields: bool,
+    /// Purge deleted labels
+    pub purge_labels: bool,
+    /// Only purge records deleted at least this long ago; `None` purges
+    /// regardless of age
+    pub older_than: Option<std::time::Duration>,
+}
+
+impl Default for CompactOptions {
+    fn default() -> Self {
+        Self {
+            purge_items: true,
+            purge_fields: true,
+            purge_labels: true,
+            older_than: None,
+        }
+    }
+}
+
+/// Records removed by `Wallet::compact_with`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompactResult {
+    pub items: u32,
+    /// Includes the fields of purged items
+    pub fields: u32,
+    pub labels: u32,
+}
+
 /// Type of search match
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum SearchMatchType {
@@ -168,6 +352,46 @@ pub enum SearchMatchType {
     Both,
 }
 
+/// Order of a folder listing (`Wallet::get_items_by_parent_sorted`)
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum SortOrder {
+    /// Folders first, then by name A-Z (the `get_items_by_parent` order)
+    #[default]
+    NameAsc,
+    /// Folders first, then by name Z-A
+    NameDesc,
+    /// Folders first, then newest first
+    CreatedDesc,
+    /// Folders first, then most recently changed first
+    ModifiedDesc,
+    /// The user's manual arrangement (`sort_weight`), folders not forced
+    /// first; items never arranged follow by name
+    Custom,
+}
+
+/// How closely an item matched a URL in `Wallet::find_by_url`. Ordered
+/// best first.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub enum UrlMatchRank {
+    /// A link (or the item name) has exactly the same host
+    ExactDomain,
+    /// Same registrable domain, different subdomain
+    Subdomain,
+    /// The item name mentions the site's name
+    Fuzzy,
+}
+
+/// Result item of a URL lookup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UrlMatch {
+    /// The item that matched
+    pub item: IWItem,
+    /// LINK fields that matched the URL (empty for name-only matches)
+    pub matching_fields: Vec<IWField>,
+    /// Best match rank over the item's name and links
+    pub rank: UrlMatchRank,
+}
+
 /// System field types with their metadata
 pub const SYSTEM_FIELD_TYPES: &[(&str, &str, &str, &str)] = &[
     // (field_type, value_type, icon, label_key)
@@ -219,6 +443,10 @@ mod tests {
             create_timestamp: Utc::now(),
             change_timestamp: Utc::now(),
             deleted: false,
+            primary_field: None,
+            color: None,
+            sort_weight: None,
+            locked: false,
         };
         assert!(root_item.is_root());
 
@@ -231,10 +459,37 @@ mod tests {
             create_timestamp: Utc::now(),
             change_timestamp: Utc::now(),
             deleted: false,
+            primary_field: None,
+            color: None,
+            sort_weight: None,
+            locked: false,
         };
         assert!(!regular_item.is_root());
     }
 
+    #[test]
+    fn test_item_timestamp_display() {
+        use chrono::TimeZone;
+        let ts = Utc.with_ymd_and_hms(2024, 3, 9, 22, 15, 0).unwrap();
+        let item = IWItem {
+            item_id: "abc12345".to_string(),
+            parent_id: Some(crate::ROOT_ID.to_string()),
+            name: "Test".to_string(),
+            icon: "document".to_string(),
+            folder: false,
+            create_timestamp: ts,
+            change_timestamp: ts,
+            deleted: false,
+            primary_field: None,
+            color: None,
+            sort_weight: None,
+            locked: false,
+        };
+        assert_eq!(item.created_utc_display("de"), "09.03.2024 22:15 UTC");
+        assert_eq!(item.changed_utc_display("en"), "03/09/2024 22:15 UTC");
+        assert_eq!(item.changed_local_display("en"), crate::utils::format_local(&ts, "en"));
+    }
+
     #[test]
     fn test_search_match_type() {
         assert_eq!(SearchMatchType::Name, SearchMatchType::Name);
@@ -6,6 +6,7 @@
 use rusqlite::{Connection, OptionalExtension, params};
 use chrono::{DateTime, Utc};
 use crate::error::Result;
+use super::models::IWIcon;
 
 /// Timestamp format used in database
 pub const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
@@ -15,11 +16,31 @@ pub fn format_timestamp(dt: &DateTime<Utc>) -> String {
     dt.format(TIMESTAMP_FORMAT).to_string()
 }
 
-/// Parse a timestamp from database
+/// .NET ticks (100 ns units since 0001-01-01) at the Unix epoch
+const TICKS_AT_UNIX_EPOCH: i64 = 621_355_968_000_000_000;
+
+/// Parse a timestamp from database.
+///
+/// Besides [`TIMESTAMP_FORMAT`], accepts what older app generations wrote:
+/// ISO 8601 with a `T` separator, fractional seconds or an offset, and
+/// sqlite-net's default `DateTime` storage as .NET ticks. Anything else is
+/// `None`, which callers treat as an unknown time.
 pub fn parse_timestamp(s: &str) -> Option<DateTime<Utc>> {
-    chrono::NaiveDateTime::parse_from_str(s, TIMESTAMP_FORMAT)
-        .ok()
-        .map(|ndt| DateTime::from_naive_utc_and_offset(ndt, Utc))
+    let s = s.trim();
+    if let Ok(ndt) = chrono::NaiveDateTime::parse_from_str(s, TIMESTAMP_FORMAT) {
+        return Some(DateTime::from_naive_utc_and_offset(ndt, Utc));
+    }
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    for format in ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"] {
+        if let Ok(ndt) = chrono::NaiveDateTime::parse_from_str(s, format) {
+            return Some(DateTime::from_naive_utc_and_offset(ndt, Utc));
+        }
+    }
+    let ticks: i64 = s.parse().ok().filter(|t| *t >= TICKS_AT_UNIX_EPOCH)?;
+    let since_epoch = ticks - TICKS_AT_UNIX_EPOCH;
+    DateTime::from_timestamp(since_epoch / 10_000_000, (since_epoch % 10_000_000) as u32 * 100)
 }
 
 /// Get current timestamp formatted for database
@@ -57,7 +78,7 @@ pub fn get_properties(conn: &Connection) -> Result<Option<RawProperties>> {
     // read error inside this closure is mapped to Ok(None) below, which would
     // make a perfectly valid vault look like it has no properties row at all.
     let result = conn.query_row(
-        "SELECT database_id, COALESCE(lang, 'en'), COALESCE(version, '4'), email, sync_timestamp, update_timestamp
+        "SELECT database_id, COALESCE(lang, 'en'), COALESCE(version, '4'), email, CAST(sync_timestamp AS TEXT), CAST(update_timestamp AS TEXT)
          FROM nswallet_properties LIMIT 1",
         [],
         |row| {
@@ -101,6 +122,23 @@ pub fn set_db_version(conn: &Connection, version: &str) -> Result<()> {
     Ok(())
 }
 
+/// Update the wallet language
+pub fn set_lang(conn: &Connection, lang: &str) -> Result<()> {
+    set_lang_no_checkpoint(conn, lang)?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(())
+}
+
+/// [`set_lang`] without the WAL checkpoint, for use inside an open
+/// transaction
+pub fn set_lang_no_checkpoint(conn: &Connection, lang: &str) -> Result<()> {
+    conn.execute(
+        "UPDATE nswallet_properties SET lang = ?, update_timestamp = ?",
+        params![lang, now_timestamp()],
+    )?;
+    Ok(())
+}
+
 /// Update the version field WITHOUT a WAL checkpoint. A `PRAGMA wal_checkpoint`
 /// cannot run inside an open write transaction, so the v5->v6 migration (which
 /// runs entirely in one transaction) uses this and checkpoints after COMMIT.
@@ -112,6 +150,219 @@ pub fn set_db_version_no_checkpoint(conn: &Connection, version: &str) -> Result<
     Ok(())
 }
 
+/// Add the unlock-throttle columns to the properties table if missing.
+/// `failed_unlocks` counts consecutive wrong passwords; `last_failed_unlock`
+/// is the time of the latest one.
+pub fn ensure_unlock_throttle_columns(conn: &Connection) -> Result<()> {
+    let mut existing: Vec<String> = Vec::new();
+    {
+        let mut stmt = conn.prepare("PRAGMA table_info(nswallet_properties)")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(1))?;
+        for r in rows {
+            existing.push(r?);
+        }
+    }
+    for (col, ty) in [
+        ("failed_unlocks", "INTEGER DEFAULT 0"),
+        ("last_failed_unlock", "TEXT"),
+    ] {
+        if !existing.iter().any(|c| c == col) {
+            conn.execute(
+                &format!("ALTER TABLE nswallet_properties ADD COLUMN {col} {ty}"),
+                [],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Add the `color` column to the items table if missing. Databases created
+/// before item colors existed lack it.
+pub fn ensure_item_color_column(conn: &Connection) -> Result<()> {
+    if !table_has_column(conn, "nswallet_items", "color")? {
+        conn.execute("ALTER TABLE nswallet_items ADD COLUMN color TEXT", [])?;
+    }
+    Ok(())
+}
+
+/// Add the `sort_weight` column to items of databases created before
+/// manual item ordering existed
+pub fn ensure_item_sort_weight_column(conn: &Connection) -> Result<()> {
+    if !table_has_column(conn, "nswallet_items", "sort_weight")? {
+        conn.execute("ALTER TABLE nswallet_items ADD COLUMN sort_weight INTEGER", [])?;
+    }
+    Ok(())
+}
+
+/// Add the `locked` column to items of databases created before item
+/// locks existed
+pub fn ensure_item_locked_column(conn: &Connection) -> Result<()> {
+    if !table_has_column(conn, "nswallet_items", "locked")? {
+        conn.execute("ALTER TABLE nswallet_items ADD COLUMN locked INTEGER DEFAULT 0", [])?;
+    }
+    Ok(())
+}
+
+/// Add the `meta` envelope columns to items and fields of databases
+/// created before metadata encryption existed
+pub fn ensure_meta_columns(conn: &Connection) -> Result<()> {
+    if !table_has_column(conn, "nswallet_items", "meta")? {
+        conn.execute("ALTER TABLE nswallet_items ADD COLUMN meta BLOB", [])?;
+    }
+    if !table_has_column(conn, "nswallet_fields", "meta")? {
+        conn.execute("ALTER TABLE nswallet_fields ADD COLUMN meta BLOB", [])?;
+    }
+    Ok(())
+}
+
+/// Metadata envelope format version stored in properties (0 = plaintext)
+pub fn get_meta_envelope_version(conn: &Connection) -> Result<i32> {
+    if !table_has_column(conn, "nswallet_properties", "meta_envelope")? {
+        return Ok(0);
+    }
+    let result = conn.query_row(
+        "SELECT COALESCE(meta_envelope, 0) FROM nswallet_properties LIMIT 1",
+        [],
+        |row| row.get(0),
+    );
+    match result {
+        Ok(version) => Ok(version),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(0),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Store the metadata envelope format version, adding the column if missing
+pub fn set_meta_envelope_version(conn: &Connection, version: i32) -> Result<()> {
+    if !table_has_column(conn, "nswallet_properties", "meta_envelope")? {
+        conn.execute("ALTER TABLE nswallet_properties ADD COLUMN meta_envelope INTEGER", [])?;
+    }
+    conn.execute("UPDATE nswallet_properties SET meta_envelope = ?", params![version])?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(())
+}
+
+/// Time of the last auto backup recorded in properties
+pub fn get_last_auto_backup(conn: &Connection) -> Result<Option<DateTime<Utc>>> {
+    if !table_has_column(conn, "nswallet_properties", "last_auto_backup")? {
+        return Ok(None);
+    }
+    let result = conn.query_row(
+        "SELECT CAST(last_auto_backup AS TEXT) FROM nswallet_properties LIMIT 1",
+        [],
+        |row| row.get::<_, Option<String>>(0),
+    );
+    match result {
+        Ok(value) => Ok(value.as_deref().and_then(parse_timestamp)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Record the time of the last auto backup in properties
+pub fn set_last_auto_backup(conn: &Connection, time: &DateTime<Utc>) -> Result<()> {
+    if !table_has_column(conn, "nswallet_properties", "last_auto_backup")? {
+        conn.execute("ALTER TABLE nswallet_properties ADD COLUMN last_auto_backup TEXT", [])?;
+    }
+    conn.execute("UPDATE nswallet_properties SET last_auto_backup = ?", params![format_timestamp(time)])?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(())
+}
+
+/// Sync high-water mark from properties, with the device that set it
+pub fn get_sync_mark(conn: &Connection) -> Result<Option<(DateTime<Utc>, Option<String>)>> {
+    let sql = if table_has_column(conn, "nswallet_properties", "sync_device")? {
+        "SELECT CAST(sync_timestamp AS TEXT), sync_device FROM nswallet_properties LIMIT 1"
+    } else {
+        "SELECT CAST(sync_timestamp AS TEXT), NULL FROM nswallet_properties LIMIT 1"
+    };
+    let result = conn.query_row(sql, [], |row| {
+        Ok((row.get::<_, Option<String>>(0)?, row.get::<_, Option<String>>(1)?))
+    });
+    match result {
+        Ok((time, device)) => Ok(time.as_deref().and_then(parse_timestamp).map(|t| (t, device))),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Record the sync high-water mark and the device that set it
+pub fn set_sync_mark(conn: &Connection, time: &DateTime<Utc>, device_id: &str) -> Result<()> {
+    if !table_has_column(conn, "nswallet_properties", "sync_device")? {
+        conn.execute("ALTER TABLE nswallet_properties ADD COLUMN sync_device TEXT", [])?;
+    }
+    conn.execute(
+        "UPDATE nswallet_properties SET sync_timestamp = ?, sync_device = ?",
+        params![format_timestamp(time), device_id],
+    )?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(())
+}
+
+/// Whether `table` has a column named `column`
+fn table_has_column(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({table})"))?;
+    let rows = stmt.query_map([], |row| row.get::<_, String>(1))?;
+    for r in rows {
+        if r? == column {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// The key check value stored in properties, if any
+pub fn get_key_check(conn: &Connection) -> Result<Option<Vec<u8>>> {
+    if !table_has_column(conn, "nswallet_properties", "key_check")? {
+        return Ok(None);
+    }
+    let result = conn.query_row(
+        "SELECT key_check FROM nswallet_properties LIMIT 1",
+        [],
+        |row| row.get::<_, Option<Vec<u8>>>(0),
+    );
+    match result {
+        Ok(kcv) => Ok(kcv),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Store (or clear) the key check value, adding the column if missing.
+/// No WAL checkpoint, for use inside an open transaction.
+pub fn set_key_check(conn: &Connection, kcv: Option<&[u8]>) -> Result<()> {
+    if !table_has_column(conn, "nswallet_properties", "key_check")? {
+        conn.execute("ALTER TABLE nswallet_properties ADD COLUMN key_check BLOB", [])?;
+    }
+    conn.execute("UPDATE nswallet_properties SET key_check = ?", params![kcv])?;
+    Ok(())
+}
+
+/// Consecutive failed unlocks and the time of the last one.
+/// Call `ensure_unlock_throttle_columns` first.
+pub fn get_unlock_failures(conn: &Connection) -> Result<(u32, Option<DateTime<Utc>>)> {
+    let result = conn.query_row(
+        "SELECT COALESCE(failed_unlocks, 0), CAST(last_failed_unlock AS TEXT) FROM nswallet_properties LIMIT 1",
+        [],
+        |row| Ok((row.get::<_, u32>(0)?, row.get::<_, Option<String>>(1)?)),
+    );
+    match result {
+        Ok((count, last)) => Ok((count, last.as_deref().and_then(parse_timestamp))),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok((0, None)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Store the failed-unlock counter (0 and `None` reset it)
+pub fn set_unlock_failures(conn: &Connection, count: u32, last: Option<&DateTime<Utc>>) -> Result<()> {
+    conn.execute(
+        "UPDATE nswallet_properties SET failed_unlocks = ?, last_failed_unlock = ?",
+        params![count, last.map(format_timestamp)],
+    )?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(())
+}
+
 // ============================================================================
 // v6 crypto key material (nswallet_crypto)
 // ============================================================================
@@ -195,6 +446,11 @@ pub fn set_crypto_record(conn: &Connection, rec: &CryptoRecord) -> Result<()> {
             rec.dek_wrapped
         ],
     )?;
+    // A key check value belongs to the KEK this record was wrapped under;
+    // callers store the new one after rewriting the record.
+    if table_has_column(conn, "nswallet_properties", "key_check")? {
+        conn.execute("UPDATE nswallet_properties SET key_check = NULL", [])?;
+    }
     Ok(())
 }
 
@@ -380,7 +636,8 @@ pub struct QuarantineRow {
 pub fn get_quarantine_rows(conn: &Connection) -> Result<Vec<QuarantineRow>> {
     let mut stmt = conn.prepare(
         "SELECT rowid, record_type, item_id, field_id, blob, parent_id, icon,
-                folder, field_type, sort_weight, create_timestamp, change_timestamp
+                folder, field_type, CAST(sort_weight AS INTEGER), CAST(create_timestamp AS TEXT),
+                CAST(change_timestamp AS TEXT)
          FROM nswallet_quarantine
          ORDER BY CASE record_type WHEN 'item' THEN 0 ELSE 1 END",
     )?;
@@ -420,6 +677,43 @@ pub fn item_row_exists(conn: &Connection, item_id: &str) -> Result<bool> {
     Ok(n > 0)
 }
 
+/// Whether `ancestor_id` is `item_id` itself or one of its ancestors
+pub fn is_self_or_ancestor(conn: &Connection, ancestor_id: &str, item_id: &str) -> Result<bool> {
+    conn.query_row(
+        "WITH RECURSIVE ancestors(id, depth) AS (
+            SELECT ?1, 0
+            UNION
+            SELECT i.parent_id, a.depth + 1 FROM nswallet_items i JOIN ancestors a ON i.item_id = a.id
+             WHERE i.parent_id IS NOT NULL AND a.depth < 1000
+         )
+         SELECT EXISTS(SELECT 1 FROM ancestors WHERE id = ?2)",
+        params![item_id, ancestor_id],
+        |row| row.get(0),
+    ).map_err(Into::into)
+}
+
+/// Whether any field row (any item, any deleted state) uses this field id.
+/// Field ids are looked up on their own elsewhere, so they are kept unique
+/// across the whole table.
+pub fn field_id_exists(conn: &Connection, field_id: &str) -> Result<bool> {
+    let n: u32 = conn.query_row(
+        "SELECT COUNT(*) FROM nswallet_fields WHERE field_id = ?",
+        [field_id],
+        |row| row.get(0),
+    )?;
+    Ok(n > 0)
+}
+
+/// Whether a label row (any deleted state) with this field type exists.
+pub fn label_row_exists(conn: &Connection, field_type: &str) -> Result<bool> {
+    let n: u32 = conn.query_row(
+        "SELECT COUNT(*) FROM nswallet_labels WHERE field_type = ?",
+        [field_type],
+        |row| row.get(0),
+    )?;
+    Ok(n > 0)
+}
+
 /// Whether a live field row with this key exists (any deleted state).
 pub fn field_row_exists(conn: &Connection, item_id: &str, field_id: &str) -> Result<bool> {
     let n: u32 = conn.query_row(
@@ -480,6 +774,222 @@ pub fn restore_quarantined_field(
     Ok(())
 }
 
+/// Insert an item row exactly as given, or overwrite the row with the same
+/// id. For sync tools moving encrypted records between devices.
+pub fn upsert_item_raw(conn: &Connection, item: &RawItem) -> Result<()> {
+    upsert_item_raw_no_checkpoint(conn, item)?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(())
+}
+
+/// [`upsert_item_raw`] without the WAL checkpoint, for use inside an open
+/// transaction
+pub fn upsert_item_raw_no_checkpoint(conn: &Connection, item: &RawItem) -> Result<()> {
+    conn.execute(
+        "INSERT INTO nswallet_items
+            (item_id, parent_id, name, icon, field_id, folder, create_timestamp, change_timestamp, deleted, color, meta,
+             sort_weight, locked)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+         ON CONFLICT(item_id) DO UPDATE SET
+            parent_id = excluded.parent_id, name = excluded.name, icon = excluded.icon,
+            field_id = excluded.field_id, folder = excluded.folder,
+            create_timestamp = excluded.create_timestamp, change_timestamp = excluded.change_timestamp,
+            deleted = excluded.deleted, color = excluded.color, meta = excluded.meta,
+            sort_weight = excluded.sort_weight, locked = excluded.locked",
+        params![
+            item.item_id,
+            item.parent_id,
+            item.name_encrypted,
+            item.icon,
+            item.field_id,
+            item.folder as i32,
+            item.create_timestamp,
+            item.change_timestamp,
+            item.deleted as i32,
+            item.color,
+            item.meta,
+            item.sort_weight,
+            item.locked as i32,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Insert a field row exactly as given, or overwrite the row with the same
+/// key. For sync tools moving encrypted records between devices.
+pub fn upsert_field_raw(conn: &Connection, field: &RawField) -> Result<()> {
+    upsert_field_raw_no_checkpoint(conn, field)?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(())
+}
+
+/// [`upsert_field_raw`] without the WAL checkpoint, for use inside an open
+/// transaction
+pub fn upsert_field_raw_no_checkpoint(conn: &Connection, field: &RawField) -> Result<()> {
+    conn.execute(
+        "INSERT INTO nswallet_fields
+            (item_id, field_id, type, value, change_timestamp, deleted, sort_weight, meta)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+         ON CONFLICT(item_id, field_id) DO UPDATE SET
+            type = excluded.type, value = excluded.value, change_timestamp = excluded.change_timestamp,
+            deleted = excluded.deleted, sort_weight = excluded.sort_weight, meta = excluded.meta",
+        params![
+            field.item_id,
+            field.field_id,
+            field.field_type,
+            field.value_encrypted,
+            field.change_timestamp,
+            field.deleted as i32,
+            field.sort_weight,
+            field.meta,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Which rows `get_item_meta_rows` / `get_field_meta_rows` return
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetaRowFilter {
+    /// Rows that still carry plaintext metadata
+    Unsealed,
+    /// Rows with an envelope
+    Sealed,
+}
+
+/// The metadata columns of one item or field row
+#[derive(Debug, Clone)]
+pub struct MetaRow {
+    pub item_id: String,
+    /// `None` for item rows
+    pub field_id: Option<String>,
+    pub icon: Option<String>,
+    pub create_timestamp: Option<String>,
+    pub change_timestamp: Option<String>,
+    pub meta: Option<Vec<u8>>,
+}
+
+/// Metadata columns of item rows (the root item excluded)
+pub fn get_item_meta_rows(conn: &Connection, filter: MetaRowFilter) -> Result<Vec<MetaRow>> {
+    let condition = match filter {
+        MetaRowFilter::Unsealed => "(COALESCE(icon, '') != '' OR create_timestamp IS NOT NULL
+                                     OR change_timestamp IS NOT NULL)",
+        MetaRowFilter::Sealed => "meta IS NOT NULL",
+    };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT item_id, CAST(icon AS TEXT), CAST(create_timestamp AS TEXT), CAST(change_timestamp AS TEXT), meta
+         FROM nswallet_items WHERE item_id != '__ROOT__' AND {condition}"
+    ))?;
+    let rows = stmt.query_map([], |row| {
+        Ok(MetaRow {
+            item_id: row.get(0)?,
+            field_id: None,
+            icon: row.get(1)?,
+            create_timestamp: row.get(2)?,
+            change_timestamp: row.get(3)?,
+            meta: row.get(4)?,
+        })
+    })?;
+    rows.collect::<std::result::Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// Metadata columns of field rows
+pub fn get_field_meta_rows(conn: &Connection, filter: MetaRowFilter) -> Result<Vec<MetaRow>> {
+    let condition = match filter {
+        MetaRowFilter::Unsealed => "change_timestamp IS NOT NULL",
+        MetaRowFilter::Sealed => "meta IS NOT NULL",
+    };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT item_id, field_id, CAST(change_timestamp AS TEXT), meta FROM nswallet_fields WHERE {condition}"
+    ))?;
+    let rows = stmt.query_map([], |row| {
+        Ok(MetaRow {
+            item_id: row.get(0)?,
+            field_id: Some(row.get(1)?),
+            icon: None,
+            create_timestamp: None,
+            change_timestamp: row.get(2)?,
+            meta: row.get(3)?,
+        })
+    })?;
+    rows.collect::<std::result::Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// Create the connection's triggers that seal the plaintext metadata of
+/// every item and field row in the statement that writes it, through the
+/// `iw_seal_meta(meta, icon, created, changed)` SQL function. TEMP: they
+/// live and die with the connection, next to the function.
+pub fn create_meta_seal_triggers(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TEMP TRIGGER IF NOT EXISTS iw_seal_item_insert AFTER INSERT ON main.nswallet_items
+         WHEN NEW.item_id != '__ROOT__' AND (COALESCE(NEW.icon, '') != ''
+              OR NEW.create_timestamp IS NOT NULL OR NEW.change_timestamp IS NOT NULL)
+         BEGIN
+             UPDATE nswallet_items SET
+                 meta = iw_seal_meta(NEW.meta, CAST(NEW.icon AS TEXT), CAST(NEW.create_timestamp AS TEXT),
+                                     CAST(NEW.change_timestamp AS TEXT)),
+                 icon = '', create_timestamp = NULL, change_timestamp = NULL
+             WHERE item_id = NEW.item_id;
+         END;
+         CREATE TEMP TRIGGER IF NOT EXISTS iw_seal_item_update
+         AFTER UPDATE OF icon, create_timestamp, change_timestamp ON main.nswallet_items
+         WHEN NEW.item_id != '__ROOT__' AND (COALESCE(NEW.icon, '') != ''
+              OR NEW.create_timestamp IS NOT NULL OR NEW.change_timestamp IS NOT NULL)
+         BEGIN
+             UPDATE nswallet_items SET
+                 meta = iw_seal_meta(NEW.meta, CAST(NEW.icon AS TEXT), CAST(NEW.create_timestamp AS TEXT),
+                                     CAST(NEW.change_timestamp AS TEXT)),
+                 icon = '', create_timestamp = NULL, change_timestamp = NULL
+             WHERE item_id = NEW.item_id;
+         END;
+         CREATE TEMP TRIGGER IF NOT EXISTS iw_seal_field_insert AFTER INSERT ON main.nswallet_fields
+         WHEN NEW.change_timestamp IS NOT NULL
+         BEGIN
+             UPDATE nswallet_fields SET
+                 meta = iw_seal_meta(NEW.meta, NULL, NULL, CAST(NEW.change_timestamp AS TEXT)),
+                 change_timestamp = NULL
+             WHERE item_id = NEW.item_id AND field_id = NEW.field_id;
+         END;
+         CREATE TEMP TRIGGER IF NOT EXISTS iw_seal_field_update
+         AFTER UPDATE OF change_timestamp ON main.nswallet_fields
+         WHEN NEW.change_timestamp IS NOT NULL
+         BEGIN
+             UPDATE nswallet_fields SET
+                 meta = iw_seal_meta(NEW.meta, NULL, NULL, CAST(NEW.change_timestamp AS TEXT)),
+                 change_timestamp = NULL
+             WHERE item_id = NEW.item_id AND field_id = NEW.field_id;
+         END;"
+    )?;
+    Ok(())
+}
+
+/// Drop the triggers of `create_meta_seal_triggers`
+pub fn drop_meta_seal_triggers(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "DROP TRIGGER IF EXISTS temp.iw_seal_item_insert;
+         DROP TRIGGER IF EXISTS temp.iw_seal_item_update;
+         DROP TRIGGER IF EXISTS temp.iw_seal_field_insert;
+         DROP TRIGGER IF EXISTS temp.iw_seal_field_update;"
+    )?;
+    Ok(())
+}
+
+/// Write the metadata columns of one item or field row as given.
+/// No WAL checkpoint, for use inside an open transaction.
+pub fn write_meta_row(conn: &Connection, row: &MetaRow) -> Result<()> {
+    match &row.field_id {
+        None => conn.execute(
+            "UPDATE nswallet_items SET icon = ?, create_timestamp = ?, change_timestamp = ?, meta = ?
+             WHERE item_id = ?",
+            params![row.icon, row.create_timestamp, row.change_timestamp, row.meta, row.item_id],
+        )?,
+        Some(field_id) => conn.execute(
+            "UPDATE nswallet_fields SET change_timestamp = ?, meta = ? WHERE item_id = ? AND field_id = ?",
+            params![row.change_timestamp, row.meta, row.item_id, field_id],
+        )?,
+    };
+    Ok(())
+}
+
 /// A sample of active encrypted blobs (items first, then fields) used to
 /// verify a password against the DATA when the root record is missing.
 /// NULL/empty blobs are excluded: they cannot verify anything.
@@ -525,27 +1035,143 @@ pub fn get_all_items_raw(conn: &Connection) -> Result<Vec<RawItem>> {
     // whole listing (a single NULL icon used to blank the entire main screen).
     // NULL deleted counts as active, matching the original C# app.
     let mut stmt = conn.prepare(
-        "SELECT item_id, parent_id, COALESCE(name, X''), COALESCE(icon, ''), COALESCE(folder, 0),
-                create_timestamp, change_timestamp, COALESCE(deleted, 0)
+        "SELECT item_id, parent_id, COALESCE(name, X''), COALESCE(CAST(icon AS TEXT), ''), COALESCE(folder, 0),
+                CAST(create_timestamp AS TEXT), CAST(change_timestamp AS TEXT), COALESCE(deleted, 0), field_id, color, meta,
+                CAST(sort_weight AS INTEGER), COALESCE(locked, 0)
          FROM nswallet_items WHERE COALESCE(deleted, 0) = 0"
     )?;
 
-    let items = stmt.query_map([], |row| {
-        Ok(RawItem {
-            item_id: row.get(0)?,
-            parent_id: row.get(1)?,
-            name_encrypted: row.get(2)?,
-            icon: row.get(3)?,
-            folder: row.get::<_, i32>(4)? != 0,
-            create_timestamp: row.get(5)?,
-            change_timestamp: row.get(6)?,
-            deleted: row.get::<_, i32>(7)? != 0,
-        })
-    })?;
+    let items = stmt.query_map([], raw_item_from_row)?;
+
+    items.collect::<std::result::Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// Build a `RawItem` from the columns selected by `get_all_items_raw`
+fn raw_item_from_row(row: &rusqlite::Row) -> rusqlite::Result<RawItem> {
+    Ok(RawItem {
+        item_id: row.get(0)?,
+        parent_id: row.get(1)?,
+        name_encrypted: row.get(2)?,
+        icon: row.get(3)?,
+        folder: row.get::<_, i32>(4)? != 0,
+        create_timestamp: row.get(5)?,
+        change_timestamp: row.get(6)?,
+        deleted: row.get::<_, i32>(7)? != 0,
+        field_id: row.get(8)?,
+        color: row.get(9)?,
+        meta: row.get(10)?,
+        sort_weight: row.get(11)?,
+        locked: row.get::<_, i32>(12)? != 0,
+    })
+}
 
+/// SQL expression for the timestamp in `column` as [`TIMESTAMP_FORMAT`]
+/// text, so stored timestamps compare and sort as text. Handles the
+/// formats [`parse_timestamp`] accepts (ISO 8601 variants via SQLite's
+/// `datetime`, .NET ticks by arithmetic); anything else is NULL.
+pub fn normalized_timestamp_sql(column: &str) -> String {
+    format!(
+        "(CASE WHEN CAST({column} AS TEXT) NOT GLOB '*[^0-9]*' AND length(CAST({column} AS TEXT)) >= 18
+               THEN datetime((CAST({column} AS INTEGER) - {TICKS_AT_UNIX_EPOCH}) / 10000000, 'unixepoch')
+               ELSE datetime(CAST({column} AS TEXT)) END)"
+    )
+}
+
+/// Active items (root excluded) matching the parent, field type, folder,
+/// locked and color conditions of `filter`, in no particular order.
+/// `modified_since` and `limit` are left to the caller, for wallets whose
+/// timestamps are sealed in the metadata envelope; see
+/// [`get_items_raw_page`] for the rest.
+pub fn get_items_raw_filtered(conn: &Connection, filter: &crate::database::ItemFilter) -> Result<Vec<RawItem>> {
+    let Some((sql, args)) = filtered_items_sql(conn, filter)? else {
+        return Ok(Vec::new());
+    };
+    let mut stmt = conn.prepare(&sql)?;
+    let items = stmt.query_map(rusqlite::params_from_iter(args), raw_item_from_row)?;
     items.collect::<std::result::Result<Vec<_>, _>>().map_err(Into::into)
 }
 
+/// Up to `count` active items from `offset` on, matching every condition
+/// of `filter` except `limit`: most recently changed first (then by ID),
+/// `modified_since` compared on normalized timestamps. Rows whose
+/// timestamp does not parse count as changed now, as in `get_items`.
+/// The caller pages on while rows fail to decrypt.
+pub fn get_items_raw_page(
+    conn: &Connection,
+    filter: &crate::database::ItemFilter,
+    offset: usize,
+    count: Option<usize>,
+) -> Result<Vec<RawItem>> {
+    use rusqlite::types::Value;
+
+    let Some((mut sql, mut args)) = filtered_items_sql(conn, filter)? else {
+        return Ok(Vec::new());
+    };
+    let changed = format!("COALESCE({}, datetime('now'))", normalized_timestamp_sql("change_timestamp"));
+    if let Some(since) = &filter.modified_since {
+        sql.push_str(&format!(" AND {changed} >= ?"));
+        args.push(Value::Text(format_timestamp(since)));
+    }
+    sql.push_str(&format!(" ORDER BY {changed} DESC, item_id ASC LIMIT ? OFFSET ?"));
+    args.push(Value::Integer(count.map_or(-1, |c| c as i64)));
+    args.push(Value::Integer(offset as i64));
+
+    let mut stmt = conn.prepare(&sql)?;
+    let items = stmt.query_map(rusqlite::params_from_iter(args), raw_item_from_row)?;
+    items.collect::<std::result::Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// SELECT over the active items with the parent, field type, folder,
+/// locked, color and favorite-candidate conditions of `filter`; `None`
+/// when nothing can match
+fn filtered_items_sql(
+    conn: &Connection,
+    filter: &crate::database::ItemFilter,
+) -> Result<Option<(String, Vec<rusqlite::types::Value>)>> {
+    use rusqlite::types::Value;
+
+    let mut sql = String::from(
+        "SELECT item_id, parent_id, COALESCE(name, X''), COALESCE(CAST(icon AS TEXT), ''), COALESCE(folder, 0),
+                CAST(create_timestamp AS TEXT), CAST(change_timestamp AS TEXT), COALESCE(deleted, 0), field_id, color, meta,
+                CAST(sort_weight AS INTEGER), COALESCE(locked, 0)
+         FROM nswallet_items i WHERE COALESCE(deleted, 0) = 0 AND item_id != '__ROOT__'"
+    );
+    let mut args: Vec<Value> = Vec::new();
+
+    if let Some(parent_id) = &filter.parent_id {
+        sql.push_str(" AND parent_id = ?");
+        args.push(Value::Text(parent_id.clone()));
+    }
+    for field_type in &filter.field_types {
+        sql.push_str(
+            " AND EXISTS (SELECT 1 FROM nswallet_fields f WHERE f.item_id = i.item_id
+                 AND f.type = ? AND COALESCE(f.deleted, 0) = 0)"
+        );
+        args.push(Value::Text(field_type.clone()));
+    }
+    if let Some(folder) = filter.folder {
+        sql.push_str(" AND COALESCE(folder, 0) = ?");
+        args.push(Value::Integer(folder as i64));
+    }
+    if let Some(locked) = filter.locked {
+        sql.push_str(" AND COALESCE(locked, 0) = ?");
+        args.push(Value::Integer(locked as i64));
+    }
+    if let Some(color) = &filter.color {
+        sql.push_str(" AND color = ?");
+        args.push(Value::Text(color.clone()));
+    }
+    // Favorites are marked in the encrypted metadata, so only items that
+    // have metadata can be one; the caller checks the flag itself
+    if filter.favorite == Some(true) {
+        if !item_metadata_table_exists(conn)? {
+            return Ok(None);
+        }
+        sql.push_str(" AND EXISTS (SELECT 1 FROM nswallet_item_metadata m WHERE m.item_id = i.item_id)");
+    }
+    Ok(Some((sql, args)))
+}
+
 /// Get root item (encrypted name). A root row with a NULL/empty name is
 /// treated as missing (`None`): it cannot verify any password, and the
 /// rootless self-healing path then rebuilds it during migration.
@@ -568,6 +1194,16 @@ pub fn has_root_item(conn: &Connection) -> Result<bool> {
     Ok(count > 0)
 }
 
+/// Number of active items and folders, excluding root
+pub fn count_active_items(conn: &Connection) -> Result<u32> {
+    let count: u32 = conn.query_row(
+        "SELECT COUNT(*) FROM nswallet_items WHERE COALESCE(deleted, 0) = 0 AND item_id != '__ROOT__'",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(count)
+}
+
 /// Create a new item
 pub fn create_item(
     conn: &Connection,
@@ -602,39 +1238,215 @@ pub fn create_item_no_checkpoint(
     Ok(())
 }
 
-/// Update item name (encrypted)
-pub fn update_item_name(conn: &Connection, item_id: &str, name_encrypted: &[u8]) -> Result<()> {
+/// Set the creation and change timestamps of an item, e.g. to carry them
+/// over to a copy
+pub fn set_item_timestamps(
+    conn: &Connection,
+    item_id: &str,
+    created: &DateTime<Utc>,
+    changed: &DateTime<Utc>,
+) -> Result<()> {
     conn.execute(
-        "UPDATE nswallet_items SET name = ?, change_timestamp = ? WHERE item_id = ?",
-        params![name_encrypted, now_timestamp(), item_id],
+        "UPDATE nswallet_items SET create_timestamp = ?, change_timestamp = ? WHERE item_id = ?",
+        params![format_timestamp(created), format_timestamp(changed), item_id],
     )?;
     conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
     Ok(())
 }
 
-/// Update item icon
-pub fn update_item_icon(conn: &Connection, item_id: &str, icon: &str) -> Result<()> {
+/// Set the change timestamp of a field, e.g. to carry it over to a copy
+pub fn set_field_change_timestamp(conn: &Connection, item_id: &str, field_id: &str, changed: &DateTime<Utc>) -> Result<()> {
     conn.execute(
-        "UPDATE nswallet_items SET icon = ?, change_timestamp = ? WHERE item_id = ?",
-        params![icon, now_timestamp(), item_id],
+        "UPDATE nswallet_fields SET change_timestamp = ? WHERE item_id = ? AND field_id = ?",
+        params![format_timestamp(changed), item_id, field_id],
     )?;
     conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
     Ok(())
 }
 
-/// Update item parent (move item)
-pub fn update_item_parent(conn: &Connection, item_id: &str, parent_id: &str) -> Result<()> {
-    conn.execute(
-        "UPDATE nswallet_items SET parent_id = ?, change_timestamp = ? WHERE item_id = ?",
-        params![parent_id, now_timestamp(), item_id],
+/// True if an active (not deleted) item with this id exists
+pub fn item_exists(conn: &Connection, item_id: &str) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM nswallet_items WHERE item_id = ? AND COALESCE(deleted, 0) = 0",
+        params![item_id],
+        |row| row.get(0),
     )?;
-    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
-    Ok(())
+    Ok(count > 0)
 }
 
-/// Soft delete an item and cascade to its fields
-pub fn delete_item(conn: &Connection, item_id: &str) -> Result<()> {
-    let now = now_timestamp();
+/// Folder flag of an active item; `None` if there is no such item
+pub fn get_item_folder_flag(conn: &Connection, item_id: &str) -> Result<Option<bool>> {
+    conn.query_row(
+        "SELECT COALESCE(folder, 0) FROM nswallet_items WHERE item_id = ? AND COALESCE(deleted, 0) = 0",
+        params![item_id],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(Into::into)
+}
+
+/// Update item name (encrypted)
+pub fn update_item_name(conn: &Connection, item_id: &str, name_encrypted: &[u8]) -> Result<()> {
+    let rows = conn.execute(
+        "UPDATE nswallet_items SET name = ?, change_timestamp = ? WHERE item_id = ?",
+        params![name_encrypted, now_timestamp(), item_id],
+    )?;
+    if rows == 0 {
+        return Err(crate::error::WalletError::ItemNotFound(item_id.to_string()));
+    }
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(())
+}
+
+/// Update item icon
+pub fn update_item_icon(conn: &Connection, item_id: &str, icon: &str) -> Result<()> {
+    update_item_icon_no_checkpoint(conn, item_id, icon)?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(())
+}
+
+/// [`update_item_icon`] without the WAL checkpoint, for use inside an open
+/// transaction
+pub fn update_item_icon_no_checkpoint(conn: &Connection, item_id: &str, icon: &str) -> Result<()> {
+    let rows = conn.execute(
+        "UPDATE nswallet_items SET icon = ?, change_timestamp = ? WHERE item_id = ?",
+        params![icon, now_timestamp(), item_id],
+    )?;
+    if rows == 0 {
+        return Err(crate::error::WalletError::ItemNotFound(item_id.to_string()));
+    }
+    Ok(())
+}
+
+/// Icon catalog: active rows of `nswallet_icons`, without the image blobs
+pub fn get_icons(conn: &Connection) -> Result<Vec<IWIcon>> {
+    let mut stmt = conn.prepare(
+        "SELECT icon_id, COALESCE(name, ''), COALESCE(group_id, 0), COALESCE(is_circle, 1) \
+         FROM nswallet_icons WHERE COALESCE(deleted, 0) = 0 ORDER BY icon_id"
+    )?;
+    let icons = stmt.query_map([], |row| {
+        Ok(IWIcon {
+            icon_id: row.get(0)?,
+            name: row.get(1)?,
+            group_id: row.get(2)?,
+            is_circle: row.get::<_, i32>(3)? != 0,
+            deleted: false,
+        })
+    })?.collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(icons)
+}
+
+/// Set or clear the item's primary (quick-copy) field
+pub fn update_item_primary_field(conn: &Connection, item_id: &str, field_id: Option<&str>) -> Result<()> {
+    let rows = conn.execute(
+        "UPDATE nswallet_items SET field_id = ?, change_timestamp = ? WHERE item_id = ? AND COALESCE(deleted, 0) = 0",
+        params![field_id, now_timestamp(), item_id],
+    )?;
+    if rows == 0 {
+        return Err(crate::error::WalletError::ItemNotFound(item_id.to_string()));
+    }
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(())
+}
+
+/// Set or clear the item's edit lock
+pub fn update_item_locked(conn: &Connection, item_id: &str, locked: bool) -> Result<()> {
+    let rows = conn.execute(
+        "UPDATE nswallet_items SET locked = ?, change_timestamp = ? WHERE item_id = ? AND COALESCE(deleted, 0) = 0",
+        params![locked as i32, now_timestamp(), item_id],
+    )?;
+    if rows == 0 {
+        return Err(crate::error::WalletError::ItemNotFound(item_id.to_string()));
+    }
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(())
+}
+
+/// Whether the item is locked against edits
+pub fn is_item_locked(conn: &Connection, item_id: &str) -> Result<bool> {
+    let locked: Option<i32> = conn.query_row(
+        "SELECT COALESCE(locked, 0) FROM nswallet_items WHERE item_id = ?",
+        [item_id],
+        |row| row.get(0),
+    ).optional()?;
+    Ok(locked == Some(1))
+}
+
+/// IDs of locked active items in the subtree rooted at `item_id`,
+/// including the item itself
+pub fn get_locked_in_subtree(conn: &Connection, item_id: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "WITH RECURSIVE subtree(id) AS (
+            SELECT ?
+            UNION
+            SELECT i.item_id FROM nswallet_items i JOIN subtree s ON i.parent_id = s.id
+             WHERE COALESCE(i.deleted, 0) = 0
+         )
+         SELECT i.item_id FROM nswallet_items i JOIN subtree s ON i.item_id = s.id
+          WHERE COALESCE(i.locked, 0) = 1 AND COALESCE(i.deleted, 0) = 0"
+    )?;
+    let ids = stmt.query_map([item_id], |row| row.get(0))?
+        .collect::<std::result::Result<Vec<String>, _>>()?;
+    Ok(ids)
+}
+
+/// Set or clear the item's display color
+pub fn update_item_color(conn: &Connection, item_id: &str, color: Option<&str>) -> Result<()> {
+    let rows = conn.execute(
+        "UPDATE nswallet_items SET color = ?, change_timestamp = ? WHERE item_id = ? AND COALESCE(deleted, 0) = 0",
+        params![color, now_timestamp(), item_id],
+    )?;
+    if rows == 0 {
+        return Err(crate::error::WalletError::ItemNotFound(item_id.to_string()));
+    }
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(())
+}
+
+/// Point the item's primary field at `new_field_id` if it was `old_field_id`,
+/// so the choice survives a field being replaced by a new version.
+/// No WAL checkpoint, for use inside an open transaction.
+pub fn follow_primary_field(conn: &Connection, item_id: &str, old_field_id: &str, new_field_id: &str) -> Result<()> {
+    conn.execute(
+        "UPDATE nswallet_items SET field_id = ? WHERE item_id = ? AND field_id = ?",
+        params![new_field_id, item_id, old_field_id],
+    )?;
+    Ok(())
+}
+
+/// Update item parent (move item)
+pub fn update_item_parent(conn: &Connection, item_id: &str, parent_id: &str) -> Result<()> {
+    update_item_parent_no_checkpoint(conn, item_id, parent_id)?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(())
+}
+
+/// [`update_item_parent`] without the WAL checkpoint, for use inside an
+/// open transaction
+pub fn update_item_parent_no_checkpoint(conn: &Connection, item_id: &str, parent_id: &str) -> Result<()> {
+    let rows = conn.execute(
+        "UPDATE nswallet_items SET parent_id = ?, sort_weight = NULL, change_timestamp = ? WHERE item_id = ?",
+        params![parent_id, now_timestamp(), item_id],
+    )?;
+    if rows == 0 {
+        return Err(crate::error::WalletError::ItemNotFound(item_id.to_string()));
+    }
+    Ok(())
+}
+
+/// Set an item's manual sort weight (NULL = not arranged).
+/// No WAL checkpoint, for use inside an open transaction.
+pub fn update_item_sort_weight(conn: &Connection, item_id: &str, sort_weight: Option<i32>) -> Result<()> {
+    conn.execute(
+        "UPDATE nswallet_items SET sort_weight = ?, change_timestamp = ? WHERE item_id = ?",
+        params![sort_weight, now_timestamp(), item_id],
+    )?;
+    Ok(())
+}
+
+/// Soft delete an item and cascade to its fields
+pub fn delete_item(conn: &Connection, item_id: &str) -> Result<()> {
+    let now = now_timestamp();
     conn.execute(
         "UPDATE nswallet_items SET deleted = 1, change_timestamp = ? WHERE item_id = ?",
         params![now, item_id],
@@ -650,8 +1462,9 @@ pub fn delete_item(conn: &Connection, item_id: &str) -> Result<()> {
 /// Get all soft-deleted items from database (encrypted)
 pub fn get_deleted_items_raw(conn: &Connection) -> Result<Vec<RawItem>> {
     let mut stmt = conn.prepare(
-        "SELECT item_id, parent_id, COALESCE(name, X''), COALESCE(icon, ''), COALESCE(folder, 0),
-                create_timestamp, change_timestamp, deleted
+        "SELECT item_id, parent_id, COALESCE(name, X''), COALESCE(CAST(icon AS TEXT), ''), COALESCE(folder, 0),
+                CAST(create_timestamp AS TEXT), CAST(change_timestamp AS TEXT), deleted, field_id, color, meta,
+                CAST(sort_weight AS INTEGER), COALESCE(locked, 0)
          FROM nswallet_items WHERE deleted = 1"
     )?;
 
@@ -665,6 +1478,11 @@ pub fn get_deleted_items_raw(conn: &Connection) -> Result<Vec<RawItem>> {
             create_timestamp: row.get(5)?,
             change_timestamp: row.get(6)?,
             deleted: row.get::<_, i32>(7)? != 0,
+            field_id: row.get(8)?,
+            color: row.get(9)?,
+            meta: row.get(10)?,
+            sort_weight: row.get(11)?,
+            locked: row.get::<_, i32>(12)? != 0,
         })
     })?;
 
@@ -728,7 +1546,7 @@ pub fn get_all_fields_raw(conn: &Connection) -> Result<Vec<RawField>> {
     // NOTE label so the field stays visible; NULL value reads as empty.
     let mut stmt = conn.prepare(
         "SELECT item_id, field_id, COALESCE(type, 'NOTE'), COALESCE(value, X''),
-                change_timestamp, COALESCE(deleted, 0), COALESCE(sort_weight, 0)
+                CAST(change_timestamp AS TEXT), COALESCE(deleted, 0), COALESCE(CAST(sort_weight AS INTEGER), 0), meta
          FROM nswallet_fields WHERE COALESCE(deleted, 0) = 0"
     )?;
 
@@ -741,6 +1559,7 @@ pub fn get_all_fields_raw(conn: &Connection) -> Result<Vec<RawField>> {
             change_timestamp: row.get(4)?,
             deleted: row.get::<_, i32>(5)? != 0,
             sort_weight: row.get(6)?,
+            meta: row.get(7)?,
         })
     })?;
 
@@ -755,13 +1574,27 @@ pub fn create_field(
     field_type: &str,
     value_encrypted: &[u8],
     sort_weight: i32,
+) -> Result<()> {
+    create_field_no_checkpoint(conn, item_id, field_id, field_type, value_encrypted, sort_weight)?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(())
+}
+
+/// Like [`create_field`] but without the trailing WAL checkpoint, for use
+/// inside an open transaction.
+pub fn create_field_no_checkpoint(
+    conn: &Connection,
+    item_id: &str,
+    field_id: &str,
+    field_type: &str,
+    value_encrypted: &[u8],
+    sort_weight: i32,
 ) -> Result<()> {
     conn.execute(
         "INSERT INTO nswallet_fields (item_id, field_id, type, value, change_timestamp, deleted, sort_weight)
          VALUES (?, ?, ?, ?, ?, 0, ?)",
         params![item_id, field_id, field_type, value_encrypted, now_timestamp(), sort_weight],
     )?;
-    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
     Ok(())
 }
 
@@ -797,6 +1630,35 @@ pub fn delete_field(conn: &Connection, item_id: &str, field_id: &str) -> Result<
     Ok(())
 }
 
+/// Replace a field with a new version: soft-delete `old_field_id` and insert
+/// `new_field_id` with the new value, the way `Wallet::update_field` keeps
+/// history. No WAL checkpoint, for use inside an open transaction.
+pub fn replace_field_no_checkpoint(
+    conn: &Connection,
+    item_id: &str,
+    old_field_id: &str,
+    new_field_id: &str,
+    field_type: &str,
+    value_encrypted: &[u8],
+    sort_weight: i32,
+) -> Result<()> {
+    let now = now_timestamp();
+    let rows = conn.execute(
+        "UPDATE nswallet_fields SET deleted = 1, change_timestamp = ? WHERE item_id = ? AND field_id = ? AND deleted = 0",
+        params![now, item_id, old_field_id],
+    )?;
+    if rows == 0 {
+        return Err(crate::error::WalletError::FieldNotFound(old_field_id.to_string()));
+    }
+    conn.execute(
+        "INSERT INTO nswallet_fields (item_id, field_id, type, value, change_timestamp, deleted, sort_weight)
+         VALUES (?, ?, ?, ?, ?, 0, ?)",
+        params![item_id, new_field_id, field_type, value_encrypted, now, sort_weight],
+    )?;
+    follow_primary_field(conn, item_id, old_field_id, new_field_id)?;
+    Ok(())
+}
+
 /// Update field value only (for password change)
 pub fn update_field_value_only(conn: &Connection, item_id: &str, field_id: &str, value_encrypted: &[u8]) -> Result<()> {
     conn.execute(
@@ -810,7 +1672,7 @@ pub fn update_field_value_only(conn: &Connection, item_id: &str, field_id: &str,
 pub fn get_deleted_fields_raw(conn: &Connection) -> Result<Vec<RawField>> {
     let mut stmt = conn.prepare(
         "SELECT item_id, field_id, COALESCE(type, 'NOTE'), COALESCE(value, X''),
-                change_timestamp, deleted, COALESCE(sort_weight, 0)
+                CAST(change_timestamp AS TEXT), deleted, COALESCE(CAST(sort_weight AS INTEGER), 0), meta
          FROM nswallet_fields WHERE deleted = 1"
     )?;
 
@@ -823,6 +1685,7 @@ pub fn get_deleted_fields_raw(conn: &Connection) -> Result<Vec<RawField>> {
             change_timestamp: row.get(4)?,
             deleted: row.get::<_, i32>(5)? != 0,
             sort_weight: row.get(6)?,
+            meta: row.get(7)?,
         })
     })?;
 
@@ -842,57 +1705,1120 @@ pub fn undelete_field(conn: &Connection, item_id: &str, field_id: &str) -> Resul
     Ok(())
 }
 
-/// Permanently purge all soft-deleted records.
-/// Returns (purged_items_count, purged_fields_count).
-pub fn purge_deleted(conn: &Connection) -> Result<(u32, u32)> {
-    // Count before deleting
-    let items_count: u32 = conn.query_row(
-        "SELECT COUNT(*) FROM nswallet_items WHERE deleted = 1",
+/// Overwrite the encrypted payload of every soft-deleted item, field and
+/// orphaned field with zeros of the same length, in place. Run before
+/// `purge_deleted` so the ciphertext is gone from the row itself, not only
+/// from the pages the DELETE frees.
+pub fn overwrite_deleted(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "UPDATE nswallet_fields SET value = zeroblob(length(value))
+         WHERE value IS NOT NULL
+           AND (deleted = 1 OR item_id IN (SELECT item_id FROM nswallet_items WHERE deleted = 1))",
+        [],
+    )?;
+    conn.execute(
+        "UPDATE nswallet_items SET name = zeroblob(length(name))
+         WHERE deleted = 1 AND name IS NOT NULL",
+        [],
+    )?;
+    if item_notes_table_exists(conn)? {
+        conn.execute(
+            "UPDATE nswallet_item_notes SET note = zeroblob(length(note))
+             WHERE item_id IN (SELECT item_id FROM nswallet_items WHERE deleted = 1)",
+            [],
+        )?;
+    }
+    if item_metadata_table_exists(conn)? {
+        conn.execute(
+            "UPDATE nswallet_item_metadata SET metadata = zeroblob(length(metadata))
+             WHERE item_id IN (SELECT item_id FROM nswallet_items WHERE deleted = 1)",
+            [],
+        )?;
+    }
+    if secure_notes_table_exists(conn)? {
+        conn.execute(
+            "UPDATE nswallet_secure_notes SET data = zeroblob(length(data))
+             WHERE item_id IN (SELECT item_id FROM nswallet_items WHERE deleted = 1)",
+            [],
+        )?;
+    }
+    if attachments_table_exists(conn)? {
+        conn.execute(
+            "UPDATE nswallet_attachments
+             SET name = zeroblob(length(name)), mime_type = zeroblob(length(mime_type)), data = zeroblob(length(data))
+             WHERE deleted = 1 OR item_id IN (SELECT item_id FROM nswallet_items WHERE deleted = 1)",
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Item notes (nswallet_item_notes)
+// ============================================================================
+
+/// Create the item notes table. Long note text lives here, one encrypted
+/// blob per item, instead of in `nswallet_fields`, so field listings never
+/// load it.
+pub fn ensure_item_notes_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS nswallet_item_notes (
+            item_id TEXT NOT NULL PRIMARY KEY,
+            note BLOB NOT NULL,
+            change_timestamp TEXT
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// True if the item notes table has been created
+pub fn item_notes_table_exists(conn: &Connection) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='nswallet_item_notes'",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// Get an item's encrypted note
+pub fn get_item_note_raw(conn: &Connection, item_id: &str) -> Result<Option<Vec<u8>>> {
+    if !item_notes_table_exists(conn)? {
+        return Ok(None);
+    }
+    conn.query_row(
+        "SELECT note FROM nswallet_item_notes WHERE item_id = ?",
+        params![item_id],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(Into::into)
+}
+
+/// Insert or replace an item's encrypted note
+pub fn set_item_note_raw(conn: &Connection, item_id: &str, note_encrypted: &[u8]) -> Result<()> {
+    set_item_note_raw_no_checkpoint(conn, item_id, note_encrypted)?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(())
+}
+
+/// [`set_item_note_raw`] without the WAL checkpoint, for use inside an open
+/// transaction
+pub fn set_item_note_raw_no_checkpoint(conn: &Connection, item_id: &str, note_encrypted: &[u8]) -> Result<()> {
+    ensure_item_notes_table(conn)?;
+    conn.execute(
+        "INSERT INTO nswallet_item_notes (item_id, note, change_timestamp) VALUES (?, ?, ?)
+         ON CONFLICT(item_id) DO UPDATE SET note = excluded.note, change_timestamp = excluded.change_timestamp",
+        params![item_id, note_encrypted, now_timestamp()],
+    )?;
+    Ok(())
+}
+
+/// Remove an item's note. Returns true if there was one.
+pub fn delete_item_note(conn: &Connection, item_id: &str) -> Result<bool> {
+    if !item_notes_table_exists(conn)? {
+        return Ok(false);
+    }
+    let rows = conn.execute("DELETE FROM nswallet_item_notes WHERE item_id = ?", params![item_id])?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(rows > 0)
+}
+
+// ============================================================================
+// Secure note bodies (nswallet_secure_notes)
+// ============================================================================
+
+/// Create the secure note table. A secure note's body is split into
+/// separately encrypted chunks, so it can be streamed in and out without
+/// holding the whole text in one blob.
+pub fn ensure_secure_notes_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS nswallet_secure_notes (
+            item_id TEXT NOT NULL,
+            chunk INTEGER NOT NULL,
+            data BLOB NOT NULL,
+            change_timestamp TEXT,
+            PRIMARY KEY (item_id, chunk)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// True if the secure note table has been created
+pub fn secure_notes_table_exists(conn: &Connection) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='nswallet_secure_notes'",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// Number of stored body chunks of an item; 0 if it is not a secure note
+pub fn get_secure_note_chunk_count(conn: &Connection, item_id: &str) -> Result<u32> {
+    if !secure_notes_table_exists(conn)? {
+        return Ok(0);
+    }
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM nswallet_secure_notes WHERE item_id = ?",
+        params![item_id],
+        |row| row.get(0),
+    )?;
+    Ok(count.max(0) as u32)
+}
+
+/// Get one encrypted body chunk of a secure note
+pub fn get_secure_note_chunk(conn: &Connection, item_id: &str, chunk: u32) -> Result<Option<Vec<u8>>> {
+    if !secure_notes_table_exists(conn)? {
+        return Ok(None);
+    }
+    conn.query_row(
+        "SELECT data FROM nswallet_secure_notes WHERE item_id = ? AND chunk = ?",
+        params![item_id, chunk],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(Into::into)
+}
+
+/// Insert or replace one encrypted body chunk. Does not checkpoint, so it
+/// can run inside a transaction.
+pub fn set_secure_note_chunk(conn: &Connection, item_id: &str, chunk: u32, data: &[u8]) -> Result<()> {
+    ensure_secure_notes_table(conn)?;
+    conn.execute(
+        "INSERT INTO nswallet_secure_notes (item_id, chunk, data, change_timestamp) VALUES (?, ?, ?, ?)
+         ON CONFLICT(item_id, chunk) DO UPDATE SET data = excluded.data, change_timestamp = excluded.change_timestamp",
+        params![item_id, chunk, data, now_timestamp()],
+    )?;
+    Ok(())
+}
+
+/// IDs of the items that have a secure note body
+pub fn get_secure_note_item_ids(conn: &Connection) -> Result<Vec<String>> {
+    if !secure_notes_table_exists(conn)? {
+        return Ok(Vec::new());
+    }
+    let mut stmt = conn.prepare("SELECT DISTINCT item_id FROM nswallet_secure_notes ORDER BY item_id")?;
+    let rows = stmt.query_map([], |row| row.get(0))?;
+    rows.collect::<std::result::Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// Remove the body chunks of an item from index `from_chunk` on (0 removes
+/// the whole body). Does not checkpoint. Returns the number of chunks removed.
+pub fn delete_secure_note_chunks(conn: &Connection, item_id: &str, from_chunk: u32) -> Result<u32> {
+    if !secure_notes_table_exists(conn)? {
+        return Ok(0);
+    }
+    let rows = conn.execute(
+        "DELETE FROM nswallet_secure_notes WHERE item_id = ? AND chunk >= ?",
+        params![item_id, from_chunk],
+    )?;
+    Ok(rows as u32)
+}
+
+// ============================================================================
+// Attachments (nswallet_attachments)
+// ============================================================================
+
+/// Create the attachments table. Each row is one file attached to an
+/// item; name, MIME type and content are separately encrypted blobs, and
+/// `size` is the plaintext length so listings need not load the content.
+pub fn ensure_attachments_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS nswallet_attachments (
+            attachment_id TEXT NOT NULL PRIMARY KEY,
+            item_id TEXT NOT NULL,
+            name BLOB NOT NULL,
+            mime_type BLOB NOT NULL,
+            data BLOB NOT NULL,
+            size INTEGER NOT NULL,
+            create_timestamp TEXT,
+            change_timestamp TEXT,
+            deleted INTEGER NOT NULL DEFAULT 0
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS nswallet_attachments_item ON nswallet_attachments (item_id)",
+        [],
+    )?;
+    Ok(())
+}
+
+/// True if the attachments table has been created
+pub fn attachments_table_exists(conn: &Connection) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='nswallet_attachments'",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// True if any attachment row, deleted or not, has this ID
+pub fn attachment_row_exists(conn: &Connection, attachment_id: &str) -> Result<bool> {
+    if !attachments_table_exists(conn)? {
+        return Ok(false);
+    }
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM nswallet_attachments WHERE attachment_id = ?",
+        params![attachment_id],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// Active attachments of an item, oldest first, without their content
+pub fn get_attachments_raw(conn: &Connection, item_id: &str) -> Result<Vec<RawAttachment>> {
+    if !attachments_table_exists(conn)? {
+        return Ok(Vec::new());
+    }
+    let mut stmt = conn.prepare(
+        "SELECT attachment_id, item_id, name, mime_type, size, CAST(create_timestamp AS TEXT)
+         FROM nswallet_attachments
+         WHERE item_id = ? AND deleted = 0
+         ORDER BY create_timestamp, rowid"
+    )?;
+    let rows = stmt.query_map(params![item_id], |row| {
+        Ok(RawAttachment {
+            attachment_id: row.get(0)?,
+            item_id: row.get(1)?,
+            name_encrypted: row.get(2)?,
+            mime_type_encrypted: row.get(3)?,
+            size: row.get::<_, i64>(4)?.max(0) as u64,
+            create_timestamp: row.get(5)?,
+        })
+    })?
+    .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// Encrypted content of an active attachment
+pub fn get_attachment_data(conn: &Connection, attachment_id: &str) -> Result<Option<Vec<u8>>> {
+    if !attachments_table_exists(conn)? {
+        return Ok(None);
+    }
+    conn.query_row(
+        "SELECT data FROM nswallet_attachments WHERE attachment_id = ? AND deleted = 0",
+        params![attachment_id],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(Into::into)
+}
+
+/// Item of an active attachment
+pub fn get_attachment_item_id(conn: &Connection, attachment_id: &str) -> Result<Option<String>> {
+    if !attachments_table_exists(conn)? {
+        return Ok(None);
+    }
+    conn.query_row(
+        "SELECT item_id FROM nswallet_attachments WHERE attachment_id = ? AND deleted = 0",
+        params![attachment_id],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(Into::into)
+}
+
+/// Insert a new attachment
+pub fn insert_attachment(
+    conn: &Connection,
+    attachment: &RawAttachment,
+    data_encrypted: &[u8],
+) -> Result<()> {
+    ensure_attachments_table(conn)?;
+    let now = now_timestamp();
+    conn.execute(
+        "INSERT INTO nswallet_attachments
+            (attachment_id, item_id, name, mime_type, data, size, create_timestamp, change_timestamp, deleted)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, 0)",
+        params![
+            attachment.attachment_id,
+            attachment.item_id,
+            attachment.name_encrypted,
+            attachment.mime_type_encrypted,
+            data_encrypted,
+            attachment.size as i64,
+            attachment.create_timestamp.as_deref().unwrap_or(&now),
+            now,
+        ],
+    )?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(())
+}
+
+/// Soft-delete an attachment. Returns true if it was active.
+pub fn delete_attachment(conn: &Connection, attachment_id: &str) -> Result<bool> {
+    if !attachments_table_exists(conn)? {
+        return Ok(false);
+    }
+    let rows = conn.execute(
+        "UPDATE nswallet_attachments SET deleted = 1, change_timestamp = ?
+         WHERE attachment_id = ? AND deleted = 0",
+        params![now_timestamp(), attachment_id],
+    )?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(rows > 0)
+}
+
+// ============================================================================
+// Folder defaults (nswallet_folder_defaults)
+// ============================================================================
+
+/// Create the folder defaults table: the icon and field template new items
+/// created inside a folder start with. The template is a comma-separated
+/// list of field types.
+pub fn ensure_folder_defaults_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS nswallet_folder_defaults (
+            folder_id TEXT NOT NULL PRIMARY KEY,
+            icon TEXT,
+            template TEXT,
+            change_timestamp TEXT
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// True if the folder defaults table has been created
+pub fn folder_defaults_table_exists(conn: &Connection) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='nswallet_folder_defaults'",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// Get a folder's own defaults as (icon, template)
+pub fn get_folder_defaults_raw(conn: &Connection, folder_id: &str) -> Result<Option<(Option<String>, Option<String>)>> {
+    if !folder_defaults_table_exists(conn)? {
+        return Ok(None);
+    }
+    conn.query_row(
+        "SELECT icon, template FROM nswallet_folder_defaults WHERE folder_id = ?",
+        params![folder_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+    .map_err(Into::into)
+}
+
+/// Defaults of `folder_id` and its ancestors as (icon, template), nearest
+/// folder first. Folders without defaults are left out.
+pub fn get_folder_defaults_chain(conn: &Connection, folder_id: &str) -> Result<Vec<(Option<String>, Option<String>)>> {
+    if !folder_defaults_table_exists(conn)? {
+        return Ok(Vec::new());
+    }
+    let mut stmt = conn.prepare(
+        "WITH RECURSIVE ancestors(id, depth) AS (
+            SELECT ?, 0
+            UNION
+            SELECT i.parent_id, a.depth + 1 FROM nswallet_items i JOIN ancestors a ON i.item_id = a.id
+             WHERE i.parent_id IS NOT NULL AND a.depth < 1000
+         )
+         SELECT d.icon, d.template FROM nswallet_folder_defaults d JOIN ancestors a ON d.folder_id = a.id
+          ORDER BY a.depth"
+    )?;
+    let rows = stmt.query_map([folder_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// Insert or replace a folder's defaults
+pub fn set_folder_defaults_raw(conn: &Connection, folder_id: &str, icon: Option<&str>, template: Option<&str>) -> Result<()> {
+    ensure_folder_defaults_table(conn)?;
+    conn.execute(
+        "INSERT INTO nswallet_folder_defaults (folder_id, icon, template, change_timestamp) VALUES (?, ?, ?, ?)
+         ON CONFLICT(folder_id) DO UPDATE SET icon = excluded.icon, template = excluded.template,
+             change_timestamp = excluded.change_timestamp",
+        params![folder_id, icon, template, now_timestamp()],
+    )?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(())
+}
+
+/// Remove a folder's defaults. Returns true if there were any.
+pub fn delete_folder_defaults(conn: &Connection, folder_id: &str) -> Result<bool> {
+    if !folder_defaults_table_exists(conn)? {
+        return Ok(false);
+    }
+    let rows = conn.execute("DELETE FROM nswallet_folder_defaults WHERE folder_id = ?", params![folder_id])?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(rows > 0)
+}
+
+// ============================================================================
+// Folder PINs (nswallet_folder_pins)
+// ============================================================================
+
+/// PIN protection of one folder. The folder key is derived from the DEK and
+/// the PIN with these Argon2id parameters; `key_check` is the folder ID
+/// sealed under it.
+#[derive(Debug, Clone)]
+pub struct FolderPinRecord {
+    pub folder_id: String,
+    pub m_cost_kib: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+    pub salt: Vec<u8>,
+    pub key_check: Vec<u8>,
+}
+
+/// Create the folder PIN table
+pub fn ensure_folder_pins_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS nswallet_folder_pins (
+            folder_id TEXT NOT NULL PRIMARY KEY,
+            kdf_m_cost INTEGER NOT NULL,
+            kdf_t_cost INTEGER NOT NULL,
+            kdf_p_cost INTEGER NOT NULL,
+            kdf_salt BLOB NOT NULL,
+            key_check BLOB NOT NULL,
+            change_timestamp TEXT
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// True if the folder PIN table has been created
+pub fn folder_pins_table_exists(conn: &Connection) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='nswallet_folder_pins'",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// PIN protection of `folder_id`, if any
+pub fn get_folder_pin(conn: &Connection, folder_id: &str) -> Result<Option<FolderPinRecord>> {
+    if !folder_pins_table_exists(conn)? {
+        return Ok(None);
+    }
+    conn.query_row(
+        "SELECT folder_id, kdf_m_cost, kdf_t_cost, kdf_p_cost, kdf_salt, key_check
+         FROM nswallet_folder_pins WHERE folder_id = ?",
+        params![folder_id],
+        |row| Ok(FolderPinRecord {
+            folder_id: row.get(0)?,
+            m_cost_kib: row.get(1)?,
+            t_cost: row.get(2)?,
+            p_cost: row.get(3)?,
+            salt: row.get(4)?,
+            key_check: row.get(5)?,
+        }),
+    )
+    .optional()
+    .map_err(Into::into)
+}
+
+/// IDs of all PIN-protected folders
+pub fn get_folder_pin_ids(conn: &Connection) -> Result<Vec<String>> {
+    if !folder_pins_table_exists(conn)? {
+        return Ok(Vec::new());
+    }
+    let mut stmt = conn.prepare("SELECT folder_id FROM nswallet_folder_pins ORDER BY folder_id")?;
+    let rows = stmt.query_map([], |row| row.get(0))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// The nearest PIN-protected folder among `item_id` and its ancestors
+pub fn get_protecting_folder(conn: &Connection, item_id: &str) -> Result<Option<String>> {
+    nearest_folder_pin(conn, item_id, 0)
+}
+
+/// The nearest PIN-protected folder among the ancestors of `item_id`
+pub fn get_enclosing_protected_folder(conn: &Connection, item_id: &str) -> Result<Option<String>> {
+    nearest_folder_pin(conn, item_id, 1)
+}
+
+fn nearest_folder_pin(conn: &Connection, item_id: &str, min_depth: i64) -> Result<Option<String>> {
+    if !folder_pins_table_exists(conn)? {
+        return Ok(None);
+    }
+    conn.query_row(
+        "WITH RECURSIVE ancestors(id, depth) AS (
+            SELECT ?1, 0
+            UNION
+            SELECT i.parent_id, a.depth + 1 FROM nswallet_items i JOIN ancestors a ON i.item_id = a.id
+             WHERE i.parent_id IS NOT NULL AND a.depth < 1000
+         )
+         SELECT p.folder_id FROM nswallet_folder_pins p JOIN ancestors a ON p.folder_id = a.id
+          WHERE a.depth >= ?2 ORDER BY a.depth LIMIT 1",
+        params![item_id, min_depth],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(Into::into)
+}
+
+/// Value blobs `(item_id, field_id, value)` of every field, deleted ones
+/// included, of the items in the subtree rooted at `item_id`
+pub fn get_subtree_field_blobs(conn: &Connection, item_id: &str) -> Result<Vec<(String, String, Vec<u8>)>> {
+    let mut stmt = conn.prepare(
+        "WITH RECURSIVE subtree(id) AS (
+            SELECT ?
+            UNION
+            SELECT i.item_id FROM nswallet_items i JOIN subtree s ON i.parent_id = s.id
+         )
+         SELECT f.item_id, f.field_id, f.value FROM nswallet_fields f JOIN subtree s ON f.item_id = s.id
+          WHERE f.value IS NOT NULL AND length(f.value) > 0"
+    )?;
+    let rows = stmt.query_map([item_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// Where an [`ItemBlob`] is stored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ItemBlobKind {
+    Note,
+    Metadata,
+    /// Chunk of a secure note body, and whether it is the last one
+    SecureNoteChunk { chunk: u32, last: bool },
+    /// Name of the attachment with this ID
+    AttachmentName(String),
+    AttachmentMimeType(String),
+    AttachmentData(String),
+}
+
+/// A value of an item kept outside the fields table: its note, metadata,
+/// a secure note chunk or part of an attachment
+#[derive(Debug, Clone)]
+pub struct ItemBlob {
+    pub item_id: String,
+    pub kind: ItemBlobKind,
+    pub blob: Vec<u8>,
+}
+
+/// Every [`ItemBlob`] of the items in the subtree rooted at `item_id`,
+/// deleted attachments included
+pub fn get_subtree_item_blobs(conn: &Connection, item_id: &str) -> Result<Vec<ItemBlob>> {
+    const SUBTREE: &str = "WITH RECURSIVE subtree(id) AS (
+            SELECT ?
+            UNION
+            SELECT i.item_id FROM nswallet_items i JOIN subtree s ON i.parent_id = s.id
+         )";
+    let mut blobs = Vec::new();
+    if item_notes_table_exists(conn)? {
+        let mut stmt = conn.prepare(&format!(
+            "{SUBTREE} SELECT n.item_id, n.note FROM nswallet_item_notes n JOIN subtree s ON n.item_id = s.id"
+        ))?;
+        let rows = stmt.query_map([item_id], |row| Ok(ItemBlob { item_id: row.get(0)?, kind: ItemBlobKind::Note, blob: row.get(1)? }))?;
+        blobs.extend(rows.collect::<std::result::Result<Vec<_>, _>>()?);
+    }
+    if item_metadata_table_exists(conn)? {
+        let mut stmt = conn.prepare(&format!(
+            "{SUBTREE} SELECT m.item_id, m.metadata FROM nswallet_item_metadata m JOIN subtree s ON m.item_id = s.id"
+        ))?;
+        let rows = stmt.query_map([item_id], |row| Ok(ItemBlob { item_id: row.get(0)?, kind: ItemBlobKind::Metadata, blob: row.get(1)? }))?;
+        blobs.extend(rows.collect::<std::result::Result<Vec<_>, _>>()?);
+    }
+    if secure_notes_table_exists(conn)? {
+        let mut stmt = conn.prepare(&format!(
+            "{SUBTREE} SELECT n.item_id, n.chunk, n.data,
+                    n.chunk = (SELECT MAX(l.chunk) FROM nswallet_secure_notes l WHERE l.item_id = n.item_id)
+             FROM nswallet_secure_notes n JOIN subtree s ON n.item_id = s.id"
+        ))?;
+        let rows = stmt.query_map([item_id], |row| Ok(ItemBlob {
+            item_id: row.get(0)?,
+            kind: ItemBlobKind::SecureNoteChunk { chunk: row.get(1)?, last: row.get(3)? },
+            blob: row.get(2)?,
+        }))?;
+        blobs.extend(rows.collect::<std::result::Result<Vec<_>, _>>()?);
+    }
+    if attachments_table_exists(conn)? {
+        let mut stmt = conn.prepare(&format!(
+            "{SUBTREE} SELECT a.item_id, a.attachment_id, a.name, a.mime_type, a.data
+             FROM nswallet_attachments a JOIN subtree s ON a.item_id = s.id"
+        ))?;
+        let mut rows = stmt.query([item_id])?;
+        while let Some(row) = rows.next()? {
+            let item_id: String = row.get(0)?;
+            let attachment_id: String = row.get(1)?;
+            for (kind, column) in [
+                (ItemBlobKind::AttachmentName(attachment_id.clone()), 2),
+                (ItemBlobKind::AttachmentMimeType(attachment_id.clone()), 3),
+                (ItemBlobKind::AttachmentData(attachment_id.clone()), 4),
+            ] {
+                blobs.push(ItemBlob { item_id: item_id.clone(), kind, blob: row.get(column)? });
+            }
+        }
+    }
+    Ok(blobs)
+}
+
+/// Replace the stored value of an [`ItemBlob`] with its `blob`
+pub fn update_item_blob(conn: &Connection, blob: &ItemBlob) -> Result<()> {
+    match &blob.kind {
+        ItemBlobKind::Note => conn.execute(
+            "UPDATE nswallet_item_notes SET note = ? WHERE item_id = ?",
+            params![blob.blob, blob.item_id],
+        ),
+        ItemBlobKind::Metadata => conn.execute(
+            "UPDATE nswallet_item_metadata SET metadata = ? WHERE item_id = ?",
+            params![blob.blob, blob.item_id],
+        ),
+        ItemBlobKind::SecureNoteChunk { chunk, .. } => conn.execute(
+            "UPDATE nswallet_secure_notes SET data = ? WHERE item_id = ? AND chunk = ?",
+            params![blob.blob, blob.item_id, chunk],
+        ),
+        ItemBlobKind::AttachmentName(id) => conn.execute(
+            "UPDATE nswallet_attachments SET name = ? WHERE attachment_id = ?",
+            params![blob.blob, id],
+        ),
+        ItemBlobKind::AttachmentMimeType(id) => conn.execute(
+            "UPDATE nswallet_attachments SET mime_type = ? WHERE attachment_id = ?",
+            params![blob.blob, id],
+        ),
+        ItemBlobKind::AttachmentData(id) => conn.execute(
+            "UPDATE nswallet_attachments SET data = ? WHERE attachment_id = ?",
+            params![blob.blob, id],
+        ),
+    }?;
+    Ok(())
+}
+
+/// Raw rows of the active items in the subtree rooted at `item_id`, the
+/// root included. Descendants of deleted items are left out.
+pub fn get_subtree_items_raw(conn: &Connection, item_id: &str) -> Result<Vec<RawItem>> {
+    let mut stmt = conn.prepare(
+        "WITH RECURSIVE subtree(id) AS (
+            SELECT item_id FROM nswallet_items WHERE item_id = ? AND COALESCE(deleted, 0) = 0
+            UNION
+            SELECT i.item_id FROM nswallet_items i JOIN subtree s ON i.parent_id = s.id
+             WHERE COALESCE(i.deleted, 0) = 0
+         )
+         SELECT i.item_id, i.parent_id, COALESCE(i.name, X''), COALESCE(CAST(i.icon AS TEXT), ''), COALESCE(i.folder, 0),
+                CAST(i.create_timestamp AS TEXT), CAST(i.change_timestamp AS TEXT), COALESCE(i.deleted, 0), i.field_id,
+                i.color, i.meta, CAST(i.sort_weight AS INTEGER), COALESCE(i.locked, 0)
+         FROM nswallet_items i JOIN subtree s ON i.item_id = s.id"
+    )?;
+    let items = stmt.query_map([item_id], raw_item_from_row)?;
+    items.collect::<std::result::Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// Raw rows of the active fields of the items returned by
+/// `get_subtree_items_raw`
+pub fn get_subtree_fields_raw(conn: &Connection, item_id: &str) -> Result<Vec<RawField>> {
+    let mut stmt = conn.prepare(
+        "WITH RECURSIVE subtree(id) AS (
+            SELECT item_id FROM nswallet_items WHERE item_id = ? AND COALESCE(deleted, 0) = 0
+            UNION
+            SELECT i.item_id FROM nswallet_items i JOIN subtree s ON i.parent_id = s.id
+             WHERE COALESCE(i.deleted, 0) = 0
+         )
+         SELECT f.item_id, f.field_id, COALESCE(f.type, 'NOTE'), COALESCE(f.value, X''),
+                CAST(f.change_timestamp AS TEXT), COALESCE(f.deleted, 0), COALESCE(CAST(f.sort_weight AS INTEGER), 0), f.meta
+         FROM nswallet_fields f JOIN subtree s ON f.item_id = s.id
+         WHERE COALESCE(f.deleted, 0) = 0"
+    )?;
+    let fields = stmt.query_map([item_id], |row| {
+        Ok(RawField {
+            item_id: row.get(0)?,
+            field_id: row.get(1)?,
+            field_type: row.get(2)?,
+            value_encrypted: row.get(3)?,
+            change_timestamp: row.get(4)?,
+            deleted: row.get::<_, i32>(5)? != 0,
+            sort_weight: row.get(6)?,
+            meta: row.get(7)?,
+        })
+    })?;
+    fields.collect::<std::result::Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// Insert a folder's PIN protection. No WAL checkpoint, for use inside an
+/// open transaction.
+pub fn create_folder_pin(conn: &Connection, rec: &FolderPinRecord) -> Result<()> {
+    ensure_folder_pins_table(conn)?;
+    conn.execute(
+        "INSERT INTO nswallet_folder_pins
+            (folder_id, kdf_m_cost, kdf_t_cost, kdf_p_cost, kdf_salt, key_check, change_timestamp)
+         VALUES (?, ?, ?, ?, ?, ?, ?)",
+        params![rec.folder_id, rec.m_cost_kib, rec.t_cost, rec.p_cost, rec.salt, rec.key_check, now_timestamp()],
+    )?;
+    Ok(())
+}
+
+/// Remove a folder's PIN protection. No WAL checkpoint, for use inside an
+/// open transaction.
+pub fn delete_folder_pin(conn: &Connection, folder_id: &str) -> Result<()> {
+    if folder_pins_table_exists(conn)? {
+        conn.execute("DELETE FROM nswallet_folder_pins WHERE folder_id = ?", params![folder_id])?;
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Item metadata (nswallet_item_metadata)
+// ============================================================================
+
+/// Create the item metadata table: one encrypted JSON object per item for
+/// host application data.
+pub fn ensure_item_metadata_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS nswallet_item_metadata (
+            item_id TEXT NOT NULL PRIMARY KEY,
+            metadata BLOB NOT NULL,
+            change_timestamp TEXT
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// True if the item metadata table has been created
+pub fn item_metadata_table_exists(conn: &Connection) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='nswallet_item_metadata'",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// Get an item's encrypted metadata blob
+pub fn get_item_metadata_raw(conn: &Connection, item_id: &str) -> Result<Option<Vec<u8>>> {
+    if !item_metadata_table_exists(conn)? {
+        return Ok(None);
+    }
+    conn.query_row(
+        "SELECT metadata FROM nswallet_item_metadata WHERE item_id = ?",
+        params![item_id],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(Into::into)
+}
+
+/// Insert or replace an item's encrypted metadata blob
+pub fn set_item_metadata_raw(conn: &Connection, item_id: &str, metadata_encrypted: &[u8]) -> Result<()> {
+    set_item_metadata_raw_no_checkpoint(conn, item_id, metadata_encrypted)?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(())
+}
+
+/// [`set_item_metadata_raw`] without the WAL checkpoint, for use inside an open
+/// transaction
+pub fn set_item_metadata_raw_no_checkpoint(conn: &Connection, item_id: &str, metadata_encrypted: &[u8]) -> Result<()> {
+    ensure_item_metadata_table(conn)?;
+    conn.execute(
+        "INSERT INTO nswallet_item_metadata (item_id, metadata, change_timestamp) VALUES (?, ?, ?)
+         ON CONFLICT(item_id) DO UPDATE SET metadata = excluded.metadata, change_timestamp = excluded.change_timestamp",
+        params![item_id, metadata_encrypted, now_timestamp()],
+    )?;
+    Ok(())
+}
+
+/// Remove an item's metadata. Returns true if there was any.
+pub fn delete_item_metadata(conn: &Connection, item_id: &str) -> Result<bool> {
+    if !item_metadata_table_exists(conn)? {
+        return Ok(false);
+    }
+    let rows = conn.execute("DELETE FROM nswallet_item_metadata WHERE item_id = ?", params![item_id])?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(rows > 0)
+}
+
+// ============================================================================
+// Emergency access grants (nswallet_emergency_grants)
+// ============================================================================
+
+/// One emergency access grant. `dek_wrapped` is the DEK wrapped under a KEK
+/// derived from the contact's secret (empty once revoked).
+#[derive(Debug, Clone)]
+pub struct EmergencyGrantRecord {
+    pub grant_id: String,
+    /// Contact name, encrypted under the DEK
+    pub contact_encrypted: Vec<u8>,
+    pub m_cost_kib: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+    pub salt: Vec<u8>,
+    pub dek_wrapped: Vec<u8>,
+    pub wait_secs: u64,
+    pub created_at: Option<DateTime<Utc>>,
+    pub requested_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// Create the emergency grants table
+pub fn ensure_emergency_grants_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS nswallet_emergency_grants (
+            grant_id TEXT NOT NULL PRIMARY KEY,
+            contact BLOB NOT NULL,
+            m_cost_kib INTEGER NOT NULL,
+            t_cost INTEGER NOT NULL,
+            p_cost INTEGER NOT NULL,
+            salt BLOB NOT NULL,
+            dek_wrapped BLOB NOT NULL,
+            wait_secs INTEGER NOT NULL,
+            created_at TEXT,
+            requested_at TEXT,
+            revoked_at TEXT
+        )",
         [],
-        |row| row.get(0),
     )?;
+    Ok(())
+}
 
-    // Count deleted fields + orphaned fields (fields belonging to deleted items)
-    let fields_count: u32 = conn.query_row(
-        "SELECT COUNT(*) FROM nswallet_fields WHERE deleted = 1 OR item_id IN (SELECT item_id FROM nswallet_items WHERE deleted = 1)",
-        [],
-        |row| row.get(0),
-    )?;
+fn emergency_grant_from_row(row: &rusqlite::Row) -> rusqlite::Result<EmergencyGrantRecord> {
+    let ts = |s: Option<String>| s.as_deref().and_then(parse_timestamp);
+    Ok(EmergencyGrantRecord {
+        grant_id: row.get(0)?,
+        contact_encrypted: row.get(1)?,
+        m_cost_kib: row.get(2)?,
+        t_cost: row.get(3)?,
+        p_cost: row.get(4)?,
+        salt: row.get(5)?,
+        dek_wrapped: row.get(6)?,
+        wait_secs: row.get::<_, i64>(7)?.max(0) as u64,
+        created_at: ts(row.get(8)?),
+        requested_at: ts(row.get(9)?),
+        revoked_at: ts(row.get(10)?),
+    })
+}
 
-    // Delete orphaned fields first (fields belonging to deleted items)
-    conn.execute(
-        "DELETE FROM nswallet_fields WHERE item_id IN (SELECT item_id FROM nswallet_items WHERE deleted = 1)",
-        [],
-    )?;
+const EMERGENCY_GRANT_COLUMNS: &str = "grant_id, contact, m_cost_kib, t_cost, p_cost, salt, dek_wrapped, \
+    wait_secs, created_at, requested_at, revoked_at";
 
-    // Delete soft-deleted fields
+/// All emergency grants, oldest first
+pub fn get_emergency_grants(conn: &Connection) -> Result<Vec<EmergencyGrantRecord>> {
+    ensure_emergency_grants_table(conn)?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {EMERGENCY_GRANT_COLUMNS} FROM nswallet_emergency_grants ORDER BY created_at, grant_id"
+    ))?;
+    let rows = stmt.query_map([], emergency_grant_from_row)?;
+    rows.collect::<rusqlite::Result<Vec<_>>>().map_err(Into::into)
+}
+
+/// Get one emergency grant
+pub fn get_emergency_grant(conn: &Connection, grant_id: &str) -> Result<Option<EmergencyGrantRecord>> {
+    ensure_emergency_grants_table(conn)?;
+    conn.query_row(
+        &format!("SELECT {EMERGENCY_GRANT_COLUMNS} FROM nswallet_emergency_grants WHERE grant_id = ?"),
+        params![grant_id],
+        emergency_grant_from_row,
+    )
+    .optional()
+    .map_err(Into::into)
+}
+
+/// Store a new emergency grant
+pub fn create_emergency_grant(conn: &Connection, grant: &EmergencyGrantRecord) -> Result<()> {
+    ensure_emergency_grants_table(conn)?;
     conn.execute(
-        "DELETE FROM nswallet_fields WHERE deleted = 1",
-        [],
+        &format!("INSERT INTO nswallet_emergency_grants ({EMERGENCY_GRANT_COLUMNS}) \
+                  VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"),
+        params![
+            grant.grant_id,
+            grant.contact_encrypted,
+            grant.m_cost_kib,
+            grant.t_cost,
+            grant.p_cost,
+            grant.salt,
+            grant.dek_wrapped,
+            grant.wait_secs as i64,
+            grant.created_at.as_ref().map(format_timestamp),
+            grant.requested_at.as_ref().map(format_timestamp),
+            grant.revoked_at.as_ref().map(format_timestamp),
+        ],
     )?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(())
+}
 
-    // Delete soft-deleted items
+/// Set or clear the time access was requested
+pub fn set_emergency_grant_requested(
+    conn: &Connection,
+    grant_id: &str,
+    requested_at: Option<&DateTime<Utc>>,
+) -> Result<()> {
+    ensure_emergency_grants_table(conn)?;
     conn.execute(
-        "DELETE FROM nswallet_items WHERE deleted = 1",
-        [],
+        "UPDATE nswallet_emergency_grants SET requested_at = ? WHERE grant_id = ?",
+        params![requested_at.map(format_timestamp), grant_id],
     )?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(())
+}
 
-    // Delete soft-deleted labels
+/// Revoke a grant: record the time and overwrite the wrapped key, so the
+/// contact's secret no longer opens anything
+pub fn revoke_emergency_grant(conn: &Connection, grant_id: &str, revoked_at: &DateTime<Utc>) -> Result<()> {
+    ensure_emergency_grants_table(conn)?;
     conn.execute(
-        "DELETE FROM nswallet_labels WHERE deleted = 1",
-        [],
+        "UPDATE nswallet_emergency_grants
+         SET revoked_at = ?, requested_at = NULL, dek_wrapped = zeroblob(length(dek_wrapped))
+         WHERE grant_id = ?",
+        params![format_timestamp(revoked_at), grant_id],
     )?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(())
+}
+
+/// Permanently purge all soft-deleted records, attachments included.
+/// Returns (purged_items_count, purged_fields_count).
+pub fn purge_deleted(conn: &Connection) -> Result<(u32, u32)> {
+    let (items, fields, _) = purge_deleted_filtered(conn, true, true, true, None)?;
+    Ok((items, fields))
+}
+
+/// Permanently purge the selected kinds of soft-deleted records. With a
+/// `cutoff`, only records deleted (last changed) at or before it are
+/// purged; records without a timestamp count as old. Stored timestamps
+/// are compared normalized (see [`normalized_timestamp_sql`]) and sealed
+/// ones are read with the `iw_meta_changed(meta)` SQL function of the
+/// unlocked wallet, so the connection must have it. Fields of deleted
+/// items go with their item, so `purge_fields` only covers fields of
+/// active items; deleted attachments are purged along with fields.
+/// The deletes run in one transaction; the vacuum follows it.
+/// Returns (items, fields, labels) purged.
+pub fn purge_deleted_filtered(
+    conn: &Connection,
+    purge_items: bool,
+    purge_fields: bool,
+    purge_labels: bool,
+    cutoff: Option<&DateTime<Utc>>,
+) -> Result<(u32, u32, u32)> {
+    conn.execute("BEGIN TRANSACTION", [])?;
+    let counts = match delete_purged(conn, purge_items, purge_fields, purge_labels, cutoff) {
+        Ok(counts) => {
+            conn.execute("COMMIT", [])?;
+            counts
+        }
+        Err(e) => {
+            let _ = conn.execute("ROLLBACK", []);
+            return Err(e);
+        }
+    };
 
     // Physically erase the purged records and return the freed pages to the
     // filesystem. Without this the DELETEs only unlink the rows: the file
     // never shrinks and the encrypted blobs linger in free pages, which is
     // weaker than the "permanently purge" this feature promises. VACUUM must
-    // run outside a transaction, which holds here.
+    // run outside a transaction, so it comes after the commit.
     conn.execute_batch("VACUUM")?;
 
     conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
 
-    Ok((items_count, fields_count))
+    Ok(counts)
+}
+
+/// The deletes of [`purge_deleted_filtered`], without vacuum or checkpoint
+fn delete_purged(
+    conn: &Connection,
+    purge_items: bool,
+    purge_fields: bool,
+    purge_labels: bool,
+    cutoff: Option<&DateTime<Utc>>,
+) -> Result<(u32, u32, u32)> {
+    let cutoff = cutoff.map_or_else(|| "9999-12-31 23:59:59".to_string(), format_timestamp);
+    let changed = normalized_timestamp_sql("change_timestamp");
+    let purged_items = format!("SELECT item_id FROM nswallet_items
+         WHERE deleted = 1 AND COALESCE({changed}, iw_meta_changed(meta), ?1) <= ?1");
+    let (mut items_count, mut fields_count, mut labels_count) = (0, 0, 0);
+
+    if purge_items {
+        // Fields, notes and metadata of the purged items first
+        fields_count += conn.execute(
+            &format!("DELETE FROM nswallet_fields WHERE item_id IN ({purged_items})"),
+            params![cutoff],
+        )? as u32;
+        if item_notes_table_exists(conn)? {
+            conn.execute(
+                &format!("DELETE FROM nswallet_item_notes WHERE item_id IN ({purged_items})"),
+                params![cutoff],
+            )?;
+        }
+        if item_metadata_table_exists(conn)? {
+            conn.execute(
+                &format!("DELETE FROM nswallet_item_metadata WHERE item_id IN ({purged_items})"),
+                params![cutoff],
+            )?;
+        }
+        if secure_notes_table_exists(conn)? {
+            conn.execute(
+                &format!("DELETE FROM nswallet_secure_notes WHERE item_id IN ({purged_items})"),
+                params![cutoff],
+            )?;
+        }
+        if folder_defaults_table_exists(conn)? {
+            conn.execute(
+                &format!("DELETE FROM nswallet_folder_defaults WHERE folder_id IN ({purged_items})"),
+                params![cutoff],
+            )?;
+        }
+        if attachments_table_exists(conn)? {
+            conn.execute(
+                &format!("DELETE FROM nswallet_attachments WHERE item_id IN ({purged_items})"),
+                params![cutoff],
+            )?;
+        }
+        if folder_pins_table_exists(conn)? {
+            conn.execute(
+                &format!("DELETE FROM nswallet_folder_pins WHERE folder_id IN ({purged_items})"),
+                params![cutoff],
+            )?;
+        }
+        if notification_snoozes_table_exists(conn)? {
+            conn.execute(
+                &format!("DELETE FROM nswallet_notification_snoozes WHERE item_id IN ({purged_items})"),
+                params![cutoff],
+            )?;
+        }
+        items_count = conn.execute(
+            &format!("DELETE FROM nswallet_items
+             WHERE deleted = 1 AND COALESCE({changed}, iw_meta_changed(meta), ?1) <= ?1"),
+            params![cutoff],
+        )? as u32;
+    }
+
+    if purge_fields {
+        // Fields of items still in the trash stay, so restoring the item
+        // brings them back
+        let purged_fields = format!("SELECT item_id, field_id FROM nswallet_fields
+             WHERE deleted = 1 AND COALESCE({changed}, iw_meta_changed(meta), ?1) <= ?1
+               AND item_id NOT IN (SELECT item_id FROM nswallet_items WHERE deleted = 1)");
+        if notification_snoozes_table_exists(conn)? {
+            conn.execute(
+                &format!("DELETE FROM nswallet_notification_snoozes WHERE (item_id, field_id) IN ({purged_fields})"),
+                params![cutoff],
+            )?;
+        }
+        fields_count += conn.execute(
+            &format!("DELETE FROM nswallet_fields WHERE (item_id, field_id) IN ({purged_fields})"),
+            params![cutoff],
+        )? as u32;
+        // Deleted attachments go with deleted fields, on the same terms
+        if attachments_table_exists(conn)? {
+            conn.execute(
+                &format!("DELETE FROM nswallet_attachments
+                 WHERE deleted = 1 AND COALESCE({changed}, ?1) <= ?1
+                   AND item_id NOT IN (SELECT item_id FROM nswallet_items WHERE deleted = 1)"),
+                params![cutoff],
+            )?;
+        }
+    }
+
+    if purge_labels {
+        labels_count = conn.execute(
+            &format!("DELETE FROM nswallet_labels
+             WHERE deleted = 1 AND COALESCE({changed}, ?1) <= ?1"),
+            params![cutoff],
+        )? as u32;
+    }
+
+    Ok((items_count, fields_count, labels_count))
 }
 
 /// Database statistics
@@ -912,8 +2838,132 @@ pub struct DatabaseStats {
     pub deleted_items: u32,
     /// Soft-deleted fields
     pub deleted_fields: u32,
+    /// Active folders (excluding root) with no active children
+    pub empty_folders: u32,
+    /// Active non-folder items with no active fields
+    pub items_without_fields: u32,
     /// Database file size in bytes
     pub file_size_bytes: u64,
+    /// Size of the write-ahead log in bytes
+    pub wal_size_bytes: u64,
+    /// Database page size in bytes
+    pub page_size: u64,
+    /// Unused pages kept in the file
+    pub freelist_pages: u64,
+    /// Estimated bytes `compact` frees: the free pages, the soft-deleted
+    /// records and (when set by the wallet) the write-ahead log
+    pub reclaimable_bytes: u64,
+}
+
+/// Total bytes of encrypted blobs (item names, field values, notes, item
+/// metadata, attachments and metadata envelopes), active and deleted
+pub fn get_encrypted_blob_bytes(conn: &Connection) -> Result<u64> {
+    let sum = |sql: &str| -> Result<u64> {
+        let bytes: i64 = conn.query_row(sql, [], |row| row.get(0))?;
+        Ok(bytes.max(0) as u64)
+    };
+    let mut total = sum("SELECT COALESCE(SUM(length(name)), 0) FROM nswallet_items")?
+        + sum("SELECT COALESCE(SUM(length(value)), 0) FROM nswallet_fields")?;
+    for table in ["nswallet_items", "nswallet_fields"] {
+        if table_has_column(conn, table, "meta")? {
+            total += sum(&format!("SELECT COALESCE(SUM(length(meta)), 0) FROM {table}"))?;
+        }
+    }
+    if item_notes_table_exists(conn)? {
+        total += sum("SELECT COALESCE(SUM(length(note)), 0) FROM nswallet_item_notes")?;
+    }
+    if item_metadata_table_exists(conn)? {
+        total += sum("SELECT COALESCE(SUM(length(metadata)), 0) FROM nswallet_item_metadata")?;
+    }
+    if secure_notes_table_exists(conn)? {
+        total += sum("SELECT COALESCE(SUM(length(data)), 0) FROM nswallet_secure_notes")?;
+    }
+    if attachments_table_exists(conn)? {
+        total += sum("SELECT COALESCE(SUM(length(name) + length(mime_type) + length(data)), 0) FROM nswallet_attachments")?;
+    }
+    Ok(total)
+}
+
+/// Rows of the item, field, label, note and attachment tables, active and
+/// deleted
+pub fn get_record_row_count(conn: &Connection) -> Result<u64> {
+    let count = |table: &str| -> Result<u64> {
+        let n: i64 = conn.query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| row.get(0))?;
+        Ok(n.max(0) as u64)
+    };
+    let mut total = count("nswallet_items")? + count("nswallet_fields")? + count("nswallet_labels")?;
+    if item_notes_table_exists(conn)? {
+        total += count("nswallet_item_notes")?;
+    }
+    if item_metadata_table_exists(conn)? {
+        total += count("nswallet_item_metadata")?;
+    }
+    if secure_notes_table_exists(conn)? {
+        total += count("nswallet_secure_notes")?;
+    }
+    if attachments_table_exists(conn)? {
+        total += count("nswallet_attachments")?;
+    }
+    Ok(total)
+}
+
+/// Per-row storage overhead assumed on top of the column payload
+const ROW_OVERHEAD_BYTES: u64 = 16;
+
+/// Estimated storage taken by the records `compact` purges: deleted items
+/// with their fields, notes and attachments, deleted fields, attachments
+/// and labels. Only columns present in every schema version are counted.
+pub fn get_deleted_record_bytes(conn: &Connection) -> Result<u64> {
+    let sum = |sql: &str| -> Result<u64> {
+        let (bytes, rows): (i64, i64) = conn.query_row(sql, [], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(bytes.max(0) as u64 + rows.max(0) as u64 * ROW_OVERHEAD_BYTES)
+    };
+    let mut total = sum(
+        "SELECT COALESCE(SUM(COALESCE(length(item_id), 0) + COALESCE(length(parent_id), 0)
+                + COALESCE(length(name), 0) + COALESCE(length(icon), 0)
+                + COALESCE(length(create_timestamp), 0) + COALESCE(length(change_timestamp), 0)), 0),
+                COUNT(*)
+         FROM nswallet_items WHERE deleted = 1"
+    )?;
+    // Deleted fields, and active fields of deleted items
+    total += sum(
+        "SELECT COALESCE(SUM(COALESCE(length(item_id), 0) + COALESCE(length(field_id), 0)
+                + COALESCE(length(type), 0) + COALESCE(length(value), 0)
+                + COALESCE(length(change_timestamp), 0)), 0),
+                COUNT(*)
+         FROM nswallet_fields
+         WHERE deleted = 1 OR item_id IN (SELECT item_id FROM nswallet_items WHERE deleted = 1)"
+    )?;
+    total += sum(
+        "SELECT COALESCE(SUM(COALESCE(length(field_type), 0) + COALESCE(length(label_name), 0)
+                + COALESCE(length(value_type), 0) + COALESCE(length(icon), 0)
+                + COALESCE(length(change_timestamp), 0)), 0),
+                COUNT(*)
+         FROM nswallet_labels WHERE deleted = 1"
+    )?;
+    if item_notes_table_exists(conn)? {
+        total += sum(
+            "SELECT COALESCE(SUM(length(item_id) + length(note)), 0), COUNT(*)
+             FROM nswallet_item_notes
+             WHERE item_id IN (SELECT item_id FROM nswallet_items WHERE deleted = 1)"
+        )?;
+    }
+    if secure_notes_table_exists(conn)? {
+        total += sum(
+            "SELECT COALESCE(SUM(length(item_id) + length(data) + COALESCE(length(change_timestamp), 0)), 0), COUNT(*)
+             FROM nswallet_secure_notes
+             WHERE item_id IN (SELECT item_id FROM nswallet_items WHERE deleted = 1)"
+        )?;
+    }
+    if attachments_table_exists(conn)? {
+        total += sum(
+            "SELECT COALESCE(SUM(length(attachment_id) + length(item_id) + length(name) + length(mime_type)
+                    + length(data) + COALESCE(length(change_timestamp), 0)), 0), COUNT(*)
+             FROM nswallet_attachments
+             WHERE deleted = 1 OR item_id IN (SELECT item_id FROM nswallet_items WHERE deleted = 1)"
+        )?;
+    }
+    Ok(total)
 }
 
 /// Get database statistics (counts of items, fields, labels, deleted records)
@@ -955,6 +3005,11 @@ pub fn get_database_stats(conn: &Connection) -> Result<DatabaseStats> {
         [],
         |row| row.get(0),
     )?;
+    let empty_folders = get_empty_folder_ids(conn)?.len() as u32;
+    let items_without_fields = get_item_ids_without_fields(conn)?.len() as u32;
+    let page_size = conn.query_row("PRAGMA page_size", [], |row| row.get::<_, i64>(0))?.max(0) as u64;
+    let freelist_pages = conn.query_row("PRAGMA freelist_count", [], |row| row.get::<_, i64>(0))?.max(0) as u64;
+    let reclaimable_bytes = freelist_pages * page_size + get_deleted_record_bytes(conn)?;
 
     Ok(DatabaseStats {
         total_items,
@@ -964,15 +3019,51 @@ pub fn get_database_stats(conn: &Connection) -> Result<DatabaseStats> {
         custom_labels,
         deleted_items,
         deleted_fields,
+        empty_folders,
+        items_without_fields,
         file_size_bytes: 0, // Caller sets this from file metadata
+        wal_size_bytes: 0,  // Likewise
+        page_size,
+        freelist_pages,
+        reclaimable_bytes,
     })
 }
 
+/// Ids of active folders (excluding root and profile roots) that have no
+/// active children
+pub fn get_empty_folder_ids(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT f.item_id FROM nswallet_items f
+         WHERE COALESCE(f.deleted, 0) = 0 AND COALESCE(f.folder, 0) = 1 AND f.item_id != '__ROOT__'
+           AND COALESCE(f.parent_id, '') != '________'
+           AND NOT EXISTS (
+               SELECT 1 FROM nswallet_items c
+               WHERE c.parent_id = f.item_id AND COALESCE(c.deleted, 0) = 0
+           )"
+    )?;
+    let ids = stmt.query_map([], |row| row.get(0))?;
+    ids.collect::<std::result::Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// Ids of active non-folder items that have no active fields
+pub fn get_item_ids_without_fields(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT i.item_id FROM nswallet_items i
+         WHERE COALESCE(i.deleted, 0) = 0 AND COALESCE(i.folder, 0) = 0 AND i.item_id != '__ROOT__'
+           AND NOT EXISTS (
+               SELECT 1 FROM nswallet_fields f
+               WHERE f.item_id = i.item_id AND COALESCE(f.deleted, 0) = 0
+           )"
+    )?;
+    let ids = stmt.query_map([], |row| row.get(0))?;
+    ids.collect::<std::result::Result<Vec<_>, _>>().map_err(Into::into)
+}
+
 /// Get a single active field's raw data by field_id
 pub fn get_field_raw_by_id(conn: &Connection, field_id: &str) -> Result<Option<RawField>> {
     let result = conn.query_row(
         "SELECT item_id, field_id, COALESCE(type, 'NOTE'), COALESCE(value, X''),
-                change_timestamp, COALESCE(deleted, 0), COALESCE(sort_weight, 0)
+                CAST(change_timestamp AS TEXT), COALESCE(deleted, 0), COALESCE(CAST(sort_weight AS INTEGER), 0), meta
          FROM nswallet_fields WHERE field_id = ? AND COALESCE(deleted, 0) = 0",
         params![field_id],
         |row| {
@@ -984,6 +3075,7 @@ pub fn get_field_raw_by_id(conn: &Connection, field_id: &str) -> Result<Option<R
                 change_timestamp: row.get(4)?,
                 deleted: row.get::<_, i32>(5)? != 0,
                 sort_weight: row.get(6)?,
+                meta: row.get(7)?,
             })
         },
     );
@@ -1010,6 +3102,145 @@ pub fn get_max_field_weight(conn: &Connection, item_id: &str) -> Result<i32> {
     Ok(result.unwrap_or(0))
 }
 
+// ============================================================================
+// Notification snoozes (nswallet_notification_snoozes)
+// ============================================================================
+
+/// Create the notification snoozes table: expiry reminders of a field are
+/// held back until `until`
+pub fn ensure_notification_snoozes_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS nswallet_notification_snoozes (
+            item_id TEXT NOT NULL,
+            field_id TEXT NOT NULL,
+            until TEXT NOT NULL,
+            PRIMARY KEY (item_id, field_id)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// True if the notification snoozes table has been created
+pub fn notification_snoozes_table_exists(conn: &Connection) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='nswallet_notification_snoozes'",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// All snoozes as (item_id, field_id, until)
+pub fn get_notification_snoozes(conn: &Connection) -> Result<Vec<(String, String, String)>> {
+    if !notification_snoozes_table_exists(conn)? {
+        return Ok(Vec::new());
+    }
+    let mut stmt = conn.prepare("SELECT item_id, field_id, until FROM nswallet_notification_snoozes")?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// Insert or replace the snooze of a field
+pub fn set_notification_snooze(conn: &Connection, item_id: &str, field_id: &str, until: &DateTime<Utc>) -> Result<()> {
+    ensure_notification_snoozes_table(conn)?;
+    conn.execute(
+        "INSERT INTO nswallet_notification_snoozes (item_id, field_id, until) VALUES (?, ?, ?)
+         ON CONFLICT(item_id, field_id) DO UPDATE SET until = excluded.until",
+        params![item_id, field_id, format_timestamp(until)],
+    )?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(())
+}
+
+/// Remove the snooze of a field. Returns true if there was one.
+pub fn delete_notification_snooze(conn: &Connection, item_id: &str, field_id: &str) -> Result<bool> {
+    if !notification_snoozes_table_exists(conn)? {
+        return Ok(false);
+    }
+    let rows = conn.execute(
+        "DELETE FROM nswallet_notification_snoozes WHERE item_id = ? AND field_id = ?",
+        params![item_id, field_id],
+    )?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(rows > 0)
+}
+
+// ============================================================================
+// Import profiles (nswallet_import_profiles)
+// ============================================================================
+
+/// Create the import profiles table: saved import settings, encrypted, by name
+pub fn ensure_import_profiles_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS nswallet_import_profiles (
+            name TEXT NOT NULL PRIMARY KEY,
+            profile BLOB NOT NULL,
+            change_timestamp TEXT
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// True if the import profiles table has been created
+pub fn import_profiles_table_exists(conn: &Connection) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='nswallet_import_profiles'",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// Encrypted blob of the import profile called `name`
+pub fn get_import_profile_raw(conn: &Connection, name: &str) -> Result<Option<Vec<u8>>> {
+    if !import_profiles_table_exists(conn)? {
+        return Ok(None);
+    }
+    conn.query_row(
+        "SELECT profile FROM nswallet_import_profiles WHERE name = ?",
+        params![name],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(Into::into)
+}
+
+/// Names of all import profiles, sorted
+pub fn get_import_profile_names(conn: &Connection) -> Result<Vec<String>> {
+    if !import_profiles_table_exists(conn)? {
+        return Ok(Vec::new());
+    }
+    let mut stmt = conn.prepare("SELECT name FROM nswallet_import_profiles ORDER BY name")?;
+    let names = stmt.query_map([], |row| row.get(0))?
+        .collect::<std::result::Result<Vec<String>, _>>()?;
+    Ok(names)
+}
+
+/// Insert or replace the import profile called `name`
+pub fn set_import_profile_raw(conn: &Connection, name: &str, profile: &[u8]) -> Result<()> {
+    ensure_import_profiles_table(conn)?;
+    conn.execute(
+        "INSERT INTO nswallet_import_profiles (name, profile, change_timestamp) VALUES (?, ?, ?)
+         ON CONFLICT(name) DO UPDATE SET profile = excluded.profile, change_timestamp = excluded.change_timestamp",
+        params![name, profile, now_timestamp()],
+    )?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(())
+}
+
+/// Remove an import profile. Returns true if there was one.
+pub fn delete_import_profile(conn: &Connection, name: &str) -> Result<bool> {
+    if !import_profiles_table_exists(conn)? {
+        return Ok(false);
+    }
+    let rows = conn.execute("DELETE FROM nswallet_import_profiles WHERE name = ?", params![name])?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(rows > 0)
+}
+
 // ============================================================================
 // Labels queries
 // ============================================================================
@@ -1018,7 +3249,7 @@ pub fn get_max_field_weight(conn: &Connection, item_id: &str) -> Result<i32> {
 pub fn get_all_labels(conn: &Connection) -> Result<Vec<RawLabel>> {
     let mut stmt = conn.prepare(
         "SELECT l.field_type, COALESCE(l.label_name, ''), COALESCE(l.value_type, 'text'),
-                COALESCE(l.icon, ''), COALESCE(l.system, 0), l.change_timestamp, COALESCE(l.deleted, 0),
+                COALESCE(l.icon, ''), COALESCE(l.system, 0), CAST(l.change_timestamp AS TEXT), COALESCE(l.deleted, 0),
                 COALESCE((SELECT COUNT(*) FROM nswallet_fields f WHERE f.type = l.field_type AND COALESCE(f.deleted, 0) = 0), 0) as usage
          FROM nswallet_labels l WHERE COALESCE(l.deleted, 0) = 0"
     )?;
@@ -1039,6 +3270,44 @@ pub fn get_all_labels(conn: &Connection) -> Result<Vec<RawLabel>> {
     labels.collect::<std::result::Result<Vec<_>, _>>().map_err(Into::into)
 }
 
+/// Get all soft-deleted labels from database (with usage count of active fields)
+pub fn get_deleted_labels(conn: &Connection) -> Result<Vec<RawLabel>> {
+    let mut stmt = conn.prepare(
+        "SELECT l.field_type, COALESCE(l.label_name, ''), COALESCE(l.value_type, 'text'),
+                COALESCE(l.icon, ''), COALESCE(l.system, 0), CAST(l.change_timestamp AS TEXT), l.deleted,
+                COALESCE((SELECT COUNT(*) FROM nswallet_fields f WHERE f.type = l.field_type AND COALESCE(f.deleted, 0) = 0), 0) as usage
+         FROM nswallet_labels l WHERE l.deleted = 1"
+    )?;
+
+    let labels = stmt.query_map([], |row| {
+        Ok(RawLabel {
+            field_type: row.get(0)?,
+            label_name: row.get(1)?,
+            value_type: row.get(2)?,
+            icon: row.get(3)?,
+            system: row.get::<_, i32>(4)? != 0,
+            change_timestamp: row.get(5)?,
+            deleted: row.get::<_, i32>(6)? != 0,
+            usage: row.get(7)?,
+        })
+    })?;
+
+    labels.collect::<std::result::Result<Vec<_>, _>>().map_err(Into::into)
+}
+
+/// Undelete a label (set deleted = 0)
+pub fn undelete_label(conn: &Connection, field_type: &str) -> Result<()> {
+    let rows = conn.execute(
+        "UPDATE nswallet_labels SET deleted = 0, change_timestamp = ? WHERE field_type = ? AND deleted = 1",
+        params![now_timestamp(), field_type],
+    )?;
+    if rows == 0 {
+        return Err(crate::error::WalletError::LabelNotFound(field_type.to_string()));
+    }
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(())
+}
+
 /// Create a new label
 pub fn create_label(
     conn: &Connection,
@@ -1057,6 +3326,54 @@ pub fn create_label(
     Ok(result > 0)
 }
 
+/// Insert a label row exactly as given, or overwrite the row with the same
+/// field type
+pub fn upsert_label_raw(conn: &Connection, label: &RawLabel) -> Result<()> {
+    upsert_label_raw_no_checkpoint(conn, label)?;
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(())
+}
+
+/// [`upsert_label_raw`] without the WAL checkpoint, for use inside an open
+/// transaction
+pub fn upsert_label_raw_no_checkpoint(conn: &Connection, label: &RawLabel) -> Result<()> {
+    conn.execute(
+        "INSERT INTO nswallet_labels (field_type, label_name, value_type, icon, system, change_timestamp, deleted)
+         VALUES (?, ?, ?, ?, ?, ?, ?)
+         ON CONFLICT(field_type) DO UPDATE SET
+            label_name = excluded.label_name, value_type = excluded.value_type, icon = excluded.icon,
+            system = excluded.system, change_timestamp = excluded.change_timestamp, deleted = excluded.deleted",
+        params![
+            label.field_type,
+            label.label_name,
+            label.value_type,
+            label.icon,
+            label.system as i32,
+            label.change_timestamp,
+            label.deleted as i32,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Insert a label row. Unlike `create_label` an existing field type is an
+/// error, and there is no checkpoint, so it can run inside a transaction.
+pub fn insert_label(
+    conn: &Connection,
+    field_type: &str,
+    label_name: &str,
+    value_type: &str,
+    icon: &str,
+    timestamp: &DateTime<Utc>,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO nswallet_labels (field_type, label_name, value_type, icon, system, change_timestamp, deleted)
+         VALUES (?, ?, ?, ?, 0, ?, 0)",
+        params![field_type, label_name, value_type, icon, format_timestamp(timestamp)],
+    )?;
+    Ok(())
+}
+
 /// Update label name
 pub fn update_label_name(conn: &Connection, field_type: &str, label_name: &str) -> Result<()> {
     conn.execute(
@@ -1077,8 +3394,8 @@ pub fn update_label_icon(conn: &Connection, field_type: &str, icon: &str) -> Res
     Ok(())
 }
 
-/// Soft delete a label (returns count of fields using it)
-pub fn delete_label(conn: &Connection, field_type: &str) -> Result<i32> {
+/// Soft delete a label. Fails with `LabelInUse` while active fields use it.
+pub fn delete_label(conn: &Connection, field_type: &str) -> Result<()> {
     // Count fields using this label
     let count: i32 = conn.query_row(
         "SELECT COUNT(*) FROM nswallet_fields WHERE type = ? AND deleted = 0",
@@ -1086,15 +3403,19 @@ pub fn delete_label(conn: &Connection, field_type: &str) -> Result<i32> {
         |row| row.get(0),
     )?;
 
-    if count == 0 {
-        conn.execute(
-            "UPDATE nswallet_labels SET deleted = 1, change_timestamp = ? WHERE field_type = ?",
-            params![now_timestamp(), field_type],
-        )?;
-        conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    if count > 0 {
+        return Err(crate::error::WalletError::LabelInUse(count as u32));
     }
 
-    Ok(count)
+    let rows = conn.execute(
+        "UPDATE nswallet_labels SET deleted = 1, change_timestamp = ? WHERE field_type = ? AND deleted = 0",
+        params![now_timestamp(), field_type],
+    )?;
+    if rows == 0 {
+        return Err(crate::error::WalletError::LabelNotFound(field_type.to_string()));
+    }
+    conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(())
 }
 
 /// Permanently delete a label
@@ -1107,6 +3428,81 @@ pub fn remove_label_for_real(conn: &Connection, field_type: &str) -> Result<bool
     Ok(result > 0)
 }
 
+// ============================================================================
+// Integrity manifest (all tables)
+// ============================================================================
+
+/// Names of all tables except SQLite's own, sorted
+pub fn list_user_tables(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name"
+    )?;
+    let names = stmt.query_map([], |row| row.get(0))?
+        .collect::<std::result::Result<Vec<String>, _>>()?;
+    Ok(names)
+}
+
+/// Every row of `table` as (key, canonical bytes). The key is the primary
+/// key values joined with `/` (the rowid for tables without one). The
+/// bytes encode each column but `skip_columns`, sorted by name, as the
+/// name, a type tag and the value, all length-prefixed, so equal bytes
+/// mean equal rows whatever the column order on disk.
+pub fn get_canonical_rows(conn: &Connection, table: &str, skip_columns: &[&str]) -> Result<Vec<(String, Vec<u8>)>> {
+    use rusqlite::types::ValueRef;
+
+    let quoted = format!("\"{}\"", table.replace('"', "\"\""));
+    let mut columns: Vec<(String, i32)> = {
+        let mut stmt = conn.prepare(&format!("PRAGMA table_info({quoted})"))?;
+        stmt.query_map([], |row| Ok((row.get::<_, String>(1)?, row.get::<_, i32>(5)?)))?
+            .collect::<std::result::Result<Vec<_>, _>>()?
+    };
+    columns.retain(|(name, _)| !skip_columns.contains(&name.as_str()));
+    columns.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut key_columns: Vec<(usize, i32)> = columns.iter().enumerate()
+        .filter(|(_, (_, pk))| *pk > 0)
+        .map(|(i, (_, pk))| (i, *pk))
+        .collect();
+    key_columns.sort_by_key(|(_, pk)| *pk);
+
+    let select: Vec<String> = columns.iter()
+        .map(|(name, _)| format!("\"{}\"", name.replace('"', "\"\"")))
+        .collect();
+    let sql = format!("SELECT rowid, {} FROM {quoted}", select.join(", "));
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query([])?;
+
+    let push = |out: &mut Vec<u8>, bytes: &[u8]| {
+        out.extend_from_slice(&(bytes.len() as u64).to_le_bytes());
+        out.extend_from_slice(bytes);
+    };
+    let mut result = Vec::new();
+    while let Some(row) = rows.next()? {
+        let mut bytes = Vec::new();
+        let mut values = Vec::with_capacity(columns.len());
+        for (i, (name, _)) in columns.iter().enumerate() {
+            push(&mut bytes, name.as_bytes());
+            let (tag, value, text) = match row.get_ref(i + 1)? {
+                ValueRef::Null => (0u8, Vec::new(), String::new()),
+                ValueRef::Integer(v) => (1, v.to_le_bytes().to_vec(), v.to_string()),
+                ValueRef::Real(v) => (2, v.to_le_bytes().to_vec(), v.to_string()),
+                ValueRef::Text(t) => (3, t.to_vec(), String::from_utf8_lossy(t).into_owned()),
+                ValueRef::Blob(b) => (4, b.to_vec(), b.iter().map(|x| format!("{x:02x}")).collect()),
+            };
+            bytes.push(tag);
+            push(&mut bytes, &value);
+            values.push(text);
+        }
+        let key = if key_columns.is_empty() {
+            format!("rowid:{}", row.get::<_, i64>(0)?)
+        } else {
+            key_columns.iter().map(|(i, _)| values[*i].as_str()).collect::<Vec<_>>().join("/")
+        };
+        result.push((key, bytes));
+    }
+    Ok(result)
+}
+
 // ============================================================================
 // Raw data structures (before decryption)
 // ============================================================================
@@ -1150,6 +3546,16 @@ pub struct RawItem {
     pub change_timestamp: Option<String>,
     /// Whether this item is soft-deleted
     pub deleted: bool,
+    /// Primary (quick-copy) field ID, if one is set
+    pub field_id: Option<String>,
+    /// Display color (`#rrggbb`), if one is set
+    pub color: Option<String>,
+    /// Encrypted metadata envelope (icon and timestamps), if sealed
+    pub meta: Option<Vec<u8>>,
+    /// Manual position within the parent folder, if arranged
+    pub sort_weight: Option<i32>,
+    /// Protected from edits until unlocked with `set_item_locked`
+    pub locked: bool,
 }
 
 /// Raw field data from database (before decryption)
@@ -1169,6 +3575,26 @@ pub struct RawField {
     pub deleted: bool,
     /// Display ordering weight
     pub sort_weight: Option<i32>,
+    /// Encrypted metadata envelope (change timestamp), if sealed
+    pub meta: Option<Vec<u8>>,
+}
+
+/// Raw attachment data from database (before decryption), without the
+/// content
+#[derive(Debug, Clone)]
+pub struct RawAttachment {
+    /// Unique attachment identifier
+    pub attachment_id: String,
+    /// Item the file is attached to
+    pub item_id: String,
+    /// Encrypted file name
+    pub name_encrypted: Vec<u8>,
+    /// Encrypted MIME type
+    pub mime_type_encrypted: Vec<u8>,
+    /// Plaintext content length in bytes
+    pub size: u64,
+    /// Creation timestamp
+    pub create_timestamp: Option<String>,
 }
 
 /// Raw label data from database
@@ -1220,6 +3646,19 @@ mod tests {
         assert!(parse_timestamp("2023-13-01 00:00:00").is_none());
     }
 
+    #[test]
+    fn test_parse_legacy_timestamps() {
+        let expected = Utc.with_ymd_and_hms(2013, 5, 20, 8, 15, 0).unwrap();
+        assert_eq!(parse_timestamp("2013-05-20T08:15:00"), Some(expected));
+        assert_eq!(parse_timestamp("2013-05-20T08:15:00.000Z"), Some(expected));
+        assert_eq!(parse_timestamp("2013-05-20T10:15:00+02:00"), Some(expected));
+        assert_eq!(parse_timestamp("2013-05-20 08:15:00.0000000"), Some(expected));
+        // .NET ticks, as stored by sqlite-net
+        assert_eq!(parse_timestamp("635046345000000000"), Some(expected));
+        assert_eq!(parse_timestamp("42"), None);
+        assert_eq!(parse_timestamp(""), None);
+    }
+
     #[test]
     fn test_now_timestamp() {
         let ts = now_timestamp();
@@ -0,0 +1,212 @@
+//! Salvage of damaged database files
+//!
+//! A torn write or a truncated WAL can leave `nswallet.dat` with damaged
+//! b-tree pages. SQLite then refuses some queries while most rows are still
+//! perfectly readable. This module detects that state and copies every
+//! readable row into a freshly created database, table by table and, when a
+//! bulk copy fails, row by row.
+
+use std::path::Path;
+use rusqlite::{Connection, OpenFlags, OptionalExtension};
+use crate::error::Result;
+use super::connection::Database;
+use super::queries;
+
+/// Row counts from one salvage pass
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SalvageStats {
+    /// Rows copied into the new database
+    pub rows_recovered: u64,
+    /// Rows that were listed in the damaged file but could not be read
+    pub rows_lost: u64,
+}
+
+/// Whether the database at `path` opens and passes `PRAGMA integrity_check`.
+pub fn is_healthy(path: &Path) -> bool {
+    let Ok(conn) = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY) else {
+        return false;
+    };
+    matches!(
+        conn.query_row("PRAGMA integrity_check", [], |row| row.get::<_, String>(0)),
+        Ok(ref s) if s == "ok"
+    )
+}
+
+/// Copy every readable row of every table of `damaged` into a new database
+/// created at `target`. Only columns present in both schemas are copied, so
+/// older schema generations salvage into the current layout; tables the
+/// new database does not have yet (those created on first use) are created
+/// from the damaged file's schema.
+pub fn salvage_into(damaged: &Path, target: &Path) -> Result<SalvageStats> {
+    let tables = {
+        let source = Connection::open_with_flags(damaged, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        queries::list_user_tables(&source)?
+    };
+
+    let db = Database::create(target)?;
+    let conn = db.connection()?;
+    queries::ensure_crypto_table(conn)?;
+    queries::ensure_quarantine_table(conn)?;
+
+    conn.execute(
+        "ATTACH DATABASE ? AS damaged",
+        [damaged.to_string_lossy().as_ref()],
+    )?;
+
+    let mut stats = SalvageStats::default();
+    for table in &tables {
+        create_missing_table(conn, table)?;
+        let columns = shared_columns(conn, table);
+        if columns.is_empty() {
+            continue;
+        }
+        let cols = columns.join(", ");
+
+        let bulk = format!(
+            "INSERT OR IGNORE INTO main.{table} ({cols}) SELECT {cols} FROM damaged.{table}"
+        );
+        if let Ok(n) = conn.execute(&bulk, []) {
+            stats.rows_recovered += n as u64;
+            continue;
+        }
+
+        // Bulk copy hit a damaged page: walk the rowids we can still reach
+        // and copy those one at a time.
+        let rowids = readable_rowids(conn, table);
+        let single = format!(
+            "INSERT OR IGNORE INTO main.{table} ({cols}) SELECT {cols} FROM damaged.{table} WHERE rowid = ?"
+        );
+        let mut copied = 0u64;
+        for rowid in &rowids {
+            if let Ok(n) = conn.execute(&single, [rowid]) {
+                copied += n as u64;
+            }
+        }
+        stats.rows_recovered += copied;
+        stats.rows_lost += (rowids.len() as u64).saturating_sub(copied);
+        if let Ok(total) = conn.query_row(
+            &format!("SELECT COUNT(*) FROM damaged.{table}"),
+            [],
+            |row| row.get::<_, i64>(0),
+        ) {
+            stats.rows_lost += (total as u64).saturating_sub(rowids.len() as u64);
+        }
+    }
+
+    conn.execute("DETACH DATABASE damaged", [])?;
+    Ok(stats)
+}
+
+/// Create `table` in `main` with its schema from `damaged`, unless it
+/// already exists
+fn create_missing_table(conn: &Connection, table: &str) -> Result<()> {
+    if !table_columns(conn, "main", table).is_empty() {
+        return Ok(());
+    }
+    let sql: Option<String> = conn.query_row(
+        "SELECT sql FROM damaged.sqlite_master WHERE type = 'table' AND name = ?",
+        [table],
+        |row| row.get(0),
+    ).optional()?;
+    if let Some(sql) = sql {
+        conn.execute(&sql, [])?;
+    }
+    Ok(())
+}
+
+/// Columns of `table` present in both `main` and `damaged`. Empty when the
+/// table is missing (or unreadable) on either side.
+fn shared_columns(conn: &Connection, table: &str) -> Vec<String> {
+    let target = table_columns(conn, "main", table);
+    table_columns(conn, "damaged", table)
+        .into_iter()
+        .filter(|c| target.contains(c))
+        .collect()
+}
+
+fn table_columns(conn: &Connection, schema: &str, table: &str) -> Vec<String> {
+    let Ok(mut stmt) = conn.prepare(&format!("PRAGMA {schema}.table_info({table})")) else {
+        return Vec::new();
+    };
+    let Ok(rows) = stmt.query_map([], |row| row.get::<_, String>(1)) else {
+        return Vec::new();
+    };
+    rows.map_while(|r| r.ok()).collect()
+}
+
+/// Rowids of `table` in the damaged file, up to the first unreadable page.
+fn readable_rowids(conn: &Connection, table: &str) -> Vec<i64> {
+    let Ok(mut stmt) = conn.prepare(&format!("SELECT rowid FROM damaged.{table}")) else {
+        return Vec::new();
+    };
+    let Ok(rows) = stmt.query_map([], |row| row.get::<_, i64>(0)) else {
+        return Vec::new();
+    };
+    rows.map_while(|r| r.ok()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_is_healthy() {
+        let temp = TempDir::new().unwrap();
+        let good = temp.path().join("good.db");
+        Database::create(&good).unwrap();
+        assert!(is_healthy(&good));
+
+        let bad = temp.path().join("bad.db");
+        std::fs::write(&bad, b"definitely not a sqlite database file").unwrap();
+        assert!(!is_healthy(&bad));
+        assert!(!is_healthy(&temp.path().join("missing.db")));
+    }
+
+    #[test]
+    fn test_salvage_copies_rows() {
+        let temp = TempDir::new().unwrap();
+        let src = temp.path().join("src.db");
+        {
+            let db = Database::create(&src).unwrap();
+            let conn = db.connection().unwrap();
+            super::super::queries::set_properties(conn, "db1", "en", "6", 0).unwrap();
+            super::super::queries::create_item(conn, "item0001", "__ROOT__", b"x", "doc", false).unwrap();
+        }
+
+        let dst = temp.path().join("dst.db");
+        let stats = salvage_into(&src, &dst).unwrap();
+        assert_eq!(stats.rows_recovered, 2);
+        assert_eq!(stats.rows_lost, 0);
+
+        let conn = Connection::open(&dst).unwrap();
+        assert!(super::super::queries::has_properties(&conn).unwrap());
+    }
+
+    #[test]
+    fn test_salvage_keeps_folder_pins() {
+        use crate::business::wallet::tests::create_test_wallet;
+        use crate::business::wallet::Wallet;
+
+        let (mut wallet, temp) = create_test_wallet();
+        let vault = wallet.add_item("Vault", "folder", true, None).unwrap();
+        let bank = wallet.add_item("Bank", "document", false, Some(&vault)).unwrap();
+        wallet.add_field(&bank, "PASS", "secret", None).unwrap();
+        wallet.set_folder_pin(&vault, "1234").unwrap();
+        wallet.set_item_note(&bank, "note").unwrap();
+        let src = wallet.folder.join(crate::DATABASE_FILENAME);
+        wallet.close();
+
+        let dir = temp.path().join("salvaged");
+        std::fs::create_dir(&dir).unwrap();
+        let stats = salvage_into(&src, &dir.join(crate::DATABASE_FILENAME)).unwrap();
+        assert_eq!(stats.rows_lost, 0);
+
+        let mut salvaged = Wallet::open(&dir).unwrap();
+        assert!(salvaged.unlock("TestPassword123").unwrap());
+        assert_eq!(salvaged.protected_folders().unwrap(), vec![vault.clone()]);
+        assert!(salvaged.unlock_folder(&vault, "1234").unwrap());
+        assert_eq!(salvaged.get_fields_by_item(&bank).unwrap()[0].value, "secret");
+        assert_eq!(salvaged.get_item_note(&bank).unwrap().as_deref(), Some("note"));
+    }
+}
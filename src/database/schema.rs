@@ -41,7 +41,11 @@ CREATE TABLE IF NOT EXISTS nswallet_items (
     folder          INTEGER,
     create_timestamp TEXT,
     change_timestamp TEXT,
-    deleted         INTEGER DEFAULT 0
+    deleted         INTEGER DEFAULT 0,
+    color           TEXT,
+    meta            BLOB,
+    sort_weight     INTEGER,
+    locked          INTEGER DEFAULT 0
 )
 "#;
 
@@ -55,6 +59,7 @@ CREATE TABLE IF NOT EXISTS nswallet_fields (
     change_timestamp TEXT,
     deleted         INTEGER DEFAULT 0,
     sort_weight     INTEGER,
+    meta            BLOB,
     PRIMARY KEY (item_id, field_id)
 )
 "#;
@@ -1,7 +1,17 @@
 //! Error types for IntelliWallet Core
+//!
+//! Errors from SQLite, ZIP archives and JSON keep the original error as
+//! their `source()`, so callers can inspect it (for example the SQLite
+//! error code) while `Display` stays a readable message.
 
 use thiserror::Error;
 
+use crate::business::PasswordReuse;
+use crate::crypto::DecryptError;
+
+/// Boxed underlying error of a [`WalletError::Backup`]
+pub type BoxedSource = Box<dyn std::error::Error + Send + Sync>;
+
 /// Main error type for wallet operations
 #[derive(Error, Debug)]
 pub enum WalletError {
@@ -21,22 +31,74 @@ pub enum WalletError {
     #[error("Encryption error: {0}")]
     EncryptionError(String),
 
-    /// Decryption failed - data may be corrupted
+    /// Decryption failed; the kind tells a wrong key from corrupted data
     #[error("Decryption error: {0}")]
-    DecryptionError(String),
+    DecryptionError(#[from] DecryptError),
 
     /// Database operation failed
     #[error("Database error: {0}")]
     DatabaseError(String),
 
+    /// SQLite call failed
+    #[error("Database error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+
     /// Backup operation failed
     #[error("Backup error: {0}")]
     BackupError(String),
 
+    /// Backup step failed on an underlying I/O, ZIP or SQLite error
+    #[error("Backup error: {context}: {source}")]
+    Backup {
+        context: String,
+        #[source]
+        source: BoxedSource,
+    },
+
+    /// Backup folder cannot be created or written to
+    #[error("Backup folder not writable: {0}")]
+    BackupFolderNotWritable(String),
+
+    /// Backup folder lacks the free space a backup needs
+    #[error("Not enough space for backup: {required} bytes needed, {available} available")]
+    InsufficientSpace { required: u64, available: u64 },
+
+    /// ZIP archive operation failed
+    #[error("Backup error: {0}")]
+    Zip(#[from] zip::result::ZipError),
+
+    /// JSON serialization or parsing failed
+    #[error("{context}: {source}")]
+    Json {
+        context: String,
+        #[source]
+        source: serde_json::Error,
+    },
+
     /// Item not found
     #[error("Item not found: {0}")]
     ItemNotFound(String),
 
+    /// Item is locked against edits; unlock it with `set_item_locked` first
+    #[error("Item is locked: {0}")]
+    ItemLocked(String),
+
+    /// Folder is PIN-protected; open it with `unlock_folder` first
+    #[error("Folder is locked: {0}")]
+    FolderLocked(String),
+
+    /// New password is a reused one and the reuse policy is `Reject`
+    #[error("Password reused: {0}")]
+    PasswordReused(PasswordReuse),
+
+    /// Session token is unknown, expired or revoked
+    #[error("Invalid session")]
+    InvalidSession,
+
+    /// The session's permissions do not allow the operation
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+
     /// Field not found
     #[error("Field not found: {0}")]
     FieldNotFound(String),
@@ -45,6 +107,46 @@ pub enum WalletError {
     #[error("Label not found: {0}")]
     LabelNotFound(String),
 
+    /// Label cannot be deleted while active fields use it
+    #[error("Label is in use by {0} field(s)")]
+    LabelInUse(u32),
+
+    /// Target parent folder does not exist (or is deleted)
+    #[error("Parent not found: {0}")]
+    ParentNotFound(String),
+
+    /// Item name exceeds `ITEM_NAME_MAX_LENGTH` characters
+    #[error("Name too long: {len} characters (max {max})")]
+    NameTooLong { len: usize, max: usize },
+
+    /// New master password is shorter than `PASSWORD_MIN_LENGTH` characters
+    #[error("Password too short: {len} characters (min {min})")]
+    PasswordTooShort { len: usize, min: usize },
+
+    /// New master password is longer than `PASSWORD_MAX_LENGTH` characters
+    #[error("Password too long: {len} characters (max {max})")]
+    PasswordTooLong { len: usize, max: usize },
+
+    /// No unused ID found after the allowed number of attempts
+    #[error("ID collision: {0}")]
+    IdCollision(String),
+
+    /// Item note exceeds the wallet's note size limit
+    #[error("Note too large: {size} bytes (max {max})")]
+    NoteTooLarge { size: usize, max: usize },
+
+    /// Attachment not found (or deleted)
+    #[error("Attachment not found: {0}")]
+    AttachmentNotFound(String),
+
+    /// Attachment exceeds `ATTACHMENT_MAX_SIZE`
+    #[error("Attachment too large: {size} bytes (max {max})")]
+    AttachmentTooLarge { size: usize, max: usize },
+
+    /// Emergency access grant not found
+    #[error("Emergency grant not found: {0}")]
+    EmergencyGrantNotFound(String),
+
     /// Invalid database version
     #[error("Invalid database version: {0}")]
     InvalidVersion(String),
@@ -64,17 +166,50 @@ pub enum WalletError {
     /// Export error
     #[error("Export error: {0}")]
     ExportError(String),
+
+    /// Field value does not match its label's value type (strict mode)
+    #[error("Validation error: {0}")]
+    ValidationError(String),
+
+    /// Integrity manifest cannot be checked against this wallet
+    #[error("Integrity error: {0}")]
+    IntegrityError(String),
+
+    /// Operation stopped by its `CancelToken` or time budget
+    #[error("Operation cancelled")]
+    Cancelled,
+
+    /// Config file could not be read or has invalid settings
+    #[error("Config error: {0}")]
+    ConfigError(String),
 }
 
-impl From<rusqlite::Error> for WalletError {
-    fn from(err: rusqlite::Error) -> Self {
-        WalletError::DatabaseError(err.to_string())
+impl WalletError {
+    /// A `Backup` error for a failed step, keeping `source` in the chain
+    pub(crate) fn backup(context: impl Into<String>, source: impl Into<BoxedSource>) -> Self {
+        WalletError::Backup { context: context.into(), source: source.into() }
     }
-}
 
-impl From<zip::result::ZipError> for WalletError {
-    fn from(err: zip::result::ZipError) -> Self {
-        WalletError::BackupError(err.to_string())
+    /// A `Json` error for a failed step, keeping `source` in the chain
+    pub(crate) fn json(context: impl Into<String>, source: serde_json::Error) -> Self {
+        WalletError::Json { context: context.into(), source }
+    }
+
+    /// The SQLite error this error came from, anywhere in its source chain
+    pub fn sqlite_error(&self) -> Option<&rusqlite::Error> {
+        let mut current: Option<&(dyn std::error::Error + 'static)> = Some(self);
+        while let Some(err) = current {
+            if let Some(sqlite) = err.downcast_ref::<rusqlite::Error>() {
+                return Some(sqlite);
+            }
+            current = err.source();
+        }
+        None
+    }
+
+    /// The SQLite result code of the underlying failure, if any
+    pub fn sqlite_error_code(&self) -> Option<rusqlite::ErrorCode> {
+        self.sqlite_error().and_then(|e| e.sqlite_error_code())
     }
 }
 
@@ -104,25 +239,50 @@ mod tests {
 
         let err = WalletError::InvalidVersion("999".to_string());
         assert!(err.to_string().contains("999"));
+
+        let err = WalletError::LabelInUse(3);
+        assert_eq!(err.to_string(), "Label is in use by 3 field(s)");
+
+        let err = WalletError::NameTooLong { len: 300, max: 256 };
+        assert_eq!(err.to_string(), "Name too long: 300 characters (max 256)");
     }
 
     #[test]
     fn test_error_from_rusqlite() {
         let sqlite_err = rusqlite::Error::QueryReturnedNoRows;
         let wallet_err: WalletError = sqlite_err.into();
-        match wallet_err {
-            WalletError::DatabaseError(msg) => assert!(!msg.is_empty()),
-            _ => panic!("Expected DatabaseError"),
-        }
+        assert!(wallet_err.to_string().starts_with("Database error: "));
+        assert!(matches!(wallet_err, WalletError::Sqlite(rusqlite::Error::QueryReturnedNoRows)));
+        assert!(std::error::Error::source(&wallet_err).is_some());
+    }
+
+    #[test]
+    fn test_error_from_decrypt() {
+        let wallet_err: WalletError = DecryptError::WrongKeyOrCorrupt.into();
+        assert_eq!(wallet_err.to_string(), "Decryption error: wrong key or corrupted data");
+        assert!(matches!(wallet_err, WalletError::DecryptionError(DecryptError::WrongKeyOrCorrupt)));
     }
 
     #[test]
     fn test_error_from_zip() {
         let zip_err = zip::result::ZipError::FileNotFound;
         let wallet_err: WalletError = zip_err.into();
-        match wallet_err {
-            WalletError::BackupError(msg) => assert!(!msg.is_empty()),
-            _ => panic!("Expected BackupError"),
-        }
+        assert!(wallet_err.to_string().starts_with("Backup error: "));
+        assert!(matches!(wallet_err, WalletError::Zip(_)));
+    }
+
+    #[test]
+    fn test_sqlite_error_code_through_chain() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE t (id INTEGER PRIMARY KEY)").unwrap();
+        conn.execute("INSERT INTO t VALUES (1)", []).unwrap();
+        let err: WalletError = conn.execute("INSERT INTO t VALUES (1)", []).unwrap_err().into();
+        assert_eq!(err.sqlite_error_code(), Some(rusqlite::ErrorCode::ConstraintViolation));
+
+        let wrapped = WalletError::backup("Failed to copy database", err);
+        assert_eq!(wrapped.sqlite_error_code(), Some(rusqlite::ErrorCode::ConstraintViolation));
+        assert!(wrapped.to_string().starts_with("Backup error: Failed to copy database: Database error: "));
+
+        assert_eq!(WalletError::Locked.sqlite_error_code(), None);
     }
 }
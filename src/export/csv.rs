@@ -3,41 +3,131 @@
 //! Produces an RFC 4180 CSV with one row per non-deleted field, including
 //! the parent item's id/name/path/folder flag as context columns.
 //! Items with no fields still get one row (empty field columns).
+//!
+//! [`generate_csv_columns`] writes a CSV of the entries with the columns a
+//! [`CsvExportOptions`] selects, for spreadsheets and audits, and can mask
+//! password values.
 
-use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 
+use super::fields::{mask, FieldMasking};
+use super::order::{compute_path, fields_by_item, items_by_id, sort_items};
+use super::ExportOptions;
 use crate::database::models::{IWField, IWItem};
 use crate::error::Result;
+use crate::utils::ValueType;
+use crate::utils::time::format_utc;
 
-const HEADER: &str = "item_id,item_name,item_path,item_is_folder,field_id,field_type,field_label,field_value,field_value_type,field_sort_weight,field_change_timestamp\n";
+const HEADER: &str = "item_id,item_name,item_path,item_is_folder,field_id,field_type,field_label,field_value,field_value_type,field_sort_weight,field_change_timestamp,item_color\n";
 
-/// Generate a CSV document from wallet items and fields.
-pub fn generate_csv(items: &[IWItem], fields: &[IWField]) -> Result<Vec<u8>> {
-    let items_map: HashMap<&str, &IWItem> = items
-        .iter()
-        .map(|item| (item.item_id.as_str(), item))
-        .collect();
+/// Translation keys of the column titles, in `HEADER` order
+const HEADER_KEYS: &[&str] = &[
+    "export_col_item_id", "export_col_item_name", "export_col_item_path", "export_col_item_is_folder",
+    "export_col_field_id", "export_col_field_type", "export_col_field_label", "export_col_field_value",
+    "export_col_field_value_type", "export_col_field_sort_weight", "export_col_field_changed",
+    "export_col_item_color",
+];
 
-    let mut fields_by_item: HashMap<&str, Vec<&IWField>> = HashMap::new();
-    for field in fields {
-        if !field.deleted {
-            fields_by_item
-                .entry(field.item_id.as_str())
-                .or_default()
-                .push(field);
+/// A column of [`generate_csv_columns`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CsvColumn {
+    ItemId,
+    /// Folders above the item ("Banking / Cards")
+    Path,
+    ItemName,
+    FieldType,
+    FieldLabel,
+    Value,
+    ItemCreated,
+    ItemChanged,
+    FieldChanged,
+}
+
+impl CsvColumn {
+    /// Title of the column in an unlocalized export
+    pub fn key(self) -> &'static str {
+        match self {
+            CsvColumn::ItemId => "item_id",
+            CsvColumn::Path => "item_path",
+            CsvColumn::ItemName => "item_name",
+            CsvColumn::FieldType => "field_type",
+            CsvColumn::FieldLabel => "field_label",
+            CsvColumn::Value => "field_value",
+            CsvColumn::ItemCreated => "item_create_timestamp",
+            CsvColumn::ItemChanged => "item_change_timestamp",
+            CsvColumn::FieldChanged => "field_change_timestamp",
         }
     }
-    for v in fields_by_item.values_mut() {
-        v.sort_by_key(|f| f.sort_weight);
+
+    /// Translation key of the column title
+    fn title_key(self) -> &'static str {
+        match self {
+            CsvColumn::ItemId => "export_col_item_id",
+            CsvColumn::Path => "export_col_item_path",
+            CsvColumn::ItemName => "export_col_item_name",
+            CsvColumn::FieldType => "export_col_field_type",
+            CsvColumn::FieldLabel => "export_col_field_label",
+            CsvColumn::Value => "export_col_field_value",
+            CsvColumn::ItemCreated => "export_col_item_created",
+            CsvColumn::ItemChanged => "export_col_item_changed",
+            CsvColumn::FieldChanged => "export_col_field_changed",
+        }
     }
+}
+
+/// Columns and masking of [`generate_csv_columns`]. The default has the
+/// path, item name, field label, value and field change time, unmasked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvExportOptions {
+    /// Columns in output order
+    pub columns: Vec<CsvColumn>,
+    /// How values of password-type fields (value type "pass") are shown;
+    /// other values are never masked
+    pub password_masking: FieldMasking,
+}
+
+impl Default for CsvExportOptions {
+    fn default() -> Self {
+        Self {
+            columns: vec![
+                CsvColumn::Path,
+                CsvColumn::ItemName,
+                CsvColumn::FieldLabel,
+                CsvColumn::Value,
+                CsvColumn::FieldChanged,
+            ],
+            password_masking: FieldMasking::None,
+        }
+    }
+}
+
+/// Generate a CSV document from wallet items and fields.
+pub fn generate_csv(items: &[IWItem], fields: &[IWField]) -> Result<Vec<u8>> {
+    generate_csv_with(items, fields, &ExportOptions::default())
+}
+
+/// [`generate_csv`] with column titles and dates in the language of
+/// `options`. Columns keep their order either way.
+pub fn generate_csv_with(items: &[IWItem], fields: &[IWField], options: &ExportOptions) -> Result<Vec<u8>> {
+    let items_map = items_by_id(items);
+    let fields_by_item = fields_by_item(fields);
 
     let mut out = String::new();
-    out.push_str(HEADER);
+    match options.translations {
+        Some(tr) => {
+            let titles: Vec<String> = HEADER_KEYS.iter().map(|k| csv_escape(tr.get(k))).collect();
+            out.push_str(&titles.join(","));
+            out.push('\n');
+        }
+        None => out.push_str(HEADER),
+    }
 
     let mut entries: Vec<&IWItem> = items.iter().filter(|i| !i.deleted).collect();
-    entries.sort_by_key(|a| a.name.to_lowercase());
+    sort_items(&mut entries, &items_map);
 
     for item in entries {
+        options.check_cancel()?;
         let path = compute_path(item, &items_map);
         let item_cols = format!(
             "{},{},{},{}",
@@ -46,6 +136,7 @@ pub fn generate_csv(items: &[IWItem], fields: &[IWField]) -> Result<Vec<u8>> {
             csv_escape(&path),
             item.folder,
         );
+        let color = csv_escape(item.color.as_deref().unwrap_or(""));
         match fields_by_item.get(item.item_id.as_str()) {
             Some(item_fields) if !item_fields.is_empty() => {
                 for f in item_fields {
@@ -63,13 +154,21 @@ pub fn generate_csv(items: &[IWItem], fields: &[IWField]) -> Result<Vec<u8>> {
                     out.push(',');
                     out.push_str(&f.sort_weight.to_string());
                     out.push(',');
-                    out.push_str(&csv_escape(&f.change_timestamp.to_rfc3339()));
+                    let changed = match options.translations {
+                        Some(tr) => format_utc(&f.change_timestamp, tr.get_language()),
+                        None => f.change_timestamp.to_rfc3339(),
+                    };
+                    out.push_str(&csv_escape(&changed));
+                    out.push(',');
+                    out.push_str(&color);
                     out.push('\n');
                 }
             }
             _ => {
                 out.push_str(&item_cols);
-                out.push_str(",,,,,,,\n");
+                out.push_str(",,,,,,,,");
+                out.push_str(&color);
+                out.push('\n');
             }
         }
     }
@@ -77,7 +176,68 @@ pub fn generate_csv(items: &[IWItem], fields: &[IWField]) -> Result<Vec<u8>> {
     Ok(out.into_bytes())
 }
 
-fn csv_escape(s: &str) -> String {
+/// Generate a CSV document of the active entries with the columns of
+/// `csv`: one row per active field, or one row with empty field columns
+/// for an entry without fields. Folders get no rows; their names are in
+/// the path column. Titles and dates follow the language of `options`.
+pub fn generate_csv_columns(
+    items: &[IWItem],
+    fields: &[IWField],
+    csv: &CsvExportOptions,
+    options: &ExportOptions,
+) -> Result<Vec<u8>> {
+    let items_map = items_by_id(items);
+    let fields_by_item = fields_by_item(fields);
+    let date = |time: &DateTime<Utc>| match options.translations {
+        Some(tr) => format_utc(time, tr.get_language()),
+        None => time.to_rfc3339(),
+    };
+
+    let titles: Vec<String> = csv.columns.iter()
+        .map(|c| csv_escape(options.translations.map_or(c.key(), |tr| tr.get(c.title_key()))))
+        .collect();
+    let mut out = titles.join(",");
+    out.push('\n');
+
+    let mut entries: Vec<&IWItem> = items.iter().filter(|i| !i.deleted && !i.folder).collect();
+    sort_items(&mut entries, &items_map);
+
+    for item in entries {
+        options.check_cancel()?;
+        let path = compute_path(item, &items_map);
+        let item_fields = fields_by_item.get(item.item_id.as_str());
+        let rows: Vec<Option<&IWField>> = match item_fields {
+            Some(group) if !group.is_empty() => group.iter().map(|f| Some(*f)).collect(),
+            _ => vec![None],
+        };
+        for field in rows {
+            let cells: Vec<String> = csv.columns.iter()
+                .map(|column| match (column, field) {
+                    (CsvColumn::ItemId, _) => item.item_id.clone(),
+                    (CsvColumn::Path, _) => path.clone(),
+                    (CsvColumn::ItemName, _) => item.name.clone(),
+                    (CsvColumn::ItemCreated, _) => date(&item.create_timestamp),
+                    (CsvColumn::ItemChanged, _) => date(&item.change_timestamp),
+                    (_, None) => String::new(),
+                    (CsvColumn::FieldType, Some(f)) => f.field_type.clone(),
+                    (CsvColumn::FieldLabel, Some(f)) => f.label.clone(),
+                    (CsvColumn::Value, Some(f)) => match ValueType::from_code(&f.value_type) {
+                        ValueType::Pass | ValueType::Pin => mask(f, csv.password_masking),
+                        _ => f.value.clone(),
+                    },
+                    (CsvColumn::FieldChanged, Some(f)) => date(&f.change_timestamp),
+                })
+                .map(|cell| csv_escape(&cell))
+                .collect();
+            out.push_str(&cells.join(","));
+            out.push('\n');
+        }
+    }
+
+    Ok(out.into_bytes())
+}
+
+pub(crate) fn csv_escape(s: &str) -> String {
     if s.contains(',') || s.contains('"') || s.contains('\n') || s.contains('\r') {
         let escaped = s.replace('"', "\"\"");
         format!("\"{}\"", escaped)
@@ -86,24 +246,6 @@ fn csv_escape(s: &str) -> String {
     }
 }
 
-fn compute_path(item: &IWItem, items_map: &HashMap<&str, &IWItem>) -> String {
-    let mut parts = Vec::new();
-    let mut cur = item.parent_id.as_deref();
-    while let Some(pid) = cur {
-        if pid == crate::ROOT_ID {
-            break;
-        }
-        if let Some(parent) = items_map.get(pid) {
-            parts.push(parent.name.clone());
-            cur = parent.parent_id.as_deref();
-        } else {
-            break;
-        }
-    }
-    parts.reverse();
-    parts.join(" / ")
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -119,6 +261,10 @@ mod tests {
             create_timestamp: Utc::now(),
             change_timestamp: Utc::now(),
             deleted,
+            primary_field: None,
+            color: None,
+            sort_weight: None,
+            locked: false,
         }
     }
 
@@ -211,4 +357,67 @@ mod tests {
         let s = String::from_utf8(generate_csv(&items, &[]).unwrap()).unwrap();
         assert!(s.contains("Banking / Cards"));
     }
+
+    #[test]
+    fn item_color_column() {
+        let mut entry = make_item("entry1", "Visa", Some("__ROOT__"), false, false);
+        entry.color = Some("#ff0000".to_string());
+        let items = vec![entry, make_item("entry2", "Amex", Some("__ROOT__"), false, false)];
+        let s = String::from_utf8(generate_csv(&items, &[]).unwrap()).unwrap();
+        let rows: Vec<&str> = s.lines().skip(1).collect();
+        assert_eq!(rows[0].split(',').next_back(), Some(""));
+        assert_eq!(rows[1].split(',').next_back(), Some("#ff0000"));
+        assert!(HEADER.trim_end().ends_with(",item_color"));
+    }
+
+    #[test]
+    fn output_does_not_depend_on_input_order() {
+        let mut items = vec![
+            make_item("folder1", "Banking", Some("__ROOT__"), true, false),
+            make_item("entry2", "Visa", Some("folder1"), false, false),
+            make_item("entry1", "Visa", Some("folder1"), false, false),
+            make_item("entry3", "Amazon", Some("__ROOT__"), false, false),
+        ];
+        let mut fields = vec![
+            make_field("entry1", "f2", "Pin", "1", 5, false),
+            make_field("entry1", "f1", "Number", "2", 5, false),
+            make_field("entry3", "f3", "Login", "me", 0, false),
+        ];
+        let a = generate_csv(&items, &fields).unwrap();
+        items.reverse();
+        fields.reverse();
+        assert_eq!(generate_csv(&items, &fields).unwrap(), a);
+
+        let s = String::from_utf8(a).unwrap();
+        let ids: Vec<&str> = s.lines().skip(1).map(|l| l.split(',').next().unwrap()).collect();
+        assert_eq!(ids, vec!["entry3", "folder1", "entry1", "entry1", "entry2"]);
+        assert!(s.find(",f1,").unwrap() < s.find(",f2,").unwrap());
+    }
+
+    #[test]
+    fn selected_columns_with_masked_passwords() {
+        let items = vec![
+            make_item("folder1", "Banking, Cards", Some("__ROOT__"), true, false),
+            make_item("entry1", "Visa", Some("folder1"), false, false),
+            make_item("entry2", "Empty", Some("__ROOT__"), false, false),
+        ];
+        let mut pass = make_field("entry1", "f2", "Password", "hunter22", 1, false);
+        pass.value_type = "pass".to_string();
+        let fields = vec![make_field("entry1", "f1", "Number", "4111", 0, false), pass];
+        let csv = CsvExportOptions {
+            columns: vec![CsvColumn::Path, CsvColumn::ItemName, CsvColumn::FieldLabel, CsvColumn::Value],
+            password_masking: FieldMasking::Full,
+        };
+        let s = String::from_utf8(generate_csv_columns(&items, &fields, &csv, &ExportOptions::default()).unwrap()).unwrap();
+        assert_eq!(s, "item_path,item_name,field_label,field_value\n\
+            \"Banking, Cards\",Visa,Number,4111\n\
+            \"Banking, Cards\",Visa,Password,••••••••\n\
+            ,Empty,,\n");
+
+        let tr = crate::localization::Translations::new().unwrap();
+        let csv = CsvExportOptions { columns: vec![CsvColumn::ItemCreated, CsvColumn::Value], ..Default::default() };
+        let s = String::from_utf8(generate_csv_columns(&items, &fields, &csv, &ExportOptions::localized(&tr)).unwrap()).unwrap();
+        assert!(s.starts_with("Item created,Value\n"));
+        assert!(s.contains("hunter22"));
+    }
 }
@@ -0,0 +1,354 @@
+//! Printable emergency sheet
+//!
+//! A "break-glass" page for a safe or a lawyer's file: where the wallet
+//! lives, its database ID, how to restore it from a backup, and optional
+//! recovery shares as QR codes. The master password is never on it; the
+//! sheet only helps someone who also has the password (or enough shares
+//! of it, split by other means) find and restore the vault.
+//! No entries are on it either: field values and secure note bodies stay
+//! in the vault and its backups.
+
+use std::path::PathBuf;
+
+use genpdf::elements::{Break, Paragraph};
+use genpdf::style::{Color, Style, StyledString};
+use genpdf::{Document, Element, Mm, Position, RenderResult, SimplePageDecorator, Size};
+use qrcode::QrCode;
+
+use crate::business::Wallet;
+use crate::error::{Result, WalletError};
+use super::font_family;
+
+/// Output format of [`emergency_sheet`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmergencySheetFormat {
+    /// PDF document
+    #[default]
+    Pdf,
+    /// Self-contained HTML page (QR codes as inline SVG)
+    Html,
+}
+
+/// What goes on the emergency sheet
+#[derive(Debug, Clone, Default)]
+pub struct EmergencySheetOptions {
+    pub format: EmergencySheetFormat,
+    /// Name printed in the title, e.g. the vault owner
+    pub owner: Option<String>,
+    /// Where backups are kept; defaults to the attached backup manager's
+    /// folder
+    pub backup_folder: Option<PathBuf>,
+    /// Recovery shares, each printed as a QR code with its text
+    pub recovery_shares: Vec<String>,
+    /// Free text for the reader (who to call, where the password is kept)
+    pub notes: Option<String>,
+}
+
+/// One labelled line of the sheet
+struct SheetEntry {
+    label: &'static str,
+    value: String,
+}
+
+/// Everything on the sheet, independent of the output format
+struct SheetContent {
+    title: String,
+    generated: String,
+    entries: Vec<SheetEntry>,
+    instructions: Vec<String>,
+    shares: Vec<String>,
+    notes: Option<String>,
+}
+
+const FOOTER: &str = "This sheet does not contain the master password. \
+    Keep it apart from anything that does.";
+
+/// Render the emergency sheet of `wallet`. Works on a locked wallet: it
+/// reads only the database properties and the backup folder.
+pub fn emergency_sheet(wallet: &Wallet, options: &EmergencySheetOptions) -> Result<Vec<u8>> {
+    let content = sheet_content(wallet, options)?;
+    match options.format {
+        EmergencySheetFormat::Pdf => render_pdf(&content),
+        EmergencySheetFormat::Html => render_html(&content),
+    }
+}
+
+fn sheet_content(wallet: &Wallet, options: &EmergencySheetOptions) -> Result<SheetContent> {
+    let properties = wallet.get_properties()?;
+    let backup_folder = options.backup_folder.clone()
+        .or_else(|| wallet.backup_manager().map(|m| m.folder().to_path_buf()));
+    let latest_backup = match wallet.backup_manager() {
+        Some(manager) => manager.get_latest_backup()?,
+        None => None,
+    };
+
+    let mut entries = vec![
+        SheetEntry { label: "Wallet folder", value: wallet.folder().display().to_string() },
+        SheetEntry { label: "Database file", value: wallet.database_path().display().to_string() },
+        SheetEntry { label: "Database ID", value: properties.database_id.clone() },
+        SheetEntry { label: "Database version", value: properties.version.clone() },
+    ];
+    if let Some(folder) = &backup_folder {
+        entries.push(SheetEntry { label: "Backup folder", value: folder.display().to_string() });
+    }
+    if let Some(backup) = &latest_backup {
+        let name = backup.path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        entries.push(SheetEntry {
+            label: "Latest backup",
+            value: format!("{} ({})", name, backup.timestamp.format("%Y-%m-%d %H:%M UTC")),
+        });
+    }
+
+    let backup_source = match &backup_folder {
+        Some(_) => "the backup folder above",
+        None => "wherever the owner keeps backups",
+    };
+    let instructions = vec![
+        "Install IntelliWallet on a new device.".to_string(),
+        format!(
+            "Copy the newest backup whose name contains the database ID {} from {}, \
+             or copy the database file itself.",
+            properties.database_id, backup_source
+        ),
+        "In IntelliWallet, choose to restore from a backup and select the copied file.".to_string(),
+        "Unlock with the master password.".to_string(),
+    ];
+
+    let title = match &options.owner {
+        Some(owner) => format!("IntelliWallet Emergency Sheet: {owner}"),
+        None => "IntelliWallet Emergency Sheet".to_string(),
+    };
+
+    Ok(SheetContent {
+        title,
+        generated: chrono::Utc::now().format("%Y-%m-%d %H:%M UTC").to_string(),
+        entries,
+        instructions,
+        shares: options.recovery_shares.clone(),
+        notes: options.notes.clone(),
+    })
+}
+
+fn qr_code(data: &str) -> Result<QrCode> {
+    QrCode::new(data.as_bytes())
+        .map_err(|e| WalletError::ExportError(format!("Failed to encode QR code: {}", e)))
+}
+
+fn render_pdf(content: &SheetContent) -> Result<Vec<u8>> {
+    let mut doc = Document::new(font_family()?);
+    doc.set_title(content.title.clone());
+
+    let mut decorator = SimplePageDecorator::new();
+    decorator.set_margins(15);
+    doc.set_page_decorator(decorator);
+
+    let title_style = Style::new().bold().with_font_size(16);
+    let heading_style = Style::new().bold().with_font_size(11);
+    let label_style = Style::new().bold().with_font_size(9);
+    let text_style = Style::new().with_font_size(9);
+    let muted_style = Style::new().with_font_size(8).with_color(Color::Rgb(110, 110, 110));
+
+    doc.push(Paragraph::new(StyledString::new(content.title.clone(), title_style)));
+    doc.push(Paragraph::new(StyledString::new(format!("Generated {}", content.generated), muted_style)));
+    doc.push(Break::new(1.5));
+
+    for entry in &content.entries {
+        let mut p = Paragraph::new(StyledString::new(format!("{}: ", entry.label), label_style));
+        p.push_styled(entry.value.clone(), text_style);
+        doc.push(p);
+    }
+    doc.push(Break::new(1.5));
+
+    doc.push(Paragraph::new(StyledString::new("How to restore", heading_style)));
+    for (i, step) in content.instructions.iter().enumerate() {
+        doc.push(Paragraph::new(StyledString::new(format!("{}. {}", i + 1, step), text_style)));
+    }
+
+    if !content.shares.is_empty() {
+        doc.push(Break::new(1.5));
+        doc.push(Paragraph::new(StyledString::new("Recovery shares", heading_style)));
+        for (i, share) in content.shares.iter().enumerate() {
+            doc.push(Break::new(1.0));
+            doc.push(Paragraph::new(StyledString::new(
+                format!("Share {} of {}", i + 1, content.shares.len()),
+                label_style,
+            )));
+            doc.push(QrElement::new(&qr_code(share)?, 1.0));
+            doc.push(Paragraph::new(StyledString::new(share.clone(), muted_style)));
+        }
+    }
+
+    if let Some(notes) = &content.notes {
+        doc.push(Break::new(1.5));
+        doc.push(Paragraph::new(StyledString::new("Notes", heading_style)));
+        for line in notes.lines() {
+            doc.push(Paragraph::new(StyledString::new(line.to_string(), text_style)));
+        }
+    }
+
+    doc.push(Break::new(2.0));
+    doc.push(Paragraph::new(StyledString::new(FOOTER, muted_style)));
+
+    let mut buf = Vec::new();
+    doc.render(&mut buf)
+        .map_err(|e| WalletError::ExportError(format!("Failed to render PDF: {}", e)))?;
+    Ok(buf)
+}
+
+pub(super) fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn render_html(content: &SheetContent) -> Result<Vec<u8>> {
+    let mut out = String::new();
+    out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    out.push_str(&format!("<title>{}</title>\n", html_escape(&content.title)));
+    out.push_str("<style>body{font-family:sans-serif;max-width:48em;margin:2em auto}\
+        .muted{color:#6e6e6e;font-size:smaller}.share{break-inside:avoid;margin:1em 0}\
+        .share svg{width:45mm;height:45mm}</style>\n</head>\n<body>\n");
+    out.push_str(&format!("<h1>{}</h1>\n", html_escape(&content.title)));
+    out.push_str(&format!("<p class=\"muted\">Generated {}</p>\n", html_escape(&content.generated)));
+
+    out.push_str("<dl>\n");
+    for entry in &content.entries {
+        out.push_str(&format!("<dt>{}</dt><dd>{}</dd>\n", entry.label, html_escape(&entry.value)));
+    }
+    out.push_str("</dl>\n");
+
+    out.push_str("<h2>How to restore</h2>\n<ol>\n");
+    for step in &content.instructions {
+        out.push_str(&format!("<li>{}</li>\n", html_escape(step)));
+    }
+    out.push_str("</ol>\n");
+
+    if !content.shares.is_empty() {
+        out.push_str("<h2>Recovery shares</h2>\n");
+        for (i, share) in content.shares.iter().enumerate() {
+            let svg = qr_code(share)?
+                .render::<qrcode::render::svg::Color>()
+                .quiet_zone(true)
+                .build();
+            // Drop the XML declaration so the SVG can sit inline
+            let svg = svg.split_once("?>").map_or(svg.as_str(), |(_, rest)| rest);
+            out.push_str(&format!(
+                "<div class=\"share\"><h3>Share {} of {}</h3>\n{}\n<p class=\"muted\">{}</p></div>\n",
+                i + 1, content.shares.len(), svg, html_escape(share)
+            ));
+        }
+    }
+
+    if let Some(notes) = &content.notes {
+        out.push_str("<h2>Notes</h2>\n");
+        for line in notes.lines() {
+            out.push_str(&format!("<p>{}</p>\n", html_escape(line)));
+        }
+    }
+
+    out.push_str(&format!("<p class=\"muted\">{}</p>\n</body>\n</html>\n", html_escape(FOOTER)));
+    Ok(out.into_bytes())
+}
+
+/// A QR code drawn with PDF strokes. genpdf has no filled shapes, so each
+/// row of dark modules is filled with closely spaced horizontal lines.
+struct QrElement {
+    /// Dark modules per row, quiet zone excluded
+    rows: Vec<Vec<bool>>,
+    /// Module edge length in mm
+    module: f64,
+}
+
+/// Quiet zone around the code, in modules
+const QR_QUIET_ZONE: usize = 4;
+/// Spacing of the fill lines, in mm (a default stroke is about 0.35 mm)
+const QR_LINE_STEP: f64 = 0.2;
+
+impl QrElement {
+    fn new(code: &QrCode, module: f64) -> Self {
+        let width = code.width();
+        let colors = code.to_colors();
+        let rows = colors.chunks(width)
+            .map(|row| row.iter().map(|c| *c == qrcode::Color::Dark).collect())
+            .collect();
+        Self { rows, module }
+    }
+
+    fn edge(&self) -> f64 {
+        (self.rows.len() + 2 * QR_QUIET_ZONE) as f64 * self.module
+    }
+}
+
+impl Element for QrElement {
+    fn render(
+        &mut self,
+        _context: &genpdf::Context,
+        area: genpdf::render::Area<'_>,
+        style: Style,
+    ) -> std::result::Result<RenderResult, genpdf::error::Error> {
+        let edge = self.edge();
+        let available = area.size();
+        if available.height < Mm::from(edge) || available.width < Mm::from(edge) {
+            // Does not fit: ask for a new page
+            return Ok(RenderResult { size: Size::new(0, 0), has_more: true });
+        }
+
+        let offset = QR_QUIET_ZONE as f64 * self.module;
+        let lines_per_module = (self.module / QR_LINE_STEP).ceil() as usize;
+        for (r, row) in self.rows.iter().enumerate() {
+            let mut c = 0;
+            while c < row.len() {
+                if !row[c] {
+                    c += 1;
+                    continue;
+                }
+                let start = c;
+                while c < row.len() && row[c] {
+                    c += 1;
+                }
+                let x0 = offset + start as f64 * self.module;
+                let x1 = offset + c as f64 * self.module;
+                for k in 0..lines_per_module {
+                    let y = offset + r as f64 * self.module
+                        + (k as f64 + 0.5) * self.module / lines_per_module as f64;
+                    area.draw_line(vec![Position::new(x0, y), Position::new(x1, y)], style);
+                }
+            }
+        }
+        Ok(RenderResult { size: Size::new(edge, edge), has_more: false })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::business::wallet::tests::create_test_wallet;
+
+    #[test]
+    fn test_emergency_sheet_pdf_and_html() {
+        let (mut wallet, _dir) = create_test_wallet();
+        wallet.lock();
+        let database_id = wallet.get_properties().unwrap().database_id;
+
+        let options = EmergencySheetOptions {
+            owner: Some("Alex <Doe>".to_string()),
+            recovery_shares: vec!["share-1-abc".to_string(), "share-2-def".to_string()],
+            notes: Some("Call the notary".to_string()),
+            ..EmergencySheetOptions::default()
+        };
+        let pdf = emergency_sheet(&wallet, &options).unwrap();
+        assert!(pdf.starts_with(b"%PDF"));
+
+        let html = emergency_sheet(&wallet, &EmergencySheetOptions {
+            format: EmergencySheetFormat::Html,
+            ..options
+        }).unwrap();
+        let html = String::from_utf8(html).unwrap();
+        assert!(html.contains(&database_id));
+        assert!(html.contains("Alex &lt;Doe&gt;"));
+        assert_eq!(html.matches("<svg").count(), 2);
+        assert!(html.contains("Call the notary"));
+        assert!(!html.contains("TestPassword123"));
+    }
+}
@@ -0,0 +1,223 @@
+//! Export of the fields of one type
+//!
+//! Slices of the wallet that other programs take as input: every LINK as a
+//! bookmarks file for a browser, every MAIL address as a CSV for an
+//! address book. Values can be masked for lists that are shared or
+//! printed.
+
+use serde::Serialize;
+
+use super::emergency::html_escape;
+use super::order::{compute_path, items_by_id, ordered};
+use super::csv_escape;
+use crate::database::models::{IWField, IWItem};
+use crate::error::{Result, WalletError};
+use crate::utils::card::{mask_card_number, CARD_MASK_CHAR};
+use crate::utils::seed::mask_seed_phrase;
+
+/// Output format of [`generate_field_export`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldExportFormat {
+    /// `item_name,item_path,field_label,value` rows
+    Csv,
+    /// Array of objects with the item ID, name, path, label and value
+    Json,
+    /// Netscape bookmark file, imported by all major browsers. Link fields
+    /// only, unmasked.
+    BookmarksHtml,
+}
+
+impl FieldExportFormat {
+    /// File extension
+    pub fn extension(&self) -> &'static str {
+        match self {
+            FieldExportFormat::Csv => "csv",
+            FieldExportFormat::Json => "json",
+            FieldExportFormat::BookmarksHtml => "html",
+        }
+    }
+}
+
+/// How much of each value an export shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FieldMasking {
+    /// Values as stored
+    #[default]
+    None,
+    /// Enough to recognize a value: the last four digits of a card, the
+    /// first letters of a seed phrase, the last four characters otherwise
+    Partial,
+    /// A fixed-length mask that hides the value and its length
+    Full,
+}
+
+/// Shown characters of a partially masked value
+const PARTIAL_VISIBLE: usize = 4;
+
+/// Length of a fully masked value
+const FULL_MASK_LEN: usize = 8;
+
+pub(crate) fn mask(field: &IWField, masking: FieldMasking) -> String {
+    match masking {
+        FieldMasking::None => field.value.clone(),
+        FieldMasking::Full => CARD_MASK_CHAR.to_string().repeat(FULL_MASK_LEN),
+        FieldMasking::Partial => match field.field_type.as_str() {
+            "CARD" => mask_card_number(&field.value),
+            "SEED" => mask_seed_phrase(&field.value),
+            _ => {
+                let chars: Vec<char> = field.value.chars().collect();
+                // Short values would be mostly revealed; hide them entirely
+                let visible = if chars.len() > 2 * PARTIAL_VISIBLE { PARTIAL_VISIBLE } else { 0 };
+                std::iter::repeat_n(CARD_MASK_CHAR, chars.len() - visible)
+                    .chain(chars[chars.len() - visible..].iter().copied())
+                    .collect()
+            }
+        },
+    }
+}
+
+#[derive(Serialize)]
+struct FieldRow<'a> {
+    item_id: &'a str,
+    item_name: &'a str,
+    item_path: String,
+    label: &'a str,
+    value: String,
+}
+
+/// Export the active fields of type `field_type` (e.g. "LINK", "MAIL"), in
+/// export order. Bookmarks need unmasked fields of a link value type.
+pub fn generate_field_export(
+    items: &[IWItem],
+    fields: &[IWField],
+    field_type: &str,
+    format: FieldExportFormat,
+    masking: FieldMasking,
+) -> Result<Vec<u8>> {
+    let (_, ordered_fields) = ordered(items, fields);
+    let items_map = items_by_id(items);
+    let mut rows = Vec::new();
+    let mut all_links = true;
+    for field in ordered_fields.into_iter().filter(|f| f.field_type == field_type) {
+        let Some(item) = items_map.get(field.item_id.as_str()).filter(|i| !i.deleted) else {
+            continue;
+        };
+        all_links &= field.value_type == "link";
+        rows.push(FieldRow {
+            item_id: &item.item_id,
+            item_name: &item.name,
+            item_path: compute_path(item, &items_map),
+            label: &field.label,
+            value: mask(field, masking),
+        });
+    }
+
+    match format {
+        FieldExportFormat::Csv => {
+            let mut out = String::from("item_name,item_path,field_label,value\n");
+            for row in &rows {
+                out.push_str(&format!(
+                    "{},{},{},{}\n",
+                    csv_escape(row.item_name),
+                    csv_escape(&row.item_path),
+                    csv_escape(row.label),
+                    csv_escape(&row.value),
+                ));
+            }
+            Ok(out.into_bytes())
+        }
+        FieldExportFormat::Json => serde_json::to_vec_pretty(&rows)
+            .map_err(|e| WalletError::json("Failed to serialize fields", e)),
+        FieldExportFormat::BookmarksHtml => {
+            if masking != FieldMasking::None {
+                return Err(WalletError::ExportError("Bookmarks cannot be masked".to_string()));
+            }
+            if !all_links {
+                return Err(WalletError::ExportError(format!("{field_type} fields are not links")));
+            }
+            let mut out = String::from(
+                "<!DOCTYPE NETSCAPE-Bookmark-file-1>\n\
+                 <META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=UTF-8\">\n\
+                 <TITLE>Bookmarks</TITLE>\n<H1>Bookmarks</H1>\n<DL><p>\n",
+            );
+            for row in &rows {
+                out.push_str(&format!(
+                    "    <DT><A HREF=\"{}\">{}</A>\n",
+                    html_escape(&row.value),
+                    html_escape(row.item_name),
+                ));
+            }
+            out.push_str("</DL><p>\n");
+            Ok(out.into_bytes())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn make_item(id: &str, name: &str) -> IWItem {
+        IWItem {
+            item_id: id.to_string(),
+            parent_id: None,
+            name: name.to_string(),
+            icon: "icon".to_string(),
+            folder: false,
+            create_timestamp: Utc::now(),
+            change_timestamp: Utc::now(),
+            deleted: false,
+            primary_field: None,
+            color: None,
+            sort_weight: None,
+            locked: false,
+        }
+    }
+
+    fn make_field(item_id: &str, field_id: &str, field_type: &str, value_type: &str, value: &str) -> IWField {
+        IWField {
+            item_id: item_id.to_string(),
+            field_id: field_id.to_string(),
+            field_type: field_type.to_string(),
+            value: value.to_string(),
+            label: field_type.to_string(),
+            icon: String::new(),
+            value_type: value_type.to_string(),
+            sort_weight: 0,
+            change_timestamp: Utc::now(),
+            deleted: false,
+            expired: false,
+            expiring: false,
+        }
+    }
+
+    #[test]
+    fn test_mask() {
+        let pass = make_field("I1", "F1", "PASS", "pass", "correct-horse");
+        assert_eq!(mask(&pass, FieldMasking::None), "correct-horse");
+        assert_eq!(mask(&pass, FieldMasking::Partial), "•••••••••orse");
+        assert_eq!(mask(&pass, FieldMasking::Full), "••••••••");
+        let pin = make_field("I1", "F2", "PINC", "pass", "1234");
+        assert_eq!(mask(&pin, FieldMasking::Partial), "••••");
+    }
+
+    #[test]
+    fn test_bookmarks_export() {
+        let items = vec![make_item("I1", "Bank & Co"), make_item("I2", "Mail")];
+        let fields = vec![
+            make_field("I1", "F1", "LINK", "link", "https://bank.example/?a=1&b=2"),
+            make_field("I2", "F2", "LINK", "link", "https://mail.example"),
+            make_field("I2", "F3", "MAIL", "mail", "me@mail.example"),
+        ];
+        let html = String::from_utf8(
+            generate_field_export(&items, &fields, "LINK", FieldExportFormat::BookmarksHtml, FieldMasking::None).unwrap()
+        ).unwrap();
+        assert!(html.starts_with("<!DOCTYPE NETSCAPE-Bookmark-file-1>"));
+        assert!(html.contains("<A HREF=\"https://bank.example/?a=1&amp;b=2\">Bank &amp; Co</A>"));
+        assert!(!html.contains("me@mail.example"));
+
+        assert!(generate_field_export(&items, &fields, "MAIL", FieldExportFormat::BookmarksHtml, FieldMasking::None).is_err());
+        assert!(generate_field_export(&items, &fields, "LINK", FieldExportFormat::BookmarksHtml, FieldMasking::Full).is_err());
+    }
+}
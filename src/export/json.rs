@@ -5,6 +5,7 @@
 
 use serde::Serialize;
 
+use super::order::ordered;
 use crate::database::models::{IWField, IWItem};
 use crate::error::{Result, WalletError};
 
@@ -22,16 +23,17 @@ struct JsonExport<'a> {
 
 /// Generate a pretty-printed JSON document from wallet items and fields.
 pub fn generate_json(items: &[IWItem], fields: &[IWField]) -> Result<Vec<u8>> {
+    let (items, fields) = ordered(items, fields);
     let payload = JsonExport {
         format: FORMAT,
         version: VERSION,
         exported_at: chrono::Utc::now(),
-        items: items.iter().filter(|i| !i.deleted).collect(),
-        fields: fields.iter().filter(|f| !f.deleted).collect(),
+        items,
+        fields,
     };
 
     serde_json::to_vec_pretty(&payload)
-        .map_err(|e| WalletError::ExportError(format!("Failed to serialize JSON: {}", e)))
+        .map_err(|e| WalletError::json("Failed to serialize JSON", e))
 }
 
 #[cfg(test)]
@@ -50,6 +52,10 @@ mod tests {
             create_timestamp: Utc::now(),
             change_timestamp: Utc::now(),
             deleted,
+            primary_field: None,
+            color: None,
+            sort_weight: None,
+            locked: false,
         }
     }
 
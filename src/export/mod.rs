@@ -1,15 +1,25 @@
 //! Export functionality for IntelliWallet
 //!
 //! This module provides data structures and utilities for exporting
-//! wallet data to various formats (PDF, CSV, JSON, XML).
+//! wallet data to various formats (PDF, CSV, JSON, XML), the fields of one
+//! type, and the printable emergency sheet.
 
 mod csv;
+mod emergency;
+mod fields;
 mod json;
+mod order;
 mod xml;
 
-pub use csv::generate_csv;
+pub use csv::{generate_csv, generate_csv_columns, generate_csv_with, CsvColumn, CsvExportOptions};
+pub(crate) use csv::csv_escape;
+pub use emergency::{emergency_sheet, EmergencySheetFormat, EmergencySheetOptions};
+pub use fields::{generate_field_export, FieldExportFormat, FieldMasking};
 pub use json::generate_json;
 pub use xml::generate_xml;
+pub(crate) use order::ordered;
+
+use order::{compute_path, fields_by_item, items_by_id, sort_items};
 
 use std::collections::HashMap;
 
@@ -21,6 +31,9 @@ use serde::{Deserialize, Serialize};
 
 use crate::database::models::{IWField, IWItem};
 use crate::error::{Result, WalletError};
+use crate::localization::Translations;
+use crate::utils::{sanitize_filename, CancelToken};
+use crate::utils::time::format_utc;
 
 static REGULAR_FONT: &[u8] = include_bytes!("fonts/NotoSans-Regular.ttf");
 static BOLD_FONT: &[u8] = include_bytes!("fonts/NotoSans-Bold.ttf");
@@ -46,6 +59,76 @@ impl std::fmt::Display for ExportItemType {
     }
 }
 
+/// Output format of an export
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExportFormat {
+    /// PDF document
+    Pdf,
+    /// RFC 4180 CSV, one row per field
+    Csv,
+    /// Pretty-printed JSON
+    Json,
+    /// UTF-8 XML
+    Xml,
+}
+
+impl ExportFormat {
+    /// Conventional file extension, without the dot
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Pdf => "pdf",
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+            ExportFormat::Xml => "xml",
+        }
+    }
+
+    /// Portable file name for an export of `name` in this format
+    pub fn filename(&self, name: &str) -> String {
+        format!("{}.{}", sanitize_filename(name), self.extension())
+    }
+
+    /// Render `items` and `fields` in this format
+    pub fn generate(&self, items: &[IWItem], fields: &[IWField]) -> Result<Vec<u8>> {
+        self.generate_with(items, fields, &ExportOptions::default())
+    }
+
+    /// Render `items` and `fields` in this format with `options`
+    pub fn generate_with(&self, items: &[IWItem], fields: &[IWField], options: &ExportOptions) -> Result<Vec<u8>> {
+        options.check_cancel()?;
+        match self {
+            ExportFormat::Pdf => generate_pdf_with(items, fields, options),
+            ExportFormat::Csv => generate_csv_with(items, fields, options),
+            ExportFormat::Json => generate_json(items, fields),
+            ExportFormat::Xml => generate_xml(items, fields),
+        }
+    }
+}
+
+/// Presentation options for exports
+#[derive(Clone, Copy, Default)]
+pub struct ExportOptions<'a> {
+    /// Language of the PDF date line and of CSV column titles and dates.
+    /// `None` keeps the English machine-oriented output (CSV keys, RFC 3339
+    /// dates). JSON and XML keys are never translated.
+    pub translations: Option<&'a Translations>,
+    /// Stops the export with `Cancelled` when it trips, checked between
+    /// entries
+    pub cancel: Option<&'a CancelToken>,
+}
+
+impl<'a> ExportOptions<'a> {
+    /// Options for documents in the language of `translations`
+    pub fn localized(translations: &'a Translations) -> Self {
+        ExportOptions { translations: Some(translations), cancel: None }
+    }
+
+    /// `Err(Cancelled)` if the export's token has tripped
+    pub(crate) fn check_cancel(&self) -> Result<()> {
+        self.cancel.map_or(Ok(()), CancelToken::check)
+    }
+}
+
 /// Model for PDF export items
 ///
 /// Represents a single exportable item with its display information.
@@ -109,54 +192,29 @@ const WIDE_CARD_THRESHOLD: usize = 6;
 /// Generate a PDF document from wallet items and fields.
 ///
 /// Produces a compact 2-column card layout of all non-deleted entries
-/// with their fields, sorted alphabetically.
+/// with their fields, sorted by folder path and name.
 pub fn generate_pdf(items: &[IWItem], fields: &[IWField]) -> Result<Vec<u8>> {
-    // Load embedded fonts
-    let regular = FontData::new(REGULAR_FONT.to_vec(), None)
-        .map_err(|e| WalletError::ExportError(format!("Failed to load regular font: {}", e)))?;
-    let bold = FontData::new(BOLD_FONT.to_vec(), None)
-        .map_err(|e| WalletError::ExportError(format!("Failed to load bold font: {}", e)))?;
-
-    let font_family = FontFamily {
-        regular: regular.clone(),
-        bold: bold.clone(),
-        italic: regular.clone(),
-        bold_italic: bold,
-    };
+    generate_pdf_with(items, fields, &ExportOptions::default())
+}
 
-    let mut doc = Document::new(font_family);
+/// [`generate_pdf`] with the export date in the language of `options`
+pub fn generate_pdf_with(items: &[IWItem], fields: &[IWField], options: &ExportOptions) -> Result<Vec<u8>> {
+    let mut doc = Document::new(font_family()?);
     doc.set_title("IntelliWallet Export");
 
     let mut decorator = SimplePageDecorator::new();
     decorator.set_margins(10);
     doc.set_page_decorator(decorator);
 
-    // Build field lookup: item_id -> Vec<&IWField> (non-deleted, sorted by sort_weight)
-    let mut fields_by_item: HashMap<String, Vec<&IWField>> = HashMap::new();
-    for field in fields {
-        if !field.deleted {
-            fields_by_item
-                .entry(field.item_id.clone())
-                .or_default()
-                .push(field);
-        }
-    }
-    for item_fields in fields_by_item.values_mut() {
-        item_fields.sort_by_key(|f| f.sort_weight);
-    }
-
-    // Build item lookup for path resolution
-    let items_map: HashMap<&str, &IWItem> = items
-        .iter()
-        .map(|item| (item.item_id.as_str(), item))
-        .collect();
+    let fields_by_item = fields_by_item(fields);
+    let items_map = items_by_id(items);
 
-    // Collect non-deleted, non-folder items sorted alphabetically
+    // Collect non-deleted, non-folder items in export order
     let mut entries: Vec<&IWItem> = items
         .iter()
         .filter(|item| !item.deleted && !item.folder)
         .collect();
-    entries.sort_by_key(|a| a.name.to_lowercase());
+    sort_items(&mut entries, &items_map);
 
     // -- Compact header --
     let title_style = Style::new().bold().with_font_size(14);
@@ -165,7 +223,11 @@ pub fn generate_pdf(items: &[IWItem], fields: &[IWField]) -> Result<Vec<u8>> {
             .aligned(genpdf::Alignment::Center),
     );
 
-    let date_str = chrono::Utc::now().format("%Y-%m-%d %H:%M UTC").to_string();
+    let now = chrono::Utc::now();
+    let date_str = match options.translations {
+        Some(tr) => tr.format("export_generated", &[("date", &format_utc(&now, tr.get_language()))]),
+        None => now.format("%Y-%m-%d %H:%M UTC").to_string(),
+    };
     let date_style = Style::new()
         .with_font_size(8)
         .with_color(Color::Rgb(130, 130, 130));
@@ -190,8 +252,9 @@ pub fn generate_pdf(items: &[IWItem], fields: &[IWField]) -> Result<Vec<u8>> {
 
     let mut i = 0;
     while i < entries.len() {
+        options.check_cancel()?;
         let field_count = fields_by_item
-            .get(&entries[i].item_id)
+            .get(entries[i].item_id.as_str())
             .map_or(0, |f| f.len());
         let is_wide = field_count > WIDE_CARD_THRESHOLD;
 
@@ -210,7 +273,7 @@ pub fn generate_pdf(items: &[IWItem], fields: &[IWField]) -> Result<Vec<u8>> {
         } else if i + 1 < entries.len() {
             // Check if next entry is also narrow
             let next_count = fields_by_item
-                .get(&entries[i + 1].item_id)
+                .get(entries[i + 1].item_id.as_str())
                 .map_or(0, |f| f.len());
             if next_count > WIDE_CARD_THRESHOLD {
                 // Current is narrow but next is wide — put current alone
@@ -258,6 +321,7 @@ pub fn generate_pdf(items: &[IWItem], fields: &[IWField]) -> Result<Vec<u8>> {
     }
 
     // Render to bytes
+    options.check_cancel()?;
     let mut buf = Vec::new();
     doc.render(&mut buf)
         .map_err(|e| WalletError::ExportError(format!("Failed to render PDF: {}", e)))?;
@@ -265,11 +329,37 @@ pub fn generate_pdf(items: &[IWItem], fields: &[IWField]) -> Result<Vec<u8>> {
     Ok(buf)
 }
 
+/// The embedded Noto Sans fonts as a PDF font family
+fn font_family() -> Result<FontFamily<FontData>> {
+    let regular = FontData::new(REGULAR_FONT.to_vec(), None)
+        .map_err(|e| WalletError::ExportError(format!("Failed to load regular font: {}", e)))?;
+    let bold = FontData::new(BOLD_FONT.to_vec(), None)
+        .map_err(|e| WalletError::ExportError(format!("Failed to load bold font: {}", e)))?;
+
+    Ok(FontFamily {
+        regular: regular.clone(),
+        bold: bold.clone(),
+        italic: regular,
+        bold_italic: bold,
+    })
+}
+
+/// A field value as printed. With the `phone` feature, PHON numbers written
+/// with a country code are shown in international format.
+fn display_value(field: &IWField) -> String {
+    #[cfg(feature = "phone")]
+    if field.field_type == "PHON"
+        && let Some(formatted) = crate::utils::format_phone_display(&field.value, "") {
+            return formatted;
+        }
+    field.value.clone()
+}
+
 /// Build a single entry card as a LinearLayout.
 #[allow(clippy::too_many_arguments)]
 fn build_card(
     item: &IWItem,
-    fields_by_item: &HashMap<String, Vec<&IWField>>,
+    fields_by_item: &HashMap<&str, Vec<&IWField>>,
     items_map: &HashMap<&str, &IWItem>,
     name_style: &Style,
     path_style: &Style,
@@ -298,13 +388,13 @@ fn build_card(
     )));
 
     // Fields
-    if let Some(item_fields) = fields_by_item.get(&item.item_id) {
+    if let Some(item_fields) = fields_by_item.get(item.item_id.as_str()) {
         for field in item_fields {
             let mut p = Paragraph::new(StyledString::new(
                 format!("{}: ", field.label),
                 *label_style,
             ));
-            p.push_styled(&field.value, *value_style);
+            p.push_styled(display_value(field), *value_style);
             card.push(p);
         }
     }
@@ -312,26 +402,6 @@ fn build_card(
     card
 }
 
-fn compute_path(item: &IWItem, items_map: &HashMap<&str, &IWItem>) -> String {
-    let mut path_parts = Vec::new();
-    let mut current_id = item.parent_id.as_deref();
-
-    while let Some(pid) = current_id {
-        if pid == "__ROOT__" {
-            break;
-        }
-        if let Some(parent) = items_map.get(pid) {
-            path_parts.push(parent.name.clone());
-            current_id = parent.parent_id.as_deref();
-        } else {
-            break;
-        }
-    }
-
-    path_parts.reverse();
-    path_parts.join(" / ")
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -347,6 +417,10 @@ mod tests {
             create_timestamp: Utc::now(),
             change_timestamp: Utc::now(),
             deleted,
+            primary_field: None,
+            color: None,
+            sort_weight: None,
+            locked: false,
         }
     }
 
@@ -0,0 +1,160 @@
+//! Deterministic export order
+//!
+//! Every exporter (and `Wallet::snapshot`) lists items by folder path, then
+//! name, then ID, and each item's fields by sort weight, then ID. Output is
+//! therefore identical for identical data, whatever order the database or a
+//! `HashMap` produced, so exports can be kept in version control and diffed.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use crate::database::models::{IWField, IWItem};
+
+/// Item lookup by ID, for path resolution
+pub(crate) fn items_by_id(items: &[IWItem]) -> HashMap<&str, &IWItem> {
+    items.iter().map(|item| (item.item_id.as_str(), item)).collect()
+}
+
+/// Names of the folders above `item`, outermost first (root excluded)
+pub(crate) fn path_parts(item: &IWItem, items_map: &HashMap<&str, &IWItem>) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut cur = item.parent_id.as_deref();
+    while let Some(pid) = cur {
+        if pid == crate::ROOT_ID || parts.len() > items_map.len() {
+            break;
+        }
+        match items_map.get(pid) {
+            Some(parent) => {
+                parts.push(parent.name.clone());
+                cur = parent.parent_id.as_deref();
+            }
+            None => break,
+        }
+    }
+    parts.reverse();
+    parts
+}
+
+/// Display path of the folder holding `item` ("Banking / Credit Cards")
+pub(crate) fn compute_path(item: &IWItem, items_map: &HashMap<&str, &IWItem>) -> String {
+    path_parts(item, items_map).join(" / ")
+}
+
+/// Sort items by path (case-insensitive, a folder right before its
+/// contents), then name, then ID
+pub(crate) fn sort_items(entries: &mut [&IWItem], items_map: &HashMap<&str, &IWItem>) {
+    entries.sort_by_cached_key(|item| {
+        let mut key: Vec<String> = path_parts(item, items_map)
+            .iter()
+            .map(|p| p.to_lowercase())
+            .collect();
+        key.push(item.name.to_lowercase());
+        (key, item.name.clone(), item.item_id.clone())
+    });
+}
+
+/// Field order within an item: sort weight, then ID
+pub(crate) fn cmp_fields(a: &IWField, b: &IWField) -> Ordering {
+    a.sort_weight.cmp(&b.sort_weight).then_with(|| a.field_id.cmp(&b.field_id))
+}
+
+/// Active fields grouped by item, each group in field order
+pub(crate) fn fields_by_item(fields: &[IWField]) -> HashMap<&str, Vec<&IWField>> {
+    let mut map: HashMap<&str, Vec<&IWField>> = HashMap::new();
+    for field in fields.iter().filter(|f| !f.deleted) {
+        map.entry(field.item_id.as_str()).or_default().push(field);
+    }
+    for group in map.values_mut() {
+        group.sort_by(|a, b| cmp_fields(a, b));
+    }
+    map
+}
+
+/// Active items in export order, and their active fields in the same order
+pub(crate) fn ordered<'a>(items: &'a [IWItem], fields: &'a [IWField]) -> (Vec<&'a IWItem>, Vec<&'a IWField>) {
+    let items_map = items_by_id(items);
+    let mut entries: Vec<&IWItem> = items.iter().filter(|i| !i.deleted).collect();
+    sort_items(&mut entries, &items_map);
+    let mut grouped = fields_by_item(fields);
+    let mut ordered_fields = Vec::new();
+    for item in &entries {
+        if let Some(group) = grouped.remove(item.item_id.as_str()) {
+            ordered_fields.extend(group);
+        }
+    }
+    // Fields of items not exported (orphans) keep a stable order at the end
+    let mut rest: Vec<&IWField> = grouped.into_values().flatten().collect();
+    rest.sort_by(|a, b| a.item_id.cmp(&b.item_id).then_with(|| cmp_fields(a, b)));
+    ordered_fields.extend(rest);
+    (entries, ordered_fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn make_item(id: &str, name: &str, parent_id: Option<&str>) -> IWItem {
+        IWItem {
+            item_id: id.to_string(),
+            parent_id: parent_id.map(|s| s.to_string()),
+            name: name.to_string(),
+            icon: "icon".to_string(),
+            folder: false,
+            create_timestamp: Utc::now(),
+            change_timestamp: Utc::now(),
+            deleted: false,
+            primary_field: None,
+            color: None,
+            sort_weight: None,
+            locked: false,
+        }
+    }
+
+    fn make_field(item_id: &str, field_id: &str, sort_weight: i32) -> IWField {
+        IWField {
+            item_id: item_id.to_string(),
+            field_id: field_id.to_string(),
+            field_type: "TEXT".to_string(),
+            value: String::new(),
+            label: String::new(),
+            icon: String::new(),
+            value_type: "text".to_string(),
+            sort_weight,
+            change_timestamp: Utc::now(),
+            deleted: false,
+            expired: false,
+            expiring: false,
+        }
+    }
+
+    #[test]
+    fn test_ordered_is_independent_of_input_order() {
+        let items = vec![
+            make_item("z1", "Zeta", Some("__ROOT__")),
+            make_item("b2", "bank", Some("f1")),
+            make_item("f1", "Banking", Some("__ROOT__")),
+            make_item("a1", "Alpha", Some("__ROOT__")),
+            make_item("b1", "bank", Some("f1")),
+        ];
+        let fields = vec![
+            make_field("b1", "fb", 1),
+            make_field("a1", "fa2", 2),
+            make_field("b1", "fa", 1),
+            make_field("a1", "fa1", 1),
+        ];
+        let (entries, ordered_fields) = ordered(&items, &fields);
+        let ids: Vec<&str> = entries.iter().map(|i| i.item_id.as_str()).collect();
+        assert_eq!(ids, vec!["a1", "f1", "b1", "b2", "z1"]);
+        let fids: Vec<&str> = ordered_fields.iter().map(|f| f.field_id.as_str()).collect();
+        assert_eq!(fids, vec!["fa1", "fa2", "fa", "fb"]);
+
+        let mut reversed_items = items.clone();
+        reversed_items.reverse();
+        let mut reversed_fields = fields.clone();
+        reversed_fields.reverse();
+        let (entries2, fields2) = ordered(&reversed_items, &reversed_fields);
+        assert_eq!(entries2.iter().map(|i| i.item_id.as_str()).collect::<Vec<_>>(), ids);
+        assert_eq!(fields2.iter().map(|f| f.field_id.as_str()).collect::<Vec<_>>(), fids);
+    }
+}
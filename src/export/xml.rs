@@ -5,8 +5,7 @@
 //! text content (so multi-line values aren't normalized away by attribute
 //! parsing rules).
 
-use std::collections::HashMap;
-
+use super::order::{fields_by_item, items_by_id, sort_items};
 use crate::database::models::{IWField, IWItem};
 use crate::error::Result;
 
@@ -14,15 +13,8 @@ const VERSION: &str = "1";
 
 /// Generate an XML document from wallet items and fields.
 pub fn generate_xml(items: &[IWItem], fields: &[IWField]) -> Result<Vec<u8>> {
-    let mut fields_by_item: HashMap<&str, Vec<&IWField>> = HashMap::new();
-    for f in fields {
-        if !f.deleted {
-            fields_by_item.entry(f.item_id.as_str()).or_default().push(f);
-        }
-    }
-    for v in fields_by_item.values_mut() {
-        v.sort_by_key(|f| f.sort_weight);
-    }
+    let items_map = items_by_id(items);
+    let fields_by_item = fields_by_item(fields);
 
     let mut out = String::new();
     out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
@@ -34,17 +26,21 @@ pub fn generate_xml(items: &[IWItem], fields: &[IWField]) -> Result<Vec<u8>> {
     out.push_str("  <items>\n");
 
     let mut entries: Vec<&IWItem> = items.iter().filter(|i| !i.deleted).collect();
-    entries.sort_by_key(|a| a.name.to_lowercase());
+    sort_items(&mut entries, &items_map);
 
     for item in entries {
         let parent_attr = item.parent_id.as_deref().unwrap_or("");
+        let color_attr = item.color.as_deref()
+            .map(|c| format!(" color=\"{}\"", xml_escape_attr(c)))
+            .unwrap_or_default();
         out.push_str(&format!(
-            "    <item id=\"{}\" name=\"{}\" parent_id=\"{}\" folder=\"{}\" icon=\"{}\" change_timestamp=\"{}\">\n",
+            "    <item id=\"{}\" name=\"{}\" parent_id=\"{}\" folder=\"{}\" icon=\"{}\"{} change_timestamp=\"{}\">\n",
             xml_escape_attr(&item.item_id),
             xml_escape_attr(&item.name),
             xml_escape_attr(parent_attr),
             item.folder,
             xml_escape_attr(&item.icon),
+            color_attr,
             xml_escape_attr(&item.change_timestamp.to_rfc3339()),
         ));
         if let Some(item_fields) = fields_by_item.get(item.item_id.as_str()) {
@@ -97,6 +93,10 @@ mod tests {
             create_timestamp: Utc::now(),
             change_timestamp: Utc::now(),
             deleted,
+            primary_field: None,
+            color: None,
+            sort_weight: None,
+            locked: false,
         }
     }
 
@@ -166,4 +166,14 @@ mod tests {
         assert!(s.contains("name=\"Почта\""));
         assert!(s.contains(">тест@mail.ru<"));
     }
+
+    #[test]
+    fn item_color_is_an_optional_attribute() {
+        let mut colored = make_item("a", "Red", None, false, false);
+        colored.color = Some("#ff0000".to_string());
+        let items = vec![colored, make_item("b", "Plain", None, false, false)];
+        let s = String::from_utf8(generate_xml(&items, &[]).unwrap()).unwrap();
+        assert!(s.contains("icon=\"icon\" color=\"#ff0000\" change_timestamp="));
+        assert_eq!(s.matches("color=").count(), 1);
+    }
 }
@@ -0,0 +1,270 @@
+//! CSV exports of other password managers
+//!
+//! Parses RFC 4180 CSV and describes, with a [`CsvMapping`], which columns
+//! hold the item name, the folder and the field values. Mappings for the
+//! exports of common password managers come from [`CsvSource`]; other
+//! layouts can be described with a mapping of their own.
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, WalletError};
+
+/// How the columns of a CSV file map to items. Columns are found by their
+/// header, ignoring case and surrounding spaces.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CsvMapping {
+    /// Column holding the item name
+    pub name_column: String,
+    /// Column holding the folder of the item, if any
+    pub folder_column: Option<String>,
+    /// Separator of nested folders in the folder column, e.g. `/` for
+    /// "Work/VPN"; `None` if folders do not nest
+    pub folder_separator: Option<char>,
+    /// Columns holding field values, with the field type of each, in the
+    /// order fields are added
+    pub field_columns: Vec<(String, String)>,
+    /// Values treated as empty, such as the placeholder URL LastPass
+    /// exports for secure notes
+    pub placeholder_values: Vec<String>,
+}
+
+impl CsvMapping {
+    /// Mapping with the item name in `name_column` and no folder
+    pub fn new(name_column: &str) -> Self {
+        Self {
+            name_column: name_column.to_string(),
+            folder_column: None,
+            folder_separator: None,
+            field_columns: Vec::new(),
+            placeholder_values: Vec::new(),
+        }
+    }
+
+    /// Take folders from `column`, nested by `separator`
+    pub fn folder(mut self, column: &str, separator: Option<char>) -> Self {
+        self.folder_column = Some(column.to_string());
+        self.folder_separator = separator;
+        self
+    }
+
+    /// Add a field of `field_type` from `column`
+    pub fn field(mut self, column: &str, field_type: &str) -> Self {
+        self.field_columns.push((column.to_string(), field_type.to_string()));
+        self
+    }
+}
+
+/// Password managers with a built-in [`CsvMapping`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CsvSource {
+    /// LastPass "Export" CSV
+    LastPass,
+    /// Bitwarden "Export vault" in `.csv` format
+    Bitwarden,
+    /// Chrome and other Chromium browsers, "Export passwords"
+    Chrome,
+    /// 1Password 8 CSV export
+    OnePassword,
+}
+
+impl CsvSource {
+    /// Column mapping of this source's export
+    pub fn mapping(self) -> CsvMapping {
+        match self {
+            CsvSource::LastPass => {
+                let mut mapping = CsvMapping::new("name")
+                    .folder("grouping", Some('\\'))
+                    .field("username", "USER")
+                    .field("password", "PASS")
+                    .field("url", "LINK")
+                    .field("totp", "2FAC")
+                    .field("extra", "NOTE");
+                mapping.placeholder_values.push("http://sn".to_string());
+                mapping
+            }
+            CsvSource::Bitwarden => CsvMapping::new("name")
+                .folder("folder", Some('/'))
+                .field("login_username", "USER")
+                .field("login_password", "PASS")
+                .field("login_uri", "LINK")
+                .field("login_totp", "2FAC")
+                .field("notes", "NOTE")
+                .field("fields", "NOTE"),
+            CsvSource::Chrome => CsvMapping::new("name")
+                .field("username", "USER")
+                .field("password", "PASS")
+                .field("url", "LINK")
+                .field("note", "NOTE"),
+            CsvSource::OnePassword => CsvMapping::new("Title")
+                .field("Username", "USER")
+                .field("Password", "PASS")
+                .field("Url", "LINK")
+                .field("OTPAuth", "2FAC")
+                .field("Notes", "NOTE"),
+        }
+    }
+}
+
+/// A CSV data row mapped to an item
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvRecord {
+    /// Line of the row in the file, counting from 1
+    pub line: usize,
+    /// Item name; empty if the name column is
+    pub name: String,
+    /// Folder names from the outermost in; empty for the root
+    pub folders: Vec<String>,
+    /// Non-empty field values with their field types, in mapping order
+    pub fields: Vec<(String, String)>,
+}
+
+/// Map the data rows of a CSV document with `mapping`. Fails if the header
+/// has no name column or a quoted value is not closed.
+pub fn read_csv(text: &str, mapping: &CsvMapping) -> Result<Vec<CsvRecord>> {
+    let mut rows = parse_csv(text)?.into_iter();
+    let Some((_, header)) = rows.next() else {
+        return Ok(Vec::new());
+    };
+    let column = |name: &str| header.iter().position(|h| h.trim().eq_ignore_ascii_case(name.trim()));
+
+    let name_index = column(&mapping.name_column).ok_or_else(|| WalletError::ValidationError(
+        format!("CSV has no {:?} column", mapping.name_column)
+    ))?;
+    let folder_index = mapping.folder_column.as_deref().and_then(column);
+    let field_indexes: Vec<(usize, &str)> = mapping.field_columns.iter()
+        .filter_map(|(col, field_type)| Some((column(col)?, field_type.as_str())))
+        .collect();
+
+    // Names, folders and URLs are trimmed; other values, passwords above
+    // all, are kept exactly as exported
+    let cell = |row: &[String], index: usize, trim: bool| -> Option<String> {
+        let raw = row.get(index)?;
+        let value = if trim { raw.trim() } else { raw.as_str() };
+        (!value.is_empty() && !mapping.placeholder_values.iter().any(|p| p == value.trim())).then(|| value.to_string())
+    };
+    Ok(rows
+        .filter(|(_, row)| row.iter().any(|v| !v.trim().is_empty()))
+        .map(|(line, row)| {
+            let folders = folder_index.and_then(|i| cell(&row, i, true))
+                .map(|path| match mapping.folder_separator {
+                    Some(sep) => path.split(sep).map(str::trim).filter(|f| !f.is_empty()).map(str::to_string).collect(),
+                    None => vec![path],
+                })
+                .unwrap_or_default();
+            CsvRecord {
+                line,
+                name: cell(&row, name_index, true).unwrap_or_default(),
+                folders,
+                fields: field_indexes.iter()
+                    .filter_map(|&(i, field_type)| Some((field_type.to_string(), cell(&row, i, field_type == "LINK")?)))
+                    .collect(),
+            }
+        })
+        .collect())
+}
+
+/// Split an RFC 4180 document into rows, each with the line it starts on.
+/// Accepts LF or CRLF line ends and a leading byte order mark.
+fn parse_csv(text: &str) -> Result<Vec<(usize, Vec<String>)>> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut value = String::new();
+    let mut line = 1;
+    let mut row_line = 1;
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    value.push('"');
+                }
+                '"' => quoted = false,
+                _ => {
+                    if c == '\n' {
+                        line += 1;
+                    }
+                    value.push(c);
+                }
+            }
+            continue;
+        }
+        match c {
+            '"' => quoted = true,
+            ',' => row.push(std::mem::take(&mut value)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                row.push(std::mem::take(&mut value));
+                rows.push((row_line, std::mem::take(&mut row)));
+                line += 1;
+                row_line = line;
+            }
+            _ => value.push(c),
+        }
+    }
+    if quoted {
+        return Err(WalletError::ValidationError(format!("CSV quote opened on line {row_line} is not closed")));
+    }
+    if !value.is_empty() || !row.is_empty() {
+        row.push(value);
+        rows.push((row_line, row));
+    }
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csv_quoting() {
+        let rows = parse_csv("\u{feff}a,b,c\r\n\"x, y\",\"say \"\"hi\"\"\",\"two\nlines\"\n\nlast,,").unwrap();
+        let values: Vec<Vec<&str>> = rows.iter().map(|(_, r)| r.iter().map(String::as_str).collect()).collect();
+        assert_eq!(values, [
+            vec!["a", "b", "c"],
+            vec!["x, y", "say \"hi\"", "two\nlines"],
+            vec![""],
+            vec!["last", "", ""],
+        ]);
+        assert_eq!(rows.iter().map(|(line, _)| *line).collect::<Vec<_>>(), [1, 2, 4, 5]);
+        assert!(parse_csv("a,\"open\n").is_err());
+    }
+
+    #[test]
+    fn test_read_csv_with_source_mappings() {
+        let lastpass = "url,username,password,totp,extra,name,grouping,fav\n\
+            https://mail.example.com,joe,pw1,,,Mail,Personal\\Email,0\n\
+            http://sn,,,,Wifi code 1234,Router,,0\n";
+        let records = read_csv(lastpass, &CsvSource::LastPass.mapping()).unwrap();
+        assert_eq!(records[0].folders, ["Personal", "Email"]);
+        assert_eq!(records[0].fields, [
+            ("USER".to_string(), "joe".to_string()),
+            ("PASS".to_string(), "pw1".to_string()),
+            ("LINK".to_string(), "https://mail.example.com".to_string()),
+        ]);
+        assert_eq!(records[1].fields, [("NOTE".to_string(), "Wifi code 1234".to_string())]);
+        assert_eq!((records[1].line, records[1].folders.len()), (3, 0));
+
+        // Headers match regardless of case; unknown columns are ignored
+        let custom = CsvMapping::new("Site").folder("Group", None).field("Login", "USER");
+        let records = read_csv("SITE,LOGIN,Extra\nShop,ann,x\n", &custom).unwrap();
+        assert_eq!((records[0].name.as_str(), records[0].fields.len()), ("Shop", 1));
+        assert!(matches!(read_csv("title,url\nA,b\n", &CsvSource::Chrome.mapping()), Err(WalletError::ValidationError(_))));
+    }
+
+    #[test]
+    fn test_read_csv_keeps_values_verbatim() {
+        let chrome = "name,url,username,password,note\n \
+            Bank , https://bank.example.com ,joe,\"  pw with spaces \",\n";
+        let records = read_csv(chrome, &CsvSource::Chrome.mapping()).unwrap();
+        assert_eq!(records[0].name, "Bank");
+        assert_eq!(records[0].fields, [
+            ("USER".to_string(), "joe".to_string()),
+            ("PASS".to_string(), "  pw with spaces ".to_string()),
+            ("LINK".to_string(), "https://bank.example.com".to_string()),
+        ]);
+    }
+}
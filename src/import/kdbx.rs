@@ -0,0 +1,877 @@
+//! KeePass 2.x database (.kdbx) reader
+//!
+//! Reads KDBX 3.1 and 4.x files protected by a master password alone:
+//! AES-256 or ChaCha20 outer encryption, AES-KDF, Argon2d or Argon2id key
+//! derivation, and Salsa20 or ChaCha20 for values KeePass keeps protected in
+//! memory. Key files and Twofish-encrypted databases are reported as
+//! unsupported.
+
+use std::io::Read;
+
+use aes::Aes256;
+use aes::cipher::{BlockCipherEncrypt, KeyInit};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use cbc::cipher::{BlockModeDecrypt, KeyIvInit};
+use chacha20::ChaCha20;
+use chacha20::cipher::StreamCipher;
+use hmac::{Hmac, Mac};
+use quick_xml::Reader;
+use quick_xml::events::{BytesStart, Event};
+use sha2::{Digest, Sha256, Sha512};
+use uuid::Uuid;
+use zeroize::Zeroizing;
+
+use crate::crypto::kdf::KdfParams;
+use crate::error::{Result, WalletError};
+
+type HmacSha256 = Hmac<Sha256>;
+
+const SIGNATURE_1: u32 = 0x9AA2_D903;
+const SIGNATURE_2: u32 = 0xB54B_FB67;
+
+const CIPHER_AES256: Uuid = Uuid::from_u128(0x31c1f2e6_bf71_4350_be58_05216afc5aff);
+const CIPHER_CHACHA20: Uuid = Uuid::from_u128(0xd6038a2b_8b6f_4cb5_a524_339a31dbb59a);
+
+const KDF_AES: Uuid = Uuid::from_u128(0xc9d9f39a_628a_4460_bf74_0d08c18a4fea);
+const KDF_ARGON2D: Uuid = Uuid::from_u128(0xef636ddf_8c29_444b_91f7_a9a403e30a0c);
+const KDF_ARGON2ID: Uuid = Uuid::from_u128(0x9e298b19_56db_4773_b23d_fc3ec6f0a1e6);
+
+/// Inner random stream IDs
+const STREAM_NONE: u32 = 0;
+const STREAM_SALSA20: u32 = 2;
+const STREAM_CHACHA20: u32 = 3;
+
+/// Most AES-KDF rounds accepted; KeePass benchmarks one second of rounds,
+/// a few million on current hardware
+const MAX_AES_KDF_ROUNDS: u64 = 100_000_000;
+
+/// Largest decompressed payload accepted
+const MAX_PAYLOAD_LEN: u64 = 256 * 1024 * 1024;
+
+/// Deepest group nesting accepted; groups are imported as nested folders
+const MAX_GROUP_DEPTH: usize = 64;
+
+/// Fixed nonce of the Salsa20 inner stream
+const SALSA20_NONCE: [u8; 8] = [0xE8, 0x30, 0x09, 0x4B, 0x97, 0x20, 0x5D, 0x2A];
+
+/// A KeePass group with its subgroups and entries
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KdbxGroup {
+    pub name: String,
+    pub groups: Vec<KdbxGroup>,
+    pub entries: Vec<KdbxEntry>,
+    /// Whether this group is the database's recycle bin
+    pub recycle_bin: bool,
+}
+
+/// A KeePass entry. History entries are not included.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KdbxEntry {
+    /// String fields in file order, e.g. `("UserName", "joe")`. Protected
+    /// values are already decrypted.
+    pub strings: Vec<(String, String)>,
+}
+
+impl KdbxEntry {
+    /// Value of the string field `key`
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.strings.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+}
+
+/// Decrypt a KeePass database and return its root group. A wrong password
+/// is reported as [`WalletError::InvalidPassword`].
+pub fn read_kdbx(bytes: &[u8], password: &str) -> Result<KdbxGroup> {
+    let mut cursor = Cursor { data: bytes, pos: 0 };
+    if cursor.u32()? != SIGNATURE_1 || cursor.u32()? != SIGNATURE_2 {
+        return Err(WalletError::ValidationError("Not a KeePass database".to_string()));
+    }
+    let _minor = cursor.u16()?;
+    let major = cursor.u16()?;
+    if !(3..=4).contains(&major) {
+        return Err(unsupported(&format!("KDBX version {major}")));
+    }
+    let header = Header::read(&mut cursor, major)?;
+    let header_bytes = &bytes[..cursor.pos];
+
+    let composite = composite_key(password);
+    let transformed = header.kdf.transform(&composite)?;
+    let key = Zeroizing::new(sha256(&[&header.master_seed, transformed.as_ref()]));
+
+    let (payload, stream_id, stream_key) = if major >= 4 {
+        if cursor.take(32)? != sha256(&[header_bytes]) {
+            return Err(corrupt());
+        }
+        let hmac_base = hmac_base_key(&header.master_seed, &transformed);
+        let header_mac = cursor.take(32)?;
+        if block_mac(&hmac_base, u64::MAX, &[header_bytes]).verify_slice(header_mac).is_err() {
+            return Err(WalletError::InvalidPassword);
+        }
+        let encrypted = read_hmac_blocks(&mut cursor, &hmac_base)?;
+        let plain = decrypt_payload(&header, &key, &encrypted)?;
+        let plain = if header.compressed { gunzip(&plain, MAX_PAYLOAD_LEN)? } else { plain };
+        let mut inner = Cursor { data: &plain, pos: 0 };
+        let (stream_id, stream_key) = read_inner_header(&mut inner)?;
+        (Zeroizing::new(plain[inner.pos..].to_vec()), stream_id, stream_key)
+    } else {
+        let plain = decrypt_payload(&header, &key, &bytes[cursor.pos..])?;
+        if plain.len() < 32 || plain[..32] != header.stream_start_bytes[..] {
+            return Err(WalletError::InvalidPassword);
+        }
+        let blocks = read_hashed_blocks(&mut Cursor { data: &plain, pos: 32 })?;
+        let payload = if header.compressed { gunzip(&blocks, MAX_PAYLOAD_LEN)? } else { blocks };
+        (payload, header.inner_stream, header.protected_stream_key.clone())
+    };
+
+    let mut stream = InnerStream::new(stream_id, &stream_key)?;
+    let xml = std::str::from_utf8(&payload).map_err(|_| corrupt())?;
+    parse_xml(xml.trim_start_matches('\u{feff}'), &mut stream)
+}
+
+fn unsupported(what: &str) -> WalletError {
+    WalletError::ValidationError(format!("Unsupported KeePass database: {what}"))
+}
+
+fn corrupt() -> WalletError {
+    WalletError::ValidationError("KeePass database is damaged".to_string())
+}
+
+struct Cursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.data.len()).ok_or_else(corrupt)?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().expect("take returns N bytes"))
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn i32(&mut self) -> Result<i32> {
+        Ok(i32::from_le_bytes(self.array()?))
+    }
+}
+
+/// Little-endian integer of 4 or 8 bytes
+fn le_uint(bytes: &[u8]) -> Result<u64> {
+    match bytes.len() {
+        4 => Ok(u32::from_le_bytes(bytes.try_into().expect("4 bytes")) as u64),
+        8 => Ok(u64::from_le_bytes(bytes.try_into().expect("8 bytes"))),
+        _ => Err(corrupt()),
+    }
+}
+
+/// Key derivation function and its parameters
+enum Kdf {
+    Aes { seed: Vec<u8>, rounds: u64 },
+    Argon2 { algorithm: Algorithm, salt: Vec<u8>, memory: u64, iterations: u64, parallelism: u32, version: u32 },
+}
+
+impl Kdf {
+    /// Parse the KDBX 4 KDF parameters, a serialized variant dictionary
+    fn from_parameters(data: &[u8]) -> Result<Self> {
+        let mut cursor = Cursor { data, pos: 0 };
+        if cursor.u16()? >> 8 != 1 {
+            return Err(unsupported("KDF parameter format"));
+        }
+        let mut params = Vec::new();
+        loop {
+            let kind = cursor.u8()?;
+            if kind == 0 {
+                break;
+            }
+            let name_len = cursor.i32()?;
+            let name = cursor.take(usize::try_from(name_len).map_err(|_| corrupt())?)?;
+            let value_len = cursor.i32()?;
+            let value = cursor.take(usize::try_from(value_len).map_err(|_| corrupt())?)?;
+            params.push((name, value));
+        }
+        let get = |name: &str| params.iter()
+            .find(|(n, _)| *n == name.as_bytes())
+            .map(|(_, v)| *v)
+            .ok_or_else(corrupt);
+
+        let uuid = Uuid::from_slice(get("$UUID")?).map_err(|_| corrupt())?;
+        let algorithm = match uuid {
+            KDF_AES => return Ok(Kdf::Aes { seed: get("S")?.to_vec(), rounds: le_uint(get("R")?)? }),
+            KDF_ARGON2D => Algorithm::Argon2d,
+            KDF_ARGON2ID => Algorithm::Argon2id,
+            _ => return Err(unsupported("key derivation function")),
+        };
+        Ok(Kdf::Argon2 {
+            algorithm,
+            salt: get("S")?.to_vec(),
+            memory: le_uint(get("M")?)?,
+            iterations: le_uint(get("I")?)?,
+            parallelism: le_uint(get("P")?)? as u32,
+            version: get("V").ok().map(le_uint).transpose()?.unwrap_or(0x13) as u32,
+        })
+    }
+
+    /// Derive the transformed key from the composite key. The parameters
+    /// come from the file, so costs past `MAX_AES_KDF_ROUNDS` or the
+    /// untrusted Argon2 limits are refused before any work is done.
+    fn transform(&self, composite: &[u8; 32]) -> Result<Zeroizing<[u8; 32]>> {
+        let mut out = Zeroizing::new([0u8; 32]);
+        match self {
+            Kdf::Aes { seed, rounds } => {
+                if *rounds > MAX_AES_KDF_ROUNDS {
+                    return Err(unsupported("AES-KDF rounds"));
+                }
+                let cipher = Aes256::new_from_slice(seed).map_err(|_| corrupt())?;
+                let mut blocks = [aes::Block::default(), aes::Block::default()];
+                blocks[0].copy_from_slice(&composite[..16]);
+                blocks[1].copy_from_slice(&composite[16..]);
+                for _ in 0..*rounds {
+                    cipher.encrypt_blocks(&mut blocks);
+                }
+                let mut transformed = Zeroizing::new([0u8; 32]);
+                transformed[..16].copy_from_slice(&blocks[0]);
+                transformed[16..].copy_from_slice(&blocks[1]);
+                *out = sha256(&[transformed.as_ref()]);
+            }
+            Kdf::Argon2 { algorithm, salt, memory, iterations, parallelism, version } => {
+                let version = if *version == 0x10 { Version::V0x10 } else { Version::V0x13 };
+                let m_cost = u32::try_from(memory / 1024).map_err(|_| unsupported("Argon2 memory size"))?;
+                let t_cost = u32::try_from(*iterations).map_err(|_| unsupported("Argon2 iterations"))?;
+                let limits = KdfParams { m_cost_kib: m_cost, t_cost, p_cost: *parallelism };
+                if !limits.within_untrusted_limits() {
+                    return Err(unsupported("Argon2 cost"));
+                }
+                let params = Params::new(m_cost, t_cost, *parallelism, Some(out.len()))
+                    .map_err(|e| unsupported(&format!("Argon2 parameters: {e}")))?;
+                Argon2::new(*algorithm, version, params)
+                    .hash_password_into(composite, salt, out.as_mut())
+                    .map_err(|e| unsupported(&format!("Argon2 parameters: {e}")))?;
+            }
+        }
+        Ok(out)
+    }
+}
+
+/// Outer header fields needed to decrypt the payload
+struct Header {
+    cipher: Uuid,
+    compressed: bool,
+    master_seed: Vec<u8>,
+    iv: Vec<u8>,
+    kdf: Kdf,
+    /// KDBX 3.1 only: first bytes of the decrypted payload
+    stream_start_bytes: Vec<u8>,
+    /// KDBX 3.1 only; KDBX 4 keeps the inner stream in the inner header
+    inner_stream: u32,
+    protected_stream_key: Zeroizing<Vec<u8>>,
+}
+
+impl Header {
+    fn read(cursor: &mut Cursor, major: u16) -> Result<Self> {
+        let mut cipher = None;
+        let mut compressed = false;
+        let mut master_seed = None;
+        let mut iv = None;
+        let mut kdf = None;
+        let mut transform_seed = None;
+        let mut transform_rounds = None;
+        let mut stream_start_bytes = Vec::new();
+        let mut inner_stream = STREAM_NONE;
+        let mut protected_stream_key = Zeroizing::new(Vec::new());
+        loop {
+            let id = cursor.u8()?;
+            let len = if major >= 4 { cursor.u32()? as usize } else { cursor.u16()? as usize };
+            let data = cursor.take(len)?;
+            match id {
+                0 => break,
+                2 => cipher = Some(Uuid::from_slice(data).map_err(|_| corrupt())?),
+                3 => compressed = le_uint(data)? != 0,
+                4 => master_seed = Some(data.to_vec()),
+                5 => transform_seed = Some(data.to_vec()),
+                6 => transform_rounds = Some(le_uint(data)?),
+                7 => iv = Some(data.to_vec()),
+                8 => protected_stream_key = Zeroizing::new(data.to_vec()),
+                9 => stream_start_bytes = data.to_vec(),
+                10 => inner_stream = le_uint(data)? as u32,
+                11 => kdf = Some(Kdf::from_parameters(data)?),
+                _ => {}
+            }
+        }
+        let kdf = match (kdf, transform_seed, transform_rounds) {
+            (Some(kdf), _, _) => kdf,
+            (None, Some(seed), Some(rounds)) => Kdf::Aes { seed, rounds },
+            _ => return Err(corrupt()),
+        };
+        Ok(Header {
+            cipher: cipher.ok_or_else(corrupt)?,
+            compressed,
+            master_seed: master_seed.ok_or_else(corrupt)?,
+            iv: iv.ok_or_else(corrupt)?,
+            kdf,
+            stream_start_bytes,
+            inner_stream,
+            protected_stream_key,
+        })
+    }
+}
+
+fn sha256(parts: &[&[u8]]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    hasher.finalize().into()
+}
+
+/// Composite key of a password-only database
+fn composite_key(password: &str) -> Zeroizing<[u8; 32]> {
+    let password_hash = Zeroizing::new(sha256(&[password.as_bytes()]));
+    Zeroizing::new(sha256(&[password_hash.as_ref()]))
+}
+
+/// KDBX 4 HMAC base key, from which the header and block keys derive
+fn hmac_base_key(master_seed: &[u8], transformed: &[u8; 32]) -> Zeroizing<[u8; 64]> {
+    let mut hasher = Sha512::new();
+    hasher.update(master_seed);
+    hasher.update(transformed);
+    hasher.update([1u8]);
+    Zeroizing::new(hasher.finalize().into())
+}
+
+/// HMAC-SHA256 over `parts` under the key of block `index`; the header uses
+/// index `u64::MAX`
+fn block_mac(base: &[u8; 64], index: u64, parts: &[&[u8]]) -> HmacSha256 {
+    let mut hasher = Sha512::new();
+    hasher.update(index.to_le_bytes());
+    hasher.update(base);
+    let key = Zeroizing::new(<[u8; 64]>::from(hasher.finalize()));
+    let mut mac = <HmacSha256 as hmac::KeyInit>::new_from_slice(key.as_ref()).expect("HMAC accepts keys of any length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac
+}
+
+/// KDBX 4 payload: HMAC-authenticated blocks, ending with an empty one
+fn read_hmac_blocks(cursor: &mut Cursor, hmac_base: &[u8; 64]) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    for index in 0u64.. {
+        let mac = cursor.take(32)?;
+        let len = cursor.i32()?;
+        let data = cursor.take(usize::try_from(len).map_err(|_| corrupt())?)?;
+        if block_mac(hmac_base, index, &[&index.to_le_bytes(), &len.to_le_bytes(), data]).verify_slice(mac).is_err() {
+            return Err(corrupt());
+        }
+        if data.is_empty() {
+            break;
+        }
+        out.extend_from_slice(data);
+    }
+    Ok(out)
+}
+
+/// KDBX 3.1 payload: SHA-256-checked blocks, ending with an empty one
+fn read_hashed_blocks(cursor: &mut Cursor) -> Result<Zeroizing<Vec<u8>>> {
+    let mut out = Zeroizing::new(Vec::new());
+    loop {
+        let _index = cursor.u32()?;
+        let hash = cursor.take(32)?;
+        let len = cursor.i32()?;
+        let data = cursor.take(usize::try_from(len).map_err(|_| corrupt())?)?;
+        if data.is_empty() {
+            break;
+        }
+        if sha256(&[data]) != hash {
+            return Err(corrupt());
+        }
+        out.extend_from_slice(data);
+    }
+    Ok(out)
+}
+
+/// Decrypt the payload with the outer cipher. A KDBX 3.1 payload that
+/// does not unpad was decrypted with the wrong key.
+fn decrypt_payload(header: &Header, key: &[u8; 32], encrypted: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+    let mut buffer = Zeroizing::new(encrypted.to_vec());
+    match header.cipher {
+        CIPHER_AES256 => {
+            let decryptor = cbc::Decryptor::<Aes256>::new_from_slices(key, &header.iv).map_err(|_| corrupt())?;
+            let len = decryptor.decrypt_padded::<block_padding::Pkcs7>(&mut buffer)
+                .map_err(|_| WalletError::InvalidPassword)?
+                .len();
+            buffer.truncate(len);
+        }
+        CIPHER_CHACHA20 => {
+            let mut cipher = ChaCha20::new_from_slices(key, &header.iv).map_err(|_| corrupt())?;
+            cipher.apply_keystream(&mut buffer);
+        }
+        _ => return Err(unsupported("cipher")),
+    }
+    Ok(buffer)
+}
+
+/// Decompress `data`, failing once the output passes `max_len` bytes
+fn gunzip(data: &[u8], max_len: u64) -> Result<Zeroizing<Vec<u8>>> {
+    let mut out = Zeroizing::new(Vec::new());
+    flate2::read::GzDecoder::new(data).take(max_len + 1).read_to_end(&mut out).map_err(|_| corrupt())?;
+    if out.len() as u64 > max_len {
+        return Err(unsupported("decompressed size"));
+    }
+    Ok(out)
+}
+
+/// KDBX 4 inner header: the inner stream and its key. Attachments it
+/// carries are skipped.
+fn read_inner_header(cursor: &mut Cursor) -> Result<(u32, Zeroizing<Vec<u8>>)> {
+    let mut stream_id = STREAM_NONE;
+    let mut stream_key = Zeroizing::new(Vec::new());
+    loop {
+        let id = cursor.u8()?;
+        let len = cursor.u32()? as usize;
+        let data = cursor.take(len)?;
+        match id {
+            0 => break,
+            1 => stream_id = le_uint(data)? as u32,
+            2 => stream_key = Zeroizing::new(data.to_vec()),
+            _ => {}
+        }
+    }
+    Ok((stream_id, stream_key))
+}
+
+/// Keystream that protected values are XORed with, in document order
+enum InnerStream {
+    None,
+    Salsa20(Box<salsa20::Salsa20>),
+    ChaCha20(Box<ChaCha20>),
+}
+
+impl InnerStream {
+    fn new(id: u32, key: &[u8]) -> Result<Self> {
+        match id {
+            STREAM_NONE => Ok(InnerStream::None),
+            STREAM_SALSA20 => {
+                use salsa20::cipher::KeyIvInit as _;
+                let key = Zeroizing::new(sha256(&[key]));
+                Ok(InnerStream::Salsa20(Box::new(salsa20::Salsa20::new(key.as_ref().into(), &SALSA20_NONCE.into()))))
+            }
+            STREAM_CHACHA20 => {
+                let hash = Zeroizing::new(<[u8; 64]>::from(Sha512::digest(key)));
+                let cipher = ChaCha20::new_from_slices(&hash[..32], &hash[32..44]).map_err(|_| corrupt())?;
+                Ok(InnerStream::ChaCha20(Box::new(cipher)))
+            }
+            _ => Err(unsupported("protected value stream")),
+        }
+    }
+
+    fn apply(&mut self, data: &mut [u8]) {
+        match self {
+            InnerStream::None => {}
+            InnerStream::Salsa20(cipher) => salsa20::cipher::StreamCipher::apply_keystream(cipher.as_mut(), data),
+            InnerStream::ChaCha20(cipher) => cipher.apply_keystream(data),
+        }
+    }
+}
+
+fn is_protected(element: &BytesStart) -> bool {
+    element.try_get_attribute("Protected").ok().flatten()
+        .is_some_and(|attr| attr.value.eq_ignore_ascii_case(b"true"))
+}
+
+/// Walk the database XML, collecting groups and entries
+fn parse_xml(xml: &str, stream: &mut InnerStream) -> Result<KdbxGroup> {
+    let xml_error = |e: quick_xml::Error| WalletError::ValidationError(format!("KeePass database XML: {e}"));
+    let mut reader = Reader::from_str(xml);
+    let mut path: Vec<String> = Vec::new();
+    let mut groups: Vec<(KdbxGroup, String)> = Vec::new();
+    let mut entries: Vec<KdbxEntry> = Vec::new();
+    let mut string_key = String::new();
+    let mut recycle_bin_uuid = String::new();
+    let mut recycle_bin_enabled = true;
+    let mut root = None;
+
+    loop {
+        match reader.read_event().map_err(xml_error)? {
+            Event::Start(element) => {
+                let name = String::from_utf8_lossy(element.local_name().as_ref()).into_owned();
+                let parent = path.last().map(String::as_str);
+                let is_leaf = matches!(
+                    (parent, name.as_str()),
+                    (Some("String"), "Key" | "Value")
+                        | (Some("Group"), "Name" | "UUID")
+                        | (Some("Meta"), "RecycleBinUUID" | "RecycleBinEnabled")
+                );
+                if !is_leaf && !is_protected(&element) {
+                    match name.as_str() {
+                        "Group" if groups.len() >= MAX_GROUP_DEPTH => {
+                            return Err(unsupported(&format!("groups nested more than {MAX_GROUP_DEPTH} deep")));
+                        }
+                        "Group" => groups.push((KdbxGroup::default(), String::new())),
+                        "Entry" => entries.push(KdbxEntry::default()),
+                        _ => {}
+                    }
+                    path.push(name);
+                    continue;
+                }
+
+                let raw = reader.read_text(element.name()).map_err(xml_error)?;
+                let raw = raw.decode().map_err(|e| xml_error(e.into()))?;
+                let mut text = quick_xml::escape::unescape(&raw)
+                    .map_err(|e| xml_error(e.into()))?
+                    .into_owned();
+                if is_protected(&element) {
+                    let mut bytes = Zeroizing::new(BASE64.decode(text.trim()).map_err(|_| corrupt())?);
+                    stream.apply(&mut bytes);
+                    text = String::from_utf8_lossy(&bytes).into_owned();
+                }
+                match (parent, name.as_str()) {
+                    (Some("String"), "Key") => string_key = text,
+                    (Some("String"), "Value") => {
+                        if let Some(entry) = entries.last_mut() {
+                            entry.strings.push((std::mem::take(&mut string_key), text));
+                        }
+                    }
+                    (Some("Group"), "Name") => {
+                        if let Some((group, _)) = groups.last_mut() {
+                            group.name = text;
+                        }
+                    }
+                    (Some("Group"), "UUID") => {
+                        if let Some((_, uuid)) = groups.last_mut() {
+                            *uuid = text;
+                        }
+                    }
+                    (Some("Meta"), "RecycleBinUUID") => recycle_bin_uuid = text,
+                    (Some("Meta"), "RecycleBinEnabled") => recycle_bin_enabled = !text.eq_ignore_ascii_case("false"),
+                    _ => {}
+                }
+            }
+            // <Value/>: an empty string field
+            Event::Empty(element) if element.local_name().as_ref() == b"Value" && path.last().is_some_and(|p| p == "String") => {
+                if let Some(entry) = entries.last_mut() {
+                    entry.strings.push((std::mem::take(&mut string_key), String::new()));
+                }
+            }
+            Event::End(_) => {
+                match path.pop().as_deref() {
+                    Some("Entry") => {
+                        let entry = entries.pop().ok_or_else(corrupt)?;
+                        // Entries nested in another entry are its history
+                        if entries.is_empty() && let Some((group, _)) = groups.last_mut() {
+                            group.entries.push(entry);
+                        }
+                    }
+                    Some("Group") => {
+                        let (mut group, uuid) = groups.pop().ok_or_else(corrupt)?;
+                        group.recycle_bin = recycle_bin_enabled && !uuid.is_empty() && uuid == recycle_bin_uuid;
+                        match groups.last_mut() {
+                            Some((parent, _)) => parent.groups.push(group),
+                            None => root = root.or(Some(group)),
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    root.ok_or_else(corrupt)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::Write;
+    use cbc::cipher::BlockModeEncrypt;
+    use quick_xml::escape::escape;
+
+    /// Options of [`write_kdbx`]
+    #[derive(Clone, Copy)]
+    pub(crate) struct WriteOptions {
+        pub major: u16,
+        pub chacha20: bool,
+        pub argon2: bool,
+    }
+
+    impl Default for WriteOptions {
+        fn default() -> Self {
+            Self { major: 4, chacha20: false, argon2: true }
+        }
+    }
+
+    fn write_group(xml: &mut String, group: &KdbxGroup, uuid: u128, stream: &mut InnerStream) {
+        xml.push_str(&format!(
+            "<Group><UUID>{}</UUID><Name>{}</Name>",
+            BASE64.encode(uuid.to_le_bytes()),
+            escape(group.name.as_str())
+        ));
+        for entry in &group.entries {
+            xml.push_str("<Entry>");
+            for (key, value) in &entry.strings {
+                if key == "Password" {
+                    let mut bytes = value.as_bytes().to_vec();
+                    stream.apply(&mut bytes);
+                    xml.push_str(&format!(
+                        "<String><Key>{key}</Key><Value Protected=\"True\">{}</Value></String>",
+                        BASE64.encode(bytes)
+                    ));
+                } else if value.is_empty() {
+                    xml.push_str(&format!("<String><Key>{}</Key><Value/></String>", escape(key.as_str())));
+                } else {
+                    xml.push_str(&format!(
+                        "<String><Key>{}</Key><Value>{}</Value></String>",
+                        escape(key.as_str()),
+                        escape(value.as_str())
+                    ));
+                }
+            }
+            xml.push_str("</Entry>");
+        }
+        for (i, child) in group.groups.iter().enumerate() {
+            let child_uuid = if child.recycle_bin { 0xB1 } else { uuid * 16 + i as u128 + 1 };
+            write_group(xml, child, child_uuid, stream);
+        }
+        xml.push_str("</Group>");
+    }
+
+    fn header_field(out: &mut Vec<u8>, major: u16, id: u8, data: &[u8]) {
+        out.push(id);
+        if major >= 4 {
+            out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        } else {
+            out.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        }
+        out.extend_from_slice(data);
+    }
+
+    /// Serialized variant dictionary of `(type, name, value)` entries
+    fn variant_dictionary(params: &[(u8, &str, &[u8])]) -> Vec<u8> {
+        let mut out = vec![0x00, 0x01];
+        for (kind, name, value) in params {
+            out.push(*kind);
+            out.extend_from_slice(&(name.len() as i32).to_le_bytes());
+            out.extend_from_slice(name.as_bytes());
+            out.extend_from_slice(&(value.len() as i32).to_le_bytes());
+            out.extend_from_slice(value);
+        }
+        out.push(0);
+        out
+    }
+
+    /// Serialize `root` as a KeePass database protected by `password`.
+    /// Passwords are written as protected values; a child group with
+    /// `recycle_bin` set becomes the recycle bin.
+    pub(crate) fn write_kdbx(root: &KdbxGroup, password: &str, options: WriteOptions) -> Vec<u8> {
+        let WriteOptions { major, chacha20, argon2 } = options;
+        let master_seed = [7u8; 32];
+        let kdf_seed = [9u8; 32];
+        let iv: Vec<u8> = if chacha20 { vec![3u8; 12] } else { vec![3u8; 16] };
+        let stream_key = [5u8; 64];
+        let stream_id = if major >= 4 { STREAM_CHACHA20 } else { STREAM_SALSA20 };
+        let stream_start = [1u8; 32];
+
+        let mut stream = InnerStream::new(stream_id, &stream_key).unwrap();
+        let mut xml = String::from(
+            "<?xml version=\"1.0\" encoding=\"utf-8\" standalone=\"yes\"?>\n<KeePassFile><Meta>\
+             <Generator>iwcore</Generator><RecycleBinEnabled>True</RecycleBinEnabled>",
+        );
+        xml.push_str(&format!("<RecycleBinUUID>{}</RecycleBinUUID></Meta><Root>", BASE64.encode(0xB1u128.to_le_bytes())));
+        write_group(&mut xml, root, 1, &mut stream);
+        xml.push_str("<DeletedObjects/></Root></KeePassFile>");
+
+        let mut out = Vec::new();
+        out.extend_from_slice(&SIGNATURE_1.to_le_bytes());
+        out.extend_from_slice(&SIGNATURE_2.to_le_bytes());
+        out.extend_from_slice(&1u16.to_le_bytes());
+        out.extend_from_slice(&major.to_le_bytes());
+        let cipher = if chacha20 { CIPHER_CHACHA20 } else { CIPHER_AES256 };
+        header_field(&mut out, major, 2, cipher.as_bytes());
+        header_field(&mut out, major, 3, &1u32.to_le_bytes());
+        header_field(&mut out, major, 4, &master_seed);
+        let kdf = if major >= 4 && argon2 {
+            header_field(&mut out, major, 11, &variant_dictionary(&[
+                (0x42, "$UUID", KDF_ARGON2D.as_bytes()),
+                (0x42, "S", &kdf_seed),
+                (0x04, "P", &1u32.to_le_bytes()),
+                (0x05, "M", &(64u64 * 1024).to_le_bytes()),
+                (0x05, "I", &2u64.to_le_bytes()),
+                (0x04, "V", &0x13u32.to_le_bytes()),
+            ]));
+            Kdf::Argon2 { algorithm: Algorithm::Argon2d, salt: kdf_seed.to_vec(), memory: 64 * 1024, iterations: 2, parallelism: 1, version: 0x13 }
+        } else if major >= 4 {
+            header_field(&mut out, major, 11, &variant_dictionary(&[
+                (0x42, "$UUID", KDF_AES.as_bytes()),
+                (0x42, "S", &kdf_seed),
+                (0x05, "R", &100u64.to_le_bytes()),
+            ]));
+            Kdf::Aes { seed: kdf_seed.to_vec(), rounds: 100 }
+        } else {
+            header_field(&mut out, major, 5, &kdf_seed);
+            header_field(&mut out, major, 6, &100u64.to_le_bytes());
+            Kdf::Aes { seed: kdf_seed.to_vec(), rounds: 100 }
+        };
+        header_field(&mut out, major, 7, &iv);
+        if major < 4 {
+            header_field(&mut out, major, 8, &stream_key);
+            header_field(&mut out, major, 9, &stream_start);
+            header_field(&mut out, major, 10, &stream_id.to_le_bytes());
+        }
+        header_field(&mut out, major, 0, b"\r\n\r\n");
+
+        let transformed = kdf.transform(&composite_key(password)).unwrap();
+        let key = sha256(&[&master_seed, transformed.as_ref()]);
+
+        // KDBX 4 compresses the inner header along with the XML
+        let mut content = Vec::new();
+        if major >= 4 {
+            for (id, data) in [(1u8, stream_id.to_le_bytes().to_vec()), (2, stream_key.to_vec()), (0, Vec::new())] {
+                content.push(id);
+                content.extend_from_slice(&(data.len() as u32).to_le_bytes());
+                content.extend_from_slice(&data);
+            }
+        }
+        content.extend_from_slice(xml.as_bytes());
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(&content).unwrap();
+        let compressed = gz.finish().unwrap();
+        let plain = if major >= 4 {
+            compressed
+        } else {
+            let mut blocks = stream_start.to_vec();
+            for (index, data) in [(0u32, compressed.as_slice()), (1, &[][..])] {
+                blocks.extend_from_slice(&index.to_le_bytes());
+                blocks.extend_from_slice(&if data.is_empty() { [0u8; 32] } else { sha256(&[data]) });
+                blocks.extend_from_slice(&(data.len() as i32).to_le_bytes());
+                blocks.extend_from_slice(data);
+            }
+            blocks
+        };
+
+        let encrypted = if chacha20 {
+            let mut buffer = plain;
+            ChaCha20::new_from_slices(&key, &iv).unwrap().apply_keystream(&mut buffer);
+            buffer
+        } else {
+            let mut buffer = plain.clone();
+            buffer.resize(plain.len() + 16, 0);
+            cbc::Encryptor::<Aes256>::new_from_slices(&key, &iv).unwrap()
+                .encrypt_padded::<block_padding::Pkcs7>(&mut buffer, plain.len())
+                .unwrap()
+                .to_vec()
+        };
+
+        if major >= 4 {
+            let hmac_base = hmac_base_key(&master_seed, &transformed);
+            let header = out.clone();
+            out.extend_from_slice(&sha256(&[&header]));
+            out.extend_from_slice(&block_mac(&hmac_base, u64::MAX, &[&header]).finalize().into_bytes());
+            for (index, data) in [(0u64, encrypted.as_slice()), (1, &[][..])] {
+                let len = data.len() as i32;
+                let mac = block_mac(&hmac_base, index, &[&index.to_le_bytes(), &len.to_le_bytes(), data]);
+                out.extend_from_slice(&mac.finalize().into_bytes());
+                out.extend_from_slice(&len.to_le_bytes());
+                out.extend_from_slice(data);
+            }
+        } else {
+            out.extend_from_slice(&encrypted);
+        }
+        out
+    }
+
+    pub(crate) fn entry(strings: &[(&str, &str)]) -> KdbxEntry {
+        KdbxEntry { strings: strings.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect() }
+    }
+
+    fn sample() -> KdbxGroup {
+        KdbxGroup {
+            name: "Passwords".to_string(),
+            entries: vec![entry(&[("Title", "Mail & <Co>"), ("UserName", "joe"), ("Password", "s3cr3t!"), ("Notes", "")])],
+            groups: vec![KdbxGroup {
+                name: "Banking".to_string(),
+                entries: vec![entry(&[("Title", "Bank"), ("Password", "pä$$")])],
+                ..Default::default()
+            }],
+            recycle_bin: false,
+        }
+    }
+
+    #[test]
+    fn test_read_all_formats() {
+        for options in [
+            WriteOptions::default(),
+            WriteOptions { major: 4, chacha20: true, argon2: false },
+            WriteOptions { major: 3, chacha20: false, argon2: false },
+        ] {
+            let file = write_kdbx(&sample(), "kp-pass", options);
+            assert_eq!(read_kdbx(&file, "kp-pass").unwrap(), sample(), "KDBX {}", options.major);
+            assert!(matches!(read_kdbx(&file, "wrong"), Err(WalletError::InvalidPassword)));
+        }
+    }
+
+    #[test]
+    fn test_rejects_damaged_and_foreign_files() {
+        let mut file = write_kdbx(&sample(), "kp-pass", WriteOptions::default());
+        let last = file.len() - 40;
+        file[last] ^= 1;
+        assert!(matches!(read_kdbx(&file, "kp-pass"), Err(WalletError::ValidationError(_))));
+        assert!(matches!(read_kdbx(b"PK\x03\x04 not keepass", "x"), Err(WalletError::ValidationError(_))));
+        assert!(matches!(read_kdbx(&file[..20], "kp-pass"), Err(WalletError::ValidationError(_))));
+    }
+
+    #[test]
+    fn test_rejects_excessive_costs() {
+        let composite = [7u8; 32];
+        let aes = Kdf::Aes { seed: vec![0; 32], rounds: MAX_AES_KDF_ROUNDS + 1 };
+        assert!(matches!(aes.transform(&composite), Err(WalletError::ValidationError(e)) if e.contains("AES-KDF rounds")));
+
+        let argon2 = |memory: u64, iterations: u64, parallelism: u32| Kdf::Argon2 {
+            algorithm: Algorithm::Argon2d, salt: vec![0; 32], memory, iterations, parallelism, version: 0x13,
+        };
+        for kdf in [argon2(64 << 30, 2, 1), argon2(64 << 20, 1_000_000, 1), argon2(64 << 20, 2, 1024)] {
+            assert!(matches!(kdf.transform(&composite), Err(WalletError::ValidationError(e)) if e.contains("Argon2 cost")));
+        }
+
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(&[0u8; 4096]).unwrap();
+        let compressed = gz.finish().unwrap();
+        assert_eq!(gunzip(&compressed, 4096).unwrap().len(), 4096);
+        assert!(matches!(gunzip(&compressed, 4095), Err(WalletError::ValidationError(e)) if e.contains("decompressed size")));
+    }
+
+    #[test]
+    fn test_rejects_deeply_nested_groups() {
+        let nested = |depth: usize| {
+            format!("<KeePassFile><Root>{}{}</Root></KeePassFile>", "<Group><Name>g</Name>".repeat(depth), "</Group>".repeat(depth))
+        };
+        let mut stream = InnerStream::new(STREAM_NONE, &[]).unwrap();
+        let mut root = parse_xml(&nested(MAX_GROUP_DEPTH), &mut stream).unwrap();
+        let mut depth = 1;
+        while let Some(child) = root.groups.pop() {
+            root = child;
+            depth += 1;
+        }
+        assert_eq!(depth, MAX_GROUP_DEPTH);
+        assert!(matches!(
+            parse_xml(&nested(100_000), &mut stream),
+            Err(WalletError::ValidationError(e)) if e.contains("nested")
+        ));
+    }
+}
@@ -0,0 +1,76 @@
+//! Import from other password managers
+//!
+//! This module reads the files other password managers export or store,
+//! KeePass databases and CSV exports, into plain structures. The wallet
+//! methods that turn them into folders, items and fields live in
+//! `business::import` and report what they did in an [`ImportReport`].
+
+pub mod csv;
+pub mod kdbx;
+
+pub use csv::{read_csv, CsvMapping, CsvRecord, CsvSource};
+pub use kdbx::{read_kdbx, KdbxEntry, KdbxGroup};
+
+use serde::{Deserialize, Serialize};
+
+use crate::business::BatchResult;
+use crate::error::{Result, WalletError};
+
+/// What a CSV import does with a row matching an item the folder already
+/// has (same name, all of the row's values present)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ImportConflict {
+    /// Count the row in [`ImportReport::duplicates`] and leave it out
+    #[default]
+    Skip,
+    /// Import the row as another item
+    KeepBoth,
+}
+
+/// Saved settings of a recurring CSV import, stored in the wallet by name
+/// and replayed with `Wallet::rerun_import`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportProfile {
+    pub name: String,
+    pub mapping: CsvMapping,
+    #[serde(default)]
+    pub conflict: ImportConflict,
+}
+
+impl ImportProfile {
+    /// Profile called `name` with `mapping`, skipping duplicates
+    pub fn new(name: &str, mapping: CsvMapping) -> Self {
+        Self { name: name.to_string(), mapping, conflict: ImportConflict::default() }
+    }
+}
+
+/// What an import created and what it left out
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub folders_created: usize,
+    pub items_created: usize,
+    pub fields_created: usize,
+    /// Records already in the wallet, which were not imported again
+    pub duplicates: usize,
+    /// Where each record the import took up is in the source: its group
+    /// path and name, or its CSV line and name. The indexes of `records`
+    /// point into this list.
+    pub sources: Vec<String>,
+    /// Outcome of each record of `sources`: the ID of the folder or item
+    /// created for it, or why it was left out. A record whose item was
+    /// created but lost a field fails with the field's error.
+    pub records: BatchResult<String>,
+}
+
+impl ImportReport {
+    /// Record the outcome of the source record at `source`
+    pub(crate) fn record(&mut self, source: String, outcome: Result<String>) {
+        self.records.record(self.sources.len(), outcome);
+        self.sources.push(source);
+    }
+
+    /// The records left out, by where they are in the source
+    pub fn skipped(&self) -> impl Iterator<Item = (&str, &WalletError)> {
+        self.records.failed.iter().map(|f| (self.sources[f.index].as_str(), &f.error))
+    }
+}
@@ -40,19 +40,33 @@ pub mod localization;
 pub mod utils;
 pub mod error;
 pub mod export;
+pub mod config;
+pub mod capabilities;
+pub mod import;
+#[cfg(any(test, feature = "testsupport"))]
+pub mod testsupport;
 
 // Re-export main types
 pub use error::{WalletError, Result};
-pub use database::models::{IWItem, IWField, IWLabel, IWProperties, SearchResult, SearchMatchType, FieldValueUsage};
-pub use business::{MigrationSummary, RecoveryResult, Wallet};
-pub use backup::{BackupManager, BackupType};
+pub use database::models::{IWItem, IWField, IWProfile, IWLabel, IWProperties, SearchResult, SearchOptions, SearchMatchType, FolderGroup, ItemFilter, CompactOptions, CompactResult, CopyOptions, JsonExportOptions, FieldValueUsage, SortOrder, UrlMatch, UrlMatchRank};
+pub use business::{ActivityEntry, ActivityKind, Attachment, BatchFailure, BatchResult, DueNotification, FieldUpdate, IconSuggestion, IdCollisionStats, IdKind, IntegrityManifest, IntegrityReport, ItemQuery, LabelCreateResult, LabelPack, LabelPackReport, NewItem, NewLabel, PasswordReuse, PasswordReusePolicy, RecentChange, RecentChangeKind, StarterKind};
+pub use business::{SecureNoteReader, SecureNoteWriter, SECURE_NOTE_CHUNK_SIZE, SECURE_NOTE_ICON};
+pub use business::{EmergencyGrant, EmergencyUnlock, FieldReplacement, RawRecord, ReplaceScope, SyncMark, UnlockAttempt, WalletDiff, WalletSnapshot};
+pub use business::{QuotaConfig, QuotaEvent, QuotaLevel, QuotaMetric, QuotaStatus, QuotaUsage};
+pub use business::{SessionAccess, SessionInfo, SessionPermissions, SessionToken, SimilarFolders};
+pub use business::{SealedField, SealedItem, SealedLabel, SealedSubtree, SEALED_SUBTREE_VERSION, WALLET_JSON_FORMAT, WALLET_JSON_VERSION};
+pub use business::{MigrationSummary, OpenMetrics, OpenRecovery, RecordKind, RecoveryResult, UndecryptableRecord, Wallet};
+pub use backup::{AutoBackupConfig, BackupInspection, BackupManager, BackupNaming, BackupType, INSPECT_MAX_SIZE};
 pub use localization::Translations;
 pub use crypto::{
-    generate_password, generate_clever_password, generate_memorable_password,
-    PasswordOptions, MemorableOptions, MemorableCaps,
+    generate_password, generate_clever_password, generate_memorable_password, validate_pattern,
+    pattern_entropy_bits, normalize_master_password, check_master_password_length, PasswordOptions, PasswordStrength, PatternInfo, PatternToken, MemorableOptions, MemorableCaps,
 };
-pub use export::{ExportItemType, PDFItemModel};
+pub use export::{CsvColumn, CsvExportOptions, EmergencySheetFormat, EmergencySheetOptions, ExportFormat, ExportOptions, ExportItemType, FieldExportFormat, FieldMasking, PDFItemModel};
+pub use import::{CsvMapping, CsvSource, ImportConflict, ImportProfile, ImportReport};
+pub use capabilities::{capabilities, Capabilities, EncryptionFormat};
 pub use database::queries::DatabaseStats;
+pub use utils::{CancelToken, IdGenerator, RandomIdGenerator, ValueType};
 
 /// Database version constant.
 ///
@@ -83,6 +97,15 @@ pub const LABEL_ID_LENGTH: usize = 4;
 /// Database filename
 pub const DATABASE_FILENAME: &str = "nswallet.dat";
 
+/// Maximum item name length, in characters
+pub const ITEM_NAME_MAX_LENGTH: usize = 256;
+
+/// Default maximum item note size, in bytes of UTF-8 text
+pub const NOTE_MAX_SIZE_DEFAULT: usize = 64 * 1024;
+
+/// Maximum size of one attachment, in bytes
+pub const ATTACHMENT_MAX_SIZE: usize = 16 * 1024 * 1024;
+
 /// Minimum password length
 pub const PASSWORD_MIN_LENGTH: usize = 3;
 
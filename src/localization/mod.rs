@@ -1,8 +1,14 @@
 //! Localization and translation support
 //!
 //! Provides multi-language support with 11 languages embedded at compile time.
+//!
+//! Each language file carries a bundle version under the `_version` key.
+//! The host app can install a newer bundle it downloaded with
+//! [`Translations::install_bundle`]; its strings are merged over the
+//! embedded ones, so translation fixes ship without a crate release.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::RwLock;
 use crate::error::{Result, WalletError};
 
 /// Supported languages with their codes and names
@@ -51,6 +57,35 @@ fn get_language_json(lang: &str) -> Option<&'static str> {
     }
 }
 
+/// Key of the bundle version in a language file
+const VERSION_KEY: &str = "_version";
+
+/// A language bundle installed at runtime
+struct Bundle {
+    version: String,
+    strings: HashMap<String, String>,
+}
+
+/// Bundles installed with `Translations::install_bundle`, by language
+static BUNDLES: RwLock<BTreeMap<String, Bundle>> = RwLock::new(BTreeMap::new());
+
+/// Parse a language file into its version and strings
+fn parse_bundle(lang: &str, json: &str) -> Result<(Option<String>, HashMap<String, String>)> {
+    // Strip UTF-8 BOM if present
+    let json = json.strip_prefix('\u{feff}').unwrap_or(json);
+
+    let mut strings: HashMap<String, String> = serde_json::from_str(json)
+        .map_err(|e| WalletError::json(
+            format!("Failed to parse language '{}'", lang), e
+        ))?;
+    Ok((strings.remove(VERSION_KEY), strings))
+}
+
+/// Split a bundle version such as "3" or "2.1" into its numbers
+fn version_parts(version: &str) -> Option<Vec<u64>> {
+    version.split('.').map(|part| part.parse().ok()).collect()
+}
+
 /// Check if a language code is supported
 pub fn is_language_supported(lang: &str) -> bool {
     SUPPORTED_LANGUAGES.iter().any(|(code, _, _)| *code == lang)
@@ -77,20 +112,68 @@ impl Translations {
         })
     }
 
-    /// Load a language from embedded JSON
+    /// Load a language from embedded JSON, with an installed bundle
+    /// merged over it
     fn load_language(lang: &str) -> Result<HashMap<String, String>> {
         let json = get_language_json(lang)
             .ok_or_else(|| WalletError::LocalizationError(
                 format!("Language '{}' not found", lang)
             ))?;
+        let (_, mut strings) = parse_bundle(lang, json)?;
 
-        // Strip UTF-8 BOM if present
-        let json = json.strip_prefix('\u{feff}').unwrap_or(json);
+        let bundles = BUNDLES.read().unwrap_or_else(|e| e.into_inner());
+        if let Some(bundle) = bundles.get(lang) {
+            strings.extend(bundle.strings.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+        Ok(strings)
+    }
 
-        serde_json::from_str(json)
-            .map_err(|e| WalletError::LocalizationError(
-                format!("Failed to parse language '{}': {}", lang, e)
-            ))
+    /// Version of the bundle a language loads from: the installed one, or
+    /// the embedded one. `None` for an unsupported language.
+    pub fn bundle_version(lang: &str) -> Option<String> {
+        let bundles = BUNDLES.read().unwrap_or_else(|e| e.into_inner());
+        if let Some(bundle) = bundles.get(lang) {
+            return Some(bundle.version.clone());
+        }
+        parse_bundle(lang, get_language_json(lang)?).ok()?.0
+    }
+
+    /// Install a language bundle downloaded by the host app. `json` is a
+    /// language file with a `_version` of dotted numbers; its strings are
+    /// merged over the embedded ones, and keys it lacks keep their embedded
+    /// text. Returns false, installing nothing, when the bundle is not
+    /// newer than the current one. Applies to translations loaded
+    /// afterwards, including `set_language` on an existing instance.
+    pub fn install_bundle(lang: &str, json: &str) -> Result<bool> {
+        if !is_language_supported(lang) {
+            return Err(WalletError::LocalizationError(
+                format!("Language '{}' is not supported", lang)
+            ));
+        }
+        let (version, strings) = parse_bundle(lang, json)?;
+        let version = version.ok_or_else(|| WalletError::LocalizationError(
+            format!("Bundle for '{}' has no {}", lang, VERSION_KEY)
+        ))?;
+        let parts = version_parts(&version).ok_or_else(|| WalletError::LocalizationError(
+            format!("Invalid bundle version '{}'", version)
+        ))?;
+
+        let mut bundles = BUNDLES.write().unwrap_or_else(|e| e.into_inner());
+        let current = match bundles.get(lang) {
+            Some(bundle) => Some(bundle.version.clone()),
+            None => parse_bundle(lang, get_language_json(lang).unwrap_or_default())?.0,
+        };
+        if current.as_deref().and_then(version_parts).is_some_and(|current| parts <= current) {
+            return Ok(false);
+        }
+        bundles.insert(lang.to_string(), Bundle { version, strings });
+        Ok(true)
+    }
+
+    /// Remove the bundle installed for a language, going back to the
+    /// embedded one. Returns false if none was installed.
+    pub fn remove_bundle(lang: &str) -> bool {
+        BUNDLES.write().unwrap_or_else(|e| e.into_inner()).remove(lang).is_some()
     }
 
     /// Set the current language
@@ -122,6 +205,16 @@ impl Translations {
             .map(|s| s.as_str())
     }
 
+    /// Get a translated string with `{placeholder}` parameters substituted,
+    /// e.g. `format("item_copy_name", &[("name", "Bank")])`.
+    pub fn format(&self, key: &str, args: &[(&str, &str)]) -> String {
+        let mut out = self.get(key).to_string();
+        for (name, value) in args {
+            out = out.replace(&format!("{{{name}}}"), value);
+        }
+        out
+    }
+
     /// Get English translation (always from English dictionary)
     pub fn get_en<'a>(&'a self, key: &'a str) -> &'a str {
         self.english.get(key)
@@ -333,4 +426,52 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_format_parameters() {
+        let mut tr = Translations::new().unwrap();
+        assert_eq!(tr.format("item_copy_name", &[("name", "Bank")]), "Copy of Bank");
+        tr.set_language("de").unwrap();
+        assert_eq!(tr.format("item_copy_name", &[("name", "Bank")]), "Kopie von Bank");
+        // Unknown placeholders are left alone
+        assert_eq!(tr.format("item_copy_name", &[("other", "x")]), "Kopie von {name}");
+    }
+
+    #[test]
+    fn test_embedded_bundle_versions() {
+        for (code, _, _) in SUPPORTED_LANGUAGES {
+            let version = Translations::bundle_version(code)
+                .unwrap_or_else(|| panic!("Language {} has no bundle version", code));
+            assert!(version_parts(&version).is_some());
+        }
+        assert_eq!(Translations::bundle_version("xx"), None);
+        assert!(Translations::new().unwrap().get_opt(VERSION_KEY).is_none());
+    }
+
+    #[test]
+    fn test_install_bundle_overlay() {
+        // Catalan only: bundles are process-wide and tests run in parallel
+        let embedded = Translations::bundle_version("ca").unwrap();
+        let mut tr = Translations::new().unwrap();
+        tr.set_language("ca").unwrap();
+        let cancel = tr.get("cancel").to_string();
+
+        let stale = format!("{{\"_version\": \"{embedded}\", \"ok\": \"D'acord\"}}");
+        assert!(!Translations::install_bundle("ca", &stale).unwrap());
+        assert!(Translations::install_bundle("ca", "{\"ok\": \"x\"}").is_err());
+        assert!(Translations::install_bundle("ca", "{\"_version\": \"next\"}").is_err());
+        assert!(Translations::install_bundle("xx", "{\"_version\": \"99\"}").is_err());
+
+        let newer = "\u{feff}{\"_version\": \"99.1\", \"ok\": \"D'acord\"}";
+        assert!(Translations::install_bundle("ca", newer).unwrap());
+        assert_eq!(Translations::bundle_version("ca").as_deref(), Some("99.1"));
+        assert!(!Translations::install_bundle("ca", "{\"_version\": \"99\"}").unwrap());
+        tr.set_language("ca").unwrap();
+        assert_eq!(tr.get("ok"), "D'acord");
+        assert_eq!(tr.get("cancel"), cancel);
+
+        assert!(Translations::remove_bundle("ca"));
+        assert!(!Translations::remove_bundle("ca"));
+        assert_eq!(Translations::bundle_version("ca"), Some(embedded));
+    }
 }
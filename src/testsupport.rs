@@ -0,0 +1,352 @@
+//! Wallet fixtures for integration tests (feature `testsupport`)
+//!
+//! [`WalletFixture`] builds a wallet on disk from a description instead of a
+//! committed binary like `testdata/nswallet.dat`. IDs, names, values and
+//! timestamps are deterministic, so two builds of the same fixture list the
+//! same records; only the encrypted blobs (fresh nonces) and the root item's
+//! random name differ.
+//!
+//! ```no_run
+//! use iwcore::testsupport::WalletFixture;
+//!
+//! let (_dir, mut wallet) = WalletFixture::new("password")
+//!     .item("Banking/Bank", &[("PASS", "secret"), ("MAIL", "me@example.com")])
+//!     .deleted_item("Old login", &[("PASS", "old")])
+//!     .generated(3, 10, 2)
+//!     .build_temp()
+//!     .unwrap();
+//! wallet.unlock("password").unwrap();
+//! ```
+
+use std::path::Path;
+
+use tempfile::TempDir;
+
+use crate::business::Wallet;
+use crate::crypto;
+use crate::database::queries;
+use crate::error::{WalletError, Result};
+use crate::utils::IdGenerator;
+
+/// Creation and change time of every fixture record
+pub const FIXTURE_TIMESTAMP: &str = "2024-01-01 12:00:00";
+
+/// Database ID of every fixture wallet
+pub const FIXTURE_DATABASE_ID: &str = "f1x7u4e0000000000000000000000000";
+
+/// Field types cycled through by [`WalletFixture::generated`]
+const GENERATED_FIELD_TYPES: &[&str] = &["PASS", "MAIL", "NOTE", "LINK", "USER"];
+
+/// Counter-based IDs: `I0000001`, `F001`, `L001`, ...
+#[derive(Debug, Default)]
+struct SequentialIdGenerator {
+    items: u32,
+    fields: u32,
+    labels: u32,
+}
+
+impl IdGenerator for SequentialIdGenerator {
+    fn item_id(&mut self) -> String {
+        self.items += 1;
+        format!("I{:07}", self.items)
+    }
+
+    fn field_id(&mut self) -> String {
+        self.fields += 1;
+        format!("F{:03}", self.fields)
+    }
+
+    fn label_id(&mut self) -> String {
+        self.labels += 1;
+        format!("L{:03}", self.labels)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Entry {
+    Folder(String),
+    Item { path: String, fields: Vec<(String, String)>, deleted: bool },
+    DeletedField { path: String, field_type: String, value: String },
+}
+
+/// Description of a test wallet, built with [`WalletFixture::build`]
+#[derive(Debug, Clone)]
+pub struct WalletFixture {
+    password: String,
+    lang: String,
+    entries: Vec<Entry>,
+    legacy_count: Option<u32>,
+}
+
+impl WalletFixture {
+    /// An empty wallet with `password`, in English
+    pub fn new(password: &str) -> Self {
+        Self {
+            password: password.to_string(),
+            lang: "en".to_string(),
+            entries: Vec::new(),
+            legacy_count: None,
+        }
+    }
+
+    /// Wallet language
+    pub fn lang(mut self, lang: &str) -> Self {
+        self.lang = lang.to_string();
+        self
+    }
+
+    /// A folder at a `/`-separated path; missing parents are created
+    pub fn folder(mut self, path: &str) -> Self {
+        self.entries.push(Entry::Folder(path.to_string()));
+        self
+    }
+
+    /// An item at a `/`-separated path (the last part is its name) with
+    /// `(field type, value)` fields; missing parent folders are created
+    pub fn item(mut self, path: &str, fields: &[(&str, &str)]) -> Self {
+        self.entries.push(Entry::Item { path: path.to_string(), fields: owned(fields), deleted: false });
+        self
+    }
+
+    /// Like [`WalletFixture::item`], then deleted (with its fields)
+    pub fn deleted_item(mut self, path: &str, fields: &[(&str, &str)]) -> Self {
+        self.entries.push(Entry::Item { path: path.to_string(), fields: owned(fields), deleted: true });
+        self
+    }
+
+    /// A field added to the item at `path` and then deleted; the item must
+    /// be described before
+    pub fn deleted_field(mut self, path: &str, field_type: &str, value: &str) -> Self {
+        self.entries.push(Entry::DeletedField {
+            path: path.to_string(),
+            field_type: field_type.to_string(),
+            value: value.to_string(),
+        });
+        self
+    }
+
+    /// Bulk content: `folders` folders named `Folder N`, each holding
+    /// `items_per_folder` items `Item N.M` with `fields_per_item` fields
+    pub fn generated(mut self, folders: usize, items_per_folder: usize, fields_per_item: usize) -> Self {
+        for f in 1..=folders {
+            self.entries.push(Entry::Folder(format!("Folder {f}")));
+            for i in 1..=items_per_folder {
+                let fields = (0..fields_per_item)
+                    .map(|n| {
+                        let field_type = GENERATED_FIELD_TYPES[n % GENERATED_FIELD_TYPES.len()];
+                        (field_type.to_string(), format!("value {f}.{i}.{n}"))
+                    })
+                    .collect();
+                self.entries.push(Entry::Item { path: format!("Folder {f}/Item {f}.{i}"), fields, deleted: false });
+            }
+        }
+        self
+    }
+
+    /// Write the wallet in the legacy (v5) format: AES-CBC blobs under
+    /// `encryption_count`, no crypto record. Unlocking it runs the v5->v6
+    /// migration.
+    pub fn legacy(mut self, encryption_count: u32) -> Self {
+        self.legacy_count = Some(encryption_count);
+        self
+    }
+
+    /// Build the wallet in `folder`, which must not hold one yet
+    pub fn build(&self, folder: &Path) -> Result<()> {
+        if folder.join(crate::DATABASE_FILENAME).exists() {
+            return Err(WalletError::InvalidOperation(format!(
+                "A wallet already exists in {}", folder.display()
+            )));
+        }
+        let mut wallet = Wallet::create(folder, &self.password, &self.lang)?;
+        wallet.set_id_generator(Box::new(SequentialIdGenerator::default()));
+
+        for entry in &self.entries {
+            match entry {
+                Entry::Folder(path) => {
+                    ensure_folders(&mut wallet, &parts(path))?;
+                }
+                Entry::Item { path, fields, deleted } => {
+                    let parts = parts(path);
+                    let Some((name, parents)) = parts.split_last() else { continue };
+                    let parent = ensure_folders(&mut wallet, parents)?;
+                    let item_id = wallet.add_item(name, "document", false, parent.as_deref())?;
+                    for (field_type, value) in fields {
+                        wallet.add_field(&item_id, field_type, value, None)?;
+                    }
+                    if *deleted {
+                        wallet.delete_item(&item_id)?;
+                    }
+                }
+                Entry::DeletedField { path, field_type, value } => {
+                    let item_id = find_path(&mut wallet, &parts(path), false)?
+                        .ok_or_else(|| WalletError::ItemNotFound(path.clone()))?;
+                    let field_id = wallet.add_field(&item_id, field_type, value, None)?;
+                    wallet.delete_field(&item_id, &field_id)?;
+                }
+            }
+        }
+
+        let conn = wallet.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        conn.execute(
+            "UPDATE nswallet_items SET create_timestamp = ?1, change_timestamp = ?1",
+            [FIXTURE_TIMESTAMP],
+        )?;
+        conn.execute("UPDATE nswallet_fields SET change_timestamp = ?1", [FIXTURE_TIMESTAMP])?;
+        conn.execute("UPDATE nswallet_properties SET database_id = ?1", [FIXTURE_DATABASE_ID])?;
+
+        if let Some(count) = self.legacy_count {
+            self.convert_to_legacy(&wallet, count)?;
+        }
+        wallet.close();
+        Ok(())
+    }
+
+    /// Build the wallet in a new temporary folder and open it (locked). The
+    /// folder is deleted when the returned `TempDir` is dropped.
+    pub fn build_temp(&self) -> Result<(TempDir, Wallet)> {
+        let dir = TempDir::new()?;
+        self.build(dir.path())?;
+        let wallet = Wallet::open(dir.path())?;
+        Ok((dir, wallet))
+    }
+
+    /// Re-encrypt every blob with the legacy scheme and drop the v6 key
+    /// material, as a vault written by the old apps
+    fn convert_to_legacy(&self, wallet: &Wallet, count: u32) -> Result<()> {
+        let conn = wallet.db.as_ref()
+            .ok_or_else(|| WalletError::DatabaseError("Database not open".to_string()))?
+            .connection()?;
+        let legacy = |blob: Option<&[u8]>| -> Result<Vec<u8>> {
+            let plaintext = match blob {
+                Some(blob) if !blob.is_empty() => wallet.dec_value(blob)?,
+                _ => String::new(),
+            };
+            crypto::legacy::encrypt(&plaintext, &self.password, count, None)
+                .map_err(WalletError::EncryptionError)
+        };
+        for (item_id, blob, _) in queries::get_all_item_blobs(conn)? {
+            conn.execute(
+                "UPDATE nswallet_items SET name = ? WHERE item_id = ?",
+                rusqlite::params![legacy(blob.as_deref())?, item_id],
+            )?;
+        }
+        for (item_id, field_id, blob, _) in queries::get_all_field_blobs(conn)? {
+            conn.execute(
+                "UPDATE nswallet_fields SET value = ? WHERE item_id = ? AND field_id = ?",
+                rusqlite::params![legacy(blob.as_deref())?, item_id, field_id],
+            )?;
+        }
+        conn.execute("DROP TABLE nswallet_crypto", [])?;
+        queries::set_key_check(conn, None)?;
+        conn.execute(
+            "UPDATE nswallet_properties SET version = '5', email = ?",
+            [count.to_string()],
+        )?;
+        Ok(())
+    }
+}
+
+fn owned(fields: &[(&str, &str)]) -> Vec<(String, String)> {
+    fields.iter().map(|(t, v)| (t.to_string(), v.to_string())).collect()
+}
+
+fn parts(path: &str) -> Vec<String> {
+    path.split('/').map(str::trim).filter(|p| !p.is_empty()).map(str::to_string).collect()
+}
+
+/// The active record at `parts` below the root, if any
+fn find_path(wallet: &mut Wallet, parts: &[String], folder: bool) -> Result<Option<String>> {
+    let mut parent = crate::ROOT_ID.to_string();
+    for (i, name) in parts.iter().enumerate() {
+        let want_folder = folder || i + 1 < parts.len();
+        let Some(item) = wallet.get_items_by_parent(&parent)?.into_iter()
+            .find(|it| &it.name == name && it.folder == want_folder) else {
+            return Ok(None);
+        };
+        parent = item.item_id;
+    }
+    Ok(Some(parent))
+}
+
+/// Create the folder chain `parts` where missing; returns the last folder
+/// (`None` for the root)
+fn ensure_folders(wallet: &mut Wallet, parts: &[String]) -> Result<Option<String>> {
+    let mut parent: Option<String> = None;
+    for (i, name) in parts.iter().enumerate() {
+        parent = Some(match find_path(wallet, &parts[..=i], true)? {
+            Some(id) => id,
+            None => wallet.add_item(name, "folder", true, parent.as_deref())?,
+        });
+    }
+    Ok(parent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> WalletFixture {
+        WalletFixture::new("FixturePass1")
+            .item("Banking/Bank", &[("PASS", "secret"), ("MAIL", "me@example.com")])
+            .folder("Banking/Cards")
+            .deleted_item("Old login", &[("PASS", "old")])
+            .deleted_field("Banking/Bank", "NOTE", "gone")
+            .generated(2, 2, 3)
+    }
+
+    #[test]
+    fn test_fixture_is_deterministic() {
+        let list = |wallet: &mut Wallet| {
+            let mut items: Vec<(String, String, String)> = wallet.get_items().unwrap().iter()
+                .filter(|i| i.item_id != crate::ROOT_ID)
+                .map(|i| (i.item_id.clone(), i.name.clone(), i.change_timestamp.to_string()))
+                .collect();
+            items.sort();
+            let mut fields: Vec<(String, String)> = wallet.get_fields().unwrap().iter()
+                .map(|f| (f.field_id.clone(), f.value.clone()))
+                .collect();
+            fields.sort();
+            (items, fields)
+        };
+
+        let (_d1, mut first) = fixture().build_temp().unwrap();
+        assert!(first.unlock("FixturePass1").unwrap());
+        let (_d2, mut second) = fixture().build_temp().unwrap();
+        assert!(second.unlock("FixturePass1").unwrap());
+        assert_eq!(list(&mut first), list(&mut second));
+
+        // Banking, Bank, Cards, 2 generated folders with 2 items each
+        let (items, fields) = list(&mut first);
+        assert_eq!(items.len(), 9);
+        assert_eq!(fields.len(), 2 + 4 * 3);
+        assert_eq!(first.get_deleted_items().unwrap().len(), 1);
+        assert!(first.get_deleted_fields().unwrap().iter().any(|f| f.value == "gone"));
+        assert_eq!(first.get_properties().unwrap().database_id, FIXTURE_DATABASE_ID);
+    }
+
+    #[test]
+    fn test_legacy_fixture_migrates_on_unlock() {
+        let (_dir, mut wallet) = WalletFixture::new("FixturePass1")
+            .item("Bank", &[("PASS", "secret")])
+            .legacy(200)
+            .build_temp()
+            .unwrap();
+        assert_eq!(wallet.get_properties().unwrap().version, "5");
+        assert!(!wallet.unlock("WrongPass").unwrap());
+        assert!(wallet.unlock("FixturePass1").unwrap());
+        assert!(wallet.last_migration_summary().is_some());
+        assert!(wallet.get_fields().unwrap().iter().any(|f| f.value == "secret"));
+    }
+
+    #[test]
+    fn test_build_refuses_existing_wallet() {
+        let (dir, _wallet) = WalletFixture::new("FixturePass1").build_temp().unwrap();
+        assert!(matches!(
+            WalletFixture::new("FixturePass1").build(dir.path()),
+            Err(WalletError::InvalidOperation(_))
+        ));
+    }
+}
@@ -0,0 +1,72 @@
+//! Cancellation of long operations
+//!
+//! A [`CancelToken`] is passed to a long operation (search, export, a
+//! password change) and checked between its batches. The host cancels it
+//! from another thread, or gives it a time budget up front; the operation
+//! then stops with `WalletError::Cancelled` before its next write, so the
+//! database is left either untouched or fully updated.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::error::{Result, WalletError};
+
+/// Shared cancellation flag with an optional deadline. Clones share the
+/// flag, so one clone can cancel the operation holding another.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+    deadline: Option<Instant>,
+}
+
+impl CancelToken {
+    /// A token that only trips when cancelled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A token that also trips once `budget` has passed from now
+    pub fn with_timeout(budget: Duration) -> Self {
+        Self {
+            cancelled: Arc::default(),
+            deadline: Instant::now().checked_add(budget),
+        }
+    }
+
+    /// Ask the operation holding this token (or a clone) to stop
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the token was cancelled or its time budget is used up
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+            || self.deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// `Err(Cancelled)` if the operation should stop
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(WalletError::Cancelled);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancel_and_timeout() {
+        let token = CancelToken::new();
+        let clone = token.clone();
+        assert!(token.check().is_ok());
+        clone.cancel();
+        assert!(matches!(token.check(), Err(WalletError::Cancelled)));
+
+        assert!(!CancelToken::with_timeout(Duration::from_secs(3600)).is_cancelled());
+        assert!(CancelToken::with_timeout(Duration::ZERO).is_cancelled());
+    }
+}
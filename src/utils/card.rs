@@ -0,0 +1,163 @@
+//! Payment card helpers
+//!
+//! Brand detection from the IIN prefix, Luhn checksum validation and masked
+//! display of card numbers (for CARD fields in UIs and exports).
+
+use super::common::{card_groups, group_chars};
+
+/// Mask character used for hidden card digits
+pub const CARD_MASK_CHAR: char = '•';
+
+/// Card network, detected from the leading digits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CardBrand {
+    Visa,
+    Mastercard,
+    Amex,
+    Discover,
+    DinersClub,
+    Jcb,
+    UnionPay,
+    Maestro,
+    Mir,
+    Unknown,
+}
+
+impl CardBrand {
+    /// Human-readable brand name
+    pub fn name(&self) -> &'static str {
+        match self {
+            CardBrand::Visa => "Visa",
+            CardBrand::Mastercard => "Mastercard",
+            CardBrand::Amex => "American Express",
+            CardBrand::Discover => "Discover",
+            CardBrand::DinersClub => "Diners Club",
+            CardBrand::Jcb => "JCB",
+            CardBrand::UnionPay => "UnionPay",
+            CardBrand::Maestro => "Maestro",
+            CardBrand::Mir => "Mir",
+            CardBrand::Unknown => "Unknown",
+        }
+    }
+}
+
+fn card_digits(number: &str) -> String {
+    number.chars().filter(|c| c.is_ascii_digit()).collect()
+}
+
+/// Leading `n` digits as a number (0 when there are fewer digits)
+fn prefix(digits: &str, n: usize) -> u32 {
+    digits.get(..n).and_then(|p| p.parse().ok()).unwrap_or(0)
+}
+
+/// Detect the card brand from its number. Spaces and dashes are ignored.
+pub fn detect_card_brand(number: &str) -> CardBrand {
+    let d = card_digits(number);
+    if d.len() < 2 {
+        return CardBrand::Unknown;
+    }
+    let p2 = prefix(&d, 2);
+    let p3 = prefix(&d, 3);
+    let p4 = prefix(&d, 4);
+    let p6 = prefix(&d, 6);
+
+    if p2 == 34 || p2 == 37 {
+        CardBrand::Amex
+    } else if (300..=305).contains(&p3) || p2 == 36 || p2 == 38 || p2 == 39 || p4 == 3095 {
+        CardBrand::DinersClub
+    } else if (3528..=3589).contains(&p4) {
+        CardBrand::Jcb
+    } else if d.starts_with('4') {
+        CardBrand::Visa
+    } else if (51..=55).contains(&p2) || (222100..=272099).contains(&p6) {
+        CardBrand::Mastercard
+    } else if (2200..=2204).contains(&p4) {
+        CardBrand::Mir
+    } else if p4 == 6011 || p2 == 65 || (644..=649).contains(&p3) || (622126..=622925).contains(&p6) {
+        CardBrand::Discover
+    } else if p2 == 62 {
+        CardBrand::UnionPay
+    } else if p2 == 50 || (56..=69).contains(&p2) {
+        CardBrand::Maestro
+    } else {
+        CardBrand::Unknown
+    }
+}
+
+/// Validate a card number with the Luhn checksum. Spaces and dashes are
+/// ignored; any other non-digit, or fewer than 12 digits, fails.
+pub fn luhn_check(number: &str) -> bool {
+    if number.chars().any(|c| !(c.is_ascii_digit() || c == ' ' || c == '-')) {
+        return false;
+    }
+    let d = card_digits(number);
+    if d.len() < 12 || d.len() > 19 {
+        return false;
+    }
+    let sum: u32 = d.bytes().rev().enumerate().map(|(i, b)| {
+        let n = (b - b'0') as u32;
+        if i % 2 == 1 {
+            let doubled = n * 2;
+            if doubled > 9 { doubled - 9 } else { doubled }
+        } else {
+            n
+        }
+    }).sum();
+    sum.is_multiple_of(10)
+}
+
+/// Mask all but the last four digits, keeping the brand's grouping:
+/// `4111111111111111` -> `•••• •••• •••• 1111`. Inputs with fewer than
+/// five digits are fully masked.
+pub fn mask_card_number(number: &str) -> String {
+    let d = card_digits(number);
+    let len = d.len();
+    let visible = if len > 4 { 4 } else { 0 };
+    let masked: String = std::iter::repeat_n(CARD_MASK_CHAR, len - visible)
+        .chain(d[len - visible..].chars())
+        .collect();
+    match card_groups(len) {
+        Some(groups) => group_chars(&masked, groups),
+        None => masked,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_card_brand() {
+        assert_eq!(detect_card_brand("4111 1111 1111 1111"), CardBrand::Visa);
+        assert_eq!(detect_card_brand("5500000000000004"), CardBrand::Mastercard);
+        assert_eq!(detect_card_brand("2221000000000009"), CardBrand::Mastercard);
+        assert_eq!(detect_card_brand("378282246310005"), CardBrand::Amex);
+        assert_eq!(detect_card_brand("30569309025904"), CardBrand::DinersClub);
+        assert_eq!(detect_card_brand("6011111111111117"), CardBrand::Discover);
+        assert_eq!(detect_card_brand("3530111333300000"), CardBrand::Jcb);
+        assert_eq!(detect_card_brand("6200000000000005"), CardBrand::UnionPay);
+        assert_eq!(detect_card_brand("2200123412341234"), CardBrand::Mir);
+        assert_eq!(detect_card_brand("1234"), CardBrand::Unknown);
+        assert_eq!(detect_card_brand(""), CardBrand::Unknown);
+        assert_eq!(CardBrand::Amex.name(), "American Express");
+    }
+
+    #[test]
+    fn test_luhn_check() {
+        assert!(luhn_check("4111 1111 1111 1111"));
+        assert!(luhn_check("378282246310005"));
+        assert!(luhn_check("3056-9309-0259-04"));
+        assert!(!luhn_check("4111111111111112"));
+        assert!(!luhn_check("4111x11111111111"));
+        assert!(!luhn_check("0"));
+    }
+
+    #[test]
+    fn test_mask_card_number() {
+        assert_eq!(mask_card_number("4111111111111111"), "•••• •••• •••• 1111");
+        assert_eq!(mask_card_number("3782 822463 10005"), "•••• •••••• •0005");
+        assert_eq!(mask_card_number("30569309025904"), "•••• •••••• 5904");
+        assert_eq!(mask_card_number("123456789"), "•••••6789");
+        assert_eq!(mask_card_number("1234"), "••••");
+    }
+}
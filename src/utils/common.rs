@@ -31,21 +31,40 @@ pub fn mask_string(s: &str) -> String {
 }
 
 /// Format a credit card number with spaces
+///
+/// 16 digits group as 4-4-4-4, 15 digits (Amex) as 4-6-5 and 14 digits
+/// (Diners Club) as 4-6-4. Any other input is returned unchanged.
 pub fn format_card_number(card: &str) -> String {
     let digits: String = card.chars().filter(|c| c.is_ascii_digit()).collect();
-    if digits.len() == 16 {
-        format!(
-            "{} {} {} {}",
-            &digits[0..4],
-            &digits[4..8],
-            &digits[8..12],
-            &digits[12..16]
-        )
-    } else {
-        card.to_string()
+    match card_groups(digits.len()) {
+        Some(groups) => group_chars(&digits, groups),
+        None => card.to_string(),
+    }
+}
+
+/// Digit grouping used when displaying a card number of `len` digits
+pub(crate) fn card_groups(len: usize) -> Option<&'static [usize]> {
+    match len {
+        16 => Some(&[4, 4, 4, 4]),
+        15 => Some(&[4, 6, 5]),
+        14 => Some(&[4, 6, 4]),
+        _ => None,
     }
 }
 
+/// Split `s` into space-separated groups of the given sizes (by chars)
+pub(crate) fn group_chars(s: &str, groups: &[usize]) -> String {
+    let chars: Vec<char> = s.chars().collect();
+    let mut parts = Vec::with_capacity(groups.len());
+    let mut pos = 0;
+    for &size in groups {
+        let end = (pos + size).min(chars.len());
+        parts.push(chars[pos..end].iter().collect::<String>());
+        pos = end;
+    }
+    parts.join(" ")
+}
+
 /// Format time string (HHmm -> HH:mm)
 pub fn format_time(time: &str) -> String {
     if time.len() == 4 {
@@ -105,6 +124,8 @@ mod tests {
     fn test_format_card_number() {
         assert_eq!(format_card_number("1234567890123456"), "1234 5678 9012 3456");
         assert_eq!(format_card_number("123"), "123");
+        assert_eq!(format_card_number("378282246310005"), "3782 822463 10005");
+        assert_eq!(format_card_number("3056 9309 0259 04"), "3056 930902 5904");
     }
 
     #[test]
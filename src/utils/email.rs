@@ -0,0 +1,88 @@
+//! Email address helpers
+//!
+//! Syntax checks for MAIL fields (used by strict value-type validation) and
+//! domain extraction, for grouping accounts by provider.
+
+use crate::error::{WalletError, Result};
+use super::url::is_valid_host;
+
+/// Longest local part allowed by RFC 5321
+const LOCAL_PART_MAX_LENGTH: usize = 64;
+
+/// Characters allowed in an unquoted local part besides letters and digits
+const LOCAL_PART_SPECIALS: &str = "!#$%&'*+/=?^_`{|}~.-";
+
+fn invalid(reason: &str) -> WalletError {
+    WalletError::ValidationError(format!("invalid email address: {reason}"))
+}
+
+/// Check the syntax of an email address: one `@`, a dot-atom local part
+/// of at most 64 characters and a dotted domain of valid host labels.
+/// Quoted local parts and IP literals are not accepted. Surrounding
+/// whitespace is ignored.
+pub fn validate_email(value: &str) -> Result<()> {
+    let v = value.trim();
+    if v.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err(invalid("contains whitespace"));
+    }
+    let Some((local, domain)) = v.split_once('@') else {
+        return Err(invalid("missing @"));
+    };
+    if domain.contains('@') {
+        return Err(invalid("more than one @"));
+    }
+
+    if local.is_empty() {
+        return Err(invalid("empty local part"));
+    }
+    if local.chars().count() > LOCAL_PART_MAX_LENGTH {
+        return Err(invalid("local part too long"));
+    }
+    if local.starts_with('.') || local.ends_with('.') || local.contains("..") {
+        return Err(invalid("misplaced dot in local part"));
+    }
+    if !local.chars().all(|c| c.is_alphanumeric() || LOCAL_PART_SPECIALS.contains(c)) {
+        return Err(invalid("invalid character in local part"));
+    }
+
+    let domain = domain.to_lowercase();
+    let labels: Vec<&str> = domain.split('.').collect();
+    if labels.len() < 2 || !is_valid_host(&domain) {
+        return Err(invalid("invalid domain"));
+    }
+    if labels.last().is_some_and(|tld| tld.chars().all(|c| c.is_ascii_digit())) {
+        return Err(invalid("invalid domain"));
+    }
+    Ok(())
+}
+
+/// The lowercase domain of a valid email address (`user@Mail.Example.com`
+/// -> `mail.example.com`), or `None` when the value is not a valid address
+pub fn email_domain(value: &str) -> Option<String> {
+    validate_email(value).ok()?;
+    value.trim().split_once('@').map(|(_, domain)| domain.to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_email() {
+        for ok in ["user@example.com", " first.last+tag@mail.example.co.uk ", "o'brien@example.ie", "юзер@пример.рф"] {
+            assert!(validate_email(ok).is_ok(), "{ok}");
+        }
+        for bad in ["", "user", "@example.com", "user@", "user@example", "a b@example.com", "a@b@example.com",
+                    ".user@example.com", "us..er@example.com", "user@exa mple.com", "user@-example.com",
+                    "user@example..com", "user@10.0.0.1", "us(er)@example.com"] {
+            assert!(matches!(validate_email(bad), Err(WalletError::ValidationError(_))), "{bad}");
+        }
+        assert!(validate_email(&format!("{}@example.com", "a".repeat(65))).is_err());
+    }
+
+    #[test]
+    fn test_email_domain() {
+        assert_eq!(email_domain("User@Mail.Example.com").as_deref(), Some("mail.example.com"));
+        assert_eq!(email_domain("not an email"), None);
+    }
+}
@@ -0,0 +1,107 @@
+//! Portable file names from item names
+//!
+//! Item names end up as names of exported files, which may later be copied
+//! to any file system. The rules here are the union of what Windows (NTFS,
+//! FAT), macOS and Linux reject, so a name that passes on one platform keeps
+//! working after the file moves to another.
+
+use unicode_normalization::UnicodeNormalization;
+
+/// Longest sanitized name in bytes; leaves room for an extension within the
+/// common 255-byte limit
+pub const FILENAME_MAX_BYTES: usize = 200;
+
+/// Used when nothing of the name survives
+const FALLBACK_NAME: &str = "untitled";
+
+/// Characters Windows rejects in file names (`/` is also the Unix separator)
+const RESERVED_CHARS: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+/// Device names Windows reserves regardless of extension
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL",
+    "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Emoji and other symbols outside the Basic Multilingual Plane, plus the
+/// joiners and variation selectors that build emoji sequences. FAT and some
+/// Android storage reject them.
+fn is_emoji_part(c: char) -> bool {
+    c as u32 > 0xFFFF || c == '\u{200D}' || ('\u{FE00}'..='\u{FE0F}').contains(&c)
+}
+
+/// Make `name` usable as a file name on every platform: NFC-normalized,
+/// separators, reserved and control characters replaced by `_`, emoji
+/// dropped, no leading or trailing dots and spaces, Windows device names
+/// prefixed with `_`, and at most [`FILENAME_MAX_BYTES`] bytes. Returns
+/// `untitled` when nothing is left.
+pub fn sanitize_filename(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    for c in name.nfc() {
+        if is_emoji_part(c) {
+            continue;
+        }
+        let c = if RESERVED_CHARS.contains(&c) || c.is_control() { '_' } else { c };
+        // Runs of replacements collapse into one
+        if c == '_' && out.ends_with('_') {
+            continue;
+        }
+        out.push(c);
+    }
+
+    let trim = |c: char| c == '.' || c.is_whitespace();
+    let mut out = out.trim_matches(trim).to_string();
+    if out.len() > FILENAME_MAX_BYTES {
+        let mut end = FILENAME_MAX_BYTES;
+        while !out.is_char_boundary(end) {
+            end -= 1;
+        }
+        out.truncate(end);
+        out = out.trim_end_matches(trim).to_string();
+    }
+    if out.is_empty() {
+        return FALLBACK_NAME.to_string();
+    }
+
+    let stem = out.split('.').next().unwrap_or("").trim_end();
+    if RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(stem)) {
+        out.insert(0, '_');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_filename() {
+        assert_eq!(sanitize_filename("Bank account"), "Bank account");
+        assert_eq!(sanitize_filename("Банк / карта"), "Банк _ карта");
+        assert_eq!(sanitize_filename("a:b*c?d\"e<f>g|h\\i"), "a_b_c_d_e_f_g_h_i");
+        assert_eq!(sanitize_filename("tab\there\n"), "tab_here_");
+        assert_eq!(sanitize_filename("Home 🏠 wifi"), "Home  wifi");
+        assert_eq!(sanitize_filename("👨\u{200D}👩\u{200D}👧"), "untitled");
+        assert_eq!(sanitize_filename("  ..hidden.  "), "hidden");
+        assert_eq!(sanitize_filename("???"), "_");
+        assert_eq!(sanitize_filename(""), "untitled");
+        // Decomposed input is composed, so macOS and Linux agree
+        assert_eq!(sanitize_filename("Cafe\u{301}"), "Caf\u{e9}");
+    }
+
+    #[test]
+    fn test_sanitize_reserved_names() {
+        assert_eq!(sanitize_filename("CON"), "_CON");
+        assert_eq!(sanitize_filename("com1.txt"), "_com1.txt");
+        assert_eq!(sanitize_filename("Console"), "Console");
+    }
+
+    #[test]
+    fn test_sanitize_long_name() {
+        let name = "ж".repeat(150);
+        let out = sanitize_filename(&name);
+        assert!(out.len() <= FILENAME_MAX_BYTES);
+        assert_eq!(out, "ж".repeat(FILENAME_MAX_BYTES / 2));
+    }
+}
@@ -3,7 +3,7 @@
 use rand::RngExt;
 
 /// Characters used for ID generation
-const ID_CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+pub(crate) const ID_CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
 
 /// Generate a unique string ID of specified length
 pub fn generate_id(length: usize) -> String {
@@ -16,6 +16,12 @@ pub fn generate_id(length: usize) -> String {
         .collect()
 }
 
+/// Whether `id` is made of the characters IDs are generated from. Length
+/// is not checked: custom generators pick their own.
+pub(crate) fn is_valid_id(id: &str) -> bool {
+    !id.is_empty() && id.bytes().all(|b| ID_CHARS.contains(&b))
+}
+
 /// Generate an item ID (8 characters)
 pub fn generate_item_id() -> String {
     generate_id(crate::ITEM_ID_LENGTH)
@@ -31,6 +37,35 @@ pub fn generate_label_id() -> String {
     generate_id(crate::LABEL_ID_LENGTH)
 }
 
+/// Source of new item, field and label IDs. The wallet uses
+/// [`RandomIdGenerator`] unless a custom one is plugged in with
+/// `Wallet::set_id_generator` (e.g. ULIDs for sync). IDs must be non-empty
+/// and ASCII alphanumeric, or sync rejects them; the wallet retries when a
+/// generated ID is already taken.
+pub trait IdGenerator: Send {
+    fn item_id(&mut self) -> String;
+    fn field_id(&mut self) -> String;
+    fn label_id(&mut self) -> String;
+}
+
+/// Default generator: random alphanumeric IDs of the standard lengths
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RandomIdGenerator;
+
+impl IdGenerator for RandomIdGenerator {
+    fn item_id(&mut self) -> String {
+        generate_item_id()
+    }
+
+    fn field_id(&mut self) -> String {
+        generate_field_id()
+    }
+
+    fn label_id(&mut self) -> String {
+        generate_label_id()
+    }
+}
+
 /// Generate a database ID (32 characters, UUID-like)
 pub fn generate_database_id() -> String {
     uuid::Uuid::new_v4().to_string().replace("-", "")
@@ -0,0 +1,137 @@
+//! Markdown helpers
+//!
+//! Notes may contain light Markdown. List views only need a plain-text
+//! preview, so this strips the common syntax without a full parser.
+
+/// Convert Markdown to plain text for previews.
+///
+/// Removes heading markers, block quotes, list bullets, code fences,
+/// emphasis and inline code markers; links and images keep only their text.
+/// Line structure is kept, blank runs are collapsed to one empty line.
+pub fn markdown_to_plaintext(markdown: &str) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for raw in markdown.lines() {
+        let line = raw.trim();
+        if line.starts_with("```") || line.starts_with("~~~") {
+            continue;
+        }
+        if !line.is_empty() && line.chars().all(|c| matches!(c, '-' | '*' | '_' | '=' | ' ')) && line.len() >= 3 {
+            // Horizontal rule or setext underline
+            continue;
+        }
+        let line = strip_block_prefix(line);
+        let text = strip_inline(line);
+        if text.is_empty() && lines.last().is_none_or(|l| l.is_empty()) {
+            continue;
+        }
+        lines.push(text);
+    }
+    while lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+/// Strip heading, quote and list markers from the start of a line
+fn strip_block_prefix(mut line: &str) -> &str {
+    loop {
+        let before = line;
+        line = line.trim_start_matches('>').trim_start();
+        if line.starts_with('#') {
+            let rest = line.trim_start_matches('#');
+            if rest.is_empty() || rest.starts_with(' ') {
+                line = rest.trim_start();
+            }
+        }
+        for bullet in ["- [ ] ", "- [x] ", "- ", "* ", "+ "] {
+            if let Some(rest) = line.strip_prefix(bullet) {
+                line = rest;
+                break;
+            }
+        }
+        let digits = line.chars().take_while(char::is_ascii_digit).count();
+        if digits > 0
+            && let Some(rest) = line[digits..].strip_prefix(". ").or_else(|| line[digits..].strip_prefix(") ")) {
+                line = rest;
+            }
+        if line == before {
+            return line;
+        }
+    }
+}
+
+/// Strip emphasis, inline code, links and images within a line
+fn strip_inline(line: &str) -> String {
+    let chars: Vec<char> = line.chars().collect();
+    let mut out = String::with_capacity(line.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\\' if i + 1 < chars.len() => {
+                out.push(chars[i + 1]);
+                i += 2;
+            }
+            '!' if chars.get(i + 1) == Some(&'[') => {
+                i += 1;
+            }
+            '[' => {
+                // [text](url) -> text; anything else stays literal
+                if let Some(close) = find(&chars, i + 1, ']')
+                    && chars.get(close + 1) == Some(&'(')
+                    && let Some(end) = find(&chars, close + 2, ')') {
+                        out.push_str(&strip_inline(&chars[i + 1..close].iter().collect::<String>()));
+                        i = end + 1;
+                        continue;
+                    }
+                out.push(c);
+                i += 1;
+            }
+            '*' | '`' | '~' => {
+                i += 1;
+            }
+            '_' => {
+                // Keep underscores inside words (snake_case, e-mail users)
+                let prev = i.checked_sub(1).map(|p| chars[p]);
+                let next = chars.get(i + 1).copied();
+                if prev.is_some_and(char::is_alphanumeric) && next.is_some_and(char::is_alphanumeric) {
+                    out.push(c);
+                }
+                i += 1;
+            }
+            _ => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    out.trim().to_string()
+}
+
+fn find(chars: &[char], from: usize, target: char) -> Option<usize> {
+    (from..chars.len()).find(|&j| chars[j] == target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markdown_to_plaintext_blocks() {
+        let md = "# Title\n\nSome **bold** and _italic_ text.\n\n\n- one\n* two\n1. three\n> quoted\n---\n```\nlet x = 1;\n```\n";
+        assert_eq!(
+            markdown_to_plaintext(md),
+            "Title\n\nSome bold and italic text.\n\none\ntwo\nthree\nquoted\nlet x = 1;"
+        );
+    }
+
+    #[test]
+    fn test_markdown_to_plaintext_inline() {
+        assert_eq!(markdown_to_plaintext("See [the site](https://example.com) now"), "See the site now");
+        assert_eq!(markdown_to_plaintext("![logo](a.png) Acme"), "logo Acme");
+        assert_eq!(markdown_to_plaintext("Run `cargo test` ~~never~~"), "Run cargo test never");
+        assert_eq!(markdown_to_plaintext("user_name stays, \\*literal\\*"), "user_name stays, *literal*");
+        assert_eq!(markdown_to_plaintext("[not a link] #tag"), "[not a link] #tag");
+        assert_eq!(markdown_to_plaintext(""), "");
+    }
+}
@@ -1,7 +1,31 @@
 //! Utility functions
 
+pub mod cancel;
+pub mod card;
 pub mod common;
+pub mod email;
+pub mod filename;
 pub mod id_gen;
+pub mod markdown;
+#[cfg(feature = "phone")]
+pub mod phone;
+pub mod seed;
+pub mod size;
+pub mod time;
+pub mod validation;
+pub mod url;
 
+pub use cancel::CancelToken;
+pub use card::{CardBrand, detect_card_brand, luhn_check, mask_card_number};
 pub use common::*;
+pub use email::{email_domain, validate_email};
+pub use filename::sanitize_filename;
 pub use id_gen::*;
+pub use markdown::markdown_to_plaintext;
+#[cfg(feature = "phone")]
+pub use phone::{format_phone_display, normalize_phone, phone_dial_uri, whatsapp_url};
+pub use seed::{mask_seed_phrase, split_seed_words};
+pub use size::format_size;
+pub use time::{to_local, format_local};
+pub use url::{favicon_cache_key, normalize_url};
+pub use validation::ValueType;
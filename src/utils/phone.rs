@@ -0,0 +1,58 @@
+//! Phone number helpers (`phone` feature)
+//!
+//! Normalization to E.164 and display formatting for PHON fields, backed by
+//! the libphonenumber metadata of the `phonenumber` crate. Numbers written
+//! without a country code are read in `default_region`, an ISO 3166-1
+//! alpha-2 code such as `"US"` or `"DE"` (any case); pass `""` when there
+//! is none and only international numbers should be accepted.
+
+use phonenumber::{Mode, PhoneNumber, country};
+
+/// Parse and validate a number; `None` when it is not a valid phone number
+fn parse_phone(value: &str, default_region: &str) -> Option<PhoneNumber> {
+    let region = default_region.trim().to_ascii_uppercase().parse::<country::Id>().ok();
+    let number = phonenumber::parse(region, value.trim()).ok()?;
+    phonenumber::is_valid(&number).then_some(number)
+}
+
+/// The number in E.164 form (`+4930123456`), for storage, comparison and
+/// dialing. `None` when the value is not a valid phone number.
+pub fn normalize_phone(value: &str, default_region: &str) -> Option<String> {
+    parse_phone(value, default_region).map(|n| n.format().mode(Mode::E164).to_string())
+}
+
+/// The number in international display form (`+49 30 123456`). `None`
+/// when the value is not a valid phone number.
+pub fn format_phone_display(value: &str, default_region: &str) -> Option<String> {
+    parse_phone(value, default_region).map(|n| n.format().mode(Mode::International).to_string())
+}
+
+/// A `tel:` URI (RFC 3966) that starts a call
+pub fn phone_dial_uri(value: &str, default_region: &str) -> Option<String> {
+    parse_phone(value, default_region).map(|n| n.format().mode(Mode::Rfc3966).to_string())
+}
+
+/// A `https://wa.me/` link that opens a WhatsApp chat with the number
+pub fn whatsapp_url(value: &str, default_region: &str) -> Option<String> {
+    normalize_phone(value, default_region)
+        .map(|e164| format!("https://wa.me/{}", e164.trim_start_matches('+')))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_and_format_phone() {
+        assert_eq!(normalize_phone("(202) 555-0143", "us").as_deref(), Some("+12025550143"));
+        assert_eq!(normalize_phone("+1 202-555-0143", "").as_deref(), Some("+12025550143"));
+        assert_eq!(normalize_phone("030 123456", "DE").as_deref(), Some("+4930123456"));
+        assert_eq!(format_phone_display("+4930123456", "").as_deref(), Some("+49 30 123456"));
+        assert_eq!(phone_dial_uri("+12025550143", "").as_deref(), Some("tel:+1-202-555-0143"));
+        assert_eq!(whatsapp_url("+4930123456", "").as_deref(), Some("https://wa.me/4930123456"));
+
+        assert_eq!(normalize_phone("2025550143", ""), None);
+        assert_eq!(normalize_phone("not a phone", "US"), None);
+        assert_eq!(normalize_phone("+1 202", "US"), None);
+    }
+}
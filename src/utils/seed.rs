@@ -0,0 +1,69 @@
+//! Seed phrase helpers
+//!
+//! Display helpers for SEED fields (wallet recovery phrases), so UIs can
+//! show a phrase safely without formatting the raw text themselves.
+
+use super::card::CARD_MASK_CHAR;
+
+/// Split a seed phrase into its words, numbered from 1.
+///
+/// Words may be separated by whitespace, commas or semicolons. Numbering
+/// left over from a pasted list ("1." or "2)") is dropped.
+pub fn split_seed_words(phrase: &str) -> Vec<(usize, String)> {
+    phrase
+        .split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+        .filter(|w| !w.is_empty() && !is_list_number(w))
+        .enumerate()
+        .map(|(i, w)| (i + 1, w.to_string()))
+        .collect()
+}
+
+/// Mask a seed phrase, keeping the word count and each word's first
+/// letter: `legal winner thank year` -> `4 words: l••• w••• t••• y•••`.
+/// Every word gets the same mask length, so word lengths are not revealed.
+pub fn mask_seed_phrase(phrase: &str) -> String {
+    let words = split_seed_words(phrase);
+    let masked: Vec<String> = words
+        .iter()
+        .filter_map(|(_, w)| w.chars().next())
+        .map(|c| std::iter::once(c).chain(std::iter::repeat_n(CARD_MASK_CHAR, 3)).collect())
+        .collect();
+    let noun = if words.len() == 1 { "word" } else { "words" };
+    if masked.is_empty() {
+        format!("0 {noun}")
+    } else {
+        format!("{} {noun}: {}", words.len(), masked.join(" "))
+    }
+}
+
+/// "1." / "12)" / "3:" as left by numbered lists
+fn is_list_number(token: &str) -> bool {
+    let digits = token.trim_end_matches(['.', ')', ':']);
+    digits.len() < token.len() && !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_seed_words() {
+        let words = split_seed_words("  legal winner,thank\nyear ");
+        assert_eq!(words, vec![
+            (1, "legal".to_string()),
+            (2, "winner".to_string()),
+            (3, "thank".to_string()),
+            (4, "year".to_string()),
+        ]);
+        let pasted = split_seed_words("1. abandon 2. ability 3) able");
+        assert_eq!(pasted.iter().map(|(_, w)| w.as_str()).collect::<Vec<_>>(), vec!["abandon", "ability", "able"]);
+        assert!(split_seed_words("   ").is_empty());
+    }
+
+    #[test]
+    fn test_mask_seed_phrase() {
+        assert_eq!(mask_seed_phrase("legal winner thank year"), "4 words: l••• w••• t••• y•••");
+        assert_eq!(mask_seed_phrase("zoo"), "1 word: z•••");
+        assert_eq!(mask_seed_phrase(""), "0 words");
+    }
+}
@@ -0,0 +1,54 @@
+//! Human-readable file sizes
+//!
+//! Sizes are shown in binary units (1 KB = 1024 bytes) with one decimal,
+//! using the decimal separator and unit names of the display language.
+
+/// Unit names (bytes, KB, MB, GB) for a language code
+fn units(lang: &str) -> [&'static str; 4] {
+    match lang {
+        "ru" | "uk" | "be" | "bg" => ["Б", "КБ", "МБ", "ГБ"],
+        _ => ["B", "KB", "MB", "GB"],
+    }
+}
+
+/// Decimal separator for a language code. Unknown languages use a dot.
+fn decimal_separator(lang: &str) -> char {
+    match lang {
+        "de" | "ru" | "uk" | "be" | "bg" | "pl" | "pt" | "es" | "ca" => ',',
+        _ => '.',
+    }
+}
+
+/// Format a byte count for display, e.g. `2.4 MB` in English or `2,4 МБ`
+/// in Russian. Counts below 1 KB are shown as whole bytes.
+pub fn format_size(bytes: u64, lang: &str) -> String {
+    let units = units(lang);
+    if bytes < 1024 {
+        return format!("{bytes} {}", units[0]);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 1;
+    while value >= 1024.0 && unit < units.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    let number = format!("{value:.1}").replace('.', &decimal_separator(lang).to_string());
+    format!("{number} {}", units[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(0, "en"), "0 B");
+        assert_eq!(format_size(1023, "en"), "1023 B");
+        assert_eq!(format_size(1536, "en"), "1.5 KB");
+        assert_eq!(format_size(2_516_582, "en"), "2.4 MB");
+        assert_eq!(format_size(2_516_582, "de"), "2,4 MB");
+        assert_eq!(format_size(2_516_582, "ru"), "2,4 МБ");
+        assert_eq!(format_size(5 * 1024 * 1024 * 1024 * 1024, "en"), "5120.0 GB");
+        assert_eq!(format_size(1536, "xx"), "1.5 KB");
+    }
+}
@@ -0,0 +1,78 @@
+//! Local time display helpers
+//!
+//! Timestamps are stored and handled as UTC. These helpers convert them to
+//! the device's local offset (as reported by the OS) and format them with a
+//! per-language date pattern for display.
+
+use chrono::{DateTime, FixedOffset, Local, TimeZone, Utc};
+
+/// Display pattern for a language code. Unknown languages use ISO order.
+pub fn local_datetime_pattern(lang: &str) -> &'static str {
+    match lang {
+        "en" => "%m/%d/%Y %H:%M",
+        "de" | "ru" | "uk" | "be" | "bg" | "pl" => "%d.%m.%Y %H:%M",
+        "pt" | "es" | "ca" | "hi" => "%d/%m/%Y %H:%M",
+        _ => "%Y-%m-%d %H:%M",
+    }
+}
+
+/// Convert a UTC timestamp to the device's local time zone
+pub fn to_local(dt: &DateTime<Utc>) -> DateTime<Local> {
+    dt.with_timezone(&Local)
+}
+
+/// Format a UTC timestamp in the device's local time with the date pattern
+/// of `lang`
+pub fn format_local(dt: &DateTime<Utc>, lang: &str) -> String {
+    format_in(dt, &Local, lang)
+}
+
+/// Format a UTC timestamp at an explicit UTC offset (e.g. the host app's
+/// chosen time zone) with the date pattern of `lang`
+pub fn format_with_offset(dt: &DateTime<Utc>, offset: FixedOffset, lang: &str) -> String {
+    format_in(dt, &offset, lang)
+}
+
+/// Format a UTC timestamp as UTC with the date pattern of `lang`, suffixed
+/// with "UTC"
+pub fn format_utc(dt: &DateTime<Utc>, lang: &str) -> String {
+    format!("{} UTC", format_in(dt, &Utc, lang))
+}
+
+fn format_in<Tz: TimeZone>(dt: &DateTime<Utc>, tz: &Tz, lang: &str) -> String
+where
+    Tz::Offset: std::fmt::Display,
+{
+    dt.with_timezone(tz).format(local_datetime_pattern(lang)).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 3, 9, 22, 15, 0).unwrap()
+    }
+
+    #[test]
+    fn test_format_with_offset() {
+        let plus_two = FixedOffset::east_opt(2 * 3600).unwrap();
+        assert_eq!(format_with_offset(&sample(), plus_two, "de"), "10.03.2024 00:15");
+        assert_eq!(format_with_offset(&sample(), plus_two, "en"), "03/10/2024 00:15");
+        let minus_five = FixedOffset::west_opt(5 * 3600).unwrap();
+        assert_eq!(format_with_offset(&sample(), minus_five, "es"), "09/03/2024 17:15");
+        assert_eq!(format_with_offset(&sample(), minus_five, "xx"), "2024-03-09 17:15");
+    }
+
+    #[test]
+    fn test_format_utc() {
+        assert_eq!(format_utc(&sample(), "en"), "03/09/2024 22:15 UTC");
+    }
+
+    #[test]
+    fn test_to_local_is_same_instant() {
+        let local = to_local(&sample());
+        assert_eq!(local.with_timezone(&Utc), sample());
+        assert_eq!(format_local(&sample(), "en"), local.format("%m/%d/%Y %H:%M").to_string());
+    }
+}
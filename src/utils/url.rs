@@ -0,0 +1,232 @@
+//! URL helpers
+//!
+//! Small, dependency-free URL parsing used for matching stored LINK fields
+//! against a site the user is visiting (autofill), cleaning imported links
+//! and keying cached site icons.
+
+/// Public suffixes that span more than one label. Not the full Public
+/// Suffix List: it covers the country-code second-level domains that are
+/// common in practice, which keeps `bbc.co.uk` from collapsing to `co.uk`,
+/// and the hosting platforms of the list's private section, where every
+/// subdomain belongs to someone else (`a.github.io` is not `b.github.io`).
+const MULTI_LABEL_SUFFIXES: &[&str] = &[
+    "co.uk", "org.uk", "ac.uk", "gov.uk", "me.uk", "ltd.uk", "plc.uk", "net.uk",
+    "com.au", "net.au", "org.au", "edu.au", "gov.au",
+    "co.nz", "org.nz", "net.nz",
+    "co.jp", "ne.jp", "or.jp", "ac.jp", "go.jp",
+    "co.kr", "or.kr",
+    "com.br", "net.br", "org.br", "gov.br",
+    "com.cn", "net.cn", "org.cn", "gov.cn",
+    "com.tr", "org.tr", "gov.tr",
+    "com.mx", "org.mx", "gob.mx",
+    "co.in", "net.in", "org.in", "gov.in",
+    "co.za", "org.za", "gov.za",
+    "com.ar", "com.sg", "com.hk", "com.tw", "com.my", "com.ua", "com.pl",
+    "co.il", "co.id", "co.th",
+    // Private section
+    "github.io", "gitlab.io", "bitbucket.io", "readthedocs.io", "ngrok.io",
+    "herokuapp.com", "blogspot.com", "appspot.com", "firebaseapp.com", "web.app",
+    "netlify.app", "vercel.app", "pages.dev", "workers.dev", "fly.dev",
+    "azurewebsites.net", "cloudapp.net", "cloudfront.net", "s3.amazonaws.com",
+    "elasticbeanstalk.com", "onrender.com", "myshopify.com", "wixsite.com",
+    "glitch.me", "surge.sh", "neocities.org", "duckdns.org",
+];
+
+/// Extract the lowercase host from a URL or bare domain: strips the scheme,
+/// user info, port, path, query, fragment, trailing dot and a leading
+/// `www.`. Returns `None` when nothing host-like remains.
+pub fn extract_host(url: &str) -> Option<String> {
+    let s = url.trim();
+    let s = match s.find("://") {
+        Some(pos) => &s[pos + 3..],
+        None => s,
+    };
+    let end = s.find(['/', '?', '#']).unwrap_or(s.len());
+    let authority = &s[..end];
+    let host_port = authority.rsplit('@').next().unwrap_or(authority);
+    let host = if host_port.starts_with('[') {
+        // IPv6 literal: keep as-is up to the closing bracket
+        &host_port[..host_port.find(']').map_or(host_port.len(), |p| p + 1)]
+    } else {
+        host_port.split(':').next().unwrap_or(host_port)
+    };
+    let host = host.trim_end_matches('.').to_lowercase();
+    let host = host.strip_prefix("www.").map(str::to_string).unwrap_or(host);
+    if host.is_empty() || host.chars().any(char::is_whitespace) {
+        None
+    } else {
+        Some(host)
+    }
+}
+
+/// The registrable domain of a host ("eTLD+1"): `login.example.com` ->
+/// `example.com`, `news.bbc.co.uk` -> `bbc.co.uk`. IP addresses and
+/// single-label hosts are returned unchanged.
+pub fn registrable_domain(host: &str) -> String {
+    let host = host.trim_end_matches('.').to_lowercase();
+    if host.parse::<std::net::IpAddr>().is_ok() || host.starts_with('[') {
+        return host;
+    }
+    let labels: Vec<&str> = host.split('.').collect();
+    if labels.len() <= 2 {
+        return host;
+    }
+    // The longest listed suffix wins, plus one label
+    let take = (2..labels.len())
+        .rev()
+        .find(|&n| MULTI_LABEL_SUFFIXES.contains(&labels[labels.len() - n..].join(".").as_str()))
+        .map_or(2, |n| n + 1);
+    labels[labels.len() - take..].join(".")
+}
+
+/// Whether `host` is a plausible host name, IPv4 or bracketed IPv6 address
+pub(crate) fn is_valid_host(host: &str) -> bool {
+    if host.starts_with('[') {
+        return host.ends_with(']') && host[1..host.len() - 1].parse::<std::net::Ipv6Addr>().is_ok();
+    }
+    !host.is_empty()
+        && host.len() <= 253
+        && host.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        })
+}
+
+/// Clean up a LINK value into an absolute web URL: trims whitespace and
+/// wrapping quotes or angle brackets, adds `https://` when the scheme is
+/// missing, lowercases scheme and host, and drops the default port and an
+/// empty fragment. Returns `None` for values that are not web links
+/// (`mailto:`, `javascript:`, text with spaces, invalid hosts).
+pub fn normalize_url(value: &str) -> Option<String> {
+    let s = value.trim().trim_matches(|c| matches!(c, '"' | '\'' | '<' | '>')).trim();
+    if s.is_empty() || s.chars().any(char::is_whitespace) {
+        return None;
+    }
+
+    let (scheme, rest) = match s.find("://") {
+        Some(pos) => (s[..pos].to_ascii_lowercase(), &s[pos + 3..]),
+        None => {
+            // `name:` not followed by a port is a scheme without authority
+            if let Some((head, tail)) = s.split_once(':')
+                && !head.contains(['/', '.'])
+                && !tail.starts_with(|c: char| c.is_ascii_digit()) {
+                    return None;
+                }
+            ("https".to_string(), s.strip_prefix("//").unwrap_or(s))
+        }
+    };
+    if !matches!(scheme.as_str(), "http" | "https" | "ftp") {
+        return None;
+    }
+
+    let end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let (authority, tail) = rest.split_at(end);
+    let (userinfo, host_port) = match authority.rsplit_once('@') {
+        Some((user, hp)) => (Some(user), hp),
+        None => (None, authority),
+    };
+    let (host, port) = match host_port.rfind(':') {
+        Some(pos) if !host_port[pos..].contains(']') => (&host_port[..pos], Some(&host_port[pos + 1..])),
+        _ => (host_port, None),
+    };
+    let host = host.trim_end_matches('.').to_lowercase();
+    if !is_valid_host(&host) {
+        return None;
+    }
+    let port = match port {
+        Some("") => None,
+        Some(p) => {
+            let n: u16 = p.parse().ok()?;
+            let default = match scheme.as_str() {
+                "http" => 80,
+                "https" => 443,
+                _ => 21,
+            };
+            (n != default).then_some(n)
+        }
+        None => None,
+    };
+
+    let mut url = format!("{scheme}://");
+    if let Some(user) = userinfo {
+        url.push_str(user);
+        url.push('@');
+    }
+    url.push_str(&host);
+    if let Some(port) = port {
+        url.push_str(&format!(":{port}"));
+    }
+    let tail = tail.strip_suffix('#').unwrap_or(tail);
+    if tail.is_empty() {
+        url.push('/');
+    } else {
+        if !tail.starts_with('/') {
+            url.push('/');
+        }
+        url.push_str(tail);
+    }
+    Some(url)
+}
+
+/// Cache key for a site's icon: the normalized host of the URL (lowercase,
+/// no `www.`, scheme, port or path), so every link to the same site shares
+/// one cached favicon. `None` when the value is not a web link.
+pub fn favicon_cache_key(url: &str) -> Option<String> {
+    extract_host(&normalize_url(url)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_host() {
+        assert_eq!(extract_host("https://www.Example.com/login?x=1").as_deref(), Some("example.com"));
+        assert_eq!(extract_host("http://user:pw@mail.example.com:8080/").as_deref(), Some("mail.example.com"));
+        assert_eq!(extract_host("example.com").as_deref(), Some("example.com"));
+        assert_eq!(extract_host("example.com.").as_deref(), Some("example.com"));
+        assert_eq!(extract_host("http://[::1]:80/").as_deref(), Some("[::1]"));
+        assert_eq!(extract_host("   "), None);
+        assert_eq!(extract_host("https:///path"), None);
+    }
+
+    #[test]
+    fn test_registrable_domain() {
+        assert_eq!(registrable_domain("login.example.com"), "example.com");
+        assert_eq!(registrable_domain("example.com"), "example.com");
+        assert_eq!(registrable_domain("news.bbc.co.uk"), "bbc.co.uk");
+        assert_eq!(registrable_domain("bbc.co.uk"), "bbc.co.uk");
+        assert_eq!(registrable_domain("a.b.c.shop.com.au"), "shop.com.au");
+        assert_eq!(registrable_domain("docs.me.github.io"), "me.github.io");
+        assert_eq!(registrable_domain("x.bucket.s3.amazonaws.com"), "bucket.s3.amazonaws.com");
+        assert_eq!(registrable_domain("github.io"), "github.io");
+        assert_eq!(registrable_domain("192.168.1.10"), "192.168.1.10");
+        assert_eq!(registrable_domain("localhost"), "localhost");
+    }
+
+    #[test]
+    fn test_normalize_url() {
+        assert_eq!(normalize_url(" Example.COM ").as_deref(), Some("https://example.com/"));
+        assert_eq!(normalize_url("HTTP://Example.com:80/Login?x=1#top").as_deref(), Some("http://example.com/Login?x=1#top"));
+        assert_eq!(normalize_url("<https://example.com:8443>").as_deref(), Some("https://example.com:8443/"));
+        assert_eq!(normalize_url("example.com:8080/app").as_deref(), Some("https://example.com:8080/app"));
+        assert_eq!(normalize_url("//cdn.example.com/x").as_deref(), Some("https://cdn.example.com/x"));
+        assert_eq!(normalize_url("https://[::1]:443/").as_deref(), Some("https://[::1]/"));
+        assert_eq!(normalize_url("mailto:user@example.com"), None);
+        assert_eq!(normalize_url("javascript:alert(1)"), None);
+        assert_eq!(normalize_url("not a link"), None);
+        assert_eq!(normalize_url("https://exa_mple..com"), None);
+        assert_eq!(normalize_url("https://example.com:99999"), None);
+    }
+
+    #[test]
+    fn test_favicon_cache_key() {
+        assert_eq!(favicon_cache_key("https://www.Example.com/login").as_deref(), Some("example.com"));
+        assert_eq!(favicon_cache_key("example.com:8080").as_deref(), Some("example.com"));
+        assert_eq!(favicon_cache_key("mail.example.com").as_deref(), Some("mail.example.com"));
+        assert_eq!(favicon_cache_key("mailto:a@b.c"), None);
+    }
+}
@@ -0,0 +1,183 @@
+//! Field value validation
+//!
+//! Labels carry a free-form `value_type` string ("mail", "date", "phon", ...).
+//! [`ValueType`] gives that string a closed set of meanings and checks a
+//! field value against it. Validation is advisory by default; the wallet only
+//! enforces it when strict mode is switched on.
+
+use chrono::NaiveDate;
+use crate::error::{WalletError, Result};
+use super::email::validate_email;
+
+/// Semantic type of a field value, derived from a label's `value_type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueType {
+    /// Free text (also the fallback for unknown codes)
+    Text,
+    /// Secret value (password, answer, CVV, ...)
+    Pass,
+    /// Email address
+    Mail,
+    /// URL / link
+    Link,
+    /// Phone number
+    Phone,
+    /// Date, compact `YYYYMMDD` or ISO `YYYY-MM-DD`
+    Date,
+    /// Time of day, `HH:MM` or `HH:MM:SS`
+    Time,
+    /// Numeric PIN (PINC fields; stored with value type "pass")
+    Pin,
+}
+
+impl ValueType {
+    /// Map a label `value_type` code to a `ValueType`. Unknown codes are
+    /// treated as free text so custom labels never become unusable.
+    pub fn from_code(code: &str) -> Self {
+        match code.trim().to_ascii_lowercase().as_str() {
+            "pass" => ValueType::Pass,
+            "mail" => ValueType::Mail,
+            "link" => ValueType::Link,
+            "phon" => ValueType::Phone,
+            "date" => ValueType::Date,
+            "time" => ValueType::Time,
+            "pin" => ValueType::Pin,
+            _ => ValueType::Text,
+        }
+    }
+
+    /// Resolve the value type for a field, taking the field type into
+    /// account: PINC is stored as "pass" but must hold digits only.
+    pub fn for_field(field_type: &str, value_type: &str) -> Self {
+        if field_type == "PINC" {
+            ValueType::Pin
+        } else {
+            Self::from_code(value_type)
+        }
+    }
+
+    /// Database code for this value type
+    pub fn as_code(&self) -> &'static str {
+        match self {
+            ValueType::Text => "text",
+            ValueType::Pass => "pass",
+            ValueType::Mail => "mail",
+            ValueType::Link => "link",
+            ValueType::Phone => "phon",
+            ValueType::Date => "date",
+            ValueType::Time => "time",
+            ValueType::Pin => "pin",
+        }
+    }
+
+    /// Check `value` against this type. Empty values are always accepted
+    /// (a field can be created blank and filled in later).
+    pub fn validate(&self, value: &str) -> Result<()> {
+        let v = value.trim();
+        if v.is_empty() {
+            return Ok(());
+        }
+        let ok = match self {
+            ValueType::Text | ValueType::Pass => true,
+            ValueType::Mail => validate_email(v).is_ok(),
+            ValueType::Link => is_valid_link(v),
+            ValueType::Phone => is_valid_phone(v),
+            ValueType::Date => is_valid_date(v),
+            ValueType::Time => is_valid_time(v),
+            ValueType::Pin => v.chars().all(|c| c.is_ascii_digit()),
+        };
+        if ok {
+            Ok(())
+        } else {
+            Err(WalletError::ValidationError(format!(
+                "value is not a valid {}",
+                self.as_code()
+            )))
+        }
+    }
+}
+
+/// A link is a whitespace-free string with a dotted host or an explicit scheme.
+fn is_valid_link(v: &str) -> bool {
+    !v.chars().any(char::is_whitespace) && (v.contains("://") || v.contains('.'))
+}
+
+/// Digits plus the usual separators; at least three digits.
+fn is_valid_phone(v: &str) -> bool {
+    let allowed = |c: char| c.is_ascii_digit() || " +-().".contains(c);
+    v.chars().all(allowed) && v.chars().filter(|c| c.is_ascii_digit()).count() >= 3
+}
+
+fn is_valid_date(v: &str) -> bool {
+    NaiveDate::parse_from_str(v, "%Y%m%d").is_ok()
+        || NaiveDate::parse_from_str(v, "%Y-%m-%d").is_ok()
+}
+
+fn is_valid_time(v: &str) -> bool {
+    chrono::NaiveTime::parse_from_str(v, "%H:%M").is_ok()
+        || chrono::NaiveTime::parse_from_str(v, "%H:%M:%S").is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_code() {
+        assert_eq!(ValueType::from_code("mail"), ValueType::Mail);
+        assert_eq!(ValueType::from_code("phon"), ValueType::Phone);
+        assert_eq!(ValueType::from_code("DATE"), ValueType::Date);
+        assert_eq!(ValueType::from_code("whatever"), ValueType::Text);
+        assert_eq!(ValueType::for_field("PINC", "pass"), ValueType::Pin);
+        assert_eq!(ValueType::for_field("PASS", "pass"), ValueType::Pass);
+    }
+
+    #[test]
+    fn test_code_round_trip() {
+        for vt in [
+            ValueType::Text, ValueType::Pass, ValueType::Mail, ValueType::Link,
+            ValueType::Phone, ValueType::Date, ValueType::Time, ValueType::Pin,
+        ] {
+            assert_eq!(ValueType::from_code(vt.as_code()), vt);
+        }
+    }
+
+    #[test]
+    fn test_validate_email() {
+        assert!(ValueType::Mail.validate("user@example.com").is_ok());
+        assert!(ValueType::Mail.validate("user@example").is_err());
+        assert!(ValueType::Mail.validate("@example.com").is_err());
+        assert!(ValueType::Mail.validate("a b@example.com").is_err());
+        assert!(ValueType::Mail.validate("a@b@example.com").is_err());
+    }
+
+    #[test]
+    fn test_validate_date_and_time() {
+        assert!(ValueType::Date.validate("20250625").is_ok());
+        assert!(ValueType::Date.validate("2025-06-25").is_ok());
+        assert!(ValueType::Date.validate("20251325").is_err());
+        assert!(ValueType::Date.validate("tomorrow").is_err());
+        assert!(ValueType::Time.validate("09:30").is_ok());
+        assert!(ValueType::Time.validate("23:59:59").is_ok());
+        assert!(ValueType::Time.validate("25:00").is_err());
+    }
+
+    #[test]
+    fn test_validate_pin_phone_link() {
+        assert!(ValueType::Pin.validate("0042").is_ok());
+        assert!(ValueType::Pin.validate("12a4").is_err());
+        assert!(ValueType::Phone.validate("+1 (555) 123-4567").is_ok());
+        assert!(ValueType::Phone.validate("call me").is_err());
+        assert!(ValueType::Link.validate("https://example.com").is_ok());
+        assert!(ValueType::Link.validate("example.com").is_ok());
+        assert!(ValueType::Link.validate("not a link").is_err());
+    }
+
+    #[test]
+    fn test_validate_empty_and_free_text() {
+        assert!(ValueType::Mail.validate("").is_ok());
+        assert!(ValueType::Pin.validate("   ").is_ok());
+        assert!(ValueType::Text.validate("anything at all").is_ok());
+        assert!(ValueType::Pass.validate("p@ss w0rd").is_ok());
+    }
+}
@@ -723,9 +723,11 @@ fn test_password_generator_basic() {
         special: false,
         avoid_ambiguous: false,
         length: 16,
+        custom_symbols: None,
+        exclude_chars: None,
     };
 
-    let password = generate_password(&options);
+    let password = generate_password(&options).unwrap();
     assert_eq!(password.len(), 16);
     assert!(password.chars().all(|c| c.is_ascii_alphanumeric()));
 }
@@ -741,9 +743,11 @@ fn test_password_generator_all_types() {
         special: true,
         avoid_ambiguous: false,
         length: 32,
+        custom_symbols: None,
+        exclude_chars: None,
     };
 
-    let password = generate_password(&options);
+    let password = generate_password(&options).unwrap();
     assert_eq!(password.len(), 32);
 }
 
@@ -758,9 +762,11 @@ fn test_password_generator_lowercase_only() {
         special: false,
         avoid_ambiguous: false,
         length: 20,
+        custom_symbols: None,
+        exclude_chars: None,
     };
 
-    let password = generate_password(&options);
+    let password = generate_password(&options).unwrap();
     assert_eq!(password.len(), 20);
     assert!(password.chars().all(|c| c.is_ascii_lowercase()));
 }
@@ -776,9 +782,11 @@ fn test_password_generator_digits_only() {
         special: false,
         avoid_ambiguous: false,
         length: 10,
+        custom_symbols: None,
+        exclude_chars: None,
     };
 
-    let password = generate_password(&options);
+    let password = generate_password(&options).unwrap();
     assert_eq!(password.len(), 10);
     assert!(password.chars().all(|c| c.is_ascii_digit()));
 }
@@ -790,9 +798,9 @@ fn test_password_generator_uniqueness() {
     let options = PasswordOptions::default();
 
     // Generate multiple passwords and ensure they're different
-    let p1 = generate_password(&options);
-    let p2 = generate_password(&options);
-    let p3 = generate_password(&options);
+    let p1 = generate_password(&options).unwrap();
+    let p2 = generate_password(&options).unwrap();
+    let p3 = generate_password(&options).unwrap();
 
     // With 16 chars from 62+ char pool, collision probability is negligible
     assert_ne!(p1, p2);